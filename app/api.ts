@@ -1,9 +1,11 @@
+export const basePath = process.env.NEXT_PUBLIC_BASE_PATH || ''
+
 export async function apiFetch<T = unknown>(
   url: string,
   options: RequestInit = {}
 ): Promise<{ data?: T; error?: string }> {
   try {
-    const res = await fetch(url, {
+    const res = await fetch(`${basePath}${url}`, {
       ...options,
       headers: {
         ...(options.body !== undefined ? { 'Content-Type': 'application/json' } : {}),
@@ -1,7 +1,7 @@
 'use client'
 
 import React, { useState, useEffect, useCallback, ReactNode } from 'react'
-import { api } from './api'
+import { api, basePath } from './api'
 
 // --- Types ---
 
@@ -202,6 +202,7 @@ function formatTime(iso: string | null): string {
 function statusDot(status: string | null, error: string | null) {
   if (!status) return <span className="sync-dot pending" title="Not synced yet" />
   if (status === 'ok') return <span className="sync-dot ok" title="Last sync successful" />
+  if (status === 'unchanged') return <span className="sync-dot ok" title="Last sync successful, no changes" />
   return <span className="sync-dot error" title={error ?? 'Sync failed'} />
 }
 
@@ -751,14 +752,14 @@ export default function Home() {
   }
 
   function renderSourceExtra(src: Source) {
-    const origin = typeof window !== 'undefined' ? window.location.origin : ''
+    const origin = (typeof window !== 'undefined' ? window.location.origin : '') + basePath
     const standardUrl = `${origin}/ics/${src.ics_path}`
     return (
       <>
         <div className="detail-row">
           <strong>ICS URL</strong>
           <span className="ics-url-row">
-            <a href={`/ics/${src.ics_path}`} target="_blank" rel="noreferrer">
+            <a href={`${basePath}/ics/${src.ics_path}`} target="_blank" rel="noreferrer">
               {standardUrl}
             </a>
             <CopyButton text={standardUrl} />
@@ -774,7 +775,7 @@ export default function Home() {
                   return (
                     <>
                       <a
-                        href={`/ics/public/${src.public_ics_path}`}
+                        href={`${basePath}/ics/public/${src.public_ics_path}`}
                         target="_blank"
                         rel="noreferrer"
                       >
@@ -1,7 +1,10 @@
 /** @type {import('next').NextConfig} */
 
 const nextConfig = {
-  output: 'standalone',
+  // `export` writes a static site to out/ for the embed-frontend build.
+  output: process.env.NEXT_OUTPUT === 'export' ? 'export' : 'standalone',
   trailingSlash: true,
+  // Must match the backend's BASE_PATH when serving under a subpath.
+  basePath: process.env.NEXT_PUBLIC_BASE_PATH || '',
 }
 export default nextConfig
@@ -0,0 +1,202 @@
+use crate::api::error::ApiError;
+use crate::api::{AppState, require_admin};
+use crate::auto_sync;
+use crate::config_transfer::{self, ConfigExport, ImportSummary};
+use crate::db;
+use crate::maintenance::{self, MaintenanceReport};
+use crate::server::auth::CurrentUser;
+use axum::{
+    Extension, Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub const PASSPHRASE_HEADER: &str = "x-config-passphrase";
+
+#[derive(Deserialize, ToSchema)]
+pub struct ExportParams {
+    format: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImportResponse {
+    status: String,
+    message: String,
+    summary: ImportSummary,
+}
+
+fn passphrase(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(PASSPHRASE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+}
+
+fn is_yaml(value: Option<&str>) -> bool {
+    value.is_some_and(|v| v.contains("yaml") || v.contains("yml"))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/export",
+    params(
+        ("format" = Option<String>, Query, description = "`json` (default) or `yaml`"),
+        ("X-Config-Passphrase" = Option<String>, Header, description = "Include credentials encrypted with this passphrase"),
+    ),
+    responses((status = 200, body = ConfigExport))
+)]
+pub async fn export_config(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+    headers: HeaderMap,
+    Query(params): Query<ExportParams>,
+) -> Response {
+    if let Some(denied) = require_admin(&user) {
+        return denied;
+    }
+    let doc = {
+        let db = state.db.lock().unwrap();
+        config_transfer::export(&db, passphrase(&headers))
+    };
+    let doc = match doc {
+        Ok(doc) => doc,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    if !is_yaml(params.format.as_deref()) {
+        return (StatusCode::OK, Json(doc)).into_response();
+    }
+    match config_transfer::to_yaml(&doc) {
+        Ok(yaml) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "application/yaml; charset=utf-8")],
+            yaml,
+        )
+            .into_response(),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/import",
+    params(("X-Config-Passphrase" = Option<String>, Header, description = "Passphrase the credentials were exported with")),
+    request_body(content = ConfigExport, description = "JSON, or YAML with a YAML content type"),
+    responses((status = 200, body = ImportResponse))
+)]
+pub async fn import_config(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if let Some(denied) = require_admin(&user) {
+        return denied;
+    }
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+    let doc = match is_yaml(content_type) {
+        true => config_transfer::from_yaml(&body),
+        false => serde_json::from_str::<ConfigExport>(&body).map_err(Into::into),
+    };
+    let doc = match doc {
+        Ok(doc) => doc,
+        Err(e) => return ApiError::bad_request(format!("{:#}", e)).into_response(),
+    };
+    let result = {
+        let db = state.db.lock().unwrap();
+        config_transfer::import(&db, &doc, passphrase(&headers)).and_then(|summary| {
+            let sources = db::list_sources(&db)?;
+            let destinations = db::list_destinations(&db)?;
+            Ok((summary, sources, destinations))
+        })
+    };
+    match result {
+        Ok((summary, sources, destinations)) => {
+            // Reschedule only what the document touched.
+            for s in sources
+                .iter()
+                .filter(|s| doc.sources.iter().any(|d| d.ics_path == s.ics_path))
+            {
+                auto_sync::register_source(&state.sync_tasks, &state, s);
+            }
+            for d in destinations
+                .iter()
+                .filter(|d| doc.destinations.iter().any(|e| e.name == d.name))
+            {
+                auto_sync::register_destination(&state.sync_tasks, &state, d);
+            }
+            (
+                StatusCode::OK,
+                Json(ImportResponse {
+                    status: "success".into(),
+                    message: format!(
+                        "Imported {} sources and {} destinations",
+                        summary.sources, summary.destinations
+                    ),
+                    summary,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => ApiError::bad_request(format!("{:#}", e)).into_response(),
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MaintenanceResponse {
+    // Unset until maintenance has run since the server started.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_report: Option<MaintenanceReport>,
+    // 0 when scheduled maintenance is off.
+    interval_secs: i64,
+    retention_secs: i64,
+}
+
+#[utoipa::path(get, path = "/api/admin/maintenance", responses((status = 200, body = MaintenanceResponse)))]
+pub async fn maintenance_status(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+) -> Response {
+    if let Some(denied) = require_admin(&user) {
+        return denied;
+    }
+    (
+        StatusCode::OK,
+        Json(MaintenanceResponse {
+            last_report: maintenance::last_report(),
+            interval_secs: state.maintenance.interval_secs,
+            retention_secs: state.maintenance.retention_secs,
+        }),
+    )
+        .into_response()
+}
+
+#[utoipa::path(post, path = "/api/admin/maintenance", responses((status = 200, body = MaintenanceReport)))]
+pub async fn run_maintenance(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+) -> Response {
+    if let Some(denied) = require_admin(&user) {
+        return denied;
+    }
+    match maintenance::run_blocking(state.db.clone(), state.maintenance).await {
+        Ok(report) => (StatusCode::OK, Json(report)).into_response(),
+        Err(e) => {
+            tracing::error!("Maintenance failed: {}", e);
+            ApiError::from(e).into_response()
+        }
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/export", get(export_config))
+        .route("/admin/import", post(import_config))
+        .route("/admin/maintenance", get(maintenance_status))
+        .route("/admin/maintenance", post(run_maintenance))
+}
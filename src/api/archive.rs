@@ -0,0 +1,132 @@
+use crate::api::error::ApiError;
+use crate::api::{AppState, sync};
+use crate::db::{self, ArchivedEvent};
+use crate::{event_index, ics_component};
+use axum::{
+    Router,
+    body::Body,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use chrono::{Months, NaiveDateTime};
+use sha2::{Digest, Sha256};
+
+pub struct SplitIcs {
+    pub content: String,
+    pub archived: Vec<ArchivedEvent>,
+}
+
+// Overrides of a recurring event share its UID, so RECURRENCE-ID is part of the key.
+pub(crate) fn event_key(vevent: &str) -> String {
+    let recurrence = ics_component::property(vevent, "RECURRENCE-ID").unwrap_or_default();
+    match sync::event_uid(vevent) {
+        Some(uid) => format!("{}|{}", uid, recurrence),
+        None => format!("{:x}", Sha256::digest(vevent.as_bytes())),
+    }
+}
+
+fn ended_before(vevent: &str, cutoff: NaiveDateTime) -> Option<NaiveDateTime> {
+    // Recurring series keep producing occurrences; only single events and overrides expire.
+    if ics_component::has_property(vevent, "RRULE") {
+        return None;
+    }
+    let event = event_index::index(vevent).into_iter().next()?;
+    event
+        .ends_at
+        .or(event.starts_at)
+        .filter(|end| *end < cutoff)
+}
+
+// Moves events that ended more than `months` months before `now` out of the feed.
+pub fn split_past(ics: &str, months: Option<i64>, now: NaiveDateTime) -> SplitIcs {
+    let cutoff = months
+        .filter(|m| *m > 0)
+        .and_then(|m| now.checked_sub_months(Months::new(m as u32)));
+    let Some(cutoff) = cutoff else {
+        return SplitIcs {
+            content: ics.to_owned(),
+            archived: Vec::new(),
+        };
+    };
+    let mut kept = Vec::new();
+    let mut archived = Vec::new();
+    for vevent in sync::split_vevents(ics) {
+        match ended_before(&vevent, cutoff) {
+            Some(end) => archived.push(ArchivedEvent {
+                key: event_key(&vevent),
+                ends_at: end.format(event_index::TIMESTAMP_FORMAT).to_string(),
+                vevent,
+            }),
+            None => kept.push(vevent),
+        }
+    }
+    if archived.is_empty() {
+        return SplitIcs {
+            content: ics.to_owned(),
+            archived,
+        };
+    }
+    SplitIcs {
+        content: sync::build_calendar(&kept),
+        archived,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/sources/{id}/archive",
+    params(("id" = i64, Path, description = "Source ID")),
+    responses((status = 200, description = "Archived events as ICS", content_type = "text/calendar"))
+)]
+pub async fn export_archive(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    let db = state.db.lock().unwrap();
+    match db::get_source(&db, id) {
+        Ok(Some(_)) => {}
+        Ok(None) => return ApiError::not_found("Source not found").into_response(),
+        Err(e) => return ApiError::from(e).into_response(),
+    }
+    match db::list_archived_events(&db, id) {
+        Ok(events) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
+            .header(
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"source-{}-archive.ics\"", id),
+            )
+            .body(Body::from(sync::build_calendar(&events)))
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/sources/{id}/archive", get(export_archive))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archives_only_events_past_the_cutoff() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\nUID:old\r\nDTSTART:20250101T090000Z\r\nDTEND:20250101T100000Z\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:weekly\r\nDTSTART:20250101T090000Z\r\nRRULE:FREQ=WEEKLY\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:recent\r\nDTSTART:20260201T090000Z\r\nEND:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let now = event_index::parse_datetime("20260310T000000Z", None).unwrap();
+
+        let split = split_past(ics, Some(6), now);
+        assert_eq!(split.archived.len(), 1);
+        assert_eq!(split.archived[0].key, "old|");
+        assert_eq!(split.archived[0].ends_at, "2025-01-01 10:00:00");
+        assert!(!split.content.contains("UID:old"));
+        assert!(split.content.contains("UID:weekly") && split.content.contains("UID:recent"));
+
+        let split = split_past(ics, None, now);
+        assert!(split.archived.is_empty());
+        assert_eq!(split.content, ics);
+    }
+}
@@ -0,0 +1,200 @@
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+use crate::db::Attachment;
+
+pub struct ProcessedIcs {
+    pub content: String,
+    pub attachments: Vec<Attachment>,
+}
+
+fn split_property(line: &str) -> Option<(&str, &str)> {
+    let mut in_quotes = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ':' if !in_quotes => return Some((&line[..i], &line[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn is_attach(name_and_params: &str) -> bool {
+    name_and_params
+        .split(';')
+        .next()
+        .is_some_and(|name| name.eq_ignore_ascii_case("ATTACH"))
+}
+
+fn is_binary_param(param: &str) -> bool {
+    param.eq_ignore_ascii_case("ENCODING=BASE64") || param.eq_ignore_ascii_case("VALUE=BINARY")
+}
+
+fn rehost(params: &str, value: &str) -> Option<(String, Attachment)> {
+    let cleaned: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(cleaned)
+        .ok()?;
+    let hash = format!("{:x}", Sha256::digest(&data));
+    let kept: Vec<&str> = params
+        .split(';')
+        .skip(1)
+        .filter(|p| !is_binary_param(p))
+        .collect();
+    let fmttype = kept.iter().find_map(|p| {
+        p.split_once('=')
+            .filter(|(k, _)| k.eq_ignore_ascii_case("FMTTYPE"))
+            .map(|(_, v)| v.to_owned())
+    });
+    let mut line = String::from("ATTACH");
+    for p in kept {
+        line.push(';');
+        line.push_str(p);
+    }
+    line.push_str(&format!(":/attachments/{}", hash));
+    Some((
+        line,
+        Attachment {
+            hash,
+            fmttype,
+            data,
+        },
+    ))
+}
+
+pub fn process(ics: &str, mode: &str) -> ProcessedIcs {
+    if mode == "keep" {
+        return ProcessedIcs {
+            content: ics.to_owned(),
+            attachments: Vec::new(),
+        };
+    }
+
+    let mut content = String::with_capacity(ics.len());
+    let mut attachments = Vec::new();
+    let mut physical = ics.split_inclusive('\n').peekable();
+
+    while let Some(first) = physical.next() {
+        let mut raw = first.to_owned();
+        let mut logical = first.trim_end_matches(['\r', '\n']).to_owned();
+        while let Some(next) = physical.peek() {
+            if !(next.starts_with(' ') || next.starts_with('\t')) {
+                break;
+            }
+            raw.push_str(next);
+            logical.push_str(next[1..].trim_end_matches(['\r', '\n']));
+            physical.next();
+        }
+
+        let Some((params, value)) = split_property(&logical).filter(|(p, _)| is_attach(p)) else {
+            content.push_str(&raw);
+            continue;
+        };
+        let binary = params.split(';').skip(1).any(is_binary_param);
+
+        match mode {
+            "strip" => {}
+            "uri_only" if binary => {}
+            "rehost" if binary => match rehost(params, value) {
+                Some((line, attachment)) => {
+                    content.push_str(&line);
+                    content.push_str("\r\n");
+                    attachments.push(attachment);
+                }
+                None => tracing::warn!("Dropping ATTACH with undecodable base64 value"),
+            },
+            _ => content.push_str(&raw),
+        }
+    }
+
+    ProcessedIcs {
+        content,
+        attachments,
+    }
+}
+
+// Rehosted attachments are stored with a relative URI; resolve it against the
+// origin the feed is served from.
+pub fn absolutize(ics: &str, origin: &str) -> String {
+    if !ics.contains(":/attachments/") {
+        return ics.to_owned();
+    }
+    ics.split_inclusive('\n')
+        .map(|line| {
+            if line
+                .get(..6)
+                .is_some_and(|name| name.eq_ignore_ascii_case("ATTACH"))
+            {
+                line.replacen(":/attachments/", &format!(":{}/attachments/", origin), 1)
+            } else {
+                line.to_owned()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEED: &str = "BEGIN:VEVENT\r\n\
+        UID:1\r\n\
+        ATTACH:https://example.com/agenda.pdf\r\n\
+        ATTACH;FMTTYPE=text/plain;ENCODING=BASE64;VALUE=BINARY:aGVsbG8g\r\n \
+        d29ybGQ=\r\n\
+        SUMMARY:Test\r\n\
+        END:VEVENT\r\n";
+
+    #[test]
+    fn keep_leaves_feed_untouched() {
+        let out = process(FEED, "keep");
+        assert_eq!(out.content, FEED);
+        assert!(out.attachments.is_empty());
+    }
+
+    #[test]
+    fn strip_removes_all_attachments() {
+        let out = process(FEED, "strip");
+        assert!(!out.content.contains("ATTACH"));
+        assert!(!out.content.contains("d29ybGQ="));
+        assert!(out.content.contains("SUMMARY:Test"));
+    }
+
+    #[test]
+    fn uri_only_keeps_uri_attachments() {
+        let out = process(FEED, "uri_only");
+        assert!(
+            out.content
+                .contains("ATTACH:https://example.com/agenda.pdf")
+        );
+        assert!(!out.content.contains("BASE64"));
+        assert!(!out.content.contains("d29ybGQ="));
+    }
+
+    #[test]
+    fn rehost_rewrites_binary_attachment() {
+        let out = process(FEED, "rehost");
+        assert_eq!(out.attachments.len(), 1);
+        let a = &out.attachments[0];
+        assert_eq!(a.data, b"hello world");
+        assert_eq!(a.fmttype.as_deref(), Some("text/plain"));
+        assert!(out.content.contains(&format!(
+            "ATTACH;FMTTYPE=text/plain:/attachments/{}\r\n",
+            a.hash
+        )));
+        assert!(
+            out.content
+                .contains("ATTACH:https://example.com/agenda.pdf")
+        );
+    }
+
+    #[test]
+    fn absolutize_prefixes_rehosted_attachments() {
+        let ics = "ATTACH;FMTTYPE=text/plain:/attachments/abc\r\nSUMMARY:x\r\n";
+        assert_eq!(
+            absolutize(ics, "https://cal.example.com"),
+            "ATTACH;FMTTYPE=text/plain:https://cal.example.com/attachments/abc\r\nSUMMARY:x\r\n"
+        );
+    }
+}
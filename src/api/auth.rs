@@ -0,0 +1,146 @@
+use crate::api::AppState;
+use crate::api::error::{ApiError, ApiJson};
+use crate::db;
+use crate::server::auth::{AuthChain, CSRF_COOKIE, CurrentUser};
+use crate::server::authenticators::SESSION_COOKIE;
+use axum::{
+    Extension, Json, Router,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite, SignedCookieJar};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Deserialize, ToSchema)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LoginResponse {
+    status: String,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<CurrentUser>,
+    // Send back as `X-CSRF-Token` on POST/PUT/DELETE requests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    csrf_token: Option<String>,
+}
+
+fn logged_out() -> Response {
+    (
+        StatusCode::OK,
+        Json(LoginResponse {
+            status: "success".into(),
+            message: "Logged out".into(),
+            user: None,
+            csrf_token: None,
+        }),
+    )
+        .into_response()
+}
+
+fn is_https(headers: &HeaderMap) -> bool {
+    headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("https"))
+}
+
+// Scoped to BASE_PATH so other apps on the host never receive the session.
+fn cookie_path(state: &AppState) -> String {
+    match state.base_path.as_str() {
+        "" => "/".into(),
+        path => path.into(),
+    }
+}
+
+#[utoipa::path(post, path = "/api/auth/login", request_body = LoginRequest, responses((status = 200, body = LoginResponse)))]
+pub async fn login(
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthChain>>,
+    headers: HeaderMap,
+    ApiJson(body): ApiJson<LoginRequest>,
+) -> Response {
+    let Some(Extension(auth)) = auth else {
+        return ApiError::bad_request("Authentication is disabled").into_response();
+    };
+    let Some(sessions) = auth.sessions() else {
+        return ApiError::bad_request("Authentication is disabled").into_response();
+    };
+    let Some(user) = auth
+        .verify_password(&state, &body.username, &body.password)
+        .await
+    else {
+        return ApiError::unauthorized("Invalid username or password").into_response();
+    };
+
+    let session_id = {
+        let db = state.db.lock().unwrap();
+        match db::create_session(&db, user.id, &user.username, sessions.ttl_secs) {
+            Ok(id) => id,
+            Err(e) => return ApiError::from(e).into_response(),
+        }
+    };
+    let max_age = time::Duration::seconds(sessions.ttl_secs);
+    let cookie = Cookie::build((SESSION_COOKIE, session_id))
+        .path(cookie_path(&state))
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .secure(is_https(&headers))
+        .max_age(max_age);
+    // Readable by the frontend so it can echo it in X-CSRF-Token.
+    let csrf_token = uuid::Uuid::new_v4().simple().to_string();
+    let csrf_cookie = Cookie::build((CSRF_COOKIE, csrf_token.clone()))
+        .path(cookie_path(&state))
+        .same_site(SameSite::Strict)
+        .secure(is_https(&headers))
+        .max_age(max_age);
+    let jar = SignedCookieJar::new(sessions.key.clone()).add(cookie);
+    (
+        jar,
+        CookieJar::new().add(csrf_cookie),
+        Json(LoginResponse {
+            status: "success".into(),
+            message: "Logged in".into(),
+            user: Some(user),
+            csrf_token: Some(csrf_token),
+        }),
+    )
+        .into_response()
+}
+
+#[utoipa::path(post, path = "/api/auth/logout", responses((status = 200, body = LoginResponse)))]
+pub async fn logout(
+    State(state): State<AppState>,
+    auth: Option<Extension<AuthChain>>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(sessions) = auth.as_ref().and_then(|Extension(a)| a.sessions()) else {
+        return logged_out();
+    };
+    let jar = SignedCookieJar::from_headers(&headers, sessions.key.clone());
+    if let Some(cookie) = jar.get(SESSION_COOKIE) {
+        let db = state.db.lock().unwrap();
+        if let Err(e) = db::delete_session(&db, cookie.value()) {
+            return ApiError::from(e).into_response();
+        }
+    }
+    (
+        jar.remove(Cookie::build(SESSION_COOKIE).path(cookie_path(&state))),
+        CookieJar::from_headers(&headers)
+            .remove(Cookie::build(CSRF_COOKIE).path(cookie_path(&state))),
+        logged_out(),
+    )
+        .into_response()
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/auth/login", post(login))
+        .route("/auth/logout", post(logout))
+}
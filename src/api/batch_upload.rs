@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, ensure};
+use reqwest::{Client, header};
+
+use crate::throttle::Throttle;
+
+// Servers with a batch upload extension, such as a SabreDAV plugin, advertise
+// it in the DAV header of OPTIONS and take a POST to the collection with one
+// multipart/related body holding many calendar objects, each named by its
+// Content-Location. The 207 reply has a status and ETag per object.
+pub const CAPABILITY: &str = "bulk-upload";
+pub const BATCH_SIZE: usize = 100;
+const BOUNDARY: &str = "caldav-ics-sync-batch";
+
+pub async fn supported(client: &Client, collection: &str) -> bool {
+    let Ok(res) = client
+        .request(reqwest::Method::OPTIONS, collection)
+        .send()
+        .await
+    else {
+        return false;
+    };
+    res.headers()
+        .get_all("DAV")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|token| token.trim().eq_ignore_ascii_case(CAPABILITY))
+}
+
+// `items` are (percent-encoded resource name, calendar object) pairs.
+fn multipart_body(items: &[(String, &str)]) -> String {
+    let mut body = String::new();
+    for (name, data) in items {
+        body.push_str(&format!(
+            "--{}\r\nContent-Type: text/calendar; charset=utf-8\r\nContent-Location: {}\r\n\r\n{}\r\n",
+            BOUNDARY, name, data
+        ));
+    }
+    body.push_str(&format!("--{}--\r\n", BOUNDARY));
+    body
+}
+
+// Names of the stored objects with their new ETag. Rejected objects are left
+// out, for the caller to PUT one by one.
+fn parse_multistatus(text: &str) -> Result<HashMap<String, Option<String>>> {
+    let doc = roxmltree::Document::parse(text)?;
+    let mut stored = HashMap::new();
+    for response in doc
+        .descendants()
+        .filter(|n| n.has_tag_name(("DAV:", "response")))
+    {
+        let text_of = |name: &str| {
+            response
+                .descendants()
+                .find(|n| n.has_tag_name(("DAV:", name)))
+                .and_then(|n| n.text())
+                .map(str::trim)
+        };
+        let Some(name) = text_of("href").and_then(|href| href.rsplit('/').next()) else {
+            continue;
+        };
+        let ok = text_of("status")
+            .and_then(|s| s.split_whitespace().nth(1))
+            .is_some_and(|code| code.starts_with('2'));
+        if ok {
+            stored.insert(name.to_string(), text_of("getetag").map(str::to_owned));
+        }
+    }
+    Ok(stored)
+}
+
+pub async fn upload(
+    client: &Client,
+    throttle: &mut Throttle,
+    collection: &str,
+    items: &[(String, &str)],
+) -> Result<HashMap<String, Option<String>>> {
+    let body = multipart_body(items);
+    let res = throttle
+        .send(|| {
+            client
+                .post(collection)
+                .header(
+                    header::CONTENT_TYPE,
+                    format!(
+                        "multipart/related; boundary={}; type=\"text/calendar\"",
+                        BOUNDARY
+                    ),
+                )
+                .body(body.clone())
+        })
+        .await?;
+    ensure!(
+        res.status() == reqwest::StatusCode::MULTI_STATUS,
+        "Batch upload to {} returned {}",
+        collection,
+        res.status()
+    );
+    parse_multistatus(&crate::ics_text::read_text(res).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_batches_and_reads_per_object_status() {
+        let body = multipart_body(&[
+            ("a.ics".into(), "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n"),
+            ("b.ics".into(), "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n"),
+        ]);
+        assert_eq!(body.matches("Content-Location: ").count(), 2);
+        assert!(body.ends_with("--caldav-ics-sync-batch--\r\n"));
+
+        let stored = parse_multistatus(
+            r#"<d:multistatus xmlns:d="DAV:">
+  <d:response>
+    <d:href>/cal/a.ics</d:href>
+    <d:propstat><d:prop><d:getetag>"e1"</d:getetag></d:prop><d:status>HTTP/1.1 201 Created</d:status></d:propstat>
+  </d:response>
+  <d:response><d:href>/cal/b.ics</d:href><d:status>HTTP/1.1 403 Forbidden</d:status></d:response>
+</d:multistatus>"#,
+        )
+        .unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored["a.ics"].as_deref(), Some("\"e1\""));
+    }
+}
@@ -0,0 +1,335 @@
+use crate::api::error::{ApiError, ApiJson};
+use crate::api::{AppState, owner_scope};
+use crate::auto_sync::{self, AutoSyncKey};
+use crate::db;
+use crate::server::auth::CurrentUser;
+use crate::validation;
+use anyhow::{Result, bail};
+use axum::{
+    Extension, Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum BulkOperation<C, U> {
+    Create { item: C },
+    Update { id: i64, item: U },
+    Delete { id: i64 },
+    Enable { id: i64 },
+    Disable { id: i64 },
+}
+
+impl<C, U> BulkOperation<C, U> {
+    fn action(&self) -> &'static str {
+        match self {
+            Self::Create { .. } => "create",
+            Self::Update { .. } => "update",
+            Self::Delete { .. } => "delete",
+            Self::Enable { .. } => "enable",
+            Self::Disable { .. } => "disable",
+        }
+    }
+
+    fn id(&self) -> Option<i64> {
+        match self {
+            Self::Create { .. } => None,
+            Self::Update { id, .. }
+            | Self::Delete { id }
+            | Self::Enable { id }
+            | Self::Disable { id } => Some(*id),
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BulkRequest<C, U> {
+    operations: Vec<BulkOperation<C, U>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkItemResult {
+    index: usize,
+    action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<i64>,
+    // "ok", "error", or "rolled_back" for items undone by a later failure.
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkResponse {
+    status: String,
+    message: String,
+    results: Vec<BulkItemResult>,
+}
+
+// The DB calls behind each action, so sources and destinations share one runner.
+trait BulkTarget {
+    type Create;
+    type Update;
+    const NAME: &'static str;
+
+    fn create(conn: &Connection, item: &mut Self::Create, owner: Option<i64>) -> Result<i64>;
+    fn update(
+        conn: &Connection,
+        id: i64,
+        item: &mut Self::Update,
+        owner: Option<i64>,
+    ) -> Result<bool>;
+    fn delete(conn: &Connection, id: i64) -> Result<bool>;
+    fn set_enabled(conn: &Connection, id: i64, enabled: bool) -> Result<bool>;
+    fn owned_by(conn: &Connection, id: i64, owner: i64) -> Result<bool>;
+    // Re-reads the row after commit so auto-sync follows the new state.
+    fn refresh(state: &AppState, id: i64);
+    fn removed(state: &AppState, id: i64);
+}
+
+struct Sources;
+struct Destinations;
+
+impl BulkTarget for Sources {
+    type Create = db::CreateSource;
+    type Update = db::UpdateSource;
+    const NAME: &'static str = "Source";
+
+    fn create(conn: &Connection, item: &mut db::CreateSource, owner: Option<i64>) -> Result<i64> {
+        if owner.is_some() {
+            item.owner_id = owner;
+        }
+        validation::check_create_source(conn, item)?;
+        db::create_source(conn, item)
+    }
+
+    fn update(
+        conn: &Connection,
+        id: i64,
+        item: &mut db::UpdateSource,
+        owner: Option<i64>,
+    ) -> Result<bool> {
+        if owner.is_some() {
+            item.owner_id = None;
+        }
+        if let Some(existing) = db::get_source(conn, id)? {
+            validation::check_update_source(conn, &existing, item)?;
+        }
+        db::update_source(conn, id, item)
+    }
+
+    fn delete(conn: &Connection, id: i64) -> Result<bool> {
+        db::delete_source(conn, id)
+    }
+
+    fn set_enabled(conn: &Connection, id: i64, enabled: bool) -> Result<bool> {
+        db::set_source_enabled(conn, id, enabled)
+    }
+
+    fn owned_by(conn: &Connection, id: i64, owner: i64) -> Result<bool> {
+        db::source_owned_by(conn, id, owner)
+    }
+
+    fn refresh(state: &AppState, id: i64) {
+        let source = db::get_source(&state.db.lock().unwrap(), id).ok().flatten();
+        if let Some(s) = source {
+            auto_sync::register_source(&state.sync_tasks, state, &s);
+            if !s.enabled {
+                auto_sync::cancel_running(&state.running_syncs, id);
+            }
+        }
+    }
+
+    fn removed(state: &AppState, id: i64) {
+        auto_sync::cancel(&state.sync_tasks, &AutoSyncKey::Source(id));
+        auto_sync::cancel_running(&state.running_syncs, id);
+    }
+}
+
+impl BulkTarget for Destinations {
+    type Create = db::CreateDestination;
+    type Update = db::UpdateDestination;
+    const NAME: &'static str = "Destination";
+
+    fn create(
+        conn: &Connection,
+        item: &mut db::CreateDestination,
+        owner: Option<i64>,
+    ) -> Result<i64> {
+        if owner.is_some() {
+            item.owner_id = owner;
+        }
+        db::create_destination(conn, item)
+    }
+
+    fn update(
+        conn: &Connection,
+        id: i64,
+        item: &mut db::UpdateDestination,
+        owner: Option<i64>,
+    ) -> Result<bool> {
+        if owner.is_some() {
+            item.owner_id = None;
+        }
+        db::update_destination(conn, id, item)
+    }
+
+    fn delete(conn: &Connection, id: i64) -> Result<bool> {
+        db::delete_destination(conn, id)
+    }
+
+    fn set_enabled(conn: &Connection, id: i64, enabled: bool) -> Result<bool> {
+        db::set_destination_enabled(conn, id, enabled)
+    }
+
+    fn owned_by(conn: &Connection, id: i64, owner: i64) -> Result<bool> {
+        db::destination_owned_by(conn, id, owner)
+    }
+
+    fn refresh(state: &AppState, id: i64) {
+        let dest = db::get_destination(&state.db.lock().unwrap(), id)
+            .ok()
+            .flatten();
+        if let Some(d) = dest {
+            auto_sync::register_destination(&state.sync_tasks, state, &d);
+        }
+    }
+
+    fn removed(state: &AppState, id: i64) {
+        auto_sync::cancel(&state.sync_tasks, &AutoSyncKey::Destination(id));
+    }
+}
+
+fn apply<T: BulkTarget>(
+    conn: &Connection,
+    op: &mut BulkOperation<T::Create, T::Update>,
+    owner: Option<i64>,
+) -> Result<i64> {
+    if let (Some(id), Some(owner)) = (op.id(), owner)
+        && !T::owned_by(conn, id, owner)?
+    {
+        bail!("{} {} not found", T::NAME, id);
+    }
+    let found = match op {
+        BulkOperation::Create { item } => return T::create(conn, item, owner),
+        BulkOperation::Update { id, item } => T::update(conn, *id, item, owner)?,
+        BulkOperation::Delete { id } => T::delete(conn, *id)?,
+        BulkOperation::Enable { id } => T::set_enabled(conn, *id, true)?,
+        BulkOperation::Disable { id } => T::set_enabled(conn, *id, false)?,
+    };
+    match (found, op.id()) {
+        (true, Some(id)) => Ok(id),
+        (_, id) => bail!("{} {} not found", T::NAME, id.unwrap_or_default()),
+    }
+}
+
+// Runs every operation in one transaction; the first failure rolls back the lot.
+fn run<T: BulkTarget>(
+    state: &AppState,
+    owner: Option<i64>,
+    mut operations: Vec<BulkOperation<T::Create, T::Update>>,
+) -> Response {
+    if operations.is_empty() {
+        return ApiError::bad_request("No operations given").into_response();
+    }
+    let mut results = Vec::with_capacity(operations.len());
+    let outcome = {
+        let db = state.db.lock().unwrap();
+        let tx = match db.unchecked_transaction() {
+            Ok(tx) => tx,
+            Err(e) => return ApiError::from(anyhow::Error::from(e)).into_response(),
+        };
+        let mut failure = None;
+        for (index, op) in operations.iter_mut().enumerate() {
+            let result = apply::<T>(&tx, op, owner);
+            results.push(BulkItemResult {
+                index,
+                action: op.action().into(),
+                id: result.as_ref().ok().copied().or(op.id()),
+                status: if result.is_ok() { "ok" } else { "error" }.into(),
+                message: result.as_ref().err().map(|e| e.to_string()),
+            });
+            if let Err(e) = result {
+                failure = Some((index, e));
+                break;
+            }
+        }
+        match failure {
+            Some(failure) => Err(failure),
+            None => tx.commit().map_err(|e| (0, e.into())),
+        }
+    };
+
+    if let Err((index, e)) = outcome {
+        for r in results.iter_mut().filter(|r| r.status == "ok") {
+            r.status = "rolled_back".into();
+        }
+        return ApiError::unprocessable(format!("Operation {} failed: {}", index, e))
+            .with_details(serde_json::json!({ "results": results }))
+            .into_response();
+    }
+
+    for (op, result) in operations.iter().zip(&results) {
+        let Some(id) = result.id else { continue };
+        match op {
+            BulkOperation::Delete { .. } => T::removed(state, id),
+            _ => T::refresh(state, id),
+        }
+    }
+
+    (
+        StatusCode::OK,
+        Json(BulkResponse {
+            status: "success".into(),
+            message: format!("{} operations applied", results.len()),
+            results,
+        }),
+    )
+        .into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/sources/bulk",
+    request_body = BulkRequest<db::CreateSource, db::UpdateSource>,
+    responses(
+        (status = 200, body = BulkResponse),
+        (status = 422, description = "An operation failed and nothing was applied; `details.results` has the per-item outcome", body = ApiError)
+    )
+)]
+async fn bulk_sources(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+    ApiJson(body): ApiJson<BulkRequest<db::CreateSource, db::UpdateSource>>,
+) -> Response {
+    run::<Sources>(&state, owner_scope(&user), body.operations)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/destinations/bulk",
+    request_body = BulkRequest<db::CreateDestination, db::UpdateDestination>,
+    responses(
+        (status = 200, body = BulkResponse),
+        (status = 422, description = "An operation failed and nothing was applied; `details.results` has the per-item outcome", body = ApiError)
+    )
+)]
+async fn bulk_destinations(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+    ApiJson(body): ApiJson<BulkRequest<db::CreateDestination, db::UpdateDestination>>,
+) -> Response {
+    run::<Destinations>(&state, owner_scope(&user), body.operations)
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/sources/bulk", post(bulk_sources))
+        .route("/destinations/bulk", post(bulk_destinations))
+}
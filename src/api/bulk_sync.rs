@@ -0,0 +1,109 @@
+use crate::api::error::ApiError;
+use crate::api::{AppState, owner_scope, require_admin};
+use crate::auto_sync::{self, AutoSyncKey};
+use crate::db;
+use crate::server::auth::CurrentUser;
+use axum::{
+    Extension, Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct SyncAllResponse {
+    status: String,
+    message: String,
+    sources: usize,
+    destinations: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SyncOverviewResponse {
+    items: Vec<db::SyncOverviewEntry>,
+}
+
+#[utoipa::path(post, path = "/api/sync/all", responses((status = 202, body = SyncAllResponse)))]
+pub async fn sync_all(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+) -> Response {
+    if let Some(denied) = require_admin(&user) {
+        return denied;
+    }
+    let keys: Vec<AutoSyncKey> = {
+        let db = state.db.lock().unwrap();
+        let (sources, destinations) = match (db::list_sources(&db), db::list_destinations(&db)) {
+            (Ok(s), Ok(d)) => (s, d),
+            (Err(e), _) | (_, Err(e)) => {
+                return ApiError::from(e).into_response();
+            }
+        };
+        sources
+            .iter()
+            .filter(|s| s.enabled && s.source_type != "upload")
+            .map(|s| AutoSyncKey::Source(s.id))
+            .chain(
+                destinations
+                    .iter()
+                    .filter(|d| d.enabled)
+                    .map(|d| AutoSyncKey::Destination(d.id)),
+            )
+            .collect()
+    };
+    let sources = keys
+        .iter()
+        .filter(|k| matches!(k, AutoSyncKey::Source(_)))
+        .count();
+    let destinations = keys.len() - sources;
+    // Each run waits for a slot in the shared sync limit.
+    for key in keys {
+        tokio::spawn(auto_sync::run_now(state.clone(), key));
+    }
+    (
+        StatusCode::ACCEPTED,
+        Json(SyncAllResponse {
+            status: "success".into(),
+            message: format!(
+                "Started {} source and {} destination syncs",
+                sources, destinations
+            ),
+            sources,
+            destinations,
+        }),
+    )
+        .into_response()
+}
+
+#[utoipa::path(get, path = "/api/sync/overview", responses((status = 200, body = SyncOverviewResponse)))]
+pub async fn sync_overview(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+) -> Response {
+    let result = {
+        let db = state.db.lock().unwrap();
+        db::sync_overview(&db, owner_scope(&user))
+    };
+    let mut items = match result {
+        Ok(items) => items,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    for item in &mut items {
+        let key = match item.kind.as_str() {
+            "source" => AutoSyncKey::Source(item.id),
+            _ => AutoSyncKey::Destination(item.id),
+        };
+        item.next_run = auto_sync::next_run(&state.sync_tasks, &key).map(|t| t.to_rfc3339());
+        item.last_sync_status = auto_sync::sync_status(&state, &key, item.last_sync_status.take());
+    }
+    (StatusCode::OK, Json(SyncOverviewResponse { items })).into_response()
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/sync/all", post(sync_all))
+        .route("/sync/overview", get(sync_overview))
+}
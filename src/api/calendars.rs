@@ -0,0 +1,249 @@
+use crate::api::error::ApiError;
+use crate::api::sources::SyncResult;
+use crate::api::{AppState, sync};
+use crate::auto_sync::{self, AutoSyncKey};
+use crate::db;
+use crate::http_clients;
+use crate::sync_progress;
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct CalendarEntry {
+    href: String,
+    displayname: Option<String>,
+    color: Option<String>,
+    order: Option<i64>,
+    components: Vec<String>,
+    // Events fetched from it by the last sync; null before the first one.
+    event_count: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CalendarListResponse {
+    calendars: Vec<CalendarEntry>,
+    discovered_at: String,
+    // Served from the last discovery instead of asking the server.
+    cached: bool,
+}
+
+#[derive(Deserialize)]
+struct CalendarListParams {
+    #[serde(default)]
+    refresh: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/sources/{id}/calendars",
+    params(
+        ("id" = i64, Path, description = "Source ID"),
+        ("refresh" = Option<bool>, Query, description = "Ask the server again instead of returning the last discovery")
+    ),
+    responses(
+        (status = 200, body = CalendarListResponse),
+        (status = 404, description = "Source not found", body = ApiError),
+        (status = 502, description = "The CalDAV server failed or could not be reached", body = ApiError)
+    )
+)]
+async fn list_calendars(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<CalendarListParams>,
+) -> Response {
+    let (source, cached) = {
+        let db = state.db.lock().unwrap();
+        let source = match db::get_source(&db, id) {
+            Ok(Some(s)) if s.source_type != "caldav" => {
+                return ApiError::bad_request("Only CalDAV sources have upstream calendars")
+                    .into_response();
+            }
+            Ok(Some(s)) => s,
+            Ok(None) => return ApiError::not_found("Source not found").into_response(),
+            Err(e) => return ApiError::from(e).into_response(),
+        };
+        let cached = match params.refresh {
+            true => None,
+            false => db::list_discovered_calendars(&db, id).ok().flatten(),
+        };
+        (source, cached)
+    };
+    let is_cached = cached.is_some();
+
+    let discovered = match cached {
+        Some(cached) => Ok(cached),
+        None => {
+            let discover = async {
+                let client = http_clients::get(
+                    &state.http_clients,
+                    &source.caldav_url,
+                    &source.username,
+                    &source.password,
+                    &source.user_agent,
+                    &source.custom_headers,
+                )
+                .map_err(ApiError::from)?;
+                // The server failing or being down is not this service's error.
+                sync::discover_calendars(&client, &source.caldav_url)
+                    .await
+                    .map_err(|e| {
+                        tracing::error!("Calendar discovery failed for source {}: {:#}", id, e);
+                        ApiError::bad_gateway(format!("Calendar discovery failed: {:#}", e))
+                    })
+            };
+            match discover.await {
+                Ok(mut calendars) => {
+                    calendars.sort_by_key(|c| (c.order.is_none(), c.order));
+                    let db = state.db.lock().unwrap();
+                    db::save_discovered_calendars(&db, id, &calendars)
+                        .map(|_| {
+                            let at = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+                            (calendars, at)
+                        })
+                        .map_err(ApiError::from)
+                }
+                Err(e) => Err(e),
+            }
+        }
+    };
+    let (calendars, discovered_at) = match discovered {
+        Ok(discovered) => discovered,
+        Err(e) => return e.into_response(),
+    };
+
+    let event_counts: std::collections::HashMap<String, usize> = {
+        let db = state.db.lock().unwrap();
+        db::list_source_calendars(&db, id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| (c.href, c.events.len()))
+            .collect()
+    };
+    let calendars = calendars
+        .into_iter()
+        .map(|c| CalendarEntry {
+            event_count: event_counts.get(&c.href).copied(),
+            href: c.href,
+            displayname: c.displayname,
+            color: c.color,
+            order: c.order,
+            components: c.components,
+        })
+        .collect();
+    (
+        StatusCode::OK,
+        Json(CalendarListResponse {
+            calendars,
+            discovered_at,
+            cached: is_cached,
+        }),
+    )
+        .into_response()
+}
+
+// The stored href matching `href`, which may differ in its trailing slash.
+fn known_calendar(calendars: &[db::SourceCalendar], href: &str) -> Option<String> {
+    calendars
+        .iter()
+        .find(|c| c.href.trim_end_matches('/') == href.trim_end_matches('/'))
+        .map(|c| c.href.clone())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/sources/{id}/calendars/{href}/sync",
+    params(
+        ("id" = i64, Path, description = "Source ID"),
+        ("href" = String, Path, description = "Calendar href, percent-encoded")
+    ),
+    responses(
+        (status = 200, body = SyncResult),
+        (status = 404, description = "Source or calendar not found", body = ApiError)
+    )
+)]
+pub async fn sync_calendar(
+    State(state): State<AppState>,
+    Path((id, href)): Path<(i64, String)>,
+) -> Response {
+    let (source, href) = {
+        let db = state.db.lock().unwrap();
+        let source = match db::get_source(&db, id) {
+            Ok(Some(s)) if s.source_type != "caldav" => {
+                return ApiError::bad_request("Only CalDAV sources have upstream calendars")
+                    .into_response();
+            }
+            Ok(Some(s)) => s,
+            Ok(None) => return ApiError::not_found("Source not found").into_response(),
+            Err(e) => return ApiError::from(e).into_response(),
+        };
+        let calendars = match db::list_source_calendars(&db, id) {
+            Ok(calendars) => calendars,
+            Err(e) => return ApiError::from(e).into_response(),
+        };
+        // Only calendars seen by a full sync, so the rest of the feed is known.
+        let Some(href) = known_calendar(&calendars, &href) else {
+            return ApiError::not_found(format!(
+                "Calendar '{}' not found; run a full sync of the source first",
+                href
+            ))
+            .into_response();
+        };
+        (source, href)
+    };
+
+    let run = sync_progress::track(&state.sync_progress, AutoSyncKey::Source(id), async {
+        sync_progress::update(|p| p.calendars_found = 1);
+        let client = http_clients::get(
+            &state.http_clients,
+            &source.caldav_url,
+            &source.username,
+            &source.password,
+            &source.user_agent,
+            &source.custom_headers,
+        )?;
+        let cache = sync::object_cache(&state.db.lock().unwrap(), &source)?;
+        sync::fetch_calendar(&client, &source.caldav_url, &href, cache.as_ref()).await
+    });
+    match auto_sync::cancellable(&state.running_syncs, id, run).await {
+        Ok(calendar) => {
+            let db = state.db.lock().unwrap();
+            match sync::store_calendar(&db, &source, calendar) {
+                Ok((events, calendars, diff)) => (
+                    StatusCode::OK,
+                    Json(SyncResult::success(events, calendars, &diff)),
+                )
+                    .into_response(),
+                Err(e) => {
+                    tracing::error!("Failed to save ICS data: {}", e);
+                    let _ = db::update_sync_status(&db, id, "error", Some(&e.to_string()));
+                    ApiError::from(e).into_response()
+                }
+            }
+        }
+        Err(e) if e.is::<auto_sync::SyncAlreadyRunning>() => ApiError::from(e).into_response(),
+        Err(e) if e.is::<auto_sync::SyncCancelled>() => {
+            let db = state.db.lock().unwrap();
+            let _ = auto_sync::mark_cancelled(&db, id);
+            ApiError::from(e).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Sync error for calendar {} of source {}: {}", href, id, e);
+            let db = state.db.lock().unwrap();
+            let _ = db::update_sync_status(&db, id, "error", Some(&e.to_string()));
+            ApiError::from(e).into_response()
+        }
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/sources/{id}/calendars", get(list_calendars))
+        .route("/sources/{id}/calendars/{href}/sync", post(sync_calendar))
+}
@@ -1,28 +1,129 @@
+use anyhow::Context;
 use axum::{
-    Json, Router,
-    extract::{Path, State},
-    http::StatusCode,
+    Extension, Json, Router,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
     response::IntoResponse,
     routing::{delete, get, post, put},
 };
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use super::AppState;
+use super::error::{ApiError, ApiJson};
+use super::{AppState, owner_scope, reverse_sync};
 use crate::auto_sync::{self, AutoSyncKey};
+use crate::calendar_routes::CalendarRoutes;
+use crate::csv_events;
 use crate::db;
+use crate::http_clients::{self, CustomHeaders};
+use crate::redact;
+use crate::server::auth::CurrentUser;
+use crate::server::ranges;
+use crate::sync_progress;
+
+// A destination as the API returns it: no password, and URL credentials masked.
+#[derive(Serialize, ToSchema)]
+#[schema(as = Destination)]
+pub struct DestinationView {
+    id: i64,
+    name: String,
+    ics_url: String,
+    // Where the ICS URL led on the last fetch, if elsewhere.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolved_ics_url: Option<String>,
+    caldav_url: String,
+    calendar_name: String,
+    username: String,
+    sync_interval_secs: i64,
+    sync_all: bool,
+    keep_local: bool,
+    last_synced: Option<String>,
+    last_sync_status: Option<String>,
+    last_sync_error: Option<String>,
+    created_at: String,
+    owner_id: Option<i64>,
+    enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_id: Option<i64>,
+    conflict_policy: String,
+    bidirectional: bool,
+    namespace_uids: bool,
+    user_agent: String,
+    // Values are masked.
+    custom_headers: CustomHeaders,
+    summary_template: String,
+    description_template: String,
+    location_template: String,
+    drop_properties: String,
+    routes: CalendarRoutes,
+    scheduling: String,
+    max_requests_per_second: f64,
+    request_delay_ms: i64,
+    ics_username: String,
+    // Values are masked.
+    ics_headers: CustomHeaders,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_sync_result: Option<db::DestinationRunResult>,
+}
+
+impl From<db::Destination> for DestinationView {
+    fn from(d: db::Destination) -> Self {
+        Self {
+            id: d.id,
+            name: d.name,
+            ics_url: redact::url(&d.ics_url).into_owned(),
+            resolved_ics_url: d.resolved_ics_url.map(|u| redact::url(&u).into_owned()),
+            caldav_url: redact::url(&d.caldav_url).into_owned(),
+            calendar_name: d.calendar_name,
+            username: d.username,
+            sync_interval_secs: d.sync_interval_secs,
+            sync_all: d.sync_all,
+            keep_local: d.keep_local,
+            last_synced: d.last_synced,
+            last_sync_status: d.last_sync_status,
+            last_sync_error: d.last_sync_error,
+            created_at: d.created_at,
+            owner_id: d.owner_id,
+            enabled: d.enabled,
+            source_id: d.source_id,
+            conflict_policy: d.conflict_policy,
+            bidirectional: d.bidirectional,
+            namespace_uids: d.namespace_uids,
+            custom_headers: redact::headers(&d.custom_headers),
+            user_agent: d.user_agent,
+            summary_template: d.summary_template,
+            description_template: d.description_template,
+            location_template: d.location_template,
+            drop_properties: d.drop_properties,
+            routes: d.routes,
+            scheduling: d.scheduling,
+            max_requests_per_second: d.max_requests_per_second,
+            request_delay_ms: d.request_delay_ms,
+            ics_username: d.ics_username,
+            ics_headers: redact::headers(&d.ics_headers),
+            last_sync_result: d.last_sync_result,
+        }
+    }
+}
 
 #[derive(Serialize, ToSchema)]
 pub struct DestinationResponse {
     status: String,
     message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    destination: Option<db::Destination>,
+    destination: Option<DestinationView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_sync_at: Option<String>,
+}
+
+// Unset when auto-sync is disabled or a sync is currently running.
+fn next_sync_at(state: &AppState, id: i64) -> Option<String> {
+    auto_sync::next_run(&state.sync_tasks, &AutoSyncKey::Destination(id)).map(|t| t.to_rfc3339())
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct DestinationListResponse {
-    destinations: Vec<db::Destination>,
+    destinations: Vec<DestinationView>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -30,9 +131,38 @@ pub struct ReverseSyncResult {
     status: String,
     message: String,
     uploaded: usize,
+    added: usize,
+    changed: usize,
     skipped: usize,
     deleted: usize,
     total: usize,
+    // Events edited in the calendar since they were last pushed.
+    conflicts: Vec<db::EventConflict>,
+    // Events created in the calendar and published in the combined feed.
+    merged: usize,
+    // Events that failed; the next sync retries them.
+    failed: Vec<db::FailedEvent>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CsvImportResult {
+    status: String,
+    message: String,
+    // Rows below the header, blank ones not counted.
+    rows: usize,
+    uploaded: usize,
+    added: usize,
+    changed: usize,
+    skipped: usize,
+    conflicts: Vec<db::EventConflict>,
+    failed: Vec<db::FailedEvent>,
+    // Rows that could not be read and were left out.
+    errors: Vec<csv_events::RowError>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ConflictListResponse {
+    conflicts: Vec<db::EventConflict>,
 }
 
 pub fn routes() -> Router<AppState> {
@@ -43,34 +173,57 @@ pub fn routes() -> Router<AppState> {
         .route("/destinations/{id}", put(update_destination))
         .route("/destinations/{id}", delete(delete_destination))
         .route("/destinations/{id}/sync", post(sync_destination))
+        .route(
+            "/destinations/{id}/sync/progress",
+            get(destination_sync_progress),
+        )
+        .route("/destinations/{id}/sync/state", get(destination_sync_state))
+        .route("/destinations/{id}/runs", get(list_runs))
+        .route("/destinations/{id}/import/csv", post(import_csv))
+        .route("/destinations/{id}/conflicts", get(list_conflicts))
+        .route("/destinations/{id}/feed", get(combined_feed))
 }
 
 #[utoipa::path(get, path = "/api/destinations", responses((status = 200, body = DestinationListResponse)))]
-pub async fn list_destinations(State(state): State<AppState>) -> impl IntoResponse {
+pub async fn list_destinations(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+) -> impl IntoResponse {
     let db = state.db.lock().unwrap();
-    match db::list_destinations(&db) {
+    let result = match owner_scope(&user) {
+        Some(owner_id) => db::list_destinations_for_owner(&db, owner_id),
+        None => db::list_destinations(&db),
+    };
+    match result {
         Ok(destinations) => (
             StatusCode::OK,
-            Json(DestinationListResponse { destinations }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(DestinationResponse {
-                status: "error".into(),
-                message: e.to_string(),
-                destination: None,
+            Json(DestinationListResponse {
+                destinations: destinations
+                    .into_iter()
+                    .map(|d| {
+                        let key = AutoSyncKey::Destination(d.id);
+                        let mut view = DestinationView::from(d);
+                        view.last_sync_status =
+                            auto_sync::sync_status(&state, &key, view.last_sync_status);
+                        view
+                    })
+                    .collect(),
             }),
         )
             .into_response(),
+        Err(e) => ApiError::from(e).into_response(),
     }
 }
 
 #[utoipa::path(post, path = "/api/destinations", request_body = db::CreateDestination, responses((status = 201, body = DestinationResponse)))]
 pub async fn create_destination(
     State(state): State<AppState>,
-    Json(body): Json<db::CreateDestination>,
+    user: Option<Extension<CurrentUser>>,
+    ApiJson(mut body): ApiJson<db::CreateDestination>,
 ) -> impl IntoResponse {
+    if let Some(owner_id) = owner_scope(&user) {
+        body.owner_id = Some(owner_id);
+    }
     let (id, dest) = {
         let db = state.db.lock().unwrap();
         match db::create_destination(&db, &body) {
@@ -79,15 +232,7 @@ pub async fn create_destination(
                 (id, dest)
             }
             Err(e) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(DestinationResponse {
-                        status: "error".into(),
-                        message: e.to_string(),
-                        destination: None,
-                    }),
-                )
-                    .into_response();
+                return ApiError::bad_request(e.to_string()).into_response();
             }
         }
     };
@@ -101,7 +246,8 @@ pub async fn create_destination(
         Json(DestinationResponse {
             status: "success".into(),
             message: format!("Destination created with id {}", id),
-            destination: dest,
+            destination: dest.map(Into::into),
+            next_sync_at: next_sync_at(&state, id),
         }),
     )
         .into_response()
@@ -111,33 +257,21 @@ pub async fn create_destination(
 pub async fn update_destination(
     State(state): State<AppState>,
     Path(id): Path<i64>,
-    Json(body): Json<db::UpdateDestination>,
+    user: Option<Extension<CurrentUser>>,
+    ApiJson(mut body): ApiJson<db::UpdateDestination>,
 ) -> impl IntoResponse {
+    if owner_scope(&user).is_some() {
+        body.owner_id = None;
+    }
     let dest = {
         let db = state.db.lock().unwrap();
         match db::update_destination(&db, id, &body) {
             Ok(true) => db::get_destination(&db, id).ok().flatten(),
             Ok(false) => {
-                return (
-                    StatusCode::NOT_FOUND,
-                    Json(DestinationResponse {
-                        status: "error".into(),
-                        message: "Destination not found".into(),
-                        destination: None,
-                    }),
-                )
-                    .into_response();
+                return ApiError::not_found("Destination not found").into_response();
             }
             Err(e) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(DestinationResponse {
-                        status: "error".into(),
-                        message: e.to_string(),
-                        destination: None,
-                    }),
-                )
-                    .into_response();
+                return ApiError::bad_request(e.to_string()).into_response();
             }
         }
     };
@@ -151,7 +285,8 @@ pub async fn update_destination(
         Json(DestinationResponse {
             status: "success".into(),
             message: "Destination updated".into(),
-            destination: dest,
+            destination: dest.map(Into::into),
+            next_sync_at: next_sync_at(&state, id),
         }),
     )
         .into_response()
@@ -176,28 +311,13 @@ pub async fn delete_destination(
                     status: "success".into(),
                     message: "Destination deleted".into(),
                     destination: None,
+                    next_sync_at: None,
                 }),
             )
                 .into_response()
         }
-        Ok(false) => (
-            StatusCode::NOT_FOUND,
-            Json(DestinationResponse {
-                status: "error".into(),
-                message: "Destination not found".into(),
-                destination: None,
-            }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(DestinationResponse {
-                status: "error".into(),
-                message: e.to_string(),
-                destination: None,
-            }),
-        )
-            .into_response(),
+        Ok(false) => ApiError::not_found("Destination not found").into_response(),
+        Err(e) => ApiError::from(e).into_response(),
     }
 }
 
@@ -206,75 +326,99 @@ pub async fn sync_destination(
     State(state): State<AppState>,
     Path(id): Path<i64>,
 ) -> impl IntoResponse {
-    let (ics_url, caldav_url, calendar_name, username, password, sync_all, keep_local) = {
+    let d = {
         let db = state.db.lock().unwrap();
         match db::get_destination(&db, id) {
-            Ok(Some(d)) => (
-                d.ics_url,
-                d.caldav_url,
-                d.calendar_name,
-                d.username,
-                d.password,
-                d.sync_all,
-                d.keep_local,
-            ),
+            Ok(Some(d)) => d,
             Ok(None) => {
-                return (
-                    StatusCode::NOT_FOUND,
-                    Json(ReverseSyncResult {
-                        status: "error".into(),
-                        message: "Destination not found".into(),
-                        uploaded: 0,
-                        skipped: 0,
-                        deleted: 0,
-                        total: 0,
-                    }),
-                )
-                    .into_response();
+                return ApiError::not_found("Destination not found").into_response();
             }
             Err(e) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ReverseSyncResult {
-                        status: "error".into(),
-                        message: e.to_string(),
-                        uploaded: 0,
-                        skipped: 0,
-                        deleted: 0,
-                        total: 0,
-                    }),
-                )
-                    .into_response();
+                return ApiError::from(e).into_response();
             }
         }
     };
 
-    match crate::api::reverse_sync::run_reverse_sync(
-        &ics_url,
-        &caldav_url,
-        &calendar_name,
-        &username,
-        &password,
-        sync_all,
-        keep_local,
-    )
-    .await
-    {
-        Ok(stats) => {
+    let started = std::time::Instant::now();
+    // Manual syncs always push, even when the feed matches the last one pushed.
+    let run = async {
+        let known_etags = db::get_destination_event_etags(&state.db.lock().unwrap(), id)?;
+        let feed = reverse_sync::load_feed(&state, &d, None, None)
+            .await?
+            .context("ICS feed returned no content")?;
+        let client = http_clients::get(
+            &state.http_clients,
+            &d.caldav_url,
+            &d.username,
+            &d.password,
+            &d.user_agent,
+            &d.custom_headers,
+        )?;
+        let stats =
+            reverse_sync::push_destination(&state, &client, &feed.text, &d, &known_etags).await?;
+        anyhow::Ok((stats, feed))
+    };
+    match sync_progress::track(&state.sync_progress, AutoSyncKey::Destination(id), run).await {
+        Ok((stats, feed)) => {
             let db = state.db.lock().unwrap();
-            let _ = db::update_destination_sync_status(&db, id, "ok", None);
+            let _ =
+                db::save_destination_events(&db, id, &stats.etags, &stats.conflicts, &stats.failed);
+            let _ = db::set_destination_run_result(&db, id, &stats.run_result());
+            if stats.failed.is_empty() {
+                let _ = db::update_destination_sync_status(&db, id, "ok", None);
+                let _ = db::set_destination_feed_validators(
+                    &db,
+                    id,
+                    Some(&feed.hash),
+                    feed.etag.as_deref(),
+                );
+            } else {
+                let summary = reverse_sync::failure_summary(&stats.failed);
+                let _ = db::update_destination_sync_status(&db, id, "degraded", Some(&summary));
+                let _ = db::set_destination_feed_validators(&db, id, None, None);
+            }
+            if let Some(combined) = &stats.combined_feed {
+                let _ = db::save_destination_feed(&db, id, combined);
+            }
+            let _ =
+                db::update_destination_sync_duration(&db, id, started.elapsed().as_millis() as i64);
+            let mut message = format!(
+                "{} added, {} changed, {} unchanged of {} events; deleted {} orphans",
+                stats.added, stats.changed, stats.skipped, stats.total, stats.deleted
+            );
+            if d.bidirectional {
+                message.push_str(&format!("; merged {} from the calendar", stats.merged));
+            }
+            if !stats.conflicts.is_empty() {
+                message.push_str(&format!(
+                    "; {} edited in the calendar ({})",
+                    stats.conflicts.len(),
+                    d.conflict_policy
+                ));
+            }
+            if !stats.failed.is_empty() {
+                message.push_str(&format!(
+                    "; {} failed and will be retried",
+                    stats.failed.len()
+                ));
+            }
             (
                 StatusCode::OK,
                 Json(ReverseSyncResult {
-                    status: "success".into(),
-                    message: format!(
-                        "Uploaded {} of {} events ({} unchanged); deleted {} orphans",
-                        stats.uploaded, stats.total, stats.skipped, stats.deleted
-                    ),
+                    status: match stats.failed.is_empty() {
+                        true => "success".into(),
+                        false => "partial".into(),
+                    },
+                    message,
                     uploaded: stats.uploaded,
+                    added: stats.added,
+                    changed: stats.changed,
                     skipped: stats.skipped,
                     deleted: stats.deleted,
                     total: stats.total,
+                    conflicts: stats.conflicts,
+                    merged: stats.merged,
+                    failed: stats.failed,
                 }),
             )
                 .into_response()
@@ -283,22 +427,187 @@ pub async fn sync_destination(
             tracing::error!("Reverse sync error for destination {}: {}", id, e);
             let db = state.db.lock().unwrap();
             let _ = db::update_destination_sync_status(&db, id, "error", Some(&e.to_string()));
+            ApiError::from(e).into_response()
+        }
+    }
+}
+
+// Uploads the rows of a CSV file to the destination's calendar the way a sync
+// uploads its feed, but never deletes: the calendar keeps what the file
+// doesn't have. See `csv_events` for the mapping.
+#[utoipa::path(
+    post,
+    path = "/api/destinations/{id}/import/csv",
+    params(("id" = i64, Path, description = "Destination ID"), csv_events::CsvMapping),
+    request_body(content = String, content_type = "text/csv"),
+    responses((status = 200, body = CsvImportResult))
+)]
+pub async fn import_csv(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(mapping): Query<csv_events::CsvMapping>,
+    body: String,
+) -> impl IntoResponse {
+    let d = match db::get_destination(&state.db.lock().unwrap(), id) {
+        Ok(Some(d)) => d,
+        Ok(None) => return ApiError::not_found("Destination not found").into_response(),
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    let converted = match csv_events::to_ics(&body, &mapping) {
+        Ok(converted) => converted,
+        Err(e) => return ApiError::bad_request(e.to_string()).into_response(),
+    };
+    let d = db::Destination {
+        sync_all: true,
+        keep_local: true,
+        bidirectional: false,
+        ..d
+    };
+    let run = async {
+        let known_etags = db::get_destination_event_etags(&state.db.lock().unwrap(), id)?;
+        let client = http_clients::get(
+            &state.http_clients,
+            &d.caldav_url,
+            &d.username,
+            &d.password,
+            &d.user_agent,
+            &d.custom_headers,
+        )?;
+        reverse_sync::push_destination(&state, &client, &converted.ics, &d, &known_etags).await
+    };
+    match sync_progress::track(&state.sync_progress, AutoSyncKey::Destination(id), run).await {
+        Ok(stats) => {
+            let mut message = format!(
+                "{} added, {} changed, {} unchanged of {} rows",
+                stats.added, stats.changed, stats.skipped, converted.rows
+            );
+            if !converted.errors.is_empty() {
+                message.push_str(&format!("; {} unreadable", converted.errors.len()));
+            }
+            if !stats.failed.is_empty() {
+                message.push_str(&format!("; {} failed", stats.failed.len()));
+            }
+            let status = match converted.errors.is_empty() && stats.failed.is_empty() {
+                true => "success",
+                false => "partial",
+            };
             (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ReverseSyncResult {
-                    status: "error".into(),
-                    message: e.to_string(),
-                    uploaded: 0,
-                    skipped: 0,
-                    deleted: 0,
-                    total: 0,
+                StatusCode::OK,
+                Json(CsvImportResult {
+                    status: status.into(),
+                    message,
+                    rows: converted.rows,
+                    uploaded: stats.uploaded,
+                    added: stats.added,
+                    changed: stats.changed,
+                    skipped: stats.skipped,
+                    conflicts: stats.conflicts,
+                    failed: stats.failed,
+                    errors: converted.errors,
                 }),
             )
                 .into_response()
         }
+        Err(e) => {
+            tracing::error!("CSV import for destination {} failed: {}", id, e);
+            ApiError::from(e).into_response()
+        }
     }
 }
 
+#[utoipa::path(get, path = "/api/destinations/{id}/sync/progress", responses((status = 200, body = crate::api::sources::SyncProgressResponse)))]
+pub async fn destination_sync_progress(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    crate::api::sources::progress_response(&state, AutoSyncKey::Destination(id))
+}
+
+// Failed events and orphans left to delete, from the last sync and any run
+// that was interrupted.
+#[utoipa::path(get, path = "/api/destinations/{id}/sync/state", responses((status = 200, body = db::DestinationSyncState)))]
+pub async fn destination_sync_state(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let db = state.db.lock().unwrap();
+    match db::get_destination(&db, id) {
+        Ok(Some(_)) => {}
+        Ok(None) => return ApiError::not_found("Destination not found").into_response(),
+        Err(e) => return ApiError::from(e).into_response(),
+    }
+    match db::get_destination_sync_state(&db, id) {
+        Ok(sync_state) => (StatusCode::OK, Json(sync_state)).into_response(),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+// The last 50 runs, newest first.
+#[utoipa::path(get, path = "/api/destinations/{id}/runs", responses((status = 200, body = crate::api::sources::SyncRunListResponse)))]
+pub async fn list_runs(State(state): State<AppState>, Path(id): Path<i64>) -> impl IntoResponse {
+    crate::api::sources::runs_response(&state, AutoSyncKey::Destination(id))
+}
+
+// Events the last sync found edited in the calendar and how they were handled.
+#[utoipa::path(get, path = "/api/destinations/{id}/conflicts", responses((status = 200, body = ConflictListResponse)))]
+pub async fn list_conflicts(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let db = state.db.lock().unwrap();
+    match db::get_destination(&db, id) {
+        Ok(Some(_)) => {}
+        Ok(None) => return ApiError::not_found("Destination not found").into_response(),
+        Err(e) => return ApiError::from(e).into_response(),
+    }
+    match db::list_destination_conflicts(&db, id) {
+        Ok(conflicts) => (StatusCode::OK, Json(ConflictListResponse { conflicts })).into_response(),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+// The ICS feed plus the events created in the calendar, for bidirectional
+// destinations. Revalidates with If-None-Match like the /ics feeds.
+#[utoipa::path(
+    get,
+    path = "/api/destinations/{id}/feed",
+    params(("id" = i64, Path, description = "Destination ID")),
+    responses((status = 200, description = "Combined feed as ICS", content_type = "text/calendar"))
+)]
+pub async fn combined_feed(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let db = state.db.lock().unwrap();
+    let feed = match db::get_destination(&db, id) {
+        Ok(Some(d)) if d.bidirectional => db::get_destination_feed(&db, id),
+        Ok(Some(_)) => {
+            return ApiError::not_found("Destination is not bidirectional").into_response();
+        }
+        Ok(None) => return ApiError::not_found("Destination not found").into_response(),
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    let content = match feed {
+        Ok(Some(content)) => content,
+        Ok(None) => return ApiError::not_found("Destination has not synced yet").into_response(),
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    let etag = ranges::etag(content.as_bytes());
+    if ranges::not_modified(&headers, &etag, None) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "text/calendar".to_string()),
+            (header::ETAG, etag),
+        ],
+        content,
+    )
+        .into_response()
+}
+
 #[derive(Deserialize, ToSchema)]
 pub struct OverlapQuery {
     caldav_url: String,
@@ -332,8 +641,10 @@ pub struct OverlapResponse {
 )]
 pub async fn check_overlap(
     State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
     axum::extract::Query(q): axum::extract::Query<OverlapQuery>,
 ) -> impl IntoResponse {
+    let scope = owner_scope(&user);
     let db = state.db.lock().unwrap();
     match db::find_overlapping_destinations(&db, &q.caldav_url, &q.calendar_name, q.exclude_id) {
         Ok(dests) => (
@@ -341,6 +652,7 @@ pub async fn check_overlap(
             Json(OverlapResponse {
                 overlapping: dests
                     .into_iter()
+                    .filter(|d| scope.is_none_or(|o| d.owner_id == Some(o)))
                     .map(|d| OverlapEntry {
                         id: d.id,
                         name: d.name,
@@ -354,13 +666,7 @@ pub async fn check_overlap(
             .into_response(),
         Err(e) => {
             tracing::error!("Failed to check destination overlap: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(OverlapResponse {
-                    overlapping: vec![],
-                }),
-            )
-                .into_response()
+            ApiError::from(e).into_response()
         }
     }
 }
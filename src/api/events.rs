@@ -0,0 +1,164 @@
+use crate::api::error::ApiError;
+use crate::api::{AppState, owner_scope};
+use crate::db;
+use crate::event_index::TIMESTAMP_FORMAT;
+use crate::server::auth::CurrentUser;
+use axum::{
+    Extension, Json, Router,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+#[derive(Deserialize, ToSchema)]
+pub struct EventQuery {
+    email: Option<String>,
+    q: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    source_id: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct KeywordQuery {
+    q: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EventListResponse {
+    status: String,
+    message: String,
+    events: Vec<db::Event>,
+}
+
+fn respond(message: impl Into<String>, events: Vec<db::Event>) -> Response {
+    (
+        StatusCode::OK,
+        Json(EventListResponse {
+            status: "success".into(),
+            message: message.into(),
+            events,
+        }),
+    )
+        .into_response()
+}
+
+// Accepts `2026-03-10` or an RFC 3339 timestamp and returns it in UTC.
+fn parse_bound(name: &str, value: Option<&str>) -> Result<Option<String>, String> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(Some(format!("{} 00:00:00", date)));
+    }
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|dt| Some(dt.naive_utc().format(TIMESTAMP_FORMAT).to_string()))
+        .map_err(|_| {
+            format!(
+                "'{}' must be a date (YYYY-MM-DD) or RFC 3339 timestamp",
+                name
+            )
+        })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/events",
+    params(
+        ("email" = Option<String>, Query, description = "Organizer or attendee email address"),
+        ("q" = Option<String>, Query, description = "Text to match in the event summary"),
+        ("from" = Option<String>, Query, description = "Only events ending at or after this date/time"),
+        ("to" = Option<String>, Query, description = "Only events starting before this date/time"),
+        ("source_id" = Option<i64>, Query, description = "Restrict to one source"),
+        ("limit" = Option<i64>, Query, description = "Maximum number of events (default 100)"),
+    ),
+    responses((status = 200, body = EventListResponse))
+)]
+pub async fn search_events(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+    Query(query): Query<EventQuery>,
+) -> Response {
+    let (from, to) = match (
+        parse_bound("from", query.from.as_deref()),
+        parse_bound("to", query.to.as_deref()),
+    ) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(e), _) | (_, Err(e)) => return ApiError::bad_request(e).into_response(),
+    };
+    let search = db::EventSearch {
+        email: query.email.filter(|e| !e.trim().is_empty()),
+        text: query.q.filter(|q| !q.trim().is_empty()),
+        keywords: None,
+        from,
+        to,
+        source_id: query.source_id,
+        owner_id: owner_scope(&user),
+        limit: query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+    };
+    let db = state.db.lock().unwrap();
+    match db::search_events(&db, &search) {
+        Ok(events) => respond(format!("Found {} events", events.len()), events),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/events/search",
+    params(
+        ("q" = String, Query, description = "Text to match in the summary, description or location"),
+        ("from" = Option<String>, Query, description = "Only events ending at or after this date/time"),
+        ("to" = Option<String>, Query, description = "Only events starting before this date/time"),
+        ("limit" = Option<i64>, Query, description = "Maximum number of events (default 100)"),
+    ),
+    responses(
+        (status = 200, body = EventListResponse),
+        (status = 400, description = "Missing `q` or invalid date", body = ApiError)
+    )
+)]
+pub async fn search_keywords(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+    Query(query): Query<KeywordQuery>,
+) -> Response {
+    let Some(keywords) = query.q.filter(|q| !q.trim().is_empty()) else {
+        return ApiError::bad_request("'q' is required").into_response();
+    };
+    let (from, to) = match (
+        parse_bound("from", query.from.as_deref()),
+        parse_bound("to", query.to.as_deref()),
+    ) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(e), _) | (_, Err(e)) => return ApiError::bad_request(e).into_response(),
+    };
+    let search = db::EventSearch {
+        keywords: Some(keywords),
+        from,
+        to,
+        owner_id: owner_scope(&user),
+        limit: query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+        ..Default::default()
+    };
+    let db = state.db.lock().unwrap();
+    match db::search_events(&db, &search) {
+        Ok(events) => respond(format!("Found {} events", events.len()), events),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/events", get(search_events))
+        .route("/events/search", get(search_keywords))
+}
@@ -0,0 +1,193 @@
+use crate::api::error::{ApiError, ApiJson};
+use crate::api::{AppState, owner_scope};
+use crate::db;
+use crate::feed_metadata::{FeedMetadata, FeedProperties};
+use crate::feed_urls;
+use crate::server::auth::CurrentUser;
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct FeedListResponse {
+    feeds: Vec<db::Feed>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FeedMetadataResponse {
+    status: String,
+    message: String,
+    metadata: FeedMetadata,
+}
+
+fn metadata_response(message: &str, metadata: FeedMetadata) -> Response {
+    (
+        StatusCode::OK,
+        Json(FeedMetadataResponse {
+            status: "success".into(),
+            message: message.into(),
+            metadata,
+        }),
+    )
+        .into_response()
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FeedPropertiesResponse {
+    status: String,
+    message: String,
+    properties: FeedProperties,
+}
+
+fn properties_response(message: &str, properties: FeedProperties) -> Response {
+    (
+        StatusCode::OK,
+        Json(FeedPropertiesResponse {
+            status: "success".into(),
+            message: message.into(),
+            properties,
+        }),
+    )
+        .into_response()
+}
+
+#[utoipa::path(get, path = "/api/feeds", responses((status = 200, body = FeedListResponse)))]
+pub async fn list_feeds(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+) -> Response {
+    let db = state.db.lock().unwrap();
+    match db::list_feeds(&db, owner_scope(&user)) {
+        Ok(mut feeds) => {
+            if let Some(base) = state.public_base_url.as_deref() {
+                for feed in &mut feeds {
+                    let public_path = feed.public_ics_path.as_deref().filter(|_| feed.public_ics);
+                    feed.feed_urls = feed_urls::for_feed(base, &feed.ics_path, public_path);
+                }
+            }
+            (StatusCode::OK, Json(FeedListResponse { feeds })).into_response()
+        }
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/sources/{id}/metadata",
+    params(("id" = i64, Path, description = "Source ID")),
+    responses((status = 200, body = FeedMetadataResponse))
+)]
+pub async fn get_metadata(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    let db = state.db.lock().unwrap();
+    match db::get_source(&db, id) {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return ApiError::not_found("Source not found").into_response();
+        }
+        Err(e) => {
+            return ApiError::from(e).into_response();
+        }
+    }
+    match db::get_feed_metadata(&db, id) {
+        Ok(metadata) => metadata_response("Feed metadata", metadata),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/sources/{id}/metadata",
+    params(("id" = i64, Path, description = "Source ID")),
+    request_body = FeedMetadata,
+    responses((status = 200, body = FeedMetadataResponse))
+)]
+pub async fn set_metadata(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    ApiJson(metadata): ApiJson<FeedMetadata>,
+) -> Response {
+    let db = state.db.lock().unwrap();
+    match db::get_source(&db, id) {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return ApiError::not_found("Source not found").into_response();
+        }
+        Err(e) => {
+            return ApiError::from(e).into_response();
+        }
+    }
+    match db::set_feed_metadata(&db, id, &metadata) {
+        Ok(()) => metadata_response("Feed metadata updated", metadata),
+        Err(e) => ApiError::bad_request(e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/sources/{id}/properties",
+    params(("id" = i64, Path, description = "Source ID")),
+    responses((status = 200, body = FeedPropertiesResponse))
+)]
+pub async fn get_properties(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    let db = state.db.lock().unwrap();
+    match db::get_source(&db, id) {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return ApiError::not_found("Source not found").into_response();
+        }
+        Err(e) => {
+            return ApiError::from(e).into_response();
+        }
+    }
+    match db::get_feed_properties(&db, id) {
+        Ok(properties) => properties_response("Feed properties", properties),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/sources/{id}/properties",
+    params(("id" = i64, Path, description = "Source ID")),
+    request_body = FeedProperties,
+    responses((status = 200, body = FeedPropertiesResponse))
+)]
+pub async fn set_properties(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    ApiJson(properties): ApiJson<FeedProperties>,
+) -> Response {
+    let db = state.db.lock().unwrap();
+    match db::get_source(&db, id) {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return ApiError::not_found("Source not found").into_response();
+        }
+        Err(e) => {
+            return ApiError::from(e).into_response();
+        }
+    }
+    match db::set_feed_properties(&db, id, &properties) {
+        Ok(()) => properties_response("Feed properties updated", properties),
+        Err(e) => ApiError::bad_request(e.to_string()).into_response(),
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/feeds", get(list_feeds))
+        .route(
+            "/sources/{id}/metadata",
+            get(get_metadata).put(set_metadata),
+        )
+        .route(
+            "/sources/{id}/properties",
+            get(get_properties).put(set_properties),
+        )
+}
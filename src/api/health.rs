@@ -1,5 +1,11 @@
-use crate::api::AppState;
-use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::get};
+use crate::api::version::{LatestRelease, latest_release};
+use crate::api::{AppState, owner_scope};
+use crate::auto_sync::{self, AutoSyncKey};
+use crate::circuit_breaker::CircuitStatus;
+use crate::server::auth::CurrentUser;
+use axum::{
+    Extension, Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::get,
+};
 use serde::Serialize;
 use utoipa::ToSchema;
 
@@ -13,7 +19,15 @@ pub struct DetailedHealthResponse {
     pub status: String,
     pub uptime_seconds: u64,
     pub source_count: usize,
+    // Sources and destinations whose startup sync hasn't finished yet.
+    pub initializing: usize,
     pub db_ok: bool,
+    // CalDAV hosts that failed scheduled syncs since their last success;
+    // admins only, as they span every user's sources.
+    pub circuits: Vec<CircuitStatus>,
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_release: Option<LatestRelease>,
 }
 
 #[utoipa::path(get, path = "/api/health", responses((status = 200, body = HealthResponse)))]
@@ -27,22 +41,60 @@ pub async fn health() -> impl IntoResponse {
 }
 
 #[utoipa::path(get, path = "/api/health/detailed", responses((status = 200, body = DetailedHealthResponse)))]
-pub async fn health_detailed(State(state): State<AppState>) -> impl IntoResponse {
-    let (source_count, db_ok) = {
+pub async fn health_detailed(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+) -> impl IntoResponse {
+    // Counts cover what the user owns, like the source and destination lists.
+    let scope = owner_scope(&user);
+    let (source_count, db_ok, keys) = {
         let db = state.db.lock().unwrap();
-        match crate::db::list_sources(&db) {
-            Ok(sources) => (sources.len(), true),
-            Err(_) => (0, false),
+        let sources = match scope {
+            Some(owner_id) => crate::db::list_sources_for_owner(&db, owner_id),
+            None => crate::db::list_sources(&db),
+        };
+        match sources {
+            Ok(sources) => {
+                let destinations = match scope {
+                    Some(owner_id) => crate::db::list_destinations_for_owner(&db, owner_id),
+                    None => crate::db::list_destinations(&db),
+                }
+                .unwrap_or_default();
+                let keys: Vec<AutoSyncKey> = sources
+                    .iter()
+                    .map(|s| AutoSyncKey::Source(s.id))
+                    .chain(destinations.iter().map(|d| AutoSyncKey::Destination(d.id)))
+                    .collect();
+                (sources.len(), true, keys)
+            }
+            Err(_) => (0, false, vec![]),
         }
     };
+    // Deleted ones may linger in the startup set; only existing ones count.
+    let initializing = keys
+        .iter()
+        .filter(|key| auto_sync::is_initializing(&state, key))
+        .count();
+    let status = match (db_ok, initializing) {
+        (false, _) => "degraded",
+        (true, 0) => "ok",
+        (true, _) => "initializing",
+    };
     let uptime = state.start_time.elapsed().as_secs();
     (
         StatusCode::OK,
         Json(DetailedHealthResponse {
-            status: if db_ok { "ok" } else { "degraded" }.into(),
+            status: status.into(),
             uptime_seconds: uptime,
             source_count,
+            initializing,
             db_ok,
+            circuits: match user.as_ref().is_some_and(|Extension(u)| !u.is_admin) {
+                true => Vec::new(),
+                false => state.circuit_breakers.statuses(),
+            },
+            version: env!("CARGO_PKG_VERSION").into(),
+            latest_release: latest_release(),
         }),
     )
 }
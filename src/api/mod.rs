@@ -1,28 +1,90 @@
 use axum::Router;
+use axum::response::{IntoResponse, Response};
 use std::sync::{Arc, Mutex};
 
-use crate::auto_sync::AutoSyncRegistry;
+use crate::auto_sync::{AutoSyncRegistry, RunningSyncs, SyncLimit};
+use crate::http_clients::ClientCache;
+use crate::server::auth::CurrentUser;
+use crate::server::caldav_proxy::ProxyCache;
+use crate::server::feed_signing::FeedSigner;
+use crate::sync_progress::ProgressRegistry;
 
+pub mod admin;
+pub mod archive;
+pub mod attachments;
+pub mod auth;
+pub mod batch_upload;
+pub mod bulk;
+pub mod bulk_sync;
+pub mod calendars;
 pub mod destinations;
+pub mod error;
+pub mod events;
+pub mod feeds;
 pub mod health;
 pub mod openapi;
 pub mod reverse_sync;
 pub mod source_paths;
 pub mod sources;
 pub mod sync;
+pub mod tools;
+pub mod uploads;
+pub mod users;
+pub mod version;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Arc<Mutex<rusqlite::Connection>>,
     pub start_time: std::time::Instant,
     pub sync_tasks: AutoSyncRegistry,
+    pub sync_limit: SyncLimit,
+    pub running_syncs: RunningSyncs,
+    pub sync_progress: ProgressRegistry,
+    pub feed_signer: Option<FeedSigner>,
+    pub public_base_url: Option<String>,
+    // BASE_PATH the router is nested under, e.g. `/calsync`; empty at the root.
+    pub base_path: String,
+    pub proxy_cache: ProxyCache,
+    pub http_clients: ClientCache,
+    pub startup_syncs: crate::auto_sync::StartupSyncs,
+    pub circuit_breakers: crate::circuit_breaker::CircuitBreakers,
+    // HOLIDAY_PROVIDER_URL; built-in holidays are used without one.
+    pub holiday_provider: Option<String>,
+    pub maintenance: crate::maintenance::Settings,
 }
 
 pub fn routes() -> Router<AppState> {
     Router::new()
+        .merge(bulk::routes())
         .merge(sources::routes())
+        .merge(calendars::routes())
         .merge(source_paths::routes())
+        .merge(archive::routes())
+        .merge(uploads::routes())
+        .merge(users::routes())
+        .merge(admin::routes())
+        .merge(auth::routes())
         .merge(destinations::routes())
+        .merge(bulk_sync::routes())
+        .merge(events::routes())
+        .merge(feeds::routes())
+        .merge(tools::routes())
         .merge(health::routes())
+        .merge(version::routes())
         .merge(openapi::routes())
 }
+
+// Requests without a CurrentUser (auth disabled) are unrestricted.
+pub(crate) fn owner_scope(user: &Option<axum::Extension<CurrentUser>>) -> Option<i64> {
+    user.as_ref().and_then(|u| u.owner_scope())
+}
+
+// The 403 response for a signed-in non-admin; None lets the request through.
+pub(crate) fn require_admin(user: &Option<axum::Extension<CurrentUser>>) -> Option<Response> {
+    match user {
+        Some(axum::Extension(u)) if !u.is_admin => {
+            Some(error::ApiError::forbidden("Admin access required").into_response())
+        }
+        _ => None,
+    }
+}
@@ -1,16 +1,49 @@
 use crate::api::AppState;
+use crate::api::admin::{ExportParams, ImportResponse, MaintenanceResponse};
+use crate::api::auth::{LoginRequest, LoginResponse};
+use crate::api::bulk::{BulkItemResult, BulkResponse};
+use crate::api::bulk_sync::{SyncAllResponse, SyncOverviewResponse};
+use crate::api::calendars::{CalendarEntry, CalendarListResponse};
 use crate::api::destinations::{
-    DestinationListResponse, DestinationResponse, OverlapEntry, OverlapResponse, ReverseSyncResult,
+    ConflictListResponse, CsvImportResult, DestinationListResponse, DestinationResponse,
+    DestinationView, OverlapEntry, OverlapResponse, ReverseSyncResult,
 };
+use crate::api::error::ApiError;
+use crate::api::events::{EventListResponse, EventQuery, KeywordQuery};
+use crate::api::feeds::{FeedListResponse, FeedMetadataResponse, FeedPropertiesResponse};
 use crate::api::health::{DetailedHealthResponse, HealthResponse};
 use crate::api::source_paths::{SourcePathListResponse, SourcePathResponse};
-use crate::api::sources::{SourceListResponse, SourceResponse, SyncResult};
+use crate::api::sources::{
+    SourceListResponse, SourceResponse, SourceView, SyncProgressResponse, SyncResult,
+};
+use crate::api::tools::{
+    DiffRequest, DiffResponse, DiffSide, ValidateIcsRequest, ValidateIcsResponse,
+};
+use crate::api::users::{UsageResponse, UserListResponse, UserResponse};
+use crate::api::version::{LatestRelease, VersionResponse};
+use crate::circuit_breaker::{CircuitState, CircuitStatus};
+use crate::config_transfer::{
+    ConfigExport, Encryption, ExportedDestination, ExportedPath, ExportedSource, ImportSummary,
+};
 use crate::db::{
-    CreateDestination, CreateSource, CreateSourcePath, Destination, Source, SourcePath,
-    UpdateDestination, UpdateSource, UpdateSourcePath,
+    CloneSource, CreateDestination, CreateSource, CreateSourcePath, CreateUser, Event,
+    EventConflict, Feed, Quotas, SourcePath, SyncOverviewEntry, UpdateDestination, UpdateSource,
+    UpdateSourcePath, UpdateUser, Usage, User,
 };
-use axum::{Json, Router, response::IntoResponse, routing::get};
-use utoipa::OpenApi;
+use crate::feed_diff::{ChangedEvent, EventRef, FeedDiff, FieldDiff};
+use crate::feed_urls::FeedUrls;
+use crate::ics_repair::{Repair, RepairReport};
+use crate::ics_validation::{Category, Issue, Severity, ValidationReport};
+use crate::server::auth::CurrentUser;
+use crate::sync_progress::SyncProgress;
+use axum::{
+    Json, Router,
+    extract::State,
+    response::{Html, IntoResponse},
+    routing::get,
+};
+use utoipa::openapi::{ContentBuilder, Ref, ResponseBuilder, Server};
+use utoipa::{Modify, OpenApi};
 
 #[derive(OpenApi)]
 #[openapi(
@@ -19,44 +52,156 @@ use utoipa::OpenApi;
         crate::api::sources::create_source,
         crate::api::sources::update_source,
         crate::api::sources::delete_source_handler,
+        crate::api::sources::clone_source,
         crate::api::sources::sync_source,
+        crate::api::sources::cancel_sync,
+        crate::api::sources::sync_progress_handler,
         crate::api::sources::source_status,
+        crate::api::sources::list_runs,
+        crate::api::calendars::list_calendars,
+        crate::api::calendars::sync_calendar,
         crate::api::source_paths::list_source_paths,
         crate::api::source_paths::create_source_path,
         crate::api::source_paths::update_source_path,
         crate::api::source_paths::delete_source_path,
+        crate::api::uploads::upload_source,
+        crate::api::archive::export_archive,
         crate::api::destinations::list_destinations,
         crate::api::destinations::create_destination,
         crate::api::destinations::update_destination,
         crate::api::destinations::delete_destination,
         crate::api::destinations::sync_destination,
+        crate::api::destinations::destination_sync_progress,
+        crate::api::destinations::destination_sync_state,
+        crate::api::destinations::list_runs,
+        crate::api::destinations::import_csv,
+        crate::api::destinations::list_conflicts,
+        crate::api::destinations::combined_feed,
         crate::api::destinations::check_overlap,
+        crate::api::bulk::bulk_sources,
+        crate::api::bulk::bulk_destinations,
+        crate::api::bulk_sync::sync_all,
+        crate::api::bulk_sync::sync_overview,
+        crate::api::events::search_events,
+        crate::api::events::search_keywords,
+        crate::api::tools::validate_ics,
+        crate::api::tools::diff_feeds,
+        crate::api::feeds::list_feeds,
+        crate::api::feeds::get_metadata,
+        crate::api::feeds::set_metadata,
+        crate::api::feeds::get_properties,
+        crate::api::feeds::set_properties,
+        crate::api::admin::export_config,
+        crate::api::admin::import_config,
+        crate::api::admin::maintenance_status,
+        crate::api::admin::run_maintenance,
+        crate::api::users::current_user,
+        crate::api::users::list_users,
+        crate::api::users::create_user,
+        crate::api::users::update_user,
+        crate::api::users::delete_user,
+        crate::api::users::current_usage,
+        crate::api::users::user_usage,
+        crate::api::auth::login,
+        crate::api::auth::logout,
         crate::api::health::health,
         crate::api::health::health_detailed,
+        crate::api::version::version,
     ),
     components(schemas(
-        Source,
+        SourceView,
         CreateSource,
         UpdateSource,
+        CloneSource,
         SourceResponse,
         SourceListResponse,
         SyncResult,
+        RepairReport,
+        Repair,
+        SyncProgressResponse,
+        SyncProgress,
         SourcePath,
         CreateSourcePath,
         UpdateSourcePath,
         SourcePathResponse,
         SourcePathListResponse,
-        Destination,
+        DestinationView,
         CreateDestination,
         UpdateDestination,
         DestinationResponse,
         DestinationListResponse,
         ReverseSyncResult,
+        CsvImportResult,
+        crate::csv_events::RowError,
+        EventConflict,
+        crate::db::FailedEvent,
+        crate::db::DestinationSyncState,
+        crate::db::SyncRun,
+        crate::api::sources::SyncRunListResponse,
+        ConflictListResponse,
         OverlapEntry,
         OverlapResponse,
+        SyncAllResponse,
+        BulkResponse,
+        BulkItemResult,
+        SyncOverviewResponse,
+        CalendarListResponse,
+        CalendarEntry,
+        SyncOverviewEntry,
+        Event,
+        EventQuery,
+        KeywordQuery,
+        EventListResponse,
+        ValidateIcsRequest,
+        ValidateIcsResponse,
+        ValidationReport,
+        Issue,
+        Severity,
+        Category,
+        DiffSide,
+        DiffRequest,
+        DiffResponse,
+        FeedDiff,
+        EventRef,
+        ChangedEvent,
+        FieldDiff,
+        Feed,
+        FeedUrls,
+        FeedListResponse,
+        FeedMetadataResponse,
+        FeedPropertiesResponse,
+        crate::feed_metadata::FeedProperties,
+        crate::calendar_routes::CalendarRoute,
+        ConfigExport,
+        Encryption,
+        ExportedSource,
+        ExportedPath,
+        ExportedDestination,
+        ExportParams,
+        ImportResponse,
+        MaintenanceResponse,
+        crate::maintenance::MaintenanceReport,
+        ImportSummary,
+        User,
+        CreateUser,
+        UpdateUser,
+        UserResponse,
+        UserListResponse,
+        UsageResponse,
+        Quotas,
+        Usage,
+        CurrentUser,
+        LoginRequest,
+        LoginResponse,
         HealthResponse,
         DetailedHealthResponse,
+        CircuitStatus,
+        CircuitState,
+        VersionResponse,
+        LatestRelease,
+        ApiError,
     )),
+    modifiers(&ErrorResponses),
     info(
         title = "CalDAV/ICS Sync API",
         version = env!("CARGO_PKG_VERSION"),
@@ -65,10 +210,78 @@ use utoipa::OpenApi;
 )]
 pub struct ApiDoc;
 
-async fn openapi_json() -> impl IntoResponse {
-    Json(ApiDoc::openapi())
+// Every operation can fail with the shared error envelope.
+struct ErrorResponses;
+
+impl Modify for ErrorResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let error = ResponseBuilder::new()
+            .description("Error; `code` identifies the kind")
+            .content(
+                "application/json",
+                ContentBuilder::new()
+                    .schema(Some(Ref::from_schema_name("ApiError")))
+                    .build(),
+            )
+            .build();
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ];
+            for op in operations.into_iter().flatten() {
+                op.responses
+                    .responses
+                    .entry("default".into())
+                    .or_insert_with(|| error.clone().into());
+            }
+        }
+    }
+}
+
+async fn openapi_json(State(state): State<AppState>) -> impl IntoResponse {
+    let mut doc = ApiDoc::openapi();
+    if !state.base_path.is_empty() {
+        doc.servers = Some(vec![Server::new(&state.base_path)]);
+    }
+    Json(doc)
+}
+
+// Swagger UI is loaded from a CDN; the spec is fetched relative to the page, so
+// it follows BASE_PATH and goes through the same auth as the rest of /api.
+const SWAGGER_UI_VERSION: &str = "5.17.14";
+
+async fn docs() -> Html<String> {
+    Html(format!(
+        r##"<!doctype html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>CalDAV/ICS Sync API</title>
+  <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/swagger-ui-dist@{v}/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://cdn.jsdelivr.net/npm/swagger-ui-dist@{v}/swagger-ui-bundle.js" crossorigin></script>
+  <script>
+    window.ui = SwaggerUIBundle({{
+      url: new URL("openapi.json", window.location.href).href,
+      dom_id: "#swagger-ui",
+      withCredentials: true,
+    }});
+  </script>
+</body>
+</html>
+"##,
+        v = SWAGGER_UI_VERSION
+    ))
 }
 
 pub fn routes() -> Router<AppState> {
-    Router::new().route("/openapi.json", get(openapi_json))
+    Router::new()
+        .route("/openapi.json", get(openapi_json))
+        .route("/docs", get(docs))
 }
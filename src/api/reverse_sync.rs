@@ -1,19 +1,104 @@
 use std::collections::{HashMap, HashSet};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, ensure};
 use chrono::NaiveDateTime;
 use reqwest::{Client, header};
 
-use crate::api::sync;
+use crate::api::{AppState, attachments, batch_upload, sync};
+use crate::calendar_routes;
+use crate::event_template::{self, EventTemplate};
+use crate::scheduling;
+use crate::server::caldav::xml_escape;
+use crate::throttle::{Pace, Throttle};
+use crate::{dav_urls, db, ics_component, ics_text, legacy_ics};
+use crate::{http_clients, sync_origin, sync_progress};
 
 const VOLATILE_FIELDS: &[&str] = &["DTSTAMP", "SEQUENCE", "LAST-MODIFIED", "CREATED"];
 
-#[derive(Debug)]
+// Appended to the UID of the feed's copy of an event under the "duplicate"
+// conflict policy, so both versions end up in the calendar.
+const DUPLICATE_SUFFIX: &str = "-feed-copy";
+
+#[derive(Debug, Default)]
 pub struct ReverseSyncStats {
     pub uploaded: usize,
+    pub added: usize,
+    pub changed: usize,
     pub skipped: usize,
     pub deleted: usize,
     pub total: usize,
+    pub conflicts: Vec<db::EventConflict>,
+    // Event ETags to remember for the next sync's conflict check.
+    pub etags: HashMap<String, Option<String>>,
+    // Events created in the calendar, merged into `combined_feed`.
+    pub merged: usize,
+    // The feed plus the calendar's own events, for bidirectional destinations.
+    pub combined_feed: Option<String>,
+    // Events that could not be uploaded or deleted; the next sync retries them.
+    pub failed: Vec<db::FailedEvent>,
+}
+
+impl ReverseSyncStats {
+    pub fn run_result(&self) -> db::DestinationRunResult {
+        db::DestinationRunResult {
+            uploaded: self.uploaded,
+            added: self.added,
+            changed: self.changed,
+            skipped: self.skipped,
+            deleted: self.deleted,
+            total: self.total,
+            failed: self.failed.clone(),
+        }
+    }
+}
+
+// Where and how a feed is pushed.
+pub struct PushTarget<'a> {
+    pub caldav_url: &'a str,
+    pub calendar_name: &'a str,
+    pub username: &'a str,
+    pub password: &'a str,
+    pub sync_all: bool,
+    pub keep_local: bool,
+    pub conflict_policy: &'a str,
+    // The destination's namespace when it namespaces UIDs or is bidirectional.
+    // Feed events stamped with it are never pushed back; see `sync_origin`.
+    pub origin: Option<String>,
+    pub namespace_uids: bool,
+    pub bidirectional: bool,
+    pub template: EventTemplate<'a>,
+    pub scheduling: &'a str,
+    pub pace: Pace,
+}
+
+impl<'a> From<&'a db::Destination> for PushTarget<'a> {
+    fn from(d: &'a db::Destination) -> Self {
+        Self {
+            caldav_url: &d.caldav_url,
+            calendar_name: &d.calendar_name,
+            username: &d.username,
+            password: &d.password,
+            sync_all: d.sync_all,
+            keep_local: d.keep_local,
+            conflict_policy: &d.conflict_policy,
+            origin: (d.namespace_uids || d.bidirectional)
+                .then(|| sync_origin::destination_namespace(d.id)),
+            namespace_uids: d.namespace_uids,
+            bidirectional: d.bidirectional,
+            template: EventTemplate {
+                summary: &d.summary_template,
+                description: &d.description_template,
+                location: &d.location_template,
+                drop_properties: &d.drop_properties,
+                destination: &d.name,
+            },
+            scheduling: &d.scheduling,
+            pace: Pace {
+                requests_per_second: d.max_requests_per_second,
+                delay_ms: d.request_delay_ms,
+            },
+        }
+    }
 }
 
 fn unfold_ics(text: &str) -> String {
@@ -30,7 +115,7 @@ fn unfold_ics(text: &str) -> String {
     lines.join("\n")
 }
 
-fn normalize_vevent(vevent_data: &str) -> Vec<String> {
+pub(crate) fn normalize_vevent(vevent_data: &str) -> Vec<String> {
     let unfolded = unfold_ics(vevent_data);
     let mut lines: Vec<String> = unfolded
         .lines()
@@ -62,6 +147,29 @@ fn events_equal(existing: &[String], incoming: &[String]) -> bool {
     a == b
 }
 
+fn sequence(vevents: &[String]) -> i64 {
+    vevents
+        .iter()
+        .filter_map(|v| ics_component::value(v, "SEQUENCE")?.parse().ok())
+        .max()
+        .unwrap_or(0)
+}
+
+// Whether the calendar's copy changed since reverse sync last pushed or saw it:
+// by ETag when one was recorded, otherwise by a SEQUENCE ahead of the feed's.
+fn edited_in_calendar(remote: &RemoteEvent, incoming: &[String], known: Option<&str>) -> bool {
+    match (known, remote.etag.as_deref()) {
+        (Some(known), Some(current)) => known != current,
+        _ => sequence(&remote.vevents) > sequence(incoming),
+    }
+}
+
+fn with_uid(vevent: &str, uid: &str) -> String {
+    ics_component::rewrite(vevent, |name, _| {
+        (name == "UID").then(|| format!("UID:{}\r\n", uid))
+    })
+}
+
 #[derive(Debug)]
 enum EventEnd {
     Date(chrono::NaiveDate),
@@ -98,28 +206,16 @@ fn parse_ics_value(value: &str, tzid: Option<&str>) -> Option<EventEnd> {
 }
 
 fn event_end_parsed(vevent_text: &str) -> Option<EventEnd> {
-    let unfolded = unfold_ics(vevent_text);
-    let mut dtend = None;
-    let mut dtstart = None;
-    for line in unfolded.lines() {
-        let trimmed = line.trim();
-        let Some(colon_pos) = trimmed.find(':') else {
-            continue;
-        };
-        let params = &trimmed[..colon_pos];
-        let prop_name = params.split(';').next().unwrap_or("");
+    let parsed = |name| {
+        let line = ics_component::property(vevent_text, name)?;
+        let (params, value) = ics_text::split_property(line.trim())?;
         let tzid = params
             .split(';')
             .skip(1)
             .find_map(|p| p.strip_prefix("TZID="));
-        let value = &trimmed[colon_pos + 1..];
-        match prop_name {
-            "DTEND" => dtend = parse_ics_value(value, tzid),
-            "DTSTART" => dtstart = parse_ics_value(value, tzid),
-            _ => {}
-        }
-    }
-    dtend.or(dtstart)
+        parse_ics_value(value, tzid)
+    };
+    parsed("DTEND").or_else(|| parsed("DTSTART"))
 }
 
 fn is_event_in_future(vevent_text: &str) -> bool {
@@ -136,16 +232,16 @@ struct ExtractedEvents {
 }
 
 fn extract_events(ics_text: &str) -> ExtractedEvents {
-    let unfolded = unfold_ics(ics_text);
     let mut events: HashMap<String, Vec<String>> = HashMap::new();
     let mut vtimezones: Vec<String> = Vec::new();
     let mut in_vevent = false;
     let mut in_vtimezone = false;
     let mut current_event = String::new();
-    let mut current_uid = String::new();
     let mut current_tz = String::new();
 
-    for line in unfolded.lines() {
+    // Blocks keep their lines as they came, folding and nested VALARMs
+    // included, so what is pushed matches what the feed published.
+    for line in ics_text.lines() {
         if line.starts_with("BEGIN:VTIMEZONE") {
             in_vtimezone = true;
             current_tz.clear();
@@ -162,21 +258,14 @@ fn extract_events(ics_text: &str) -> ExtractedEvents {
             if line.starts_with("BEGIN:VEVENT") {
                 in_vevent = true;
                 current_event.clear();
-                current_uid.clear();
             }
             if in_vevent {
                 current_event.push_str(line);
                 current_event.push_str("\r\n");
-                if line.starts_with("UID:") {
-                    current_uid = line.trim_start_matches("UID:").trim().to_string();
-                }
                 if line.starts_with("END:VEVENT") {
                     in_vevent = false;
-                    if !current_uid.is_empty() {
-                        events
-                            .entry(current_uid.clone())
-                            .or_default()
-                            .push(current_event.clone());
+                    if let Some(uid) = sync::event_uid(&current_event).filter(|u| !u.is_empty()) {
+                        events.entry(uid).or_default().push(current_event.clone());
                     }
                 }
             }
@@ -185,23 +274,348 @@ fn extract_events(ics_text: &str) -> ExtractedEvents {
     ExtractedEvents { events, vtimezones }
 }
 
+// One calendar object to store under `put_uid`, on behalf of feed event `uid`.
+struct Upload<'a> {
+    uid: String,
+    put_uid: String,
+    body: String,
+    // The calendar's copy an update replaces; None creates the event.
+    remote: Option<&'a RemoteEvent>,
+}
+
+#[derive(Default)]
+struct RemoteEvent {
+    vevents: Vec<String>,
+    vtimezones: Vec<String>,
+    href: Option<String>,
+    etag: Option<String>,
+}
+
+// The precondition a PUT carries: `If-None-Match: *` to create, `If-Match`
+// with the listed ETag to update. Some servers refuse blind writes.
+fn precondition(remote: Option<&RemoteEvent>) -> Option<(header::HeaderName, String)> {
+    match remote {
+        None => Some((header::IF_NONE_MATCH, "*".to_string())),
+        Some(remote) => remote.etag.clone().map(|etag| (header::IF_MATCH, etag)),
+    }
+}
+
+async fn put_with(
+    client: &Client,
+    throttle: &mut Throttle,
+    url: &str,
+    body: &str,
+    precondition: Option<(header::HeaderName, String)>,
+) -> reqwest::Result<reqwest::Response> {
+    throttle
+        .send(|| {
+            let request = client
+                .put(url)
+                .header("Content-Type", "text/calendar; charset=utf-8")
+                .body(body.to_string());
+            match &precondition {
+                Some((name, value)) => request.header(name, value),
+                None => request,
+            }
+        })
+        .await
+}
+
+// PUTs an event with the precondition the listing suggests. A 412 means the
+// object changed since, or the server wants a precondition the listing had no
+// ETag for: HEAD tells whether it exists now and the PUT is retried once.
+async fn put_event(
+    client: &Client,
+    throttle: &mut Throttle,
+    url: &str,
+    body: &str,
+    remote: Option<&RemoteEvent>,
+) -> reqwest::Result<reqwest::Response> {
+    let res = put_with(client, throttle, url, body, precondition(remote)).await?;
+    if res.status() != reqwest::StatusCode::PRECONDITION_FAILED {
+        return Ok(res);
+    }
+    let head = throttle.send(|| client.head(url)).await?;
+    let retry = match head.status() {
+        reqwest::StatusCode::NOT_FOUND => (header::IF_NONE_MATCH, "*".to_string()),
+        status if status.is_success() => match head.headers().get(header::ETAG) {
+            Some(etag) => (
+                header::IF_MATCH,
+                etag.to_str().unwrap_or_default().to_string(),
+            ),
+            None => return Ok(res),
+        },
+        _ => return Ok(res),
+    };
+    tracing::info!(
+        "PUT {} failed its precondition; retrying with {}: {}",
+        url,
+        retry.0,
+        retry.1
+    );
+    put_with(client, throttle, url, body, Some(retry)).await
+}
+
+// The collection a destination writes to: its URL when that already names the
+// calendar, else the calendar under it.
+fn calendar_base(caldav_url: &str, calendar_name: &str) -> Result<String> {
+    let normalized_url = caldav_url.trim_end_matches('/');
+    if normalized_url.ends_with(&format!("/{}", calendar_name)) {
+        return Ok(format!("{}/", normalized_url));
+    }
+    dav_urls::collection_url(caldav_url, calendar_name)
+}
+
 async fn fetch_existing_events(
     client: &Client,
     calendar_base: &str,
-) -> Result<HashMap<String, Vec<String>>> {
-    let existing_data = sync::fetch_events(client, calendar_base, calendar_base)
+) -> Result<HashMap<String, RemoteEvent>> {
+    let existing_data = sync::fetch_event_resources(client, calendar_base, calendar_base)
         .await
         .context("Failed to fetch existing CalDAV events")?;
 
-    let mut map: HashMap<String, Vec<String>> = HashMap::new();
-    for ics_str in &existing_data {
-        for (uid, vevents) in extract_events(ics_str).events {
-            map.entry(uid).or_default().extend(vevents);
+    let mut map: HashMap<String, RemoteEvent> = HashMap::new();
+    for resource in existing_data {
+        let extracted = extract_events(&resource.calendar_data);
+        for (uid, vevents) in extracted.events {
+            let entry = map.entry(uid).or_default();
+            entry.vevents.extend(vevents);
+            entry
+                .vtimezones
+                .extend(extracted.vtimezones.iter().cloned());
+            entry.href = resource.href.clone();
+            entry.etag = resource.etag.clone();
         }
     }
     Ok(map)
 }
 
+// The VEVENTs of a feed by UID.
+pub(crate) fn feed_events(ics_text: &str) -> HashMap<String, Vec<String>> {
+    extract_events(ics_text).events
+}
+
+// The VEVENTs in a destination's calendar by UID. UIDs the destination
+// namespaced are given back their feed form, so they line up with the feed.
+pub(crate) async fn calendar_events(
+    dest: &db::Destination,
+) -> Result<HashMap<String, Vec<String>>> {
+    let client = http_clients::build(&dest.username, &dest.password)?;
+    let base = calendar_base(&dest.caldav_url, &dest.calendar_name)?;
+    let suffix = format!("@{}", sync_origin::destination_namespace(dest.id));
+    Ok(fetch_existing_events(&client, &base)
+        .await?
+        .into_iter()
+        .map(|(uid, remote)| {
+            let uid = match dest.namespace_uids {
+                true => uid.strip_suffix(&suffix).map(str::to_owned).unwrap_or(uid),
+                false => uid,
+            };
+            (uid, remote.vevents)
+        })
+        .collect())
+}
+
+pub struct FetchedFeed {
+    pub text: String,
+    pub hash: String,
+    pub etag: Option<String>,
+}
+
+// Feeds moved more often than this are treated as broken.
+const MAX_FEED_REDIRECTS: usize = 5;
+
+// Calendar apps hand out subscription links as webcal://, which is HTTPS.
+pub fn feed_url(ics_url: &str) -> String {
+    let ics_url = ics_url.trim();
+    for scheme in ["webcal://", "webcals://"] {
+        if ics_url
+            .get(..scheme.len())
+            .is_some_and(|s| s.eq_ignore_ascii_case(scheme))
+        {
+            return format!("https://{}", &ics_url[scheme.len()..]);
+        }
+    }
+    ics_url.to_string()
+}
+
+// Fetches the feed, or returns None when it matches the hash/ETag of the last
+// one pushed. Our own feeds answer the ETag with a 304 and no body.
+pub async fn fetch_feed(
+    ics_url: &str,
+    known_hash: Option<&str>,
+    known_etag: Option<&str>,
+) -> Result<Option<FetchedFeed>> {
+    let cache = http_clients::new_cache();
+    let (_, feed) = fetch_feed_with(&cache, None, ics_url, known_hash, known_etag).await?;
+    Ok(feed)
+}
+
+// Credentials stay with the feed's own origin, or its upgrade to HTTPS on
+// the default port, so a redirect can't hand them to someone else.
+fn keeps_credentials(from: &reqwest::Url, to: &reqwest::Url) -> bool {
+    from.host_str() == to.host_str()
+        && match (from.scheme(), to.scheme()) {
+            (a, b) if a == b => from.port_or_known_default() == to.port_or_known_default(),
+            ("http", "https") => to.port().is_none(),
+            _ => false,
+        }
+}
+
+// Sends the GET, following redirects, and returns the final URL with the
+// response.
+async fn feed_response(
+    cache: &http_clients::ClientCache,
+    dest: Option<&db::Destination>,
+    ics_url: &str,
+    known_etag: Option<&str>,
+) -> Result<(reqwest::Url, reqwest::Response)> {
+    let start = reqwest::Url::parse(&feed_url(ics_url)).context("Invalid ICS URL")?;
+    let mut url = start.clone();
+    let mut redirects = 0;
+    let response = loop {
+        let client = match dest {
+            Some(d) if keeps_credentials(&start, &url) => http_clients::get_for_feed(
+                cache,
+                url.as_str(),
+                &d.ics_username,
+                &d.ics_password,
+                &d.ics_headers,
+            )?,
+            _ => http_clients::get_for_feed(cache, url.as_str(), "", "", &Default::default())?,
+        };
+        let mut request = client.get(url.clone());
+        if let Some(etag) = known_etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        let response = request.send().await.context("Failed to fetch ICS file")?;
+        if !response.status().is_redirection()
+            || response.status() == reqwest::StatusCode::NOT_MODIFIED
+        {
+            break response;
+        }
+        ensure!(
+            redirects < MAX_FEED_REDIRECTS,
+            "ICS feed redirected more than {} times",
+            MAX_FEED_REDIRECTS
+        );
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .context("ICS feed redirected without a Location")?;
+        url = url
+            .join(&feed_url(location))
+            .context("ICS feed redirected to an invalid URL")?;
+        redirects += 1;
+    };
+    Ok((url, response))
+}
+
+// Downloads a feed as is, the way a destination (if given) fetches it, for
+// the tools that inspect feeds.
+pub async fn download_feed(dest: Option<&db::Destination>, ics_url: &str) -> Result<String> {
+    let cache = http_clients::new_cache();
+    let (_, response) = feed_response(&cache, dest, ics_url, None).await?;
+    ensure!(
+        response.status().is_success(),
+        "ICS feed returned HTTP {}",
+        response.status()
+    );
+    ics_text::read_text(response)
+        .await
+        .context("Failed to read ICS body")
+}
+
+// Also returns the URL the feed was found at after redirects.
+async fn fetch_feed_with(
+    cache: &http_clients::ClientCache,
+    dest: Option<&db::Destination>,
+    ics_url: &str,
+    known_hash: Option<&str>,
+    known_etag: Option<&str>,
+) -> Result<(reqwest::Url, Option<FetchedFeed>)> {
+    let (url, response) = feed_response(cache, dest, ics_url, known_hash.and(known_etag)).await?;
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok((url, None));
+    }
+    // A login page or error body must not pass for an empty feed, which would
+    // delete every pushed event.
+    ensure!(
+        response.status().is_success(),
+        "ICS feed returned HTTP {}",
+        response.status()
+    );
+    let etag = response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+    let text = ics_text::read_text(response)
+        .await
+        .context("Failed to read ICS body")?;
+    let (text, fixes) = legacy_ics::normalize(&text);
+    legacy_ics::log_fixes(ics_url, &fixes);
+    let hash = crate::db::content_hash(&text);
+    if known_hash == Some(hash.as_str()) {
+        return Ok((url, None));
+    }
+    Ok((url, Some(FetchedFeed { text, hash, etag })))
+}
+
+// Reads a local source's feed straight from the database. Rehosted
+// attachments get absolute links when PUBLIC_BASE_URL is set.
+fn local_feed(
+    state: &AppState,
+    source_id: i64,
+    known_hash: Option<&str>,
+) -> Result<Option<FetchedFeed>> {
+    let content = db::get_ics_data(&state.db.lock().unwrap(), source_id)?
+        .with_context(|| format!("Source {} has not been synced yet", source_id))?;
+    let text = match state.public_base_url.as_deref() {
+        Some(base) => attachments::absolutize(&content, base),
+        None => content,
+    };
+    let hash = db::content_hash(&text);
+    if known_hash == Some(hash.as_str()) {
+        return Ok(None);
+    }
+    Ok(Some(FetchedFeed {
+        text,
+        hash,
+        etag: None,
+    }))
+}
+
+// The feed a destination pushes: its local source's, or the one at its URL.
+pub async fn load_feed(
+    state: &AppState,
+    dest: &db::Destination,
+    known_hash: Option<&str>,
+    known_etag: Option<&str>,
+) -> Result<Option<FetchedFeed>> {
+    match dest.source_id {
+        Some(source_id) => local_feed(state, source_id, known_hash),
+        None => {
+            let (url, feed) = fetch_feed_with(
+                &state.http_clients,
+                Some(dest),
+                &dest.ics_url,
+                known_hash,
+                known_etag,
+            )
+            .await?;
+            let moved = reqwest::Url::parse(dest.ics_url.trim()).ok() != Some(url.clone());
+            let _ = db::set_destination_resolved_url(
+                &state.db.lock().unwrap(),
+                dest.id,
+                moved.then_some(url.as_str()),
+            );
+            Ok(feed)
+        }
+    }
+}
+
 pub async fn run_reverse_sync(
     ics_url: &str,
     caldav_url: &str,
@@ -211,28 +625,234 @@ pub async fn run_reverse_sync(
     sync_all: bool,
     keep_local: bool,
 ) -> Result<ReverseSyncStats> {
-    let ics_client = Client::new();
-    let ics_response = ics_client
-        .get(ics_url)
+    let feed = fetch_feed(ics_url, None, None)
+        .await?
+        .context("ICS feed returned no content")?;
+    let target = PushTarget {
+        caldav_url,
+        calendar_name,
+        username,
+        password,
+        sync_all,
+        keep_local,
+        conflict_policy: "overwrite",
+        origin: None,
+        namespace_uids: false,
+        bidirectional: false,
+        template: EventTemplate::default(),
+        scheduling: "keep",
+        pace: Pace::default(),
+    };
+    push_feed(&feed.text, &target, &HashMap::new()).await
+}
+
+// Uploads the events of an already fetched feed to the CalDAV calendar.
+// `known_etags` are the ETags from the previous sync (see ReverseSyncStats).
+pub async fn push_feed(
+    ics_text: &str,
+    target: &PushTarget<'_>,
+    known_etags: &HashMap<String, Option<String>>,
+) -> Result<ReverseSyncStats> {
+    let client = http_clients::build(target.username, target.password)?;
+    push_feed_with(&client, ics_text, target, known_etags).await
+}
+
+// `push_feed` with a client that already carries the target's credentials.
+pub async fn push_feed_with(
+    caldav_client: &Client,
+    ics_text: &str,
+    target: &PushTarget<'_>,
+    known_etags: &HashMap<String, Option<String>>,
+) -> Result<ReverseSyncStats> {
+    push_events(caldav_client, ics_text, target, known_etags, false, None).await
+}
+
+// Records a destination's progress while it syncs, so a run cut short leaves
+// its uploads and pending deletes behind for the next one.
+#[derive(Clone, Copy)]
+struct Journal<'a> {
+    state: &'a AppState,
+    destination_id: i64,
+}
+
+impl Journal<'_> {
+    fn record(&self, write: impl FnOnce(&rusqlite::Connection, i64) -> Result<()>) {
+        if let Err(e) = write(&self.state.db.lock().unwrap(), self.destination_id) {
+            tracing::warn!(
+                "Failed to record sync progress of destination {}: {}",
+                self.destination_id,
+                e
+            );
+        }
+    }
+
+    fn uploaded(&self, upload: &Upload, etag: Option<&str>) {
+        if upload.put_uid == upload.uid {
+            self.record(|conn, id| db::record_destination_upload(conn, id, &upload.uid, etag));
+        }
+    }
+}
+
+// Pushes a destination's feed, split over the calendars its `routes` name;
+// see `calendar_routes`. Routed calendars that don't exist yet are created.
+pub async fn push_destination(
+    state: &AppState,
+    caldav_client: &Client,
+    ics_text: &str,
+    dest: &db::Destination,
+    known_etags: &HashMap<String, Option<String>>,
+) -> Result<ReverseSyncStats> {
+    let journal = Journal {
+        state,
+        destination_id: dest.id,
+    };
+    if dest.routes.is_empty() || extract_events(ics_text).events.is_empty() {
+        return push_events(
+            caldav_client,
+            ics_text,
+            &dest.into(),
+            known_etags,
+            false,
+            Some(journal),
+        )
+        .await;
+    }
+    let source_calendars = match dest.source_id {
+        Some(source_id) => db::event_calendars(&state.db.lock().unwrap(), source_id)?,
+        None => HashMap::new(),
+    };
+    let feeds = calendar_routes::split(
+        ics_text,
+        &dest.routes,
+        &dest.calendar_name,
+        &source_calendars,
+    );
+    let mut stats = ReverseSyncStats::default();
+    for (calendar, feed) in &feeds {
+        if *calendar != dest.calendar_name {
+            let base = calendar_base(&dest.caldav_url, calendar)?;
+            ensure_calendar(caldav_client, &base, calendar).await?;
+        }
+        let target = PushTarget {
+            calendar_name: calendar,
+            ..dest.into()
+        };
+        // A calendar nothing is routed to any more still loses its orphans.
+        let part = push_events(
+            caldav_client,
+            feed,
+            &target,
+            known_etags,
+            true,
+            Some(journal),
+        )
+        .await
+        .with_context(|| format!("Calendar '{}'", calendar))?;
+        stats.uploaded += part.uploaded;
+        stats.added += part.added;
+        stats.changed += part.changed;
+        stats.skipped += part.skipped;
+        stats.deleted += part.deleted;
+        stats.total += part.total;
+        stats.conflicts.extend(part.conflicts);
+        stats.failed.extend(part.failed);
+        // An event that moved calendars is still known to the old one; the
+        // ETag the new one learned wins.
+        for (uid, etag) in part.etags {
+            if !stats.etags.contains_key(&uid) || known_etags.get(&uid) != Some(&etag) {
+                stats.etags.insert(uid, etag);
+            }
+        }
+    }
+    Ok(stats)
+}
+
+// Creates the calendar collection at `base` unless it exists.
+async fn ensure_calendar(client: &Client, base: &str, name: &str) -> Result<()> {
+    let res = client
+        .request(reqwest::Method::from_bytes(b"PROPFIND").unwrap(), base)
+        .header("Depth", "0")
         .send()
         .await
-        .context("Failed to fetch ICS file")?;
-    let ics_text = ics_response
-        .text()
+        .with_context(|| format!("Failed to look up calendar '{}'", name))?;
+    if res.status() != reqwest::StatusCode::NOT_FOUND {
+        return Ok(());
+    }
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <c:mkcalendar xmlns:d=\"DAV:\" xmlns:c=\"urn:ietf:params:xml:ns:caldav\">\
+         <d:set><d:prop><d:displayname>{}</d:displayname></d:prop></d:set></c:mkcalendar>",
+        xml_escape(name)
+    );
+    let res = client
+        .request(reqwest::Method::from_bytes(b"MKCALENDAR").unwrap(), base)
+        .header("Content-Type", "application/xml; charset=utf-8")
+        .body(body)
+        .send()
         .await
-        .context("Failed to read ICS body")?;
+        .with_context(|| format!("Failed to create calendar '{}'", name))?;
+    anyhow::ensure!(
+        res.status().is_success(),
+        "Creating calendar '{}' returned {}",
+        name,
+        res.status()
+    );
+    tracing::info!("Created calendar {}", base);
+    Ok(())
+}
 
-    let extracted = extract_events(&ics_text);
+async fn push_events(
+    caldav_client: &Client,
+    ics_text: &str,
+    target: &PushTarget<'_>,
+    known_etags: &HashMap<String, Option<String>>,
+    allow_empty: bool,
+    journal: Option<Journal<'_>>,
+) -> Result<ReverseSyncStats> {
+    let templated = event_template::apply(ics_text, &target.template);
+    let templated = scheduling::apply(&templated, target.scheduling);
+    let ics_text = templated.as_str();
+    let stamped;
+    let ics_text = match &target.origin {
+        Some(ns) if target.namespace_uids => {
+            stamped = sync_origin::apply(ics_text, ns);
+            stamped.as_str()
+        }
+        _ => ics_text,
+    };
+    let mut extracted = extract_events(ics_text);
+    // `apply` already dropped them when stamping.
+    if let Some(ns) = target.origin.as_ref().filter(|_| !target.namespace_uids) {
+        extracted
+            .events
+            .retain(|_, vevents| !vevents.iter().any(|v| sync_origin::originated_from(v, ns)));
+    }
+    sync_progress::update(|p| p.events_fetched = extracted.events.len());
 
-    if extracted.events.is_empty() {
-        tracing::warn!("ICS feed at {} returned 0 events, skipping sync", ics_url);
+    if extracted.events.is_empty() && !allow_empty {
+        tracing::warn!("ICS feed returned 0 events, skipping sync");
         return Ok(ReverseSyncStats {
             uploaded: 0,
+            added: 0,
+            changed: 0,
             skipped: 0,
             deleted: 0,
             total: 0,
+            conflicts: Vec::new(),
+            etags: known_etags.clone(),
+            merged: 0,
+            combined_feed: None,
+            failed: Vec::new(),
         });
     }
+    let PushTarget {
+        caldav_url,
+        calendar_name,
+        sync_all,
+        keep_local,
+        conflict_policy,
+        ..
+    } = *target;
 
     let tz_block = extracted.vtimezones.join("");
     let all_remote_uids: HashSet<String> = extracted.events.keys().cloned().collect();
@@ -246,77 +866,219 @@ pub async fn run_reverse_sync(
             .collect()
     };
 
-    let auth = format!("{}:{}", username, password);
-    let auth_header = format!(
-        "Basic {}",
-        base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &auth)
-    );
-
-    let mut headers = header::HeaderMap::new();
-    headers.insert(
-        header::AUTHORIZATION,
-        header::HeaderValue::from_str(&auth_header)?,
-    );
-    let caldav_client = Client::builder().default_headers(headers).build()?;
-
-    let normalized_url = caldav_url.trim_end_matches('/');
-    let calendar_base = if normalized_url.ends_with(&format!("/{}", calendar_name)) {
-        format!("{}/", normalized_url)
-    } else {
-        format!("{}/{}/", normalized_url, calendar_name)
-    };
-
-    let existing = fetch_existing_events(&caldav_client, &calendar_base).await?;
+    let calendar_base = calendar_base(caldav_url, calendar_name)?;
+    let existing = fetch_existing_events(caldav_client, &calendar_base).await?;
     tracing::info!(
         "Fetched {} existing events from CalDAV for diff",
         existing.len()
     );
 
-    let mut uploaded = 0;
+    let mut added = 0;
+    let mut changed = 0;
     let mut skipped = 0;
-    let mut errors = 0;
+    let mut failed = Vec::new();
+    // The last PUT that got no answer, and whether any got one.
+    let mut unreachable = None;
+    let mut answered = false;
+    let mut conflicts = Vec::new();
+    // Events gone from both the feed and the calendar are forgotten; the rest
+    // keep their ETag until this sync learns a newer one.
+    let mut etags: HashMap<String, Option<String>> = known_etags
+        .iter()
+        .filter(|(uid, _)| all_remote_uids.contains(*uid) || existing.contains_key(*uid))
+        .map(|(uid, etag)| (uid.clone(), etag.clone()))
+        .collect();
 
+    let mut uploads = Vec::new();
     for (uid, vevent_blocks) in &events {
-        if let Some(existing_vevents) = existing.get(uid)
-            && events_equal(existing_vevents, vevent_blocks)
+        let remote = existing.get(uid);
+        if let Some(remote) = remote
+            && events_equal(&remote.vevents, vevent_blocks)
         {
+            etags.insert(uid.clone(), remote.etag.clone());
             skipped += 1;
             continue;
         }
 
-        let vevent_block = vevent_blocks.join("");
+        let mut put_uid = uid.clone();
+        let mut vevent_block = vevent_blocks.join("");
+        if let Some(remote) = remote {
+            let known = known_etags.get(uid).cloned().flatten();
+            if edited_in_calendar(remote, vevent_blocks, known.as_deref()) {
+                tracing::warn!(
+                    "Event {} was edited in the calendar; applying '{}' policy",
+                    uid,
+                    conflict_policy
+                );
+                conflicts.push(db::EventConflict {
+                    uid: uid.clone(),
+                    summary: ics_component::value(&vevent_blocks[0], "SUMMARY")
+                        .map(|s| ics_text::unescape_text(&s)),
+                    resolution: conflict_policy.to_string(),
+                    detected_at: None,
+                });
+                match conflict_policy {
+                    "skip" => {
+                        skipped += 1;
+                        continue;
+                    }
+                    "duplicate" => {
+                        put_uid = format!("{}{}", uid, DUPLICATE_SUFFIX);
+                        vevent_block = vevent_blocks
+                            .iter()
+                            .map(|v| with_uid(v, &put_uid))
+                            .collect();
+                    }
+                    _ => {}
+                }
+            }
+        }
+
         let wrapped = format!(
             "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//CalDAV/ICS Sync//EN\r\n{}{}END:VCALENDAR\r\n",
             tz_block, vevent_block
         );
+        uploads.push(Upload {
+            remote: existing.get(&put_uid),
+            uid: uid.clone(),
+            put_uid,
+            body: wrapped,
+        });
+    }
 
-        let event_url = format!("{}{}.ics", calendar_base, uid);
+    let mut throttle = Throttle::new(target.pace);
+    // Batch-capable servers get the uploads a batch at a time; whatever a
+    // batch rejects falls back to one PUT per event.
+    let mut stored: Vec<(&Upload, Option<String>)> = Vec::new();
+    let mut pending: Vec<&Upload> = uploads.iter().collect();
+    if uploads.len() > 1 && batch_upload::supported(caldav_client, &calendar_base).await {
+        pending.clear();
+        for chunk in uploads.chunks(batch_upload::BATCH_SIZE) {
+            let items: Vec<(String, &str)> = chunk
+                .iter()
+                .map(|u| {
+                    let name = dav_urls::encode_segment(&format!("{}.ics", u.put_uid));
+                    (name, u.body.as_str())
+                })
+                .collect();
+            match batch_upload::upload(caldav_client, &mut throttle, &calendar_base, &items).await {
+                Ok(mut done) => {
+                    sync_progress::update(|p| p.events_uploaded += done.len());
+                    for (upload, (name, _)) in chunk.iter().zip(&items) {
+                        match done.remove(name) {
+                            Some(etag) => {
+                                if let Some(journal) = journal {
+                                    journal.uploaded(upload, etag.as_deref());
+                                }
+                                stored.push((upload, etag));
+                            }
+                            None => pending.push(upload),
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("{}; uploading events one by one", e);
+                    pending.extend(chunk);
+                }
+            }
+        }
+    }
 
-        match caldav_client
-            .put(&event_url)
-            .header("Content-Type", "text/calendar; charset=utf-8")
-            .body(wrapped)
-            .send()
-            .await
+    for upload in pending {
+        // Updates go to wherever the calendar keeps the event.
+        let event_url = match upload.remote.and_then(|r| r.href.as_deref()) {
+            Some(href) => dav_urls::resolve_href(&calendar_base, href)?,
+            None => dav_urls::child_url(&calendar_base, &format!("{}.ics", upload.put_uid))?,
+        };
+        match put_event(
+            caldav_client,
+            &mut throttle,
+            &event_url,
+            &upload.body,
+            upload.remote,
+        )
+        .await
         {
             Ok(res) if res.status().is_success() => {
-                uploaded += 1;
+                let etag = res
+                    .headers()
+                    .get(header::ETAG)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_owned);
+                if let Some(journal) = journal {
+                    journal.uploaded(upload, etag.as_deref());
+                }
+                stored.push((upload, etag));
+                sync_progress::update(|p| p.events_uploaded += 1);
             }
             Ok(res) => {
+                answered = true;
                 tracing::warn!("PUT {} returned {}", event_url, res.status());
-                errors += 1;
+                failed.push(failure(
+                    &upload.uid,
+                    format!("PUT returned {}", res.status()),
+                ));
             }
             Err(e) => {
                 tracing::error!("PUT {} failed: {}", event_url, e);
-                errors += 1;
+                failed.push(failure(&upload.uid, format!("PUT failed: {}", e)));
+                unreachable = Some(e);
             }
         }
     }
 
-    if errors > 0 {
-        anyhow::bail!("Uploaded {} events but {} failed", uploaded, errors);
+    for (upload, etag) in stored {
+        match existing.contains_key(&upload.put_uid) {
+            true => changed += 1,
+            false => added += 1,
+        }
+        // A duplicate leaves the edited original, and its recorded ETag, in
+        // place; the conflict stays until someone resolves it.
+        if upload.put_uid == upload.uid {
+            etags.insert(upload.uid.clone(), etag);
+        }
     }
 
+    let uploaded = added + changed;
+    // Events the server turned down are retried next run like any partial
+    // failure; only a server that answered none of them fails the run.
+    if uploaded == 0
+        && !answered
+        && let Some(e) = unreachable
+    {
+        return Err(anyhow::Error::new(e).context("CalDAV server unreachable"));
+    }
+    if !failed.is_empty() {
+        tracing::warn!(
+            "{} of {} uploads failed; retrying them next sync",
+            failed.len(),
+            uploads.len()
+        );
+    }
+
+    let copies_of_feed_events = |uid: &String| {
+        uid.strip_suffix(DUPLICATE_SUFFIX)
+            .is_some_and(|original| all_remote_uids.contains(original))
+    };
+    // Calendar events reverse sync never pushed were created there.
+    let mut calendar_created: Vec<&String> = match &target.origin {
+        Some(ns) if target.bidirectional => existing
+            .iter()
+            .filter(|(uid, remote)| {
+                !all_remote_uids.contains(*uid)
+                    && !known_etags.contains_key(*uid)
+                    && !copies_of_feed_events(uid)
+                    && !remote
+                        .vevents
+                        .iter()
+                        .any(|v| sync_origin::originated_from(v, ns))
+            })
+            .map(|(uid, _)| uid)
+            .collect(),
+        _ => Vec::new(),
+    };
+    calendar_created.sort();
+
     let mut deleted = 0;
 
     if !keep_local {
@@ -325,23 +1087,39 @@ pub async fn run_reverse_sync(
         } else {
             existing
                 .iter()
-                .filter(|(_, vevents)| vevents.iter().any(|v| is_event_in_future(v)))
+                .filter(|(_, remote)| remote.vevents.iter().any(|v| is_event_in_future(v)))
                 .map(|(uid, _)| uid.clone())
                 .collect()
         };
 
-        for uid in deletion_candidates.difference(&all_remote_uids) {
-            let event_url = format!("{}{}.ics", calendar_base, uid);
-            match caldav_client.delete(&event_url).send().await {
+        let mut orphans: Vec<&String> = deletion_candidates
+            .difference(&all_remote_uids)
+            .filter(|uid| !copies_of_feed_events(uid) && !calendar_created.contains(uid))
+            .collect();
+        orphans.sort();
+        if let Some(journal) = journal
+            && !orphans.is_empty()
+        {
+            journal.record(|conn, id| db::mark_destination_deletes(conn, id, &orphans));
+        }
+        for uid in orphans {
+            let event_url = dav_urls::child_url(&calendar_base, &format!("{}.ics", uid))?;
+            match throttle.send(|| caldav_client.delete(&event_url)).await {
                 Ok(res) if res.status().is_success() || res.status().as_u16() == 404 => {
+                    etags.remove(uid);
                     deleted += 1;
+                    if let Some(journal) = journal {
+                        journal.record(|conn, id| db::forget_destination_event(conn, id, uid));
+                    }
                     tracing::info!("Deleted orphan event: {}", uid);
                 }
                 Ok(res) => {
                     tracing::warn!("DELETE {} returned {}", event_url, res.status());
+                    failed.push(failure(uid, format!("DELETE returned {}", res.status())));
                 }
                 Err(e) => {
                     tracing::error!("DELETE {} failed: {}", event_url, e);
+                    failed.push(failure(uid, format!("DELETE failed: {}", e)));
                 }
             }
         }
@@ -349,12 +1127,83 @@ pub async fn run_reverse_sync(
 
     Ok(ReverseSyncStats {
         uploaded,
+        added,
+        changed,
         skipped,
         deleted,
         total: events.len(),
+        conflicts,
+        etags,
+        merged: calendar_created.len(),
+        combined_feed: target
+            .origin
+            .as_ref()
+            .filter(|_| target.bidirectional)
+            .map(|ns| {
+                let merged: Vec<&RemoteEvent> =
+                    calendar_created.iter().map(|uid| &existing[*uid]).collect();
+                combine_feed(ics_text, &merged, ns)
+            }),
+        failed,
     })
 }
 
+// The last sync error of a run that only partly succeeded.
+pub fn failure_summary(failed: &[db::FailedEvent]) -> String {
+    let listed: Vec<String> = failed
+        .iter()
+        .take(5)
+        .map(|f| format!("{} ({})", f.uid, f.error))
+        .collect();
+    let more = match failed.len() > listed.len() {
+        true => format!(" and {} more", failed.len() - listed.len()),
+        false => String::new(),
+    };
+    format!(
+        "{} events failed and will be retried: {}{}",
+        failed.len(),
+        listed.join(", "),
+        more
+    )
+}
+
+fn failure(uid: &str, error: String) -> db::FailedEvent {
+    db::FailedEvent {
+        uid: uid.to_string(),
+        error,
+        attempts: 1,
+    }
+}
+
+// Appends the calendar's own events to the feed, stamped with the
+// destination's namespace, and any time zones the feed doesn't already define.
+fn combine_feed(ics_text: &str, events: &[&RemoteEvent], namespace: &str) -> String {
+    let mut defined: HashSet<String> = extract_events(ics_text)
+        .vtimezones
+        .iter()
+        .filter_map(|tz| ics_component::value(tz, "TZID"))
+        .collect();
+    let mut extra = String::new();
+    for event in events {
+        for tz in &event.vtimezones {
+            if let Some(tzid) = ics_component::value(tz, "TZID")
+                && defined.insert(tzid)
+            {
+                extra.push_str(tz);
+            }
+        }
+    }
+    for event in events {
+        for vevent in &event.vevents {
+            extra.push_str(&sync_origin::stamp(vevent, namespace));
+        }
+    }
+    match ics_text.rfind("END:VCALENDAR") {
+        Some(end) => format!("{}{}{}", &ics_text[..end], extra, &ics_text[end..]),
+        None => format!("{}{}", ics_text, extra),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -555,6 +1404,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn extract_events_keeps_alarms_and_vendor_properties() {
+        let vevent = "BEGIN:VEVENT\r\n\
+            UID:event@test\r\n\
+            SUMMARY:A title long enough to have been folded by the server that\r\n  sent it\r\n\
+            X-MOZ-GENERATION:3\r\n\
+            BEGIN:VALARM\r\n\
+            UID:alarm@test\r\n\
+            ACTION:DISPLAY\r\n\
+            TRIGGER:-PT15M\r\n\
+            END:VALARM\r\n\
+            END:VEVENT\r\n";
+        let extracted = extract_events(&format!("BEGIN:VCALENDAR\r\n{}END:VCALENDAR\r\n", vevent));
+        assert_eq!(extracted.events["event@test"], [vevent]);
+        let renamed = with_uid(vevent, "other@test");
+        assert_eq!(renamed, vevent.replace("UID:event@test", "UID:other@test"));
+    }
+
     #[test]
     fn extract_events_captures_vtimezone_blocks() {
         let ics = "BEGIN:VCALENDAR\r\n\
@@ -582,4 +1449,31 @@ mod tests {
         assert!(extracted.vtimezones[0].starts_with("BEGIN:VTIMEZONE"));
         assert!(extracted.vtimezones[0].contains("END:VTIMEZONE"));
     }
+
+    #[test]
+    fn webcal_urls_become_https_and_credentials_stay_on_their_host() {
+        assert_eq!(
+            feed_url(" WEBCAL://school.example/cal.ics"),
+            "https://school.example/cal.ics"
+        );
+        assert_eq!(
+            feed_url("webcals://school.example/cal.ics"),
+            "https://school.example/cal.ics"
+        );
+        assert_eq!(feed_url("http://a.example/x"), "http://a.example/x");
+        let keeps = |from: &str, to: &str| {
+            keeps_credentials(
+                &reqwest::Url::parse(from).unwrap(),
+                &reqwest::Url::parse(to).unwrap(),
+            )
+        };
+        let from = "http://school.example/cal.ics";
+        assert!(keeps(from, "https://school.example/new.ics"));
+        assert!(!keeps(from, "https://cdn.example/cal.ics"));
+        assert!(!keeps(from, "http://school.example:8080/cal.ics"));
+        assert!(!keeps(
+            "https://school.example/a",
+            "http://school.example/a"
+        ));
+    }
 }
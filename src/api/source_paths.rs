@@ -1,4 +1,5 @@
 use crate::api::AppState;
+use crate::api::error::{ApiError, ApiJson};
 use crate::db;
 use axum::{
     Json, Router,
@@ -36,15 +37,7 @@ pub async fn list_source_paths(
     let db = state.db.lock().unwrap();
     match db::list_source_paths(&db, source_id) {
         Ok(paths) => (StatusCode::OK, Json(SourcePathListResponse { paths })).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(SourcePathResponse {
-                status: "error".into(),
-                message: e.to_string(),
-                path: None,
-            }),
-        )
-            .into_response(),
+        Err(e) => ApiError::from(e).into_response(),
     }
 }
 
@@ -58,7 +51,7 @@ pub async fn list_source_paths(
 pub async fn create_source_path(
     State(state): State<AppState>,
     Path(source_id): Path<i64>,
-    Json(body): Json<db::CreateSourcePath>,
+    ApiJson(body): ApiJson<db::CreateSourcePath>,
 ) -> impl IntoResponse {
     let db = state.db.lock().unwrap();
     match db::create_source_path(&db, source_id, &body) {
@@ -74,15 +67,7 @@ pub async fn create_source_path(
             )
                 .into_response()
         }
-        Err(e) => (
-            StatusCode::BAD_REQUEST,
-            Json(SourcePathResponse {
-                status: "error".into(),
-                message: e.to_string(),
-                path: None,
-            }),
-        )
-            .into_response(),
+        Err(e) => ApiError::bad_request(e.to_string()).into_response(),
     }
 }
 
@@ -99,20 +84,12 @@ pub async fn create_source_path(
 pub async fn update_source_path(
     State(state): State<AppState>,
     Path((source_id, path_id)): Path<(i64, i64)>,
-    Json(body): Json<db::UpdateSourcePath>,
+    ApiJson(body): ApiJson<db::UpdateSourcePath>,
 ) -> impl IntoResponse {
     let db = state.db.lock().unwrap();
     match db::get_source_path(&db, path_id) {
         Ok(Some(sp)) if sp.source_id != source_id => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(SourcePathResponse {
-                    status: "error".into(),
-                    message: "Path not found".into(),
-                    path: None,
-                }),
-            )
-                .into_response();
+            return ApiError::not_found("Path not found").into_response();
         }
         _ => {}
     }
@@ -129,24 +106,8 @@ pub async fn update_source_path(
             )
                 .into_response()
         }
-        Ok(false) => (
-            StatusCode::NOT_FOUND,
-            Json(SourcePathResponse {
-                status: "error".into(),
-                message: "Path not found".into(),
-                path: None,
-            }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::BAD_REQUEST,
-            Json(SourcePathResponse {
-                status: "error".into(),
-                message: e.to_string(),
-                path: None,
-            }),
-        )
-            .into_response(),
+        Ok(false) => ApiError::not_found("Path not found").into_response(),
+        Err(e) => ApiError::bad_request(e.to_string()).into_response(),
     }
 }
 
@@ -166,15 +127,7 @@ pub async fn delete_source_path(
     let db = state.db.lock().unwrap();
     match db::get_source_path(&db, path_id) {
         Ok(Some(sp)) if sp.source_id != source_id => {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(SourcePathResponse {
-                    status: "error".into(),
-                    message: "Path not found".into(),
-                    path: None,
-                }),
-            )
-                .into_response();
+            return ApiError::not_found("Path not found").into_response();
         }
         _ => {}
     }
@@ -188,24 +141,8 @@ pub async fn delete_source_path(
             }),
         )
             .into_response(),
-        Ok(false) => (
-            StatusCode::NOT_FOUND,
-            Json(SourcePathResponse {
-                status: "error".into(),
-                message: "Path not found".into(),
-                path: None,
-            }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(SourcePathResponse {
-                status: "error".into(),
-                message: e.to_string(),
-                path: None,
-            }),
-        )
-            .into_response(),
+        Ok(false) => ApiError::not_found("Path not found").into_response(),
+        Err(e) => ApiError::from(e).into_response(),
     }
 }
 
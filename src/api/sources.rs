@@ -1,27 +1,147 @@
-use crate::api::AppState;
+use crate::api::error::{ApiError, ApiJson};
+use crate::api::sync::SyncDiff;
+use crate::api::{AppState, owner_scope};
 use crate::auto_sync::{self, AutoSyncKey};
 use crate::db;
+use crate::feed_urls::FeedUrls;
+use crate::http_clients::CustomHeaders;
+use crate::ics_repair::RepairReport;
+use crate::redact;
+use crate::server::auth::CurrentUser;
+use crate::sync_progress::{self, SyncProgress};
+use crate::validation;
 use axum::{
-    Json, Router,
-    extract::{Path, State},
+    Extension, Json, Router,
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
     routing::{get, post, put},
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+// A source as the API returns it: no password, and URL credentials masked.
+#[derive(Serialize, ToSchema)]
+#[schema(as = Source)]
+pub struct SourceView {
+    id: i64,
+    name: String,
+    caldav_url: String,
+    username: String,
+    ics_path: String,
+    sync_interval_secs: i64,
+    last_synced: Option<String>,
+    last_sync_status: Option<String>,
+    last_sync_error: Option<String>,
+    created_at: String,
+    public_ics: bool,
+    public_ics_path: Option<String>,
+    attachment_mode: String,
+    source_type: String,
+    proxy_token: Option<String>,
+    owner_id: Option<i64>,
+    archive_after_months: Option<i64>,
+    namespace_uids: bool,
+    sync_on_startup: String,
+    fetch_mode: String,
+    user_agent: String,
+    // Values are masked.
+    custom_headers: CustomHeaders,
+    sync_window: String,
+    working_hours: String,
+    holiday_region: String,
+    repair_rules: String,
+    time_shift_zone: String,
+    time_shift_mode: String,
+    max_events: Option<i64>,
+    max_feed_bytes: Option<i64>,
+    feed_limit_policy: String,
+    // With the `split` policy: the feed's parts, as URLs when PUBLIC_BASE_URL
+    // is set and as paths otherwise.
+    feed_parts: Vec<String>,
+    enabled: bool,
+    // Filled in when PUBLIC_BASE_URL is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    feed_urls: Option<FeedUrls>,
+}
+
+pub(crate) fn source_view(state: &AppState, s: db::Source) -> SourceView {
+    let feed_urls = state.public_base_url.as_deref().and_then(|base| {
+        crate::feed_urls::for_feed(
+            base,
+            &s.ics_path,
+            s.public_ics_path.as_deref().filter(|_| s.public_ics),
+        )
+    });
+    let feed_parts = match state.public_base_url.as_deref() {
+        Some(base) => crate::feed_urls::parts(base, &s.ics_path, s.feed_parts),
+        None => crate::feed_urls::parts("", &s.ics_path, s.feed_parts)
+            .into_iter()
+            .map(|path| format!("{}{}", state.base_path, path))
+            .collect(),
+    };
+    SourceView {
+        id: s.id,
+        name: s.name,
+        caldav_url: redact::url(&s.caldav_url).into_owned(),
+        username: s.username,
+        ics_path: s.ics_path,
+        sync_interval_secs: s.sync_interval_secs,
+        last_synced: s.last_synced,
+        last_sync_status: auto_sync::sync_status(
+            state,
+            &AutoSyncKey::Source(s.id),
+            s.last_sync_status,
+        ),
+        last_sync_error: s.last_sync_error,
+        created_at: s.created_at,
+        public_ics: s.public_ics,
+        public_ics_path: s.public_ics_path,
+        attachment_mode: s.attachment_mode,
+        source_type: s.source_type,
+        proxy_token: s.proxy_token,
+        owner_id: s.owner_id,
+        archive_after_months: s.archive_after_months,
+        namespace_uids: s.namespace_uids,
+        sync_on_startup: s.sync_on_startup,
+        fetch_mode: s.fetch_mode,
+        user_agent: s.user_agent,
+        custom_headers: redact::headers(&s.custom_headers),
+        sync_window: s.sync_window,
+        working_hours: s.working_hours,
+        holiday_region: s.holiday_region,
+        repair_rules: s.repair_rules,
+        time_shift_zone: s.time_shift_zone,
+        time_shift_mode: s.time_shift_mode,
+        max_events: s.max_events,
+        max_feed_bytes: s.max_feed_bytes,
+        feed_limit_policy: s.feed_limit_policy,
+        feed_parts,
+        enabled: s.enabled,
+        feed_urls,
+    }
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct SourceResponse {
     status: String,
     message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    source: Option<db::Source>,
+    source: Option<SourceView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_sync_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    syncing: Option<bool>,
+}
+
+// Unset when auto-sync is disabled or a sync is currently running.
+fn next_sync_at(state: &AppState, id: i64) -> Option<String> {
+    auto_sync::next_run(&state.sync_tasks, &AutoSyncKey::Source(id)).map(|t| t.to_rfc3339())
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct SourceListResponse {
-    sources: Vec<db::Source>,
+    sources: Vec<SourceView>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -30,49 +150,123 @@ pub struct SyncResult {
     message: String,
     events: usize,
     calendars: usize,
+    added: usize,
+    removed: usize,
+    changed: usize,
+    total: usize,
+    unchanged: bool,
+    // Fixes applied to broken upstream events; see the source's `repair_rules`.
+    repairs: RepairReport,
+    // Feed limits that kicked in; see the source's `max_events`.
+    warnings: Vec<String>,
+}
+
+impl SyncResult {
+    pub(crate) fn success(events: usize, calendars: usize, diff: &SyncDiff) -> Self {
+        let mut message = if diff.unchanged {
+            format!(
+                "No changes: {} events from {} calendars, feed left as is",
+                events, calendars
+            )
+        } else {
+            format!(
+                "Synchronized {} events from {} calendars: {} added, {} removed, {} changed ({} total)",
+                events, calendars, diff.added, diff.removed, diff.changed, diff.total
+            )
+        };
+        if diff.repairs.total > 0 {
+            message.push_str(&format!("; {} repairs applied", diff.repairs.total));
+        }
+        for warning in &diff.warnings {
+            message.push_str(&format!("; {}", warning));
+        }
+        Self {
+            status: "success".into(),
+            message,
+            events,
+            calendars,
+            added: diff.added,
+            removed: diff.removed,
+            changed: diff.changed,
+            total: diff.total,
+            unchanged: diff.unchanged,
+            repairs: diff.repairs.clone(),
+            warnings: diff.warnings.clone(),
+        }
+    }
 }
 
 #[utoipa::path(get, path = "/api/sources", responses((status = 200, body = SourceListResponse)))]
-async fn list_sources(State(state): State<AppState>) -> impl IntoResponse {
+async fn list_sources(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+) -> impl IntoResponse {
     let db = state.db.lock().unwrap();
-    match db::list_sources(&db) {
-        Ok(sources) => (StatusCode::OK, Json(SourceListResponse { sources })).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(SourceResponse {
-                status: "error".into(),
-                message: e.to_string(),
-                source: None,
-            }),
-        )
-            .into_response(),
+    let result = match owner_scope(&user) {
+        Some(owner_id) => db::list_sources_for_owner(&db, owner_id),
+        None => db::list_sources(&db),
+    };
+    match result {
+        Ok(sources) => {
+            let sources = sources
+                .into_iter()
+                .map(|s| source_view(&state, s))
+                .collect();
+            (StatusCode::OK, Json(SourceListResponse { sources })).into_response()
+        }
+        Err(e) => ApiError::from(e).into_response(),
     }
 }
 
-#[utoipa::path(post, path = "/api/sources", request_body = db::CreateSource, responses((status = 201, body = SourceResponse)))]
+#[derive(Deserialize)]
+struct CreateSourceParams {
+    #[serde(default)]
+    force: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/sources",
+    request_body = db::CreateSource,
+    params(("force" = Option<bool>, Query, description = "Create the source even if the same URL and username is already synced")),
+    responses(
+        (status = 201, body = SourceResponse),
+        (status = 409, description = "A source with the same URL and username exists; `details.source` is the existing one", body = ApiError),
+        (status = 422, description = "Invalid fields; `details.fields` maps each field to its problem", body = ApiError)
+    )
+)]
 async fn create_source(
     State(state): State<AppState>,
-    Json(body): Json<db::CreateSource>,
+    user: Option<Extension<CurrentUser>>,
+    Query(params): Query<CreateSourceParams>,
+    ApiJson(mut body): ApiJson<db::CreateSource>,
 ) -> impl IntoResponse {
+    if let Some(owner_id) = owner_scope(&user) {
+        body.owner_id = Some(owner_id);
+    }
     let (id, source) = {
         let db = state.db.lock().unwrap();
-        match db::create_source(&db, &body) {
-            Ok(id) => {
-                let source = db::get_source(&db, id).ok().flatten();
-                (id, source)
-            }
-            Err(e) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(SourceResponse {
-                        status: "error".into(),
-                        message: e.to_string(),
-                        source: None,
-                    }),
-                )
-                    .into_response();
+        if !params.force && body.source_type.as_deref() != Some("upload") {
+            let duplicate =
+                db::find_duplicate_source(&db, &body.caldav_url, &body.username, body.owner_id);
+            if let Ok(Some(existing)) = duplicate {
+                return ApiError::conflict(format!(
+                    "Source {} already syncs this URL as this user; retry with ?force=true to create another",
+                    existing.id
+                ))
+                .with_details(serde_json::json!({
+                    "source": source_view(&state, existing),
+                }))
+                .into_response();
             }
         }
+        if let Err(e) = validation::check_create_source(&db, &body) {
+            return ApiError::from(e).into_response();
+        }
+        match db::create_source(&db, &body) {
+            Ok(id) => (id, db::get_source(&db, id).ok().flatten()),
+            Err(e) => return ApiError::bad_request(e.to_string()).into_response(),
+        }
     };
 
     if let Some(ref s) = source {
@@ -84,43 +278,49 @@ async fn create_source(
         Json(SourceResponse {
             status: "success".into(),
             message: format!("Source created with id {}", id),
-            source,
+            source: source.map(|s| source_view(&state, s)),
+            next_sync_at: next_sync_at(&state, id),
+            syncing: None,
         }),
     )
         .into_response()
 }
 
-#[utoipa::path(put, path = "/api/sources/{id}", request_body = db::UpdateSource, responses((status = 200, body = SourceResponse)))]
+#[utoipa::path(
+    put,
+    path = "/api/sources/{id}",
+    request_body = db::UpdateSource,
+    responses(
+        (status = 200, body = SourceResponse),
+        (status = 422, description = "Invalid fields; `details.fields` maps each field to its problem", body = ApiError)
+    )
+)]
 async fn update_source(
     State(state): State<AppState>,
     Path(id): Path<i64>,
-    Json(body): Json<db::UpdateSource>,
+    user: Option<Extension<CurrentUser>>,
+    ApiJson(mut body): ApiJson<db::UpdateSource>,
 ) -> impl IntoResponse {
+    if owner_scope(&user).is_some() {
+        body.owner_id = None;
+    }
     let source = {
         let db = state.db.lock().unwrap();
+        let existing = match db::get_source(&db, id) {
+            Ok(Some(s)) => s,
+            Ok(None) => return ApiError::not_found("Source not found").into_response(),
+            Err(e) => return ApiError::from(e).into_response(),
+        };
+        if let Err(e) = validation::check_update_source(&db, &existing, &body) {
+            return ApiError::from(e).into_response();
+        }
         match db::update_source(&db, id, &body) {
             Ok(true) => db::get_source(&db, id).ok().flatten(),
             Ok(false) => {
-                return (
-                    StatusCode::NOT_FOUND,
-                    Json(SourceResponse {
-                        status: "error".into(),
-                        message: "Source not found".into(),
-                        source: None,
-                    }),
-                )
-                    .into_response();
+                return ApiError::not_found("Source not found").into_response();
             }
             Err(e) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(SourceResponse {
-                        status: "error".into(),
-                        message: e.to_string(),
-                        source: None,
-                    }),
-                )
-                    .into_response();
+                return ApiError::bad_request(e.to_string()).into_response();
             }
         }
     };
@@ -134,7 +334,9 @@ async fn update_source(
         Json(SourceResponse {
             status: "success".into(),
             message: "Source updated".into(),
-            source,
+            source: source.map(|s| source_view(&state, s)),
+            next_sync_at: next_sync_at(&state, id),
+            syncing: None,
         }),
     )
         .into_response()
@@ -153,146 +355,240 @@ async fn delete_source_handler(
     match result {
         Ok(true) => {
             auto_sync::cancel(&state.sync_tasks, &AutoSyncKey::Source(id));
+            auto_sync::cancel_running(&state.running_syncs, id);
             (
                 StatusCode::OK,
                 Json(SourceResponse {
                     status: "success".into(),
                     message: "Source deleted".into(),
                     source: None,
+                    next_sync_at: None,
+                    syncing: None,
                 }),
             )
                 .into_response()
         }
-        Ok(false) => (
-            StatusCode::NOT_FOUND,
-            Json(SourceResponse {
-                status: "error".into(),
-                message: "Source not found".into(),
-                source: None,
-            }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(SourceResponse {
-                status: "error".into(),
-                message: e.to_string(),
-                source: None,
-            }),
-        )
-            .into_response(),
+        Ok(false) => ApiError::not_found("Source not found").into_response(),
+        Err(e) => ApiError::from(e).into_response(),
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/sources/{id}/clone",
+    request_body(content = Option<db::CloneSource>, description = "Overrides for the copy; by default the name gets a \" (copy)\" suffix and the feed paths a fresh random token"),
+    responses((status = 201, body = SourceResponse))
+)]
+async fn clone_source(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    body: Option<ApiJson<db::CloneSource>>,
+) -> impl IntoResponse {
+    let opts = body.map(|ApiJson(b)| b).unwrap_or_default();
+    let (new_id, source) = {
+        let db = state.db.lock().unwrap();
+        match db::clone_source(&db, id, &opts) {
+            Ok(Some(new_id)) => (new_id, db::get_source(&db, new_id).ok().flatten()),
+            Ok(None) => return ApiError::not_found("Source not found").into_response(),
+            Err(e) => return ApiError::bad_request(e.to_string()).into_response(),
+        }
+    };
+
+    if let Some(ref s) = source {
+        auto_sync::register_source(&state.sync_tasks, &state, s);
+    }
+
+    (
+        StatusCode::CREATED,
+        Json(SourceResponse {
+            status: "success".into(),
+            message: format!("Source {} cloned as {}", id, new_id),
+            source: source.map(|s| source_view(&state, s)),
+            next_sync_at: next_sync_at(&state, new_id),
+            syncing: None,
+        }),
+    )
+        .into_response()
+}
+
 #[utoipa::path(post, path = "/api/sources/{id}/sync", responses((status = 200, body = SyncResult)))]
 async fn sync_source(State(state): State<AppState>, Path(id): Path<i64>) -> impl IntoResponse {
-    let (caldav_url, username, password) = {
+    let source = {
         let db = state.db.lock().unwrap();
         match db::get_source(&db, id) {
-            Ok(Some(s)) => (s.caldav_url, s.username, s.password),
-            Ok(None) => {
-                return (
-                    StatusCode::NOT_FOUND,
-                    Json(SyncResult {
-                        status: "error".into(),
-                        message: "Source not found".into(),
-                        events: 0,
-                        calendars: 0,
-                    }),
+            Ok(Some(s)) if s.source_type == "upload" => {
+                return ApiError::bad_request(
+                    "Upload sources are updated via /api/sources/{id}/upload",
                 )
-                    .into_response();
+                .into_response();
+            }
+            Ok(Some(s)) => s,
+            Ok(None) => {
+                return ApiError::not_found("Source not found").into_response();
             }
             Err(e) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(SyncResult {
-                        status: "error".into(),
-                        message: e.to_string(),
-                        events: 0,
-                        calendars: 0,
-                    }),
-                )
-                    .into_response();
+                return ApiError::from(e).into_response();
             }
         }
     };
 
-    match crate::api::sync::run_sync(&caldav_url, &username, &password).await {
-        Ok((events, calendars, ics_data)) => {
+    let started = std::time::Instant::now();
+    let run = sync_progress::track(
+        &state.sync_progress,
+        AutoSyncKey::Source(id),
+        crate::api::sync::fetch_source(&state, &source),
+    );
+    match auto_sync::cancellable(&state.running_syncs, id, run).await {
+        Ok(fetched) => {
+            let calendars = fetched.len();
             let db = state.db.lock().unwrap();
-            if let Err(e) = db::save_ics_data(&db, id, &ics_data) {
-                tracing::error!("Failed to save ICS data: {}", e);
-            }
-            if let Err(e) = db::update_last_synced(&db, id) {
-                tracing::error!("Failed to update last_synced: {}", e);
-            }
-            let _ = db::update_sync_status(&db, id, "ok", None);
+            let (events, diff) = match crate::api::sync::store_calendars(&db, &source, &fetched) {
+                Ok(stored) => stored,
+                Err(e) => {
+                    tracing::error!("Failed to save ICS data: {}", e);
+                    let _ = db::update_sync_status(&db, id, "error", Some(&e.to_string()));
+                    return ApiError::unprocessable(e.to_string()).into_response();
+                }
+            };
+            let _ = db::update_sync_duration(&db, id, started.elapsed().as_millis() as i64);
             (
                 StatusCode::OK,
-                Json(SyncResult {
-                    status: "success".into(),
-                    message: format!(
-                        "Synchronized {} events from {} calendars",
-                        events, calendars
-                    ),
-                    events,
-                    calendars,
-                }),
+                Json(SyncResult::success(events, calendars, &diff)),
             )
                 .into_response()
         }
+        Err(e) if e.is::<auto_sync::SyncAlreadyRunning>() => ApiError::from(e).into_response(),
+        Err(e) if e.is::<auto_sync::SyncCancelled>() => {
+            let db = state.db.lock().unwrap();
+            let _ = auto_sync::mark_cancelled(&db, id);
+            ApiError::from(e).into_response()
+        }
         Err(e) => {
             tracing::error!("Sync error for source {}: {}", id, e);
             let db = state.db.lock().unwrap();
             let _ = db::update_sync_status(&db, id, "error", Some(&e.to_string()));
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(SyncResult {
-                    status: "error".into(),
-                    message: e.to_string(),
-                    events: 0,
-                    calendars: 0,
-                }),
-            )
-                .into_response()
+            ApiError::from(e).into_response()
         }
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/sources/{id}/sync/cancel",
+    responses(
+        (status = 200, body = SourceResponse),
+        (status = 409, description = "No sync is running", body = ApiError)
+    )
+)]
+async fn cancel_sync(State(state): State<AppState>, Path(id): Path<i64>) -> impl IntoResponse {
+    if !auto_sync::cancel_running(&state.running_syncs, id) {
+        return ApiError::conflict(format!("No sync is running for source {}", id)).into_response();
+    }
+    (
+        StatusCode::OK,
+        Json(SourceResponse {
+            status: "success".into(),
+            message: format!("Cancelling sync for source {}", id),
+            source: None,
+            next_sync_at: None,
+            syncing: None,
+        }),
+    )
+        .into_response()
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SyncProgressResponse {
+    status: String,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    progress: Option<SyncProgress>,
+}
+
+pub(crate) fn progress_response(state: &AppState, key: AutoSyncKey) -> axum::response::Response {
+    match sync_progress::get(&state.sync_progress, &key) {
+        Some(progress) => (
+            StatusCode::OK,
+            Json(SyncProgressResponse {
+                status: "success".into(),
+                message: match progress.running {
+                    true => "Sync in progress".into(),
+                    false => "Last sync finished".into(),
+                },
+                progress: Some(progress),
+            }),
+        )
+            .into_response(),
+        None => ApiError::not_found("No sync has run since the server started").into_response(),
+    }
+}
+
+#[utoipa::path(get, path = "/api/sources/{id}/sync/progress", responses((status = 200, body = SyncProgressResponse)))]
+async fn sync_progress_handler(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    progress_response(&state, AutoSyncKey::Source(id))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SyncRunListResponse {
+    pub runs: Vec<db::SyncRun>,
+}
+
+pub(crate) fn runs_response(state: &AppState, key: AutoSyncKey) -> axum::response::Response {
+    let db = state.db.lock().unwrap();
+    let found = match key {
+        AutoSyncKey::Source(id) => db::get_source(&db, id).map(|s| s.is_some()),
+        AutoSyncKey::Destination(id) => db::get_destination(&db, id).map(|d| d.is_some()),
+    };
+    match found {
+        Ok(true) => {}
+        Ok(false) => {
+            return ApiError::not_found(match key {
+                AutoSyncKey::Source(_) => "Source not found",
+                AutoSyncKey::Destination(_) => "Destination not found",
+            })
+            .into_response();
+        }
+        Err(e) => return ApiError::from(e).into_response(),
+    }
+    match db::list_sync_runs(&db, &key) {
+        Ok(runs) => (StatusCode::OK, Json(SyncRunListResponse { runs })).into_response(),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+// The last 50 runs, newest first.
+#[utoipa::path(get, path = "/api/sources/{id}/runs", responses((status = 200, body = SyncRunListResponse)))]
+async fn list_runs(State(state): State<AppState>, Path(id): Path<i64>) -> impl IntoResponse {
+    runs_response(&state, AutoSyncKey::Source(id))
+}
+
 #[utoipa::path(get, path = "/api/sources/{id}/status", responses((status = 200, body = SourceResponse)))]
 async fn source_status(State(state): State<AppState>, Path(id): Path<i64>) -> impl IntoResponse {
+    let syncing = auto_sync::is_running(&state.running_syncs, id);
     let db = state.db.lock().unwrap();
     match db::get_source(&db, id) {
         Ok(Some(s)) => (
             StatusCode::OK,
             Json(SourceResponse {
                 status: "success".into(),
-                message: format!(
-                    "Last synced: {}",
-                    s.last_synced.as_deref().unwrap_or("never")
-                ),
-                source: Some(s),
-            }),
-        )
-            .into_response(),
-        Ok(None) => (
-            StatusCode::NOT_FOUND,
-            Json(SourceResponse {
-                status: "error".into(),
-                message: "Source not found".into(),
-                source: None,
-            }),
-        )
-            .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(SourceResponse {
-                status: "error".into(),
-                message: e.to_string(),
-                source: None,
+                message: match syncing {
+                    true => "Sync in progress".into(),
+                    false => format!(
+                        "Last synced: {}",
+                        s.last_synced.as_deref().unwrap_or("never")
+                    ),
+                },
+                source: Some(source_view(&state, s)),
+                next_sync_at: next_sync_at(&state, id),
+                syncing: Some(syncing),
             }),
         )
             .into_response(),
+        Ok(None) => ApiError::not_found("Source not found").into_response(),
+        Err(e) => ApiError::from(e).into_response(),
     }
 }
 
@@ -303,6 +599,10 @@ pub fn routes() -> Router<AppState> {
             "/sources/{id}",
             put(update_source).delete(delete_source_handler),
         )
+        .route("/sources/{id}/clone", post(clone_source))
         .route("/sources/{id}/sync", post(sync_source))
+        .route("/sources/{id}/sync/cancel", post(cancel_sync))
+        .route("/sources/{id}/sync/progress", get(sync_progress_handler))
         .route("/sources/{id}/status", get(source_status))
+        .route("/sources/{id}/runs", get(list_runs))
 }
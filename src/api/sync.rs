@@ -1,5 +1,15 @@
-use anyhow::{Context, Result};
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{Context, Result, ensure};
 use reqwest::{Client, header};
+use rusqlite::Connection;
+
+use crate::api::{AppState, archive, attachments, reverse_sync};
+use crate::event_index;
+use crate::ics_repair::{self, RepairReport};
+use crate::server::caldav::xml_escape;
+use crate::{birthdays, dav_urls, db, feed_limits, holidays, ics_component, ics_text, legacy_ics};
+use crate::{http_clients, sync_origin, sync_progress, time_shift};
 
 pub fn toggle_slash(url: &str) -> String {
     if url.ends_with('/') {
@@ -9,7 +19,7 @@ pub fn toggle_slash(url: &str) -> String {
     }
 }
 
-async fn propfind(client: &Client, url: &str, body: &str) -> Result<reqwest::Response> {
+pub(crate) async fn propfind(client: &Client, url: &str, body: &str) -> Result<reqwest::Response> {
     client
         .request(reqwest::Method::from_bytes(b"PROPFIND").unwrap(), url)
         .header("Depth", "1")
@@ -21,13 +31,24 @@ async fn propfind(client: &Client, url: &str, body: &str) -> Result<reqwest::Res
         .map_err(Into::into)
 }
 
+const CALDAV_NS: &str = "urn:ietf:params:xml:ns:caldav";
+const APPLE_NS: &str = "http://apple.com/ns/ical/";
+
 pub async fn fetch_calendars(client: &Client, url: &str) -> Result<Vec<String>> {
+    let calendars = discover_calendars(client, url).await?;
+    Ok(calendars.into_iter().map(|c| c.href).collect())
+}
+
+// The calendar collections under `url` with what the server says about them.
+pub async fn discover_calendars(client: &Client, url: &str) -> Result<Vec<db::DiscoveredCalendar>> {
     let propfind_body = r#"<?xml version="1.0" encoding="utf-8" ?>
-<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav" xmlns:a="http://apple.com/ns/ical/">
   <d:prop>
      <d:resourcetype />
      <d:displayname />
      <c:supported-calendar-component-set />
+     <a:calendar-color />
+     <a:calendar-order />
   </d:prop>
 </d:propfind>"#;
 
@@ -40,45 +61,53 @@ pub async fn fetch_calendars(client: &Client, url: &str) -> Result<Vec<String>>
         }
     };
 
-    let text = res.text().await?;
-    let doc = roxmltree::Document::parse(&text)?;
+    parse_calendars(&ics_text::read_text(res).await?)
+}
 
-    let mut calendar_urls = Vec::new();
-    for node in doc.descendants() {
-        if node.has_tag_name(("DAV:", "response")) {
-            let mut is_calendar = false;
-            let mut href = None;
-
-            for child in node.children() {
-                if child.has_tag_name(("DAV:", "href")) {
-                    href = child.text();
-                } else if child.has_tag_name(("DAV:", "propstat")) {
-                    for propstat_child in child.children() {
-                        if propstat_child.has_tag_name(("DAV:", "prop")) {
-                            for prop in propstat_child.children() {
-                                if prop.has_tag_name(("DAV:", "resourcetype")) {
-                                    for rt_child in prop.children() {
-                                        if rt_child.has_tag_name((
-                                            "urn:ietf:params:xml:ns:caldav",
-                                            "calendar",
-                                        )) {
-                                            is_calendar = true;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
-            if is_calendar && let Some(h) = href {
-                calendar_urls.push(h.to_string());
-            }
-        }
+fn parse_calendars(text: &str) -> Result<Vec<db::DiscoveredCalendar>> {
+    let doc = roxmltree::Document::parse(text)?;
+    let mut calendars = Vec::new();
+    for response in doc
+        .descendants()
+        .filter(|n| n.has_tag_name(("DAV:", "response")))
+    {
+        let prop = |name: (&str, &str)| response.descendants().find(|n| n.has_tag_name(name));
+        let is_calendar = prop(("DAV:", "resourcetype")).is_some_and(|rt| {
+            rt.children()
+                .any(|c| c.has_tag_name((CALDAV_NS, "calendar")))
+        });
+        let href = response
+            .children()
+            .find(|n| n.has_tag_name(("DAV:", "href")))
+            .and_then(|n| n.text());
+        let (true, Some(href)) = (is_calendar, href) else {
+            continue;
+        };
+        let text_of = |name: (&str, &str)| {
+            prop(name)
+                .and_then(|n| n.text())
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_owned)
+        };
+        let components = prop((CALDAV_NS, "supported-calendar-component-set"))
+            .map(|set| {
+                set.children()
+                    .filter(|c| c.has_tag_name((CALDAV_NS, "comp")))
+                    .filter_map(|c| c.attribute("name"))
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+        calendars.push(db::DiscoveredCalendar {
+            href: href.to_string(),
+            displayname: text_of(("DAV:", "displayname")),
+            color: text_of((APPLE_NS, "calendar-color")),
+            order: text_of((APPLE_NS, "calendar-order")).and_then(|o| o.parse().ok()),
+            components,
+        });
     }
-
-    Ok(calendar_urls)
+    Ok(calendars)
 }
 
 pub async fn fetch_events(
@@ -86,19 +115,31 @@ pub async fn fetch_events(
     base_url: &str,
     calendar_path: &str,
 ) -> Result<Vec<String>> {
-    let url = if calendar_path.starts_with("http") {
-        calendar_path.to_string()
-    } else {
-        let parsed = reqwest::Url::parse(base_url)?;
-        let host = parsed.host_str().unwrap_or("");
-        let authority = match parsed.port() {
-            Some(port) => format!("{}:{}", host, port),
-            None => host.to_string(),
-        };
-        format!("{}://{}{}", parsed.scheme(), authority, calendar_path)
-    };
+    let resources = fetch_event_resources(client, base_url, calendar_path).await?;
+    Ok(resources.into_iter().map(|r| r.calendar_data).collect())
+}
 
-    let report_body = r#"<?xml version="1.0" encoding="utf-8" ?>
+pub struct EventResource {
+    pub href: Option<String>,
+    pub etag: Option<String>,
+    pub calendar_data: String,
+}
+
+// Objects fetched per calendar-multiget REPORT.
+const MULTIGET_BATCH: usize = 50;
+
+// The objects of a source as of its last multiget sync, by href.
+pub type ObjectCache = HashMap<String, db::CachedObject>;
+
+// The cache to sync `source` against, None unless it fetches with multiget.
+pub fn object_cache(conn: &Connection, source: &db::Source) -> Result<Option<ObjectCache>> {
+    match source.fetch_mode.as_str() {
+        "multiget" => Ok(Some(db::list_source_objects(conn, source.id)?)),
+        _ => Ok(None),
+    }
+}
+
+const CALENDAR_QUERY: &str = r#"<?xml version="1.0" encoding="utf-8" ?>
 <c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
   <d:prop>
     <d:getetag />
@@ -111,27 +152,235 @@ pub async fn fetch_events(
   </c:filter>
 </c:calendar-query>"#;
 
-    let res = client
-        .request(reqwest::Method::from_bytes(b"REPORT").unwrap(), &url)
-        .header("Depth", "1")
+pub(crate) async fn report(
+    client: &Client,
+    url: &str,
+    depth: &str,
+    body: String,
+) -> Result<reqwest::Response> {
+    Ok(client
+        .request(reqwest::Method::from_bytes(b"REPORT").unwrap(), url)
+        .header("Depth", depth)
         .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
-        .body(report_body)
+        .body(body)
         .send()
-        .await?;
+        .await?)
+}
+
+// A REPORT multistatus: the objects it carried, and whether the server cut it
+// short (RFC 4918 507 with DAV:number-of-matches-within-limits).
+struct ReportPage {
+    resources: Vec<EventResource>,
+    truncated: bool,
+}
 
-    let text = res.text().await?;
+fn parse_report(text: &str) -> Result<ReportPage> {
+    let doc = roxmltree::Document::parse(text)?;
+    let mut page = ReportPage {
+        resources: Vec::new(),
+        truncated: doc
+            .descendants()
+            .any(|n| n.has_tag_name(("DAV:", "number-of-matches-within-limits"))),
+    };
+    for response in doc
+        .descendants()
+        .filter(|n| n.has_tag_name(("DAV:", "response")))
+    {
+        let text_of = |name: (&str, &str)| {
+            response
+                .descendants()
+                .find(|n| n.has_tag_name(name))
+                .and_then(|n| n.text())
+        };
+        if let Some(data) = text_of((CALDAV_NS, "calendar-data")) {
+            page.resources.push(EventResource {
+                href: response
+                    .children()
+                    .find(|n| n.has_tag_name(("DAV:", "href")))
+                    .and_then(|n| n.text())
+                    .map(|h| h.trim().to_string()),
+                etag: text_of(("DAV:", "getetag")).map(str::to_owned),
+                calendar_data: data.to_string(),
+            });
+        } else if text_of(("DAV:", "status")).is_some_and(|s| s.contains(" 507 ")) {
+            page.truncated = true;
+        }
+    }
+    Ok(page)
+}
+
+// Each calendar object in the collection with the ETag the server reported for it.
+// Servers that limit how many objects one calendar-query returns are read
+// again in calendar-multiget batches rather than synced partially.
+pub async fn fetch_event_resources(
+    client: &Client,
+    base_url: &str,
+    calendar_path: &str,
+) -> Result<Vec<EventResource>> {
+    let url = dav_urls::resolve_href(base_url, calendar_path)?;
+    let res = report(client, &url, "1", CALENDAR_QUERY.to_string()).await?;
+    let too_large = res.status() == reqwest::StatusCode::INSUFFICIENT_STORAGE;
+    let text = ics_text::read_text(res).await?;
+    if !too_large {
+        let page = parse_report(&text)?;
+        if !page.truncated {
+            return Ok(page.resources);
+        }
+    }
+    tracing::warn!(
+        "REPORT on {} was truncated by the server, fetching in batches",
+        url
+    );
+    fetch_by_multiget(client, &url, &ObjectCache::new()).await
+}
+
+// Hrefs of the calendar objects in a collection, with their ETags, from a
+// Depth 1 PROPFIND.
+async fn object_hrefs(client: &Client, url: &str) -> Result<Vec<(String, Option<String>)>> {
+    let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop>
+     <d:resourcetype />
+     <d:getetag />
+  </d:prop>
+</d:propfind>"#;
+    let text = ics_text::read_text(propfind(client, url, body).await?).await?;
     let doc = roxmltree::Document::parse(&text)?;
+    let mut hrefs = Vec::new();
+    for response in doc
+        .descendants()
+        .filter(|n| n.has_tag_name(("DAV:", "response")))
+    {
+        let is_collection = response
+            .descendants()
+            .any(|n| n.has_tag_name(("DAV:", "collection")));
+        let href = response
+            .children()
+            .find(|n| n.has_tag_name(("DAV:", "href")))
+            .and_then(|n| n.text())
+            .map(str::trim);
+        let etag = response
+            .descendants()
+            .find(|n| n.has_tag_name(("DAV:", "getetag")))
+            .and_then(|n| n.text())
+            .map(str::to_owned);
+        if let (false, Some(href)) = (is_collection, href) {
+            hrefs.push((href.to_string(), etag));
+        }
+    }
+    Ok(hrefs)
+}
+
+// Lists the collection and fetches its objects in calendar-multiget batches.
+// Objects whose ETag matches the one in `cached` are taken from it instead.
+async fn fetch_by_multiget(
+    client: &Client,
+    url: &str,
+    cached: &ObjectCache,
+) -> Result<Vec<EventResource>> {
+    let listed = object_hrefs(client, url)
+        .await
+        .context("Failed to list calendar objects")?;
+    let mut resources = Vec::with_capacity(listed.len());
+    let mut changed = Vec::new();
+    for (href, etag) in listed {
+        match cached.get(&href) {
+            Some(object) if etag.as_ref() == Some(&object.etag) => resources.push(EventResource {
+                href: Some(href),
+                etag,
+                calendar_data: object.calendar_data.clone(),
+            }),
+            _ => changed.push(href),
+        }
+    }
+    for batch in changed.chunks(MULTIGET_BATCH) {
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8" ?>
+<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop>
+    <d:getetag />
+    <c:calendar-data />
+  </d:prop>
+{}</c:calendar-multiget>"#,
+            batch
+                .iter()
+                .map(|href| format!("  <d:href>{}</d:href>\n", xml_escape(href)))
+                .collect::<String>()
+        );
+        let res = report(client, url, "0", body).await?.error_for_status()?;
+        let page = parse_report(&ics_text::read_text(res).await?)?;
+        ensure!(
+            !page.truncated,
+            "Server truncated a calendar-multiget of {} objects from {}",
+            batch.len(),
+            url
+        );
+        resources.extend(page.resources);
+    }
+    Ok(resources)
+}
 
-    let mut ics_events = Vec::new();
-    for node in doc.descendants() {
-        if node.has_tag_name(("urn:ietf:params:xml:ns:caldav", "calendar-data"))
-            && let Some(data) = node.text()
-        {
-            ics_events.push(data.to_string());
+pub fn split_vevents(ics: &str) -> Vec<String> {
+    let mut events = Vec::new();
+    let mut in_vevent = false;
+    let mut current_event = String::new();
+    for line in ics.lines() {
+        if line.starts_with("BEGIN:VEVENT") {
+            in_vevent = true;
+        }
+        if in_vevent {
+            current_event.push_str(line);
+            current_event.push_str("\r\n");
+        }
+        if line.starts_with("END:VEVENT") {
+            in_vevent = false;
+            events.push(std::mem::take(&mut current_event));
         }
     }
+    events
+}
+
+pub fn event_uid(vevent: &str) -> Option<String> {
+    ics_component::value(vevent, "UID")
+}
+
+// Servers and uploads don't promise an order, and an unchanged calendar should
+// produce the same bytes so ETags stay valid and nothing is rewritten.
+// Overrides of a recurring event share its UID and sort by start.
+pub fn sort_events(events: &mut [String]) {
+    events.sort_by_cached_key(|ev| {
+        let starts_at = event_index::index(ev)
+            .into_iter()
+            .next()
+            .and_then(|e| e.starts_at);
+        (event_uid(ev), starts_at, ev.clone())
+    });
+}
 
-    Ok(ics_events)
+pub fn build_calendar(events: &[String]) -> String {
+    build_calendar_with(events, "")
+}
+
+// `header` holds extra VCALENDAR properties, each ending in CRLF.
+fn build_calendar_with(events: &[String], header: &str) -> String {
+    let mut output = String::new();
+    output.push_str(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//CalDAV/ICS Sync//EN\r\nCALSCALE:GREGORIAN\r\nMETHOD:PUBLISH\r\n",
+    );
+    output.push_str(header);
+    for ev in events {
+        // METHOD belongs to the calendar; some exports of invitations put
+        // their METHOD:REQUEST on the event, which clients take as an iTIP
+        // message rather than a published event.
+        match ev.contains("\nMETHOD") {
+            true => output.push_str(&ics_component::rewrite(ev, |name, _| {
+                (name == "METHOD").then(String::new)
+            })),
+            false => output.push_str(ev),
+        }
+    }
+    output.push_str("END:VCALENDAR\r\n");
+    output
 }
 
 pub async fn run_sync(
@@ -139,59 +388,290 @@ pub async fn run_sync(
     username: &str,
     password: &str,
 ) -> Result<(usize, usize, String)> {
-    let mut headers = header::HeaderMap::new();
-    let auth = format!("{}:{}", username, password);
-    let auth_header = format!(
-        "Basic {}",
-        base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &auth)
-    );
-    headers.insert(
-        header::AUTHORIZATION,
-        header::HeaderValue::from_str(&auth_header)?,
-    );
+    run_sync_with(&http_clients::build(username, password)?, caldav_url).await
+}
 
-    let client = Client::builder().default_headers(headers).build()?;
+// `run_sync` with a client that already carries the credentials, e.g. one
+// from the shared cache.
+pub async fn run_sync_with(client: &Client, caldav_url: &str) -> Result<(usize, usize, String)> {
+    let calendars = fetch_all_calendars(client, caldav_url, None).await?;
+    let (event_count, output) = combine_calendars(&calendars);
+    Ok((event_count, calendars.len(), output))
+}
 
-    let calendar_paths = fetch_calendars(&client, caldav_url)
+// The events of every calendar under `caldav_url`. A calendar that fails to
+// load is kept with no events, so it can be re-synced on its own later.
+// With a `cache`, objects are fetched by multiget and only when changed.
+pub async fn fetch_all_calendars(
+    client: &Client,
+    caldav_url: &str,
+    cache: Option<&ObjectCache>,
+) -> Result<Vec<db::SourceCalendar>> {
+    let discovered = discover_calendars(client, caldav_url)
         .await
         .context("Failed to fetch calendars")?;
-    let calendar_count = calendar_paths.len();
+    let calendar_count = discovered.len();
+    sync_progress::update(|p| p.calendars_found = calendar_count);
 
-    let mut combined_events = Vec::new();
+    let mut calendars = Vec::with_capacity(calendar_count);
     let mut event_count = 0;
+    for info in discovered {
+        let (events, objects) = fetch_calendar(client, caldav_url, &info.href, cache)
+            .await
+            .map(|c| (c.events, c.objects))
+            .unwrap_or_default();
+        event_count += events.len();
+        let calendar = db::SourceCalendar {
+            href: info.href,
+            events,
+            color: info.color,
+            order: info.order,
+            objects,
+        };
+        calendars.push(calendar);
+        sync_progress::update(|p| {
+            p.calendars_fetched += 1;
+            p.events_fetched = event_count;
+        });
+    }
+    Ok(calendars)
+}
 
-    for path in &calendar_paths {
-        if let Ok(events_data) = fetch_events(&client, caldav_url, path).await {
-            for ics_str in events_data {
-                let mut in_vevent = false;
-                let mut current_event = String::new();
-                for line in ics_str.lines() {
-                    if line.starts_with("BEGIN:VEVENT") {
-                        in_vevent = true;
-                    }
-                    if in_vevent {
-                        current_event.push_str(line);
-                        current_event.push_str("\r\n");
-                    }
-                    if line.starts_with("END:VEVENT") {
-                        in_vevent = false;
-                        combined_events.push(current_event.clone());
-                        current_event.clear();
-                        event_count += 1;
-                    }
-                }
-            }
+// The calendars of a source: from its CalDAV server, or generated for
+// holiday and birthday sources.
+pub async fn fetch_source(
+    state: &AppState,
+    source: &db::Source,
+) -> Result<Vec<db::SourceCalendar>> {
+    if source.source_type == "holidays" {
+        return holidays::calendars(state.holiday_provider.as_deref(), &source.holiday_region)
+            .await;
+    }
+    let client = http_clients::get(
+        &state.http_clients,
+        &source.caldav_url,
+        &source.username,
+        &source.password,
+        &source.user_agent,
+        &source.custom_headers,
+    )?;
+    if source.source_type == "birthdays" {
+        return birthdays::calendars(&client, &source.caldav_url).await;
+    }
+    let cache = object_cache(&state.db.lock().unwrap(), source)?;
+    fetch_all_calendars(&client, &source.caldav_url, cache.as_ref()).await
+}
+
+pub async fn fetch_calendar(
+    client: &Client,
+    caldav_url: &str,
+    href: &str,
+    cache: Option<&ObjectCache>,
+) -> Result<db::SourceCalendar> {
+    let resources = match cache {
+        Some(cache) => {
+            fetch_by_multiget(client, &dav_urls::resolve_href(caldav_url, href)?, cache).await?
+        }
+        None => fetch_event_resources(client, caldav_url, href).await?,
+    };
+    let mut fixes = BTreeSet::new();
+    let events = resources
+        .iter()
+        .flat_map(|r| {
+            let (ics, fixed) = legacy_ics::normalize(&r.calendar_data);
+            fixes.extend(fixed);
+            split_vevents(&ics)
+        })
+        .collect();
+    legacy_ics::log_fixes(href, &fixes);
+    let objects = match cache {
+        Some(_) => resources
+            .into_iter()
+            .filter_map(|r| {
+                Some(db::CachedObject {
+                    href: r.href?,
+                    etag: r.etag?,
+                    calendar_data: r.calendar_data,
+                })
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    Ok(db::SourceCalendar {
+        href: href.to_string(),
+        events,
+        color: None,
+        order: None,
+        objects,
+    })
+}
+
+// The feed takes the color of the first calendar, by the user's order, that
+// has one. Hex colors go in X-APPLE-CALENDAR-COLOR without the alpha Apple
+// servers append, anything else is taken as a CSS name for RFC 7986 COLOR.
+// A color set in the feed properties replaces it when the feed is saved.
+fn calendar_color(calendars: &[db::SourceCalendar]) -> String {
+    let mut ordered: Vec<&db::SourceCalendar> = calendars.iter().collect();
+    ordered.sort_by_key(|c| (c.order.is_none(), c.order));
+    let Some(color) = ordered.iter().find_map(|c| c.color.as_deref()) else {
+        return String::new();
+    };
+    match color.strip_prefix('#') {
+        Some(hex) => hex
+            .get(..6)
+            .filter(|rgb| rgb.chars().all(|c| c.is_ascii_hexdigit()))
+            .map(|rgb| format!("X-APPLE-CALENDAR-COLOR:#{}\r\n", rgb))
+            .unwrap_or_default(),
+        None if color.chars().all(|c| c.is_ascii_alphabetic()) => {
+            format!("COLOR:{}\r\n", color.to_ascii_lowercase())
         }
+        None => String::new(),
     }
+}
 
-    let mut output = String::new();
-    output.push_str(
-        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//CalDAV/ICS Sync//EN\r\nCALSCALE:GREGORIAN\r\nMETHOD:PUBLISH\r\n",
-    );
-    for ev in combined_events {
-        output.push_str(&ev);
+pub fn combine_calendars(calendars: &[db::SourceCalendar]) -> (usize, String) {
+    let mut combined_events: Vec<String> = calendars
+        .iter()
+        .flat_map(|c| c.events.iter().cloned())
+        .collect();
+    sort_events(&mut combined_events);
+    let output = build_calendar_with(&combined_events, &calendar_color(calendars));
+    (combined_events.len(), output)
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncDiff {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    pub total: usize,
+    // The feed came out byte-identical and wasn't rewritten.
+    pub unchanged: bool,
+    pub repairs: RepairReport,
+    // Feed limits that kicked in; see `feed_limits`.
+    pub warnings: Vec<String>,
+}
+
+// Compares two feeds event by event; DTSTAMP and other volatile fields are ignored.
+pub fn diff_events(old: &str, new: &str) -> SyncDiff {
+    let mut previous: HashMap<String, Vec<String>> = split_vevents(old)
+        .iter()
+        .map(|v| (archive::event_key(v), reverse_sync::normalize_vevent(v)))
+        .collect();
+    let current = split_vevents(new);
+    let mut diff = SyncDiff {
+        total: current.len(),
+        ..Default::default()
+    };
+    for vevent in &current {
+        match previous.remove(&archive::event_key(vevent)) {
+            Some(before) if before == reverse_sync::normalize_vevent(vevent) => {}
+            Some(_) => diff.changed += 1,
+            None => diff.added += 1,
+        }
     }
-    output.push_str("END:VCALENDAR\r\n");
+    diff.removed = previous.len();
+    diff
+}
+
+// Stores a full sync and keeps each calendar's events, for re-syncing one
+// calendar without fetching the others. Returns the number of events fetched.
+pub fn store_calendars(
+    conn: &Connection,
+    source: &db::Source,
+    calendars: &[db::SourceCalendar],
+) -> Result<(usize, SyncDiff)> {
+    let (events, ics_data) = combine_calendars(calendars);
+    let diff = store_sync_result(conn, source, &ics_data)?;
+    db::replace_source_calendars(conn, source.id, calendars)?;
+    let objects: Vec<_> = calendars
+        .iter()
+        .flat_map(|c| c.objects.iter().map(|o| (c.href.as_str(), o)))
+        .collect();
+    db::save_source_objects(conn, source.id, None, &objects)?;
+    Ok((events, diff))
+}
 
-    Ok((event_count, calendar_count, output))
+// Replaces one calendar's events and rebuilds the feed with the others as of
+// their last sync. Returns the number of events and calendars in the feed.
+pub fn store_calendar(
+    conn: &Connection,
+    source: &db::Source,
+    calendar: db::SourceCalendar,
+) -> Result<(usize, usize, SyncDiff)> {
+    let mut calendars = db::list_source_calendars(conn, source.id)?;
+    let Some(slot) = calendars.iter_mut().find(|c| c.href == calendar.href) else {
+        return Err(rusqlite::Error::QueryReturnedNoRows.into());
+    };
+    slot.events = calendar.events;
+    let (events, ics_data) = combine_calendars(&calendars);
+    let diff = store_sync_result(conn, source, &ics_data)?;
+    db::replace_source_calendars(conn, source.id, &calendars)?;
+    let objects: Vec<_> = calendar
+        .objects
+        .iter()
+        .map(|o| (calendar.href.as_str(), o))
+        .collect();
+    db::save_source_objects(conn, source.id, Some(&calendar.href), &objects)?;
+    Ok((events, calendars.len(), diff))
+}
+
+pub fn store_sync_result(
+    conn: &Connection,
+    source: &db::Source,
+    ics_data: &str,
+) -> Result<SyncDiff> {
+    let (repaired, repairs) = ics_repair::apply(ics_data, &source.repair_rules);
+    if repairs.total > 0 {
+        tracing::info!(
+            "Repaired {} events of source {}: {:?}",
+            repairs.total,
+            source.id,
+            repairs.by_rule
+        );
+    }
+    let shifted = time_shift::apply(&repaired, &source.time_shift_zone, &source.time_shift_mode);
+    let processed = attachments::process(&shifted, &source.attachment_mode);
+    let content = match source.namespace_uids {
+        true => sync_origin::apply(
+            &processed.content,
+            &sync_origin::source_namespace(source.id),
+        ),
+        false => processed.content.clone(),
+    };
+    let split = archive::split_past(
+        &content,
+        source.archive_after_months,
+        chrono::Utc::now().naive_utc(),
+    );
+    let (content, warnings) = feed_limits::apply(
+        &split.content,
+        source.max_events,
+        source.max_feed_bytes,
+        &source.feed_limit_policy,
+    )?;
+    for warning in &warnings {
+        tracing::warn!("Feed of source {}: {}", source.id, warning);
+    }
+    db::check_event_quota(conn, source, db::count_events(&content))?;
+    let previous = db::get_ics_data(conn, source.id)?.unwrap_or_default();
+    let diff = diff_events(&previous, &content);
+    db::save_archived_events(conn, source.id, &split.archived)?;
+    // Rules stamping the sync time would otherwise rewrite the feed, and
+    // change its ETag, on every sync.
+    let stamps_only = ics_repair::uses_sync_time(&source.repair_rules)
+        && !previous.is_empty()
+        && diff.added + diff.removed + diff.changed == 0;
+    let written = !stamps_only && db::save_ics_data(conn, source.id, &content)?;
+    // Archived events may still reference rehosted attachments.
+    db::save_attachments(conn, source.id, &processed.attachments, &processed.content)?;
+    db::update_last_synced(conn, source.id)?;
+    let status = if written { "ok" } else { "unchanged" };
+    db::update_sync_status(conn, source.id, status, None)?;
+    Ok(SyncDiff {
+        unchanged: !written,
+        repairs,
+        warnings,
+        ..diff
+    })
 }
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use axum::{
+    Extension, Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api::error::{ApiError, ApiJson};
+use crate::api::{AppState, owner_scope, reverse_sync};
+use crate::feed_diff::{self, FeedDiff};
+use crate::ics_validation::{self, ValidationReport};
+use crate::server::auth::CurrentUser;
+use crate::{db, legacy_ics};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize, ToSchema)]
+pub struct ValidateIcsRequest {
+    // The ICS document itself, or
+    ics: Option<String>,
+    // an http(s) URL to download it from.
+    url: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ValidateIcsResponse {
+    status: String,
+    message: String,
+    report: ValidationReport,
+}
+
+// Downloaded as is: unlike destination syncs, legacy vCalendar constructs
+// are not rewritten first, so they show up in the report. The `ics_url` of a
+// destination is fetched with its credentials and headers. The server fetches
+// from inside the network, so other URLs are for admins only.
+async fn download(
+    state: &AppState,
+    user: &Option<Extension<CurrentUser>>,
+    url: &str,
+) -> Result<String, ApiError> {
+    let scope = owner_scope(user);
+    let wanted = reverse_sync::feed_url(url);
+    let dest = db::list_destinations(&state.db.lock().unwrap())?
+        .into_iter()
+        .filter(|d| scope.is_none_or(|o| d.owner_id == Some(o)))
+        .find(|d| reverse_sync::feed_url(&d.ics_url) == wanted);
+    if dest.is_none() && user.as_ref().is_some_and(|Extension(u)| !u.is_admin) {
+        return Err(ApiError::forbidden(
+            "Fetching a URL other than a destination's ICS URL requires admin access",
+        ));
+    }
+    match reqwest::Url::parse(&wanted) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+        _ => {
+            return Err(ApiError::bad_request(
+                "'url' must be an http, https or webcal URL",
+            ));
+        }
+    }
+    match tokio::time::timeout(
+        FETCH_TIMEOUT,
+        reverse_sync::download_feed(dest.as_ref(), url),
+    )
+    .await
+    {
+        Ok(Ok(text)) => Ok(text),
+        Ok(Err(e)) => Err(ApiError::unprocessable(format!("{:#}", e))),
+        Err(_) => Err(ApiError::unprocessable("Timed out fetching the ICS file")),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/tools/validate-ics",
+    request_body = ValidateIcsRequest,
+    responses(
+        (status = 200, description = "Validation report; `report.valid` is false when errors were found", body = ValidateIcsResponse),
+        (status = 400, description = "Neither or both of `ics` and `url` given, or an invalid URL", body = ApiError),
+        (status = 403, description = "A non-admin gave a URL that is not one of their destinations' `ics_url`", body = ApiError),
+        (status = 422, description = "The URL could not be fetched", body = ApiError)
+    )
+)]
+pub async fn validate_ics(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+    ApiJson(req): ApiJson<ValidateIcsRequest>,
+) -> Response {
+    let text = match (req.ics, req.url) {
+        (Some(ics), None) => ics,
+        (None, Some(url)) => match download(&state, &user, &url).await {
+            Ok(text) => text,
+            Err(e) => return e.into_response(),
+        },
+        _ => {
+            return ApiError::bad_request("Give exactly one of 'ics' or 'url'").into_response();
+        }
+    };
+    let report = ics_validation::validate(&text);
+    let message = format!("{} error(s), {} warning(s)", report.errors, report.warnings);
+    (
+        StatusCode::OK,
+        Json(ValidateIcsResponse {
+            status: "success".into(),
+            message,
+            report,
+        }),
+    )
+        .into_response()
+}
+
+// One side of a diff: exactly one of these.
+#[derive(Deserialize, ToSchema)]
+pub struct DiffSide {
+    // The stored feed of a source.
+    source_id: Option<i64>,
+    // An ICS file to download.
+    url: Option<String>,
+    // The CalDAV calendar a destination writes to.
+    destination_id: Option<i64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DiffRequest {
+    left: DiffSide,
+    right: DiffSide,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DiffResponse {
+    status: String,
+    message: String,
+    // What each side was read from.
+    left: String,
+    right: String,
+    diff: FeedDiff,
+}
+
+type Events = HashMap<String, Vec<String>>;
+
+async fn load_side(
+    state: &AppState,
+    user: &Option<Extension<CurrentUser>>,
+    side: DiffSide,
+) -> Result<(String, Events), ApiError> {
+    let scope = owner_scope(user);
+    match (side.source_id, side.url, side.destination_id) {
+        (Some(id), None, None) => {
+            let db = state.db.lock().unwrap();
+            let source = db::get_source(&db, id)?
+                .filter(|s| scope.is_none_or(|o| s.owner_id == Some(o)))
+                .ok_or_else(|| ApiError::not_found(format!("Source {} not found", id)))?;
+            let ics = db::get_ics_data(&db, id)?.ok_or_else(|| {
+                ApiError::unprocessable(format!("Source {} has not been synced yet", id))
+            })?;
+            Ok((
+                format!("source {} ({})", id, source.name),
+                reverse_sync::feed_events(&ics),
+            ))
+        }
+        // Read the way a destination sync would, with legacy fixes applied.
+        (None, Some(url), None) => {
+            let text = download(state, user, &url).await?;
+            let (text, _) = legacy_ics::normalize(&text);
+            Ok((url, reverse_sync::feed_events(&text)))
+        }
+        (None, None, Some(id)) => {
+            let dest = db::get_destination(&state.db.lock().unwrap(), id)?
+                .filter(|d| scope.is_none_or(|o| d.owner_id == Some(o)))
+                .ok_or_else(|| ApiError::not_found(format!("Destination {} not found", id)))?;
+            let events = reverse_sync::calendar_events(&dest).await.map_err(|e| {
+                ApiError::unprocessable(format!("Failed to read the CalDAV calendar: {:#}", e))
+            })?;
+            Ok((
+                format!("destination {} ({}) calendar", id, dest.name),
+                events,
+            ))
+        }
+        _ => Err(ApiError::bad_request(
+            "Each side needs exactly one of 'source_id', 'url' or 'destination_id'",
+        )),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/tools/diff",
+    request_body = DiffRequest,
+    responses(
+        (status = 200, body = DiffResponse),
+        (status = 400, description = "A side without exactly one input, or an invalid URL", body = ApiError),
+        (status = 403, description = "A non-admin gave a URL that is not one of their destinations' `ics_url`", body = ApiError),
+        (status = 404, description = "Source or destination not found", body = ApiError),
+        (status = 422, description = "A side could not be read", body = ApiError)
+    )
+)]
+pub async fn diff_feeds(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+    ApiJson(req): ApiJson<DiffRequest>,
+) -> Response {
+    let (left, right) = match tokio::try_join!(
+        load_side(&state, &user, req.left),
+        load_side(&state, &user, req.right)
+    ) {
+        Ok(sides) => sides,
+        Err(e) => return e.into_response(),
+    };
+    let diff = feed_diff::diff(&left.1, &right.1);
+    let message = format!(
+        "{} only in left, {} only in right, {} changed, {} identical",
+        diff.only_left.len(),
+        diff.only_right.len(),
+        diff.changed.len(),
+        diff.identical
+    );
+    (
+        StatusCode::OK,
+        Json(DiffResponse {
+            status: "success".into(),
+            message,
+            left: left.0,
+            right: right.0,
+            diff,
+        }),
+    )
+        .into_response()
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/tools/validate-ics", post(validate_ics))
+        .route("/tools/diff", post(diff_feeds))
+}
@@ -0,0 +1,182 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Result, anyhow, ensure};
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::{Method, StatusCode},
+    response::IntoResponse,
+    routing::post,
+};
+
+use crate::api::AppState;
+use crate::api::error::ApiError;
+use crate::api::sources::SyncResult;
+use crate::api::sync;
+use crate::db;
+use crate::ics_repair;
+use crate::legacy_ics;
+use crate::sync_origin;
+
+pub fn validate_ics(body: &str) -> Result<()> {
+    ensure!(
+        body.trim_start().starts_with("BEGIN:VCALENDAR"),
+        "Invalid ICS: body must start with BEGIN:VCALENDAR"
+    );
+    let unfolded = icalendar::parser::unfold(body);
+    icalendar::parser::read_calendar(&unfolded).map_err(|e| anyhow!("Invalid ICS: {}", e))?;
+    Ok(())
+}
+
+// Events without a UID get one derived from their content, so uploading the
+// same event again replaces it rather than adding a copy.
+pub fn merge_events(existing: &[String], uploaded: &[String]) -> Vec<String> {
+    let mut by_uid: HashMap<String, Vec<String>> = HashMap::new();
+    let mut order: Vec<String> = Vec::new();
+
+    for (events, replace) in [(existing, false), (uploaded, true)] {
+        let mut replaced = HashSet::new();
+        for ev in events {
+            let ev = ics_repair::ensure_uid(ev);
+            let uid = sync::event_uid(&ev).unwrap_or_default();
+            if !by_uid.contains_key(&uid) {
+                order.push(uid.clone());
+            }
+            let slot = by_uid.entry(uid.clone()).or_default();
+            if replace && replaced.insert(uid) {
+                slot.clear();
+            }
+            slot.push(ev);
+        }
+    }
+
+    order
+        .into_iter()
+        .flat_map(|uid| by_uid.remove(&uid).unwrap_or_default())
+        .collect()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/sources/{id}/upload",
+    params(("id" = i64, Path, description = "Source ID")),
+    request_body(content = String, content_type = "text/calendar"),
+    responses((status = 200, body = SyncResult))
+)]
+pub async fn upload_source(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    method: Method,
+    body: String,
+) -> impl IntoResponse {
+    let db = state.db.lock().unwrap();
+    let source = match db::get_source(&db, id) {
+        Ok(Some(s)) => s,
+        Ok(None) => return ApiError::not_found("Source not found").into_response(),
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    if source.source_type != "upload" {
+        return ApiError::bad_request("Source does not accept uploads").into_response();
+    }
+    if let Err(e) = validate_ics(&body) {
+        return ApiError::bad_request(e.to_string()).into_response();
+    }
+
+    let (body, fixes) = legacy_ics::normalize(&body);
+    legacy_ics::log_fixes(&format!("upload to source {}", id), &fixes);
+    // With `namespace_uids`, the stored feed and files exported from it carry
+    // this source's stamp. Merging by UID needs the plain UIDs, and the
+    // source's own events must not be dropped as having come back; storing
+    // stamps them again.
+    let namespace = sync_origin::source_namespace(id);
+    let own = |events: Vec<String>| -> Vec<String> {
+        match source.namespace_uids {
+            true => events
+                .iter()
+                .map(|ev| sync_origin::unstamp(ev, &namespace))
+                .collect(),
+            false => events,
+        }
+    };
+    let uploaded = own(sync::split_vevents(&body));
+    let mut events = if method == Method::PUT {
+        uploaded
+            .iter()
+            .map(|ev| ics_repair::ensure_uid(ev))
+            .collect()
+    } else {
+        let existing = db::get_ics_data(&db, id)
+            .ok()
+            .flatten()
+            .map(|ics| own(sync::split_vevents(&ics)))
+            .unwrap_or_default();
+        merge_events(&existing, &uploaded)
+    };
+    sync::sort_events(&mut events);
+    let ics_data = sync::build_calendar(&events);
+
+    match sync::store_sync_result(&db, &source, &ics_data) {
+        Ok(diff) => (
+            StatusCode::OK,
+            Json(SyncResult::success(events.len(), 1, &diff)),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to store upload for source {}: {}", id, e);
+            ApiError::from(e).into_response()
+        }
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route(
+        "/sources/{id}/upload",
+        post(upload_source).put(upload_source),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ev(uid: &str, summary: &str) -> String {
+        format!("BEGIN:VEVENT\r\nUID:{uid}\r\nSUMMARY:{summary}\r\nEND:VEVENT\r\n")
+    }
+
+    #[test]
+    fn merge_replaces_matching_uids_and_appends_new() {
+        let existing = vec![ev("a", "Old A"), ev("b", "B")];
+        let uploaded = vec![ev("a", "New A"), ev("c", "C")];
+        let merged = merge_events(&existing, &uploaded);
+        assert_eq!(merged, vec![ev("a", "New A"), ev("b", "B"), ev("c", "C")]);
+    }
+
+    #[test]
+    fn merge_keeps_all_instances_of_uploaded_uid() {
+        let existing = vec![ev("a", "Old")];
+        let uploaded = vec![ev("a", "Master"), ev("a", "Override")];
+        let merged = merge_events(&existing, &uploaded);
+        assert_eq!(merged, vec![ev("a", "Master"), ev("a", "Override")]);
+    }
+
+    #[test]
+    fn merge_gives_uidless_events_a_stable_uid() {
+        let uidless = |stamp: &str| {
+            format!("BEGIN:VEVENT\r\nDTSTAMP:{stamp}\r\nSUMMARY:Lunch\r\nEND:VEVENT\r\n")
+        };
+        let once = merge_events(&[ev("a", "A")], &[uidless("20260101T000000Z")]);
+        assert_eq!(once.len(), 2);
+        assert!(sync::event_uid(&once[1]).is_some());
+        // Exported again with a new DTSTAMP, it replaces the stored copy.
+        let twice = merge_events(&once, &[uidless("20260102T000000Z")]);
+        assert_eq!(twice.len(), 2);
+        assert_eq!(sync::event_uid(&twice[1]), sync::event_uid(&once[1]));
+        assert!(twice[1].contains("DTSTAMP:20260102T000000Z"));
+    }
+
+    #[test]
+    fn validate_rejects_non_calendar_body() {
+        assert!(validate_ics("hello").is_err());
+        assert!(validate_ics("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nEND:VCALENDAR\r\n").is_ok());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::api::error::{ApiError, ApiJson};
+use crate::api::{AppState, require_admin};
+use crate::db;
+use crate::server::auth::CurrentUser;
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, put},
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct UserResponse {
+    status: String,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<db::User>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UserListResponse {
+    users: Vec<db::User>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UsageResponse {
+    usage: db::Usage,
+    quotas: db::Quotas,
+}
+
+#[utoipa::path(get, path = "/api/users/me", responses((status = 200, body = CurrentUser)))]
+pub async fn current_user(user: Option<Extension<CurrentUser>>) -> impl IntoResponse {
+    let user = user.map(|Extension(u)| u).unwrap_or(CurrentUser {
+        id: None,
+        username: String::new(),
+        is_admin: true,
+    });
+    (StatusCode::OK, Json(user))
+}
+
+fn usage_response(state: &AppState, user_id: Option<i64>) -> Response {
+    let db = state.db.lock().unwrap();
+    let quotas = match user_id.map(|id| db::get_user(&db, id)).transpose() {
+        Ok(Some(None)) => return ApiError::not_found("User not found").into_response(),
+        Ok(user) => user.flatten().map(|u| u.quotas).unwrap_or_default(),
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    match db::get_usage(&db, user_id) {
+        Ok(usage) => (StatusCode::OK, Json(UsageResponse { usage, quotas })).into_response(),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+#[utoipa::path(get, path = "/api/users/me/usage", responses((status = 200, body = UsageResponse)))]
+pub async fn current_usage(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+) -> Response {
+    usage_response(&state, user.and_then(|Extension(u)| u.id))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/users/{id}/usage",
+    params(("id" = i64, Path, description = "User ID")),
+    responses((status = 200, body = UsageResponse))
+)]
+pub async fn user_usage(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    user: Option<Extension<CurrentUser>>,
+) -> Response {
+    if let Some(resp) = require_admin(&user) {
+        return resp;
+    }
+    usage_response(&state, Some(id))
+}
+
+#[utoipa::path(get, path = "/api/users", responses((status = 200, body = UserListResponse)))]
+pub async fn list_users(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+) -> Response {
+    if let Some(resp) = require_admin(&user) {
+        return resp;
+    }
+    let db = state.db.lock().unwrap();
+    match db::list_users(&db) {
+        Ok(users) => (StatusCode::OK, Json(UserListResponse { users })).into_response(),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+#[utoipa::path(post, path = "/api/users", request_body = db::CreateUser, responses((status = 201, body = UserResponse)))]
+pub async fn create_user(
+    State(state): State<AppState>,
+    user: Option<Extension<CurrentUser>>,
+    ApiJson(body): ApiJson<db::CreateUser>,
+) -> Response {
+    if let Some(resp) = require_admin(&user) {
+        return resp;
+    }
+    let db = state.db.lock().unwrap();
+    match db::create_user(&db, &body) {
+        Ok(id) => (
+            StatusCode::CREATED,
+            Json(UserResponse {
+                status: "success".into(),
+                message: format!("User created with id {}", id),
+                user: db::get_user(&db, id).ok().flatten(),
+            }),
+        )
+            .into_response(),
+        Err(e) => ApiError::bad_request(e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/users/{id}",
+    params(("id" = i64, Path, description = "User ID")),
+    request_body = db::UpdateUser,
+    responses((status = 200, body = UserResponse))
+)]
+pub async fn update_user(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    user: Option<Extension<CurrentUser>>,
+    ApiJson(body): ApiJson<db::UpdateUser>,
+) -> Response {
+    if let Some(resp) = require_admin(&user) {
+        return resp;
+    }
+    let db = state.db.lock().unwrap();
+    match db::update_user(&db, id, &body) {
+        Ok(true) => (
+            StatusCode::OK,
+            Json(UserResponse {
+                status: "success".into(),
+                message: "User updated".into(),
+                user: db::get_user(&db, id).ok().flatten(),
+            }),
+        )
+            .into_response(),
+        Ok(false) => ApiError::not_found("User not found").into_response(),
+        Err(e) => ApiError::bad_request(e.to_string()).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/users/{id}",
+    params(("id" = i64, Path, description = "User ID")),
+    responses((status = 200, body = UserResponse))
+)]
+pub async fn delete_user(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    user: Option<Extension<CurrentUser>>,
+) -> Response {
+    if let Some(resp) = require_admin(&user) {
+        return resp;
+    }
+    let db = state.db.lock().unwrap();
+    match db::delete_user(&db, id) {
+        Ok(true) => (
+            StatusCode::OK,
+            Json(UserResponse {
+                status: "success".into(),
+                message: "User deleted".into(),
+                user: None,
+            }),
+        )
+            .into_response(),
+        Ok(false) => ApiError::not_found("User not found").into_response(),
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/users", get(list_users).post(create_user))
+        .route("/users/me", get(current_user))
+        .route("/users/me/usage", get(current_usage))
+        .route("/users/{id}/usage", get(user_usage))
+        .route("/users/{id}", put(update_user).delete(delete_user))
+}
@@ -0,0 +1,144 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::{Json, Router, http::StatusCode, response::IntoResponse, routing::get};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api::AppState;
+
+const RELEASES_URL: &str = "https://api.github.com/repos/RobbyV2/caldav-to-ics/releases/latest";
+const CHECK_INTERVAL: Duration = Duration::from_secs(86_400);
+
+static LATEST_RELEASE: Mutex<Option<LatestRelease>> = Mutex::new(None);
+
+#[derive(Clone, Serialize, ToSchema)]
+pub struct LatestRelease {
+    pub version: String,
+    pub url: String,
+    pub checked_at: String,
+    pub update_available: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VersionResponse {
+    pub version: String,
+    pub git_sha: String,
+    pub build_date: String,
+    pub features: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_release: Option<LatestRelease>,
+}
+
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    html_url: String,
+}
+
+pub fn build_date() -> String {
+    env!("BUILD_TIMESTAMP")
+        .parse::<i64>()
+        .ok()
+        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_else(|| "unknown".into())
+}
+
+fn parse_version(v: &str) -> Option<Vec<u64>> {
+    let core = v.trim().trim_start_matches('v');
+    let core = core.split(['-', '+']).next()?;
+    core.split('.').map(|p| p.parse().ok()).collect()
+}
+
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    match (parse_version(candidate), parse_version(current)) {
+        (Some(c), Some(cur)) => c > cur,
+        _ => false,
+    }
+}
+
+pub fn latest_release() -> Option<LatestRelease> {
+    LATEST_RELEASE.lock().ok()?.clone()
+}
+
+async fn check_latest_release() -> anyhow::Result<LatestRelease> {
+    let release: GithubRelease = reqwest::Client::new()
+        .get(RELEASES_URL)
+        .header(
+            reqwest::header::USER_AGENT,
+            concat!("caldav-ics-sync/", env!("CARGO_PKG_VERSION")),
+        )
+        .timeout(Duration::from_secs(30))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let version = release.tag_name.trim_start_matches('v').to_owned();
+    Ok(LatestRelease {
+        update_available: is_newer(&version, env!("CARGO_PKG_VERSION")),
+        version,
+        url: release.html_url,
+        checked_at: chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+pub fn spawn_update_check() {
+    tokio::spawn(async {
+        loop {
+            match check_latest_release().await {
+                Ok(release) => {
+                    if release.update_available {
+                        tracing::info!(
+                            "A newer release is available: {} ({})",
+                            release.version,
+                            release.url
+                        );
+                    }
+                    if let Ok(mut latest) = LATEST_RELEASE.lock() {
+                        *latest = Some(release);
+                    }
+                }
+                Err(e) => tracing::warn!("Update check failed: {}", e),
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+#[utoipa::path(get, path = "/api/version", responses((status = 200, body = VersionResponse)))]
+pub async fn version() -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        Json(VersionResponse {
+            version: env!("CARGO_PKG_VERSION").into(),
+            git_sha: env!("BUILD_GIT_SHA").into(),
+            build_date: build_date(),
+            features: env!("BUILD_FEATURES")
+                .split(',')
+                .filter(|f| !f.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            latest_release: latest_release(),
+        }),
+    )
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/version", get(version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_release_versions_numerically() {
+        assert!(is_newer("v0.10.0", "0.9.3"));
+        assert!(is_newer("0.2.6", "0.2.5"));
+        assert!(!is_newer("v0.2.5", "0.2.5"));
+        assert!(!is_newer("0.2.4", "0.2.5"));
+        assert!(!is_newer("nightly", "0.2.5"));
+    }
+}
@@ -1,20 +1,28 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::future::Future;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Datelike, Utc};
+use tokio::sync::{AcquireError, OwnedSemaphorePermit, Semaphore};
 use tokio::task::AbortHandle;
 use tokio_retry2::strategy::ExponentialBackoff;
 use tokio_retry2::{Retry, RetryError};
+use tokio_util::sync::CancellationToken;
 use tracing::info;
 
-use crate::api::AppState;
-use crate::db;
+use crate::api::{AppState, reverse_sync};
+use crate::circuit_breaker::{self, Admission};
+use crate::sync_window::{self, SyncWindow};
+use crate::{db, holidays};
+use crate::{http_clients, sync_progress};
 
 const RETRY_BASE_MS: u64 = 30_000;
 const RETRY_MAX_MS: u64 = 300_000;
 const MAX_RETRIES: usize = 5;
+// Each scheduled run that fails doubles the wait before the next, up to this.
+const MAX_BACKOFF_SECS: u64 = 86_400;
 
 static GENERATION: AtomicU64 = AtomicU64::new(0);
 
@@ -28,34 +36,210 @@ pub enum AutoSyncKey {
     Destination(i64),
 }
 
-pub type AutoSyncRegistry = Arc<Mutex<HashMap<AutoSyncKey, (u64, AbortHandle)>>>;
+pub struct ScheduledSync {
+    generation: u64,
+    handle: AbortHandle,
+    next_run: Option<DateTime<Utc>>,
+}
+
+pub type AutoSyncRegistry = Arc<Mutex<HashMap<AutoSyncKey, ScheduledSync>>>;
 
 pub fn new_registry() -> AutoSyncRegistry {
     Arc::new(Mutex::new(HashMap::new()))
 }
 
+// Caps how many syncs (scheduled or bulk-triggered) talk to upstream servers
+// at once, overall and per CalDAV host, so many sources on one account don't
+// get it throttled.
+#[derive(Clone)]
+pub struct SyncLimit {
+    global: Arc<Semaphore>,
+    per_host: usize,
+    hosts: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+pub struct SyncPermit {
+    _host: OwnedSemaphorePermit,
+    _global: OwnedSemaphorePermit,
+}
+
+pub fn new_sync_limit(permits: usize, per_host: usize) -> SyncLimit {
+    SyncLimit {
+        global: Arc::new(Semaphore::new(permits.max(1))),
+        per_host: per_host.max(1),
+        hosts: Arc::new(Mutex::new(HashMap::new())),
+    }
+}
+
+impl SyncLimit {
+    // The host slot is taken first, so a sync waiting on a busy host doesn't
+    // hold up syncs against other hosts.
+    pub async fn acquire(&self, url: &str) -> Result<SyncPermit, AcquireError> {
+        let host = circuit_breaker::host_of(url);
+        let semaphore = Arc::clone(
+            self.hosts
+                .lock()
+                .unwrap()
+                .entry(host)
+                .or_insert_with(|| Arc::new(Semaphore::new(self.per_host))),
+        );
+        let host = semaphore.acquire_owned().await?;
+        let global = Arc::clone(&self.global).acquire_owned().await?;
+        Ok(SyncPermit {
+            _host: host,
+            _global: global,
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Sync cancelled")]
+pub struct SyncCancelled;
+
+#[derive(Debug, thiserror::Error)]
+#[error("A sync is already running for this source")]
+pub struct SyncAlreadyRunning;
+
+// Source syncs in flight (manual, scheduled or bulk); at most one per source.
+pub type RunningSyncs = Arc<Mutex<HashMap<i64, CancellationToken>>>;
+
+pub fn new_running_syncs() -> RunningSyncs {
+    Arc::new(Mutex::new(HashMap::new()))
+}
+
+// Syncs due at startup whose first run hasn't finished yet. Their sources and
+// destinations report `initializing` until then, instead of the status the
+// data they serve was left with before the restart.
+pub type StartupSyncs = Arc<Mutex<HashSet<AutoSyncKey>>>;
+
+pub fn new_startup_syncs() -> StartupSyncs {
+    Arc::new(Mutex::new(HashSet::new()))
+}
+
+pub fn is_initializing(state: &AppState, key: &AutoSyncKey) -> bool {
+    state
+        .startup_syncs
+        .lock()
+        .is_ok_and(|keys| keys.contains(key))
+}
+
+// The last sync status to report: `initializing` while the startup sync of
+// `key` is pending.
+pub fn sync_status(state: &AppState, key: &AutoSyncKey, status: Option<String>) -> Option<String> {
+    match is_initializing(state, key) {
+        true => Some("initializing".into()),
+        false => status,
+    }
+}
+
+fn start_startup_sync(state: &AppState, key: &AutoSyncKey) {
+    if let Ok(mut keys) = state.startup_syncs.lock() {
+        keys.insert(key.clone());
+    }
+}
+
+fn finish_startup_sync(state: &AppState, key: &AutoSyncKey) {
+    if let Ok(mut keys) = state.startup_syncs.lock() {
+        keys.remove(key);
+    }
+}
+
+pub fn is_running(running: &RunningSyncs, id: i64) -> bool {
+    running.lock().is_ok_and(|map| map.contains_key(&id))
+}
+
+// Frees the source's slot even when the sync future is dropped mid-run.
+struct RunningGuard<'a> {
+    running: &'a RunningSyncs,
+    id: i64,
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut map) = self.running.lock() {
+            map.remove(&self.id);
+        }
+    }
+}
+
+// Runs `fut` unless another sync of the source is running, or until `cancel_running` is called.
+pub async fn cancellable<T>(
+    running: &RunningSyncs,
+    id: i64,
+    fut: impl Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<T> {
+    let token = {
+        let mut map = running.lock().unwrap();
+        if map.contains_key(&id) {
+            return Err(SyncAlreadyRunning.into());
+        }
+        map.entry(id).or_default().clone()
+    };
+    let _guard = RunningGuard { running, id };
+    tokio::select! {
+        result = fut => result,
+        _ = token.cancelled() => Err(SyncCancelled.into()),
+    }
+}
+
+// Returns false when no sync is running for the source.
+pub fn cancel_running(running: &RunningSyncs, id: i64) -> bool {
+    let Ok(map) = running.lock() else {
+        return false;
+    };
+    match map.get(&id) {
+        Some(token) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn mark_cancelled(conn: &rusqlite::Connection, id: i64) -> anyhow::Result<()> {
+    db::update_sync_status(conn, id, "cancelled", Some("Cancelled by user"))
+}
+
+pub fn next_run(registry: &AutoSyncRegistry, key: &AutoSyncKey) -> Option<DateTime<Utc>> {
+    registry.lock().ok()?.get(key)?.next_run
+}
+
+fn set_next_run(
+    registry: &Mutex<HashMap<AutoSyncKey, ScheduledSync>>,
+    key: &AutoSyncKey,
+    generation: u64,
+    next_run: Option<DateTime<Utc>>,
+) {
+    let Ok(mut map) = registry.lock() else {
+        return;
+    };
+    if let Some(entry) = map.get_mut(key)
+        && entry.generation == generation
+    {
+        entry.next_run = next_run;
+    }
+}
+
 pub fn cancel(registry: &AutoSyncRegistry, key: &AutoSyncKey) {
     let Ok(mut map) = registry.lock() else {
         tracing::error!("Registry mutex poisoned during cancel for {:?}", key);
         return;
     };
-    if let Some((_, handle)) = map.remove(key) {
-        handle.abort();
+    if let Some(entry) = map.remove(key) {
+        entry.handle.abort();
         info!("Cancelled auto-sync for {:?}", key);
     }
 }
 
 fn try_remove(
-    registry: &Mutex<HashMap<AutoSyncKey, (u64, AbortHandle)>>,
+    registry: &Mutex<HashMap<AutoSyncKey, ScheduledSync>>,
     key: &AutoSyncKey,
     generation: u64,
 ) {
     let Ok(mut map) = registry.lock() else {
         return;
     };
-    if let Some(&(current, _)) = map.get(key)
-        && current == generation
-    {
+    if map.get(key).is_some_and(|e| e.generation == generation) {
         map.remove(key);
     }
 }
@@ -101,10 +285,90 @@ fn handle_sync_error(state: &AppState, key: &AutoSyncKey, msg: &str) -> bool {
     }
 }
 
+// Stores one more failure in a row and returns when the next attempt may
+// start: one interval after the first failure, doubling with each after it.
+fn record_failure(state: &AppState, key: &AutoSyncKey, interval: Duration) -> DateTime<Utc> {
+    let failures = state
+        .db
+        .lock()
+        .ok()
+        .and_then(|db| db::get_sync_backoff(&db, key).ok())
+        .map_or(0, |b| b.failures)
+        + 1;
+    let wait = interval
+        .saturating_mul(1 << (failures - 1).clamp(0, 16))
+        .min(Duration::from_secs(MAX_BACKOFF_SECS))
+        .max(interval);
+    let retry_after = Utc::now() + chrono::Duration::seconds(wait.as_secs() as i64);
+    let backoff = db::SyncBackoff {
+        failures,
+        retry_after: Some(retry_after),
+    };
+    save_backoff(state, key, &backoff);
+    if failures > 1 {
+        info!(
+            "{:?} failed {} times in a row, backing off until {}",
+            key, failures, retry_after
+        );
+    }
+    retry_after
+}
+
+fn save_backoff(state: &AppState, key: &AutoSyncKey, backoff: &db::SyncBackoff) {
+    let Ok(db) = state.db.lock() else {
+        return;
+    };
+    if let Err(e) = db::set_sync_backoff(&db, key, backoff) {
+        tracing::error!("Failed to save backoff for {:?}: {}", key, e);
+    }
+}
+
+// Time left before a target that kept failing may be tried again, so a
+// restart doesn't skip its backoff.
+fn remaining_backoff(state: &AppState, key: &AutoSyncKey) -> Duration {
+    let db = state.db.lock().unwrap();
+    db::get_sync_backoff(&db, key)
+        .ok()
+        .and_then(|b| b.retry_after)
+        .and_then(|at| (at - Utc::now()).to_std().ok())
+        .unwrap_or_default()
+}
+
+// None when a scheduled sync against `endpoint` may run, probing its host
+// first when its circuit's cooldown is over.
+async fn circuit_open_until(state: &AppState, endpoint: &str) -> Option<DateTime<Utc>> {
+    let breakers = &state.circuit_breakers;
+    match breakers.admit(endpoint) {
+        Admission::Allow => return None,
+        Admission::Skip(until) => return Some(until),
+        Admission::Probe => {}
+    }
+    let ok = match http_clients::build("", "") {
+        Ok(client) => circuit_breaker::probe(&client, endpoint).await,
+        Err(_) => false,
+    };
+    breakers.probe_result(endpoint, ok);
+    match breakers.admit(endpoint) {
+        Admission::Skip(until) => Some(until),
+        _ => None,
+    }
+}
+
+// How often a task runs, the hours it may run in, and the CalDAV URL whose
+// host's circuit breaker it goes through.
+struct Schedule {
+    interval: Duration,
+    window: Option<SyncWindow>,
+    endpoint: String,
+}
+
+// Runs due outside the window wait for it; a zero `first_delay` (startup and
+// edits) still runs right away.
 fn spawn_sync_task<F, Fut>(
     registry: &AutoSyncRegistry,
     key: AutoSyncKey,
-    interval_secs: u64,
+    schedule: Schedule,
+    first_delay: Duration,
     display_name: String,
     state: AppState,
     sync_fn: F,
@@ -116,32 +380,65 @@ fn spawn_sync_task<F, Fut>(
     let registry_ref = Arc::clone(registry);
     let key_clone = key.clone();
     let log_name = display_name.clone();
+    let first_run = match first_delay.is_zero() {
+        true => Utc::now(),
+        false => sync_window::first_run(schedule.window.as_ref(), Utc::now() + first_delay),
+    };
+    let interval_secs = schedule.interval.as_secs();
 
     let handle = tokio::spawn(async move {
+        sleep_until(first_run).await;
         loop {
-            let strategy = ExponentialBackoff::from_millis(RETRY_BASE_MS)
-                .max_delay(Duration::from_millis(RETRY_MAX_MS))
-                .take(MAX_RETRIES);
-
-            let result = Retry::spawn(strategy, || sync_fn(state.clone())).await;
-
-            match result {
-                Ok(msg) => info!("{}", msg),
-                Err(e) => {
-                    let msg = e.to_string();
-                    tracing::error!(
-                        "Auto-sync '{}' failed after {} retries: {}",
-                        display_name,
-                        MAX_RETRIES,
-                        msg
-                    );
-                    if !handle_sync_error(&state, &key_clone, &msg) {
-                        break;
+            let mut retry_after = circuit_open_until(&state, &schedule.endpoint).await;
+            if let Some(until) = retry_after {
+                info!(
+                    "Skipping auto-sync '{}': circuit for {} is open until {}",
+                    display_name,
+                    circuit_breaker::host_of(&schedule.endpoint),
+                    until
+                );
+            } else {
+                let strategy = ExponentialBackoff::from_millis(RETRY_BASE_MS)
+                    .max_delay(Duration::from_millis(RETRY_MAX_MS))
+                    .take(MAX_RETRIES);
+
+                let result = Retry::spawn(strategy, || sync_fn(state.clone())).await;
+                let host_ok = result
+                    .as_ref()
+                    .map_or_else(|e| !circuit_breaker::is_host_failure(e), |_| true);
+                state.circuit_breakers.record(&schedule.endpoint, host_ok);
+
+                match result {
+                    Ok(msg) => {
+                        info!("{}", msg);
+                        save_backoff(&state, &key_clone, &db::SyncBackoff::default());
+                    }
+                    Err(e) => {
+                        let msg = e.to_string();
+                        tracing::error!(
+                            "Auto-sync '{}' failed after {} retries: {}",
+                            display_name,
+                            MAX_RETRIES,
+                            msg
+                        );
+                        if !handle_sync_error(&state, &key_clone, &msg) {
+                            finish_startup_sync(&state, &key_clone);
+                            break;
+                        }
+                        retry_after = Some(record_failure(&state, &key_clone, schedule.interval));
                     }
                 }
             }
+            finish_startup_sync(&state, &key_clone);
 
-            tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+            let mut next =
+                sync_window::next_tick(schedule.window.as_ref(), Utc::now(), schedule.interval);
+            if let Some(at) = retry_after.filter(|at| *at > next) {
+                next = sync_window::first_run(schedule.window.as_ref(), at);
+            }
+            set_next_run(&registry_ref, &key_clone, generation, Some(next));
+            sleep_until(next).await;
+            set_next_run(&registry_ref, &key_clone, generation, None);
         }
         try_remove(&registry_ref, &key_clone, generation);
     });
@@ -151,7 +448,14 @@ fn spawn_sync_task<F, Fut>(
         handle.abort();
         return;
     };
-    map.insert(key, (generation, handle.abort_handle()));
+    map.insert(
+        key,
+        ScheduledSync {
+            generation,
+            handle: handle.abort_handle(),
+            next_run: Some(first_run),
+        },
+    );
     drop(map);
     info!(
         "Auto-sync enabled for '{}' (every {}s)",
@@ -159,54 +463,73 @@ fn spawn_sync_task<F, Fut>(
     );
 }
 
+// Schedules a source, with its first run right away. Called on edits, so a
+// startup sync it replaces no longer counts.
 pub fn register_source(registry: &AutoSyncRegistry, state: &AppState, source: &db::Source) {
+    finish_startup_sync(state, &AutoSyncKey::Source(source.id));
+    schedule_source(registry, state, source, Duration::ZERO);
+}
+
+fn schedule_source(
+    registry: &AutoSyncRegistry,
+    state: &AppState,
+    source: &db::Source,
+    first_delay: Duration,
+) {
     let key = AutoSyncKey::Source(source.id);
     cancel(registry, &key);
 
-    if source.sync_interval_secs <= 0 {
+    if !source.enabled || source.sync_interval_secs <= 0 || source.source_type == "upload" {
         return;
     }
 
+    let window = match SyncWindow::parse(&source.sync_window) {
+        Ok(window) => window,
+        Err(e) => {
+            tracing::warn!("Ignoring sync window of '{}': {}", source.name, e);
+            None
+        }
+    };
     let id = source.id;
     spawn_sync_task(
         registry,
         key,
-        source.sync_interval_secs as u64,
+        Schedule {
+            interval: Duration::from_secs(source.sync_interval_secs as u64),
+            window,
+            endpoint: match (source.source_type.as_str(), &state.holiday_provider) {
+                ("holidays", Some(provider)) => {
+                    holidays::provider_url(provider, &source.holiday_region, Utc::now().year())
+                }
+                _ => source.caldav_url.clone(),
+            },
+        },
+        first_delay,
         source.name.clone(),
         state.clone(),
         move |state| async move {
-            let (url, user, pass) = {
-                let db = state.db.lock().unwrap();
-                match db::get_source(&db, id) {
-                    Ok(Some(s)) => (s.caldav_url, s.username, s.password),
-                    _ => {
-                        return Err(RetryError::permanent(anyhow::anyhow!(
-                            "Source {} no longer exists",
-                            id
-                        )));
-                    }
-                }
-            };
-            let (events, calendars, ics_data) = crate::api::sync::run_sync(&url, &user, &pass)
+            sync_source_once(&state, id)
                 .await
-                .map_err(RetryError::transient)?;
-            let db = state.db.lock().unwrap();
-            db::save_ics_data(&db, id, &ics_data).map_err(RetryError::transient)?;
-            db::update_last_synced(&db, id).map_err(RetryError::transient)?;
-            db::update_sync_status(&db, id, "ok", None).map_err(RetryError::transient)?;
-            Ok(format!(
-                "Auto-sync source {}: {} events from {} calendars",
-                id, events, calendars
-            ))
+                .map(|msg| format!("Auto-sync {}", msg))
         },
     );
 }
 
 pub fn register_destination(registry: &AutoSyncRegistry, state: &AppState, dest: &db::Destination) {
+    finish_startup_sync(state, &AutoSyncKey::Destination(dest.id));
+    schedule_destination(registry, state, dest, Duration::ZERO);
+}
+
+fn schedule_destination(
+    registry: &AutoSyncRegistry,
+    state: &AppState,
+    dest: &db::Destination,
+    first_delay: Duration,
+) {
     let key = AutoSyncKey::Destination(dest.id);
     cancel(registry, &key);
 
-    if dest.sync_interval_secs <= 0 {
+    if !dest.enabled || dest.sync_interval_secs <= 0 {
         return;
     }
 
@@ -214,45 +537,204 @@ pub fn register_destination(registry: &AutoSyncRegistry, state: &AppState, dest:
     spawn_sync_task(
         registry,
         key,
-        dest.sync_interval_secs as u64,
+        Schedule {
+            interval: Duration::from_secs(dest.sync_interval_secs as u64),
+            window: None,
+            endpoint: dest.caldav_url.clone(),
+        },
+        first_delay,
         dest.name.clone(),
         state.clone(),
         move |state| async move {
-            let d = {
-                let db = state.db.lock().unwrap();
-                match db::get_destination(&db, id) {
-                    Ok(Some(d)) => d,
-                    _ => {
-                        return Err(RetryError::permanent(anyhow::anyhow!(
-                            "Destination {} no longer exists",
-                            id
-                        )));
-                    }
-                }
-            };
-            let stats = crate::api::reverse_sync::run_reverse_sync(
-                &d.ics_url,
-                &d.caldav_url,
-                &d.calendar_name,
-                &d.username,
-                &d.password,
-                d.sync_all,
-                d.keep_local,
-            )
-            .await
-            .map_err(RetryError::transient)?;
-            let db = state.db.lock().unwrap();
-            db::update_destination_sync_status(&db, id, "ok", None)
-                .map_err(RetryError::transient)?;
-            Ok(format!(
-                "Auto-sync destination {}: uploaded {}, skipped {}, deleted {}, total {}",
-                id, stats.uploaded, stats.skipped, stats.deleted, stats.total
-            ))
+            sync_destination_once(&state, id)
+                .await
+                .map(|msg| format!("Auto-sync {}", msg))
         },
     );
 }
 
-pub fn register_all(registry: &AutoSyncRegistry, state: &AppState) {
+async fn sync_source_once(state: &AppState, id: i64) -> Result<String, RetryError<anyhow::Error>> {
+    let source = {
+        let db = state.db.lock().unwrap();
+        match db::get_source(&db, id) {
+            Ok(Some(s)) => s,
+            _ => {
+                return Err(RetryError::permanent(anyhow::anyhow!(
+                    "Source {} no longer exists",
+                    id
+                )));
+            }
+        }
+    };
+    // Queued runs can be cancelled too, so the permit is taken inside the cancellable part.
+    let run = async {
+        let permit = state.sync_limit.acquire(&source.caldav_url).await?;
+        let started = Instant::now();
+        let fetched = sync_progress::track(
+            &state.sync_progress,
+            AutoSyncKey::Source(id),
+            crate::api::sync::fetch_source(state, &source),
+        )
+        .await?;
+        Ok((permit, started, fetched))
+    };
+    let (_permit, started, fetched) = match cancellable(&state.running_syncs, id, run).await {
+        Ok(run) => run,
+        Err(e) if e.is::<SyncCancelled>() => {
+            let db = state.db.lock().unwrap();
+            let _ = mark_cancelled(&db, id);
+            return Ok(format!("source {}: sync cancelled", id));
+        }
+        Err(e) if e.is::<SyncAlreadyRunning>() => {
+            return Ok(format!("source {}: already syncing, skipped", id));
+        }
+        Err(e) => return Err(RetryError::transient(e)),
+    };
+    let db = state.db.lock().unwrap();
+    let calendars = fetched.len();
+    let (events, diff) =
+        crate::api::sync::store_calendars(&db, &source, &fetched).map_err(RetryError::transient)?;
+    let _ = db::update_sync_duration(&db, id, started.elapsed().as_millis() as i64);
+    if diff.unchanged {
+        return Ok(format!(
+            "source {}: {} events from {} calendars, no changes",
+            id, events, calendars
+        ));
+    }
+    Ok(format!(
+        "source {}: {} events from {} calendars ({} added, {} removed, {} changed)",
+        id, events, calendars, diff.added, diff.removed, diff.changed
+    ))
+}
+
+async fn sync_destination_once(
+    state: &AppState,
+    id: i64,
+) -> Result<String, RetryError<anyhow::Error>> {
+    let d = {
+        let db = state.db.lock().unwrap();
+        match db::get_destination(&db, id) {
+            Ok(Some(d)) => d,
+            _ => {
+                return Err(RetryError::permanent(anyhow::anyhow!(
+                    "Destination {} no longer exists",
+                    id
+                )));
+            }
+        }
+    };
+    let _permit = state
+        .sync_limit
+        .acquire(&d.caldav_url)
+        .await
+        .map_err(|e| RetryError::permanent(e.into()))?;
+    let started = Instant::now();
+    let (known_hash, known_etag, known_events) = {
+        let db = state.db.lock().unwrap();
+        // A bidirectional destination also picks up calendar changes, so it
+        // can't skip an unchanged feed.
+        let (hash, etag) = match d.bidirectional {
+            true => (None, None),
+            false => db::get_destination_feed_validators(&db, id).map_err(RetryError::transient)?,
+        };
+        let events = db::get_destination_event_etags(&db, id).map_err(RetryError::transient)?;
+        (hash, etag, events)
+    };
+    // A feed identical to the one last pushed has nothing new to upload.
+    let run = async {
+        let fetched =
+            reverse_sync::load_feed(state, &d, known_hash.as_deref(), known_etag.as_deref())
+                .await?;
+        let Some(feed) = fetched else {
+            return Ok(None);
+        };
+        let client = http_clients::get(
+            &state.http_clients,
+            &d.caldav_url,
+            &d.username,
+            &d.password,
+            &d.user_agent,
+            &d.custom_headers,
+        )?;
+        let stats =
+            reverse_sync::push_destination(state, &client, &feed.text, &d, &known_events).await?;
+        Ok(Some((stats, feed)))
+    };
+    let pushed = sync_progress::track(&state.sync_progress, AutoSyncKey::Destination(id), run)
+        .await
+        .map_err(RetryError::transient)?;
+    let db = state.db.lock().unwrap();
+    let _ = db::update_destination_sync_duration(&db, id, started.elapsed().as_millis() as i64);
+    let Some((stats, feed)) = pushed else {
+        db::update_destination_sync_status(&db, id, "unchanged", None)
+            .map_err(RetryError::transient)?;
+        return Ok(format!("destination {}: feed unchanged, skipped", id));
+    };
+    let _ = db::save_destination_events(&db, id, &stats.etags, &stats.conflicts, &stats.failed);
+    let _ = db::set_destination_run_result(&db, id, &stats.run_result());
+    if !stats.failed.is_empty() {
+        // Forgetting the feed makes the next run push it again, failed events
+        // included, even if it hasn't changed.
+        let summary = reverse_sync::failure_summary(&stats.failed);
+        db::update_destination_sync_status(&db, id, "degraded", Some(&summary))
+            .map_err(RetryError::transient)?;
+        let _ = db::set_destination_feed_validators(&db, id, None, None);
+    } else {
+        db::update_destination_sync_status(&db, id, "ok", None).map_err(RetryError::transient)?;
+        let _ =
+            db::set_destination_feed_validators(&db, id, Some(&feed.hash), feed.etag.as_deref());
+    }
+    if let Some(combined) = &stats.combined_feed {
+        db::save_destination_feed(&db, id, combined).map_err(RetryError::transient)?;
+    }
+    Ok(format!(
+        "destination {}: uploaded {}, skipped {}, deleted {}, conflicts {}, failed {}, total {}",
+        id,
+        stats.uploaded,
+        stats.skipped,
+        stats.deleted,
+        stats.conflicts.len(),
+        stats.failed.len(),
+        stats.total
+    ))
+}
+
+// Runs one sync immediately, outside the schedule and without retries.
+pub async fn run_now(state: AppState, key: AutoSyncKey) {
+    let result = match key {
+        AutoSyncKey::Source(id) => sync_source_once(&state, id).await,
+        AutoSyncKey::Destination(id) => sync_destination_once(&state, id).await,
+    };
+    match result {
+        Ok(msg) => info!("Bulk sync {}", msg),
+        Err(e) => {
+            let msg = e.to_string();
+            tracing::error!("Bulk sync {:?} failed: {}", key, msg);
+            handle_sync_error(&state, &key, &msg);
+        }
+    }
+}
+
+async fn sleep_until(at: DateTime<Utc>) {
+    tokio::time::sleep((at - Utc::now()).to_std().unwrap_or_default()).await;
+}
+
+// Time left of the interval since the last sync; none when it never ran.
+fn remaining_interval(last_synced: Option<&str>, interval_secs: i64) -> Duration {
+    let Some(last) = last_synced
+        .and_then(|t| chrono::NaiveDateTime::parse_from_str(t, "%Y-%m-%d %H:%M:%S").ok())
+    else {
+        return Duration::ZERO;
+    };
+    let elapsed = (Utc::now().naive_utc() - last).num_seconds();
+    Duration::from_secs((interval_secs - elapsed).max(0) as u64)
+}
+
+// At startup, syncs run right away when SYNC_ON_STARTUP (or the source's
+// override) says so, or when there is nothing to serve yet. Otherwise the
+// schedule resumes from the last sync. A source set to "always" is synced
+// once even if it is manual-only.
+pub fn register_all(registry: &AutoSyncRegistry, state: &AppState, sync_on_startup: bool) {
     let sources = {
         let db = state.db.lock().unwrap();
         db::list_sources(&db).unwrap_or_else(|e| {
@@ -261,7 +743,27 @@ pub fn register_all(registry: &AutoSyncRegistry, state: &AppState) {
         })
     };
     for source in &sources {
-        register_source(registry, state, source);
+        let key = AutoSyncKey::Source(source.id);
+        let on_startup = match source.sync_on_startup.as_str() {
+            "always" => true,
+            "never" => false,
+            _ => sync_on_startup,
+        };
+        let syncable = source.enabled && source.source_type != "upload";
+        let delay = match on_startup {
+            true => Duration::ZERO,
+            false => remaining_interval(source.last_synced.as_deref(), source.sync_interval_secs),
+        }
+        .max(remaining_backoff(state, &key));
+        if syncable && source.sync_interval_secs > 0 {
+            if delay.is_zero() {
+                start_startup_sync(state, &key);
+            }
+            schedule_source(registry, state, source, delay);
+        } else if syncable && source.sync_on_startup == "always" {
+            start_startup_sync(state, &key);
+            tokio::spawn(run_startup_sync(state.clone(), key));
+        }
     }
 
     let destinations = {
@@ -272,6 +774,20 @@ pub fn register_all(registry: &AutoSyncRegistry, state: &AppState) {
         })
     };
     for dest in &destinations {
-        register_destination(registry, state, dest);
+        let key = AutoSyncKey::Destination(dest.id);
+        let delay = match sync_on_startup {
+            true => Duration::ZERO,
+            false => remaining_interval(dest.last_synced.as_deref(), dest.sync_interval_secs),
+        }
+        .max(remaining_backoff(state, &key));
+        if dest.enabled && dest.sync_interval_secs > 0 && delay.is_zero() {
+            start_startup_sync(state, &key);
+        }
+        schedule_destination(registry, state, dest, delay);
     }
 }
+
+async fn run_startup_sync(state: AppState, key: AutoSyncKey) {
+    run_now(state.clone(), key.clone()).await;
+    finish_startup_sync(&state, &key);
+}
@@ -0,0 +1,213 @@
+use anyhow::{Context, Result, bail, ensure};
+use chrono::{Days, NaiveDate, NaiveTime, Weekday};
+use chrono_tz::Tz;
+
+use crate::ics_text::escape_text;
+
+// Served next to a feed: `/ics/{path}.availability`.
+pub const AVAILABILITY_SUFFIX: &str = ".availability";
+
+const DAYS: [(&str, Weekday); 7] = [
+    ("mon", Weekday::Mon),
+    ("tue", Weekday::Tue),
+    ("wed", Weekday::Wed),
+    ("thu", Weekday::Thu),
+    ("fri", Weekday::Fri),
+    ("sat", Weekday::Sat),
+    ("sun", Weekday::Sun),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Slot {
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+// Weekly working hours, written `Mon-Fri 09:00-17:00; Sat 10:00-12:00` with
+// an optional time zone entry such as `; Europe/Berlin` (UTC without one).
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkingHours {
+    slots: Vec<Slot>,
+    tz: Tz,
+}
+
+// `Mon` or `monday`, in any case.
+fn weekday(name: &str) -> Result<Weekday> {
+    let name = name.trim().to_ascii_lowercase();
+    DAYS.iter()
+        .find(|(short, day)| name == *short || name == day_name(*day))
+        .map(|(_, day)| *day)
+        .with_context(|| format!("Unknown day '{}' in working hours", name))
+}
+
+fn day_name(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "monday",
+        Weekday::Tue => "tuesday",
+        Weekday::Wed => "wednesday",
+        Weekday::Thu => "thursday",
+        Weekday::Fri => "friday",
+        Weekday::Sat => "saturday",
+        Weekday::Sun => "sunday",
+    }
+}
+
+// `Mon-Fri`, `Sat,Sun` or `Tue`.
+fn parse_days(spec: &str) -> Result<Vec<Weekday>> {
+    let mut days = Vec::new();
+    for part in spec.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (mut day, to) = (weekday(from)?, weekday(to)?);
+                loop {
+                    days.push(day);
+                    if day == to {
+                        break;
+                    }
+                    day = day.succ();
+                }
+            }
+            None => days.push(weekday(part)?),
+        }
+    }
+    days.sort_by_key(|d| d.num_days_from_monday());
+    days.dedup();
+    Ok(days)
+}
+
+impl WorkingHours {
+    // None for an empty string.
+    pub fn parse(value: &str) -> Result<Option<WorkingHours>> {
+        let mut slots = Vec::new();
+        let mut tz = None;
+        for entry in value.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let Some((days, span)) = entry.split_once(char::is_whitespace) else {
+                ensure!(tz.is_none(), "Working hours can only have one time zone");
+                tz = Some(entry.parse::<Tz>().map_err(|_| {
+                    anyhow::anyhow!("Unknown time zone '{}' in working hours", entry)
+                })?);
+                continue;
+            };
+            let Some((start, end)) = span.trim().split_once('-') else {
+                bail!(
+                    "Working hours must look like 'Mon-Fri 09:00-17:00', got '{}'",
+                    entry
+                );
+            };
+            let time = |t: &str| {
+                NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                    .with_context(|| format!("Invalid time '{}' in working hours", t.trim()))
+            };
+            let slot = Slot {
+                days: parse_days(days)?,
+                start: time(start)?,
+                end: time(end)?,
+            };
+            ensure!(
+                slot.start < slot.end,
+                "Working hours '{}' must end after they start",
+                entry
+            );
+            slots.push(slot);
+        }
+        if slots.is_empty() {
+            ensure!(tz.is_none(), "Working hours need at least one day and time");
+            return Ok(None);
+        }
+        Ok(Some(WorkingHours {
+            slots,
+            tz: tz.unwrap_or(Tz::UTC),
+        }))
+    }
+
+    // An RFC 7953 calendar with one VAVAILABILITY: time outside the working
+    // hours is unavailable, and each slot is a weekly AVAILABLE.
+    pub fn to_ics(&self, uid: &str, name: &str) -> String {
+        let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%SZ");
+        let utc = self.tz == Tz::UTC;
+        let time = |name: &str, date: NaiveDate, t: NaiveTime| match utc {
+            true => format!("{}:{}T{}Z", name, date.format("%Y%m%d"), t.format("%H%M%S")),
+            false => format!(
+                "{};TZID={}:{}T{}",
+                name,
+                self.tz.name(),
+                date.format("%Y%m%d"),
+                t.format("%H%M%S")
+            ),
+        };
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_owned(),
+            "VERSION:2.0".to_owned(),
+            "PRODID:-//CalDAV/ICS Sync//EN".to_owned(),
+            "BEGIN:VAVAILABILITY".to_owned(),
+            format!("UID:{}", uid),
+            format!("DTSTAMP:{}", stamp),
+            format!(
+                "SUMMARY:{}",
+                escape_text(&format!("Working hours of {}", name))
+            ),
+            "BUSYTYPE:BUSY-UNAVAILABLE".to_owned(),
+        ];
+        // Slots repeat weekly from the first matching day of a past week.
+        let monday = NaiveDate::from_isoywd_opt(2024, 1, Weekday::Mon).expect("static date");
+        for (i, slot) in self.slots.iter().enumerate() {
+            let first = monday + Days::new(slot.days[0].num_days_from_monday() as u64);
+            let byday: Vec<String> = slot
+                .days
+                .iter()
+                .map(|d| DAYS[d.num_days_from_monday() as usize].0[..2].to_ascii_uppercase())
+                .collect();
+            lines.extend([
+                "BEGIN:AVAILABLE".to_owned(),
+                format!("UID:{}-{}", uid, i + 1),
+                format!("DTSTAMP:{}", stamp),
+                time("DTSTART", first, slot.start),
+                time("DTEND", first, slot.end),
+                format!("RRULE:FREQ=WEEKLY;BYDAY={}", byday.join(",")),
+                "END:AVAILABLE".to_owned(),
+            ]);
+        }
+        lines.extend(["END:VAVAILABILITY".to_owned(), "END:VCALENDAR".to_owned()]);
+        lines.join("\r\n") + "\r\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_working_hours() {
+        assert_eq!(WorkingHours::parse(" ").unwrap(), None);
+        let hours = WorkingHours::parse("Mon-Fri 09:00-17:00; sat,sun 10:00-12:00; Europe/Berlin")
+            .unwrap()
+            .unwrap();
+        assert_eq!(hours.slots[0].days.len(), 5);
+        assert_eq!(hours.slots[1].days, [Weekday::Sat, Weekday::Sun]);
+        assert_eq!(hours.tz, chrono_tz::Europe::Berlin);
+        assert!(WorkingHours::parse("Fri-Mon 09:00-17:00").is_ok());
+        assert!(WorkingHours::parse("Mon 17:00-09:00").is_err());
+        assert!(WorkingHours::parse("saturday 08:00-09:00").is_ok());
+        assert!(WorkingHours::parse("Someday 09:00-17:00").is_err());
+        assert!(WorkingHours::parse("Mon 09:00-17:00; Mars/Base").is_err());
+    }
+
+    #[test]
+    fn builds_vavailability() {
+        let hours = WorkingHours::parse("Tue-Thu 08:30-16:00; Europe/Berlin")
+            .unwrap()
+            .unwrap();
+        let ics = hours.to_ics("work@example", "Work");
+        assert!(ics.contains("BEGIN:VAVAILABILITY\r\nUID:work@example\r\n"));
+        assert!(ics.contains("BUSYTYPE:BUSY-UNAVAILABLE\r\n"));
+        assert!(ics.contains("DTSTART;TZID=Europe/Berlin:20240102T083000\r\n"));
+        assert!(ics.contains("DTEND;TZID=Europe/Berlin:20240102T160000\r\n"));
+        assert!(ics.contains("RRULE:FREQ=WEEKLY;BYDAY=TU,WE,TH\r\n"));
+        let utc = WorkingHours::parse("Mon 09:00-10:00").unwrap().unwrap();
+        assert!(
+            utc.to_ics("x", "X")
+                .contains("DTSTART:20240101T090000Z\r\n")
+        );
+    }
+}
@@ -1,45 +1,187 @@
+use axum::Router;
+use axum::extract::DefaultBodyLimit;
 use axum::http::{HeaderName, Method, header};
 use axum::middleware;
+use axum::serve::ListenerExt;
 use caldav_ics_sync::api::AppState;
 use caldav_ics_sync::auto_sync;
 use caldav_ics_sync::config::AppConfig;
-use caldav_ics_sync::server::auth::{AuthConfig, basic_auth_middleware};
+use caldav_ics_sync::doctor;
+use caldav_ics_sync::redact::Redacting;
+use caldav_ics_sync::server::access_log::{self, AccessLogFormat};
+use caldav_ics_sync::server::auth::{AuthChain, basic_auth_middleware};
 use caldav_ics_sync::server::build_router;
+use caldav_ics_sync::server::feed_signing::FeedSigner;
+use caldav_ics_sync::server::frontend::Frontend;
+use caldav_ics_sync::server::listen::Listener;
+use caldav_ics_sync::server::tls::{self, CertStore, TlsListener};
+use std::sync::Arc;
 use tower_http::cors::{AllowOrigin, CorsLayer};
-use tracing::info;
+use tracing::{info, warn};
+use tracing_subscriber::{Layer, filter, fmt, prelude::*};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let _ = dotenvy::from_filename(".env.local");
     let _ = dotenvy::dotenv();
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let has = |flag: &str| args.iter().any(|a| a == flag);
+    if has("--check") || has("--check-endpoints") {
+        let findings = match AppConfig::load() {
+            Ok(cfg) => doctor::run(&cfg, has("--check-endpoints")).await,
+            Err(e) => vec![doctor::Finding {
+                level: doctor::Level::Failure,
+                area: "config",
+                message: format!("{:#}", e),
+            }],
+        };
+        std::process::exit(if doctor::print(&findings) { 1 } else { 0 });
+    }
+    let mut cfg = AppConfig::load()?;
+    if has("--demo") {
+        cfg.demo_mode = true;
+    }
+
+    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "info".into())
+        .add_directive(format!("{}=off", access_log::TARGET).parse()?);
+    let access_filter = filter::filter_fn(|meta| meta.target() == access_log::TARGET);
+    let access_layer = match cfg.access_log {
+        AccessLogFormat::Off => None,
+        AccessLogFormat::Text => Some(
+            fmt::layer()
+                .with_writer(Redacting(std::io::stdout))
+                .with_filter(access_filter)
+                .boxed(),
+        ),
+        AccessLogFormat::Json => Some(
+            fmt::layer()
+                .with_writer(Redacting(std::io::stdout))
+                .json()
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(false)
+                .with_filter(access_filter)
+                .boxed(),
+        ),
+    };
+    tracing_subscriber::registry()
+        .with(
+            fmt::layer()
+                .with_writer(Redacting(std::io::stdout))
+                .with_filter(env_filter),
         )
+        .with(access_layer)
         .init();
 
-    let cfg = AppConfig::load()?;
+    cfg.check_writable_dirs()?;
 
     let db_path = cfg.db_path();
-    if let Some(parent) = std::path::Path::new(&db_path).parent() {
-        std::fs::create_dir_all(parent)?;
+    for dir in cfg.writable_dirs() {
+        if let Some(fs_type) = caldav_ics_sync::config::detect_network_filesystem(&dir) {
+            warn!(
+                "Database directory '{}' is on a network filesystem ({}); SQLite locking may be unreliable. Prefer local storage for DB_PATH{}",
+                dir.display(),
+                fs_type,
+                if cfg.sqlite_journal_mode == "wal" {
+                    ", or set SQLITE_JOURNAL_MODE=delete since WAL needs shared memory"
+                } else {
+                    ""
+                }
+            );
+        }
     }
     let conn = rusqlite::Connection::open(&db_path)?;
-    conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA foreign_keys=ON;")?;
+    conn.execute_batch(&cfg.sqlite_pragmas())?;
     caldav_ics_sync::db::init_db(&conn)?;
+    caldav_ics_sync::feed_store::init(
+        cfg.feed_dir(),
+        caldav_ics_sync::feed_store::DEFAULT_INLINE_LIMIT,
+    )?;
+    let moved = caldav_ics_sync::db::move_large_feeds_to_files(&conn)?;
+    if moved > 0 {
+        info!("Moved {} large feeds out of the database", moved);
+    }
+    let pruned =
+        caldav_ics_sync::feed_store::prune(&caldav_ics_sync::db::referenced_feed_files(&conn)?)?;
+    if pruned > 0 {
+        info!("Removed {} unused feed files", pruned);
+    }
     info!("Database initialized at {}", db_path);
-
-    let proxy_url = cfg.proxy_url();
+    if cfg.demo_mode {
+        let mock =
+            caldav_ics_sync::mock_caldav::MockCalDav::sample(chrono::Utc::now().date_naive());
+        let addr = mock.spawn().await?;
+        cfg.sources
+            .push(caldav_ics_sync::mock_caldav::demo_source(addr));
+        info!(
+            "Demo mode: sample CalDAV server on http://{}, feed at /ics/{}",
+            addr,
+            caldav_ics_sync::mock_caldav::DEMO_ICS_PATH
+        );
+    }
+    let deprecated = cfg.deprecated_settings();
+    if !deprecated.is_empty() {
+        warn!(
+            "{} are deprecated; configure sources in the UI, API or config file instead",
+            deprecated.join(", ")
+        );
+    }
+    if let Some(src) = cfg.legacy_source() {
+        match caldav_ics_sync::db::migrate_legacy_source(&conn, &src) {
+            Ok(Some(id)) => info!(
+                "Migrated CALDAV_URL into source {} (feed at /ics/{}); the variables can now be removed",
+                id,
+                caldav_ics_sync::config::LEGACY_ICS_PATH
+            ),
+            Ok(None) => {}
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+    if !cfg.sources.is_empty() || !cfg.destinations.is_empty() {
+        caldav_ics_sync::db::apply_declared_config(&conn, &cfg.sources, &cfg.destinations)?;
+        info!(
+            "Applied {} declared sources and {} declared destinations",
+            cfg.sources.len(),
+            cfg.destinations.len()
+        );
+    }
 
     let sync_tasks = auto_sync::new_registry();
     let app_state = AppState {
         db: std::sync::Arc::new(std::sync::Mutex::new(conn)),
         start_time: std::time::Instant::now(),
         sync_tasks: sync_tasks.clone(),
+        sync_limit: auto_sync::new_sync_limit(cfg.sync_concurrency, cfg.sync_host_concurrency),
+        running_syncs: auto_sync::new_running_syncs(),
+        sync_progress: caldav_ics_sync::sync_progress::new_registry(),
+        feed_signer: cfg
+            .feed_signing_key
+            .as_deref()
+            .map(FeedSigner::from_base64)
+            .transpose()?,
+        public_base_url: cfg.public_base_url.clone(),
+        base_path: cfg.base_path.clone(),
+        proxy_cache: caldav_ics_sync::server::caldav_proxy::new_cache(),
+        http_clients: caldav_ics_sync::http_clients::new_cache(),
+        startup_syncs: caldav_ics_sync::auto_sync::new_startup_syncs(),
+        circuit_breakers: caldav_ics_sync::circuit_breaker::CircuitBreakers::new(
+            cfg.circuit_breaker_threshold,
+            std::time::Duration::from_secs(cfg.circuit_breaker_cooldown as u64),
+        ),
+        holiday_provider: cfg.holiday_provider_url.clone(),
+        maintenance: caldav_ics_sync::maintenance::Settings {
+            interval_secs: cfg.maintenance_interval,
+            retention_secs: cfg.maintenance_retention,
+        },
     };
 
-    auto_sync::register_all(&sync_tasks, &app_state);
+    auto_sync::register_all(&sync_tasks, &app_state, cfg.sync_on_startup);
+    caldav_ics_sync::maintenance::spawn(app_state.db.clone(), app_state.maintenance);
+    if cfg.update_check {
+        caldav_ics_sync::api::version::spawn_update_check();
+    }
 
     let cors = CorsLayer::new()
         .allow_origin(AllowOrigin::mirror_request())
@@ -61,42 +203,70 @@ async fn main() -> anyhow::Result<()> {
         ])
         .allow_credentials(true);
 
-    let auth_config = AuthConfig::from_config(&cfg);
-    match &auth_config {
-        AuthConfig::Disabled => {
-            info!("HTTP Basic Auth disabled (AUTH_USERNAME not set or no password configured)");
-        }
-        AuthConfig::PlainText { username, .. } => {
-            info!(
-                "HTTP Basic Auth enabled for user '{}' (plain text)",
-                username
-            );
-        }
-        AuthConfig::Hashed { username, .. } => {
-            info!(
-                "HTTP Basic Auth enabled for user '{}' (argon2 hash)",
-                username
-            );
-        }
+    let auth = AuthChain::from_config(&cfg)?;
+    if auth.is_enabled() {
+        info!("Authentication enabled: {}", auth.names().join(", "));
+    } else {
+        info!("Authentication disabled (AUTH_USERNAME not set or no password configured)");
+    }
+
+    let frontend = cfg.frontend();
+    match &frontend {
+        Frontend::Proxy(url) => info!("Proxying the web UI to {}", url),
+        Frontend::Embedded => info!("Serving the embedded web UI"),
     }
 
-    let app = build_router(app_state.clone(), &proxy_url)
+    let app = build_router(app_state.clone(), frontend)
         .await
         .layer(middleware::from_fn(basic_auth_middleware))
-        .layer(axum::Extension(auth_config))
+        .layer(axum::Extension(auth))
         .layer(axum::Extension(app_state))
-        .layer(cors);
+        .layer(DefaultBodyLimit::max(cfg.max_body_size as usize));
+    // Routes and auth rules see paths relative to BASE_PATH.
+    let app = match cfg.base_path.as_str() {
+        "" => app,
+        base => {
+            info!("Serving under {}", base);
+            Router::new().nest(base, app)
+        }
+    }
+    .layer(cors)
+    .layer(access_log::layer());
+
+    // systemd socket activation wins over LISTEN_UNIX_SOCKET, which wins over TCP.
+    let listener = match (Listener::from_systemd()?, &cfg.listen_unix_socket) {
+        (Some(listener), _) => listener,
+        (None, Some(path)) => Listener::unix(path)?,
+        (None, None) => Listener::tcp(&format!("{}:{}", cfg.server_host, cfg.server_port)).await?,
+    };
 
-    let addr = format!("{}:{}", cfg.server_host, cfg.server_port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    let socket_file = listener.socket_file();
 
     info!("Starting server");
-    info!("Listening on http://{}", addr);
+    let service = app.into_make_service_with_connect_info::<std::net::SocketAddr>();
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    match (&cfg.tls_cert_path, &cfg.tls_key_path) {
+        (Some(cert), Some(key)) => {
+            let store = Arc::new(CertStore::load(cert, key)?);
+            tls::spawn_reloader(store.clone());
+            info!("Listening on {}", listener.describe("https"));
+            // tap_io gives the custom listener axum's SocketAddr connect info.
+            let tls_listener = TlsListener::new(listener, tls::server_config(store)?)?;
+            axum::serve(tls_listener.tap_io(|_| {}), service)
+                .with_graceful_shutdown(shutdown_signal())
+                .await?;
+        }
+        _ => {
+            info!("Listening on {}", listener.describe("http"));
+            axum::serve(listener.tap_io(|_| {}), service)
+                .with_graceful_shutdown(shutdown_signal())
+                .await?;
+        }
+    }
 
+    if let Some(path) = socket_file {
+        let _ = std::fs::remove_file(path);
+    }
     info!("Server shutdown complete");
 
     Ok(())
@@ -0,0 +1,255 @@
+use anyhow::{Result, ensure};
+use chrono::{Datelike, Days, NaiveDate};
+use reqwest::Client;
+
+use crate::api::sync::{propfind, report, toggle_slash};
+use crate::ics_text::{escape_text, split_property, unescape_text};
+use crate::{dav_urls, db, ics_text, sync_progress};
+
+const CARDDAV_NS: &str = "urn:ietf:params:xml:ns:carddav";
+
+// Birthdays without a year start in a leap year, so 29 February fits.
+const NO_YEAR: i32 = 2000;
+// Apple writes this year, along with X-APPLE-OMIT-YEAR, when there is none.
+const APPLE_NO_YEAR: i32 = 1604;
+
+const ADDRESSBOOK_QUERY: &str = r#"<?xml version="1.0" encoding="utf-8" ?>
+<card:addressbook-query xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav">
+  <d:prop>
+    <d:getetag />
+    <card:address-data />
+  </d:prop>
+  <card:filter>
+    <card:prop-filter name="BDAY" />
+  </card:filter>
+</card:addressbook-query>"#;
+
+#[derive(Debug, PartialEq)]
+pub struct Birthday {
+    pub uid: String,
+    pub name: String,
+    pub month: u32,
+    pub day: u32,
+    pub year: Option<i32>,
+}
+
+// `19850412`, `1985-04-12`, `1985-04-12T00:00:00Z`, or `--0412` and
+// `--04-12` without a year.
+fn parse_bday(value: &str) -> Option<(Option<i32>, u32, u32)> {
+    let date = value.trim().split('T').next()?.replace('-', "");
+    let (year, rest) = match date.len() {
+        8 => (Some(date[..4].parse().ok()?), &date[4..]),
+        4 => (None, &date[..]),
+        _ => return None,
+    };
+    let (month, day) = (rest[..2].parse().ok()?, rest[2..].parse().ok()?);
+    NaiveDate::from_ymd_opt(year.unwrap_or(NO_YEAR), month, day)?;
+    Some((year, month, day))
+}
+
+// The birthday of a vCard, if it has a usable BDAY. `fallback_uid` is used
+// for cards without a UID.
+pub fn parse_vcard(vcard: &str, fallback_uid: &str) -> Option<Birthday> {
+    let unfolded = vcard
+        .replace("\r\n ", "")
+        .replace("\r\n\t", "")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    let (mut uid, mut full_name, mut name, mut bday) = (None, None, None, None);
+    for line in unfolded.lines() {
+        let Some((head, value)) = split_property(line) else {
+            continue;
+        };
+        let mut params = head.split(';');
+        let property = params.next().unwrap_or_default();
+        // Grouped properties look like `item1.BDAY`.
+        let property = property.rsplit('.').next().unwrap_or(property);
+        match property.to_ascii_uppercase().as_str() {
+            "UID" => uid = Some(value.trim().to_string()),
+            "FN" => full_name = Some(unescape_text(value.trim())),
+            "N" => {
+                let parts: Vec<&str> = value.split(';').collect();
+                let given = parts.get(1).copied().unwrap_or_default();
+                let joined = format!("{} {}", given, parts[0]);
+                name = Some(unescape_text(joined.trim()));
+            }
+            "BDAY" => {
+                let omit_year = params.any(|p| {
+                    p.to_ascii_uppercase().starts_with("X-APPLE-OMIT-YEAR")
+                        || p.eq_ignore_ascii_case("VALUE=text")
+                });
+                bday = parse_bday(value).map(|(year, month, day)| match omit_year {
+                    true => (None, month, day),
+                    false => (year.filter(|y| *y != APPLE_NO_YEAR), month, day),
+                });
+            }
+            _ => {}
+        }
+    }
+    let (year, month, day) = bday?;
+    let name = full_name
+        .or(name)
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "Unnamed contact".to_string());
+    Some(Birthday {
+        uid: uid
+            .filter(|u| !u.is_empty())
+            .unwrap_or_else(|| fallback_uid.to_string()),
+        name,
+        month,
+        day,
+        year,
+    })
+}
+
+// A yearly, all-day and transparent VEVENT from the birth date on.
+pub fn vevent(birthday: &Birthday) -> String {
+    let start = NaiveDate::from_ymd_opt(
+        birthday.year.unwrap_or(NO_YEAR),
+        birthday.month,
+        birthday.day,
+    )
+    .expect("validated when parsed");
+    let day = start.format("%Y%m%d");
+    let next = (start + Days::new(1)).format("%Y%m%d");
+    // A plain yearly rule would skip 29 February outside leap years.
+    let rule = match (start.month(), start.day()) {
+        (2, 29) => "FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=-1",
+        _ => "FREQ=YEARLY",
+    };
+    let description = match birthday.year {
+        Some(year) => format!("DESCRIPTION:Born in {}\r\n", year),
+        None => String::new(),
+    };
+    format!(
+        "BEGIN:VEVENT\r\nUID:birthday-{}\r\nDTSTAMP:{day}T000000Z\r\nDTSTART;VALUE=DATE:{day}\r\nDTEND;VALUE=DATE:{next}\r\nRRULE:{rule}\r\nSUMMARY:{}\r\n{description}CATEGORIES:Birthdays\r\nTRANSP:TRANSPARENT\r\nEND:VEVENT\r\n",
+        birthday.uid.replace(['\r', '\n'], ""),
+        escape_text(&format!("{}'s birthday", birthday.name)),
+    )
+}
+
+fn address_books(text: &str) -> Result<Vec<String>> {
+    let doc = roxmltree::Document::parse(text)?;
+    Ok(doc
+        .descendants()
+        .filter(|n| n.has_tag_name(("DAV:", "response")))
+        .filter(|response| {
+            response
+                .descendants()
+                .any(|n| n.has_tag_name((CARDDAV_NS, "addressbook")))
+        })
+        .filter_map(|response| {
+            response
+                .children()
+                .find(|n| n.has_tag_name(("DAV:", "href")))
+                .and_then(|n| n.text())
+                .map(|h| h.trim().to_string())
+        })
+        .collect())
+}
+
+// (href, vCard) of every contact in an addressbook-query answer.
+fn parse_cards(text: &str) -> Result<Vec<(String, String)>> {
+    let doc = roxmltree::Document::parse(text)?;
+    Ok(doc
+        .descendants()
+        .filter(|n| n.has_tag_name(("DAV:", "response")))
+        .filter_map(|response| {
+            let href = response
+                .children()
+                .find(|n| n.has_tag_name(("DAV:", "href")))
+                .and_then(|n| n.text())?;
+            let data = response
+                .descendants()
+                .find(|n| n.has_tag_name((CARDDAV_NS, "address-data")))
+                .and_then(|n| n.text())?;
+            Some((href.trim().to_string(), data.to_string()))
+        })
+        .collect())
+}
+
+// One calendar of birthdays per address book under `url`, which can be an
+// address book or the collection holding them.
+pub async fn calendars(client: &Client, url: &str) -> Result<Vec<db::SourceCalendar>> {
+    let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop>
+     <d:resourcetype />
+  </d:prop>
+</d:propfind>"#;
+    let res = match propfind(client, url, body).await {
+        Ok(r) => r,
+        Err(_) => propfind(client, &toggle_slash(url), body).await?,
+    };
+    let books = address_books(&ics_text::read_text(res).await?)?;
+    ensure!(
+        !books.is_empty(),
+        "No CardDAV address books found at {}",
+        url
+    );
+    sync_progress::update(|p| p.calendars_found = books.len());
+
+    let mut calendars = Vec::with_capacity(books.len());
+    let mut event_count = 0;
+    for href in books {
+        let book_url = dav_urls::resolve_href(url, &href)?;
+        let res = report(client, &book_url, "1", ADDRESSBOOK_QUERY.to_string())
+            .await?
+            .error_for_status()?;
+        let events: Vec<String> = parse_cards(&ics_text::read_text(res).await?)?
+            .iter()
+            .filter_map(|(card_href, card)| parse_vcard(card, card_href))
+            .map(|birthday| vevent(&birthday))
+            .collect();
+        event_count += events.len();
+        calendars.push(db::SourceCalendar {
+            href,
+            events,
+            color: None,
+            order: None,
+            objects: Vec::new(),
+        });
+        sync_progress::update(|p| {
+            p.calendars_fetched += 1;
+            p.events_fetched = event_count;
+        });
+    }
+    Ok(calendars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_birthday_formats() {
+        assert_eq!(parse_bday("19850412"), Some((Some(1985), 4, 12)));
+        assert_eq!(
+            parse_bday("1985-04-12T00:00:00Z"),
+            Some((Some(1985), 4, 12))
+        );
+        assert_eq!(parse_bday("--0229"), Some((None, 2, 29)));
+        assert_eq!(parse_bday("--04-12"), Some((None, 4, 12)));
+        assert_eq!(parse_bday("1985-02-30"), None);
+        assert_eq!(parse_bday("circa 1800"), None);
+    }
+
+    #[test]
+    fn reads_name_and_birthday_from_vcards() {
+        let card = "BEGIN:VCARD\r\nVERSION:3.0\r\nUID:abc\r\nN:Doe;Jane;;;\r\nitem1.BDAY;X-APPLE-OMIT-YEAR=1604:1604-07-\r\n 01\r\nEND:VCARD\r\n";
+        let birthday = parse_vcard(card, "/c/1.vcf").unwrap();
+        assert_eq!(birthday.name, "Jane Doe");
+        assert_eq!((birthday.year, birthday.month, birthday.day), (None, 7, 1));
+
+        let card = "BEGIN:VCARD\r\nFN:Bob\\, Jr.\r\nBDAY:19960229\r\nEND:VCARD\r\n";
+        let birthday = parse_vcard(card, "/c/2.vcf").unwrap();
+        assert_eq!(birthday.uid, "/c/2.vcf");
+        let event = vevent(&birthday);
+        assert!(event.contains("DTSTART;VALUE=DATE:19960229\r\n"));
+        assert!(event.contains("RRULE:FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=-1\r\n"));
+        assert!(event.contains("SUMMARY:Bob\\, Jr.'s birthday\r\n"));
+        assert!(event.contains("DESCRIPTION:Born in 1996\r\n"));
+
+        assert!(parse_vcard("BEGIN:VCARD\r\nFN:No Date\r\nEND:VCARD\r\n", "x").is_none());
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Context, Result, ensure};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api::sync;
+use crate::ics_component::{self, Part};
+use crate::ics_text::{split_property, unescape_text};
+
+// Splits one feed over several calendars of a destination's CalDAV account.
+// Routes are tried in order and the first one whose conditions all match an
+// event sends it to its calendar; events no route matches go to the
+// destination's own calendar. Recurrence overrides follow their series.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CalendarRoute {
+    pub calendar: String,
+    // One of the event's CATEGORIES, case-insensitively.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    // A regular expression the SUMMARY must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    // The upstream calendar, by href or its last path segment, that a local
+    // source fetched the event from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_calendar: Option<String>,
+}
+
+pub type CalendarRoutes = Vec<CalendarRoute>;
+
+const MAX_ROUTES: usize = 50;
+
+pub fn validate(routes: &[CalendarRoute]) -> Result<()> {
+    ensure!(
+        routes.len() <= MAX_ROUTES,
+        "At most {} calendar routes are allowed",
+        MAX_ROUTES
+    );
+    for route in routes {
+        ensure!(
+            !route.calendar.trim().is_empty(),
+            "Every calendar route needs a calendar"
+        );
+        ensure!(
+            route.category.is_some() || route.summary.is_some() || route.source_calendar.is_some(),
+            "Calendar route to '{}' has no conditions",
+            route.calendar
+        );
+        if let Some(pattern) = &route.summary {
+            Regex::new(pattern)
+                .with_context(|| format!("Invalid summary pattern for '{}'", route.calendar))?;
+        }
+    }
+    Ok(())
+}
+
+// CATEGORIES values, split at unescaped commas.
+fn categories(vevent: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    for part in ics_component::parts(vevent) {
+        let Part::Property { name, raw } = part else {
+            continue;
+        };
+        if name != "CATEGORIES" {
+            continue;
+        }
+        let line = ics_component::unfold(raw);
+        let Some((_, value)) = split_property(&line) else {
+            continue;
+        };
+        let mut current = String::new();
+        let mut escaped = false;
+        for c in value.chars() {
+            match c {
+                ',' if !escaped => out.push(unescape_text(&std::mem::take(&mut current))),
+                _ => current.push(c),
+            }
+            escaped = c == '\\' && !escaped;
+        }
+        out.push(unescape_text(&current));
+    }
+    out.into_iter().map(|c| c.trim().to_string()).collect()
+}
+
+fn same_calendar(href: &str, wanted: &str) -> bool {
+    let wanted = wanted.trim().trim_end_matches('/');
+    let href = href.trim_end_matches('/');
+    href == wanted || href.rsplit('/').next() == Some(wanted)
+}
+
+struct Compiled<'a> {
+    route: &'a CalendarRoute,
+    summary: Option<Regex>,
+}
+
+fn matches(
+    rule: &Compiled,
+    vevent: &str,
+    uid: &str,
+    source_calendars: &HashMap<String, String>,
+) -> bool {
+    let route = rule.route;
+    if let Some(category) = &route.category
+        && !categories(vevent)
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(category.trim()))
+    {
+        return false;
+    }
+    if let Some(pattern) = &rule.summary {
+        let summary = ics_component::value(vevent, "SUMMARY")
+            .map(|s| unescape_text(&s))
+            .unwrap_or_default();
+        if !pattern.is_match(&summary) {
+            return false;
+        }
+    }
+    if let Some(wanted) = &route.source_calendar
+        && !source_calendars
+            .get(uid)
+            .is_some_and(|href| same_calendar(href, wanted))
+    {
+        return false;
+    }
+    true
+}
+
+// The calendar each UID of `ics` is routed to. `source_calendars` maps UIDs
+// to the upstream calendar a local source fetched them from.
+fn assign(
+    ics: &str,
+    routes: &[CalendarRoute],
+    default_calendar: &str,
+    source_calendars: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    let compiled: Vec<Compiled> = routes
+        .iter()
+        .map(|route| Compiled {
+            route,
+            summary: route.summary.as_deref().and_then(|p| Regex::new(p).ok()),
+        })
+        .collect();
+    let mut assigned = HashMap::new();
+    let mut vevents = sync::split_vevents(ics);
+    // A series decides for its overrides.
+    vevents.sort_by_key(|v| ics_component::has_property(v, "RECURRENCE-ID"));
+    for vevent in &vevents {
+        let Some(uid) = sync::event_uid(vevent) else {
+            continue;
+        };
+        if assigned.contains_key(&uid) {
+            continue;
+        }
+        let calendar = compiled
+            .iter()
+            .find(|rule| matches(rule, vevent, &uid, source_calendars))
+            .map_or(default_calendar, |rule| rule.route.calendar.trim());
+        assigned.insert(uid, calendar.to_string());
+    }
+    assigned
+}
+
+// `ics` once per calendar, each copy with only the events routed there. Every
+// routed calendar is listed, even when no event goes to it.
+pub fn split(
+    ics: &str,
+    routes: &[CalendarRoute],
+    default_calendar: &str,
+    source_calendars: &HashMap<String, String>,
+) -> BTreeMap<String, String> {
+    let assigned = assign(ics, routes, default_calendar, source_calendars);
+    let mut feeds: BTreeMap<String, String> = routes
+        .iter()
+        .map(|r| r.calendar.trim())
+        .chain([default_calendar])
+        .map(|c| (c.to_string(), String::new()))
+        .collect();
+    let mut vevent = String::new();
+    let mut in_vevent = false;
+    for line in ics.split_inclusive('\n') {
+        if line.starts_with("BEGIN:VEVENT") {
+            in_vevent = true;
+        }
+        if !in_vevent {
+            for feed in feeds.values_mut() {
+                feed.push_str(line);
+            }
+            continue;
+        }
+        vevent.push_str(line);
+        if line.starts_with("END:VEVENT") {
+            in_vevent = false;
+            let calendar = sync::event_uid(&vevent)
+                .and_then(|uid| assigned.get(&uid))
+                .map_or(default_calendar, String::as_str);
+            if let Some(feed) = feeds.get_mut(calendar) {
+                feed.push_str(&vevent);
+            }
+            vevent.clear();
+        }
+    }
+    feeds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ICS: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+        BEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\nEND:VTIMEZONE\r\n\
+        BEGIN:VEVENT\r\nUID:a\r\nSUMMARY:Room 1: planning\r\nCATEGORIES:Meeting\r\nEND:VEVENT\r\n\
+        BEGIN:VEVENT\r\nUID:b\r\nSUMMARY:Lunch\r\nCATEGORIES:Food\\,drink,Social\r\nEND:VEVENT\r\n\
+        BEGIN:VEVENT\r\nUID:b\r\nRECURRENCE-ID:20260310T120000Z\r\nSUMMARY:Room 1 lunch\r\nEND:VEVENT\r\n\
+        BEGIN:VEVENT\r\nUID:c\r\nSUMMARY:Payroll\r\nEND:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    fn route(calendar: &str) -> CalendarRoute {
+        CalendarRoute {
+            calendar: calendar.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn routes_events_by_first_matching_rule() {
+        let routes = vec![
+            CalendarRoute {
+                category: Some("social".into()),
+                ..route("team")
+            },
+            CalendarRoute {
+                summary: Some("^Room 1".into()),
+                ..route("room-1")
+            },
+            CalendarRoute {
+                source_calendar: Some("finance".into()),
+                ..route("finance")
+            },
+        ];
+        let upstream = HashMap::from([("c".to_string(), "/dav/u/finance/".to_string())]);
+        let feeds = split(ICS, &routes, "main", &upstream);
+        assert_eq!(
+            feeds.keys().collect::<Vec<_>>(),
+            ["finance", "main", "room-1", "team"]
+        );
+        // The override of "b" follows its series into "team".
+        assert_eq!(feeds["team"].matches("UID:b").count(), 2);
+        assert!(feeds["room-1"].contains("UID:a\r\n"));
+        assert!(feeds["finance"].contains("UID:c\r\n"));
+        assert!(!feeds["main"].contains("BEGIN:VEVENT"));
+        assert!(feeds.values().all(|f| f.contains("TZID:Europe/Berlin")));
+        assert_eq!(
+            categories(&sync::split_vevents(ICS)[1]),
+            ["Food,drink", "Social"]
+        );
+    }
+
+    #[test]
+    fn rejects_routes_without_conditions_or_with_bad_patterns() {
+        assert!(validate(&[route("team")]).is_err());
+        let bad = CalendarRoute {
+            summary: Some("(".into()),
+            ..route("team")
+        };
+        assert!(validate(&[bad]).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    // Scheduled syncs against the host are skipped until `open_until`.
+    Open,
+    // The cooldown is over and a probe is deciding whether to close it.
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CircuitStatus {
+    pub host: String,
+    pub state: CircuitState,
+    pub failures: u32,
+    // RFC 3339; none while closed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_until: Option<String>,
+}
+
+// What a scheduled sync may do against its host.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Admission {
+    Allow,
+    // Probe the host first; report the result with `probe_result`.
+    Probe,
+    Skip(DateTime<Utc>),
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: CircuitState,
+    failures: u32,
+    open_until: Option<DateTime<Utc>>,
+}
+
+// Per-host circuit breakers for scheduled syncs. After `threshold` failures
+// in a row against a CalDAV host, its syncs are skipped for `cooldown`; the
+// first one due after that probes the host and closes the circuit if it
+// answers. A threshold of 0 turns them off.
+#[derive(Clone)]
+pub struct CircuitBreakers {
+    threshold: u32,
+    cooldown: Duration,
+    hosts: Arc<Mutex<BTreeMap<String, Breaker>>>,
+}
+
+pub fn host_of(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_ascii_lowercase))
+        .unwrap_or_else(|| url.to_string())
+}
+
+impl CircuitBreakers {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreakers {
+            threshold,
+            cooldown,
+            hosts: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    pub fn admit(&self, url: &str) -> Admission {
+        let mut hosts = self.hosts.lock().unwrap();
+        let Some(breaker) = hosts.get_mut(&host_of(url)) else {
+            return Admission::Allow;
+        };
+        match (breaker.state, breaker.open_until) {
+            (CircuitState::Closed, _) => Admission::Allow,
+            (CircuitState::Open, Some(until)) if until > Utc::now() => Admission::Skip(until),
+            (CircuitState::Open, _) => {
+                breaker.state = CircuitState::HalfOpen;
+                Admission::Probe
+            }
+            // Another sync is probing; wait for its result.
+            (CircuitState::HalfOpen, until) => Admission::Skip(until.unwrap_or_else(Utc::now)),
+        }
+    }
+
+    // A failed probe opens the circuit for another cooldown.
+    pub fn probe_result(&self, url: &str, ok: bool) {
+        let mut hosts = self.hosts.lock().unwrap();
+        let Some(breaker) = hosts.get_mut(&host_of(url)) else {
+            return;
+        };
+        match ok {
+            true => {
+                tracing::info!(
+                    "Circuit for {} closed after a successful probe",
+                    host_of(url)
+                );
+                breaker.state = CircuitState::Closed;
+                breaker.failures = 0;
+                breaker.open_until = None;
+            }
+            false => self.open(&host_of(url), breaker),
+        }
+    }
+
+    pub fn record(&self, url: &str, ok: bool) {
+        if self.threshold == 0 {
+            return;
+        }
+        let host = host_of(url);
+        let mut hosts = self.hosts.lock().unwrap();
+        if ok {
+            hosts.remove(&host);
+            return;
+        }
+        let breaker = hosts.entry(host.clone()).or_insert(Breaker {
+            state: CircuitState::Closed,
+            failures: 0,
+            open_until: None,
+        });
+        breaker.failures += 1;
+        if breaker.state == CircuitState::Closed && breaker.failures >= self.threshold {
+            self.open(&host, breaker);
+        }
+    }
+
+    fn open(&self, host: &str, breaker: &mut Breaker) {
+        let until = Utc::now() + chrono::Duration::seconds(self.cooldown.as_secs() as i64);
+        tracing::warn!(
+            "Circuit for {} opened after {} failures; skipping scheduled syncs until {}",
+            host,
+            breaker.failures,
+            until
+        );
+        breaker.state = CircuitState::Open;
+        breaker.open_until = Some(until);
+    }
+
+    // Hosts with failures since their last success.
+    pub fn statuses(&self) -> Vec<CircuitStatus> {
+        self.hosts
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(host, b)| CircuitStatus {
+                host: host.clone(),
+                state: b.state,
+                failures: b.failures,
+                open_until: b.open_until.map(|t| t.to_rfc3339()),
+            })
+            .collect()
+    }
+}
+
+// Whether an error means the host itself is unreachable or failing, rather
+// than a problem with one source (bad credentials, a missing calendar).
+pub fn is_host_failure(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|e| e.downcast_ref::<reqwest::Error>())
+        .any(|e| match e.status() {
+            Some(status) => status.is_server_error(),
+            None => e.is_connect() || e.is_timeout() || e.is_request(),
+        })
+}
+
+// A lightweight OPTIONS request; any answer short of a server error counts,
+// since even a 401 shows the server is back.
+pub async fn probe(client: &reqwest::Client, url: &str) -> bool {
+    match client.request(reqwest::Method::OPTIONS, url).send().await {
+        Ok(res) => !res.status().is_server_error(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_threshold_and_probes_after_cooldown() {
+        let breakers = CircuitBreakers::new(2, Duration::ZERO);
+        let url = "https://DAV.example.com/cal/";
+        breakers.record(url, false);
+        assert_eq!(breakers.admit(url), Admission::Allow);
+        breakers.record(url, false);
+        assert_eq!(breakers.statuses()[0].state, CircuitState::Open);
+        assert_eq!(breakers.statuses()[0].host, "dav.example.com");
+
+        assert_eq!(breakers.admit(url), Admission::Probe);
+        assert!(matches!(breakers.admit(url), Admission::Skip(_)));
+        breakers.probe_result(url, false);
+        assert_eq!(breakers.statuses()[0].state, CircuitState::Open);
+        assert_eq!(breakers.admit(url), Admission::Probe);
+        breakers.probe_result(url, true);
+        assert_eq!(breakers.admit(url), Admission::Allow);
+        assert_eq!(breakers.statuses()[0].failures, 0);
+    }
+
+    #[test]
+    fn skips_while_cooling_down() {
+        let breakers = CircuitBreakers::new(1, Duration::from_secs(300));
+        breakers.record("http://a/", false);
+        assert!(matches!(breakers.admit("http://a/x"), Admission::Skip(_)));
+        assert_eq!(breakers.admit("http://b/"), Admission::Allow);
+        breakers.record("http://a/", true);
+        assert!(breakers.statuses().is_empty());
+    }
+}
@@ -1,30 +1,218 @@
-use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
 use serde::Deserialize;
 
+use crate::db::{CreateDestination, CreateSource};
+use crate::server::frontend::Frontend;
+
+const JOURNAL_MODES: &[&str] = &["wal", "delete", "truncate", "persist", "memory"];
+const SYNCHRONOUS_LEVELS: &[&str] = &["off", "normal", "full", "extra"];
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "ceph",
+    "glusterfs",
+    "fuse.sshfs",
+    "fuse.rclone",
+];
+
 #[derive(Debug, Deserialize)]
 pub struct AppConfig {
     pub server_host: String,
     pub server_port: u16,
     pub port: u16,
     pub server_proxy_url: Option<String>,
+    pub frontend_mode: Option<String>,
     pub data_dir: String,
     pub db_path: Option<String>,
     pub auth_username: Option<String>,
     pub auth_password: Option<String>,
     pub auth_password_hash: Option<String>,
+    pub auth_username_file: Option<String>,
+    pub auth_password_file: Option<String>,
+    pub auth_password_hash_file: Option<String>,
+    pub auth_api_keys: Option<String>,
+    pub auth_policy: Option<String>,
+    pub auth_proxy_header: Option<String>,
+    pub auth_trusted_proxies: Option<String>,
+    pub oidc_issuer: Option<String>,
+    pub oidc_audience: Option<String>,
+    pub oidc_jwks_url: Option<String>,
+    pub oidc_username_claim: Option<String>,
+    pub session_secret: Option<String>,
+    pub feed_signing_key: Option<String>,
+    pub feed_signing_key_file: Option<String>,
+    pub public_base_url: Option<String>,
+    #[serde(default)]
+    pub base_path: String,
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    pub listen_unix_socket: Option<String>,
+    #[serde(deserialize_with = "crate::units::duration_secs")]
+    pub session_ttl: i64,
+    #[serde(deserialize_with = "crate::units::size_bytes")]
+    pub max_body_size: u64,
+    pub access_log: crate::server::access_log::AccessLogFormat,
+    pub sync_concurrency: usize,
+    pub sync_host_concurrency: usize,
+    pub sync_on_startup: bool,
+    pub circuit_breaker_threshold: u32,
+    #[serde(deserialize_with = "crate::units::duration_secs")]
+    pub circuit_breaker_cooldown: i64,
+    #[serde(deserialize_with = "crate::units::duration_secs")]
+    pub maintenance_interval: i64,
+    #[serde(deserialize_with = "crate::units::duration_secs")]
+    pub maintenance_retention: i64,
+    pub holiday_provider_url: Option<String>,
+    pub sqlite_journal_mode: String,
+    pub sqlite_synchronous: String,
+    #[serde(deserialize_with = "crate::units::duration_secs")]
+    pub sqlite_busy_timeout: i64,
+    #[serde(default)]
+    pub update_check: bool,
+    #[serde(default)]
+    pub demo_mode: bool,
+    #[serde(default)]
+    pub sources: Vec<CreateSource>,
+    #[serde(default)]
+    pub destinations: Vec<CreateDestination>,
+    // Single-source settings from before sources lived in the database.
+    // Migrated into a source on first startup; see `legacy_source`.
+    pub caldav_url: Option<String>,
+    pub caldav_username: Option<String>,
+    pub caldav_password: Option<String>,
+    pub storage_strategy: Option<String>,
 }
 
+pub const LEGACY_ICS_PATH: &str = "calendar";
+
 impl AppConfig {
     pub fn load() -> Result<Self> {
-        let cfg = config::Config::builder()
+        Self::load_from(std::env::var("CONFIG_FILE").ok().as_deref())
+    }
+
+    pub fn load_from(config_file: Option<&str>) -> Result<Self> {
+        let mut builder = config::Config::builder()
             .set_default("server_host", "0.0.0.0")?
             .set_default("server_port", 6765_i64)?
             .set_default("port", 6766_i64)?
             .set_default("data_dir", "./data")?
+            .set_default("max_body_size", "2MB")?
+            .set_default("sqlite_journal_mode", "wal")?
+            .set_default("sqlite_synchronous", "full")?
+            .set_default("sqlite_busy_timeout", "5s")?
+            .set_default("session_ttl", "7d")?
+            .set_default("access_log", "text")?
+            .set_default("sync_concurrency", 4_i64)?
+            .set_default("sync_host_concurrency", 2_i64)?
+            .set_default("sync_on_startup", true)?
+            .set_default("circuit_breaker_threshold", 5_i64)?
+            .set_default("circuit_breaker_cooldown", "5m")?
+            .set_default("maintenance_interval", "1d")?
+            .set_default("maintenance_retention", "90d")?;
+        if let Some(path) = config_file.filter(|p| !p.is_empty()) {
+            builder = builder.add_source(config::File::with_name(path));
+        }
+        let mut cfg = builder
             .add_source(config::Environment::default())
             .build()?
             .try_deserialize::<Self>()?;
 
+        resolve_secret(
+            "AUTH_USERNAME",
+            &mut cfg.auth_username,
+            cfg.auth_username_file.as_deref(),
+        )?;
+        resolve_secret(
+            "AUTH_PASSWORD",
+            &mut cfg.auth_password,
+            cfg.auth_password_file.as_deref(),
+        )?;
+        resolve_secret(
+            "AUTH_PASSWORD_HASH",
+            &mut cfg.auth_password_hash,
+            cfg.auth_password_hash_file.as_deref(),
+        )?;
+
+        resolve_secret(
+            "FEED_SIGNING_KEY",
+            &mut cfg.feed_signing_key,
+            cfg.feed_signing_key_file.as_deref(),
+        )?;
+
+        cfg.public_base_url = cfg
+            .public_base_url
+            .as_deref()
+            .filter(|u| !u.trim().is_empty())
+            .map(crate::feed_urls::normalize_base_url)
+            .transpose()?;
+
+        cfg.base_path = normalize_base_path(&cfg.base_path)?;
+
+        match cfg
+            .frontend_mode
+            .as_deref()
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            None | Some("") => {}
+            Some("proxy") => cfg.frontend_mode = Some("proxy".into()),
+            Some("embedded") if crate::server::frontend::EMBEDDED => {
+                cfg.frontend_mode = Some("embedded".into())
+            }
+            Some("embedded") => {
+                bail!("FRONTEND_MODE=embedded needs a build with the embed-frontend feature")
+            }
+            Some(other) => bail!(
+                "FRONTEND_MODE must be 'embedded' or 'proxy', got '{}'",
+                other
+            ),
+        }
+
+        if cfg.tls_cert_path.is_some() != cfg.tls_key_path.is_some() {
+            bail!("TLS_CERT_PATH and TLS_KEY_PATH must be set together");
+        }
+
+        cfg.sqlite_journal_mode = cfg.sqlite_journal_mode.to_ascii_lowercase();
+        cfg.sqlite_synchronous = cfg.sqlite_synchronous.to_ascii_lowercase();
+        if !JOURNAL_MODES.contains(&cfg.sqlite_journal_mode.as_str()) {
+            bail!(
+                "SQLITE_JOURNAL_MODE must be one of: {}",
+                JOURNAL_MODES.join(", ")
+            );
+        }
+        if !SYNCHRONOUS_LEVELS.contains(&cfg.sqlite_synchronous.as_str()) {
+            bail!(
+                "SQLITE_SYNCHRONOUS must be one of: {}",
+                SYNCHRONOUS_LEVELS.join(", ")
+            );
+        }
+        if cfg.sqlite_busy_timeout < 0 {
+            bail!("SQLITE_BUSY_TIMEOUT cannot be negative");
+        }
+
+        if cfg.sync_concurrency == 0 {
+            bail!("SYNC_CONCURRENCY must be at least 1");
+        }
+        if cfg.sync_host_concurrency == 0 {
+            bail!("SYNC_HOST_CONCURRENCY must be at least 1");
+        }
+        if cfg.circuit_breaker_cooldown < 0 {
+            bail!("CIRCUIT_BREAKER_COOLDOWN cannot be negative");
+        }
+
+        cfg.holiday_provider_url = cfg.holiday_provider_url.filter(|u| !u.trim().is_empty());
+        if let Some(url) = &cfg.holiday_provider_url
+            && !url.contains("{year}")
+        {
+            bail!("HOLIDAY_PROVIDER_URL must contain {{year}}, got '{}'", url);
+        }
+
         if cfg.auth_password.is_some() && cfg.auth_password_hash.is_some() {
             bail!("AUTH_PASSWORD and AUTH_PASSWORD_HASH are mutually exclusive; set only one");
         }
@@ -32,6 +220,39 @@ impl AppConfig {
         Ok(cfg)
     }
 
+    // Legacy variables that are set, for the deprecation notice.
+    pub fn deprecated_settings(&self) -> Vec<&'static str> {
+        [
+            ("CALDAV_URL", &self.caldav_url),
+            ("CALDAV_USERNAME", &self.caldav_username),
+            ("CALDAV_PASSWORD", &self.caldav_password),
+            ("STORAGE_STRATEGY", &self.storage_strategy),
+        ]
+        .into_iter()
+        .filter(|(_, value)| value.as_deref().is_some_and(|v| !v.trim().is_empty()))
+        .map(|(name, _)| name)
+        .collect()
+    }
+
+    // The source CALDAV_URL/CALDAV_USERNAME/CALDAV_PASSWORD used to describe,
+    // served at /ics/calendar. STORAGE_STRATEGY has no counterpart: feeds are
+    // always kept in the database.
+    pub fn legacy_source(&self) -> Option<CreateSource> {
+        let url = self.caldav_url.as_deref().map(str::trim)?;
+        if url.is_empty() {
+            return None;
+        }
+        Some(CreateSource {
+            name: "CalDAV".into(),
+            caldav_url: url.into(),
+            username: self.caldav_username.clone().unwrap_or_default(),
+            password: self.caldav_password.clone().unwrap_or_default(),
+            ics_path: LEGACY_ICS_PATH.into(),
+            sync_interval_secs: 3600,
+            ..Default::default()
+        })
+    }
+
     pub fn db_path(&self) -> String {
         match &self.db_path {
             Some(path) => path.clone(),
@@ -39,6 +260,59 @@ impl AppConfig {
         }
     }
 
+    // Next to the database, so the server still writes to one directory.
+    pub fn feed_dir(&self) -> PathBuf {
+        self.writable_dirs().remove(0).join("feeds")
+    }
+
+    pub fn writable_dirs(&self) -> Vec<PathBuf> {
+        let db_path = self.db_path();
+        match Path::new(&db_path).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => vec![parent.to_path_buf()],
+            _ => vec![PathBuf::from(".")],
+        }
+    }
+
+    pub fn check_writable_dirs(&self) -> Result<()> {
+        for dir in self.writable_dirs() {
+            std::fs::create_dir_all(&dir).with_context(|| {
+                format!(
+                    "Cannot create directory '{}'. Mount a writable volume there or point DATA_DIR/DB_PATH at a writable location",
+                    dir.display()
+                )
+            })?;
+            let probe = dir.join(format!(".write-check-{}", std::process::id()));
+            std::fs::write(&probe, b"ok")
+                .and_then(|_| std::fs::remove_file(&probe))
+                .with_context(|| {
+                    format!(
+                        "Directory '{}' is not writable. On a read-only root filesystem, mount a writable volume there or point DATA_DIR/DB_PATH at a writable location",
+                        dir.display()
+                    )
+                })?;
+        }
+        Ok(())
+    }
+
+    pub fn sqlite_pragmas(&self) -> String {
+        format!(
+            "PRAGMA journal_mode={}; PRAGMA synchronous={}; PRAGMA busy_timeout={}; PRAGMA foreign_keys=ON; PRAGMA temp_store=MEMORY;",
+            self.sqlite_journal_mode,
+            self.sqlite_synchronous,
+            self.sqlite_busy_timeout * 1000
+        )
+    }
+
+    // Builds with the frontend embedded serve it unless FRONTEND_MODE=proxy.
+    pub fn frontend(&self) -> Frontend {
+        match self.frontend_mode.as_deref() {
+            Some("proxy") => Frontend::Proxy(self.proxy_url()),
+            Some("embedded") => Frontend::Embedded,
+            _ if crate::server::frontend::EMBEDDED => Frontend::Embedded,
+            _ => Frontend::Proxy(self.proxy_url()),
+        }
+    }
+
     pub fn proxy_url(&self) -> String {
         match &self.server_proxy_url {
             Some(url) => url.clone(),
@@ -46,3 +320,60 @@ impl AppConfig {
         }
     }
 }
+
+// `calsync/` -> `/calsync`; empty or `/` mounts at the root.
+fn normalize_base_path(raw: &str) -> Result<String> {
+    let trimmed = raw.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        return Ok(String::new());
+    }
+    if trimmed
+        .split('/')
+        .any(|seg| seg.is_empty() || seg == "." || seg == "..")
+        || trimmed.contains(|c: char| c.is_whitespace() || matches!(c, '?' | '#' | '{' | '}'))
+    {
+        bail!("BASE_PATH must be a plain URL path such as /calsync");
+    }
+    Ok(format!("/{}", trimmed))
+}
+
+fn resolve_secret(name: &str, value: &mut Option<String>, file: Option<&str>) -> Result<()> {
+    let Some(path) = file.filter(|p| !p.is_empty()) else {
+        return Ok(());
+    };
+    if value.is_some() {
+        bail!(
+            "{} and {}_FILE are mutually exclusive; set only one",
+            name,
+            name
+        );
+    }
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}_FILE at '{}'", name, path))?;
+    *value = Some(contents.trim_end_matches(['\r', '\n']).to_owned());
+    Ok(())
+}
+
+// Returns the filesystem type of `path` if it lives on a known network filesystem,
+// using a /proc/mounts-style table.
+pub fn network_filesystem(mounts: &str, path: &Path) -> Option<String> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            let mount_point = fields.next()?.replace("\\040", " ");
+            let fs_type = fields.next()?;
+            path.starts_with(&mount_point)
+                .then(|| (mount_point.len(), fs_type.to_owned()))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, fs_type)| fs_type)
+        .filter(|fs_type| NETWORK_FILESYSTEMS.contains(&fs_type.as_str()))
+}
+
+pub fn detect_network_filesystem(path: &Path) -> Option<String> {
+    let mounts = std::fs::read_to_string("/proc/mounts").ok()?;
+    let path = std::fs::canonicalize(path).ok()?;
+    network_filesystem(&mounts, &path)
+}
@@ -0,0 +1,643 @@
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use ring::aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::calendar_routes::CalendarRoutes;
+use crate::db::{self, CreateDestination, CreateSource, CreateSourcePath, UpdateSourcePath};
+use crate::feed_metadata::{FeedMetadata, FeedProperties};
+use crate::http_clients::CustomHeaders;
+use crate::redact;
+
+pub const FORMAT_VERSION: u32 = 1;
+pub const CIPHER: &str = "argon2id-chacha20poly1305";
+const SALT_LEN: usize = 16;
+
+// Credentials are only exported when a passphrase is given. One key is derived
+// per document; every password gets its own nonce.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Encryption {
+    pub cipher: String,
+    pub salt: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExportedPath {
+    pub path: String,
+    #[serde(default)]
+    pub is_public: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExportedSource {
+    pub name: String,
+    #[serde(default)]
+    pub caldav_url: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    pub ics_path: String,
+    #[serde(deserialize_with = "crate::units::duration_secs")]
+    pub sync_interval_secs: i64,
+    #[serde(default)]
+    pub public_ics: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_ics_path: Option<String>,
+    #[serde(default = "default_attachment_mode")]
+    pub attachment_mode: String,
+    #[serde(default = "default_source_type")]
+    pub source_type: String,
+    #[serde(default)]
+    pub proxy_enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_after_months: Option<i64>,
+    #[serde(default)]
+    pub namespace_uids: bool,
+    #[serde(default = "default_sync_on_startup")]
+    pub sync_on_startup: String,
+    #[serde(default = "default_fetch_mode")]
+    pub fetch_mode: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user_agent: String,
+    // Values are encrypted like passwords and left out without a passphrase.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_headers: Option<CustomHeaders>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub sync_window: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub working_hours: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub holiday_region: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub repair_rules: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub time_shift_zone: String,
+    #[serde(default = "default_time_shift_mode")]
+    pub time_shift_mode: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_events: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_feed_bytes: Option<i64>,
+    #[serde(default = "default_feed_limit_policy")]
+    pub feed_limit_policy: String,
+    #[serde(default)]
+    pub paths: Vec<ExportedPath>,
+    #[serde(default)]
+    pub metadata: FeedMetadata,
+    #[serde(default)]
+    pub properties: FeedProperties,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExportedDestination {
+    pub name: String,
+    #[serde(default)]
+    pub ics_url: String,
+    // ICS path of the local source the destination reads, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub caldav_url: String,
+    pub calendar_name: String,
+    pub username: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(deserialize_with = "crate::units::duration_secs")]
+    pub sync_interval_secs: i64,
+    #[serde(default)]
+    pub sync_all: bool,
+    #[serde(default)]
+    pub keep_local: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default = "default_conflict_policy")]
+    pub conflict_policy: String,
+    #[serde(default)]
+    pub bidirectional: bool,
+    #[serde(default)]
+    pub namespace_uids: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user_agent: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_headers: Option<CustomHeaders>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub summary_template: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description_template: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub location_template: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub drop_properties: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: CalendarRoutes,
+    #[serde(default = "default_scheduling")]
+    pub scheduling: String,
+    #[serde(default)]
+    pub max_requests_per_second: f64,
+    #[serde(default)]
+    pub request_delay_ms: i64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub ics_username: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ics_password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ics_headers: Option<CustomHeaders>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ConfigExport {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<Encryption>,
+    #[serde(default)]
+    pub sources: Vec<ExportedSource>,
+    #[serde(default)]
+    pub destinations: Vec<ExportedDestination>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, ToSchema)]
+pub struct ImportSummary {
+    pub sources: usize,
+    pub destinations: usize,
+    pub paths: usize,
+}
+
+fn default_attachment_mode() -> String {
+    "keep".into()
+}
+
+fn default_time_shift_mode() -> String {
+    "shift".into()
+}
+
+fn default_feed_limit_policy() -> String {
+    "truncate".into()
+}
+
+fn default_source_type() -> String {
+    "caldav".into()
+}
+
+fn default_sync_on_startup() -> String {
+    "default".into()
+}
+
+fn default_fetch_mode() -> String {
+    "query".into()
+}
+
+fn default_conflict_policy() -> String {
+    "overwrite".into()
+}
+
+fn default_scheduling() -> String {
+    "keep".into()
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<LessSafeKey> {
+    ensure!(!passphrase.is_empty(), "Passphrase cannot be empty");
+    let mut key = [0u8; 32];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive key: {}", e))?;
+    let key = UnboundKey::new(&CHACHA20_POLY1305, &key).map_err(|_| anyhow!("Invalid key"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+fn encrypt(key: &LessSafeKey, rng: &SystemRandom, plaintext: &str) -> Result<String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill(&mut nonce)
+        .map_err(|_| anyhow!("Failed to generate nonce"))?;
+    let mut sealed = plaintext.as_bytes().to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::empty(),
+        &mut sealed,
+    )
+    .map_err(|_| anyhow!("Failed to encrypt credential"))?;
+    Ok(STANDARD.encode([nonce.as_slice(), &sealed].concat()))
+}
+
+fn decrypt(key: &LessSafeKey, encoded: &str) -> Result<String> {
+    let mut data = STANDARD
+        .decode(encoded)
+        .context("Encrypted credential is not valid base64")?;
+    ensure!(data.len() > NONCE_LEN, "Encrypted credential is too short");
+    let mut sealed = data.split_off(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(&data).map_err(|_| anyhow!("Invalid nonce"))?;
+    let plaintext = key
+        .open_in_place(nonce, Aad::empty(), &mut sealed)
+        .map_err(|_| anyhow!("Cannot decrypt credentials: wrong passphrase"))?;
+    String::from_utf8(plaintext.to_vec()).context("Decrypted credential is not UTF-8")
+}
+
+fn owner_names(conn: &Connection) -> Result<std::collections::HashMap<i64, String>> {
+    Ok(db::list_users(conn)?
+        .into_iter()
+        .map(|u| (u.id, u.username))
+        .collect())
+}
+
+pub fn export(conn: &Connection, passphrase: Option<&str>) -> Result<ConfigExport> {
+    let rng = SystemRandom::new();
+    let (encryption, key) = match passphrase {
+        Some(passphrase) => {
+            let mut salt = [0u8; SALT_LEN];
+            rng.fill(&mut salt)
+                .map_err(|_| anyhow!("Failed to generate salt"))?;
+            let key = derive_key(passphrase, &salt)?;
+            let encryption = Encryption {
+                cipher: CIPHER.into(),
+                salt: STANDARD.encode(salt),
+            };
+            (Some(encryption), Some(key))
+        }
+        None => (None, None),
+    };
+    let seal = |password: &str| -> Result<Option<String>> {
+        match &key {
+            Some(key) if !password.is_empty() => encrypt(key, &rng, password).map(Some),
+            _ => Ok(None),
+        }
+    };
+    let seal_headers = |headers: &CustomHeaders| -> Result<Option<CustomHeaders>> {
+        match &key {
+            Some(key) if !headers.is_empty() => headers
+                .iter()
+                .map(|(name, value)| Ok((name.clone(), encrypt(key, &rng, value)?)))
+                .collect::<Result<_>>()
+                .map(Some),
+            _ => Ok(None),
+        }
+    };
+    let owners = owner_names(conn)?;
+    let owner = |id: Option<i64>| id.and_then(|id| owners.get(&id).cloned());
+
+    let mut sources = Vec::new();
+    for src in db::list_sources(conn)? {
+        let paths = db::list_source_paths(conn, src.id)?
+            .into_iter()
+            .map(|p| ExportedPath {
+                path: p.path,
+                is_public: p.is_public,
+            })
+            .collect();
+        sources.push(ExportedSource {
+            password: seal(&src.password)?,
+            custom_headers: seal_headers(&src.custom_headers)?,
+            metadata: db::get_feed_metadata(conn, src.id)?,
+            properties: db::get_feed_properties(conn, src.id)?,
+            owner: owner(src.owner_id),
+            proxy_enabled: src.proxy_token.is_some(),
+            name: src.name,
+            // Credentials in URLs are never exported, encrypted or not.
+            caldav_url: redact::url(&src.caldav_url).into_owned(),
+            username: src.username,
+            ics_path: src.ics_path,
+            sync_interval_secs: src.sync_interval_secs,
+            public_ics: src.public_ics,
+            public_ics_path: src.public_ics_path,
+            attachment_mode: src.attachment_mode,
+            source_type: src.source_type,
+            archive_after_months: src.archive_after_months,
+            namespace_uids: src.namespace_uids,
+            sync_on_startup: src.sync_on_startup,
+            sync_window: src.sync_window,
+            working_hours: src.working_hours,
+            holiday_region: src.holiday_region,
+            repair_rules: src.repair_rules,
+            time_shift_zone: src.time_shift_zone,
+            time_shift_mode: src.time_shift_mode,
+            max_events: src.max_events,
+            max_feed_bytes: src.max_feed_bytes,
+            feed_limit_policy: src.feed_limit_policy,
+            fetch_mode: src.fetch_mode,
+            user_agent: src.user_agent,
+            paths,
+        });
+    }
+    let mut destinations = Vec::new();
+    for dest in db::list_destinations(conn)? {
+        let source = match dest.source_id {
+            Some(id) => db::get_source(conn, id)?.map(|s| s.ics_path),
+            None => None,
+        };
+        destinations.push(ExportedDestination {
+            source,
+            password: seal(&dest.password)?,
+            custom_headers: seal_headers(&dest.custom_headers)?,
+            owner: owner(dest.owner_id),
+            name: dest.name,
+            ics_url: redact::url(&dest.ics_url).into_owned(),
+            caldav_url: redact::url(&dest.caldav_url).into_owned(),
+            calendar_name: dest.calendar_name,
+            username: dest.username,
+            sync_interval_secs: dest.sync_interval_secs,
+            sync_all: dest.sync_all,
+            keep_local: dest.keep_local,
+            conflict_policy: dest.conflict_policy,
+            bidirectional: dest.bidirectional,
+            namespace_uids: dest.namespace_uids,
+            user_agent: dest.user_agent,
+            summary_template: dest.summary_template,
+            description_template: dest.description_template,
+            location_template: dest.location_template,
+            drop_properties: dest.drop_properties,
+            routes: dest.routes,
+            scheduling: dest.scheduling,
+            max_requests_per_second: dest.max_requests_per_second,
+            request_delay_ms: dest.request_delay_ms,
+            ics_password: seal(&dest.ics_password)?,
+            ics_headers: seal_headers(&dest.ics_headers)?,
+            ics_username: dest.ics_username,
+        });
+    }
+    Ok(ConfigExport {
+        version: FORMAT_VERSION,
+        encryption,
+        sources,
+        destinations,
+    })
+}
+
+// Re-importing the same document leaves the database unchanged. Entries missing
+// a password keep the stored one, so exports without credentials can be applied
+// on top of an existing instance.
+pub fn import(
+    conn: &Connection,
+    doc: &ConfigExport,
+    passphrase: Option<&str>,
+) -> Result<ImportSummary> {
+    ensure!(
+        doc.version == FORMAT_VERSION,
+        "Unsupported export version {}",
+        doc.version
+    );
+    let key = match &doc.encryption {
+        Some(enc) => {
+            ensure!(enc.cipher == CIPHER, "Unsupported cipher '{}'", enc.cipher);
+            let salt = STANDARD.decode(&enc.salt).context("Invalid salt")?;
+            match passphrase {
+                Some(p) => Some(derive_key(p, &salt)?),
+                None => None,
+            }
+        }
+        None => None,
+    };
+    let open = |password: &Option<String>| -> Result<String> {
+        match (password, &doc.encryption, &key) {
+            (None, _, _) => Ok(String::new()),
+            (Some(p), None, _) => Ok(p.clone()),
+            (Some(p), Some(_), Some(key)) => decrypt(key, p),
+            (Some(_), Some(_), None) => bail!("A passphrase is required to import credentials"),
+        }
+    };
+    let open_headers = |headers: &Option<CustomHeaders>| -> Result<Option<CustomHeaders>> {
+        let Some(headers) = headers else {
+            return Ok(None);
+        };
+        headers
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), open(&Some(value.clone()))?)))
+            .collect::<Result<_>>()
+            .map(Some)
+    };
+    let owner = |name: &Option<String>| -> Result<Option<i64>> {
+        match name {
+            Some(name) => match db::get_user_by_username(conn, name)? {
+                Some(user) => Ok(Some(user.id)),
+                None => bail!("Owner '{}' not found", name),
+            },
+            None => Ok(None),
+        }
+    };
+
+    let mut sources = Vec::new();
+    for src in &doc.sources {
+        sources.push(CreateSource {
+            name: src.name.clone(),
+            caldav_url: src.caldav_url.clone(),
+            username: src.username.clone(),
+            password: open(&src.password).with_context(|| format!("Source '{}'", src.ics_path))?,
+            ics_path: src.ics_path.clone(),
+            sync_interval_secs: src.sync_interval_secs,
+            public_ics: src.public_ics,
+            public_ics_path: src.public_ics_path.clone(),
+            attachment_mode: Some(src.attachment_mode.clone()),
+            source_type: Some(src.source_type.clone()),
+            proxy_enabled: Some(src.proxy_enabled),
+            owner_id: owner(&src.owner)?,
+            archive_after_months: src.archive_after_months,
+            namespace_uids: src.namespace_uids,
+            sync_on_startup: Some(src.sync_on_startup.clone()),
+            fetch_mode: Some(src.fetch_mode.clone()),
+            user_agent: Some(src.user_agent.clone()),
+            custom_headers: open_headers(&src.custom_headers)
+                .with_context(|| format!("Source '{}'", src.ics_path))?,
+            sync_window: Some(src.sync_window.clone()),
+            working_hours: Some(src.working_hours.clone()),
+            holiday_region: Some(src.holiday_region.clone()),
+            repair_rules: Some(src.repair_rules.clone()),
+            time_shift_zone: Some(src.time_shift_zone.clone()),
+            time_shift_mode: Some(src.time_shift_mode.clone()),
+            max_events: src.max_events,
+            max_feed_bytes: src.max_feed_bytes,
+            feed_limit_policy: Some(src.feed_limit_policy.clone()),
+        });
+    }
+    let tx = conn.unchecked_transaction()?;
+    // Sources go first so destinations can refer to them by ICS path.
+    db::upsert_declared(&tx, &sources, &[])?;
+    let mut destinations = Vec::new();
+    for dest in &doc.destinations {
+        let source_id = match &dest.source {
+            Some(path) => Some(
+                tx.query_row(
+                    "SELECT id FROM sources WHERE ics_path = ?1",
+                    [path],
+                    |row| row.get(0),
+                )
+                .with_context(|| format!("Destination '{}': source '{}'", dest.name, path))?,
+            ),
+            None => None,
+        };
+        destinations.push(CreateDestination {
+            name: dest.name.clone(),
+            ics_url: dest.ics_url.clone(),
+            source_id,
+            caldav_url: dest.caldav_url.clone(),
+            calendar_name: dest.calendar_name.clone(),
+            username: dest.username.clone(),
+            password: open(&dest.password)
+                .with_context(|| format!("Destination '{}'", dest.name))?,
+            sync_interval_secs: dest.sync_interval_secs,
+            sync_all: dest.sync_all,
+            keep_local: dest.keep_local,
+            owner_id: owner(&dest.owner)?,
+            conflict_policy: Some(dest.conflict_policy.clone()),
+            bidirectional: dest.bidirectional,
+            namespace_uids: dest.namespace_uids,
+            user_agent: Some(dest.user_agent.clone()),
+            custom_headers: open_headers(&dest.custom_headers)
+                .with_context(|| format!("Destination '{}'", dest.name))?,
+            summary_template: Some(dest.summary_template.clone()),
+            description_template: Some(dest.description_template.clone()),
+            location_template: Some(dest.location_template.clone()),
+            drop_properties: Some(dest.drop_properties.clone()),
+            routes: Some(dest.routes.clone()),
+            scheduling: Some(dest.scheduling.clone()),
+            max_requests_per_second: Some(dest.max_requests_per_second),
+            request_delay_ms: Some(dest.request_delay_ms),
+            ics_username: Some(dest.ics_username.clone()),
+            ics_password: Some(
+                open(&dest.ics_password).with_context(|| format!("Destination '{}'", dest.name))?,
+            ),
+            ics_headers: open_headers(&dest.ics_headers)
+                .with_context(|| format!("Destination '{}'", dest.name))?,
+        });
+    }
+
+    db::upsert_declared(&tx, &[], &destinations)?;
+    let mut summary = ImportSummary {
+        sources: sources.len(),
+        destinations: destinations.len(),
+        paths: 0,
+    };
+    for src in &doc.sources {
+        let source_id: i64 = tx.query_row(
+            "SELECT id FROM sources WHERE ics_path = ?1",
+            [&src.ics_path],
+            |row| row.get(0),
+        )?;
+        let existing = db::list_source_paths(&tx, source_id)?;
+        for path in &src.paths {
+            let result = match existing.iter().find(|p| p.path == path.path.trim()) {
+                Some(p) => db::update_source_path(
+                    &tx,
+                    p.id,
+                    &UpdateSourcePath {
+                        path: None,
+                        is_public: Some(path.is_public),
+                    },
+                )
+                .map(|_| ()),
+                None => db::create_source_path(
+                    &tx,
+                    source_id,
+                    &CreateSourcePath {
+                        path: path.path.clone(),
+                        is_public: path.is_public,
+                    },
+                )
+                .map(|_| ()),
+            };
+            result.with_context(|| format!("Path '{}' of source '{}'", path.path, src.ics_path))?;
+            summary.paths += 1;
+        }
+        if db::get_feed_metadata(&tx, source_id)? != src.metadata {
+            db::set_feed_metadata(&tx, source_id, &src.metadata)
+                .with_context(|| format!("Metadata of source '{}'", src.ics_path))?;
+        }
+        if db::get_feed_properties(&tx, source_id)? != src.properties {
+            db::set_feed_properties(&tx, source_id, &src.properties)
+                .with_context(|| format!("Properties of source '{}'", src.ics_path))?;
+        }
+    }
+    tx.commit()?;
+    Ok(summary)
+}
+
+fn yaml_scalar(value: &serde_json::Value) -> String {
+    // JSON strings are valid double-quoted YAML scalars.
+    value.to_string()
+}
+
+fn yaml_key(key: &str) -> String {
+    match !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        true => key.to_owned(),
+        false => serde_json::Value::from(key).to_string(),
+    }
+}
+
+fn write_yaml(value: &serde_json::Value, indent: usize, out: &mut String) {
+    use serde_json::Value;
+    let pad = " ".repeat(indent);
+    let is_block = |v: &Value| match v {
+        Value::Object(m) => !m.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        _ => false,
+    };
+    let inline = |v: &Value| match v {
+        Value::Object(_) => "{}".to_owned(),
+        Value::Array(_) => "[]".to_owned(),
+        v => yaml_scalar(v),
+    };
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map {
+                match is_block(v) {
+                    true => {
+                        out.push_str(&format!("{}{}:\n", pad, yaml_key(k)));
+                        write_yaml(v, indent + 2, out);
+                    }
+                    false => out.push_str(&format!("{}{}: {}\n", pad, yaml_key(k), inline(v))),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                match is_block(item) {
+                    true => {
+                        // Render the item one level deeper, then hang its first line on the dash.
+                        let mut nested = String::new();
+                        write_yaml(item, indent + 2, &mut nested);
+                        out.push_str(&pad);
+                        out.push_str("- ");
+                        out.push_str(&nested[indent + 2..]);
+                    }
+                    false => out.push_str(&format!("{}- {}\n", pad, inline(item))),
+                }
+            }
+        }
+        v => out.push_str(&format!("{}{}\n", pad, yaml_scalar(v))),
+    }
+}
+
+pub fn to_yaml(doc: &ConfigExport) -> Result<String> {
+    let mut out = String::new();
+    write_yaml(&serde_json::to_value(doc)?, 0, &mut out);
+    Ok(out)
+}
+
+pub fn from_yaml(text: &str) -> Result<ConfigExport> {
+    config::Config::builder()
+        .add_source(config::File::from_str(text, config::FileFormat::Yaml))
+        .build()?
+        .try_deserialize()
+        .context("Invalid YAML export")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credentials_round_trip_only_with_the_right_passphrase() {
+        let key = derive_key("hunter2", b"0123456789abcdef").unwrap();
+        let sealed = encrypt(&key, &SystemRandom::new(), "secret").unwrap();
+        assert!(!sealed.contains("secret"));
+        assert_eq!(decrypt(&key, &sealed).unwrap(), "secret");
+
+        let wrong = derive_key("hunter3", b"0123456789abcdef").unwrap();
+        assert!(decrypt(&wrong, &sealed).is_err());
+    }
+}
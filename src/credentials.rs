@@ -0,0 +1,83 @@
+use anyhow::{Context, Result, bail};
+
+// Stored passwords can point at the secret instead of holding it, e.g.
+// `file:///run/secrets/caldav_pass` or `env:CALDAV_PASS_WORK`. References are
+// resolved each time a sync needs them, so rotated secrets are picked up.
+const FILE_PREFIX: &str = "file://";
+const ENV_PREFIX: &str = "env:";
+
+enum Secret<'a> {
+    Literal(&'a str),
+    File(&'a str),
+    Env(&'a str),
+}
+
+fn parse(value: &str) -> Secret<'_> {
+    if let Some(path) = value.strip_prefix(FILE_PREFIX) {
+        Secret::File(path)
+    } else if let Some(name) = value.strip_prefix(ENV_PREFIX) {
+        Secret::Env(name)
+    } else {
+        Secret::Literal(value)
+    }
+}
+
+// What's wrong with a malformed reference, for request validation. Whether the
+// file or variable exists is only checked at sync time.
+pub fn reference_problem(value: &str) -> Option<&'static str> {
+    match parse(value) {
+        Secret::File(path) if !path.starts_with('/') => {
+            Some("file reference must be an absolute path, e.g. file:///run/secrets/name")
+        }
+        Secret::Env(name) if name.is_empty() || name.contains(['=', '\0']) => {
+            Some("env reference must name a variable, e.g. env:CALDAV_PASS")
+        }
+        _ => None,
+    }
+}
+
+// Errors name the file or variable but never include its contents.
+pub fn resolve(value: &str) -> Result<String> {
+    let secret = match parse(value) {
+        Secret::Literal(s) => return Ok(s.to_owned()),
+        Secret::File(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read password file '{}'", path))?,
+        Secret::Env(name) => std::env::var(name)
+            .with_context(|| format!("Password variable '{}' is not set", name))?,
+    };
+    // Secret files usually end with a newline that isn't part of the password.
+    let secret = secret.trim_end_matches(['\r', '\n']);
+    if secret.is_empty() {
+        bail!("Password reference '{}' is empty", value);
+    }
+    Ok(secret.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_literals_files_and_env() {
+        assert_eq!(resolve("hunter2").unwrap(), "hunter2");
+
+        let path = std::env::temp_dir().join(format!("caldav-pass-{}", std::process::id()));
+        std::fs::write(&path, "from-file\n").unwrap();
+        let reference = format!("file://{}", path.display());
+        assert_eq!(resolve(&reference).unwrap(), "from-file");
+        std::fs::remove_file(&path).unwrap();
+        let err = resolve(&reference).unwrap_err().to_string();
+        assert!(err.contains("Failed to read password file"), "{}", err);
+
+        // Cargo sets this for the test process.
+        assert_eq!(
+            resolve("env:CARGO_PKG_NAME").unwrap(),
+            env!("CARGO_PKG_NAME")
+        );
+        assert!(resolve("env:CALDAV_ICS_SYNC_UNSET_VARIABLE").is_err());
+
+        assert!(reference_problem("file://relative/path").is_some());
+        assert!(reference_problem("env:").is_some());
+        assert!(reference_problem("env:CALDAV_PASS").is_none());
+    }
+}
@@ -0,0 +1,400 @@
+use anyhow::{Context, Result, bail, ensure};
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api::sync;
+use crate::feed_metadata::fold;
+use crate::ics_text::escape_text;
+use crate::time_shift::parse_zone;
+
+// Turns a spreadsheet exported as CSV into events, for destinations fed by
+// people who keep their calendars in Excel. The first row names the columns;
+// each mapping picks a column by its header, ignoring case:
+//
+// - `summary` and `start` are required, `end`, `location`, `description`
+//   and `uid` optional.
+// - Dates are ISO 8601 (`2026-03-10`, `2026-03-10 09:00`, RFC 3339 with an
+//   offset) unless `date_format` gives a chrono format. A date without a
+//   time makes an all-day event, and its end date counts inclusively.
+// - Times without an offset are in `timezone`, or floating without one.
+// - Rows without a `uid` get one from their summary, start and location, so
+//   importing the same sheet again updates its events instead of adding
+//   copies.
+//
+// Rows that can't be read are skipped and reported with their line number.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CsvMapping {
+    #[serde(default = "default_summary")]
+    pub summary: String,
+    #[serde(default = "default_start")]
+    pub start: String,
+    #[serde(default = "default_end")]
+    pub end: String,
+    #[serde(default = "default_location")]
+    pub location: String,
+    #[serde(default = "default_description")]
+    pub description: String,
+    #[serde(default = "default_uid")]
+    pub uid: String,
+    pub timezone: Option<String>,
+    pub date_format: Option<String>,
+    // A single character; `;` for spreadsheets saved in European locales.
+    pub delimiter: Option<String>,
+}
+
+fn default_summary() -> String {
+    "summary".into()
+}
+
+fn default_start() -> String {
+    "start".into()
+}
+
+fn default_end() -> String {
+    "end".into()
+}
+
+fn default_location() -> String {
+    "location".into()
+}
+
+fn default_description() -> String {
+    "description".into()
+}
+
+fn default_uid() -> String {
+    "uid".into()
+}
+
+impl Default for CsvMapping {
+    fn default() -> Self {
+        Self {
+            summary: default_summary(),
+            start: default_start(),
+            end: default_end(),
+            location: default_location(),
+            description: default_description(),
+            uid: default_uid(),
+            timezone: None,
+            date_format: None,
+            delimiter: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RowError {
+    // The line the row starts on, counting the header as line 1.
+    pub line: usize,
+    pub error: String,
+}
+
+#[derive(Debug)]
+pub struct CsvEvents {
+    pub ics: String,
+    pub rows: usize,
+    pub errors: Vec<RowError>,
+}
+
+const MAX_ROWS: usize = 10_000;
+const DATE_TIME_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+// Records with the line each starts on. Quoted fields may hold delimiters,
+// doubled quotes and line breaks.
+fn records(text: &str, delimiter: char) -> Result<Vec<(usize, Vec<String>)>> {
+    let mut out = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let (mut line, mut start_line) = (1, 1);
+    let mut quoted = false;
+    let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                out.push((start_line, std::mem::take(&mut record)));
+                line += 1;
+                start_line = line;
+            }
+            c if c == delimiter && !quoted => record.push(std::mem::take(&mut field)),
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    ensure!(
+        !quoted,
+        "Unclosed quote in the row starting on line {}",
+        start_line
+    );
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        out.push((start_line, record));
+    }
+    // Blank lines, such as the trailing ones spreadsheets leave.
+    out.retain(|(_, r)| r.iter().any(|f| !f.trim().is_empty()));
+    Ok(out)
+}
+
+enum When {
+    Date(NaiveDate),
+    Local(NaiveDateTime),
+    Utc(DateTime<Utc>),
+}
+
+fn parse_when(value: &str, format: Option<&str>) -> Result<When> {
+    let value = value.trim();
+    if let Some(format) = format {
+        if let Ok(t) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(When::Local(t));
+        }
+        if let Ok(d) = NaiveDate::parse_from_str(value, format) {
+            return Ok(When::Date(d));
+        }
+        bail!("'{}' does not match the date format '{}'", value, format);
+    }
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Ok(When::Utc(t.with_timezone(&Utc)));
+    }
+    if let Some(t) = DATE_TIME_FORMATS
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(value, f).ok())
+    {
+        return Ok(When::Local(t));
+    }
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(d) => Ok(When::Date(d)),
+        Err(_) => bail!("'{}' is not a date", value),
+    }
+}
+
+fn property(name: &str, when: &When, tz: Option<Tz>) -> String {
+    match (when, tz) {
+        (When::Date(d), _) => format!("{};VALUE=DATE:{}\r\n", name, d.format("%Y%m%d")),
+        (When::Utc(t), _) => format!("{}:{}\r\n", name, t.format("%Y%m%dT%H%M%SZ")),
+        (When::Local(t), Some(tz)) => {
+            format!(
+                "{};TZID={}:{}\r\n",
+                name,
+                tz.name(),
+                t.format("%Y%m%dT%H%M%S")
+            )
+        }
+        (When::Local(t), None) => format!("{}:{}\r\n", name, t.format("%Y%m%dT%H%M%S")),
+    }
+}
+
+fn stamp(when: &When) -> String {
+    match when {
+        When::Date(d) => d.format("%Y%m%dT000000Z").to_string(),
+        When::Local(t) => t.format("%Y%m%dT%H%M%SZ").to_string(),
+        When::Utc(t) => t.format("%Y%m%dT%H%M%SZ").to_string(),
+    }
+}
+
+struct Columns {
+    summary: usize,
+    start: usize,
+    end: Option<usize>,
+    location: Option<usize>,
+    description: Option<usize>,
+    uid: Option<usize>,
+}
+
+fn vevent(row: &[String], cols: &Columns, mapping: &CsvMapping, tz: Option<Tz>) -> Result<String> {
+    let cell = |i: Option<usize>| {
+        i.and_then(|i| row.get(i))
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+    };
+    let summary = cell(Some(cols.summary)).context("No summary")?;
+    let start_text = cell(Some(cols.start)).context("No start")?;
+    let format = mapping.date_format.as_deref();
+    let start = parse_when(start_text, format)?;
+    let end = match cell(cols.end) {
+        Some(text) => Some(match (parse_when(text, format)?, &start) {
+            (When::Date(end), When::Date(start)) => {
+                ensure!(end >= *start, "Ends before it starts");
+                When::Date(end + Days::new(1))
+            }
+            (When::Date(_), _) | (_, When::Date(_)) => {
+                bail!("Start and end must both be dates or both have times")
+            }
+            (When::Local(end), When::Local(start)) if end < *start => {
+                bail!("Ends before it starts")
+            }
+            (When::Utc(end), When::Utc(start)) if end < *start => bail!("Ends before it starts"),
+            (end, _) => end,
+        }),
+        None => None,
+    };
+    let location = cell(cols.location);
+    let uid = match cell(cols.uid) {
+        Some(uid) => uid.to_string(),
+        None => {
+            let key = format!("{}\n{}\n{}", summary, start_text, location.unwrap_or(""));
+            format!(
+                "{}@csv.caldav-ics-sync",
+                &crate::db::content_hash(&key)[..16]
+            )
+        }
+    };
+    let mut out = format!(
+        "BEGIN:VEVENT\r\n{}DTSTAMP:{}\r\n",
+        fold(&format!("UID:{}", escape_text(&uid))),
+        stamp(&start)
+    );
+    out.push_str(&property("DTSTART", &start, tz));
+    if let Some(end) = &end {
+        out.push_str(&property("DTEND", end, tz));
+    }
+    out.push_str(&fold(&format!("SUMMARY:{}", escape_text(summary))));
+    if let Some(location) = location {
+        out.push_str(&fold(&format!("LOCATION:{}", escape_text(location))));
+    }
+    if let Some(description) = cell(cols.description) {
+        out.push_str(&fold(&format!("DESCRIPTION:{}", escape_text(description))));
+    }
+    out.push_str("END:VEVENT\r\n");
+    Ok(out)
+}
+
+pub fn to_ics(text: &str, mapping: &CsvMapping) -> Result<CsvEvents> {
+    let delimiter = match mapping.delimiter.as_deref() {
+        None | Some("") => ',',
+        Some(d) => {
+            let mut chars = d.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c != '"' && c != '\n' => c,
+                _ => bail!("Delimiter must be a single character"),
+            }
+        }
+    };
+    let tz = match mapping.timezone.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(zone) => Some(parse_zone(zone)?),
+    };
+    let mut rows = records(text, delimiter)?.into_iter();
+    let Some((_, header)) = rows.next() else {
+        bail!("The CSV file is empty");
+    };
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name.trim()))
+    };
+    let cols = Columns {
+        summary: column(&mapping.summary)
+            .with_context(|| format!("No '{}' column for the summary", mapping.summary))?,
+        start: column(&mapping.start)
+            .with_context(|| format!("No '{}' column for the start", mapping.start))?,
+        end: column(&mapping.end),
+        location: column(&mapping.location),
+        description: column(&mapping.description),
+        uid: column(&mapping.uid),
+    };
+    let rows: Vec<_> = rows.collect();
+    ensure!(
+        !rows.is_empty(),
+        "The CSV file has no rows below its header"
+    );
+    ensure!(
+        rows.len() <= MAX_ROWS,
+        "At most {} rows can be imported at once",
+        MAX_ROWS
+    );
+    let mut events = Vec::new();
+    let mut errors = Vec::new();
+    for (line, row) in &rows {
+        match vevent(row, &cols, mapping, tz) {
+            Ok(event) => events.push(event),
+            Err(e) => errors.push(RowError {
+                line: *line,
+                error: e.to_string(),
+            }),
+        }
+    }
+    if events.is_empty() {
+        bail!(
+            "No row could be imported; line {}: {}",
+            errors[0].line,
+            errors[0].error
+        );
+    }
+    Ok(CsvEvents {
+        ics: sync::build_calendar(&events),
+        rows: rows.len(),
+        errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "\u{feff}Title,Start,End,Room,Notes\r\n\
+        Fire drill,2026-03-10 09:00,2026-03-10 09:30,\"Hall A, ground floor\",\"Bring \"\"badges\"\"\nand keys\"\r\n\
+        Boiler service,2026-03-12,2026-03-13,,\r\n\
+        \r\n\
+        Broken,next tuesday,,,\r\n";
+
+    fn mapping() -> CsvMapping {
+        CsvMapping {
+            summary: "title".into(),
+            start: "START".into(),
+            location: "Room".into(),
+            description: "notes".into(),
+            timezone: Some("Europe/Berlin".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn converts_rows_and_reports_bad_ones() {
+        let out = to_ics(CSV, &mapping()).unwrap();
+        assert_eq!(out.rows, 3);
+        let events = sync::split_vevents(&out.ics);
+        assert_eq!(events.len(), 2);
+        assert!(events[0].contains("DTSTART;TZID=Europe/Berlin:20260310T090000\r\n"));
+        assert!(events[0].contains("DTEND;TZID=Europe/Berlin:20260310T093000\r\n"));
+        assert!(events[0].contains("LOCATION:Hall A\\, ground floor\r\n"));
+        assert!(events[0].contains("DESCRIPTION:Bring \"badges\"\\nand keys\r\n"));
+        // The end date of an all-day row is inclusive.
+        assert!(events[1].contains("DTSTART;VALUE=DATE:20260312\r\nDTEND;VALUE=DATE:20260314\r\n"));
+        assert_eq!(out.errors.len(), 1);
+        assert_eq!(out.errors[0].line, 6);
+        // UIDs are stable across imports.
+        assert_eq!(to_ics(CSV, &mapping()).unwrap().ics, out.ics);
+    }
+
+    #[test]
+    fn honours_delimiter_and_date_format() {
+        let csv = "summary;start;end\nStandup;10.03.2026 09:00;10.03.2026 09:15\n";
+        let mapping = CsvMapping {
+            delimiter: Some(";".into()),
+            date_format: Some("%d.%m.%Y %H:%M".into()),
+            ..Default::default()
+        };
+        let out = to_ics(csv, &mapping).unwrap();
+        assert!(out.ics.contains("DTSTART:20260310T090000\r\n"));
+        assert!(to_ics("title,when\nx,2026-03-10\n", &CsvMapping::default()).is_err());
+        assert!(to_ics("summary,start\nx,later\n", &CsvMapping::default()).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::{Context, Result, anyhow};
+use reqwest::Url;
+
+// Resolves an href from a multistatus against the collection it was listed
+// under. Hrefs can be absolute URLs, absolute paths, or paths relative to the
+// collection, and keep their percent-encoding. Servers behind a reverse proxy
+// on a subpath (Radicale without X-Script-Name) answer with paths missing the
+// prefix: `/user/cal/` for a collection at `/radicale/user/`. Those get the
+// prefix back.
+pub fn resolve_href(base_url: &str, href: &str) -> Result<String> {
+    let mut base =
+        Url::parse(base_url).with_context(|| format!("Invalid CalDAV URL '{}'", base_url))?;
+    if !base.path().ends_with('/') {
+        let path = format!("{}/", base.path());
+        base.set_path(&path);
+    }
+    let href = href.trim();
+    let resolved = match href.starts_with('/') && !href.starts_with("//") {
+        true => base.join(&format!("{}{}", proxy_prefix(base.path(), href), href)),
+        false => base.join(href),
+    };
+    Ok(resolved
+        .with_context(|| format!("Invalid href '{}' from {}", href, base_url))?
+        .into())
+}
+
+// The part of `base_path` in front of where `href` starts to match it, when
+// `href` isn't already under it.
+fn proxy_prefix<'a>(base_path: &'a str, href: &str) -> &'a str {
+    if href.starts_with(base_path) {
+        return "";
+    }
+    let Some(first) = href.split('/').find(|s| !s.is_empty()) else {
+        return "";
+    };
+    match base_path.find(&format!("/{}/", first)) {
+        Some(at) if at > 0 => &base_path[..at],
+        _ => "",
+    }
+}
+
+// `name` as one path segment under `collection`, percent-encoded.
+pub fn child_url(collection: &str, name: &str) -> Result<String> {
+    let mut url =
+        Url::parse(collection).with_context(|| format!("Invalid CalDAV URL '{}'", collection))?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("'{}' cannot have child paths", collection))?
+        .pop_if_empty()
+        .push(name);
+    Ok(url.into())
+}
+
+// `name` percent-encoded as a single path segment.
+pub fn encode_segment(name: &str) -> String {
+    let mut url = Url::parse("http://localhost/").expect("static URL");
+    url.path_segments_mut().expect("static URL").push(name);
+    url.path()[1..].to_owned()
+}
+
+// `child_url` for a sub-collection, with the trailing slash collections take.
+pub fn collection_url(parent: &str, name: &str) -> Result<String> {
+    Ok(format!("{}/", child_url(parent, name)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_hrefs_against_the_collection() {
+        let base = "http://dav.example.com:5232/cal";
+        assert_eq!(
+            resolve_href(base, "/cal/a%20b.ics").unwrap(),
+            "http://dav.example.com:5232/cal/a%20b.ics"
+        );
+        assert_eq!(
+            resolve_href(base, "1.ics").unwrap(),
+            "http://dav.example.com:5232/cal/1.ics"
+        );
+        assert_eq!(
+            resolve_href(base, "https://other.example.com:8443/x/").unwrap(),
+            "https://other.example.com:8443/x/"
+        );
+        assert_eq!(
+            resolve_href("http://host/radicale/user/", "/user/work/").unwrap(),
+            "http://host/radicale/user/work/"
+        );
+        assert_eq!(
+            resolve_href("http://host/dav/principals/me/", "/dav/calendars/me/").unwrap(),
+            "http://host/dav/calendars/me/"
+        );
+    }
+
+    #[test]
+    fn encodes_child_segments() {
+        assert_eq!(
+            child_url("http://host:8080/cal/", "a b#1@x.ics").unwrap(),
+            "http://host:8080/cal/a%20b%231@x.ics"
+        );
+        assert_eq!(encode_segment("🎉@x.ics"), "%F0%9F%8E%89@x.ics");
+        assert_eq!(
+            collection_url("http://host/dav", "work").unwrap(),
+            "http://host/dav/work/"
+        );
+    }
+}
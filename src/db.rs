@@ -1,8 +1,29 @@
-use anyhow::{Result, ensure};
-use rusqlite::{Connection, params};
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result, ensure};
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, params};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::auto_sync::AutoSyncKey;
+use crate::availability::WorkingHours;
+use crate::calendar_routes::{self, CalendarRoutes};
+use crate::event_template;
+use crate::feed_limits;
+use crate::feed_metadata::{self, FeedMetadata, FeedProperties};
+use crate::feed_store;
+use crate::feed_urls::FeedUrls;
+use crate::holidays;
+use crate::http_clients::{self, CustomHeaders};
+use crate::ics_repair;
+use crate::redact;
+use crate::scheduling;
+use crate::sync_window::SyncWindow;
+use crate::throttle;
+use crate::time_shift;
+use sha2::{Digest, Sha256};
+
 fn require_non_empty(field: &str, value: &str) -> Result<()> {
     ensure!(!value.trim().is_empty(), "{} cannot be empty", field);
     Ok(())
@@ -13,14 +34,13 @@ fn require_non_negative(field: &str, value: i64) -> Result<()> {
     Ok(())
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+// Never serialized directly; the API returns `api::sources::SourceView`.
+#[derive(Clone)]
 pub struct Source {
     pub id: i64,
     pub name: String,
     pub caldav_url: String,
     pub username: String,
-    #[serde(skip_serializing)]
-    #[schema(write_only)]
     pub password: String,
     pub ics_path: String,
     pub sync_interval_secs: i64,
@@ -30,31 +50,115 @@ pub struct Source {
     pub created_at: String,
     pub public_ics: bool,
     pub public_ics_path: Option<String>,
+    pub attachment_mode: String,
+    pub source_type: String,
+    pub proxy_token: Option<String>,
+    pub owner_id: Option<i64>,
+    pub archive_after_months: Option<i64>,
+    // Disabled sources keep serving their feed but are skipped by auto-sync and sync-all.
+    pub enabled: bool,
+    // Stamp events with this source's namespace; see `sync_origin`.
+    pub namespace_uids: bool,
+    // "default" follows SYNC_ON_STARTUP; "always" or "never" override it.
+    pub sync_on_startup: String,
+    // "query" reads each calendar with one calendar-query; "multiget" lists
+    // it and fetches only changed objects with calendar-multiget.
+    pub fetch_mode: String,
+    // Sent instead of the default User-Agent when not empty.
+    pub user_agent: String,
+    pub custom_headers: CustomHeaders,
+    // Daily hours scheduled syncs are limited to; see `sync_window`.
+    pub sync_window: String,
+    // Weekly hours published as VAVAILABILITY; see `availability`. Empty
+    // falls back to the owner's.
+    pub working_hours: String,
+    // Country or subdivision (`DE-BY`) of a holiday source; see `holidays`.
+    pub holiday_region: String,
+    // Comma-separated fixes for broken upstream events; see `ics_repair`.
+    pub repair_rules: String,
+    // IANA zone events are labelled or shifted into; see `time_shift`.
+    pub time_shift_zone: String,
+    pub time_shift_mode: String,
+    // Caps on the published feed, and what to do past them; see `feed_limits`.
+    pub max_events: Option<i64>,
+    pub max_feed_bytes: Option<i64>,
+    pub feed_limit_policy: String,
+    // Parts the feed is served in with the `split` policy, else 0.
+    pub feed_parts: i64,
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
+#[derive(Debug, Default, Deserialize, ToSchema)]
 pub struct CreateSource {
     pub name: String,
+    #[serde(default)]
     pub caldav_url: String,
+    #[serde(default)]
     pub username: String,
+    #[serde(default)]
     pub password: String,
     pub ics_path: String,
+    #[serde(deserialize_with = "crate::units::duration_secs")]
     pub sync_interval_secs: i64,
     #[serde(default)]
     pub public_ics: bool,
     pub public_ics_path: Option<String>,
+    pub attachment_mode: Option<String>,
+    pub source_type: Option<String>,
+    pub proxy_enabled: Option<bool>,
+    pub owner_id: Option<i64>,
+    pub archive_after_months: Option<i64>,
+    #[serde(default)]
+    pub namespace_uids: bool,
+    pub sync_on_startup: Option<String>,
+    pub fetch_mode: Option<String>,
+    pub user_agent: Option<String>,
+    pub custom_headers: Option<CustomHeaders>,
+    pub sync_window: Option<String>,
+    pub working_hours: Option<String>,
+    pub holiday_region: Option<String>,
+    pub repair_rules: Option<String>,
+    pub time_shift_zone: Option<String>,
+    pub time_shift_mode: Option<String>,
+    pub max_events: Option<i64>,
+    pub max_feed_bytes: Option<i64>,
+    pub feed_limit_policy: Option<String>,
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
+#[derive(Debug, Default, Deserialize, ToSchema)]
 pub struct UpdateSource {
     pub name: Option<String>,
     pub caldav_url: Option<String>,
     pub username: Option<String>,
     pub password: Option<String>,
     pub ics_path: Option<String>,
+    #[serde(default, deserialize_with = "crate::units::opt_duration_secs")]
     pub sync_interval_secs: Option<i64>,
     pub public_ics: Option<bool>,
     pub public_ics_path: Option<String>,
+    pub attachment_mode: Option<String>,
+    pub source_type: Option<String>,
+    pub proxy_enabled: Option<bool>,
+    pub owner_id: Option<i64>,
+    pub archive_after_months: Option<i64>,
+    pub namespace_uids: Option<bool>,
+    pub sync_on_startup: Option<String>,
+    pub fetch_mode: Option<String>,
+    pub user_agent: Option<String>,
+    // Values sent back masked keep the stored ones.
+    pub custom_headers: Option<CustomHeaders>,
+    // An empty string removes the window.
+    pub sync_window: Option<String>,
+    pub working_hours: Option<String>,
+    pub holiday_region: Option<String>,
+    // An empty string turns repairs off.
+    pub repair_rules: Option<String>,
+    // An empty string turns the time shift off.
+    pub time_shift_zone: Option<String>,
+    pub time_shift_mode: Option<String>,
+    // 0 removes the limit.
+    pub max_events: Option<i64>,
+    pub max_feed_bytes: Option<i64>,
+    pub feed_limit_policy: Option<String>,
 }
 
 pub fn init_db(conn: &Connection) -> Result<()> {
@@ -121,54 +225,466 @@ pub fn init_db(conn: &Connection) -> Result<()> {
             created_at TEXT NOT NULL DEFAULT (datetime('now'))
         );",
     )?;
+    let _ = conn.execute_batch(
+        "ALTER TABLE sources ADD COLUMN attachment_mode TEXT NOT NULL DEFAULT 'keep';",
+    );
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS attachments (
+            source_id INTEGER NOT NULL REFERENCES sources(id) ON DELETE CASCADE,
+            hash TEXT NOT NULL,
+            fmttype TEXT,
+            data BLOB NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (source_id, hash)
+        );",
+    )?;
+    let _ = conn.execute_batch(
+        "ALTER TABLE sources ADD COLUMN source_type TEXT NOT NULL DEFAULT 'caldav';",
+    );
+    let _ = conn.execute_batch("ALTER TABLE sources ADD COLUMN proxy_token TEXT;");
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL UNIQUE,
+            password_hash TEXT NOT NULL,
+            is_admin INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );",
+    )?;
+    let _ = conn.execute_batch(
+        "ALTER TABLE sources ADD COLUMN owner_id INTEGER REFERENCES users(id) ON DELETE SET NULL;",
+    );
+    let _ = conn.execute_batch(
+        "ALTER TABLE destinations ADD COLUMN owner_id INTEGER REFERENCES users(id) ON DELETE SET NULL;",
+    );
+    let _ = conn.execute_batch("ALTER TABLE users ADD COLUMN max_sources INTEGER;");
+    let _ = conn.execute_batch("ALTER TABLE users ADD COLUMN max_events INTEGER;");
+    let _ = conn.execute_batch("ALTER TABLE users ADD COLUMN max_feed_bytes_per_day INTEGER;");
+    if conn
+        .execute_batch("ALTER TABLE ics_data ADD COLUMN event_count INTEGER NOT NULL DEFAULT 0;")
+        .is_ok()
+    {
+        conn.execute_batch(
+            "UPDATE ics_data SET event_count =
+                (length(ics_content) - length(replace(ics_content, 'BEGIN:VEVENT', ''))) / 12;",
+        )?;
+    }
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS feed_usage (
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            day TEXT NOT NULL,
+            bytes INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (user_id, day)
+        );",
+    )?;
+    let _ = conn.execute_batch("ALTER TABLE sources ADD COLUMN archive_after_months INTEGER;");
+    let _ = conn.execute_batch("ALTER TABLE sources ADD COLUMN last_sync_duration_ms INTEGER;");
+    let _ =
+        conn.execute_batch("ALTER TABLE destinations ADD COLUMN last_sync_duration_ms INTEGER;");
+    let _ =
+        conn.execute_batch("ALTER TABLE sources ADD COLUMN enabled INTEGER NOT NULL DEFAULT 1;");
+    let _ = conn
+        .execute_batch("ALTER TABLE destinations ADD COLUMN enabled INTEGER NOT NULL DEFAULT 1;");
+    let _ = conn.execute_batch("ALTER TABLE ics_data ADD COLUMN content_hash TEXT;");
+    let _ = conn.execute_batch(
+        "ALTER TABLE destinations ADD COLUMN feed_hash TEXT;
+         ALTER TABLE destinations ADD COLUMN feed_etag TEXT;",
+    );
+    let _ = conn.execute_batch(
+        "ALTER TABLE destinations ADD COLUMN source_id INTEGER REFERENCES sources(id);",
+    );
+    let _ = conn.execute_batch(
+        "ALTER TABLE destinations ADD COLUMN conflict_policy TEXT NOT NULL DEFAULT 'overwrite';",
+    );
+    let _ = conn.execute_batch(
+        "ALTER TABLE destinations ADD COLUMN bidirectional INTEGER NOT NULL DEFAULT 0;",
+    );
+    let _ = conn.execute_batch(
+        "ALTER TABLE sources ADD COLUMN namespace_uids INTEGER NOT NULL DEFAULT 0;
+         ALTER TABLE destinations ADD COLUMN namespace_uids INTEGER NOT NULL DEFAULT 0;",
+    );
+    let _ = conn.execute_batch(
+        "ALTER TABLE sources ADD COLUMN sync_on_startup TEXT NOT NULL DEFAULT 'default';",
+    );
+    let _ = conn
+        .execute_batch("ALTER TABLE sources ADD COLUMN fetch_mode TEXT NOT NULL DEFAULT 'query';");
+    let _ = conn.execute_batch(
+        "ALTER TABLE sources ADD COLUMN user_agent TEXT NOT NULL DEFAULT '';
+         ALTER TABLE sources ADD COLUMN custom_headers TEXT NOT NULL DEFAULT '{}';
+         ALTER TABLE destinations ADD COLUMN user_agent TEXT NOT NULL DEFAULT '';
+         ALTER TABLE destinations ADD COLUMN custom_headers TEXT NOT NULL DEFAULT '{}';",
+    );
+    let _ =
+        conn.execute_batch("ALTER TABLE sources ADD COLUMN sync_window TEXT NOT NULL DEFAULT '';");
+    let _ = conn.execute_batch(
+        "ALTER TABLE sources ADD COLUMN working_hours TEXT NOT NULL DEFAULT '';
+         ALTER TABLE users ADD COLUMN working_hours TEXT NOT NULL DEFAULT '';",
+    );
+    let _ = conn
+        .execute_batch("ALTER TABLE sources ADD COLUMN holiday_region TEXT NOT NULL DEFAULT '';");
+    let _ =
+        conn.execute_batch("ALTER TABLE sources ADD COLUMN repair_rules TEXT NOT NULL DEFAULT '';");
+    let _ = conn.execute_batch(
+        "ALTER TABLE sources ADD COLUMN time_shift_zone TEXT NOT NULL DEFAULT '';
+         ALTER TABLE sources ADD COLUMN time_shift_mode TEXT NOT NULL DEFAULT 'shift';",
+    );
+    let _ = conn.execute_batch(
+        "ALTER TABLE sources ADD COLUMN max_events INTEGER;
+         ALTER TABLE sources ADD COLUMN max_feed_bytes INTEGER;
+         ALTER TABLE sources ADD COLUMN feed_limit_policy TEXT NOT NULL DEFAULT 'truncate';",
+    );
+    let _ =
+        conn.execute_batch("ALTER TABLE sources ADD COLUMN feed_parts INTEGER NOT NULL DEFAULT 0;");
+    // Parts of split feeds, stored like the feed itself so they are served
+    // without splitting it on every request.
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS feed_part_data (
+            source_id INTEGER NOT NULL REFERENCES sources(id) ON DELETE CASCADE,
+            part INTEGER NOT NULL,
+            ics_content TEXT NOT NULL,
+            content_hash TEXT NOT NULL,
+            stored_in_file INTEGER NOT NULL DEFAULT 0,
+            rehosted_attachments INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (source_id, part)
+        );",
+    )?;
+    let _ = conn.execute_batch(
+        "ALTER TABLE destinations ADD COLUMN summary_template TEXT NOT NULL DEFAULT '';
+         ALTER TABLE destinations ADD COLUMN description_template TEXT NOT NULL DEFAULT '';
+         ALTER TABLE destinations ADD COLUMN location_template TEXT NOT NULL DEFAULT '';
+         ALTER TABLE destinations ADD COLUMN drop_properties TEXT NOT NULL DEFAULT '';",
+    );
+    let _ = conn
+        .execute_batch("ALTER TABLE destinations ADD COLUMN routes TEXT NOT NULL DEFAULT '[]';");
+    let _ = conn.execute_batch(
+        "ALTER TABLE destinations ADD COLUMN scheduling TEXT NOT NULL DEFAULT 'keep';",
+    );
+    let _ = conn.execute_batch("ALTER TABLE destinations ADD COLUMN last_sync_result TEXT;");
+    let _ = conn.execute_batch(
+        "ALTER TABLE destinations ADD COLUMN max_requests_per_second REAL NOT NULL DEFAULT 0;
+         ALTER TABLE destinations ADD COLUMN request_delay_ms INTEGER NOT NULL DEFAULT 0;",
+    );
+    let _ = conn.execute_batch(
+        "ALTER TABLE destinations ADD COLUMN ics_username TEXT NOT NULL DEFAULT '';
+         ALTER TABLE destinations ADD COLUMN ics_password TEXT NOT NULL DEFAULT '';
+         ALTER TABLE destinations ADD COLUMN ics_headers TEXT NOT NULL DEFAULT '{}';",
+    );
+    let _ = conn.execute_batch("ALTER TABLE destinations ADD COLUMN resolved_ics_url TEXT;");
+    let _ = conn.execute_batch(
+        "ALTER TABLE sources ADD COLUMN sync_failures INTEGER NOT NULL DEFAULT 0;
+         ALTER TABLE sources ADD COLUMN retry_after TEXT;
+         ALTER TABLE destinations ADD COLUMN sync_failures INTEGER NOT NULL DEFAULT 0;
+         ALTER TABLE destinations ADD COLUMN retry_after TEXT;",
+    );
+    // Large feeds live in a file named by content_hash; see `feed_store`.
+    let _ = conn.execute_batch(
+        "ALTER TABLE ics_data ADD COLUMN stored_in_file INTEGER NOT NULL DEFAULT 0;
+         ALTER TABLE ics_data ADD COLUMN rehosted_attachments INTEGER NOT NULL DEFAULT 0;",
+    );
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS destination_events (
+            destination_id INTEGER NOT NULL REFERENCES destinations(id) ON DELETE CASCADE,
+            uid TEXT NOT NULL,
+            etag TEXT,
+            summary TEXT,
+            conflict TEXT,
+            detected_at TEXT,
+            PRIMARY KEY (destination_id, uid)
+        );
+        CREATE TABLE IF NOT EXISTS destination_feeds (
+            destination_id INTEGER PRIMARY KEY REFERENCES destinations(id) ON DELETE CASCADE,
+            ics_content TEXT NOT NULL,
+            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );",
+    )?;
+    // Per-event progress of destination syncs: 'synced', 'pending_delete' or
+    // 'failed', with the error and how many runs in a row it failed.
+    let _ = conn.execute_batch(
+        "ALTER TABLE destination_events ADD COLUMN state TEXT NOT NULL DEFAULT 'synced';
+         ALTER TABLE destination_events ADD COLUMN error TEXT;
+         ALTER TABLE destination_events ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;",
+    );
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS archived_events (
+            source_id INTEGER NOT NULL REFERENCES sources(id) ON DELETE CASCADE,
+            event_key TEXT NOT NULL,
+            ends_at TEXT NOT NULL,
+            vevent TEXT NOT NULL,
+            archived_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (source_id, event_key)
+        );
+        CREATE TABLE IF NOT EXISTS source_calendars (
+            source_id INTEGER NOT NULL REFERENCES sources(id) ON DELETE CASCADE,
+            href TEXT NOT NULL,
+            events TEXT NOT NULL,
+            color TEXT,
+            calendar_order INTEGER,
+            synced_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (source_id, href)
+        );
+        CREATE TABLE IF NOT EXISTS source_objects (
+            source_id INTEGER NOT NULL REFERENCES sources(id) ON DELETE CASCADE,
+            calendar TEXT NOT NULL,
+            href TEXT NOT NULL,
+            etag TEXT NOT NULL,
+            calendar_data TEXT NOT NULL,
+            PRIMARY KEY (source_id, href)
+        );
+        CREATE TABLE IF NOT EXISTS discovered_calendars (
+            source_id INTEGER NOT NULL REFERENCES sources(id) ON DELETE CASCADE,
+            href TEXT NOT NULL,
+            displayname TEXT,
+            color TEXT,
+            calendar_order INTEGER,
+            components TEXT NOT NULL,
+            discovered_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (source_id, href)
+        );",
+    )?;
+    // Upstream calendar colors and order (Apple's calendar-color/calendar-order).
+    let _ = conn.execute_batch(
+        "ALTER TABLE source_calendars ADD COLUMN color TEXT;
+         ALTER TABLE source_calendars ADD COLUMN calendar_order INTEGER;",
+    );
+    let _ =
+        conn.execute_batch("ALTER TABLE discovered_calendars ADD COLUMN calendar_order INTEGER;");
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS feed_metadata (
+            source_id INTEGER NOT NULL REFERENCES sources(id) ON DELETE CASCADE,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (source_id, key)
+        );
+        CREATE TABLE IF NOT EXISTS feed_properties (
+            source_id INTEGER PRIMARY KEY REFERENCES sources(id) ON DELETE CASCADE,
+            name TEXT,
+            description TEXT,
+            timezone TEXT,
+            color TEXT,
+            prodid TEXT,
+            published_ttl_minutes INTEGER
+        );",
+    )?;
+    let _ = conn.execute_batch("ALTER TABLE feed_properties ADD COLUMN method TEXT;");
+    let index_existing = !conn
+        .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'events'")?
+        .exists([])?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            source_id INTEGER NOT NULL REFERENCES sources(id) ON DELETE CASCADE,
+            uid TEXT,
+            summary TEXT,
+            starts_at TEXT,
+            ends_at TEXT,
+            organizer TEXT,
+            description TEXT,
+            location TEXT,
+            calendar TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_events_source ON events(source_id);
+        CREATE INDEX IF NOT EXISTS idx_events_starts_at ON events(starts_at);
+        CREATE TABLE IF NOT EXISTS event_attendees (
+            event_id INTEGER NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+            email TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_event_attendees_email ON event_attendees(email);
+        CREATE INDEX IF NOT EXISTS idx_event_attendees_event ON event_attendees(event_id);",
+    )?;
+    // Description, location and calendar came later; existing indexes are
+    // rebuilt to fill them in.
+    let added_columns = conn
+        .execute_batch(
+            "ALTER TABLE events ADD COLUMN description TEXT;
+             ALTER TABLE events ADD COLUMN location TEXT;
+             ALTER TABLE events ADD COLUMN calendar TEXT;",
+        )
+        .is_ok();
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_events_source_uid ON events(source_id, uid);",
+    )?;
+    // Full-text index over the event text, kept in step with `events` by
+    // triggers. Prefix indexes make `dent*` queries cheap.
+    let fts_existing = conn
+        .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'events_fts'")?
+        .exists([])?;
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS events_fts USING fts5(
+            summary, description, location,
+            content = 'events', content_rowid = 'id',
+            tokenize = 'unicode61 remove_diacritics 2', prefix = '2 3'
+        );
+        CREATE TRIGGER IF NOT EXISTS events_fts_insert AFTER INSERT ON events BEGIN
+            INSERT INTO events_fts (rowid, summary, description, location)
+            VALUES (new.id, new.summary, new.description, new.location);
+        END;
+        CREATE TRIGGER IF NOT EXISTS events_fts_delete AFTER DELETE ON events BEGIN
+            INSERT INTO events_fts (events_fts, rowid, summary, description, location)
+            VALUES ('delete', old.id, old.summary, old.description, old.location);
+        END;
+        CREATE TRIGGER IF NOT EXISTS events_fts_update
+        AFTER UPDATE OF summary, description, location ON events BEGIN
+            INSERT INTO events_fts (events_fts, rowid, summary, description, location)
+            VALUES ('delete', old.id, old.summary, old.description, old.location);
+            INSERT INTO events_fts (rowid, summary, description, location)
+            VALUES (new.id, new.summary, new.description, new.location);
+        END;",
+    )?;
+    if index_existing || added_columns {
+        reindex_events(conn)?;
+    } else if !fts_existing {
+        conn.execute("INSERT INTO events_fts (events_fts) VALUES ('rebuild')", [])?;
+    }
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS sessions (
+            id TEXT PRIMARY KEY,
+            user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+            username TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );",
+    )?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+    )?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS sync_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            source_id INTEGER REFERENCES sources(id) ON DELETE CASCADE,
+            destination_id INTEGER REFERENCES destinations(id) ON DELETE CASCADE,
+            status TEXT NOT NULL,
+            error TEXT,
+            result TEXT,
+            finished_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_sync_runs_source ON sync_runs(source_id, id);
+        CREATE INDEX IF NOT EXISTS idx_sync_runs_destination ON sync_runs(destination_id, id);",
+    )?;
+    // Split feeds from before their parts were stored.
+    let unstored: Vec<i64> = conn
+        .prepare(
+            "SELECT id FROM sources WHERE feed_parts > 0
+             AND NOT EXISTS (SELECT 1 FROM feed_part_data p WHERE p.source_id = sources.id)",
+        )?
+        .query_map([], |row| row.get(0))?
+        .collect::<std::result::Result<_, _>>()?;
+    for id in unstored {
+        refresh_feed_parts(conn, id)?;
+    }
     Ok(())
 }
 
-pub fn list_sources(conn: &Connection) -> Result<Vec<Source>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, name, caldav_url, username, password, ics_path, sync_interval_secs, last_synced, last_sync_status, last_sync_error, created_at, public_ics, public_ics_path FROM sources ORDER BY id",
+pub fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+pub fn set_setting(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
     )?;
-    let rows = stmt.query_map([], |row| {
-        Ok(Source {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            caldav_url: row.get(2)?,
-            username: row.get(3)?,
-            password: row.get(4)?,
-            ics_path: row.get(5)?,
-            sync_interval_secs: row.get(6)?,
-            last_synced: row.get(7)?,
-            last_sync_status: row.get(8)?,
-            last_sync_error: row.get(9)?,
-            created_at: row.get(10)?,
-            public_ics: row.get(11)?,
-            public_ics_path: row.get(12)?,
-        })
-    })?;
+    Ok(())
+}
+
+const LEGACY_MIGRATED: &str = "legacy_env_migrated";
+
+// Turns the legacy single-source environment settings into a source, once:
+// a source deleted afterwards stays deleted even if the variables remain.
+// One already pointing at the same calendar is adopted instead of
+// duplicated. Returns the source when this run did the migration.
+pub fn migrate_legacy_source(conn: &Connection, src: &CreateSource) -> Result<Option<i64>> {
+    if get_setting(conn, LEGACY_MIGRATED)?.is_some() {
+        return Ok(None);
+    }
+    let tx = conn.unchecked_transaction()?;
+    let existing: Option<i64> = tx
+        .query_row(
+            "SELECT id FROM sources WHERE caldav_url = ?1 AND username = ?2 ORDER BY id LIMIT 1",
+            params![src.caldav_url, src.username],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let id = match existing {
+        Some(id) => id,
+        None => create_source(&tx, src).context("Migrating CALDAV_URL into a source")?,
+    };
+    set_setting(&tx, LEGACY_MIGRATED, &id.to_string())?;
+    tx.commit()?;
+    Ok(Some(id))
+}
+
+const SOURCE_COLUMNS: &str = "id, name, caldav_url, username, password, ics_path, sync_interval_secs, last_synced, last_sync_status, last_sync_error, created_at, public_ics, public_ics_path, attachment_mode, source_type, proxy_token, owner_id, archive_after_months, enabled, namespace_uids, sync_on_startup, fetch_mode, user_agent, custom_headers, sync_window, working_hours, holiday_region, repair_rules, time_shift_zone, time_shift_mode, max_events, max_feed_bytes, feed_limit_policy, feed_parts";
+
+fn map_source_row(row: &rusqlite::Row) -> rusqlite::Result<Source> {
+    Ok(Source {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        caldav_url: row.get(2)?,
+        username: row.get(3)?,
+        password: row.get(4)?,
+        ics_path: row.get(5)?,
+        sync_interval_secs: row.get(6)?,
+        last_synced: row.get(7)?,
+        last_sync_status: row.get(8)?,
+        last_sync_error: row.get(9)?,
+        created_at: row.get(10)?,
+        public_ics: row.get(11)?,
+        public_ics_path: row.get(12)?,
+        attachment_mode: row.get(13)?,
+        source_type: row.get(14)?,
+        proxy_token: row.get(15)?,
+        owner_id: row.get(16)?,
+        archive_after_months: row.get(17)?,
+        enabled: row.get(18)?,
+        namespace_uids: row.get(19)?,
+        sync_on_startup: row.get(20)?,
+        fetch_mode: row.get(21)?,
+        user_agent: row.get(22)?,
+        custom_headers: headers_column(row, 23)?,
+        sync_window: row.get(24)?,
+        working_hours: row.get(25)?,
+        holiday_region: row.get(26)?,
+        repair_rules: row.get(27)?,
+        time_shift_zone: row.get(28)?,
+        time_shift_mode: row.get(29)?,
+        max_events: row.get(30)?,
+        max_feed_bytes: row.get(31)?,
+        feed_limit_policy: row.get(32)?,
+        feed_parts: row.get(33)?,
+    })
+}
+
+// Custom headers are stored as a JSON object.
+fn headers_column(row: &rusqlite::Row, idx: usize) -> rusqlite::Result<CustomHeaders> {
+    let json: String = row.get(idx)?;
+    Ok(serde_json::from_str(&json).unwrap_or_default())
+}
+
+pub fn list_sources(conn: &Connection) -> Result<Vec<Source>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM sources ORDER BY id",
+        SOURCE_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], map_source_row)?;
     Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
 }
 
 pub fn get_source(conn: &Connection, id: i64) -> Result<Option<Source>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, name, caldav_url, username, password, ics_path, sync_interval_secs, last_synced, last_sync_status, last_sync_error, created_at, public_ics, public_ics_path FROM sources WHERE id = ?1",
-    )?;
-    let mut rows = stmt.query_map(params![id], |row| {
-        Ok(Source {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            caldav_url: row.get(2)?,
-            username: row.get(3)?,
-            password: row.get(4)?,
-            ics_path: row.get(5)?,
-            sync_interval_secs: row.get(6)?,
-            last_synced: row.get(7)?,
-            last_sync_status: row.get(8)?,
-            last_sync_error: row.get(9)?,
-            created_at: row.get(10)?,
-            public_ics: row.get(11)?,
-            public_ics_path: row.get(12)?,
-        })
-    })?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM sources WHERE id = ?1",
+        SOURCE_COLUMNS
+    ))?;
+    let mut rows = stmt.query_map(params![id], map_source_row)?;
     match rows.next() {
         Some(Ok(s)) => Ok(Some(s)),
         Some(Err(e)) => Err(e.into()),
@@ -176,12 +692,138 @@ pub fn get_source(conn: &Connection, id: i64) -> Result<Option<Source>> {
     }
 }
 
+// Scheme and host are case-insensitive and default ports and trailing slashes
+// are dropped, so `HTTPS://Dav.Example.com:443/cal/` matches `https://dav.example.com/cal`.
+pub fn normalize_caldav_url(url: &str) -> String {
+    let url = url.trim();
+    match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed.as_str().trim_end_matches('/').to_owned(),
+        Err(_) => url.trim_end_matches('/').to_owned(),
+    }
+}
+
+// Finds a source that syncs the same CalDAV URL with the same username.
+pub fn find_duplicate_source(
+    conn: &Connection,
+    caldav_url: &str,
+    username: &str,
+    owner_id: Option<i64>,
+) -> Result<Option<Source>> {
+    if caldav_url.trim().is_empty() {
+        return Ok(None);
+    }
+    let url = normalize_caldav_url(caldav_url);
+    let sources = match owner_id {
+        Some(owner_id) => list_sources_for_owner(conn, owner_id)?,
+        None => list_sources(conn)?,
+    };
+    Ok(sources.into_iter().find(|s| {
+        s.source_type != "upload"
+            && s.username.trim() == username.trim()
+            && normalize_caldav_url(&s.caldav_url) == url
+    }))
+}
+
+pub const ATTACHMENT_MODES: &[&str] = &["keep", "strip", "uri_only", "rehost"];
+
+pub const STARTUP_SYNC_MODES: &[&str] = &["default", "always", "never"];
+
+fn validate_sync_on_startup(mode: &str) -> Result<()> {
+    ensure!(
+        STARTUP_SYNC_MODES.contains(&mode),
+        "Sync on startup must be one of: {}",
+        STARTUP_SYNC_MODES.join(", ")
+    );
+    Ok(())
+}
+
+pub const FETCH_MODES: &[&str] = &["query", "multiget"];
+
+fn validate_fetch_mode(mode: &str) -> Result<()> {
+    ensure!(
+        FETCH_MODES.contains(&mode),
+        "Fetch mode must be one of: {}",
+        FETCH_MODES.join(", ")
+    );
+    Ok(())
+}
+
+fn validate_attachment_mode(mode: &str) -> Result<()> {
+    ensure!(
+        ATTACHMENT_MODES.contains(&mode),
+        "Attachment mode must be one of: {}",
+        ATTACHMENT_MODES.join(", ")
+    );
+    Ok(())
+}
+
+pub const SOURCE_TYPES: &[&str] = &["caldav", "upload", "holidays", "birthdays"];
+
+fn validate_source_type(source_type: &str) -> Result<()> {
+    ensure!(
+        SOURCE_TYPES.contains(&source_type),
+        "Source type must be one of: {}",
+        SOURCE_TYPES.join(", ")
+    );
+    Ok(())
+}
+
+// The normalized region of a holiday source; other types have none.
+fn holiday_region(source_type: &str, region: &str) -> Result<String> {
+    match source_type {
+        "holidays" => {
+            require_non_empty("Holiday region", region)?;
+            holidays::normalize_region(region)
+        }
+        _ => {
+            ensure!(
+                region.trim().is_empty(),
+                "Holiday region is only used by holiday sources"
+            );
+            Ok(String::new())
+        }
+    }
+}
+
+// The canonical name of a time shift zone; empty turns the shift off.
+fn time_shift_zone(zone: &str) -> Result<String> {
+    match zone.trim() {
+        "" => Ok(String::new()),
+        zone => Ok(time_shift::parse_zone(zone)?.name().to_string()),
+    }
+}
+
+fn resolve_proxy_token(
+    enabled: Option<bool>,
+    existing: Option<&str>,
+    source_type: &str,
+) -> Result<Option<String>> {
+    match enabled {
+        Some(true) => {
+            ensure!(
+                source_type == "caldav",
+                "CalDAV proxy is only available for CalDAV sources"
+            );
+            Ok(Some(existing.map(str::to_owned).unwrap_or_else(|| {
+                uuid::Uuid::new_v4().simple().to_string()
+            })))
+        }
+        Some(false) => Ok(None),
+        None if source_type != "caldav" => Ok(None),
+        None => Ok(existing.map(str::to_owned)),
+    }
+}
+
 fn validate_ics_path(path: &str) -> Result<()> {
     let trimmed = path.trim();
     ensure!(
         trimmed != "public" && !trimmed.starts_with("public/"),
         "ICS path cannot start with 'public' — reserved for public ICS URLs"
     );
+    ensure!(
+        feed_limits::part_path(trimmed).is_none(),
+        "ICS path cannot end in /part-N.ics — reserved for feed parts"
+    );
     Ok(())
 }
 
@@ -225,13 +867,50 @@ fn validate_public_path(
 }
 
 pub fn create_source(conn: &Connection, src: &CreateSource) -> Result<i64> {
+    let source_type = src.source_type.as_deref().unwrap_or("caldav");
+    validate_source_type(source_type)?;
     require_non_empty("Name", &src.name)?;
-    require_non_empty("CalDAV URL", &src.caldav_url)?;
-    require_non_empty("Username", &src.username)?;
-    require_non_empty("Password", &src.password)?;
+    if matches!(source_type, "caldav" | "birthdays") {
+        require_non_empty("CalDAV URL", &src.caldav_url)?;
+        require_non_empty("Username", &src.username)?;
+        require_non_empty("Password", &src.password)?;
+    }
     require_non_empty("ICS Path", &src.ics_path)?;
     validate_ics_path(&src.ics_path)?;
     require_non_negative("Sync interval", src.sync_interval_secs)?;
+    let attachment_mode = src.attachment_mode.as_deref().unwrap_or("keep");
+    validate_attachment_mode(attachment_mode)?;
+    let sync_on_startup = src.sync_on_startup.as_deref().unwrap_or("default");
+    validate_sync_on_startup(sync_on_startup)?;
+    let fetch_mode = src.fetch_mode.as_deref().unwrap_or("query");
+    validate_fetch_mode(fetch_mode)?;
+    let user_agent = src.user_agent.as_deref().unwrap_or("");
+    http_clients::validate_user_agent(user_agent)?;
+    let custom_headers = src.custom_headers.clone().unwrap_or_default();
+    http_clients::validate_custom_headers(&custom_headers)?;
+    let sync_window = src.sync_window.as_deref().unwrap_or("").trim();
+    SyncWindow::parse(sync_window)?;
+    let working_hours = src.working_hours.as_deref().unwrap_or("").trim();
+    WorkingHours::parse(working_hours)?;
+    let holiday_region = holiday_region(source_type, src.holiday_region.as_deref().unwrap_or(""))?;
+    let repair_rules = ics_repair::normalize_rules(src.repair_rules.as_deref().unwrap_or(""))?;
+    let time_shift_zone = time_shift_zone(src.time_shift_zone.as_deref().unwrap_or(""))?;
+    let time_shift_mode = src.time_shift_mode.as_deref().unwrap_or("shift");
+    time_shift::validate_mode(time_shift_mode)?;
+    let feed_limit_policy = src.feed_limit_policy.as_deref().unwrap_or("truncate");
+    feed_limits::validate_policy(feed_limit_policy)?;
+    let proxy_token = resolve_proxy_token(src.proxy_enabled, None, source_type)?;
+    validate_owner(conn, src.owner_id)?;
+    check_source_quota(conn, src.owner_id)?;
+    if let Some(v) = src.archive_after_months {
+        require_non_negative("Archive after months", v)?;
+    }
+    if let Some(v) = src.max_events {
+        require_non_negative("Max events", v)?;
+    }
+    if let Some(v) = src.max_feed_bytes {
+        require_non_negative("Max feed bytes", v)?;
+    }
 
     let count: i64 = conn.query_row(
         "SELECT count(*) FROM sources WHERE ics_path = ?1 OR public_ics_path = ?1",
@@ -262,8 +941,8 @@ pub fn create_source(conn: &Connection, src: &CreateSource) -> Result<i64> {
     }
 
     conn.execute(
-        "INSERT INTO sources (name, caldav_url, username, password, ics_path, sync_interval_secs, public_ics, public_ics_path) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-        params![src.name, src.caldav_url, src.username, src.password, src.ics_path, src.sync_interval_secs, src.public_ics, public_path],
+        "INSERT INTO sources (name, caldav_url, username, password, ics_path, sync_interval_secs, public_ics, public_ics_path, attachment_mode, source_type, proxy_token, owner_id, archive_after_months, namespace_uids, sync_on_startup, fetch_mode, user_agent, custom_headers, sync_window, working_hours, holiday_region, repair_rules, time_shift_zone, time_shift_mode, max_events, max_feed_bytes, feed_limit_policy) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27)",
+        params![src.name, src.caldav_url, src.username, src.password, src.ics_path, src.sync_interval_secs, src.public_ics, public_path, attachment_mode, source_type, proxy_token, src.owner_id, src.archive_after_months.filter(|m| *m > 0), src.namespace_uids, sync_on_startup, fetch_mode, user_agent, serde_json::to_string(&custom_headers)?, sync_window, working_hours, holiday_region, repair_rules, time_shift_zone, time_shift_mode, src.max_events.filter(|m| *m > 0), src.max_feed_bytes.filter(|m| *m > 0), feed_limit_policy],
     )?;
     Ok(conn.last_insert_rowid())
 }
@@ -274,14 +953,27 @@ pub fn update_source(conn: &Connection, id: i64, upd: &UpdateSource) -> Result<b
         None => return Ok(false),
     };
 
+    let eff_source_type = upd.source_type.as_deref().unwrap_or(&existing.source_type);
+    validate_source_type(eff_source_type)?;
     if let Some(ref v) = upd.name {
         require_non_empty("Name", v)?;
     }
-    if let Some(ref v) = upd.caldav_url {
-        require_non_empty("CalDAV URL", v)?;
-    }
-    if let Some(ref v) = upd.username {
-        require_non_empty("Username", v)?;
+    if matches!(eff_source_type, "caldav" | "birthdays") {
+        require_non_empty(
+            "CalDAV URL",
+            upd.caldav_url.as_deref().unwrap_or(&existing.caldav_url),
+        )?;
+        require_non_empty(
+            "Username",
+            upd.username.as_deref().unwrap_or(&existing.username),
+        )?;
+        require_non_empty(
+            "Password",
+            upd.password
+                .as_deref()
+                .filter(|s| !s.trim().is_empty())
+                .unwrap_or(&existing.password),
+        )?;
     }
     if let Some(ref v) = upd.ics_path {
         require_non_empty("ICS Path", v)?;
@@ -290,6 +982,63 @@ pub fn update_source(conn: &Connection, id: i64, upd: &UpdateSource) -> Result<b
     if let Some(v) = upd.sync_interval_secs {
         require_non_negative("Sync interval", v)?;
     }
+    if let Some(ref v) = upd.attachment_mode {
+        validate_attachment_mode(v)?;
+    }
+    if let Some(ref v) = upd.sync_on_startup {
+        validate_sync_on_startup(v)?;
+    }
+    if let Some(ref v) = upd.fetch_mode {
+        validate_fetch_mode(v)?;
+    }
+    if let Some(ref v) = upd.user_agent {
+        http_clients::validate_user_agent(v)?;
+    }
+    let custom_headers =
+        redact::unmask_headers(upd.custom_headers.as_ref(), &existing.custom_headers);
+    http_clients::validate_custom_headers(&custom_headers)?;
+    if let Some(ref v) = upd.sync_window {
+        SyncWindow::parse(v)?;
+    }
+    if let Some(ref v) = upd.working_hours {
+        WorkingHours::parse(v)?;
+    }
+    // Switching away from holidays drops the stored region.
+    let eff_holiday_region = match (&upd.holiday_region, eff_source_type) {
+        (Some(v), _) => holiday_region(eff_source_type, v)?,
+        (None, "holidays") => holiday_region(eff_source_type, &existing.holiday_region)?,
+        (None, _) => String::new(),
+    };
+    let repair_rules = upd
+        .repair_rules
+        .as_deref()
+        .map(ics_repair::normalize_rules)
+        .transpose()?;
+    let eff_time_shift_zone = match &upd.time_shift_zone {
+        Some(v) => time_shift_zone(v)?,
+        None => existing.time_shift_zone.clone(),
+    };
+    if let Some(ref v) = upd.time_shift_mode {
+        time_shift::validate_mode(v)?;
+    }
+    if let Some(ref v) = upd.feed_limit_policy {
+        feed_limits::validate_policy(v)?;
+    }
+    if let Some(v) = upd.archive_after_months {
+        require_non_negative("Archive after months", v)?;
+    }
+    if let Some(v) = upd.max_events {
+        require_non_negative("Max events", v)?;
+    }
+    if let Some(v) = upd.max_feed_bytes {
+        require_non_negative("Max feed bytes", v)?;
+    }
+    let eff_proxy_token = resolve_proxy_token(
+        upd.proxy_enabled,
+        existing.proxy_token.as_deref(),
+        eff_source_type,
+    )?;
+    validate_owner(conn, upd.owner_id)?;
 
     if let Some(ref new_path) = upd.ics_path {
         let count: i64 = conn.query_row(
@@ -328,198 +1077,1365 @@ pub fn update_source(conn: &Connection, id: i64, upd: &UpdateSource) -> Result<b
     }
 
     conn.execute(
-        "UPDATE sources SET name = ?1, caldav_url = ?2, username = ?3, password = ?4, ics_path = ?5, sync_interval_secs = ?6, public_ics = ?7, public_ics_path = ?8 WHERE id = ?9",
+        "UPDATE sources SET name = ?1, caldav_url = ?2, username = ?3, password = ?4, ics_path = ?5, sync_interval_secs = ?6, public_ics = ?7, public_ics_path = ?8, attachment_mode = ?9, source_type = ?10, proxy_token = ?11, owner_id = ?12, archive_after_months = ?13, namespace_uids = ?14, sync_on_startup = ?15, fetch_mode = ?16, user_agent = ?17, custom_headers = ?18, sync_window = ?19, working_hours = ?20, holiday_region = ?21, repair_rules = ?22, time_shift_zone = ?23, time_shift_mode = ?24, max_events = ?25, max_feed_bytes = ?26, feed_limit_policy = ?27 WHERE id = ?28",
         params![
             upd.name.as_deref().unwrap_or(&existing.name),
-            upd.caldav_url.as_deref().unwrap_or(&existing.caldav_url),
+            redact::unmask(upd.caldav_url.as_deref(), &existing.caldav_url),
             upd.username.as_deref().unwrap_or(&existing.username),
             upd.password.as_deref().filter(|s| !s.trim().is_empty()).unwrap_or(&existing.password),
             eff_ics_path,
             upd.sync_interval_secs.unwrap_or(existing.sync_interval_secs),
             eff_public_ics,
             eff_public_path,
+            upd.attachment_mode.as_deref().unwrap_or(&existing.attachment_mode),
+            eff_source_type,
+            eff_proxy_token,
+            upd.owner_id.or(existing.owner_id),
+            // 0 turns archiving off.
+            upd.archive_after_months
+                .or(existing.archive_after_months)
+                .filter(|m| *m > 0),
+            upd.namespace_uids.unwrap_or(existing.namespace_uids),
+            upd.sync_on_startup.as_deref().unwrap_or(&existing.sync_on_startup),
+            upd.fetch_mode.as_deref().unwrap_or(&existing.fetch_mode),
+            upd.user_agent.as_deref().unwrap_or(&existing.user_agent),
+            serde_json::to_string(&custom_headers)?,
+            upd.sync_window.as_deref().map(str::trim).unwrap_or(&existing.sync_window),
+            upd.working_hours.as_deref().map(str::trim).unwrap_or(&existing.working_hours),
+            eff_holiday_region,
+            repair_rules.as_deref().unwrap_or(&existing.repair_rules),
+            eff_time_shift_zone,
+            upd.time_shift_mode.as_deref().unwrap_or(&existing.time_shift_mode),
+            upd.max_events.or(existing.max_events).filter(|m| *m > 0),
+            upd.max_feed_bytes.or(existing.max_feed_bytes).filter(|m| *m > 0),
+            upd.feed_limit_policy.as_deref().unwrap_or(&existing.feed_limit_policy),
             id
         ],
     )?;
+    refresh_feed_parts(conn, id)?;
     Ok(true)
 }
 
-pub fn delete_source(conn: &Connection, id: i64) -> Result<bool> {
-    let rows = conn.execute("DELETE FROM sources WHERE id = ?1", params![id])?;
-    Ok(rows > 0)
+// Re-splits a source's stored feed into the parts it is served in; see
+// `feed_limits::split`.
+pub fn refresh_feed_parts(conn: &Connection, id: i64) -> Result<()> {
+    let content = get_ics_data(conn, id)?;
+    store_feed_parts(conn, id, content.as_deref())
 }
 
-pub fn update_last_synced(conn: &Connection, id: i64) -> Result<()> {
+fn store_feed_parts(conn: &Connection, source_id: i64, content: Option<&str>) -> Result<()> {
+    let Some(src) = get_source(conn, source_id)? else {
+        return Ok(());
+    };
+    let parts = match (src.feed_limit_policy.as_str(), content) {
+        ("split", Some(content)) => feed_limits::split(content, src.max_events, src.max_feed_bytes),
+        _ => Vec::new(),
+    };
+    let previous_files: Vec<String> = conn
+        .prepare(
+            "SELECT content_hash FROM feed_part_data WHERE source_id = ?1 AND stored_in_file = 1",
+        )?
+        .query_map(params![source_id], |row| row.get(0))?
+        .collect::<std::result::Result<_, _>>()?;
     conn.execute(
-        "UPDATE sources SET last_synced = datetime('now') WHERE id = ?1",
-        params![id],
+        "DELETE FROM feed_part_data WHERE source_id = ?1",
+        params![source_id],
     )?;
-    Ok(())
-}
-
-pub fn update_sync_status(
-    conn: &Connection,
+    for (i, part) in parts.iter().enumerate() {
+        let hash = content_hash(part);
+        let in_file = feed_store::stores_in_file(part.len());
+        if in_file {
+            feed_store::write(&hash, part)?;
+        }
+        conn.execute(
+            "INSERT INTO feed_part_data (source_id, part, ics_content, content_hash, stored_in_file, rehosted_attachments)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                source_id,
+                i as i64 + 1,
+                if in_file { "" } else { part.as_str() },
+                hash,
+                in_file,
+                part.contains(":/attachments/")
+            ],
+        )?;
+    }
+    conn.execute(
+        "UPDATE sources SET feed_parts = ?1 WHERE id = ?2",
+        params![parts.len() as i64, source_id],
+    )?;
+    for hash in previous_files {
+        release_feed_file(conn, &hash)?;
+    }
+    Ok(())
+}
+
+pub fn delete_source(conn: &Connection, id: i64) -> Result<bool> {
+    let feed_file = stored_feed_file(conn, id)?;
+    store_feed_parts(conn, id, None)?;
+    let rows = conn
+        .execute("DELETE FROM sources WHERE id = ?1", params![id])
+        .map_err(|e| match e.sqlite_error_code() {
+            // The only foreign key pointing at sources without a cascade.
+            Some(rusqlite::ErrorCode::ConstraintViolation) => anyhow::Error::new(e)
+                .context("Source is the input of a destination; relink or delete it first"),
+            _ => e.into(),
+        })?;
+    if let Some(hash) = feed_file {
+        release_feed_file(conn, &hash)?;
+    }
+    Ok(rows > 0)
+}
+
+// Names are compared trimmed and case-insensitively within one owner's sources.
+pub fn source_name_taken(
+    conn: &Connection,
+    name: &str,
+    owner_id: Option<i64>,
+    exclude: Option<i64>,
+) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM sources
+         WHERE lower(trim(name)) = lower(trim(?1)) AND owner_id IS ?2 AND id IS NOT ?3",
+        params![name, owner_id, exclude],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+pub fn set_source_enabled(conn: &Connection, id: i64, enabled: bool) -> Result<bool> {
+    let rows = conn.execute(
+        "UPDATE sources SET enabled = ?1 WHERE id = ?2",
+        params![enabled, id],
+    )?;
+    Ok(rows > 0)
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct CloneSource {
+    pub name: Option<String>,
+    pub ics_path: Option<String>,
+    pub public_ics_path: Option<String>,
+}
+
+// `team.ics` -> `team-3f9c1a2b7d4e.ics`, so the copy's feed URL can't be guessed from the original's.
+fn fresh_feed_path(path: &str) -> String {
+    let token = &uuid::Uuid::new_v4().simple().to_string()[..12];
+    match path.strip_suffix(".ics") {
+        Some(stem) => format!("{}-{}.ics", stem, token),
+        None => format!("{}-{}", path, token),
+    }
+}
+
+// `Team (copy)`, then `Team (copy 2)` and so on until the name is free.
+fn copy_name(conn: &Connection, name: &str, owner_id: Option<i64>) -> Result<String> {
+    let mut candidate = format!("{} (copy)", name);
+    let mut n = 2;
+    while source_name_taken(conn, &candidate, owner_id, None)? {
+        candidate = format!("{} (copy {})", name, n);
+        n += 1;
+    }
+    Ok(candidate)
+}
+
+// Copies a source's settings and feed metadata; the new feed starts empty until
+// its first sync. Returns None when the source doesn't exist.
+pub fn clone_source(conn: &Connection, id: i64, opts: &CloneSource) -> Result<Option<i64>> {
+    let Some(existing) = get_source(conn, id)? else {
+        return Ok(None);
+    };
+    let public_ics_path = match existing.public_ics {
+        true => opts
+            .public_ics_path
+            .clone()
+            .or(existing.public_ics_path.as_deref().map(fresh_feed_path)),
+        false => None,
+    };
+    let src = CreateSource {
+        name: match opts.name.clone() {
+            Some(name) => name,
+            None => copy_name(conn, &existing.name, existing.owner_id)?,
+        },
+        caldav_url: existing.caldav_url,
+        username: existing.username,
+        password: existing.password,
+        ics_path: opts
+            .ics_path
+            .clone()
+            .unwrap_or_else(|| fresh_feed_path(&existing.ics_path)),
+        sync_interval_secs: existing.sync_interval_secs,
+        public_ics: existing.public_ics,
+        public_ics_path,
+        attachment_mode: Some(existing.attachment_mode),
+        source_type: Some(existing.source_type),
+        // A new proxy token rather than a copy of the original's.
+        proxy_enabled: Some(existing.proxy_token.is_some()),
+        owner_id: existing.owner_id,
+        archive_after_months: existing.archive_after_months,
+        namespace_uids: existing.namespace_uids,
+        sync_on_startup: Some(existing.sync_on_startup),
+        fetch_mode: Some(existing.fetch_mode),
+        user_agent: Some(existing.user_agent),
+        custom_headers: Some(existing.custom_headers),
+        sync_window: Some(existing.sync_window),
+        working_hours: Some(existing.working_hours),
+        holiday_region: Some(existing.holiday_region),
+        repair_rules: Some(existing.repair_rules),
+        time_shift_zone: Some(existing.time_shift_zone),
+        time_shift_mode: Some(existing.time_shift_mode),
+        max_events: existing.max_events,
+        max_feed_bytes: existing.max_feed_bytes,
+        feed_limit_policy: Some(existing.feed_limit_policy),
+    };
+    let metadata = get_feed_metadata(conn, id)?;
+    let properties = get_feed_properties(conn, id)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let new_id = create_source(&tx, &src)?;
+    if !existing.enabled {
+        set_source_enabled(&tx, new_id, false)?;
+    }
+    if !metadata.is_empty() {
+        set_feed_metadata(&tx, new_id, &metadata)?;
+    }
+    if properties != FeedProperties::default() {
+        set_feed_properties(&tx, new_id, &properties)?;
+    }
+    tx.commit()?;
+    Ok(Some(new_id))
+}
+
+pub fn update_last_synced(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE sources SET last_synced = datetime('now') WHERE id = ?1",
+        params![id],
+    )?;
+    Ok(())
+}
+
+pub fn update_sync_duration(conn: &Connection, id: i64, duration_ms: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE sources SET last_sync_duration_ms = ?1 WHERE id = ?2",
+        params![duration_ms, id],
+    )?;
+    Ok(())
+}
+
+pub fn update_sync_status(
+    conn: &Connection,
     id: i64,
     status: &str,
     error: Option<&str>,
 ) -> Result<()> {
     conn.execute(
         "UPDATE sources SET last_sync_status = ?1, last_sync_error = ?2 WHERE id = ?3",
-        params![status, error, id],
+        params![status, error.map(redact::text), id],
     )?;
-    Ok(())
+    record_sync_run(conn, &AutoSyncKey::Source(id), status, error)
+}
+
+// --- Sync history ---
+
+const SYNC_RUNS_KEPT: i64 = 50;
+
+// One finished run of a source or destination: its status (`ok`,
+// `unchanged`, `degraded`, `error` or `cancelled`) and what it did.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyncRun {
+    pub status: String,
+    pub error: Option<String>,
+    // Upload counts and failures of destination runs that got through.
+    pub result: Option<DestinationRunResult>,
+    pub finished_at: String,
+}
+
+fn sync_run_column(key: &AutoSyncKey) -> (&'static str, i64) {
+    match key {
+        AutoSyncKey::Source(id) => ("source_id", *id),
+        AutoSyncKey::Destination(id) => ("destination_id", *id),
+    }
 }
 
-pub fn save_ics_data(conn: &Connection, source_id: i64, content: &str) -> Result<()> {
+// Every status update ends a run, so the status setters record it here,
+// keeping the last `SYNC_RUNS_KEPT` per source or destination. A destination
+// run takes the run result its caller stored just before.
+fn record_sync_run(
+    conn: &Connection,
+    key: &AutoSyncKey,
+    status: &str,
+    error: Option<&str>,
+) -> Result<()> {
+    let error = error.map(redact::text);
+    match key {
+        AutoSyncKey::Source(id) => conn.execute(
+            "INSERT INTO sync_runs (source_id, status, error) VALUES (?1, ?2, ?3)",
+            params![id, status, error],
+        )?,
+        AutoSyncKey::Destination(id) => conn.execute(
+            "INSERT INTO sync_runs (destination_id, status, error, result)
+             SELECT id, ?2, ?3, CASE WHEN ?2 IN ('ok', 'degraded') THEN last_sync_result END
+             FROM destinations WHERE id = ?1",
+            params![id, status, error],
+        )?,
+    };
+    let (column, id) = sync_run_column(key);
     conn.execute(
-        "INSERT INTO ics_data (source_id, ics_content, updated_at) VALUES (?1, ?2, datetime('now'))
-         ON CONFLICT(source_id) DO UPDATE SET ics_content = ?2, updated_at = datetime('now')",
-        params![source_id, content],
+        &format!(
+            "DELETE FROM sync_runs WHERE {column} = ?1 AND id NOT IN
+             (SELECT id FROM sync_runs WHERE {column} = ?1 ORDER BY id DESC LIMIT ?2)"
+        ),
+        params![id, SYNC_RUNS_KEPT],
     )?;
     Ok(())
 }
 
-pub fn get_ics_data(conn: &Connection, source_id: i64) -> Result<Option<String>> {
-    let mut stmt = conn.prepare("SELECT ics_content FROM ics_data WHERE source_id = ?1")?;
-    let mut rows = stmt.query_map(params![source_id], |row| row.get::<_, String>(0))?;
-    match rows.next() {
-        Some(Ok(s)) => Ok(Some(s)),
-        Some(Err(e)) => Err(e.into()),
-        None => Ok(None),
+// Newest first.
+pub fn list_sync_runs(conn: &Connection, key: &AutoSyncKey) -> Result<Vec<SyncRun>> {
+    let (column, id) = sync_run_column(key);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT status, error, result, finished_at FROM sync_runs
+         WHERE {column} = ?1 ORDER BY id DESC"
+    ))?;
+    let runs = stmt
+        .query_map(params![id], |row| {
+            Ok(SyncRun {
+                status: row.get(0)?,
+                error: row.get(1)?,
+                result: row
+                    .get::<_, Option<String>>(2)?
+                    .and_then(|json| serde_json::from_str(&json).ok()),
+                finished_at: row.get(3)?,
+            })
+        })?
+        .collect::<std::result::Result<_, _>>()?;
+    Ok(runs)
+}
+
+// Scheduled syncs that failed in a row, and when the next attempt may start.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SyncBackoff {
+    pub failures: i64,
+    pub retry_after: Option<DateTime<Utc>>,
+}
+
+fn backoff_table(key: &AutoSyncKey) -> (&'static str, i64) {
+    match key {
+        AutoSyncKey::Source(id) => ("sources", *id),
+        AutoSyncKey::Destination(id) => ("destinations", *id),
     }
 }
 
-pub fn get_ics_data_by_path(conn: &Connection, path: &str) -> Result<Option<String>> {
-    let mut stmt = conn.prepare(
-        "SELECT d.ics_content FROM ics_data d JOIN sources s ON d.source_id = s.id
-         WHERE s.ics_path = ?1
-         UNION ALL
-         SELECT d.ics_content FROM ics_data d JOIN source_paths sp ON d.source_id = sp.source_id
-         WHERE sp.path = ?1
-         LIMIT 1",
+pub fn get_sync_backoff(conn: &Connection, key: &AutoSyncKey) -> Result<SyncBackoff> {
+    let (table, id) = backoff_table(key);
+    let backoff = conn
+        .query_row(
+            &format!(
+                "SELECT sync_failures, retry_after FROM {} WHERE id = ?1",
+                table
+            ),
+            params![id],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?)),
+        )
+        .optional()?;
+    let Some((failures, retry_after)) = backoff else {
+        return Ok(SyncBackoff::default());
+    };
+    Ok(SyncBackoff {
+        failures,
+        retry_after: retry_after
+            .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+            .map(|t| t.with_timezone(&Utc)),
+    })
+}
+
+// A default `SyncBackoff` clears it.
+pub fn set_sync_backoff(conn: &Connection, key: &AutoSyncKey, backoff: &SyncBackoff) -> Result<()> {
+    let (table, id) = backoff_table(key);
+    conn.execute(
+        &format!(
+            "UPDATE {} SET sync_failures = ?1, retry_after = ?2 WHERE id = ?3",
+            table
+        ),
+        params![
+            backoff.failures,
+            backoff.retry_after.map(|t| t.to_rfc3339()),
+            id
+        ],
     )?;
-    let mut rows = stmt.query_map(params![path], |row| row.get::<_, String>(0))?;
-    match rows.next() {
-        Some(Ok(s)) => Ok(Some(s)),
-        Some(Err(e)) => Err(e.into()),
-        None => Ok(None),
-    }
+    Ok(())
 }
 
-pub fn get_ics_data_by_public_path(conn: &Connection, path: &str) -> Result<Option<String>> {
-    let mut stmt = conn.prepare(
-        "SELECT d.ics_content FROM ics_data d JOIN sources s ON d.source_id = s.id
-         WHERE s.public_ics_path = ?1 AND s.public_ics = 1
-         UNION ALL
-         SELECT d.ics_content FROM ics_data d JOIN source_paths sp ON d.source_id = sp.source_id
-         WHERE sp.path = ?1 AND sp.is_public = 1
-         LIMIT 1",
+pub fn count_events(ics: &str) -> i64 {
+    ics.matches("BEGIN:VEVENT").count() as i64
+}
+
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+// Returns false when the feed was unchanged and nothing was written.
+pub fn save_ics_data(conn: &Connection, source_id: i64, content: &str) -> Result<bool> {
+    let content = &feed_metadata::embed(
+        content,
+        &get_feed_metadata(conn, source_id)?,
+        &get_feed_properties(conn, source_id)?,
+    );
+    // An unchanged feed keeps its row, updated_at (Last-Modified) and event index.
+    let hash = content_hash(content);
+    let unchanged = conn
+        .query_row(
+            "SELECT content_hash = ?2 FROM ics_data WHERE source_id = ?1",
+            params![source_id, hash],
+            |row| row.get::<_, Option<bool>>(0),
+        )
+        .optional()?
+        .flatten()
+        .unwrap_or(false);
+    if unchanged {
+        return Ok(false);
+    }
+    let previous_file = stored_feed_file(conn, source_id)?;
+    let in_file = feed_store::stores_in_file(content.len());
+    if in_file {
+        feed_store::write(&hash, content)?;
+    }
+    conn.execute(
+        "INSERT INTO ics_data (source_id, ics_content, event_count, content_hash, stored_in_file, rehosted_attachments, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, datetime('now'))
+         ON CONFLICT(source_id) DO UPDATE SET ics_content = ?2, event_count = ?3, content_hash = ?4,
+            stored_in_file = ?5, rehosted_attachments = ?6, updated_at = datetime('now')",
+        params![
+            source_id,
+            if in_file { "" } else { content },
+            count_events(content),
+            hash,
+            in_file,
+            content.contains(":/attachments/")
+        ],
     )?;
-    let mut rows = stmt.query_map(params![path], |row| row.get::<_, String>(0))?;
-    match rows.next() {
-        Some(Ok(s)) => Ok(Some(s)),
-        Some(Err(e)) => Err(e.into()),
-        None => Ok(None),
+    index_events(conn, source_id, content)?;
+    store_feed_parts(conn, source_id, Some(content))?;
+    if let Some(previous) = previous_file {
+        release_feed_file(conn, &previous)?;
     }
+    Ok(true)
 }
 
-pub fn is_public_standard_ics(conn: &Connection, ics_path: &str) -> Result<bool> {
-    let count: i64 = conn.query_row(
-        "SELECT count(*) FROM (
-            SELECT 1 FROM sources WHERE ics_path = ?1 AND public_ics = 1 AND (public_ics_path IS NULL OR public_ics_path = '')
-            UNION ALL
-            SELECT 1 FROM source_paths WHERE path = ?1 AND is_public = 1
-         ) t",
-        params![ics_path],
+fn stored_feed_file(conn: &Connection, source_id: i64) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT content_hash FROM ics_data WHERE source_id = ?1 AND stored_in_file = 1",
+            params![source_id],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+// Identical feeds share a file, so it goes once no source uses it.
+fn release_feed_file(conn: &Connection, hash: &str) -> Result<()> {
+    let used: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM ics_data WHERE content_hash = ?1 AND stored_in_file = 1)
+            OR EXISTS (SELECT 1 FROM feed_part_data WHERE content_hash = ?1 AND stored_in_file = 1)",
+        params![hash],
         |row| row.get(0),
     )?;
-    Ok(count > 0)
+    if !used {
+        feed_store::remove(hash);
+    }
+    Ok(())
 }
 
-// --- Source Paths (additional ICS routes per source) ---
+// Moves feeds stored inline before they outgrew the limit, or before files
+// were used at all, out of the database.
+pub fn move_large_feeds_to_files(conn: &Connection) -> Result<usize> {
+    let large: Vec<i64> = conn
+        .prepare(
+            "SELECT source_id, length(CAST(ics_content AS BLOB)) FROM ics_data WHERE stored_in_file = 0",
+        )?
+        .query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)?)))?
+        .filter_map(|row| match row {
+            Ok((id, len)) if feed_store::stores_in_file(len as usize) => Some(Ok(id)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<std::result::Result<_, _>>()?;
+    for &source_id in &large {
+        let content: String = conn.query_row(
+            "SELECT ics_content FROM ics_data WHERE source_id = ?1",
+            params![source_id],
+            |row| row.get(0),
+        )?;
+        let hash = content_hash(&content);
+        feed_store::write(&hash, &content)?;
+        conn.execute(
+            "UPDATE ics_data SET ics_content = '', content_hash = ?2, stored_in_file = 1,
+                rehosted_attachments = ?3 WHERE source_id = ?1",
+            params![source_id, hash, content.contains(":/attachments/")],
+        )?;
+    }
+    Ok(large.len())
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-pub struct SourcePath {
-    pub id: i64,
-    pub source_id: i64,
-    pub path: String,
-    pub is_public: bool,
-    pub created_at: String,
+pub fn referenced_feed_files(conn: &Connection) -> Result<HashSet<String>> {
+    conn.prepare(
+        "SELECT content_hash FROM ics_data WHERE stored_in_file = 1
+         UNION SELECT content_hash FROM feed_part_data WHERE stored_in_file = 1",
+    )?
+    .query_map([], |row| row.get(0))?
+    .collect::<std::result::Result<_, _>>()
+    .map_err(Into::into)
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
-pub struct CreateSourcePath {
-    pub path: String,
-    #[serde(default)]
-    pub is_public: bool,
+// Reads a feed from its ics_data row: `ics_content`, `stored_in_file` and
+// `content_hash`, in that order.
+fn feed_content(row: &rusqlite::Row, first: usize) -> rusqlite::Result<(String, bool, String)> {
+    Ok((
+        row.get(first)?,
+        row.get(first + 1)?,
+        row.get::<_, Option<String>>(first + 2)?.unwrap_or_default(),
+    ))
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
-pub struct UpdateSourcePath {
-    pub path: Option<String>,
-    pub is_public: Option<bool>,
+fn load_feed_content((content, in_file, hash): (String, bool, String)) -> Result<String> {
+    match in_file {
+        true => feed_store::read(&hash),
+        false => Ok(content),
+    }
 }
 
-fn validate_source_path(conn: &Connection, path: &str, exclude_id: Option<i64>) -> Result<String> {
-    let trimmed = path.trim();
-    require_non_empty("Path", trimmed)?;
-    validate_ics_path(trimmed)?;
-    ensure!(!trimmed.starts_with('/'), "Path must not start with /");
-    ensure!(!trimmed.contains(".."), "Path must not contain ..");
+fn reindex_events(conn: &Connection) -> Result<()> {
+    let ids: Vec<i64> = conn
+        .prepare("SELECT source_id FROM ics_data")?
+        .query_map([], |row| row.get(0))?
+        .collect::<std::result::Result<_, _>>()?;
+    for source_id in ids {
+        match get_ics_data(conn, source_id) {
+            Ok(Some(content)) => {
+                index_events(conn, source_id, &content)?;
+                label_event_calendars(conn, source_id, &list_source_calendars(conn, source_id)?)?;
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Could not index events of source {}: {}", source_id, e),
+        }
+    }
+    Ok(())
+}
 
-    let sources_count: i64 = conn.query_row(
-        "SELECT count(*) FROM sources WHERE ics_path = ?1 OR public_ics_path = ?1",
-        params![trimmed],
-        |row| row.get(0),
+// Tags indexed events with the upstream calendar they came from, by UID with
+// or without the source's namespace.
+fn label_event_calendars(
+    conn: &Connection,
+    source_id: i64,
+    calendars: &[SourceCalendar],
+) -> Result<()> {
+    let namespace = crate::sync_origin::source_namespace(source_id);
+    let mut stmt = conn.prepare_cached(
+        "UPDATE events SET calendar = ?1 WHERE source_id = ?2 AND uid IN (?3, ?4)",
     )?;
-    ensure!(
-        sources_count == 0,
-        "Path conflicts with an existing source ICS path"
-    );
+    for calendar in calendars {
+        for event in &calendar.events {
+            for uid in crate::event_index::index(event)
+                .into_iter()
+                .filter_map(|e| e.uid)
+            {
+                let namespaced = format!("{}@{}", uid, namespace);
+                stmt.execute(params![calendar.href, source_id, uid, namespaced])?;
+            }
+        }
+    }
+    Ok(())
+}
 
-    let sp_count: i64 = match exclude_id {
-        Some(id) => conn.query_row(
-            "SELECT count(*) FROM source_paths WHERE path = ?1 AND id != ?2",
-            params![trimmed, id],
-            |row| row.get(0),
-        )?,
-        None => conn.query_row(
-            "SELECT count(*) FROM source_paths WHERE path = ?1",
-            params![trimmed],
-            |row| row.get(0),
-        )?,
+// The upstream calendar of each indexed event of a source, by UID.
+pub fn event_calendars(conn: &Connection, source_id: i64) -> Result<HashMap<String, String>> {
+    let mut stmt = conn.prepare(
+        "SELECT uid, calendar FROM events
+         WHERE source_id = ?1 AND uid IS NOT NULL AND calendar IS NOT NULL",
+    )?;
+    let rows = stmt.query_map(params![source_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
+// Everything indexed about an event, to tell which rows a new feed keeps.
+type EventRow = (
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    String,
+);
+
+// Updates the source's rows in place: events that didn't change keep their
+// row, so the full-text index only sees the ones that did.
+fn index_events(conn: &Connection, source_id: i64, content: &str) -> Result<()> {
+    let mut existing: HashMap<EventRow, Vec<i64>> = HashMap::new();
+    {
+        let mut stmt = conn.prepare_cached(
+            "SELECT e.id, e.uid, e.summary, e.description, e.location, e.starts_at, e.ends_at,
+                    e.organizer,
+                    coalesce((SELECT group_concat(email, char(10)) FROM
+                        (SELECT email FROM event_attendees a WHERE a.event_id = e.id ORDER BY rowid)), '')
+             FROM events e WHERE e.source_id = ?1",
+        )?;
+        let rows = stmt.query_map(params![source_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                (
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                    row.get(7)?,
+                    row.get(8)?,
+                ),
+            ))
+        })?;
+        for row in rows {
+            let (id, key) = row?;
+            existing.entry(key).or_default().push(id);
+        }
+    }
+    let mut insert_event = conn.prepare_cached(
+        "INSERT INTO events (source_id, uid, summary, starts_at, ends_at, organizer, description, location)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )?;
+    let mut insert_attendee =
+        conn.prepare_cached("INSERT INTO event_attendees (event_id, email) VALUES (?1, ?2)")?;
+    let format = |t: Option<chrono::NaiveDateTime>| {
+        t.map(|t| t.format(crate::event_index::TIMESTAMP_FORMAT).to_string())
     };
-    ensure!(sp_count == 0, "Duplicate path is not allowed");
+    for event in crate::event_index::index(content) {
+        let key: EventRow = (
+            event.uid,
+            event.summary,
+            event.description,
+            event.location,
+            format(event.starts_at),
+            format(event.ends_at),
+            event.organizer,
+            event.attendees.join("\n"),
+        );
+        if existing.get_mut(&key).and_then(Vec::pop).is_some() {
+            continue;
+        }
+        insert_event.execute(params![
+            source_id, key.0, key.1, key.4, key.5, key.6, key.2, key.3
+        ])?;
+        let event_id = conn.last_insert_rowid();
+        for email in &event.attendees {
+            insert_attendee.execute(params![event_id, email])?;
+        }
+    }
+    let mut delete_event = conn.prepare_cached("DELETE FROM events WHERE id = ?1")?;
+    for id in existing.into_values().flatten() {
+        delete_event.execute(params![id])?;
+    }
+    Ok(())
+}
 
-    Ok(trimmed.to_owned())
+// `dentist appoint` -> `"dentist"* "appoint"*`: every word must match the
+// start of a word in the summary, description or location.
+fn fts_query(keywords: &str) -> Option<String> {
+    let terms: Vec<String> = keywords
+        .split_whitespace()
+        .map(|t| format!("\"{}\"*", t.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
 }
 
-pub fn list_source_paths(conn: &Connection, source_id: i64) -> Result<Vec<SourcePath>> {
+#[derive(Debug, Clone)]
+pub struct ArchivedEvent {
+    pub key: String,
+    pub ends_at: String,
+    pub vevent: String,
+}
+
+pub fn save_archived_events(
+    conn: &Connection,
+    source_id: i64,
+    events: &[ArchivedEvent],
+) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO archived_events (source_id, event_key, ends_at, vevent) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(source_id, event_key) DO UPDATE SET ends_at = ?3, vevent = ?4, archived_at = datetime('now')",
+    )?;
+    for e in events {
+        stmt.execute(params![source_id, e.key, e.ends_at, e.vevent])?;
+    }
+    Ok(())
+}
+
+pub fn list_archived_events(conn: &Connection, source_id: i64) -> Result<Vec<String>> {
     let mut stmt = conn.prepare(
-        "SELECT id, source_id, path, is_public, created_at FROM source_paths WHERE source_id = ?1 ORDER BY id",
+        "SELECT vevent FROM archived_events WHERE source_id = ?1 ORDER BY ends_at, event_key",
     )?;
+    let rows = stmt.query_map(params![source_id], |row| row.get::<_, String>(0))?;
+    Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+// One upstream calendar of a source with the events fetched from it at the
+// last sync, as split by `api::sync::split_vevents`.
+#[derive(Debug, Clone)]
+pub struct SourceCalendar {
+    pub href: String,
+    pub events: Vec<String>,
+    pub color: Option<String>,
+    pub order: Option<i64>,
+    // Objects fetched by a multiget sync, kept apart in `source_objects`;
+    // empty when loaded from the database.
+    pub objects: Vec<CachedObject>,
+}
+
+// A calendar object as last fetched, skipped by the next multiget sync while
+// its ETag stays the same.
+#[derive(Debug, Clone)]
+pub struct CachedObject {
+    pub href: String,
+    pub etag: String,
+    pub calendar_data: String,
+}
+
+pub fn list_source_objects(
+    conn: &Connection,
+    source_id: i64,
+) -> Result<HashMap<String, CachedObject>> {
+    let mut stmt =
+        conn.prepare("SELECT href, etag, calendar_data FROM source_objects WHERE source_id = ?1")?;
     let rows = stmt.query_map(params![source_id], |row| {
-        Ok(SourcePath {
-            id: row.get(0)?,
-            source_id: row.get(1)?,
-            path: row.get(2)?,
-            is_public: row.get(3)?,
-            created_at: row.get(4)?,
+        Ok(CachedObject {
+            href: row.get(0)?,
+            etag: row.get(1)?,
+            calendar_data: row.get(2)?,
         })
     })?;
-    Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+    rows.map(|r| Ok(r.map(|o| (o.href.clone(), o))?)).collect()
 }
 
-pub fn get_source_path(conn: &Connection, id: i64) -> Result<Option<SourcePath>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, source_id, path, is_public, created_at FROM source_paths WHERE id = ?1",
+// Replaces the cached objects of one calendar, or of all with `calendar` None.
+pub fn save_source_objects(
+    conn: &Connection,
+    source_id: i64,
+    calendar: Option<&str>,
+    objects: &[(&str, &CachedObject)],
+) -> Result<()> {
+    conn.execute(
+        "DELETE FROM source_objects WHERE source_id = ?1 AND (?2 IS NULL OR calendar = ?2)",
+        params![source_id, calendar],
     )?;
-    let mut rows = stmt.query_map(params![id], |row| {
-        Ok(SourcePath {
-            id: row.get(0)?,
-            source_id: row.get(1)?,
-            path: row.get(2)?,
-            is_public: row.get(3)?,
+    let mut stmt = conn.prepare_cached(
+        "INSERT OR REPLACE INTO source_objects (source_id, calendar, href, etag, calendar_data)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for (calendar, o) in objects {
+        stmt.execute(params![
+            source_id,
+            calendar,
+            o.href,
+            o.etag,
+            o.calendar_data
+        ])?;
+    }
+    Ok(())
+}
+
+pub fn replace_source_calendars(
+    conn: &Connection,
+    source_id: i64,
+    calendars: &[SourceCalendar],
+) -> Result<()> {
+    conn.execute(
+        "DELETE FROM source_calendars WHERE source_id = ?1",
+        params![source_id],
+    )?;
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO source_calendars (source_id, href, events, color, calendar_order)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for c in calendars {
+        stmt.execute(params![
+            source_id,
+            c.href,
+            c.events.concat(),
+            c.color,
+            c.order
+        ])?;
+    }
+    label_event_calendars(conn, source_id, calendars)
+}
+
+pub fn list_source_calendars(conn: &Connection, source_id: i64) -> Result<Vec<SourceCalendar>> {
+    let mut stmt = conn.prepare(
+        "SELECT href, events, color, calendar_order FROM source_calendars
+         WHERE source_id = ?1 ORDER BY href",
+    )?;
+    let rows = stmt.query_map(params![source_id], |row| {
+        let events: String = row.get(1)?;
+        Ok(SourceCalendar {
+            href: row.get(0)?,
+            // Every stored event ends its last line with CRLF.
+            events: events
+                .split_inclusive("END:VEVENT\r\n")
+                .map(str::to_owned)
+                .collect(),
+            color: row.get(2)?,
+            order: row.get(3)?,
+            objects: Vec::new(),
+        })
+    })?;
+    Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DiscoveredCalendar {
+    pub href: String,
+    pub displayname: Option<String>,
+    // As the server reports it, usually `#RRGGBB` or `#RRGGBBAA`.
+    pub color: Option<String>,
+    // Position in the user's calendar list; lower comes first.
+    pub order: Option<i64>,
+    // Component types the calendar accepts, e.g. `VEVENT`; empty when the
+    // server doesn't say.
+    pub components: Vec<String>,
+}
+
+pub fn save_discovered_calendars(
+    conn: &Connection,
+    source_id: i64,
+    calendars: &[DiscoveredCalendar],
+) -> Result<()> {
+    conn.execute(
+        "DELETE FROM discovered_calendars WHERE source_id = ?1",
+        params![source_id],
+    )?;
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO discovered_calendars (source_id, href, displayname, color, calendar_order, components)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    for c in calendars {
+        stmt.execute(params![
+            source_id,
+            c.href,
+            c.displayname,
+            c.color,
+            c.order,
+            c.components.join(",")
+        ])?;
+    }
+    Ok(())
+}
+
+// The last discovery of a source's calendars and when it ran, if any.
+pub fn list_discovered_calendars(
+    conn: &Connection,
+    source_id: i64,
+) -> Result<Option<(Vec<DiscoveredCalendar>, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT href, displayname, color, calendar_order, components, discovered_at
+         FROM discovered_calendars WHERE source_id = ?1
+         ORDER BY calendar_order IS NULL, calendar_order, href",
+    )?;
+    let rows = stmt.query_map(params![source_id], |row| {
+        let components: String = row.get(4)?;
+        Ok((
+            DiscoveredCalendar {
+                href: row.get(0)?,
+                displayname: row.get(1)?,
+                color: row.get(2)?,
+                order: row.get(3)?,
+                components: components
+                    .split(',')
+                    .filter(|c| !c.is_empty())
+                    .map(str::to_owned)
+                    .collect(),
+            },
+            row.get::<_, String>(5)?,
+        ))
+    })?;
+    let rows = rows.collect::<std::result::Result<Vec<_>, _>>()?;
+    let Some(discovered_at) = rows.first().map(|(_, at)| at.clone()) else {
+        return Ok(None);
+    };
+    Ok(Some((
+        rows.into_iter().map(|(c, _)| c).collect(),
+        discovered_at,
+    )))
+}
+
+pub fn get_feed_metadata(conn: &Connection, source_id: i64) -> Result<FeedMetadata> {
+    let mut stmt = conn.prepare("SELECT key, value FROM feed_metadata WHERE source_id = ?1")?;
+    let rows = stmt.query_map(params![source_id], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    Ok(rows.collect::<std::result::Result<FeedMetadata, _>>()?)
+}
+
+// Replaces a source's metadata and re-embeds it in the stored feed.
+pub fn set_feed_metadata(conn: &Connection, source_id: i64, metadata: &FeedMetadata) -> Result<()> {
+    feed_metadata::validate(metadata)?;
+    // Joins the caller's transaction when there is one (e.g. config import).
+    if !conn.is_autocommit() {
+        return write_feed_metadata(conn, source_id, metadata);
+    }
+    let tx = conn.unchecked_transaction()?;
+    write_feed_metadata(&tx, source_id, metadata)?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn write_feed_metadata(conn: &Connection, source_id: i64, metadata: &FeedMetadata) -> Result<()> {
+    conn.execute(
+        "DELETE FROM feed_metadata WHERE source_id = ?1",
+        params![source_id],
+    )?;
+    for (key, value) in metadata {
+        conn.execute(
+            "INSERT INTO feed_metadata (source_id, key, value) VALUES (?1, ?2, ?3)",
+            params![source_id, key, value],
+        )?;
+    }
+    if let Some(content) = get_ics_data(conn, source_id)? {
+        save_ics_data(conn, source_id, &content)?;
+    }
+    Ok(())
+}
+
+pub fn get_feed_properties(conn: &Connection, source_id: i64) -> Result<FeedProperties> {
+    let mut stmt = conn.prepare(
+        "SELECT name, description, timezone, color, prodid, published_ttl_minutes, method
+         FROM feed_properties WHERE source_id = ?1",
+    )?;
+    let mut rows = stmt.query_map(params![source_id], |row| {
+        Ok(FeedProperties {
+            name: row.get(0)?,
+            description: row.get(1)?,
+            timezone: row.get(2)?,
+            color: row.get(3)?,
+            prodid: row.get(4)?,
+            published_ttl_minutes: row.get(5)?,
+            method: row.get(6)?,
+        })
+    })?;
+    match rows.next() {
+        Some(row) => Ok(row?),
+        None => Ok(FeedProperties::default()),
+    }
+}
+
+// Replaces a source's header properties and re-embeds them in the stored feed.
+pub fn set_feed_properties(
+    conn: &Connection,
+    source_id: i64,
+    properties: &FeedProperties,
+) -> Result<()> {
+    feed_metadata::validate_properties(properties)?;
+    if !conn.is_autocommit() {
+        return write_feed_properties(conn, source_id, properties);
+    }
+    let tx = conn.unchecked_transaction()?;
+    write_feed_properties(&tx, source_id, properties)?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn write_feed_properties(
+    conn: &Connection,
+    source_id: i64,
+    properties: &FeedProperties,
+) -> Result<()> {
+    let previous = get_feed_properties(conn, source_id)?;
+    conn.execute(
+        "INSERT INTO feed_properties (source_id, name, description, timezone, color, prodid, published_ttl_minutes, method)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(source_id) DO UPDATE SET name = ?2, description = ?3, timezone = ?4,
+             color = ?5, prodid = ?6, published_ttl_minutes = ?7, method = ?8",
+        params![
+            source_id,
+            properties.name,
+            properties.description,
+            properties.timezone,
+            properties.color,
+            properties.prodid,
+            properties.published_ttl_minutes,
+            properties.method
+        ],
+    )?;
+    if let Some(content) = get_ics_data(conn, source_id)? {
+        save_ics_data(conn, source_id, &feed_metadata::strip(&content, &previous))?;
+    }
+    Ok(())
+}
+
+pub fn get_ics_data(conn: &Connection, source_id: i64) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT ics_content, stored_in_file, content_hash FROM ics_data WHERE source_id = ?1",
+        params![source_id],
+        |row| feed_content(row, 0),
+    )
+    .optional()?
+    .map(load_feed_content)
+    .transpose()
+}
+
+const FEED_BY_PATH: &str =
+    "SELECT d.ics_content, d.stored_in_file, d.content_hash, d.rehosted_attachments
+     FROM ics_data d JOIN sources s ON d.source_id = s.id
+     WHERE s.ics_path = ?1
+     UNION ALL
+     SELECT d.ics_content, d.stored_in_file, d.content_hash, d.rehosted_attachments
+     FROM ics_data d JOIN source_paths sp ON d.source_id = sp.source_id
+     WHERE sp.path = ?1
+     LIMIT 1";
+
+const FEED_BY_PUBLIC_PATH: &str =
+    "SELECT d.ics_content, d.stored_in_file, d.content_hash, d.rehosted_attachments
+     FROM ics_data d JOIN sources s ON d.source_id = s.id
+     WHERE s.public_ics_path = ?1 AND s.public_ics = 1
+     UNION ALL
+     SELECT d.ics_content, d.stored_in_file, d.content_hash, d.rehosted_attachments
+     FROM ics_data d JOIN source_paths sp ON d.source_id = sp.source_id
+     WHERE sp.path = ?1 AND sp.is_public = 1
+     LIMIT 1";
+
+pub fn get_ics_data_by_path(conn: &Connection, path: &str) -> Result<Option<String>> {
+    conn.query_row(FEED_BY_PATH, params![path], |row| feed_content(row, 0))
+        .optional()?
+        .map(load_feed_content)
+        .transpose()
+}
+
+pub fn get_named_ics_data_by_path(
+    conn: &Connection,
+    path: &str,
+) -> Result<Option<(String, String)>> {
+    conn.query_row(
+        "SELECT s.name, d.ics_content, d.stored_in_file, d.content_hash
+         FROM ics_data d JOIN sources s ON d.source_id = s.id
+         WHERE s.ics_path = ?1
+         UNION ALL
+         SELECT s.name, d.ics_content, d.stored_in_file, d.content_hash
+         FROM ics_data d JOIN source_paths sp ON d.source_id = sp.source_id
+         JOIN sources s ON s.id = sp.source_id
+         WHERE sp.path = ?1
+         LIMIT 1",
+        params![path],
+        |row| Ok((row.get::<_, String>(0)?, feed_content(row, 1)?)),
+    )
+    .optional()?
+    .map(|(name, feed)| Ok((name, load_feed_content(feed)?)))
+    .transpose()
+}
+
+pub fn get_ics_data_by_public_path(conn: &Connection, path: &str) -> Result<Option<String>> {
+    conn.query_row(FEED_BY_PUBLIC_PATH, params![path], |row| {
+        feed_content(row, 0)
+    })
+    .optional()?
+    .map(load_feed_content)
+    .transpose()
+}
+
+// The content hash of a feed kept in a file that can be streamed as is, i.e.
+// one without rehosted attachment links to resolve against the request.
+pub fn get_feed_file_by_path(
+    conn: &Connection,
+    path: &str,
+    public: bool,
+) -> Result<Option<String>> {
+    let sql = match public {
+        true => FEED_BY_PUBLIC_PATH,
+        false => FEED_BY_PATH,
+    };
+    Ok(conn
+        .query_row(sql, params![path], |row| {
+            let (_, in_file, hash) = feed_content(row, 0)?;
+            let rehosted: bool = row.get(3)?;
+            Ok((in_file && !rehosted).then_some(hash))
+        })
+        .optional()?
+        .flatten())
+}
+
+const PART_BY_PATH: &str =
+    "SELECT p.ics_content, p.stored_in_file, p.content_hash, p.rehosted_attachments
+     FROM feed_part_data p JOIN sources s ON p.source_id = s.id
+     WHERE s.ics_path = ?1 AND p.part = ?2
+     UNION ALL
+     SELECT p.ics_content, p.stored_in_file, p.content_hash, p.rehosted_attachments
+     FROM feed_part_data p JOIN source_paths sp ON p.source_id = sp.source_id
+     WHERE sp.path = ?1 AND p.part = ?2
+     LIMIT 1";
+
+const PART_BY_PUBLIC_PATH: &str =
+    "SELECT p.ics_content, p.stored_in_file, p.content_hash, p.rehosted_attachments
+     FROM feed_part_data p JOIN sources s ON p.source_id = s.id
+     WHERE s.public_ics_path = ?1 AND s.public_ics = 1 AND p.part = ?2
+     UNION ALL
+     SELECT p.ics_content, p.stored_in_file, p.content_hash, p.rehosted_attachments
+     FROM feed_part_data p JOIN source_paths sp ON p.source_id = sp.source_id
+     WHERE sp.path = ?1 AND sp.is_public = 1 AND p.part = ?2
+     LIMIT 1";
+
+// Part `part` of a split feed, read like `get_ics_data_by_path`.
+pub fn get_feed_part_by_path(
+    conn: &Connection,
+    path: &str,
+    part: usize,
+    public: bool,
+) -> Result<Option<String>> {
+    let sql = match public {
+        true => PART_BY_PUBLIC_PATH,
+        false => PART_BY_PATH,
+    };
+    conn.query_row(sql, params![path, part as i64], |row| feed_content(row, 0))
+        .optional()?
+        .map(load_feed_content)
+        .transpose()
+}
+
+// Like `get_feed_file_by_path`, for part `part` of a split feed.
+pub fn get_feed_part_file_by_path(
+    conn: &Connection,
+    path: &str,
+    part: usize,
+    public: bool,
+) -> Result<Option<String>> {
+    let sql = match public {
+        true => PART_BY_PUBLIC_PATH,
+        false => PART_BY_PATH,
+    };
+    Ok(conn
+        .query_row(sql, params![path, part as i64], |row| {
+            let (_, in_file, hash) = feed_content(row, 0)?;
+            let rehosted: bool = row.get(3)?;
+            Ok((in_file && !rehosted).then_some(hash))
+        })
+        .optional()?
+        .flatten())
+}
+
+pub fn is_public_standard_ics(conn: &Connection, ics_path: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM (
+            SELECT 1 FROM sources WHERE ics_path = ?1 AND public_ics = 1 AND (public_ics_path IS NULL OR public_ics_path = '')
+            UNION ALL
+            SELECT 1 FROM source_paths WHERE path = ?1 AND is_public = 1
+         ) t",
+        params![ics_path],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+// The source behind a feed path and the working hours it publishes: its
+// own, or else its owner's. Empty when neither has any.
+pub fn get_working_hours_by_path(
+    conn: &Connection,
+    path: &str,
+    public: bool,
+) -> Result<Option<(i64, String, String)>> {
+    let (by_source, by_path) = match public {
+        true => (
+            "s.public_ics_path = ?1 AND s.public_ics = 1",
+            "sp.path = ?1 AND sp.is_public = 1",
+        ),
+        false => ("s.ics_path = ?1", "sp.path = ?1"),
+    };
+    let hours = "coalesce(nullif(s.working_hours, ''), u.working_hours, '')";
+    conn.query_row(
+        &format!(
+            "SELECT s.id, s.name, {hours} FROM sources s
+             LEFT JOIN users u ON u.id = s.owner_id WHERE {by_source}
+             UNION ALL
+             SELECT s.id, s.name, {hours} FROM source_paths sp
+             JOIN sources s ON s.id = sp.source_id
+             LEFT JOIN users u ON u.id = s.owner_id WHERE {by_path}
+             LIMIT 1"
+        ),
+        params![path],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+// The source behind a feed path, its name and how often subscribers should
+// refresh: the published TTL, else the sync interval. None when neither is set.
+pub fn get_subscription_by_path(
+    conn: &Connection,
+    path: &str,
+    public: bool,
+) -> Result<Option<(i64, String, Option<i64>)>> {
+    let (by_source, by_path) = match public {
+        true => (
+            "s.public_ics_path = ?1 AND s.public_ics = 1",
+            "sp.path = ?1 AND sp.is_public = 1",
+        ),
+        false => ("s.ics_path = ?1", "sp.path = ?1"),
+    };
+    let refresh = "coalesce(fp.published_ttl_minutes * 60, nullif(s.sync_interval_secs, 0))";
+    conn.query_row(
+        &format!(
+            "SELECT s.id, s.name, {refresh} FROM sources s
+             LEFT JOIN feed_properties fp ON fp.source_id = s.id WHERE {by_source}
+             UNION ALL
+             SELECT s.id, s.name, {refresh} FROM source_paths sp
+             JOIN sources s ON s.id = sp.source_id
+             LEFT JOIN feed_properties fp ON fp.source_id = s.id WHERE {by_path}
+             LIMIT 1"
+        ),
+        params![path],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+// --- Attachments (binary ATTACH values rehosted at /attachments/{hash}) ---
+
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub hash: String,
+    pub fmttype: Option<String>,
+    pub data: Vec<u8>,
+}
+
+pub fn save_attachments(
+    conn: &Connection,
+    source_id: i64,
+    attachments: &[Attachment],
+    ics_content: &str,
+) -> Result<()> {
+    for a in attachments {
+        conn.execute(
+            "INSERT OR IGNORE INTO attachments (source_id, hash, fmttype, data) VALUES (?1, ?2, ?3, ?4)",
+            params![source_id, a.hash, a.fmttype, a.data],
+        )?;
+    }
+    conn.execute(
+        "DELETE FROM attachments WHERE source_id = ?1 AND instr(?2, '/attachments/' || hash) = 0",
+        params![source_id, ics_content],
+    )?;
+    Ok(())
+}
+
+pub fn get_attachment(conn: &Connection, hash: &str) -> Result<Option<Attachment>> {
+    let mut stmt =
+        conn.prepare("SELECT hash, fmttype, data FROM attachments WHERE hash = ?1 LIMIT 1")?;
+    let mut rows = stmt.query_map(params![hash], |row| {
+        Ok(Attachment {
+            hash: row.get(0)?,
+            fmttype: row.get(1)?,
+            data: row.get(2)?,
+        })
+    })?;
+    match rows.next() {
+        Some(Ok(a)) => Ok(Some(a)),
+        Some(Err(e)) => Err(e.into()),
+        None => Ok(None),
+    }
+}
+
+pub fn is_public_attachment(conn: &Connection, hash: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM attachments a JOIN sources s ON a.source_id = s.id
+         WHERE a.hash = ?1 AND (s.public_ics = 1 OR EXISTS (
+             SELECT 1 FROM source_paths sp WHERE sp.source_id = s.id AND sp.is_public = 1
+         ))",
+        params![hash],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+// --- Source Paths (additional ICS routes per source) ---
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SourcePath {
+    pub id: i64,
+    pub source_id: i64,
+    pub path: String,
+    pub is_public: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateSourcePath {
+    pub path: String,
+    #[serde(default)]
+    pub is_public: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateSourcePath {
+    pub path: Option<String>,
+    pub is_public: Option<bool>,
+}
+
+fn validate_source_path(conn: &Connection, path: &str, exclude_id: Option<i64>) -> Result<String> {
+    let trimmed = path.trim();
+    require_non_empty("Path", trimmed)?;
+    validate_ics_path(trimmed)?;
+    ensure!(!trimmed.starts_with('/'), "Path must not start with /");
+    ensure!(!trimmed.contains(".."), "Path must not contain ..");
+
+    let sources_count: i64 = conn.query_row(
+        "SELECT count(*) FROM sources WHERE ics_path = ?1 OR public_ics_path = ?1",
+        params![trimmed],
+        |row| row.get(0),
+    )?;
+    ensure!(
+        sources_count == 0,
+        "Path conflicts with an existing source ICS path"
+    );
+
+    let sp_count: i64 = match exclude_id {
+        Some(id) => conn.query_row(
+            "SELECT count(*) FROM source_paths WHERE path = ?1 AND id != ?2",
+            params![trimmed, id],
+            |row| row.get(0),
+        )?,
+        None => conn.query_row(
+            "SELECT count(*) FROM source_paths WHERE path = ?1",
+            params![trimmed],
+            |row| row.get(0),
+        )?,
+    };
+    ensure!(sp_count == 0, "Duplicate path is not allowed");
+
+    Ok(trimmed.to_owned())
+}
+
+pub fn list_source_paths(conn: &Connection, source_id: i64) -> Result<Vec<SourcePath>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, source_id, path, is_public, created_at FROM source_paths WHERE source_id = ?1 ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![source_id], |row| {
+        Ok(SourcePath {
+            id: row.get(0)?,
+            source_id: row.get(1)?,
+            path: row.get(2)?,
+            is_public: row.get(3)?,
+            created_at: row.get(4)?,
+        })
+    })?;
+    Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+pub fn get_source_path(conn: &Connection, id: i64) -> Result<Option<SourcePath>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, source_id, path, is_public, created_at FROM source_paths WHERE id = ?1",
+    )?;
+    let mut rows = stmt.query_map(params![id], |row| {
+        Ok(SourcePath {
+            id: row.get(0)?,
+            source_id: row.get(1)?,
+            path: row.get(2)?,
+            is_public: row.get(3)?,
             created_at: row.get(4)?,
         })
     })?;
@@ -530,236 +2446,1497 @@ pub fn get_source_path(conn: &Connection, id: i64) -> Result<Option<SourcePath>>
     }
 }
 
-pub fn create_source_path(
-    conn: &Connection,
-    source_id: i64,
-    body: &CreateSourcePath,
-) -> Result<i64> {
-    ensure!(get_source(conn, source_id)?.is_some(), "Source not found");
-    let validated_path = validate_source_path(conn, &body.path, None)?;
-    conn.execute(
-        "INSERT INTO source_paths (source_id, path, is_public) VALUES (?1, ?2, ?3)",
-        params![source_id, validated_path, body.is_public],
+pub fn create_source_path(
+    conn: &Connection,
+    source_id: i64,
+    body: &CreateSourcePath,
+) -> Result<i64> {
+    ensure!(get_source(conn, source_id)?.is_some(), "Source not found");
+    let validated_path = validate_source_path(conn, &body.path, None)?;
+    conn.execute(
+        "INSERT INTO source_paths (source_id, path, is_public) VALUES (?1, ?2, ?3)",
+        params![source_id, validated_path, body.is_public],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn update_source_path(conn: &Connection, id: i64, upd: &UpdateSourcePath) -> Result<bool> {
+    let existing = match get_source_path(conn, id)? {
+        Some(sp) => sp,
+        None => return Ok(false),
+    };
+
+    let eff_path = match &upd.path {
+        Some(p) => validate_source_path(conn, p, Some(id))?,
+        None => existing.path,
+    };
+    let eff_public = upd.is_public.unwrap_or(existing.is_public);
+
+    conn.execute(
+        "UPDATE source_paths SET path = ?1, is_public = ?2 WHERE id = ?3",
+        params![eff_path, eff_public, id],
+    )?;
+    Ok(true)
+}
+
+pub fn delete_source_path(conn: &Connection, id: i64) -> Result<bool> {
+    let rows = conn.execute("DELETE FROM source_paths WHERE id = ?1", params![id])?;
+    Ok(rows > 0)
+}
+
+// --- Destinations (ICS -> CalDAV reverse sync) ---
+
+// Never serialized directly; the API returns `api::destinations::DestinationView`.
+#[derive(Clone)]
+pub struct Destination {
+    pub id: i64,
+    pub name: String,
+    pub ics_url: String,
+    pub caldav_url: String,
+    pub calendar_name: String,
+    pub username: String,
+    pub password: String,
+    pub sync_interval_secs: i64,
+    pub sync_all: bool,
+    pub keep_local: bool,
+    pub last_synced: Option<String>,
+    pub last_sync_status: Option<String>,
+    pub last_sync_error: Option<String>,
+    pub created_at: String,
+    pub owner_id: Option<i64>,
+    pub enabled: bool,
+    // A local source whose feed is read from the database instead of `ics_url`.
+    pub source_id: Option<i64>,
+    pub conflict_policy: String,
+    // Also publish events created in the calendar; see `reverse_sync::push_feed`.
+    pub bidirectional: bool,
+    // Stamp pushed events with this destination's namespace; see `sync_origin`.
+    pub namespace_uids: bool,
+    // Sent instead of the default User-Agent when not empty.
+    pub user_agent: String,
+    pub custom_headers: CustomHeaders,
+    // Reformat pushed events; see `event_template`.
+    pub summary_template: String,
+    pub description_template: String,
+    pub location_template: String,
+    pub drop_properties: String,
+    // Send matching events to other calendars; see `calendar_routes`.
+    pub routes: CalendarRoutes,
+    // What pushed events keep of ATTENDEE and ORGANIZER; see `scheduling`.
+    pub scheduling: String,
+    pub last_sync_result: Option<DestinationRunResult>,
+    // Pace of uploads; see `throttle`.
+    pub max_requests_per_second: f64,
+    pub request_delay_ms: i64,
+    // Credentials for fetching `ics_url`, for feeds behind a login. The
+    // password may be a reference (see `credentials`); a bearer token goes in
+    // `ics_headers` as Authorization.
+    pub ics_username: String,
+    pub ics_password: String,
+    pub ics_headers: CustomHeaders,
+    // Where the last fetch of `ics_url` ended up after webcal:// and
+    // redirects, when that was somewhere else.
+    pub resolved_ics_url: Option<String>,
+}
+
+// What the last sync of a destination that got through did, failures
+// included.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct DestinationRunResult {
+    pub uploaded: usize,
+    pub added: usize,
+    pub changed: usize,
+    pub skipped: usize,
+    pub deleted: usize,
+    pub total: usize,
+    pub failed: Vec<FailedEvent>,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct CreateDestination {
+    pub name: String,
+    // Either an ICS URL or the ID of a local source, not both.
+    #[serde(default)]
+    pub ics_url: String,
+    #[serde(default)]
+    pub source_id: Option<i64>,
+    pub caldav_url: String,
+    pub calendar_name: String,
+    pub username: String,
+    pub password: String,
+    #[serde(deserialize_with = "crate::units::duration_secs")]
+    pub sync_interval_secs: i64,
+    #[serde(default)]
+    pub sync_all: bool,
+    #[serde(default)]
+    pub keep_local: bool,
+    pub owner_id: Option<i64>,
+    pub conflict_policy: Option<String>,
+    #[serde(default)]
+    pub bidirectional: bool,
+    #[serde(default)]
+    pub namespace_uids: bool,
+    pub user_agent: Option<String>,
+    pub custom_headers: Option<CustomHeaders>,
+    pub summary_template: Option<String>,
+    pub description_template: Option<String>,
+    pub location_template: Option<String>,
+    pub drop_properties: Option<String>,
+    pub routes: Option<CalendarRoutes>,
+    pub scheduling: Option<String>,
+    pub max_requests_per_second: Option<f64>,
+    pub request_delay_ms: Option<i64>,
+    pub ics_username: Option<String>,
+    pub ics_password: Option<String>,
+    pub ics_headers: Option<CustomHeaders>,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct UpdateDestination {
+    pub name: Option<String>,
+    // Setting one of these switches the destination's input to it.
+    pub ics_url: Option<String>,
+    pub source_id: Option<i64>,
+    pub caldav_url: Option<String>,
+    pub calendar_name: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[serde(default, deserialize_with = "crate::units::opt_duration_secs")]
+    pub sync_interval_secs: Option<i64>,
+    pub sync_all: Option<bool>,
+    pub keep_local: Option<bool>,
+    pub owner_id: Option<i64>,
+    pub conflict_policy: Option<String>,
+    pub bidirectional: Option<bool>,
+    pub namespace_uids: Option<bool>,
+    pub user_agent: Option<String>,
+    // Values sent back masked keep the stored ones.
+    pub custom_headers: Option<CustomHeaders>,
+    pub summary_template: Option<String>,
+    pub description_template: Option<String>,
+    pub location_template: Option<String>,
+    pub drop_properties: Option<String>,
+    pub routes: Option<CalendarRoutes>,
+    pub scheduling: Option<String>,
+    pub max_requests_per_second: Option<f64>,
+    pub request_delay_ms: Option<i64>,
+    pub ics_username: Option<String>,
+    pub ics_password: Option<String>,
+    pub ics_headers: Option<CustomHeaders>,
+}
+
+const DESTINATION_COLUMNS: &str = "id, name, ics_url, caldav_url, calendar_name, username, password, sync_interval_secs, sync_all, keep_local, last_synced, last_sync_status, last_sync_error, created_at, owner_id, enabled, source_id, conflict_policy, bidirectional, namespace_uids, user_agent, custom_headers, summary_template, description_template, location_template, drop_properties, routes, scheduling, last_sync_result, max_requests_per_second, request_delay_ms, ics_username, ics_password, ics_headers, resolved_ics_url";
+
+fn map_destination_row(row: &rusqlite::Row) -> rusqlite::Result<Destination> {
+    Ok(Destination {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        ics_url: row.get(2)?,
+        caldav_url: row.get(3)?,
+        calendar_name: row.get(4)?,
+        username: row.get(5)?,
+        password: row.get(6)?,
+        sync_interval_secs: row.get(7)?,
+        sync_all: row.get(8)?,
+        keep_local: row.get(9)?,
+        last_synced: row.get(10)?,
+        last_sync_status: row.get(11)?,
+        last_sync_error: row.get(12)?,
+        created_at: row.get(13)?,
+        owner_id: row.get(14)?,
+        enabled: row.get(15)?,
+        source_id: row.get(16)?,
+        conflict_policy: row.get(17)?,
+        bidirectional: row.get(18)?,
+        namespace_uids: row.get(19)?,
+        user_agent: row.get(20)?,
+        custom_headers: headers_column(row, 21)?,
+        summary_template: row.get(22)?,
+        description_template: row.get(23)?,
+        location_template: row.get(24)?,
+        drop_properties: row.get(25)?,
+        routes: serde_json::from_str(&row.get::<_, String>(26)?).unwrap_or_default(),
+        scheduling: row.get(27)?,
+        last_sync_result: row
+            .get::<_, Option<String>>(28)?
+            .and_then(|json| serde_json::from_str(&json).ok()),
+        max_requests_per_second: row.get(29)?,
+        request_delay_ms: row.get(30)?,
+        ics_username: row.get(31)?,
+        ics_password: row.get(32)?,
+        ics_headers: headers_column(row, 33)?,
+        resolved_ics_url: row.get(34)?,
+    })
+}
+
+pub fn list_destinations(conn: &Connection) -> Result<Vec<Destination>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM destinations ORDER BY id",
+        DESTINATION_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], map_destination_row)?;
+    Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+pub fn get_destination(conn: &Connection, id: i64) -> Result<Option<Destination>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM destinations WHERE id = ?1",
+        DESTINATION_COLUMNS
+    ))?;
+    let mut rows = stmt.query_map(params![id], map_destination_row)?;
+    match rows.next() {
+        Some(Ok(d)) => Ok(Some(d)),
+        Some(Err(e)) => Err(e.into()),
+        None => Ok(None),
+    }
+}
+
+pub fn find_overlapping_destinations(
+    conn: &Connection,
+    caldav_url: &str,
+    calendar_name: &str,
+    exclude_id: Option<i64>,
+) -> Result<Vec<Destination>> {
+    let base_sql = format!(
+        "SELECT {} FROM destinations WHERE caldav_url = ?1 AND calendar_name = ?2",
+        DESTINATION_COLUMNS
+    );
+
+    match exclude_id {
+        Some(id) => {
+            let sql = format!("{} AND id != ?3", base_sql);
+            let mut stmt = conn.prepare(&sql)?;
+            let rows =
+                stmt.query_map(params![caldav_url, calendar_name, id], map_destination_row)?;
+            Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+        }
+        None => {
+            let mut stmt = conn.prepare(&base_sql)?;
+            let rows = stmt.query_map(params![caldav_url, calendar_name], map_destination_row)?;
+            Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+        }
+    }
+}
+
+// What reverse sync does with an event that was edited in the destination
+// calendar since it was last pushed.
+pub const CONFLICT_POLICIES: &[&str] = &["overwrite", "skip", "duplicate"];
+
+fn validate_conflict_policy(policy: &str) -> Result<()> {
+    ensure!(
+        CONFLICT_POLICIES.contains(&policy),
+        "Conflict policy must be one of: {}",
+        CONFLICT_POLICIES.join(", ")
+    );
+    Ok(())
+}
+
+// Two-way sync merges events from a single calendar only.
+fn validate_routes(routes: &[calendar_routes::CalendarRoute], bidirectional: bool) -> Result<()> {
+    calendar_routes::validate(routes)?;
+    ensure!(
+        routes.is_empty() || !bidirectional,
+        "Calendar routes cannot be combined with two-way sync"
+    );
+    Ok(())
+}
+
+// A destination reads either an ICS URL or a local source of the same owner.
+fn validate_destination_input(
+    conn: &Connection,
+    ics_url: &str,
+    source_id: Option<i64>,
+    owner_id: Option<i64>,
+) -> Result<()> {
+    let Some(source_id) = source_id else {
+        return require_non_empty("ICS URL", ics_url);
+    };
+    ensure!(
+        ics_url.trim().is_empty(),
+        "Set either an ICS URL or a source, not both"
+    );
+    let source = get_source(conn, source_id)?;
+    ensure!(
+        source.is_some_and(|s| owner_id.is_none() || s.owner_id == owner_id),
+        "Source {} not found",
+        source_id
+    );
+    Ok(())
+}
+
+pub fn create_destination(conn: &Connection, dest: &CreateDestination) -> Result<i64> {
+    require_non_empty("Name", &dest.name)?;
+    validate_destination_input(conn, &dest.ics_url, dest.source_id, dest.owner_id)?;
+    require_non_empty("CalDAV URL", &dest.caldav_url)?;
+    require_non_empty("Calendar name", &dest.calendar_name)?;
+    require_non_empty("Username", &dest.username)?;
+    require_non_empty("Password", &dest.password)?;
+    require_non_negative("Sync interval", dest.sync_interval_secs)?;
+    let conflict_policy = dest.conflict_policy.as_deref().unwrap_or("overwrite");
+    validate_conflict_policy(conflict_policy)?;
+    validate_owner(conn, dest.owner_id)?;
+    let user_agent = dest.user_agent.as_deref().unwrap_or("");
+    http_clients::validate_user_agent(user_agent)?;
+    let custom_headers = dest.custom_headers.clone().unwrap_or_default();
+    http_clients::validate_custom_headers(&custom_headers)?;
+    let templates = [
+        dest.summary_template.as_deref().unwrap_or(""),
+        dest.description_template.as_deref().unwrap_or(""),
+        dest.location_template.as_deref().unwrap_or(""),
+    ];
+    for template in templates {
+        event_template::validate(template)?;
+    }
+    let drop_properties =
+        event_template::normalize_drop_properties(dest.drop_properties.as_deref().unwrap_or(""))?;
+    let routes = dest.routes.clone().unwrap_or_default();
+    validate_routes(&routes, dest.bidirectional)?;
+    let scheduling = dest.scheduling.as_deref().unwrap_or("keep");
+    scheduling::validate_mode(scheduling)?;
+    let requests_per_second = dest.max_requests_per_second.unwrap_or(0.0);
+    let request_delay_ms = dest.request_delay_ms.unwrap_or(0);
+    throttle::validate(requests_per_second, request_delay_ms)?;
+    let ics_headers = dest.ics_headers.clone().unwrap_or_default();
+    http_clients::validate_custom_headers(&ics_headers)?;
+
+    conn.execute(
+        "INSERT INTO destinations (name, ics_url, caldav_url, calendar_name, username, password, sync_interval_secs, sync_all, keep_local, owner_id, source_id, conflict_policy, bidirectional, namespace_uids, user_agent, custom_headers, summary_template, description_template, location_template, drop_properties, routes, scheduling, max_requests_per_second, request_delay_ms, ics_username, ics_password, ics_headers) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27)",
+        params![dest.name, dest.ics_url.trim(), dest.caldav_url, dest.calendar_name, dest.username, dest.password, dest.sync_interval_secs, dest.sync_all, dest.keep_local, dest.owner_id, dest.source_id, conflict_policy, dest.bidirectional, dest.namespace_uids, user_agent, serde_json::to_string(&custom_headers)?, templates[0], templates[1], templates[2], drop_properties, serde_json::to_string(&routes)?, scheduling, requests_per_second, request_delay_ms, dest.ics_username.as_deref().unwrap_or(""), dest.ics_password.as_deref().unwrap_or(""), serde_json::to_string(&ics_headers)?],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn update_destination(conn: &Connection, id: i64, upd: &UpdateDestination) -> Result<bool> {
+    let existing = match get_destination(conn, id)? {
+        Some(d) => d,
+        None => return Ok(false),
+    };
+
+    if let Some(ref v) = upd.name {
+        require_non_empty("Name", v)?;
+    }
+    let (ics_url, source_id) = match (upd.ics_url.as_deref(), upd.source_id) {
+        (None, None) => (existing.ics_url.as_str(), existing.source_id),
+        (None, Some(id)) => ("", Some(id)),
+        (url, source_id) => (redact::unmask(url, &existing.ics_url), source_id),
+    };
+    let owner_id = upd.owner_id.or(existing.owner_id);
+    validate_destination_input(conn, ics_url, source_id, owner_id)?;
+    if let Some(ref v) = upd.caldav_url {
+        require_non_empty("CalDAV URL", v)?;
+    }
+    if let Some(ref v) = upd.calendar_name {
+        require_non_empty("Calendar name", v)?;
+    }
+    if let Some(ref v) = upd.username {
+        require_non_empty("Username", v)?;
+    }
+    if let Some(v) = upd.sync_interval_secs {
+        require_non_negative("Sync interval", v)?;
+    }
+    if let Some(ref v) = upd.conflict_policy {
+        validate_conflict_policy(v)?;
+    }
+    validate_owner(conn, upd.owner_id)?;
+    if let Some(ref v) = upd.user_agent {
+        http_clients::validate_user_agent(v)?;
+    }
+    let custom_headers =
+        redact::unmask_headers(upd.custom_headers.as_ref(), &existing.custom_headers);
+    http_clients::validate_custom_headers(&custom_headers)?;
+    let templates = [
+        upd.summary_template
+            .as_deref()
+            .unwrap_or(&existing.summary_template),
+        upd.description_template
+            .as_deref()
+            .unwrap_or(&existing.description_template),
+        upd.location_template
+            .as_deref()
+            .unwrap_or(&existing.location_template),
+    ];
+    for template in templates {
+        event_template::validate(template)?;
+    }
+    let drop_properties = match upd.drop_properties.as_deref() {
+        Some(list) => event_template::normalize_drop_properties(list)?,
+        None => existing.drop_properties.clone(),
+    };
+    let routes = upd.routes.as_ref().unwrap_or(&existing.routes);
+    validate_routes(routes, upd.bidirectional.unwrap_or(existing.bidirectional))?;
+    let scheduling = upd.scheduling.as_deref().unwrap_or(&existing.scheduling);
+    scheduling::validate_mode(scheduling)?;
+    let requests_per_second = upd
+        .max_requests_per_second
+        .unwrap_or(existing.max_requests_per_second);
+    let request_delay_ms = upd.request_delay_ms.unwrap_or(existing.request_delay_ms);
+    throttle::validate(requests_per_second, request_delay_ms)?;
+    let ics_headers = redact::unmask_headers(upd.ics_headers.as_ref(), &existing.ics_headers);
+    http_clients::validate_custom_headers(&ics_headers)?;
+
+    let eff_caldav_url = redact::unmask(upd.caldav_url.as_deref(), &existing.caldav_url);
+    let eff_calendar_name = upd
+        .calendar_name
+        .as_deref()
+        .unwrap_or(&existing.calendar_name);
+
+    conn.execute(
+        "UPDATE destinations SET name = ?1, ics_url = ?2, caldav_url = ?3, calendar_name = ?4, username = ?5, password = ?6, sync_interval_secs = ?7, sync_all = ?8, keep_local = ?9, owner_id = ?10, source_id = ?11, conflict_policy = ?12, bidirectional = ?13, namespace_uids = ?14, user_agent = ?15, custom_headers = ?16, summary_template = ?17, description_template = ?18, location_template = ?19, drop_properties = ?20, routes = ?21, scheduling = ?22, max_requests_per_second = ?23, request_delay_ms = ?24, ics_username = ?25, ics_password = ?26, ics_headers = ?27, resolved_ics_url = NULL, feed_hash = NULL, feed_etag = NULL WHERE id = ?28",
+        params![
+            upd.name.as_deref().unwrap_or(&existing.name),
+            ics_url.trim(),
+            eff_caldav_url,
+            eff_calendar_name,
+            upd.username.as_deref().unwrap_or(&existing.username),
+            upd.password.as_deref().filter(|s| !s.trim().is_empty()).unwrap_or(&existing.password),
+            upd.sync_interval_secs.unwrap_or(existing.sync_interval_secs),
+            upd.sync_all.unwrap_or(existing.sync_all),
+            upd.keep_local.unwrap_or(existing.keep_local),
+            owner_id,
+            source_id,
+            upd.conflict_policy.as_deref().unwrap_or(&existing.conflict_policy),
+            upd.bidirectional.unwrap_or(existing.bidirectional),
+            upd.namespace_uids.unwrap_or(existing.namespace_uids),
+            upd.user_agent.as_deref().unwrap_or(&existing.user_agent),
+            serde_json::to_string(&custom_headers)?,
+            templates[0],
+            templates[1],
+            templates[2],
+            drop_properties,
+            serde_json::to_string(routes)?,
+            scheduling,
+            requests_per_second,
+            request_delay_ms,
+            upd.ics_username.as_deref().unwrap_or(&existing.ics_username),
+            upd.ics_password.as_deref().filter(|s| !s.trim().is_empty()).unwrap_or(&existing.ics_password),
+            serde_json::to_string(&ics_headers)?,
+            id
+        ],
+    )?;
+    // ETags only mean something for the calendar they came from.
+    if upd.caldav_url.is_some() || upd.calendar_name.is_some() {
+        conn.execute(
+            "DELETE FROM destination_events WHERE destination_id = ?1",
+            params![id],
+        )?;
+    }
+    Ok(true)
+}
+
+// Hash and ETag of the feed a destination last pushed, so an unchanged feed
+// can be skipped. Cleared when the destination is edited.
+pub fn get_destination_feed_validators(
+    conn: &Connection,
+    id: i64,
+) -> Result<(Option<String>, Option<String>)> {
+    Ok(conn
+        .query_row(
+            "SELECT feed_hash, feed_etag FROM destinations WHERE id = ?1",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?
+        .unwrap_or_default())
+}
+
+pub fn set_destination_feed_validators(
+    conn: &Connection,
+    id: i64,
+    hash: Option<&str>,
+    etag: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE destinations SET feed_hash = ?1, feed_etag = ?2 WHERE id = ?3",
+        params![hash, etag, id],
+    )?;
+    Ok(())
+}
+
+pub fn set_destination_resolved_url(conn: &Connection, id: i64, url: Option<&str>) -> Result<()> {
+    conn.execute(
+        "UPDATE destinations SET resolved_ics_url = ?1 WHERE id = ?2",
+        params![url, id],
+    )?;
+    Ok(())
+}
+
+// An event reverse sync found edited in the destination calendar, and what
+// the destination's conflict policy did about it.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EventConflict {
+    pub uid: String,
+    pub summary: Option<String>,
+    // The policy applied: "overwrite", "skip" or "duplicate".
+    pub resolution: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detected_at: Option<String>,
+}
+
+// ETag of each event as last pushed or seen unchanged, keyed by UID. None when
+// the server didn't say.
+pub fn get_destination_event_etags(
+    conn: &Connection,
+    id: i64,
+) -> Result<HashMap<String, Option<String>>> {
+    let mut stmt =
+        conn.prepare("SELECT uid, etag FROM destination_events WHERE destination_id = ?1")?;
+    let rows = stmt.query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
+// An event a destination sync could not upload or delete. It is retried on
+// the following syncs.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FailedEvent {
+    pub uid: String,
+    pub error: String,
+    // Syncs in a row the event failed in.
+    pub attempts: i64,
+}
+
+// Replaces the tracked events of a destination with the result of a sync.
+// `failed` events count one more attempt each.
+pub fn save_destination_events(
+    conn: &Connection,
+    id: i64,
+    etags: &HashMap<String, Option<String>>,
+    conflicts: &[EventConflict],
+    failed: &[FailedEvent],
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    let attempts: HashMap<String, i64> = list_destination_failures(&tx, id)?
+        .into_iter()
+        .map(|f| (f.uid, f.attempts))
+        .collect();
+    tx.execute(
+        "DELETE FROM destination_events WHERE destination_id = ?1",
+        params![id],
+    )?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO destination_events (destination_id, uid, etag) VALUES (?1, ?2, ?3)",
+        )?;
+        for (uid, etag) in etags {
+            insert.execute(params![id, uid, etag])?;
+        }
+        let mut conflict = tx.prepare(
+            "INSERT INTO destination_events (destination_id, uid, summary, conflict, detected_at)
+             VALUES (?1, ?2, ?3, ?4, datetime('now'))
+             ON CONFLICT(destination_id, uid) DO UPDATE SET summary = ?3, conflict = ?4, detected_at = datetime('now')",
+        )?;
+        for c in conflicts {
+            conflict.execute(params![id, c.uid, c.summary, c.resolution])?;
+        }
+        let mut failure = tx.prepare(
+            "INSERT INTO destination_events (destination_id, uid, state, error, attempts)
+             VALUES (?1, ?2, 'failed', ?3, ?4)
+             ON CONFLICT(destination_id, uid) DO UPDATE SET state = 'failed', error = ?3, attempts = ?4",
+        )?;
+        for f in failed {
+            let previous = attempts.get(&f.uid).copied().unwrap_or(0);
+            failure.execute(params![id, f.uid, f.error, previous + f.attempts])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+// Records an event a sync in progress uploaded, so an interrupted run keeps it.
+pub fn record_destination_upload(
+    conn: &Connection,
+    id: i64,
+    uid: &str,
+    etag: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO destination_events (destination_id, uid, etag) VALUES (?1, ?2, ?3)
+         ON CONFLICT(destination_id, uid) DO UPDATE SET etag = ?3, state = 'synced', error = NULL, attempts = 0",
+        params![id, uid, etag],
+    )?;
+    Ok(())
+}
+
+// Marks the orphans a sync in progress is about to delete.
+pub fn mark_destination_deletes(conn: &Connection, id: i64, uids: &[&String]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut mark = tx.prepare(
+            "INSERT INTO destination_events (destination_id, uid, state) VALUES (?1, ?2, 'pending_delete')
+             ON CONFLICT(destination_id, uid) DO UPDATE SET state = 'pending_delete'",
+        )?;
+        for uid in uids {
+            mark.execute(params![id, uid])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn forget_destination_event(conn: &Connection, id: i64, uid: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM destination_events WHERE destination_id = ?1 AND uid = ?2",
+        params![id, uid],
+    )?;
+    Ok(())
+}
+
+pub fn list_destination_failures(conn: &Connection, id: i64) -> Result<Vec<FailedEvent>> {
+    let mut stmt = conn.prepare(
+        "SELECT uid, error, attempts FROM destination_events
+         WHERE destination_id = ?1 AND state = 'failed' ORDER BY uid",
+    )?;
+    let rows = stmt.query_map(params![id], |row| {
+        Ok(FailedEvent {
+            uid: row.get(0)?,
+            error: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            attempts: row.get(2)?,
+        })
+    })?;
+    Ok(rows.collect::<std::result::Result<_, _>>()?)
+}
+
+// Where a destination's syncs stand: events in step with the calendar, orphans
+// an interrupted run had yet to delete, and events that failed.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DestinationSyncState {
+    pub synced: usize,
+    pub pending_deletes: Vec<String>,
+    pub failed: Vec<FailedEvent>,
+}
+
+pub fn get_destination_sync_state(conn: &Connection, id: i64) -> Result<DestinationSyncState> {
+    let synced = conn.query_row(
+        "SELECT COUNT(*) FROM destination_events WHERE destination_id = ?1 AND state = 'synced' AND conflict IS NULL",
+        params![id],
+        |row| row.get::<_, i64>(0),
+    )? as usize;
+    let mut stmt = conn.prepare(
+        "SELECT uid FROM destination_events
+         WHERE destination_id = ?1 AND state = 'pending_delete' ORDER BY uid",
+    )?;
+    let pending_deletes = stmt
+        .query_map(params![id], |row| row.get(0))?
+        .collect::<std::result::Result<_, _>>()?;
+    Ok(DestinationSyncState {
+        synced,
+        pending_deletes,
+        failed: list_destination_failures(conn, id)?,
+    })
+}
+
+// Conflicts found by the destination's most recent sync.
+pub fn list_destination_conflicts(conn: &Connection, id: i64) -> Result<Vec<EventConflict>> {
+    let mut stmt = conn.prepare(
+        "SELECT uid, summary, conflict, detected_at FROM destination_events
+         WHERE destination_id = ?1 AND conflict IS NOT NULL ORDER BY uid",
+    )?;
+    let rows = stmt.query_map(params![id], |row| {
+        Ok(EventConflict {
+            uid: row.get(0)?,
+            summary: row.get(1)?,
+            resolution: row.get(2)?,
+            detected_at: row.get(3)?,
+        })
+    })?;
+    Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+pub fn save_destination_feed(conn: &Connection, id: i64, content: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO destination_feeds (destination_id, ics_content) VALUES (?1, ?2)
+         ON CONFLICT(destination_id) DO UPDATE SET ics_content = ?2, updated_at = datetime('now')
+         WHERE ics_content != ?2",
+        params![id, content],
+    )?;
+    Ok(())
+}
+
+// The combined feed of a bidirectional destination, once it has synced.
+pub fn get_destination_feed(conn: &Connection, id: i64) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT ics_content FROM destination_feeds WHERE destination_id = ?1",
+            params![id],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+pub fn delete_destination(conn: &Connection, id: i64) -> Result<bool> {
+    let rows = conn.execute("DELETE FROM destinations WHERE id = ?1", params![id])?;
+    Ok(rows > 0)
+}
+
+pub fn set_destination_enabled(conn: &Connection, id: i64, enabled: bool) -> Result<bool> {
+    let rows = conn.execute(
+        "UPDATE destinations SET enabled = ?1 WHERE id = ?2",
+        params![enabled, id],
+    )?;
+    Ok(rows > 0)
+}
+
+pub fn update_destination_sync_duration(
+    conn: &Connection,
+    id: i64,
+    duration_ms: i64,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE destinations SET last_sync_duration_ms = ?1 WHERE id = ?2",
+        params![duration_ms, id],
+    )?;
+    Ok(())
+}
+
+pub fn update_destination_sync_status(
+    conn: &Connection,
+    id: i64,
+    status: &str,
+    error: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE destinations SET last_sync_status = ?1, last_sync_error = ?2, last_synced = datetime('now') WHERE id = ?3",
+        params![status, error.map(redact::text), id],
+    )?;
+    record_sync_run(conn, &AutoSyncKey::Destination(id), status, error)
+}
+
+pub fn set_destination_run_result(
+    conn: &Connection,
+    id: i64,
+    result: &DestinationRunResult,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE destinations SET last_sync_result = ?1 WHERE id = ?2",
+        params![serde_json::to_string(result)?, id],
+    )?;
+    Ok(())
+}
+
+// --- Declarative config (sources/destinations from CONFIG_FILE) ---
+
+// --- Users (per-user ownership of sources and destinations) ---
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    #[serde(skip_serializing)]
+    #[schema(write_only)]
+    pub password_hash: String,
+    pub is_admin: bool,
+    pub created_at: String,
+    pub quotas: Quotas,
+    // Default working hours for the user's sources.
+    pub working_hours: String,
+}
+
+// Limits for one user; None means unlimited.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct Quotas {
+    pub max_sources: Option<i64>,
+    pub max_events: Option<i64>,
+    pub max_feed_bytes_per_day: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Usage {
+    pub sources: i64,
+    pub events: i64,
+    pub feed_bytes_today: i64,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct CreateUser {
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub is_admin: bool,
+    #[serde(default)]
+    pub quotas: Quotas,
+    #[serde(default)]
+    pub working_hours: String,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct UpdateUser {
+    pub password: Option<String>,
+    pub is_admin: Option<bool>,
+    // Replaces all quotas when present.
+    pub quotas: Option<Quotas>,
+    pub working_hours: Option<String>,
+}
+
+const USER_COLUMNS: &str = "id, username, password_hash, is_admin, created_at, max_sources, max_events, max_feed_bytes_per_day, working_hours";
+
+fn map_user_row(row: &rusqlite::Row) -> rusqlite::Result<User> {
+    Ok(User {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        password_hash: row.get(2)?,
+        is_admin: row.get(3)?,
+        created_at: row.get(4)?,
+        quotas: Quotas {
+            max_sources: row.get(5)?,
+            max_events: row.get(6)?,
+            max_feed_bytes_per_day: row.get(7)?,
+        },
+        working_hours: row.get(8)?,
+    })
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    use argon2::password_hash::{PasswordHasher, SaltString};
+    let salt = SaltString::encode_b64(uuid::Uuid::new_v4().as_bytes())
+        .map_err(|e| anyhow::anyhow!("Failed to generate salt: {}", e))?;
+    Ok(argon2::Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?
+        .to_string())
+}
+
+fn validate_owner(conn: &Connection, owner_id: Option<i64>) -> Result<()> {
+    if let Some(id) = owner_id {
+        ensure!(get_user(conn, id)?.is_some(), "Owner user {} not found", id);
+    }
+    Ok(())
+}
+
+pub fn list_users(conn: &Connection) -> Result<Vec<User>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM users ORDER BY id", USER_COLUMNS))?;
+    let rows = stmt.query_map([], map_user_row)?;
+    Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+pub fn get_user(conn: &Connection, id: i64) -> Result<Option<User>> {
+    conn.query_row(
+        &format!("SELECT {} FROM users WHERE id = ?1", USER_COLUMNS),
+        params![id],
+        map_user_row,
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+pub fn get_user_by_username(conn: &Connection, username: &str) -> Result<Option<User>> {
+    conn.query_row(
+        &format!("SELECT {} FROM users WHERE username = ?1", USER_COLUMNS),
+        params![username],
+        map_user_row,
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+pub fn has_users(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row("SELECT count(*) FROM users", [], |row| row.get(0))?;
+    Ok(count > 0)
+}
+
+pub fn create_user(conn: &Connection, user: &CreateUser) -> Result<i64> {
+    require_non_empty("Username", &user.username)?;
+    require_non_empty("Password", &user.password)?;
+    ensure!(
+        get_user_by_username(conn, user.username.trim())?.is_none(),
+        "Duplicate username is not allowed"
+    );
+    validate_quotas(&user.quotas)?;
+    WorkingHours::parse(&user.working_hours)?;
+    conn.execute(
+        "INSERT INTO users (username, password_hash, is_admin, max_sources, max_events, max_feed_bytes_per_day, working_hours)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            user.username.trim(),
+            hash_password(&user.password)?,
+            user.is_admin,
+            user.quotas.max_sources,
+            user.quotas.max_events,
+            user.quotas.max_feed_bytes_per_day,
+            user.working_hours.trim()
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn update_user(conn: &Connection, id: i64, upd: &UpdateUser) -> Result<bool> {
+    let existing = match get_user(conn, id)? {
+        Some(u) => u,
+        None => return Ok(false),
+    };
+    let password_hash = match upd.password.as_deref().filter(|p| !p.trim().is_empty()) {
+        Some(p) => hash_password(p)?,
+        None => existing.password_hash,
+    };
+    let quotas = upd.quotas.clone().unwrap_or(existing.quotas);
+    validate_quotas(&quotas)?;
+    if let Some(ref v) = upd.working_hours {
+        WorkingHours::parse(v)?;
+    }
+    conn.execute(
+        "UPDATE users SET password_hash = ?1, is_admin = ?2, max_sources = ?3, max_events = ?4,
+         max_feed_bytes_per_day = ?5, working_hours = ?6 WHERE id = ?7",
+        params![
+            password_hash,
+            upd.is_admin.unwrap_or(existing.is_admin),
+            quotas.max_sources,
+            quotas.max_events,
+            quotas.max_feed_bytes_per_day,
+            upd.working_hours
+                .as_deref()
+                .map(str::trim)
+                .unwrap_or(&existing.working_hours),
+            id
+        ],
+    )?;
+    Ok(true)
+}
+
+fn validate_quotas(quotas: &Quotas) -> Result<()> {
+    for (name, value) in [
+        ("max_sources", quotas.max_sources),
+        ("max_events", quotas.max_events),
+        ("max_feed_bytes_per_day", quotas.max_feed_bytes_per_day),
+    ] {
+        ensure!(value.is_none_or(|v| v >= 0), "{} cannot be negative", name);
+    }
+    Ok(())
+}
+
+pub fn get_usage(conn: &Connection, owner_id: Option<i64>) -> Result<Usage> {
+    let (sources, events): (i64, i64) = conn.query_row(
+        "SELECT count(*), coalesce(sum(d.event_count), 0) FROM sources s
+         LEFT JOIN ics_data d ON d.source_id = s.id WHERE s.owner_id IS ?1",
+        params![owner_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
     )?;
-    Ok(conn.last_insert_rowid())
+    let feed_bytes_today = match owner_id {
+        Some(id) => conn
+            .query_row(
+                "SELECT bytes FROM feed_usage WHERE user_id = ?1 AND day = date('now')",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .unwrap_or(0),
+        None => 0,
+    };
+    Ok(Usage {
+        sources,
+        events,
+        feed_bytes_today,
+    })
 }
 
-pub fn update_source_path(conn: &Connection, id: i64, upd: &UpdateSourcePath) -> Result<bool> {
-    let existing = match get_source_path(conn, id)? {
-        Some(sp) => sp,
-        None => return Ok(false),
+fn check_source_quota(conn: &Connection, owner_id: Option<i64>) -> Result<()> {
+    let Some(user) = owner_id.map(|id| get_user(conn, id)).transpose()?.flatten() else {
+        return Ok(());
     };
+    if let Some(max) = user.quotas.max_sources {
+        let used = get_usage(conn, Some(user.id))?.sources;
+        ensure!(used < max, "Source quota reached ({} of {})", used, max);
+    }
+    Ok(())
+}
 
-    let eff_path = match &upd.path {
-        Some(p) => validate_source_path(conn, p, Some(id))?,
-        None => existing.path,
+// Called before storing a sync result that would give the source `event_count` events.
+pub fn check_event_quota(conn: &Connection, source: &Source, event_count: i64) -> Result<()> {
+    let Some(user) = source
+        .owner_id
+        .map(|id| get_user(conn, id))
+        .transpose()?
+        .flatten()
+    else {
+        return Ok(());
     };
-    let eff_public = upd.is_public.unwrap_or(existing.is_public);
+    let Some(max) = user.quotas.max_events else {
+        return Ok(());
+    };
+    let others: i64 = conn.query_row(
+        "SELECT coalesce(sum(d.event_count), 0) FROM ics_data d JOIN sources s ON d.source_id = s.id
+         WHERE s.owner_id = ?1 AND s.id != ?2",
+        params![user.id, source.id],
+        |row| row.get(0),
+    )?;
+    ensure!(
+        others + event_count <= max,
+        "Event quota exceeded: {} events would exceed the limit of {}",
+        others + event_count,
+        max
+    );
+    Ok(())
+}
 
+// Records feed bytes served for the owning user; false when today's quota is used up.
+pub fn record_feed_bytes(conn: &Connection, owner_id: i64, bytes: i64) -> Result<bool> {
+    let Some(user) = get_user(conn, owner_id)? else {
+        return Ok(true);
+    };
+    if let Some(max) = user.quotas.max_feed_bytes_per_day {
+        let used = get_usage(conn, Some(owner_id))?.feed_bytes_today;
+        if used + bytes > max {
+            return Ok(false);
+        }
+    }
     conn.execute(
-        "UPDATE source_paths SET path = ?1, is_public = ?2 WHERE id = ?3",
-        params![eff_path, eff_public, id],
+        "INSERT INTO feed_usage (user_id, day, bytes) VALUES (?1, date('now'), ?2)
+         ON CONFLICT(user_id, day) DO UPDATE SET bytes = bytes + ?2",
+        params![owner_id, bytes],
     )?;
     Ok(true)
 }
 
-pub fn delete_source_path(conn: &Connection, id: i64) -> Result<bool> {
-    let rows = conn.execute("DELETE FROM source_paths WHERE id = ?1", params![id])?;
-    Ok(rows > 0)
+pub fn feed_owner(conn: &Connection, path: &str) -> Result<Option<i64>> {
+    Ok(conn
+        .query_row(
+            "SELECT owner_id FROM sources WHERE ics_path = ?1 OR public_ics_path = ?1
+             UNION ALL
+             SELECT s.owner_id FROM source_paths sp JOIN sources s ON sp.source_id = s.id
+             WHERE sp.path = ?1
+             LIMIT 1",
+            params![path],
+            |row| row.get::<_, Option<i64>>(0),
+        )
+        .optional()?
+        .flatten())
 }
 
-// --- Destinations (ICS -> CalDAV reverse sync) ---
+// What the feed server needs for cache headers, beyond the content itself.
+#[derive(Debug, Default, PartialEq)]
+pub struct FeedCaching {
+    pub ttl_minutes: Option<i64>,
+    // When the stored feed content last changed, in UTC.
+    pub updated_at: Option<String>,
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-pub struct Destination {
+pub fn feed_caching(conn: &Connection, path: &str) -> Result<FeedCaching> {
+    Ok(conn
+        .query_row(
+            "SELECT fp.published_ttl_minutes, d.updated_at FROM sources s
+             LEFT JOIN feed_properties fp ON fp.source_id = s.id
+             LEFT JOIN ics_data d ON d.source_id = s.id
+             WHERE s.ics_path = ?1 OR s.public_ics_path = ?1
+             UNION ALL
+             SELECT fp.published_ttl_minutes, d.updated_at FROM source_paths sp
+             LEFT JOIN feed_properties fp ON fp.source_id = sp.source_id
+             LEFT JOIN ics_data d ON d.source_id = sp.source_id
+             WHERE sp.path = ?1
+             LIMIT 1",
+            params![path],
+            |row| {
+                Ok(FeedCaching {
+                    ttl_minutes: row.get(0)?,
+                    updated_at: row.get(1)?,
+                })
+            },
+        )
+        .optional()?
+        .unwrap_or_default())
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SyncOverviewEntry {
     pub id: i64,
     pub name: String,
-    pub ics_url: String,
-    pub caldav_url: String,
-    pub calendar_name: String,
-    pub username: String,
-    #[serde(skip_serializing)]
-    #[schema(write_only)]
-    pub password: String,
+    // "source" or "destination"
+    pub kind: String,
     pub sync_interval_secs: i64,
-    pub sync_all: bool,
-    pub keep_local: bool,
     pub last_synced: Option<String>,
     pub last_sync_status: Option<String>,
     pub last_sync_error: Option<String>,
-    pub created_at: String,
+    pub last_sync_duration_ms: Option<i64>,
+    // Filled in from the auto-sync scheduler; None when not scheduled.
+    pub next_run: Option<String>,
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
-pub struct CreateDestination {
+pub fn sync_overview(conn: &Connection, owner_id: Option<i64>) -> Result<Vec<SyncOverviewEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, 'source', sync_interval_secs, last_synced, last_sync_status, last_sync_error, last_sync_duration_ms
+         FROM sources WHERE source_type != 'upload' AND (?1 IS NULL OR owner_id = ?1)
+         UNION ALL
+         SELECT id, name, 'destination', sync_interval_secs, last_synced, last_sync_status, last_sync_error, last_sync_duration_ms
+         FROM destinations WHERE ?1 IS NULL OR owner_id = ?1
+         ORDER BY 3 DESC, 1",
+    )?;
+    let rows = stmt.query_map(params![owner_id], |row| {
+        Ok(SyncOverviewEntry {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            kind: row.get(2)?,
+            sync_interval_secs: row.get(3)?,
+            last_synced: row.get(4)?,
+            last_sync_status: row.get(5)?,
+            last_sync_error: row.get(6)?,
+            last_sync_duration_ms: row.get(7)?,
+            next_run: None,
+        })
+    })?;
+    Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Feed {
+    pub source_id: i64,
     pub name: String,
-    pub ics_url: String,
-    pub caldav_url: String,
-    pub calendar_name: String,
-    pub username: String,
-    pub password: String,
-    pub sync_interval_secs: i64,
-    #[serde(default)]
-    pub sync_all: bool,
-    #[serde(default)]
-    pub keep_local: bool,
+    pub ics_path: String,
+    pub public_ics: bool,
+    pub public_ics_path: Option<String>,
+    pub event_count: i64,
+    pub updated_at: Option<String>,
+    pub metadata: FeedMetadata,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed_urls: Option<FeedUrls>,
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
-pub struct UpdateDestination {
-    pub name: Option<String>,
-    pub ics_url: Option<String>,
-    pub caldav_url: Option<String>,
-    pub calendar_name: Option<String>,
-    pub username: Option<String>,
-    pub password: Option<String>,
-    pub sync_interval_secs: Option<i64>,
-    pub sync_all: Option<bool>,
-    pub keep_local: Option<bool>,
+pub fn list_feeds(conn: &Connection, owner_id: Option<i64>) -> Result<Vec<Feed>> {
+    let mut stmt = conn.prepare(
+        "SELECT s.id, s.name, s.ics_path, s.public_ics, s.public_ics_path, COALESCE(d.event_count, 0), d.updated_at
+         FROM sources s LEFT JOIN ics_data d ON d.source_id = s.id
+         WHERE ?1 IS NULL OR s.owner_id = ?1
+         ORDER BY s.id",
+    )?;
+    let rows = stmt.query_map(params![owner_id], |row| {
+        Ok(Feed {
+            source_id: row.get(0)?,
+            name: row.get(1)?,
+            ics_path: row.get(2)?,
+            public_ics: row.get(3)?,
+            public_ics_path: row.get(4)?,
+            event_count: row.get(5)?,
+            updated_at: row.get(6)?,
+            metadata: FeedMetadata::new(),
+            feed_urls: None,
+        })
+    })?;
+    let mut feeds = rows.collect::<std::result::Result<Vec<_>, _>>()?;
+    for feed in &mut feeds {
+        feed.metadata = get_feed_metadata(conn, feed.source_id)?;
+    }
+    Ok(feeds)
 }
 
-fn map_destination_row(row: &rusqlite::Row) -> rusqlite::Result<Destination> {
-    Ok(Destination {
-        id: row.get(0)?,
-        name: row.get(1)?,
-        ics_url: row.get(2)?,
-        caldav_url: row.get(3)?,
-        calendar_name: row.get(4)?,
-        username: row.get(5)?,
-        password: row.get(6)?,
-        sync_interval_secs: row.get(7)?,
-        sync_all: row.get(8)?,
-        keep_local: row.get(9)?,
-        last_synced: row.get(10)?,
-        last_sync_status: row.get(11)?,
-        last_sync_error: row.get(12)?,
-        created_at: row.get(13)?,
-    })
+pub fn delete_user(conn: &Connection, id: i64) -> Result<bool> {
+    let rows = conn.execute("DELETE FROM users WHERE id = ?1", params![id])?;
+    Ok(rows > 0)
 }
 
-pub fn list_destinations(conn: &Connection) -> Result<Vec<Destination>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, name, ics_url, caldav_url, calendar_name, username, password, sync_interval_secs, sync_all, keep_local, last_synced, last_sync_status, last_sync_error, created_at FROM destinations ORDER BY id",
-    )?;
-    let rows = stmt.query_map([], map_destination_row)?;
+pub fn list_sources_for_owner(conn: &Connection, owner_id: i64) -> Result<Vec<Source>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM sources WHERE owner_id = ?1 ORDER BY id",
+        SOURCE_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![owner_id], map_source_row)?;
     Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
 }
 
-pub fn get_destination(conn: &Connection, id: i64) -> Result<Option<Destination>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, name, ics_url, caldav_url, calendar_name, username, password, sync_interval_secs, sync_all, keep_local, last_synced, last_sync_status, last_sync_error, created_at FROM destinations WHERE id = ?1",
-    )?;
-    let mut rows = stmt.query_map(params![id], map_destination_row)?;
-    match rows.next() {
-        Some(Ok(d)) => Ok(Some(d)),
-        Some(Err(e)) => Err(e.into()),
-        None => Ok(None),
-    }
+pub fn list_destinations_for_owner(conn: &Connection, owner_id: i64) -> Result<Vec<Destination>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM destinations WHERE owner_id = ?1 ORDER BY id",
+        DESTINATION_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![owner_id], map_destination_row)?;
+    Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
 }
 
-pub fn find_overlapping_destinations(
-    conn: &Connection,
-    caldav_url: &str,
-    calendar_name: &str,
-    exclude_id: Option<i64>,
-) -> Result<Vec<Destination>> {
-    let base_sql = "SELECT id, name, ics_url, caldav_url, calendar_name, username, password, sync_interval_secs, sync_all, keep_local, last_synced, last_sync_status, last_sync_error, created_at FROM destinations WHERE caldav_url = ?1 AND calendar_name = ?2";
+pub fn source_owned_by(conn: &Connection, id: i64, owner_id: i64) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM sources WHERE id = ?1 AND owner_id = ?2",
+        params![id, owner_id],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
 
-    match exclude_id {
-        Some(id) => {
-            let sql = format!("{} AND id != ?3", base_sql);
-            let mut stmt = conn.prepare(&sql)?;
-            let rows =
-                stmt.query_map(params![caldav_url, calendar_name, id], map_destination_row)?;
-            Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
-        }
-        None => {
-            let mut stmt = conn.prepare(base_sql)?;
-            let rows = stmt.query_map(params![caldav_url, calendar_name], map_destination_row)?;
-            Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
-        }
-    }
+pub fn destination_owned_by(conn: &Connection, id: i64, owner_id: i64) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM destinations WHERE id = ?1 AND owner_id = ?2",
+        params![id, owner_id],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
 }
 
-pub fn create_destination(conn: &Connection, dest: &CreateDestination) -> Result<i64> {
-    require_non_empty("Name", &dest.name)?;
-    require_non_empty("ICS URL", &dest.ics_url)?;
-    require_non_empty("CalDAV URL", &dest.caldav_url)?;
-    require_non_empty("Calendar name", &dest.calendar_name)?;
-    require_non_empty("Username", &dest.username)?;
-    require_non_empty("Password", &dest.password)?;
-    require_non_negative("Sync interval", dest.sync_interval_secs)?;
+pub fn feed_owned_by(conn: &Connection, path: &str, owner_id: i64) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM sources s
+         WHERE s.owner_id = ?2 AND (s.ics_path = ?1 OR EXISTS (
+             SELECT 1 FROM source_paths sp WHERE sp.source_id = s.id AND sp.path = ?1
+         ))",
+        params![path, owner_id],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
 
-    conn.execute(
-        "INSERT INTO destinations (name, ics_url, caldav_url, calendar_name, username, password, sync_interval_secs, sync_all, keep_local) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-        params![dest.name, dest.ics_url, dest.caldav_url, dest.calendar_name, dest.username, dest.password, dest.sync_interval_secs, dest.sync_all, dest.keep_local],
+pub fn attachment_owned_by(conn: &Connection, hash: &str, owner_id: i64) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM attachments a JOIN sources s ON a.source_id = s.id
+         WHERE a.hash = ?1 AND s.owner_id = ?2",
+        params![hash, owner_id],
+        |row| row.get(0),
     )?;
-    Ok(conn.last_insert_rowid())
+    Ok(count > 0)
 }
 
-pub fn update_destination(conn: &Connection, id: i64, upd: &UpdateDestination) -> Result<bool> {
-    let existing = match get_destination(conn, id)? {
-        Some(d) => d,
-        None => return Ok(false),
-    };
+pub fn apply_declared_config(
+    conn: &Connection,
+    sources: &[CreateSource],
+    destinations: &[CreateDestination],
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    upsert_declared(&tx, sources, destinations)?;
+    tx.commit()?;
+    Ok(())
+}
 
-    if let Some(ref v) = upd.name {
-        require_non_empty("Name", v)?;
-    }
-    if let Some(ref v) = upd.ics_url {
-        require_non_empty("ICS URL", v)?;
-    }
-    if let Some(ref v) = upd.caldav_url {
-        require_non_empty("CalDAV URL", v)?;
-    }
-    if let Some(ref v) = upd.calendar_name {
-        require_non_empty("Calendar name", v)?;
-    }
-    if let Some(ref v) = upd.username {
-        require_non_empty("Username", v)?;
+// Sources are matched by ICS path and destinations by name. Callers own the transaction.
+pub fn upsert_declared(
+    tx: &Connection,
+    sources: &[CreateSource],
+    destinations: &[CreateDestination],
+) -> Result<()> {
+    for src in sources {
+        let existing: Option<i64> = tx
+            .query_row(
+                "SELECT id FROM sources WHERE ics_path = ?1",
+                params![src.ics_path],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let result = match existing {
+            Some(id) => update_source(
+                tx,
+                id,
+                &UpdateSource {
+                    name: Some(src.name.clone()),
+                    caldav_url: Some(src.caldav_url.clone()),
+                    username: Some(src.username.clone()),
+                    password: Some(src.password.clone()),
+                    ics_path: None,
+                    sync_interval_secs: Some(src.sync_interval_secs),
+                    public_ics: Some(src.public_ics),
+                    public_ics_path: Some(src.public_ics_path.clone().unwrap_or_default()),
+                    attachment_mode: Some(src.attachment_mode.clone().unwrap_or("keep".into())),
+                    source_type: Some(src.source_type.clone().unwrap_or("caldav".into())),
+                    proxy_enabled: src.proxy_enabled,
+                    owner_id: src.owner_id,
+                    archive_after_months: src.archive_after_months,
+                    namespace_uids: Some(src.namespace_uids),
+                    sync_on_startup: Some(src.sync_on_startup.clone().unwrap_or("default".into())),
+                    fetch_mode: Some(src.fetch_mode.clone().unwrap_or("query".into())),
+                    user_agent: Some(src.user_agent.clone().unwrap_or_default()),
+                    custom_headers: src.custom_headers.clone(),
+                    sync_window: Some(src.sync_window.clone().unwrap_or_default()),
+                    working_hours: Some(src.working_hours.clone().unwrap_or_default()),
+                    holiday_region: Some(src.holiday_region.clone().unwrap_or_default()),
+                    repair_rules: Some(src.repair_rules.clone().unwrap_or_default()),
+                    time_shift_zone: Some(src.time_shift_zone.clone().unwrap_or_default()),
+                    time_shift_mode: Some(src.time_shift_mode.clone().unwrap_or("shift".into())),
+                    max_events: Some(src.max_events.unwrap_or(0)),
+                    max_feed_bytes: Some(src.max_feed_bytes.unwrap_or(0)),
+                    feed_limit_policy: Some(
+                        src.feed_limit_policy.clone().unwrap_or("truncate".into()),
+                    ),
+                },
+            )
+            .map(|_| ()),
+            None => create_source(tx, src).map(|_| ()),
+        };
+        result.with_context(|| format!("Declared source '{}'", src.ics_path))?;
     }
-    if let Some(v) = upd.sync_interval_secs {
-        require_non_negative("Sync interval", v)?;
+    for dest in destinations {
+        let existing: Option<i64> = tx
+            .query_row(
+                "SELECT id FROM destinations WHERE name = ?1",
+                params![dest.name],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let result = match existing {
+            Some(id) => update_destination(
+                tx,
+                id,
+                &UpdateDestination {
+                    name: None,
+                    ics_url: Some(dest.ics_url.clone()),
+                    source_id: dest.source_id,
+                    caldav_url: Some(dest.caldav_url.clone()),
+                    calendar_name: Some(dest.calendar_name.clone()),
+                    username: Some(dest.username.clone()),
+                    password: Some(dest.password.clone()),
+                    sync_interval_secs: Some(dest.sync_interval_secs),
+                    sync_all: Some(dest.sync_all),
+                    keep_local: Some(dest.keep_local),
+                    owner_id: dest.owner_id,
+                    conflict_policy: dest.conflict_policy.clone(),
+                    bidirectional: Some(dest.bidirectional),
+                    namespace_uids: Some(dest.namespace_uids),
+                    user_agent: Some(dest.user_agent.clone().unwrap_or_default()),
+                    custom_headers: dest.custom_headers.clone(),
+                    summary_template: Some(dest.summary_template.clone().unwrap_or_default()),
+                    description_template: Some(
+                        dest.description_template.clone().unwrap_or_default(),
+                    ),
+                    location_template: Some(dest.location_template.clone().unwrap_or_default()),
+                    drop_properties: Some(dest.drop_properties.clone().unwrap_or_default()),
+                    routes: Some(dest.routes.clone().unwrap_or_default()),
+                    scheduling: Some(dest.scheduling.clone().unwrap_or_else(|| "keep".into())),
+                    max_requests_per_second: Some(dest.max_requests_per_second.unwrap_or(0.0)),
+                    request_delay_ms: Some(dest.request_delay_ms.unwrap_or(0)),
+                    ics_username: Some(dest.ics_username.clone().unwrap_or_default()),
+                    ics_password: dest.ics_password.clone(),
+                    ics_headers: Some(dest.ics_headers.clone().unwrap_or_default()),
+                },
+            )
+            .map(|_| ()),
+            None => create_destination(tx, dest).map(|_| ()),
+        };
+        result.with_context(|| format!("Declared destination '{}'", dest.name))?;
     }
+    Ok(())
+}
 
-    let eff_caldav_url = upd.caldav_url.as_deref().unwrap_or(&existing.caldav_url);
-    let eff_calendar_name = upd
-        .calendar_name
-        .as_deref()
-        .unwrap_or(&existing.calendar_name);
+// --- Sessions (cookie logins for the web UI) ---
+
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: String,
+    pub user_id: Option<i64>,
+    pub username: String,
+    pub expires_at: String,
+}
 
+pub fn create_session(
+    conn: &Connection,
+    user_id: Option<i64>,
+    username: &str,
+    ttl_secs: i64,
+) -> Result<String> {
     conn.execute(
-        "UPDATE destinations SET name = ?1, ics_url = ?2, caldav_url = ?3, calendar_name = ?4, username = ?5, password = ?6, sync_interval_secs = ?7, sync_all = ?8, keep_local = ?9 WHERE id = ?10",
-        params![
-            upd.name.as_deref().unwrap_or(&existing.name),
-            upd.ics_url.as_deref().unwrap_or(&existing.ics_url),
-            eff_caldav_url,
-            eff_calendar_name,
-            upd.username.as_deref().unwrap_or(&existing.username),
-            upd.password.as_deref().filter(|s| !s.trim().is_empty()).unwrap_or(&existing.password),
-            upd.sync_interval_secs.unwrap_or(existing.sync_interval_secs),
-            upd.sync_all.unwrap_or(existing.sync_all),
-            upd.keep_local.unwrap_or(existing.keep_local),
-            id
-        ],
+        "DELETE FROM sessions WHERE expires_at <= datetime('now')",
+        [],
     )?;
-    Ok(true)
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    conn.execute(
+        "INSERT INTO sessions (id, user_id, username, expires_at) VALUES (?1, ?2, ?3, datetime('now', ?4))",
+        params![id, user_id, username, format!("+{} seconds", ttl_secs)],
+    )?;
+    Ok(id)
 }
 
-pub fn delete_destination(conn: &Connection, id: i64) -> Result<bool> {
-    let rows = conn.execute("DELETE FROM destinations WHERE id = ?1", params![id])?;
-    Ok(rows > 0)
+pub fn get_session(conn: &Connection, id: &str) -> Result<Option<Session>> {
+    conn.query_row(
+        "SELECT id, user_id, username, expires_at FROM sessions WHERE id = ?1 AND expires_at > datetime('now')",
+        params![id],
+        |row| {
+            Ok(Session {
+                id: row.get(0)?,
+                user_id: row.get(1)?,
+                username: row.get(2)?,
+                expires_at: row.get(3)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(Into::into)
 }
 
-pub fn update_destination_sync_status(
-    conn: &Connection,
-    id: i64,
-    status: &str,
-    error: Option<&str>,
-) -> Result<()> {
-    conn.execute(
-        "UPDATE destinations SET last_sync_status = ?1, last_sync_error = ?2, last_synced = datetime('now') WHERE id = ?3",
-        params![status, error, id],
+pub fn delete_session(conn: &Connection, id: &str) -> Result<bool> {
+    Ok(conn.execute("DELETE FROM sessions WHERE id = ?1", params![id])? > 0)
+}
+
+// --- Event search ---
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Event {
+    pub source_id: i64,
+    pub source_name: String,
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    // UTC, formatted as `YYYY-MM-DD HH:MM:SS`.
+    pub starts_at: Option<String>,
+    pub ends_at: Option<String>,
+    pub organizer: Option<String>,
+    pub attendees: Vec<String>,
+    // Href of the upstream calendar, and its name if discovery found one.
+    pub calendar: Option<String>,
+    pub calendar_name: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct EventSearch {
+    // Matches the organizer or any attendee.
+    pub email: Option<String>,
+    // Matches the summary.
+    pub text: Option<String>,
+    // Full-text search of the summary, description and location, best
+    // matches first.
+    pub keywords: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub source_id: Option<i64>,
+    pub owner_id: Option<i64>,
+    pub limit: i64,
+}
+
+pub fn search_events(conn: &Connection, search: &EventSearch) -> Result<Vec<Event>> {
+    let email = search
+        .email
+        .as_deref()
+        .map(|e| e.trim().to_ascii_lowercase());
+    let text = search.text.as_deref().map(|t| format!("%{}%", t.trim()));
+    let keywords = search.keywords.as_deref().and_then(fts_query);
+    let mut stmt = conn.prepare(
+        "WITH matches AS (
+             SELECT rowid AS id, rank FROM events_fts WHERE events_fts MATCH coalesce(?8, '\"\"')
+         )
+         SELECT e.source_id, s.name, e.uid, e.summary, e.starts_at, e.ends_at, e.organizer,
+                (SELECT group_concat(a.email, char(10)) FROM event_attendees a WHERE a.event_id = e.id),
+                e.description, e.location, e.calendar, dc.displayname
+         FROM events e JOIN sources s ON e.source_id = s.id
+         LEFT JOIN discovered_calendars dc ON dc.source_id = e.source_id AND dc.href = e.calendar
+         LEFT JOIN matches m ON m.id = e.id
+         WHERE (?1 IS NULL OR e.organizer = ?1
+                OR EXISTS (SELECT 1 FROM event_attendees a WHERE a.event_id = e.id AND a.email = ?1))
+           AND (?2 IS NULL OR e.summary LIKE ?2)
+           AND (?3 IS NULL OR coalesce(e.ends_at, e.starts_at) >= ?3)
+           AND (?4 IS NULL OR e.starts_at < ?4)
+           AND (?5 IS NULL OR e.source_id = ?5)
+           AND (?6 IS NULL OR s.owner_id = ?6)
+           AND (?8 IS NULL OR m.id IS NOT NULL)
+         ORDER BY m.rank, e.starts_at, e.id
+         LIMIT ?7",
     )?;
-    Ok(())
+    let rows = stmt.query_map(
+        params![
+            email,
+            text,
+            search.from,
+            search.to,
+            search.source_id,
+            search.owner_id,
+            search.limit,
+            keywords
+        ],
+        |row| {
+            Ok(Event {
+                source_id: row.get(0)?,
+                source_name: row.get(1)?,
+                uid: row.get(2)?,
+                summary: row.get(3)?,
+                starts_at: row.get(4)?,
+                ends_at: row.get(5)?,
+                organizer: row.get(6)?,
+                attendees: row
+                    .get::<_, Option<String>>(7)?
+                    .map(|a| a.lines().map(str::to_owned).collect())
+                    .unwrap_or_default(),
+                description: row.get(8)?,
+                location: row.get(9)?,
+                calendar: row.get(10)?,
+                calendar_name: row.get(11)?,
+            })
+        },
+    )?;
+    Ok(rows.collect::<std::result::Result<Vec<_>, _>>()?)
 }
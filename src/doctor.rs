@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::IsTerminal;
+use std::path::Path;
+
+use anyhow::Result;
+use rusqlite::{Connection, OpenFlags};
+
+use crate::config::{self, AppConfig};
+use crate::db;
+use crate::server::auth::AuthChain;
+use crate::server::feed_signing::FeedSigner;
+use crate::server::tls::CertStore;
+
+// `server --check`: looks over the configuration, data directory and
+// database without starting the server, and with `--check-endpoints` also
+// tries to reach every configured CalDAV server and ICS URL. Problems that
+// stop the server are failures; those it works around are warnings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Ok,
+    Warning,
+    Failure,
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub level: Level,
+    pub area: &'static str,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct Report(Vec<Finding>);
+
+impl Report {
+    fn add(&mut self, level: Level, area: &'static str, message: impl Into<String>) {
+        self.0.push(Finding {
+            level,
+            area,
+            message: message.into(),
+        });
+    }
+
+    fn check(&mut self, area: &'static str, ok: &str, result: Result<()>) {
+        match result {
+            Ok(()) => self.add(Level::Ok, area, ok),
+            Err(e) => self.add(Level::Failure, area, format!("{:#}", e)),
+        }
+    }
+}
+
+fn columns(conn: &Connection) -> Result<BTreeMap<String, BTreeSet<String>>> {
+    let tables: Vec<String> = conn
+        .prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        )?
+        .query_map([], |row| row.get(0))?
+        .collect::<std::result::Result<_, _>>()?;
+    let mut out = BTreeMap::new();
+    for table in tables {
+        let names = conn
+            .prepare(&format!("SELECT name FROM pragma_table_info('{}')", table))?
+            .query_map([], |row| row.get(0))?
+            .collect::<std::result::Result<_, _>>()?;
+        out.insert(table, names);
+    }
+    Ok(out)
+}
+
+// Tables and columns this version creates that `conn` lacks. The schema has
+// no version number; migrations add what is missing on startup.
+pub fn missing_schema(conn: &Connection) -> Result<Vec<String>> {
+    let current = Connection::open_in_memory()?;
+    db::init_db(&current)?;
+    let have = columns(conn)?;
+    let mut missing = Vec::new();
+    for (table, wanted) in columns(&current)? {
+        match have.get(&table) {
+            None => missing.push(table),
+            Some(cols) => missing.extend(
+                wanted
+                    .difference(cols)
+                    .map(|col| format!("{}.{}", table, col)),
+            ),
+        }
+    }
+    Ok(missing)
+}
+
+fn check_config(report: &mut Report, cfg: &AppConfig) {
+    let deprecated = cfg.deprecated_settings();
+    if !deprecated.is_empty() {
+        report.add(
+            Level::Warning,
+            "config",
+            format!(
+                "{} are deprecated; the first startup turns them into a source",
+                deprecated.join(", ")
+            ),
+        );
+    }
+    if let Some(seed) = &cfg.feed_signing_key {
+        report.check(
+            "config",
+            "Feed signing key is valid",
+            FeedSigner::from_base64(seed).map(|_| ()),
+        );
+    }
+    if let (Some(cert), Some(key)) = (&cfg.tls_cert_path, &cfg.tls_key_path) {
+        report.check(
+            "config",
+            "TLS certificate and key load",
+            CertStore::load(cert, key).map(|_| ()),
+        );
+    }
+    report.check(
+        "config",
+        "Authentication settings are consistent",
+        AuthChain::from_config(cfg).map(|_| ()),
+    );
+    if !cfg.sources.is_empty() || !cfg.destinations.is_empty() {
+        // Applied to an empty database, as startup would apply them.
+        let applied = Connection::open_in_memory()
+            .map_err(Into::into)
+            .and_then(|conn| {
+                db::init_db(&conn)?;
+                db::apply_declared_config(&conn, &cfg.sources, &cfg.destinations)
+            });
+        report.check(
+            "config",
+            &format!(
+                "{} declared sources and {} declared destinations are valid",
+                cfg.sources.len(),
+                cfg.destinations.len()
+            ),
+            applied,
+        );
+    }
+}
+
+fn check_storage(report: &mut Report, cfg: &AppConfig) {
+    report.check(
+        "storage",
+        "Data directory is writable",
+        cfg.check_writable_dirs(),
+    );
+    for dir in cfg.writable_dirs() {
+        if let Some(fs) = config::detect_network_filesystem(&dir) {
+            report.add(
+                Level::Warning,
+                "storage",
+                format!(
+                    "'{}' is on a network filesystem ({}); SQLite locking may be unreliable",
+                    dir.display(),
+                    fs
+                ),
+            );
+        }
+    }
+    let feeds = cfg.feed_dir();
+    if feeds.exists() {
+        let probe = feeds.join(format!(".write-check-{}", std::process::id()));
+        let writable = std::fs::write(&probe, b"ok").and_then(|_| std::fs::remove_file(&probe));
+        match writable {
+            Ok(()) => report.add(Level::Ok, "storage", "Feed directory is writable"),
+            Err(e) => report.add(
+                Level::Failure,
+                "storage",
+                format!(
+                    "Feed directory '{}' is not writable: {}",
+                    feeds.display(),
+                    e
+                ),
+            ),
+        }
+    }
+}
+
+fn check_database(report: &mut Report, path: &str) -> Option<Connection> {
+    if !Path::new(path).exists() {
+        report.add(
+            Level::Ok,
+            "database",
+            format!("'{}' does not exist yet and will be created", path),
+        );
+        return None;
+    }
+    let conn = match Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) {
+        Ok(conn) => conn,
+        Err(e) => {
+            report.add(
+                Level::Failure,
+                "database",
+                format!("Cannot open '{}': {}", path, e),
+            );
+            return None;
+        }
+    };
+    match conn.query_row("PRAGMA quick_check", [], |row| row.get::<_, String>(0)) {
+        Ok(result) if result == "ok" => report.add(Level::Ok, "database", "Integrity check passed"),
+        Ok(result) => report.add(
+            Level::Failure,
+            "database",
+            format!("Integrity check failed: {}", result),
+        ),
+        Err(e) => report.add(
+            Level::Failure,
+            "database",
+            format!("Cannot read '{}': {}", path, e),
+        ),
+    }
+    match missing_schema(&conn) {
+        Ok(missing) if missing.is_empty() => {
+            report.add(Level::Ok, "database", "Schema is up to date")
+        }
+        Ok(missing) => report.add(
+            Level::Warning,
+            "database",
+            format!(
+                "Schema is from an older version and will be migrated on startup (missing {})",
+                missing.join(", ")
+            ),
+        ),
+        Err(e) => report.add(
+            Level::Failure,
+            "database",
+            format!("Cannot read the schema: {:#}", e),
+        ),
+    }
+    Some(conn)
+}
+
+// Any HTTP answer counts; only connection errors and timeouts fail.
+async fn check_endpoints(report: &mut Report, cfg: &AppConfig, conn: Option<&Connection>) {
+    let mut endpoints: BTreeSet<(String, String)> = BTreeSet::new();
+    for src in &cfg.sources {
+        endpoints.insert((src.name.clone(), src.caldav_url.clone()));
+    }
+    for dest in &cfg.destinations {
+        endpoints.insert((dest.name.clone(), dest.caldav_url.clone()));
+        endpoints.insert((dest.name.clone(), dest.ics_url.clone()));
+    }
+    if let Some(conn) = conn {
+        for src in db::list_sources(conn).unwrap_or_default() {
+            endpoints.insert((src.name, src.caldav_url));
+        }
+        for dest in db::list_destinations(conn).unwrap_or_default() {
+            endpoints.insert((dest.name.clone(), dest.caldav_url));
+            endpoints.insert((dest.name, dest.ics_url));
+        }
+    }
+    let client = match reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => return report.add(Level::Failure, "network", e.to_string()),
+    };
+    for (name, url) in endpoints {
+        if !url.starts_with("http") {
+            continue;
+        }
+        let shown = crate::redact::url(&url).into_owned();
+        match client.head(&url).send().await {
+            Ok(res) => report.add(
+                Level::Ok,
+                "network",
+                format!("{}: {} answered {}", name, shown, res.status().as_u16()),
+            ),
+            Err(e) => report.add(
+                Level::Failure,
+                "network",
+                format!(
+                    "{}: cannot reach {}: {}",
+                    name,
+                    shown,
+                    crate::redact::url(&e.to_string())
+                ),
+            ),
+        }
+    }
+}
+
+pub async fn run(cfg: &AppConfig, endpoints: bool) -> Vec<Finding> {
+    let mut report = Report::default();
+    report.add(Level::Ok, "config", "Configuration loaded");
+    check_config(&mut report, cfg);
+    check_storage(&mut report, cfg);
+    let conn = check_database(&mut report, &cfg.db_path());
+    if endpoints {
+        check_endpoints(&mut report, cfg, conn.as_ref()).await;
+    }
+    report.0
+}
+
+// Prints the findings, in color on a terminal unless NO_COLOR is set, and
+// returns whether any failed.
+pub fn print(findings: &[Finding]) -> bool {
+    let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    for f in findings {
+        let (label, code) = match f.level {
+            Level::Ok => ("  ok", "32"),
+            Level::Warning => ("warn", "33"),
+            Level::Failure => ("FAIL", "31"),
+        };
+        match color {
+            true => println!("\x1b[{}m{}\x1b[0m  {:<9}{}", code, label, f.area, f.message),
+            false => println!("{}  {:<9}{}", label, f.area, f.message),
+        }
+    }
+    let count = |level| findings.iter().filter(|f| f.level == level).count();
+    let failed = count(Level::Failure) > 0;
+    println!(
+        "\n{} failed, {} warnings, {} ok",
+        count(Level::Failure),
+        count(Level::Warning),
+        count(Level::Ok)
+    );
+    failed
+}
@@ -0,0 +1,211 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, TimeZone};
+
+use crate::ics_text::{split_property, unescape_text};
+
+// Timestamps are stored in SQLite's `datetime()` format so range filters can
+// compare them as strings. Floating times are treated as UTC.
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexedEvent {
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub starts_at: Option<NaiveDateTime>,
+    pub ends_at: Option<NaiveDateTime>,
+    pub organizer: Option<String>,
+    pub attendees: Vec<String>,
+    pub status: Option<String>,
+    pub transparent: bool,
+}
+
+fn unfold(ics: &str) -> String {
+    ics.replace("\r\n ", "")
+        .replace("\r\n\t", "")
+        .replace("\n ", "")
+        .replace("\n\t", "")
+}
+
+// `mailto:Alice@Example.com` -> `alice@example.com`; non-mailto addresses are ignored.
+fn email(value: &str) -> Option<String> {
+    let value = value.trim();
+    let addr = value
+        .get(..7)
+        .filter(|p| p.eq_ignore_ascii_case("mailto:"))
+        .map(|_| &value[7..])?;
+    (!addr.is_empty()).then(|| addr.to_ascii_lowercase())
+}
+
+// Parses a DATE or DATE-TIME value into UTC.
+pub fn parse_datetime(value: &str, tzid: Option<&str>) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    Some(match value.len() {
+        8 => NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()?
+            .and_hms_opt(0, 0, 0)?,
+        16 if value.ends_with('Z') => {
+            NaiveDateTime::parse_from_str(&value[..15], "%Y%m%dT%H%M%S").ok()?
+        }
+        15 => {
+            let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+            match tzid.and_then(|t| t.parse::<chrono_tz::Tz>().ok()) {
+                Some(tz) => tz
+                    .from_local_datetime(&naive)
+                    .earliest()
+                    .map(|dt| dt.naive_utc())
+                    .unwrap_or(naive),
+                None => naive,
+            }
+        }
+        _ => return None,
+    })
+}
+
+// Parses an RFC 5545 DURATION such as `PT1H30M`, `P1D` or `P2W`.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut rest = value.strip_prefix('P')?;
+    let mut secs: i64 = 0;
+    let mut in_time = false;
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('T') {
+            in_time = true;
+            rest = r;
+            continue;
+        }
+        let end = rest.find(|c: char| !c.is_ascii_digit())?;
+        let n: i64 = rest[..end].parse().ok()?;
+        let unit = match (&rest[end..end + 1], in_time) {
+            ("W", false) => 7 * 86_400,
+            ("D", false) => 86_400,
+            ("H", true) => 3600,
+            ("M", true) => 60,
+            ("S", true) => 1,
+            _ => return None,
+        };
+        secs = secs.checked_add(n.checked_mul(unit)?)?;
+        rest = &rest[end + 1..];
+    }
+    Some(Duration::seconds(if negative { -secs } else { secs }))
+}
+
+// Extracts the searchable fields of every VEVENT in an ICS document.
+pub fn index(ics: &str) -> Vec<IndexedEvent> {
+    let mut events = Vec::new();
+    let mut current: Option<IndexedEvent> = None;
+    let mut all_day = false;
+    let mut duration = None;
+    // Components nested in the current event, such as VALARM, whose
+    // SUMMARY or DESCRIPTION are not the event's.
+    let mut nested = 0usize;
+    for line in unfold(ics).lines() {
+        let line = line.trim_end();
+        if line == "BEGIN:VEVENT" {
+            current = Some(IndexedEvent::default());
+            all_day = false;
+            duration = None;
+            nested = 0;
+            continue;
+        }
+        if current.is_some() && line.starts_with("BEGIN:") {
+            nested += 1;
+            continue;
+        }
+        if nested > 0 {
+            if line.starts_with("END:") {
+                nested -= 1;
+            }
+            continue;
+        }
+        if line == "END:VEVENT" {
+            if let Some(mut event) = current.take() {
+                // RFC 5545: without DTEND, DURATION applies; an all-day event lasts one day.
+                if event.ends_at.is_none()
+                    && let Some(start) = event.starts_at
+                {
+                    event.ends_at = match duration {
+                        Some(d) => Some(start + d),
+                        None if all_day => Some(start + Duration::days(1)),
+                        None => None,
+                    };
+                }
+                events.push(event);
+            }
+            continue;
+        }
+        let Some(event) = current.as_mut() else {
+            continue;
+        };
+        let Some((params, value)) = split_property(line) else {
+            continue;
+        };
+        let mut params = params.split(';');
+        let name = params.next().unwrap_or("").to_ascii_uppercase();
+        let tzid = params.find_map(|p| p.strip_prefix("TZID="));
+        match name.as_str() {
+            "UID" => event.uid = Some(value.trim().to_owned()),
+            "SUMMARY" => event.summary = Some(unescape_text(value).replace('\n', " ")),
+            "DESCRIPTION" => event.description = Some(unescape_text(value)),
+            "LOCATION" => event.location = Some(unescape_text(value).replace('\n', " ")),
+            "DTSTART" => {
+                all_day = value.trim().len() == 8;
+                event.starts_at = parse_datetime(value, tzid);
+            }
+            "DTEND" => event.ends_at = parse_datetime(value, tzid),
+            "DURATION" => duration = parse_duration(value),
+            "ORGANIZER" => event.organizer = email(value),
+            "ATTENDEE" => event.attendees.extend(email(value)),
+            "STATUS" => event.status = Some(value.trim().to_ascii_uppercase()),
+            "TRANSP" => event.transparent = value.trim().eq_ignore_ascii_case("TRANSPARENT"),
+            _ => {}
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(s, TIMESTAMP_FORMAT).ok()
+    }
+
+    #[test]
+    fn indexes_people_and_normalizes_times() {
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:1\r\nSUMMARY:Planning\\, Q3\r\n\
+            DTSTART;TZID=Europe/Berlin:20260310T100000\r\nDTEND:20260310T100000Z\r\n\
+            ORGANIZER;CN=Bob:mailto:Bob@Example.com\r\n\
+            ATTENDEE;CN=Alice;PARTSTAT=ACCEPTED:mailto:alice@\r\n example.com\r\n\
+            ATTENDEE:urn:uuid:room-1\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:2\r\nDTSTART:20260311T090000Z\r\nDURATION:PT1H30M\r\n\
+            TRANSP:TRANSPARENT\r\nBEGIN:VALARM\r\nACTION:DISPLAY\r\nDESCRIPTION:Reminder\r\n\
+            TRIGGER:-PT5M\r\nEND:VALARM\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:3\r\nDTSTART;VALUE=DATE:20260312\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let events = index(ics);
+        assert_eq!(
+            events[0],
+            IndexedEvent {
+                uid: Some("1".into()),
+                summary: Some("Planning, Q3".into()),
+                starts_at: at("2026-03-10 09:00:00"),
+                ends_at: at("2026-03-10 10:00:00"),
+                organizer: Some("bob@example.com".into()),
+                attendees: vec!["alice@example.com".into()],
+                ..Default::default()
+            }
+        );
+        assert_eq!(events[1].ends_at, at("2026-03-11 10:30:00"));
+        assert!(events[1].transparent);
+        assert_eq!(events[1].description, None);
+        assert_eq!(events[2].starts_at, at("2026-03-12 00:00:00"));
+        assert_eq!(events[2].ends_at, at("2026-03-13 00:00:00"));
+        assert_eq!(parse_duration("P1W"), Some(Duration::days(7)));
+        assert_eq!(parse_duration("1H"), None);
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{Result, bail, ensure};
+
+use crate::feed_metadata::fold;
+use crate::ics_component;
+use crate::ics_text::{escape_text, split_property, unescape_text};
+use crate::sync_origin::ORIGIN_PROPERTY;
+
+// Reformats events on their way into a destination calendar, e.g. a booking
+// system's titles for a shared room calendar. Each of a destination's
+// `summary_template`, `description_template` and `location_template` replaces
+// that property's text; placeholders in braces are filled from the event as
+// it came from the feed:
+//
+// - `{summary}`, `{x-ticket-id}`, ...: the event's own property of that name,
+//   unescaped, or nothing when it has none.
+// - `{calendar}`: the feed's X-WR-CALNAME.
+// - `{destination}`: the destination's name.
+//
+// `{{` and `}}` are literal braces. A template that comes out empty removes
+// the property; an empty template leaves it alone. `drop_properties` lists
+// properties removed before upload; templates can still use them.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EventTemplate<'a> {
+    pub summary: &'a str,
+    pub description: &'a str,
+    pub location: &'a str,
+    pub drop_properties: &'a str,
+    pub destination: &'a str,
+}
+
+// Properties an upload cannot do without.
+const REQUIRED: &[&str] = &["BEGIN", "END", "UID", "DTSTART", ORIGIN_PROPERTY];
+
+enum Token<'t> {
+    Text(&'t str),
+    Placeholder(&'t str),
+}
+
+fn tokens(template: &str) -> Result<Vec<Token<'_>>> {
+    let mut out = Vec::new();
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        if i > 0 {
+            out.push(Token::Text(&rest[..i]));
+        }
+        let tail = &rest[i..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push(Token::Text(&tail[..1]));
+            rest = &tail[2..];
+            continue;
+        }
+        ensure!(
+            tail.starts_with('{'),
+            "Unmatched '}}' in template '{}'; write '}}}}' for a literal brace",
+            template
+        );
+        let Some(end) = tail.find('}') else {
+            bail!(
+                "Unclosed placeholder in template '{}'; write '{{{{' for a literal brace",
+                template
+            );
+        };
+        let name = &tail[1..end];
+        ensure!(
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'),
+            "Invalid placeholder '{{{}}}' in template '{}'",
+            name,
+            template
+        );
+        out.push(Token::Placeholder(name));
+        rest = &tail[end + 1..];
+    }
+    if !rest.is_empty() {
+        out.push(Token::Text(rest));
+    }
+    Ok(out)
+}
+
+pub fn validate(template: &str) -> Result<()> {
+    tokens(template).map(|_| ())
+}
+
+// `" x-booking-id, Attach"` -> `"X-BOOKING-ID,ATTACH"`.
+pub fn normalize_drop_properties(list: &str) -> Result<String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let name = name.to_ascii_uppercase();
+        ensure!(
+            name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'),
+            "Invalid property name '{}'",
+            name
+        );
+        ensure!(
+            !REQUIRED.contains(&name.as_str()),
+            "Property {} cannot be dropped",
+            name
+        );
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    Ok(names.join(","))
+}
+
+fn render(template: &str, vevent: &str, calendar: &str, destination: &str) -> String {
+    // Validated when the destination was saved.
+    let Ok(tokens) = tokens(template) else {
+        return String::new();
+    };
+    let mut out = String::new();
+    for token in tokens {
+        match token {
+            Token::Text(text) => out.push_str(text),
+            Token::Placeholder(name) => match name.to_ascii_lowercase().as_str() {
+                "calendar" => out.push_str(calendar),
+                "destination" => out.push_str(destination),
+                _ => {
+                    if let Some(value) = ics_component::value(vevent, name) {
+                        out.push_str(&unescape_text(&value));
+                    }
+                }
+            },
+        }
+    }
+    out.trim().to_string()
+}
+
+// The property line for `text`, keeping the parameters of `original`.
+fn text_property(name: &str, original: Option<&str>, text: &str) -> String {
+    let params = original
+        .map(ics_component::unfold)
+        .and_then(|line| split_property(&line).map(|(head, _)| head[name.len()..].to_string()))
+        .unwrap_or_default();
+    fold(&format!("{}{}:{}", name, params, escape_text(text)))
+}
+
+fn apply_event(vevent: &str, template: &EventTemplate, calendar: &str) -> String {
+    let dropped: Vec<&str> = template.drop_properties.split(',').collect();
+    let fields: Vec<(&str, String)> = [
+        ("SUMMARY", template.summary),
+        ("DESCRIPTION", template.description),
+        ("LOCATION", template.location),
+    ]
+    .into_iter()
+    .filter(|(_, t)| !t.is_empty())
+    .map(|(name, t)| (name, render(t, vevent, calendar, template.destination)))
+    .collect();
+    let mut written: Vec<&str> = Vec::new();
+    ics_component::rewrite(vevent, |name, raw| {
+        if let Some((field, text)) = fields.iter().find(|(f, _)| *f == name) {
+            if written.contains(field) || text.is_empty() {
+                return Some(String::new());
+            }
+            written.push(field);
+            return Some(text_property(field, Some(raw), text));
+        }
+        if name == "END" {
+            // Templated properties the event did not have go in at its end.
+            let mut added: String = fields
+                .iter()
+                .filter(|(f, text)| !written.contains(f) && !text.is_empty())
+                .map(|(f, text)| text_property(f, None, text))
+                .collect();
+            added.push_str(raw);
+            return Some(added);
+        }
+        dropped.contains(&name).then(String::new)
+    })
+}
+
+fn calendar_name(ics: &str) -> String {
+    let header = &ics[..ics.find("BEGIN:VEVENT").unwrap_or(ics.len())];
+    ics_component::value(header, "X-WR-CALNAME")
+        .map(|name| unescape_text(&name))
+        .unwrap_or_default()
+}
+
+// Applies `template` to every VEVENT of `ics`.
+pub fn apply(ics: &str, template: &EventTemplate) -> String {
+    if [
+        template.summary,
+        template.description,
+        template.location,
+        template.drop_properties,
+    ]
+    .iter()
+    .all(|t| t.is_empty())
+    {
+        return ics.to_string();
+    }
+    let calendar = calendar_name(ics);
+    let mut out = String::with_capacity(ics.len());
+    let mut vevent = String::new();
+    let mut in_vevent = false;
+    for line in ics.split_inclusive('\n') {
+        if line.starts_with("BEGIN:VEVENT") {
+            in_vevent = true;
+        }
+        if !in_vevent {
+            out.push_str(line);
+            continue;
+        }
+        vevent.push_str(line);
+        if line.starts_with("END:VEVENT") {
+            in_vevent = false;
+            out.push_str(&apply_event(&vevent, template, &calendar));
+            vevent.clear();
+        }
+    }
+    out.push_str(&vevent);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ICS: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nX-WR-CALNAME:Room Booker\r\n\
+        BEGIN:VEVENT\r\nUID:b-1\r\nSUMMARY;LANGUAGE=en:Quarterly review\\, finance\r\n\
+        X-TICKET-ID:OPS-42\r\nATTENDEE:mailto:ceo@example.com\r\n\
+        BEGIN:VALARM\r\nACTION:DISPLAY\r\nDESCRIPTION:Soon\r\nEND:VALARM\r\nEND:VEVENT\r\n\
+        BEGIN:VEVENT\r\nUID:b-2\r\nSUMMARY:Cleaning\r\nLOCATION:Room 4\r\nEND:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    #[test]
+    fn rewrites_fields_and_drops_properties() {
+        let template = EventTemplate {
+            summary: "[{x-ticket-id}] {summary} ({calendar})",
+            description: "Booked via {destination}",
+            location: "{x-room}",
+            drop_properties: &normalize_drop_properties("attendee, x-ticket-id").unwrap(),
+            destination: "Shared room",
+        };
+        let out = apply(ICS, &template);
+        assert!(out.contains(
+            "SUMMARY;LANGUAGE=en:[OPS-42] Quarterly review\\, finance (Room Booker)\r\n"
+        ));
+        assert!(out.contains("SUMMARY:[] Cleaning (Room Booker)\r\n"));
+        assert!(out.contains(
+            "DESCRIPTION:Soon\r\nEND:VALARM\r\nDESCRIPTION:Booked via Shared room\r\nEND:VEVENT\r\n"
+        ));
+        assert!(!out.contains("LOCATION"));
+        assert!(!out.contains("ATTENDEE") && !out.contains("X-TICKET-ID"));
+        assert_eq!(apply(ICS, &EventTemplate::default()), ICS);
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        assert!(validate("{{literal}} {summary}").is_ok());
+        assert!(validate("{summary").is_err());
+        assert!(validate("summary}").is_err());
+        assert!(validate("{two words}").is_err());
+        assert!(normalize_drop_properties("uid").is_err());
+        assert_eq!(
+            normalize_drop_properties(" x-a, Attach,X-A").unwrap(),
+            "X-A,ATTACH"
+        );
+    }
+}
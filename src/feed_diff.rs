@@ -0,0 +1,196 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::api::reverse_sync::normalize_vevent;
+use crate::ics_text::{split_property, unescape_text};
+use crate::sync_origin::ORIGIN_PROPERTY;
+
+// Lines that identify or frame an event rather than describe it.
+const IGNORED: &[&str] = &["BEGIN", "END", "UID", ORIGIN_PROPERTY];
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct EventRef {
+    pub uid: String,
+    // Set for overridden occurrences of a recurring event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurrence_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FieldDiff {
+    pub property: String,
+    // The property's lines on each side; empty where it is missing.
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ChangedEvent {
+    #[serde(flatten)]
+    pub event: EventRef,
+    pub fields: Vec<FieldDiff>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct FeedDiff {
+    pub only_left: Vec<EventRef>,
+    pub only_right: Vec<EventRef>,
+    pub changed: Vec<ChangedEvent>,
+    // Events on both sides that match apart from DTSTAMP, SEQUENCE,
+    // LAST-MODIFIED and CREATED.
+    pub identical: usize,
+}
+
+fn property_name(line: &str) -> String {
+    let end = line.find([';', ':']).unwrap_or(line.len());
+    line[..end].to_ascii_uppercase()
+}
+
+fn value_of(lines: &[String], name: &str) -> Option<String> {
+    lines
+        .iter()
+        .find(|l| property_name(l) == name)
+        .and_then(|l| split_property(l))
+        .map(|(_, value)| value.trim().to_string())
+}
+
+// Each VEVENT by (UID, RECURRENCE-ID), as its normalized property lines.
+fn instances(
+    events: &HashMap<String, Vec<String>>,
+) -> BTreeMap<(String, Option<String>), Vec<String>> {
+    let mut out = BTreeMap::new();
+    for (uid, vevents) in events {
+        for vevent in vevents {
+            let lines: Vec<String> = normalize_vevent(vevent)
+                .into_iter()
+                .filter(|l| !IGNORED.contains(&property_name(l).as_str()))
+                .collect();
+            let recurrence_id = value_of(&lines, "RECURRENCE-ID");
+            out.insert((uid.clone(), recurrence_id), lines);
+        }
+    }
+    out
+}
+
+fn event_ref(uid: &str, recurrence_id: &Option<String>, lines: &[String]) -> EventRef {
+    EventRef {
+        uid: uid.to_string(),
+        recurrence_id: recurrence_id.clone(),
+        summary: value_of(lines, "SUMMARY").map(|s| unescape_text(&s)),
+    }
+}
+
+// Compares two sets of events keyed by UID, as read from feeds or a calendar.
+pub fn diff(left: &HashMap<String, Vec<String>>, right: &HashMap<String, Vec<String>>) -> FeedDiff {
+    let (left, right) = (instances(left), instances(right));
+    let mut result = FeedDiff::default();
+    for ((uid, recurrence_id), lines) in &left {
+        let Some(other) = right.get(&(uid.clone(), recurrence_id.clone())) else {
+            result.only_left.push(event_ref(uid, recurrence_id, lines));
+            continue;
+        };
+        if lines == other {
+            result.identical += 1;
+            continue;
+        }
+        let properties: BTreeSet<String> = lines
+            .iter()
+            .chain(other)
+            .map(|l| property_name(l))
+            .collect();
+        let fields = properties
+            .into_iter()
+            .filter_map(|property| {
+                let pick = |side: &[String]| -> Vec<String> {
+                    side.iter()
+                        .filter(|l| property_name(l) == property)
+                        .cloned()
+                        .collect()
+                };
+                let (l, r) = (pick(lines), pick(other));
+                (l != r).then_some(FieldDiff {
+                    property,
+                    left: l,
+                    right: r,
+                })
+            })
+            .collect();
+        result.changed.push(ChangedEvent {
+            event: event_ref(uid, recurrence_id, lines),
+            fields,
+        });
+    }
+    for ((uid, recurrence_id), lines) in &right {
+        if !left.contains_key(&(uid.clone(), recurrence_id.clone())) {
+            result.only_right.push(event_ref(uid, recurrence_id, lines));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(vevents: &[(&str, &str)]) -> HashMap<String, Vec<String>> {
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        for (uid, body) in vevents {
+            map.entry(uid.to_string()).or_default().push(format!(
+                "BEGIN:VEVENT\r\nUID:{}\r\n{}END:VEVENT\r\n",
+                uid, body
+            ));
+        }
+        map
+    }
+
+    #[test]
+    fn finds_missing_and_changed_events() {
+        let left = events(&[
+            ("a", "DTSTAMP:20260101T000000Z\r\nSUMMARY:Same\r\n"),
+            ("b", "SUMMARY:Old title\r\nLOCATION:Room 1\r\n"),
+            ("r", "SUMMARY:Weekly\r\n"),
+            ("r", "RECURRENCE-ID:20260310T090000Z\r\nSUMMARY:Moved\r\n"),
+            ("left-only", "SUMMARY:Gone\\, really\r\n"),
+        ]);
+        let right = events(&[
+            (
+                "a",
+                "DTSTAMP:20260202T000000Z\r\nSUMMARY:Same\r\nX-SYNC-ORIGIN:dest-1.caldav-ics-sync\r\n",
+            ),
+            ("b", "SUMMARY:New title\r\nLOCATION:Room 1\r\n"),
+            ("r", "SUMMARY:Weekly\r\n"),
+            ("right-only", "SUMMARY:New\r\n"),
+        ]);
+        let result = diff(&left, &right);
+        assert_eq!(result.identical, 2);
+        assert_eq!(
+            result.only_left,
+            [
+                EventRef {
+                    uid: "left-only".into(),
+                    recurrence_id: None,
+                    summary: Some("Gone, really".into()),
+                },
+                EventRef {
+                    uid: "r".into(),
+                    recurrence_id: Some("20260310T090000Z".into()),
+                    summary: Some("Moved".into()),
+                },
+            ]
+        );
+        assert_eq!(result.only_right[0].uid, "right-only");
+        assert_eq!(result.changed.len(), 1);
+        assert_eq!(
+            result.changed[0].fields,
+            [FieldDiff {
+                property: "SUMMARY".into(),
+                left: vec!["SUMMARY:Old title".into()],
+                right: vec!["SUMMARY:New title".into()],
+            }]
+        );
+    }
+}
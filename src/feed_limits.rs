@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, bail, ensure};
+use chrono::NaiveDateTime;
+
+use crate::api::archive::event_key;
+use crate::event_index;
+use crate::ics_component;
+
+// Caps on a source's published feed. Google Calendar stops refreshing
+// subscriptions past roughly 1 MB, and an upstream that suddenly returns
+// years of history or a runaway recurrence export shouldn't take a feed
+// there. When `max_events` or `max_bytes` is exceeded the source's policy
+// decides:
+//
+// - `truncate` drops the events that ended longest ago until the feed
+//   fits; recurring series count as current and go last.
+// - `fail` fails the sync and keeps the previous feed.
+// - `warn` publishes the feed anyway.
+// - `split` publishes the feed anyway and also serves it in parts within
+//   the limits, at `/ics/{path}/part-{n}.ics`, for clients that can't take
+//   it whole. Events are ordered by start, so each part covers a time range;
+//   a series keeps its overrides in the same part.
+//
+// Either way the sync result carries a warning.
+pub const POLICIES: &[&str] = &["truncate", "fail", "warn", "split"];
+
+pub fn validate_policy(policy: &str) -> Result<()> {
+    ensure!(
+        POLICIES.contains(&policy),
+        "Feed limit policy must be one of: {}",
+        POLICIES.join(", ")
+    );
+    Ok(())
+}
+
+enum Part<'a> {
+    Other(&'a str),
+    Event(String),
+}
+
+fn parts(ics: &str) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let mut vevent = String::new();
+    for line in ics.split_inclusive('\n') {
+        if vevent.is_empty() && !line.starts_with("BEGIN:VEVENT") {
+            parts.push(Part::Other(line));
+            continue;
+        }
+        vevent.push_str(line);
+        if line.starts_with("END:VEVENT") {
+            parts.push(Part::Event(std::mem::take(&mut vevent)));
+        }
+    }
+    if !vevent.is_empty() {
+        parts.push(Part::Event(vevent));
+    }
+    parts
+}
+
+// When a single event ends; None for recurring series and undated events.
+fn ends_at(vevent: &str) -> Option<NaiveDateTime> {
+    if ics_component::has_property(vevent, "RRULE") {
+        return None;
+    }
+    let event = event_index::index(vevent).into_iter().next()?;
+    event.ends_at.or(event.starts_at)
+}
+
+// Applies the limits (`None` or 0 for none) to `ics`, returning the feed to
+// publish and the warnings for the sync result.
+pub fn apply(
+    ics: &str,
+    max_events: Option<i64>,
+    max_bytes: Option<i64>,
+    policy: &str,
+) -> Result<(String, Vec<String>)> {
+    let max_events = max_events.filter(|m| *m > 0).map(|m| m as usize);
+    let max_bytes = max_bytes.filter(|m| *m > 0).map(|m| m as usize);
+    let parts = parts(ics);
+    let events = parts.iter().filter(|p| matches!(p, Part::Event(_))).count();
+    let mut problems = Vec::new();
+    if let Some(max) = max_events.filter(|max| events > *max) {
+        problems.push(format!("{} events exceed the limit of {}", events, max));
+    }
+    if let Some(max) = max_bytes.filter(|max| ics.len() > *max) {
+        problems.push(format!("{} bytes exceed the limit of {}", ics.len(), max));
+    }
+    if problems.is_empty() {
+        return Ok((ics.to_string(), Vec::new()));
+    }
+    match policy {
+        "fail" => bail!("Feed too large: {}", problems.join("; ")),
+        "warn" => {
+            return Ok((
+                ics.to_string(),
+                vec![format!("{}; published anyway", problems.join("; "))],
+            ));
+        }
+        "split" => {
+            let count = split(
+                ics,
+                max_events.map(|m| m as i64),
+                max_bytes.map(|m| m as i64),
+            )
+            .len();
+            return Ok((
+                ics.to_string(),
+                vec![format!(
+                    "{}; split into {} parts",
+                    problems.join("; "),
+                    count
+                )],
+            ));
+        }
+        _ => {}
+    }
+
+    let mut order: Vec<(usize, &str)> = parts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| match p {
+            Part::Event(vevent) => Some((i, vevent.as_str())),
+            Part::Other(_) => None,
+        })
+        .collect();
+    // Oldest first, then series and undated events; the key keeps the choice
+    // stable between syncs.
+    order.sort_by_cached_key(|(_, vevent)| {
+        let end = ends_at(vevent);
+        (end.is_none(), end, event_key(vevent))
+    });
+    let mut dropped = vec![false; parts.len()];
+    let (mut count, mut size) = (events, ics.len());
+    for (i, vevent) in order {
+        let fits =
+            max_events.is_none_or(|max| count <= max) && max_bytes.is_none_or(|max| size <= max);
+        if fits {
+            break;
+        }
+        dropped[i] = true;
+        count -= 1;
+        size -= vevent.len();
+    }
+    let out: String = parts
+        .iter()
+        .zip(&dropped)
+        .filter(|(_, dropped)| !**dropped)
+        .map(|(part, _)| match part {
+            Part::Other(line) => *line,
+            Part::Event(vevent) => vevent.as_str(),
+        })
+        .collect();
+    Ok((
+        out,
+        vec![format!(
+            "{}; dropped the {} oldest events",
+            problems.join("; "),
+            events - count
+        )],
+    ))
+}
+
+// `team/work/part-2.ics` -> `("team/work", 2)`.
+pub fn part_path(path: &str) -> Option<(&str, usize)> {
+    let (feed, part) = path.rsplit_once("/part-")?;
+    let n = part.strip_suffix(".ics")?.parse().ok()?;
+    (n > 0 && !feed.is_empty()).then_some((feed, n))
+}
+
+// Splits `ics` into calendars within the limits (`None` or 0 for none), each
+// with the feed's own properties and time zones. Components sharing a UID, a
+// series and its overrides, stay in one part, ordered by their earliest
+// start. A group too large for any part gets one to itself. Always returns at
+// least one part.
+pub fn split(ics: &str, max_events: Option<i64>, max_bytes: Option<i64>) -> Vec<String> {
+    let max_events = max_events.filter(|m| *m > 0).map(|m| m as usize);
+    let max_bytes = max_bytes.filter(|m| *m > 0).map(|m| m as usize);
+    const END: &str = "END:VCALENDAR\r\n";
+    let mut preamble = String::new();
+    let mut groups: Vec<Vec<&str>> = Vec::new();
+    let mut by_uid: HashMap<String, usize> = HashMap::new();
+    for part in ics_component::parts(ics) {
+        match part {
+            ics_component::Part::Property { name, .. } if name == "END" => {}
+            ics_component::Part::Property { raw, .. } => preamble.push_str(raw),
+            ics_component::Part::Component { name, raw } if name == "VTIMEZONE" => {
+                preamble.push_str(raw)
+            }
+            ics_component::Part::Component { raw, .. } => match ics_component::value(raw, "UID") {
+                Some(uid) if by_uid.contains_key(&uid) => groups[by_uid[&uid]].push(raw),
+                uid => {
+                    if let Some(uid) = uid {
+                        by_uid.insert(uid, groups.len());
+                    }
+                    groups.push(vec![raw]);
+                }
+            },
+        }
+    }
+    groups.sort_by_cached_key(|group| {
+        let start = group
+            .iter()
+            .filter_map(|c| event_index::index(c).into_iter().next()?.starts_at)
+            .min();
+        (start.is_none(), start, event_key(group[0]))
+    });
+
+    let mut out = Vec::new();
+    let mut current = preamble.clone();
+    let mut count = 0;
+    for group in groups {
+        let size: usize = group.iter().map(|c| c.len()).sum();
+        let full = max_events.is_some_and(|max| count + group.len() > max)
+            || max_bytes.is_some_and(|max| current.len() + size + END.len() > max);
+        if full && count > 0 {
+            current.push_str(END);
+            out.push(std::mem::replace(&mut current, preamble.clone()));
+            count = 0;
+        }
+        group.iter().for_each(|c| current.push_str(c));
+        count += group.len();
+    }
+    current.push_str(END);
+    out.push(current);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(uid: &str, start: &str, extra: &str) -> String {
+        format!(
+            "BEGIN:VEVENT\r\nUID:{}\r\nDTSTART:{}\r\n{}END:VEVENT\r\n",
+            uid, start, extra
+        )
+    }
+
+    #[test]
+    fn applies_each_policy() {
+        let ics = format!(
+            "BEGIN:VCALENDAR\r\nBEGIN:VTIMEZONE\r\nTZID:X\r\nEND:VTIMEZONE\r\n{}{}{}{}END:VCALENDAR\r\n",
+            event("new", "20260310T100000Z", ""),
+            event("weekly", "20200101T100000Z", "RRULE:FREQ=WEEKLY\r\n"),
+            event("old", "20240101T100000Z", ""),
+            event("older", "20230101T100000Z", ""),
+        );
+
+        let (same, warnings) = apply(&ics, Some(4), Some(0), "fail").unwrap();
+        assert_eq!((same.as_str(), warnings.len()), (ics.as_str(), 0));
+
+        let err = apply(&ics, Some(2), None, "fail").unwrap_err();
+        assert!(err.to_string().contains("4 events exceed the limit of 2"));
+
+        let (same, warnings) = apply(&ics, Some(2), None, "warn").unwrap();
+        assert_eq!(same, ics);
+        assert!(warnings[0].ends_with("published anyway"));
+
+        let (truncated, warnings) = apply(&ics, Some(2), None, "truncate").unwrap();
+        assert!(!truncated.contains("UID:old"));
+        assert!(truncated.contains("UID:new") && truncated.contains("UID:weekly"));
+        assert!(truncated.contains("BEGIN:VTIMEZONE"));
+        assert!(warnings[0].contains("dropped the 2 oldest events"));
+
+        let max = ics.len() - 10;
+        let (truncated, _) = apply(&ics, None, Some(max as i64), "truncate").unwrap();
+        assert!(!truncated.contains("UID:older") && truncated.contains("UID:old\r\n"));
+        assert!(truncated.len() <= max);
+
+        let (same, warnings) = apply(&ics, Some(3), None, "split").unwrap();
+        assert_eq!(same, ics);
+        assert!(warnings[0].ends_with("split into 2 parts"));
+    }
+
+    #[test]
+    fn splits_by_time_range_within_limits() {
+        let ics = format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTIMEZONE\r\nTZID:X\r\nEND:VTIMEZONE\r\n{}{}{}END:VCALENDAR\r\n",
+            event("c", "20260310T100000Z", ""),
+            event("a", "20240101T100000Z", ""),
+            event("b", "20250101T100000Z", ""),
+        );
+        let parts = split(&ics, Some(2), None);
+        assert_eq!(parts.len(), 2);
+        for part in &parts {
+            assert!(part.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTIMEZONE"));
+            assert!(part.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        }
+        assert!(parts[0].contains("UID:a\r\n") && parts[0].contains("UID:b\r\n"));
+        assert!(parts[1].contains("UID:c\r\n"));
+
+        let max = parts[1].len() + 1;
+        let parts = split(&ics, None, Some(max as i64));
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|p| p.len() <= max));
+        assert_eq!(split(&ics, None, None).len(), 1);
+
+        let ics = format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}{}{}{}END:VCALENDAR\r\n",
+            event("weekly", "20240101T100000Z", "RRULE:FREQ=WEEKLY\r\n"),
+            event("b", "20250101T100000Z", ""),
+            event(
+                "weekly",
+                "20260310T120000Z",
+                "RECURRENCE-ID:20260310T100000Z\r\n"
+            ),
+            "BEGIN:VTODO\r\nUID:todo\r\nEND:VTODO\r\n",
+        );
+        let parts = split(&ics, Some(2), None);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].matches("UID:weekly\r\n").count(), 2);
+        assert!(parts[1].contains("UID:b\r\n") && parts[1].contains("UID:todo\r\n"));
+        assert!(!parts[0].contains("VTODO"));
+
+        assert_eq!(part_path("team/work/part-2.ics"), Some(("team/work", 2)));
+        assert_eq!(part_path("work/part-0.ics"), None);
+        assert_eq!(part_path("work/part-x.ics"), None);
+    }
+}
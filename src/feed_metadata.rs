@@ -0,0 +1,411 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, ensure};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::ics_text::escape_text;
+
+pub type FeedMetadata = BTreeMap<String, String>;
+
+// Standard VCALENDAR header properties, as opposed to the free-form X-FEED-*
+// metadata. Unset fields leave the feed as synced.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct FeedProperties {
+    // X-WR-CALNAME
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // X-WR-CALDESC; takes precedence over the `description` metadata entry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    // X-WR-TIMEZONE, an IANA zone name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    // A CSS color name (COLOR) or #rrggbb (X-APPLE-CALENDAR-COLOR).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    // Replaces the PRODID of the synced feed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prodid: Option<String>,
+    // REFRESH-INTERVAL and X-PUBLISHED-TTL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published_ttl_minutes: Option<i64>,
+    // METHOD: `PUBLISH`, or `none` to leave it out for clients that reject
+    // feeds carrying one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+}
+
+// Four weeks; clients treat anything longer as "never refresh".
+pub const MAX_TTL_MINUTES: i64 = 4 * 7 * 24 * 60;
+const MAX_PRODID_LEN: usize = 255;
+pub const METHODS: &[&str] = &["PUBLISH", "none"];
+
+const MAX_ENTRIES: usize = 32;
+const MAX_KEY_LEN: usize = 64;
+const MAX_VALUE_LEN: usize = 2048;
+const PROPERTY_PREFIX: &str = "X-FEED-";
+// Calendar apps show this one as the calendar's description.
+const DESCRIPTION_PROPERTY: &str = "X-WR-CALDESC";
+
+pub fn validate(metadata: &FeedMetadata) -> Result<()> {
+    ensure!(
+        metadata.len() <= MAX_ENTRIES,
+        "At most {} metadata entries are allowed",
+        MAX_ENTRIES
+    );
+    for (key, value) in metadata {
+        ensure!(
+            !key.is_empty()
+                && key.len() <= MAX_KEY_LEN
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'),
+            "Metadata key '{}' must be 1-{} lowercase letters, digits or dashes",
+            key,
+            MAX_KEY_LEN
+        );
+        ensure!(
+            value.len() <= MAX_VALUE_LEN,
+            "Metadata value for '{}' exceeds {} bytes",
+            key,
+            MAX_VALUE_LEN
+        );
+    }
+    Ok(())
+}
+
+pub fn validate_properties(properties: &FeedProperties) -> Result<()> {
+    for (field, value) in [
+        ("name", &properties.name),
+        ("description", &properties.description),
+    ] {
+        if let Some(value) = value {
+            ensure!(
+                value.len() <= MAX_VALUE_LEN,
+                "Feed {} exceeds {} bytes",
+                field,
+                MAX_VALUE_LEN
+            );
+        }
+    }
+    if let Some(ref tz) = properties.timezone {
+        ensure!(
+            tz.parse::<chrono_tz::Tz>().is_ok(),
+            "Unknown timezone '{}'; use an IANA name like Europe/Berlin",
+            tz
+        );
+    }
+    if let Some(ref color) = properties.color {
+        ensure!(
+            is_hex_color(color)
+                || (!color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic())),
+            "Color must be a CSS color name or #rrggbb"
+        );
+    }
+    if let Some(ref prodid) = properties.prodid {
+        ensure!(
+            !prodid.trim().is_empty()
+                && prodid.len() <= MAX_PRODID_LEN
+                && !prodid.chars().any(char::is_control),
+            "PRODID must be 1-{} characters without line breaks",
+            MAX_PRODID_LEN
+        );
+    }
+    if let Some(ttl) = properties.published_ttl_minutes {
+        ensure!(
+            (1..=MAX_TTL_MINUTES).contains(&ttl),
+            "Published TTL must be between 1 and {} minutes",
+            MAX_TTL_MINUTES
+        );
+    }
+    if let Some(ref method) = properties.method {
+        ensure!(
+            METHODS.contains(&method.as_str()),
+            "Method must be one of: {}",
+            METHODS.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn is_hex_color(color: &str) -> bool {
+    color
+        .strip_prefix('#')
+        .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+// 90 -> PT1H30M, 1440 -> P1D.
+fn ttl_duration(minutes: i64) -> String {
+    let (days, hours, mins) = (minutes / 1440, minutes % 1440 / 60, minutes % 60);
+    let mut out = String::from("P");
+    if days > 0 {
+        out.push_str(&format!("{}D", days));
+    }
+    if hours > 0 || mins > 0 {
+        out.push('T');
+    }
+    if hours > 0 {
+        out.push_str(&format!("{}H", hours));
+    }
+    if mins > 0 {
+        out.push_str(&format!("{}M", mins));
+    }
+    out
+}
+
+// RFC 5545 folding: lines longer than 75 octets continue on a line starting with a space.
+pub(crate) fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+fn property_name(line: &str) -> &str {
+    &line[..line.find([':', ';']).unwrap_or(line.len())]
+}
+
+// The header properties `header` sets. These replace what the synced feed
+// had; unset ones are left as they came from upstream.
+fn replaced_properties(header: &FeedProperties) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    if header.prodid.is_some() {
+        names.push("PRODID");
+    }
+    if header.name.is_some() {
+        names.push("X-WR-CALNAME");
+    }
+    if header.timezone.is_some() {
+        names.push("X-WR-TIMEZONE");
+    }
+    if header.color.is_some() {
+        names.extend(["COLOR", "X-APPLE-CALENDAR-COLOR"]);
+    }
+    if header.published_ttl_minutes.is_some() {
+        names.extend(["REFRESH-INTERVAL", "X-PUBLISHED-TTL"]);
+    }
+    if header.method.is_some() {
+        names.push("METHOD");
+    }
+    names
+}
+
+// Copies `ics`, dropping the VCALENDAR header lines `remove` matches (with
+// their continuation lines) and inserting `insert` after BEGIN:VCALENDAR.
+fn rewrite(ics: &str, insert: &str, remove: impl Fn(&str) -> bool) -> String {
+    let mut out = String::with_capacity(ics.len() + insert.len());
+    let mut in_header = false;
+    let mut skipping = false;
+    for line in ics.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        if skipping && line.starts_with([' ', '\t']) {
+            continue;
+        }
+        skipping = false;
+        if in_header && content.starts_with("BEGIN:") {
+            in_header = false;
+        }
+        if in_header && remove(property_name(content)) {
+            skipping = true;
+            continue;
+        }
+        out.push_str(line);
+        if content == "BEGIN:VCALENDAR" {
+            in_header = true;
+            out.push_str(insert);
+        }
+    }
+    out
+}
+
+// Removes the header properties `previous` set, so clearing one restores
+// nothing stale. METHOD goes back to the METHOD:PUBLISH every synced feed has.
+pub fn strip(ics: &str, previous: &FeedProperties) -> String {
+    let replaced = replaced_properties(previous);
+    let restored = match previous.method.as_deref() {
+        Some("none") => "METHOD:PUBLISH\r\n",
+        _ => "",
+    };
+    rewrite(ics, restored, |name| {
+        name != "METHOD" && replaced.contains(&name)
+    })
+}
+
+// Replaces the metadata and header properties in the VCALENDAR header of `ics`.
+pub fn embed(ics: &str, metadata: &FeedMetadata, header: &FeedProperties) -> String {
+    let mut properties = String::new();
+    let mut property = |name: &str, value: &str| {
+        properties.push_str(&fold(&format!("{}:{}", name, value)));
+    };
+    if let Some(ref prodid) = header.prodid {
+        property("PRODID", prodid);
+    }
+    if let Some(ref name) = header.name {
+        property("X-WR-CALNAME", &escape_text(name));
+    }
+    if let Some(description) = header
+        .description
+        .as_ref()
+        .or_else(|| metadata.get("description"))
+    {
+        property(DESCRIPTION_PROPERTY, &escape_text(description));
+    }
+    if let Some(ref tz) = header.timezone {
+        property("X-WR-TIMEZONE", tz);
+    }
+    match header.color.as_deref() {
+        Some(color) if is_hex_color(color) => property("X-APPLE-CALENDAR-COLOR", color),
+        Some(color) => property("COLOR", color),
+        None => {}
+    }
+    if let Some(ttl) = header.published_ttl_minutes {
+        let duration = ttl_duration(ttl);
+        property("REFRESH-INTERVAL;VALUE=DURATION", &duration);
+        property("X-PUBLISHED-TTL", &duration);
+    }
+    if header.method.as_deref() == Some("PUBLISH") {
+        property("METHOD", "PUBLISH");
+    }
+    for (key, value) in metadata {
+        let value = escape_text(value);
+        properties.push_str(&fold(&format!(
+            "{}{}:{}",
+            PROPERTY_PREFIX,
+            key.to_ascii_uppercase(),
+            value
+        )));
+    }
+
+    let replaced = replaced_properties(header);
+    rewrite(ics, &properties, |name| {
+        name.starts_with(PROPERTY_PREFIX)
+            || name == DESCRIPTION_PROPERTY
+            || replaced.contains(&name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embeds_and_replaces_header_properties() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:1\r\nX-FEED-OWNER:kept\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let metadata = FeedMetadata::from([
+            ("owner".into(), "Registrar, Main Campus".into()),
+            ("description".into(), "x".repeat(80)),
+        ]);
+
+        let embedded = embed(ics, &metadata, &FeedProperties::default());
+        assert!(embedded.starts_with("BEGIN:VCALENDAR\r\nX-WR-CALDESC:xxx"));
+        assert!(embedded.contains("X-FEED-OWNER:Registrar\\, Main Campus\r\n"));
+        assert!(embedded.contains("\r\n xxxxx"));
+        assert!(embedded.contains("UID:1\r\nX-FEED-OWNER:kept\r\n"));
+
+        let updated = embed(
+            &embedded,
+            &FeedMetadata::from([("contact".into(), "a@b.c".into())]),
+            &FeedProperties::default(),
+        );
+        assert_eq!(
+            updated,
+            ics.replacen(
+                "BEGIN:VCALENDAR\r\n",
+                "BEGIN:VCALENDAR\r\nX-FEED-CONTACT:a@b.c\r\n",
+                1
+            )
+        );
+
+        assert!(validate(&FeedMetadata::from([("Bad Key".into(), "v".into())])).is_err());
+    }
+
+    #[test]
+    fn embeds_calendar_properties() {
+        let ics = "BEGIN:VCALENDAR\r\nPRODID:-//Upstream//EN\r\nX-WR-CALNAME:Old\r\nBEGIN:VEVENT\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let properties = FeedProperties {
+            name: Some("Team, Berlin".into()),
+            timezone: Some("Europe/Berlin".into()),
+            color: Some("#1a2b3c".into()),
+            prodid: Some("-//Example//Calendar//EN".into()),
+            published_ttl_minutes: Some(90),
+            ..Default::default()
+        };
+        validate_properties(&properties).unwrap();
+
+        let embedded = embed(ics, &FeedMetadata::new(), &properties);
+        assert_eq!(
+            embedded,
+            "BEGIN:VCALENDAR\r\nPRODID:-//Example//Calendar//EN\r\nX-WR-CALNAME:Team\\, Berlin\r\n\
+             X-WR-TIMEZONE:Europe/Berlin\r\nX-APPLE-CALENDAR-COLOR:#1a2b3c\r\n\
+             REFRESH-INTERVAL;VALUE=DURATION:PT1H30M\r\nX-PUBLISHED-TTL:PT1H30M\r\n\
+             BEGIN:VEVENT\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n"
+        );
+        // Unset properties keep the feed's own values.
+        let kept = embed(ics, &FeedMetadata::new(), &FeedProperties::default());
+        assert_eq!(kept, ics);
+        let cleared = strip(&embedded, &properties);
+        assert_eq!(
+            cleared,
+            "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n"
+        );
+        assert_eq!(ttl_duration(2 * 1440), "P2D");
+
+        for bad in [
+            FeedProperties {
+                timezone: Some("Mars/Olympus".into()),
+                ..Default::default()
+            },
+            FeedProperties {
+                color: Some("#12345".into()),
+                ..Default::default()
+            },
+            FeedProperties {
+                published_ttl_minutes: Some(0),
+                ..Default::default()
+            },
+        ] {
+            assert!(validate_properties(&bad).is_err());
+        }
+    }
+
+    #[test]
+    fn method_can_be_left_out_and_restored() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nMETHOD:PUBLISH\r\nEND:VCALENDAR\r\n";
+        let omitted = FeedProperties {
+            method: Some("none".into()),
+            ..Default::default()
+        };
+        let embedded = embed(ics, &FeedMetadata::new(), &omitted);
+        assert_eq!(
+            embedded,
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nEND:VCALENDAR\r\n"
+        );
+        assert_eq!(
+            strip(&embedded, &omitted),
+            "BEGIN:VCALENDAR\r\nMETHOD:PUBLISH\r\nVERSION:2.0\r\nEND:VCALENDAR\r\n"
+        );
+        let publish = FeedProperties {
+            method: Some("PUBLISH".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            strip(&embed(ics, &FeedMetadata::new(), &publish), &publish),
+            "BEGIN:VCALENDAR\r\nMETHOD:PUBLISH\r\nVERSION:2.0\r\nEND:VCALENDAR\r\n"
+        );
+        let request = FeedProperties {
+            method: Some("REQUEST".into()),
+            ..Default::default()
+        };
+        assert!(validate_properties(&request).is_err());
+    }
+}
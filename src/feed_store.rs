@@ -0,0 +1,90 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+
+// Feeds larger than the limit are kept out of SQLite, in files named by their
+// content hash, so serving them doesn't load the whole blob into memory. Until
+// `init` is called (e.g. in tests with an in-memory database) every feed is
+// stored inline.
+pub const DEFAULT_INLINE_LIMIT: usize = 1024 * 1024;
+
+struct Store {
+    dir: PathBuf,
+    inline_limit: usize,
+}
+
+static STORE: OnceLock<Store> = OnceLock::new();
+
+// Later calls are ignored; the first store stays in place.
+pub fn init(dir: impl Into<PathBuf>, inline_limit: usize) -> Result<()> {
+    let dir = dir.into();
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Cannot create feed directory '{}'", dir.display()))?;
+    let _ = STORE.set(Store { dir, inline_limit });
+    Ok(())
+}
+
+pub fn stores_in_file(len: usize) -> bool {
+    STORE.get().is_some_and(|s| len > s.inline_limit)
+}
+
+pub fn path(hash: &str) -> Option<PathBuf> {
+    // Hashes come from the database, but never let one leave the directory.
+    if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    STORE.get().map(|s| s.dir.join(format!("{}.ics", hash)))
+}
+
+// Writes through a temporary file, so readers never see a partial feed.
+pub fn write(hash: &str, content: &str) -> Result<()> {
+    let path = path(hash).context("Feed file store is not initialized")?;
+    if path.exists() {
+        return Ok(());
+    }
+    let tmp = path.with_extension(format!("tmp-{}", std::process::id()));
+    std::fs::write(&tmp, content)
+        .and_then(|_| std::fs::rename(&tmp, &path))
+        .with_context(|| format!("Failed to write feed file '{}'", path.display()))
+}
+
+pub fn read(hash: &str) -> Result<String> {
+    let path = path(hash).context("Feed file store is not initialized")?;
+    std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read feed file '{}'", path.display()))
+}
+
+pub fn remove(hash: &str) {
+    if let Some(path) = path(hash)
+        && let Err(e) = std::fs::remove_file(&path)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        tracing::warn!("Failed to remove feed file '{}': {}", path.display(), e);
+    }
+}
+
+// Deletes files no source refers to any more, e.g. after a user and their
+// sources were deleted. Returns how many were removed.
+pub fn prune(referenced: &HashSet<String>) -> Result<usize> {
+    let Some(store) = STORE.get() else {
+        return Ok(0);
+    };
+    let mut removed = 0;
+    for entry in std::fs::read_dir(&store.dir)? {
+        let path = entry?.path();
+        let Some(hash) = file_hash(&path) else {
+            continue;
+        };
+        if !referenced.contains(hash) {
+            remove(hash);
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+fn file_hash(path: &Path) -> Option<&str> {
+    path.file_name()?.to_str()?.strip_suffix(".ics")
+}
@@ -0,0 +1,104 @@
+use anyhow::{Result, bail};
+use reqwest::Url;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FeedUrls {
+    pub https: String,
+    pub webcal: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_https: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_webcal: Option<String>,
+}
+
+// `https://example.com/calendars/` -> `https://example.com/calendars`
+pub fn normalize_base_url(raw: &str) -> Result<String> {
+    let Ok(url) = Url::parse(raw.trim()) else {
+        bail!("PUBLIC_BASE_URL must be an absolute URL");
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!("PUBLIC_BASE_URL must use http or https");
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        bail!("PUBLIC_BASE_URL cannot have a query or fragment");
+    }
+    Ok(url.as_str().trim_end_matches('/').to_owned())
+}
+
+fn feed_url(base: &str, prefix: &[&str], path: &str) -> Option<String> {
+    let mut url = Url::parse(base).ok()?;
+    url.path_segments_mut()
+        .ok()?
+        .pop_if_empty()
+        .extend(prefix)
+        .extend(path.split('/'));
+    Some(url.into())
+}
+
+// webcal:// tells calendar apps to subscribe instead of downloading once.
+fn webcal(url: &str) -> String {
+    match url.split_once("://") {
+        Some((_, rest)) => format!("webcal://{}", rest),
+        None => url.to_owned(),
+    }
+}
+
+pub fn for_feed(base: &str, ics_path: &str, public_ics_path: Option<&str>) -> Option<FeedUrls> {
+    let https = feed_url(base, &["ics"], ics_path)?;
+    let public_https = public_ics_path.and_then(|p| feed_url(base, &["ics", "public"], p));
+    Some(FeedUrls {
+        webcal: webcal(&https),
+        public_webcal: public_https.as_deref().map(webcal),
+        https,
+        public_https,
+    })
+}
+
+// The URLs, or with an empty `base` the paths, of a split feed's parts.
+pub fn parts(base: &str, ics_path: &str, count: i64) -> Vec<String> {
+    (1..=count)
+        .map(|n| {
+            let path = format!("{}/part-{}.ics", ics_path, n);
+            match base {
+                "" => format!("/ics/{}", path),
+                base => feed_url(base, &["ics"], &path).unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_urls_under_the_base_path() {
+        let base = normalize_base_url("https://example.com/calendars/").unwrap();
+        assert_eq!(base, "https://example.com/calendars");
+        let urls = for_feed(&base, "team/work cal.ics", Some("work")).unwrap();
+        assert_eq!(
+            urls.https,
+            "https://example.com/calendars/ics/team/work%20cal.ics"
+        );
+        assert_eq!(
+            urls.webcal,
+            "webcal://example.com/calendars/ics/team/work%20cal.ics"
+        );
+        assert_eq!(
+            urls.public_webcal.as_deref(),
+            Some("webcal://example.com/calendars/ics/public/work")
+        );
+        assert!(normalize_base_url("example.com").is_err());
+        assert!(normalize_base_url("ftp://example.com").is_err());
+        assert_eq!(
+            parts(&base, "work", 2),
+            [
+                "https://example.com/calendars/ics/work/part-1.ics",
+                "https://example.com/calendars/ics/work/part-2.ics"
+            ]
+        );
+        assert_eq!(parts("", "work", 1), ["/ics/work/part-1.ics"]);
+    }
+}
@@ -0,0 +1,571 @@
+use std::collections::BTreeSet;
+
+use anyhow::{Context, Result, bail, ensure};
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use serde::Deserialize;
+
+use crate::db;
+use crate::ics_text::escape_text;
+use crate::sync_progress;
+
+// Holiday sources cover the previous year and the next two.
+const YEARS_BEFORE: i32 = 1;
+const YEARS_AFTER: i32 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Holiday {
+    pub date: NaiveDate,
+    pub name: String,
+}
+
+enum Rule {
+    Fixed(u32, u32),
+    // Days after Easter Sunday.
+    Easter(i64),
+    // The nth weekday of a month; negative counts from the end.
+    Nth(u32, Weekday, i32),
+    // The last given weekday before a date (Buß- und Bettag).
+    Before(u32, u32, Weekday),
+    // Moved to the Saturday before when it falls on a Sunday (King's Day).
+    NotSunday(u32, u32),
+}
+
+// How holidays on a weekend are made up for.
+#[derive(Clone, Copy)]
+enum Observed {
+    Never,
+    // Saturday to the Friday before, Sunday to the Monday after.
+    NearestWeekday,
+    // To the next weekday that isn't a holiday already.
+    NextFreeWeekday,
+}
+
+struct Definition {
+    name: &'static str,
+    rule: Rule,
+    // Subdivisions that keep it; empty for the whole country.
+    regions: &'static [&'static str],
+}
+
+const fn nationwide(name: &'static str, rule: Rule) -> Definition {
+    Definition {
+        name,
+        rule,
+        regions: &[],
+    }
+}
+
+struct Country {
+    code: &'static str,
+    observed: Observed,
+    subdivisions: &'static [&'static str],
+    holidays: &'static [Definition],
+}
+
+const AT: &[Definition] = &[
+    nationwide("New Year's Day", Rule::Fixed(1, 1)),
+    nationwide("Epiphany", Rule::Fixed(1, 6)),
+    nationwide("Easter Monday", Rule::Easter(1)),
+    nationwide("Labour Day", Rule::Fixed(5, 1)),
+    nationwide("Ascension Day", Rule::Easter(39)),
+    nationwide("Whit Monday", Rule::Easter(50)),
+    nationwide("Corpus Christi", Rule::Easter(60)),
+    nationwide("Assumption Day", Rule::Fixed(8, 15)),
+    nationwide("National Day", Rule::Fixed(10, 26)),
+    nationwide("All Saints' Day", Rule::Fixed(11, 1)),
+    nationwide("Immaculate Conception", Rule::Fixed(12, 8)),
+    nationwide("Christmas Day", Rule::Fixed(12, 25)),
+    nationwide("St. Stephen's Day", Rule::Fixed(12, 26)),
+];
+
+const DE: &[Definition] = &[
+    nationwide("New Year's Day", Rule::Fixed(1, 1)),
+    Definition {
+        name: "Epiphany",
+        rule: Rule::Fixed(1, 6),
+        regions: &["BW", "BY", "ST"],
+    },
+    Definition {
+        name: "International Women's Day",
+        rule: Rule::Fixed(3, 8),
+        regions: &["BE", "MV"],
+    },
+    nationwide("Good Friday", Rule::Easter(-2)),
+    nationwide("Easter Monday", Rule::Easter(1)),
+    nationwide("Labour Day", Rule::Fixed(5, 1)),
+    nationwide("Ascension Day", Rule::Easter(39)),
+    nationwide("Whit Monday", Rule::Easter(50)),
+    Definition {
+        name: "Corpus Christi",
+        rule: Rule::Easter(60),
+        regions: &["BW", "BY", "HE", "NW", "RP", "SL"],
+    },
+    Definition {
+        name: "Assumption Day",
+        rule: Rule::Fixed(8, 15),
+        regions: &["SL"],
+    },
+    Definition {
+        name: "World Children's Day",
+        rule: Rule::Fixed(9, 20),
+        regions: &["TH"],
+    },
+    nationwide("German Unity Day", Rule::Fixed(10, 3)),
+    Definition {
+        name: "Reformation Day",
+        rule: Rule::Fixed(10, 31),
+        regions: &["BB", "HB", "HH", "MV", "NI", "SH", "SN", "ST", "TH"],
+    },
+    Definition {
+        name: "All Saints' Day",
+        rule: Rule::Fixed(11, 1),
+        regions: &["BW", "BY", "NW", "RP", "SL"],
+    },
+    Definition {
+        name: "Day of Repentance and Prayer",
+        rule: Rule::Before(11, 23, Weekday::Wed),
+        regions: &["SN"],
+    },
+    nationwide("Christmas Day", Rule::Fixed(12, 25)),
+    nationwide("St. Stephen's Day", Rule::Fixed(12, 26)),
+];
+
+const FR: &[Definition] = &[
+    nationwide("New Year's Day", Rule::Fixed(1, 1)),
+    nationwide("Easter Monday", Rule::Easter(1)),
+    nationwide("Labour Day", Rule::Fixed(5, 1)),
+    nationwide("Victory in Europe Day", Rule::Fixed(5, 8)),
+    nationwide("Ascension Day", Rule::Easter(39)),
+    nationwide("Whit Monday", Rule::Easter(50)),
+    nationwide("Bastille Day", Rule::Fixed(7, 14)),
+    nationwide("Assumption Day", Rule::Fixed(8, 15)),
+    nationwide("All Saints' Day", Rule::Fixed(11, 1)),
+    nationwide("Armistice Day", Rule::Fixed(11, 11)),
+    nationwide("Christmas Day", Rule::Fixed(12, 25)),
+];
+
+// England and Wales.
+const GB: &[Definition] = &[
+    nationwide("New Year's Day", Rule::Fixed(1, 1)),
+    nationwide("Good Friday", Rule::Easter(-2)),
+    nationwide("Easter Monday", Rule::Easter(1)),
+    nationwide("Early May Bank Holiday", Rule::Nth(5, Weekday::Mon, 1)),
+    nationwide("Spring Bank Holiday", Rule::Nth(5, Weekday::Mon, -1)),
+    nationwide("Summer Bank Holiday", Rule::Nth(8, Weekday::Mon, -1)),
+    nationwide("Christmas Day", Rule::Fixed(12, 25)),
+    nationwide("Boxing Day", Rule::Fixed(12, 26)),
+];
+
+const NL: &[Definition] = &[
+    nationwide("New Year's Day", Rule::Fixed(1, 1)),
+    nationwide("Good Friday", Rule::Easter(-2)),
+    nationwide("Easter Sunday", Rule::Easter(0)),
+    nationwide("Easter Monday", Rule::Easter(1)),
+    nationwide("King's Day", Rule::NotSunday(4, 27)),
+    nationwide("Liberation Day", Rule::Fixed(5, 5)),
+    nationwide("Ascension Day", Rule::Easter(39)),
+    nationwide("Whit Sunday", Rule::Easter(49)),
+    nationwide("Whit Monday", Rule::Easter(50)),
+    nationwide("Christmas Day", Rule::Fixed(12, 25)),
+    nationwide("Second Day of Christmas", Rule::Fixed(12, 26)),
+];
+
+// Federal holidays.
+const US: &[Definition] = &[
+    nationwide("New Year's Day", Rule::Fixed(1, 1)),
+    nationwide("Martin Luther King Jr. Day", Rule::Nth(1, Weekday::Mon, 3)),
+    nationwide("Washington's Birthday", Rule::Nth(2, Weekday::Mon, 3)),
+    nationwide("Memorial Day", Rule::Nth(5, Weekday::Mon, -1)),
+    nationwide("Juneteenth", Rule::Fixed(6, 19)),
+    nationwide("Independence Day", Rule::Fixed(7, 4)),
+    nationwide("Labor Day", Rule::Nth(9, Weekday::Mon, 1)),
+    nationwide("Columbus Day", Rule::Nth(10, Weekday::Mon, 2)),
+    nationwide("Veterans Day", Rule::Fixed(11, 11)),
+    nationwide("Thanksgiving Day", Rule::Nth(11, Weekday::Thu, 4)),
+    nationwide("Christmas Day", Rule::Fixed(12, 25)),
+];
+
+const COUNTRIES: &[Country] = &[
+    Country {
+        code: "AT",
+        observed: Observed::Never,
+        subdivisions: &[],
+        holidays: AT,
+    },
+    Country {
+        code: "DE",
+        observed: Observed::Never,
+        subdivisions: &[
+            "BB", "BE", "BW", "BY", "HB", "HE", "HH", "MV", "NI", "NW", "RP", "SH", "SL", "SN",
+            "ST", "TH",
+        ],
+        holidays: DE,
+    },
+    Country {
+        code: "FR",
+        observed: Observed::Never,
+        subdivisions: &[],
+        holidays: FR,
+    },
+    Country {
+        code: "GB",
+        observed: Observed::NextFreeWeekday,
+        subdivisions: &[],
+        holidays: GB,
+    },
+    Country {
+        code: "NL",
+        observed: Observed::Never,
+        subdivisions: &[],
+        holidays: NL,
+    },
+    Country {
+        code: "US",
+        observed: Observed::NearestWeekday,
+        subdivisions: &[],
+        holidays: US,
+    },
+];
+
+// Country codes with built-in holidays, for error messages and the docs.
+pub fn builtin_countries() -> Vec<&'static str> {
+    COUNTRIES.iter().map(|c| c.code).collect()
+}
+
+// A region is an ISO 3166-1 country code, optionally followed by a
+// subdivision: `DE` or `DE-BY`. Returns it upper-cased.
+pub fn normalize_region(region: &str) -> Result<String> {
+    let region = region.trim().to_ascii_uppercase();
+    let (country, subdivision) = match region.split_once('-') {
+        Some((country, subdivision)) => (country, Some(subdivision)),
+        None => (region.as_str(), None),
+    };
+    ensure!(
+        country.len() == 2 && country.chars().all(|c| c.is_ascii_alphabetic()),
+        "Holiday region must be a two-letter country code, optionally with a subdivision like DE-BY"
+    );
+    if let Some(subdivision) = subdivision {
+        ensure!(
+            (1..=3).contains(&subdivision.len())
+                && subdivision.chars().all(|c| c.is_ascii_alphanumeric()),
+            "Invalid subdivision '{}' in holiday region",
+            subdivision
+        );
+    }
+    Ok(region)
+}
+
+fn easter_sunday(year: i32) -> NaiveDate {
+    // Anonymous Gregorian algorithm.
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).expect("valid Easter date")
+}
+
+impl Rule {
+    fn date(&self, year: i32) -> Option<NaiveDate> {
+        match *self {
+            Rule::Fixed(month, day) => NaiveDate::from_ymd_opt(year, month, day),
+            Rule::Easter(offset) => Some(easter_sunday(year) + chrono::Duration::days(offset)),
+            Rule::Nth(month, weekday, n) if n > 0 => {
+                NaiveDate::from_weekday_of_month_opt(year, month, weekday, n as u8)
+            }
+            Rule::Nth(month, weekday, _) => {
+                let next_month = match month {
+                    12 => NaiveDate::from_ymd_opt(year + 1, 1, 1)?,
+                    _ => NaiveDate::from_ymd_opt(year, month + 1, 1)?,
+                };
+                let mut day = next_month.pred_opt()?;
+                while day.weekday() != weekday {
+                    day = day.pred_opt()?;
+                }
+                Some(day)
+            }
+            Rule::Before(month, day, weekday) => {
+                let mut date = NaiveDate::from_ymd_opt(year, month, day)?.pred_opt()?;
+                while date.weekday() != weekday {
+                    date = date.pred_opt()?;
+                }
+                Some(date)
+            }
+            Rule::NotSunday(month, day) => {
+                let date = NaiveDate::from_ymd_opt(year, month, day)?;
+                match date.weekday() {
+                    Weekday::Sun => date.pred_opt(),
+                    _ => Some(date),
+                }
+            }
+        }
+    }
+}
+
+fn is_weekend(date: NaiveDate) -> bool {
+    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+fn observe(holidays: Vec<Holiday>, observed: Observed) -> Vec<Holiday> {
+    match observed {
+        Observed::Never => holidays,
+        Observed::NearestWeekday => holidays
+            .into_iter()
+            .map(|h| match h.date.weekday() {
+                Weekday::Sat => Holiday {
+                    date: h.date - Days::new(1),
+                    name: format!("{} (observed)", h.name),
+                },
+                Weekday::Sun => Holiday {
+                    date: h.date + Days::new(1),
+                    name: format!("{} (observed)", h.name),
+                },
+                _ => h,
+            })
+            .collect(),
+        Observed::NextFreeWeekday => {
+            // Weekday holidays keep their dates; the rest take the next free
+            // weekday in order, so Christmas on a Saturday moves to Monday
+            // and Boxing Day to Tuesday.
+            let (mut kept, weekend): (Vec<_>, Vec<_>) =
+                holidays.into_iter().partition(|h| !is_weekend(h.date));
+            let mut taken: BTreeSet<NaiveDate> = kept.iter().map(|h| h.date).collect();
+            for h in weekend {
+                let mut date = h.date;
+                while is_weekend(date) || taken.contains(&date) {
+                    date = date + Days::new(1);
+                }
+                taken.insert(date);
+                kept.push(Holiday {
+                    date,
+                    name: format!("{} (substitute day)", h.name),
+                });
+            }
+            kept.sort_by_key(|h| h.date);
+            kept
+        }
+    }
+}
+
+// The built-in holidays of `region` in `year`.
+pub fn builtin(region: &str, year: i32) -> Result<Vec<Holiday>> {
+    let region = normalize_region(region)?;
+    let (code, subdivision) = match region.split_once('-') {
+        Some((code, subdivision)) => (code, Some(subdivision)),
+        None => (region.as_str(), None),
+    };
+    let Some(country) = COUNTRIES.iter().find(|c| c.code == code) else {
+        bail!(
+            "No built-in holidays for '{}'; available: {}. Set HOLIDAY_PROVIDER_URL for other countries",
+            code,
+            builtin_countries().join(", ")
+        );
+    };
+    if let Some(subdivision) = subdivision {
+        ensure!(
+            country.subdivisions.contains(&subdivision),
+            "Unknown subdivision '{}' of {}; available: {}",
+            subdivision,
+            code,
+            match country.subdivisions {
+                [] => "none".to_string(),
+                subdivisions => subdivisions.join(", "),
+            }
+        );
+    }
+    let mut holidays: Vec<Holiday> = country
+        .holidays
+        .iter()
+        .filter(|d| d.regions.is_empty() || subdivision.is_some_and(|s| d.regions.contains(&s)))
+        .filter_map(|d| {
+            Some(Holiday {
+                date: d.rule.date(year)?,
+                name: d.name.to_string(),
+            })
+        })
+        .collect();
+    holidays.sort_by_key(|h| h.date);
+    Ok(observe(holidays, country.observed))
+}
+
+// One entry of a Nager.Date style provider response.
+#[derive(Deserialize)]
+struct ProviderHoliday {
+    date: NaiveDate,
+    name: String,
+    #[serde(default = "default_global")]
+    global: bool,
+    #[serde(default)]
+    counties: Option<Vec<String>>,
+}
+
+fn default_global() -> bool {
+    true
+}
+
+pub fn provider_url(template: &str, region: &str, year: i32) -> String {
+    let country = region.split('-').next().unwrap_or(region);
+    template
+        .replace("{year}", &year.to_string())
+        .replace("{country}", country)
+}
+
+// Holidays of `region` in `year` from a provider answering like Nager.Date:
+// nationwide ones, plus those of the region's subdivision.
+pub async fn fetch(
+    client: &reqwest::Client,
+    template: &str,
+    region: &str,
+    year: i32,
+) -> Result<Vec<Holiday>> {
+    let region = normalize_region(region)?;
+    let url = provider_url(template, &region, year);
+    let entries: Vec<ProviderHoliday> = client
+        .get(&url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .with_context(|| format!("Invalid holiday data from {}", url))?;
+    let mut holidays: Vec<Holiday> = entries
+        .into_iter()
+        .filter(|h| {
+            h.global
+                || region.contains('-')
+                    && h.counties
+                        .as_ref()
+                        .is_some_and(|c| c.iter().any(|c| c.eq_ignore_ascii_case(&region)))
+        })
+        .map(|h| Holiday {
+            date: h.date,
+            name: h.name,
+        })
+        .collect();
+    holidays.sort_by_key(|h| h.date);
+    Ok(holidays)
+}
+
+fn slug(name: &str) -> String {
+    name.to_ascii_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// An all-day, transparent VEVENT with a UID stable across syncs.
+pub fn vevent(region: &str, holiday: &Holiday) -> String {
+    let day = holiday.date.format("%Y%m%d");
+    let next = (holiday.date + Days::new(1)).format("%Y%m%d");
+    format!(
+        "BEGIN:VEVENT\r\nUID:{day}-{}@holidays-{}.caldav-ics-sync\r\nDTSTAMP:{day}T000000Z\r\nDTSTART;VALUE=DATE:{day}\r\nDTEND;VALUE=DATE:{next}\r\nSUMMARY:{}\r\nCATEGORIES:Holidays\r\nTRANSP:TRANSPARENT\r\nEND:VEVENT\r\n",
+        slug(&holiday.name),
+        region.to_ascii_lowercase(),
+        escape_text(&holiday.name),
+    )
+}
+
+// The single calendar of a holiday source, from the provider when one is
+// configured and the built-in rules otherwise.
+pub async fn calendars(provider: Option<&str>, region: &str) -> Result<Vec<db::SourceCalendar>> {
+    let region = normalize_region(region)?;
+    let this_year = chrono::Utc::now().year();
+    let client = match provider {
+        Some(_) => Some(crate::http_clients::build("", "")?),
+        None => None,
+    };
+    sync_progress::update(|p| p.calendars_found = 1);
+    let mut events = Vec::new();
+    for year in this_year - YEARS_BEFORE..=this_year + YEARS_AFTER {
+        let holidays = match (provider, &client) {
+            (Some(template), Some(client)) => fetch(client, template, &region, year).await?,
+            _ => builtin(&region, year)?,
+        };
+        events.extend(holidays.iter().map(|h| vevent(&region, h)));
+    }
+    let count = events.len();
+    sync_progress::update(|p| {
+        p.calendars_fetched = 1;
+        p.events_fetched = count;
+    });
+    Ok(vec![db::SourceCalendar {
+        href: format!("holidays/{}", region),
+        events,
+        color: None,
+        order: None,
+        objects: Vec::new(),
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dates(holidays: &[Holiday]) -> Vec<String> {
+        holidays
+            .iter()
+            .map(|h| format!("{} {}", h.date.format("%m-%d"), h.name))
+            .collect()
+    }
+
+    #[test]
+    fn computes_easter() {
+        assert_eq!(easter_sunday(2024).to_string(), "2024-03-31");
+        assert_eq!(easter_sunday(2026).to_string(), "2026-04-05");
+        assert_eq!(easter_sunday(2027).to_string(), "2027-03-28");
+    }
+
+    #[test]
+    fn regional_holidays_need_the_subdivision() {
+        let national = dates(&builtin("de", 2026).unwrap());
+        assert_eq!(national.len(), 9);
+        assert!(national.contains(&"04-03 Good Friday".to_string()));
+        let bavaria = dates(&builtin("DE-BY", 2026).unwrap());
+        assert!(bavaria.contains(&"06-04 Corpus Christi".to_string()));
+        assert!(bavaria.contains(&"01-06 Epiphany".to_string()));
+        let saxony = dates(&builtin("DE-SN", 2026).unwrap());
+        assert!(saxony.contains(&"11-18 Day of Repentance and Prayer".to_string()));
+        assert!(builtin("DE-XX", 2026).is_err());
+        assert!(builtin("ZZ", 2026).is_err());
+        assert!(normalize_region("Germany").is_err());
+    }
+
+    #[test]
+    fn weekend_holidays_are_observed() {
+        // Christmas 2027 is a Saturday.
+        let gb = dates(&builtin("GB", 2027).unwrap());
+        assert!(gb.contains(&"12-27 Christmas Day (substitute day)".to_string()));
+        assert!(gb.contains(&"12-28 Boxing Day (substitute day)".to_string()));
+        let us = dates(&builtin("US", 2027).unwrap());
+        assert!(us.contains(&"12-24 Christmas Day (observed)".to_string()));
+        assert!(us.contains(&"11-25 Thanksgiving Day".to_string()));
+        assert!(us.contains(&"05-31 Memorial Day".to_string()));
+        // King's Day 2025 is a Sunday.
+        let nl = dates(&builtin("NL", 2025).unwrap());
+        assert!(nl.contains(&"04-26 King's Day".to_string()));
+    }
+
+    #[test]
+    fn builds_all_day_events() {
+        let event = vevent(
+            "DE-BY",
+            &Holiday {
+                date: NaiveDate::from_ymd_opt(2026, 10, 3).unwrap(),
+                name: "German Unity Day".into(),
+            },
+        );
+        assert!(event.contains("UID:20261003-german-unity-day@holidays-de-by.caldav-ics-sync\r\n"));
+        assert!(event.contains("DTSTART;VALUE=DATE:20261003\r\nDTEND;VALUE=DATE:20261004\r\n"));
+        assert!(event.contains("TRANSP:TRANSPARENT\r\n"));
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use reqwest::{Client, header, redirect};
+
+// Clients of recent syncs, keyed by server origin and credentials, so
+// back-to-back syncs against the same account reuse pooled connections and
+// TLS sessions instead of handshaking again.
+pub type ClientCache = Arc<Mutex<HashMap<ClientKey, Client>>>;
+
+#[derive(Debug, Hash, PartialEq, Eq)]
+pub struct ClientKey {
+    origin: String,
+    username: String,
+    password_hash: String,
+    user_agent: String,
+    custom_headers: CustomHeaders,
+    follow_redirects: bool,
+}
+
+pub const DEFAULT_USER_AGENT: &str = concat!("caldav-ics-sync/", env!("CARGO_PKG_VERSION"));
+
+// Extra headers a source or destination sends with every request, for
+// providers that want an app token or block unknown clients.
+pub type CustomHeaders = BTreeMap<String, String>;
+
+// Set per request or by the connection; overriding them would break it.
+const RESERVED_HEADERS: &[&str] = &[
+    "host",
+    "content-length",
+    "content-type",
+    "transfer-encoding",
+    "connection",
+    "depth",
+];
+
+pub fn validate_user_agent(user_agent: &str) -> Result<()> {
+    if header::HeaderValue::from_str(user_agent).is_err() {
+        bail!("User-Agent must be printable ASCII");
+    }
+    Ok(())
+}
+
+pub fn validate_custom_headers(headers: &CustomHeaders) -> Result<()> {
+    for (name, value) in headers {
+        let Ok(parsed) = header::HeaderName::from_bytes(name.as_bytes()) else {
+            bail!("Invalid header name '{}'", name);
+        };
+        if RESERVED_HEADERS.contains(&parsed.as_str()) {
+            bail!("Header '{}' cannot be overridden", name);
+        }
+        if header::HeaderValue::from_str(value).is_err() {
+            bail!("Header '{}' must have a printable ASCII value", name);
+        }
+    }
+    Ok(())
+}
+
+// Rotated credentials and deleted sources leave stale entries behind; past
+// this many the cache starts over.
+const MAX_CLIENTS: usize = 64;
+// Longer than reqwest's default so connections survive between frequent
+// scheduled syncs; servers closing them earlier is handled by the pool.
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+pub fn new_cache() -> ClientCache {
+    Arc::new(Mutex::new(HashMap::new()))
+}
+
+// A client sending Basic auth, or none when both credentials are empty.
+// `password` may be a reference (see `credentials`).
+pub fn build(username: &str, password: &str) -> Result<Client> {
+    with_auth(
+        username,
+        &crate::credentials::resolve(password)?,
+        "",
+        &CustomHeaders::new(),
+        true,
+    )
+}
+
+// A custom Authorization header replaces Basic auth.
+fn with_auth(
+    username: &str,
+    password: &str,
+    user_agent: &str,
+    custom_headers: &CustomHeaders,
+    follow_redirects: bool,
+) -> Result<Client> {
+    let builder = Client::builder()
+        .redirect(match follow_redirects {
+            true => redirect::Policy::default(),
+            false => redirect::Policy::none(),
+        })
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .tcp_keepalive(TCP_KEEPALIVE)
+        .user_agent(match user_agent {
+            "" => DEFAULT_USER_AGENT,
+            custom => custom,
+        });
+    let mut headers = header::HeaderMap::new();
+    if !username.is_empty() || !password.is_empty() {
+        let auth = format!("{}:{}", username, password);
+        let auth_header = format!(
+            "Basic {}",
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &auth)
+        );
+        headers.insert(
+            header::AUTHORIZATION,
+            header::HeaderValue::from_str(&auth_header)?,
+        );
+    }
+    for (name, value) in custom_headers {
+        headers.insert(
+            header::HeaderName::from_bytes(name.as_bytes())?,
+            header::HeaderValue::from_str(value)?,
+        );
+    }
+    Ok(builder.default_headers(headers).build()?)
+}
+
+// The cached client for `url`'s origin, these credentials and headers.
+// References are resolved first, so a rotated secret gets a fresh client.
+pub fn get(
+    cache: &ClientCache,
+    url: &str,
+    username: &str,
+    password: &str,
+    user_agent: &str,
+    custom_headers: &CustomHeaders,
+) -> Result<Client> {
+    cached(
+        cache,
+        url,
+        username,
+        password,
+        user_agent,
+        custom_headers,
+        true,
+    )
+}
+
+// Like `get`, but the client leaves redirects to the caller, which decides
+// whether the credentials go along; see `reverse_sync::fetch_feed`.
+pub fn get_for_feed(
+    cache: &ClientCache,
+    url: &str,
+    username: &str,
+    password: &str,
+    custom_headers: &CustomHeaders,
+) -> Result<Client> {
+    cached(cache, url, username, password, "", custom_headers, false)
+}
+
+fn cached(
+    cache: &ClientCache,
+    url: &str,
+    username: &str,
+    password: &str,
+    user_agent: &str,
+    custom_headers: &CustomHeaders,
+    follow_redirects: bool,
+) -> Result<Client> {
+    let resolved = crate::credentials::resolve(password)?;
+    let key = ClientKey {
+        origin: reqwest::Url::parse(url)
+            .map(|u| u.origin().ascii_serialization())
+            .unwrap_or_else(|_| url.to_string()),
+        username: username.to_string(),
+        password_hash: crate::db::content_hash(&resolved),
+        user_agent: user_agent.to_string(),
+        custom_headers: custom_headers.clone(),
+        follow_redirects,
+    };
+    let mut clients = cache.lock().unwrap();
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+    if clients.len() >= MAX_CLIENTS {
+        clients.clear();
+    }
+    let client = with_auth(
+        username,
+        &resolved,
+        user_agent,
+        custom_headers,
+        follow_redirects,
+    )?;
+    clients.insert(key, client.clone());
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_clients_per_origin_and_credentials() {
+        let cache = new_cache();
+        let none = CustomHeaders::new();
+        get(
+            &cache,
+            "https://dav.example.com/cal/a/",
+            "alice",
+            "pw",
+            "",
+            &none,
+        )
+        .unwrap();
+        get(
+            &cache,
+            "https://dav.example.com/cal/b/",
+            "alice",
+            "pw",
+            "",
+            &none,
+        )
+        .unwrap();
+        assert_eq!(cache.lock().unwrap().len(), 1);
+        get(
+            &cache,
+            "https://dav.example.com/",
+            "alice",
+            "rotated",
+            "",
+            &none,
+        )
+        .unwrap();
+        get(
+            &cache,
+            "https://other.example.com/",
+            "alice",
+            "pw",
+            "",
+            &none,
+        )
+        .unwrap();
+        get(&cache, "https://feeds.example.com/a.ics", "", "", "", &none).unwrap();
+        assert_eq!(cache.lock().unwrap().len(), 4);
+        get(
+            &cache,
+            "https://dav.example.com/",
+            "alice",
+            "pw",
+            "MyApp/1.0",
+            &none,
+        )
+        .unwrap();
+        assert_eq!(cache.lock().unwrap().len(), 5);
+    }
+}
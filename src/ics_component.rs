@@ -0,0 +1,182 @@
+use crate::ics_text::split_property;
+
+// A calendar component, usually a VEVENT, as its own content lines and the
+// components nested in it (VALARM, or anything a vendor invents). Lookups only
+// see the component's own properties, so an alarm's UID or DESCRIPTION is
+// never taken for the event's, and rewrites copy everything they don't touch
+// byte for byte: X- properties, folding and nested components included.
+
+#[derive(Debug, PartialEq)]
+pub enum Part<'a> {
+    // One of the component's own lines with its folded continuations, BEGIN
+    // and END included. `name` is upper-cased and without parameters.
+    Property { name: String, raw: &'a str },
+    // A nested component, from its BEGIN line through its END line.
+    Component { name: String, raw: &'a str },
+}
+
+fn name_of(line: &str) -> String {
+    let line = line.trim_end_matches(['\r', '\n']);
+    line[..line.find([';', ':']).unwrap_or(line.len())].to_ascii_uppercase()
+}
+
+fn value_of(line: &str) -> &str {
+    line.split_once(':').map_or("", |(_, v)| v.trim())
+}
+
+// Content lines with their continuation lines, as slices of `ics`.
+fn logical_lines(ics: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let (mut start, mut end) = (0, 0);
+    for line in ics.split_inclusive('\n') {
+        if end > start && !line.starts_with([' ', '\t']) {
+            out.push(&ics[start..end]);
+            start = end;
+        }
+        end += line.len();
+    }
+    if end > start {
+        out.push(&ics[start..end]);
+    }
+    out
+}
+
+pub fn parts(component: &str) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut nested: Option<(String, usize)> = None;
+    let mut offset = 0;
+    for line in logical_lines(component) {
+        let start = offset;
+        offset += line.len();
+        let name = name_of(line);
+        match name.as_str() {
+            "BEGIN" if depth == 1 => nested = Some((value_of(line).to_ascii_uppercase(), start)),
+            "END" if depth == 2 => {
+                if let Some((name, from)) = nested.take() {
+                    parts.push(Part::Component {
+                        name,
+                        raw: &component[from..offset],
+                    });
+                }
+            }
+            _ if depth <= 1 => parts.push(Part::Property {
+                name: name.clone(),
+                raw: line,
+            }),
+            _ => {}
+        }
+        match name.as_str() {
+            "BEGIN" => depth += 1,
+            "END" => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    // An unterminated nested component is kept as it was.
+    if let Some((name, from)) = nested {
+        parts.push(Part::Component {
+            name,
+            raw: &component[from..],
+        });
+    }
+    parts
+}
+
+// Joins a property's continuation lines and drops the line ending.
+pub fn unfold(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for (i, line) in raw.lines().enumerate() {
+        match i {
+            0 => out.push_str(line),
+            _ => out.push_str(line.get(1..).unwrap_or_default()),
+        }
+    }
+    out
+}
+
+// The unfolded line of the component's first own `name` property.
+pub fn property(component: &str, name: &str) -> Option<String> {
+    parts(component).into_iter().find_map(|part| match part {
+        Part::Property { name: n, raw } if n.eq_ignore_ascii_case(name) => Some(unfold(raw)),
+        _ => None,
+    })
+}
+
+// The trimmed value of the component's first own `name` property.
+pub fn value(component: &str, name: &str) -> Option<String> {
+    let line = property(component, name)?;
+    split_property(&line).map(|(_, value)| value.trim().to_string())
+}
+
+pub fn has_property(component: &str, name: &str) -> bool {
+    property(component, name).is_some()
+}
+
+// Copies `component`, replacing each own property `replace` returns a
+// replacement for (an empty one drops it). Nested components and every line
+// `replace` leaves alone are copied unchanged.
+pub fn rewrite(component: &str, mut replace: impl FnMut(&str, &str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(component.len());
+    for part in parts(component) {
+        match part {
+            Part::Property { name, raw } => match replace(&name, raw) {
+                Some(replacement) => out.push_str(&replacement),
+                None => out.push_str(raw),
+            },
+            Part::Component { raw, .. } => out.push_str(raw),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT: &str = "BEGIN:VEVENT\r\n\
+        UID:event-1\r\n\
+        SUMMARY:A long\r\n  title\r\n\
+        X-VENDOR-FLAG;X-PARAM=\"a:b\":keep\r\n\
+        BEGIN:VALARM\r\n\
+        UID:alarm-1\r\n\
+        ACTION:DISPLAY\r\n\
+        DESCRIPTION:Reminder\r\n\
+        BEGIN:X-NESTED\r\n\
+        X-DEEP:1\r\n\
+        END:X-NESTED\r\n\
+        END:VALARM\r\n\
+        DESCRIPTION:Event notes\r\n\
+        END:VEVENT\r\n";
+
+    #[test]
+    fn sees_only_the_components_own_properties() {
+        assert_eq!(value(EVENT, "UID").as_deref(), Some("event-1"));
+        assert_eq!(value(EVENT, "description").as_deref(), Some("Event notes"));
+        assert_eq!(value(EVENT, "SUMMARY").as_deref(), Some("A long title"));
+        assert_eq!(value(EVENT, "X-VENDOR-FLAG").as_deref(), Some("keep"));
+        assert!(!has_property(EVENT, "ACTION"));
+        let nested: Vec<_> = parts(EVENT)
+            .into_iter()
+            .filter_map(|p| match p {
+                Part::Component { name, raw } => Some((name, raw)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(nested.len(), 1);
+        assert_eq!(nested[0].0, "VALARM");
+        assert!(nested[0].1.starts_with("BEGIN:VALARM\r\n"));
+        assert!(nested[0].1.ends_with("END:VALARM\r\n"));
+    }
+
+    #[test]
+    fn rewrites_round_trip_everything_else() {
+        assert_eq!(rewrite(EVENT, |_, _| None), EVENT);
+        let renamed = rewrite(EVENT, |name, _| {
+            (name == "UID").then(|| "UID:event-2\r\n".to_string())
+        });
+        assert_eq!(renamed, EVENT.replace("UID:event-1", "UID:event-2"));
+        assert!(renamed.contains("UID:alarm-1\r\n"));
+        let dropped = rewrite(EVENT, |name, _| (name == "SUMMARY").then(String::new));
+        assert_eq!(dropped, EVENT.replace("SUMMARY:A long\r\n  title\r\n", ""));
+    }
+}
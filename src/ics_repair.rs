@@ -0,0 +1,351 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail, ensure};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use crate::api::reverse_sync::normalize_vevent;
+use crate::event_index::parse_datetime;
+use crate::ics_component;
+use crate::ics_text::split_property;
+
+// Repairs for upstream events that strict clients reject outright; Google
+// Calendar drops a whole feed over one event without DTSTAMP. A source opts in
+// per rule, as a comma-separated list in `repair_rules`:
+//
+// - `dtstamp` adds a missing DTSTAMP, copied from LAST-MODIFIED or CREATED
+//   so the feed stays byte-identical between syncs, else the Unix epoch.
+// - `swap_ranges` swaps DTSTART and DTEND when the end comes first.
+// - `clip_ranges` drops such a DTEND instead, leaving the default length.
+// - `uids` gives events without a UID one derived from their content.
+// - `regenerate_dtstamp` sets every DTSTAMP to the sync time.
+// - `clamp_timestamps` moves DTSTAMP, LAST-MODIFIED and CREATED values
+//   from the future (beyond MAX_CLOCK_SKEW) back to the sync time, for
+//   servers with a wrong clock.
+//
+// The last two change the feed on every sync, so with either enabled a
+// sync that changes nothing but such fields keeps the stored feed.
+pub const RULES: &[&str] = &[
+    "dtstamp",
+    "swap_ranges",
+    "clip_ranges",
+    "uids",
+    "regenerate_dtstamp",
+    "clamp_timestamps",
+];
+const SYNC_TIME_RULES: &[&str] = &["regenerate_dtstamp", "clamp_timestamps"];
+const CLAMPED_PROPERTIES: &[&str] = &["DTSTAMP", "LAST-MODIFIED", "CREATED"];
+const MAX_CLOCK_SKEW: chrono::TimeDelta = chrono::TimeDelta::minutes(5);
+const GENERATED_UID_DOMAIN: &str = "repaired.caldav-ics-sync";
+const FALLBACK_DTSTAMP: &str = "19700101T000000Z";
+const UTC_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+// Repairs listed in a sync result; the counts cover all of them.
+const MAX_EXAMPLES: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Repair {
+    pub rule: String,
+    // The event's UID after the repair.
+    pub uid: Option<String>,
+    pub detail: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, ToSchema)]
+pub struct RepairReport {
+    pub total: usize,
+    pub by_rule: BTreeMap<String, usize>,
+    pub examples: Vec<Repair>,
+}
+
+impl RepairReport {
+    fn add(&mut self, rule: &str, uid: Option<String>, detail: String) {
+        self.total += 1;
+        *self.by_rule.entry(rule.to_string()).or_default() += 1;
+        if self.examples.len() < MAX_EXAMPLES {
+            self.examples.push(Repair {
+                rule: rule.to_string(),
+                uid,
+                detail,
+            });
+        }
+    }
+}
+
+// `" uids, DTSTAMP"` -> `"dtstamp,uids"`; unknown rules are rejected.
+pub fn normalize_rules(rules: &str) -> Result<String> {
+    let mut enabled = Vec::new();
+    for rule in rules.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+        let rule = rule.to_ascii_lowercase();
+        match RULES.iter().find(|r| **r == rule) {
+            Some(known) => enabled.push(*known),
+            None => bail!(
+                "Unknown repair rule '{}'; expected any of: {}",
+                rule,
+                RULES.join(", ")
+            ),
+        }
+    }
+    ensure!(
+        !(enabled.contains(&"swap_ranges") && enabled.contains(&"clip_ranges")),
+        "Repair rules swap_ranges and clip_ranges cannot be combined"
+    );
+    Ok(RULES
+        .iter()
+        .filter(|r| enabled.contains(r))
+        .copied()
+        .collect::<Vec<_>>()
+        .join(","))
+}
+
+fn enabled(rules: &str, rule: &str) -> bool {
+    rules.split(',').any(|r| r.trim() == rule)
+}
+
+// Whether `rules` stamp events with the sync time.
+pub fn uses_sync_time(rules: &str) -> bool {
+    SYNC_TIME_RULES.iter().any(|rule| enabled(rules, rule))
+}
+
+// Inserts `lines` right after the component's BEGIN line.
+fn insert_after_begin(vevent: &str, lines: &str) -> String {
+    ics_component::rewrite(vevent, |name, raw| {
+        (name == "BEGIN").then(|| format!("{}{}", raw, lines))
+    })
+}
+
+fn generated_uid(vevent: &str) -> String {
+    let digest = format!(
+        "{:x}",
+        Sha256::digest(normalize_vevent(vevent).join("\n").as_bytes())
+    );
+    format!("{}@{}", &digest[..16], GENERATED_UID_DOMAIN)
+}
+
+// `vevent` with a UID derived from its content when it has none, so the same
+// event gets the same UID each time it comes back.
+pub fn ensure_uid(vevent: &str) -> String {
+    match ics_component::value(vevent, "UID") {
+        Some(_) => vevent.to_string(),
+        None => insert_after_begin(vevent, &format!("UID:{}\r\n", generated_uid(vevent))),
+    }
+}
+
+fn utc_stamp(vevent: &str) -> String {
+    ["LAST-MODIFIED", "CREATED"]
+        .iter()
+        .filter_map(|name| ics_component::value(vevent, name))
+        .find(|v| v.len() == 16 && v.ends_with('Z'))
+        .unwrap_or_else(|| FALLBACK_DTSTAMP.to_string())
+}
+
+// The DTSTART and DTEND lines of an event that ends before it starts.
+fn inverted_range(vevent: &str) -> Option<(String, String)> {
+    let parse = |line: &str| {
+        let (params, value) = split_property(line)?;
+        let tzid = params.split(';').find_map(|p| p.strip_prefix("TZID="));
+        parse_datetime(value, tzid)
+    };
+    let start = ics_component::property(vevent, "DTSTART")?;
+    let end = ics_component::property(vevent, "DTEND")?;
+    (parse(&end)? < parse(&start)?).then_some((start, end))
+}
+
+// The UTC timestamp lines of `vevent` that lie too far ahead of `now`.
+fn future_timestamps(vevent: &str, now: NaiveDateTime) -> Vec<(&'static str, String)> {
+    CLAMPED_PROPERTIES
+        .iter()
+        .filter_map(|name| Some((*name, ics_component::property(vevent, name)?)))
+        .filter(|(_, line)| {
+            split_property(line)
+                .and_then(|(_, value)| NaiveDateTime::parse_from_str(value, UTC_FORMAT).ok())
+                .is_some_and(|at| at > now + MAX_CLOCK_SKEW)
+        })
+        .collect()
+}
+
+fn repair_event(
+    vevent: &str,
+    rules: &str,
+    now: NaiveDateTime,
+    report: &mut RepairReport,
+) -> String {
+    let mut out = vevent.to_string();
+    let stamp = now.format(UTC_FORMAT).to_string();
+    if enabled(rules, "uids") && ics_component::value(&out, "UID").is_none() {
+        let uid = generated_uid(&out);
+        out = insert_after_begin(&out, &format!("UID:{}\r\n", uid));
+        report.add("uids", Some(uid), "added a generated UID".into());
+    }
+    let uid = ics_component::value(&out, "UID");
+    if enabled(rules, "clamp_timestamps") {
+        let bogus = future_timestamps(&out, now);
+        if !bogus.is_empty() {
+            out = ics_component::rewrite(&out, |name, _| {
+                bogus
+                    .iter()
+                    .any(|(bogus_name, _)| name.eq_ignore_ascii_case(bogus_name))
+                    .then(|| format!("{}:{}\r\n", name, stamp))
+            });
+            let lines: Vec<_> = bogus.into_iter().map(|(_, line)| line).collect();
+            report.add(
+                "clamp_timestamps",
+                uid.clone(),
+                format!("moved {} back to {}", lines.join(", "), stamp),
+            );
+        }
+    }
+    if enabled(rules, "regenerate_dtstamp") {
+        let line = format!("DTSTAMP:{}\r\n", stamp);
+        out = match ics_component::has_property(&out, "DTSTAMP") {
+            true => {
+                ics_component::rewrite(&out, |name, _| (name == "DTSTAMP").then(|| line.clone()))
+            }
+            false => insert_after_begin(&out, &line),
+        };
+        report.add(
+            "regenerate_dtstamp",
+            uid.clone(),
+            format!("set DTSTAMP:{}", stamp),
+        );
+    }
+    if enabled(rules, "dtstamp") && !ics_component::has_property(&out, "DTSTAMP") {
+        let stamp = utc_stamp(&out);
+        out = insert_after_begin(&out, &format!("DTSTAMP:{}\r\n", stamp));
+        report.add("dtstamp", uid.clone(), format!("added DTSTAMP:{}", stamp));
+    }
+    if let Some((start, end)) = inverted_range(&out) {
+        if enabled(rules, "swap_ranges") {
+            // Each line keeps its name and takes the other's parameters and value.
+            let new_start = format!("DTSTART{}\r\n", &end["DTEND".len()..]);
+            let new_end = format!("DTEND{}\r\n", &start["DTSTART".len()..]);
+            out = ics_component::rewrite(&out, |name, _| match name {
+                "DTSTART" => Some(new_start.clone()),
+                "DTEND" => Some(new_end.clone()),
+                _ => None,
+            });
+            report.add("swap_ranges", uid, format!("swapped {} and {}", start, end));
+        } else if enabled(rules, "clip_ranges") {
+            out = ics_component::rewrite(&out, |name, _| (name == "DTEND").then(String::new));
+            report.add(
+                "clip_ranges",
+                uid,
+                format!("dropped {} before {}", end, start),
+            );
+        }
+    }
+    out
+}
+
+// Applies the enabled `rules` to every VEVENT of `ics`; everything else is
+// copied as is.
+pub fn apply(ics: &str, rules: &str) -> (String, RepairReport) {
+    apply_at(ics, rules, chrono::Utc::now().naive_utc())
+}
+
+// `apply` with `now` as the sync time.
+pub fn apply_at(ics: &str, rules: &str, now: NaiveDateTime) -> (String, RepairReport) {
+    let mut report = RepairReport::default();
+    if rules.trim().is_empty() {
+        return (ics.to_string(), report);
+    }
+    let mut out = String::with_capacity(ics.len());
+    let mut vevent = String::new();
+    let mut in_vevent = false;
+    for line in ics.split_inclusive('\n') {
+        if line.starts_with("BEGIN:VEVENT") {
+            in_vevent = true;
+        }
+        if !in_vevent {
+            out.push_str(line);
+            continue;
+        }
+        vevent.push_str(line);
+        if line.starts_with("END:VEVENT") {
+            in_vevent = false;
+            out.push_str(&repair_event(&vevent, rules, now, &mut report));
+            vevent.clear();
+        }
+    }
+    out.push_str(&vevent);
+    (out, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calendar(events: &str) -> String {
+        format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}END:VCALENDAR\r\n",
+            events
+        )
+    }
+
+    #[test]
+    fn validates_and_orders_rules() {
+        assert_eq!(normalize_rules(" uids, DTSTAMP").unwrap(), "dtstamp,uids");
+        assert_eq!(normalize_rules("").unwrap(), "");
+        assert!(normalize_rules("dtstamp,fix_everything").is_err());
+        assert!(normalize_rules("swap_ranges,clip_ranges").is_err());
+    }
+
+    #[test]
+    fn repairs_only_what_the_rules_enable() {
+        let ics = calendar(
+            "BEGIN:VEVENT\r\nSUMMARY:No UID\r\nDTSTART:20260310T100000Z\r\nEND:VEVENT\r\n\
+             BEGIN:VEVENT\r\nUID:backwards\r\nDTSTAMP:20260101T000000Z\r\n\
+             DTSTART;TZID=Europe/Berlin:20260310T120000\r\nDTEND:20260310T090000Z\r\n\
+             BEGIN:VALARM\r\nACTION:DISPLAY\r\nTRIGGER:-PT5M\r\nEND:VALARM\r\nEND:VEVENT\r\n\
+             BEGIN:VEVENT\r\nUID:fine\r\nDTSTAMP:20260101T000000Z\r\n\
+             LAST-MODIFIED:20260102T000000Z\r\nEND:VEVENT\r\n",
+        );
+        let (unchanged, report) = apply(&ics, "");
+        assert_eq!((unchanged.as_str(), report.total), (ics.as_str(), 0));
+
+        let (fixed, report) = apply(&ics, "dtstamp,swap_ranges,uids");
+        assert_eq!(report.total, 3);
+        assert_eq!(report.by_rule["uids"], 1);
+        let uid = report.examples[0].uid.clone().unwrap();
+        assert!(uid.ends_with("@repaired.caldav-ics-sync"));
+        assert!(fixed.contains(&format!(
+            "BEGIN:VEVENT\r\nDTSTAMP:19700101T000000Z\r\nUID:{}\r\nSUMMARY:No UID\r\n",
+            uid
+        )));
+        assert!(fixed.contains(
+            "DTSTART:20260310T090000Z\r\nDTEND;TZID=Europe/Berlin:20260310T120000\r\nBEGIN:VALARM\r\n"
+        ));
+        // The same input gets the same UID on every sync.
+        assert_eq!(apply(&ics, "uids").1.examples[0].uid, Some(uid));
+
+        let (clipped, report) = apply(&ics, "clip_ranges");
+        assert_eq!(report.by_rule["clip_ranges"], 1);
+        assert!(!clipped.contains("DTEND"));
+        assert!(clipped.contains("BEGIN:VEVENT\r\nSUMMARY:No UID\r\n"));
+    }
+
+    #[test]
+    fn stamps_sync_time_and_clamps_future_timestamps() {
+        let now = NaiveDateTime::parse_from_str("20260310T120000Z", UTC_FORMAT).unwrap();
+        let ics = calendar(
+            "BEGIN:VEVENT\r\nUID:skewed\r\nDTSTAMP:20300101T000000Z\r\n\
+             LAST-MODIFIED:20260310T120200Z\r\nCREATED:20290101T000000Z\r\nEND:VEVENT\r\n\
+             BEGIN:VEVENT\r\nUID:fine\r\nDTSTAMP:20260101T000000Z\r\nEND:VEVENT\r\n",
+        );
+
+        let (clamped, report) = apply_at(&ics, "clamp_timestamps", now);
+        assert_eq!(report.by_rule["clamp_timestamps"], 1);
+        // Within the allowed skew LAST-MODIFIED stays.
+        assert!(clamped.contains(
+            "UID:skewed\r\nDTSTAMP:20260310T120000Z\r\nLAST-MODIFIED:20260310T120200Z\r\nCREATED:20260310T120000Z\r\n"
+        ));
+        assert!(clamped.contains("UID:fine\r\nDTSTAMP:20260101T000000Z\r\n"));
+
+        let (stamped, report) = apply_at(&ics, "regenerate_dtstamp", now);
+        assert_eq!(report.by_rule["regenerate_dtstamp"], 2);
+        assert_eq!(stamped.matches("DTSTAMP:20260310T120000Z").count(), 2);
+        assert!(uses_sync_time("dtstamp,regenerate_dtstamp"));
+        assert!(!uses_sync_time("dtstamp,uids"));
+    }
+}
@@ -0,0 +1,119 @@
+use anyhow::Result;
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use reqwest::header;
+
+// RFC 5545 TEXT escaping for property values written into a feed.
+pub fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace(['\n', '\r'], "\\n")
+}
+
+// Reverses `escape_text` in one pass, so `\\n` stays a backslash followed by
+// an `n`. Unknown escapes keep their character.
+pub fn unescape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+// Splits a content line into its name with parameters and its value. A colon
+// inside a quoted parameter (`ALTREP="https://..."`) doesn't end the name.
+pub fn split_property(line: &str) -> Option<(&str, &str)> {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some((&line[..i], &line[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Decodes a body in the charset its Content-Type names. Without one, a byte
+// order mark decides, then UTF-8, and bodies that aren't valid UTF-8 are read
+// as Windows-1252, which older calendar exports use.
+pub fn decode_body(bytes: &[u8], content_type: Option<&str>) -> String {
+    let declared = content_type
+        .and_then(|ct| {
+            ct.split(';')
+                .filter_map(|p| p.trim().split_once('='))
+                .find(|(k, _)| k.trim().eq_ignore_ascii_case("charset"))
+        })
+        .and_then(|(_, v)| Encoding::for_label(v.trim().trim_matches('"').as_bytes()));
+    let encoding = match (declared, Encoding::for_bom(bytes)) {
+        (Some(encoding), _) => encoding,
+        (None, Some((encoding, _))) => encoding,
+        (None, None) if std::str::from_utf8(bytes).is_ok() => UTF_8,
+        (None, None) => {
+            tracing::warn!(
+                "Response is not valid UTF-8 and names no charset; reading it as Windows-1252"
+            );
+            WINDOWS_1252
+        }
+    };
+    encoding.decode(bytes).0.into_owned()
+}
+
+// `Response::text`, with `decode_body`'s fallback for undeclared charsets.
+pub async fn read_text(res: reqwest::Response) -> Result<String> {
+    let content_type = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+    let bytes = res.bytes().await?;
+    Ok(decode_body(&bytes, content_type.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_escaping_round_trips() {
+        let value = "Planning, Q3; room 2\\B\nbring 🍰";
+        let escaped = escape_text(value);
+        assert_eq!(escaped, "Planning\\, Q3\\; room 2\\\\B\\nbring 🍰");
+        assert_eq!(unescape_text(&escaped), value);
+        assert_eq!(unescape_text("C:\\\\new"), "C:\\new");
+    }
+
+    #[test]
+    fn splits_after_quoted_parameters() {
+        assert_eq!(
+            split_property("DESCRIPTION;ALTREP=\"https://x/y\":See: notes"),
+            Some(("DESCRIPTION;ALTREP=\"https://x/y\"", "See: notes"))
+        );
+    }
+
+    #[test]
+    fn decodes_declared_and_legacy_charsets() {
+        let latin1 = b"SUMMARY:Caf\xe9";
+        assert_eq!(
+            decode_body(latin1, Some("text/calendar; charset=ISO-8859-1")),
+            "SUMMARY:Café"
+        );
+        assert_eq!(decode_body(latin1, None), "SUMMARY:Café");
+        assert_eq!(decode_body("SUMMARY:🎉".as_bytes(), None), "SUMMARY:🎉");
+        assert_eq!(
+            decode_body(b"\xef\xbb\xbfBEGIN:VCALENDAR", Some("text/calendar")),
+            "BEGIN:VCALENDAR"
+        );
+    }
+}
@@ -0,0 +1,644 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::event_index::{parse_datetime, parse_duration};
+use crate::ics_text::split_property;
+
+// Enough to debug a file without sending megabytes of repeats back.
+const MAX_ISSUES: usize = 500;
+const MAX_LINE_OCTETS: usize = 75;
+
+// RFC 5545, 5546, 7953 and 7986 components and properties.
+const KNOWN_COMPONENTS: &[&str] = &[
+    "VCALENDAR",
+    "VEVENT",
+    "VTODO",
+    "VJOURNAL",
+    "VFREEBUSY",
+    "VTIMEZONE",
+    "STANDARD",
+    "DAYLIGHT",
+    "VALARM",
+    "VAVAILABILITY",
+    "AVAILABLE",
+];
+const KNOWN_PROPERTIES: &[&str] = &[
+    "ACTION",
+    "ATTACH",
+    "ATTENDEE",
+    "BUSYTYPE",
+    "CALSCALE",
+    "CATEGORIES",
+    "CLASS",
+    "COLOR",
+    "COMMENT",
+    "COMPLETED",
+    "CONFERENCE",
+    "CONTACT",
+    "CREATED",
+    "DESCRIPTION",
+    "DTEND",
+    "DTSTAMP",
+    "DTSTART",
+    "DUE",
+    "DURATION",
+    "EXDATE",
+    "FREEBUSY",
+    "GEO",
+    "IMAGE",
+    "LAST-MODIFIED",
+    "LOCATION",
+    "METHOD",
+    "NAME",
+    "ORGANIZER",
+    "PERCENT-COMPLETE",
+    "PRIORITY",
+    "PRODID",
+    "RDATE",
+    "RECURRENCE-ID",
+    "REFRESH-INTERVAL",
+    "RELATED-TO",
+    "REPEAT",
+    "REQUEST-STATUS",
+    "RESOURCES",
+    "RRULE",
+    "SEQUENCE",
+    "SOURCE",
+    "STATUS",
+    "SUMMARY",
+    "TRANSP",
+    "TRIGGER",
+    "TZID",
+    "TZNAME",
+    "TZOFFSETFROM",
+    "TZOFFSETTO",
+    "TZURL",
+    "UID",
+    "URL",
+    "VERSION",
+];
+// Properties a component may carry at most once.
+const SINGLE_PROPERTIES: &[&str] = &[
+    "CLASS",
+    "CREATED",
+    "DESCRIPTION",
+    "DTEND",
+    "DTSTAMP",
+    "DTSTART",
+    "DUE",
+    "DURATION",
+    "LAST-MODIFIED",
+    "LOCATION",
+    "ORGANIZER",
+    "PRIORITY",
+    "PRODID",
+    "RECURRENCE-ID",
+    "SEQUENCE",
+    "STATUS",
+    "SUMMARY",
+    "TRANSP",
+    "UID",
+    "VERSION",
+];
+const DATE_PROPERTIES: &[&str] = &[
+    "COMPLETED",
+    "CREATED",
+    "DTEND",
+    "DTSTAMP",
+    "DTSTART",
+    "DUE",
+    "LAST-MODIFIED",
+    "RECURRENCE-ID",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    // The file cannot be read as iCalendar at all.
+    Syntax,
+    // A rule of RFC 5545 is broken.
+    Rfc,
+    UnknownProperty,
+    Timezone,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Issue {
+    pub severity: Severity,
+    pub category: Category,
+    // 1-based line of the input, where the problem has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ValidationReport {
+    // No errors; warnings alone leave a file valid.
+    pub valid: bool,
+    pub errors: usize,
+    pub warnings: usize,
+    // How many of each component the file holds, e.g. `{"VEVENT": 12}`.
+    pub components: BTreeMap<String, usize>,
+    pub issues: Vec<Issue>,
+    // More issues were found than are listed.
+    pub truncated: bool,
+}
+
+impl ValidationReport {
+    fn add(
+        &mut self,
+        severity: Severity,
+        category: Category,
+        line: Option<usize>,
+        message: String,
+    ) {
+        match severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+        }
+        if self.issues.len() < MAX_ISSUES {
+            self.issues.push(Issue {
+                severity,
+                category,
+                line,
+                message,
+            });
+        } else {
+            self.truncated = true;
+        }
+    }
+
+    fn error(&mut self, category: Category, line: usize, message: String) {
+        self.add(Severity::Error, category, Some(line), message);
+    }
+
+    fn warning(&mut self, category: Category, line: usize, message: String) {
+        self.add(Severity::Warning, category, Some(line), message);
+    }
+}
+
+struct Component {
+    name: String,
+    line: usize,
+    // Property name -> line of its first occurrence.
+    properties: BTreeMap<String, usize>,
+    starts: Option<(NaiveDateTime, bool)>,
+    ends: Option<(NaiveDateTime, bool, usize)>,
+    has_children: BTreeSet<String>,
+}
+
+impl Component {
+    fn new(name: String, line: usize) -> Self {
+        Self {
+            name,
+            line,
+            properties: BTreeMap::new(),
+            starts: None,
+            ends: None,
+            has_children: BTreeSet::new(),
+        }
+    }
+}
+
+// Content lines with the input line each starts on.
+fn content_lines(ics: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (i, raw) in ics.split('\n').enumerate() {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        match (raw.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some((_, last))) => last.push_str(rest),
+            _ if raw.is_empty() => {}
+            _ => lines.push((i + 1, raw.to_string())),
+        }
+    }
+    lines
+}
+
+fn required(report: &mut ValidationReport, component: &Component, names: &[&str]) {
+    for name in names {
+        if !component.properties.contains_key(*name) {
+            report.error(
+                Category::Rfc,
+                component.line,
+                format!(
+                    "{} is missing the required {} property",
+                    component.name, name
+                ),
+            );
+        }
+    }
+}
+
+fn check_component(report: &mut ValidationReport, component: &Component, method: bool) {
+    let has = |name: &str| component.properties.get(name).copied();
+    match component.name.as_str() {
+        "VCALENDAR" => {
+            required(report, component, &["PRODID", "VERSION"]);
+            if component.has_children.is_empty() {
+                report.warning(
+                    Category::Rfc,
+                    component.line,
+                    "VCALENDAR contains no components".into(),
+                );
+            }
+        }
+        "VEVENT" => {
+            required(report, component, &["UID", "DTSTAMP"]);
+            // RFC 5545 3.6.1: only iTIP messages may leave out DTSTART.
+            if !method {
+                required(report, component, &["DTSTART"]);
+            }
+            if let (Some(_), Some(line)) = (has("DTEND"), has("DURATION")) {
+                report.error(
+                    Category::Rfc,
+                    line,
+                    "VEVENT has both DTEND and DURATION".into(),
+                );
+            }
+        }
+        "VTODO" => {
+            required(report, component, &["UID", "DTSTAMP"]);
+            if let (Some(_), Some(line)) = (has("DUE"), has("DURATION")) {
+                report.error(
+                    Category::Rfc,
+                    line,
+                    "VTODO has both DUE and DURATION".into(),
+                );
+            }
+        }
+        "VJOURNAL" | "VFREEBUSY" => required(report, component, &["UID", "DTSTAMP"]),
+        "VTIMEZONE" => {
+            required(report, component, &["TZID"]);
+            if !component.has_children.contains("STANDARD")
+                && !component.has_children.contains("DAYLIGHT")
+            {
+                report.error(
+                    Category::Timezone,
+                    component.line,
+                    "VTIMEZONE needs at least one STANDARD or DAYLIGHT component".into(),
+                );
+            }
+        }
+        "STANDARD" | "DAYLIGHT" => required(
+            report,
+            component,
+            &["DTSTART", "TZOFFSETFROM", "TZOFFSETTO"],
+        ),
+        "VALARM" => required(report, component, &["ACTION", "TRIGGER"]),
+        _ => {}
+    }
+    if let (Some((start, start_date)), Some((end, end_date, line))) =
+        (component.starts, component.ends)
+    {
+        if start_date != end_date {
+            report.error(
+                Category::Rfc,
+                line,
+                format!(
+                    "{} mixes DATE and DATE-TIME values for its start and end",
+                    component.name
+                ),
+            );
+        } else if end < start {
+            report.error(
+                Category::Rfc,
+                line,
+                format!("{} ends before it starts", component.name),
+            );
+        }
+    }
+}
+
+// Checks an ICS document the way a strict client would, returning every
+// problem found rather than stopping at the first.
+pub fn validate(ics: &str) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    if let Err(e) = icalendar::parser::read_calendar(&icalendar::parser::unfold(ics)) {
+        report.add(
+            Severity::Error,
+            Category::Syntax,
+            None,
+            format!("The iCalendar parser rejected the file: {}", e),
+        );
+    }
+    if ics
+        .match_indices('\n')
+        .any(|(i, _)| i == 0 || ics.as_bytes()[i - 1] != b'\r')
+    {
+        report.add(
+            Severity::Warning,
+            Category::Rfc,
+            None,
+            "Lines end with LF instead of CRLF".into(),
+        );
+    }
+    if let Some(line) = ics
+        .lines()
+        .position(|l| l.trim_end_matches('\r').len() > MAX_LINE_OCTETS)
+    {
+        report.warning(
+            Category::Rfc,
+            line + 1,
+            format!(
+                "Lines longer than {} octets should be folded",
+                MAX_LINE_OCTETS
+            ),
+        );
+    }
+
+    let mut stack: Vec<Component> = Vec::new();
+    let mut method = false;
+    let mut calendars = 0;
+    let mut defined_zones = BTreeSet::new();
+    let mut used_zones: BTreeMap<String, usize> = BTreeMap::new();
+    for (line, content) in content_lines(ics) {
+        let Some((head, value)) = split_property(&content) else {
+            report.error(
+                Category::Syntax,
+                line,
+                format!("'{}' is not a property (NAME:value)", content),
+            );
+            continue;
+        };
+        let mut params = head.split(';');
+        let name = params.next().unwrap_or_default().to_ascii_uppercase();
+        let tzid = params
+            .find_map(|p| {
+                p.split_once('=')
+                    .filter(|(k, _)| k.eq_ignore_ascii_case("TZID"))
+            })
+            .map(|(_, v)| v.trim_matches('"').to_string());
+        let value = value.trim();
+        match name.as_str() {
+            "BEGIN" => {
+                let component = value.to_ascii_uppercase();
+                match stack.last_mut() {
+                    Some(parent) => {
+                        parent.has_children.insert(component.clone());
+                    }
+                    None if component != "VCALENDAR" => report.error(
+                        Category::Syntax,
+                        line,
+                        format!("BEGIN:{} is outside of a VCALENDAR", component),
+                    ),
+                    None => calendars += 1,
+                }
+                if !KNOWN_COMPONENTS.contains(&component.as_str()) && !component.starts_with("X-") {
+                    report.warning(
+                        Category::UnknownProperty,
+                        line,
+                        format!("Unknown component {}", component),
+                    );
+                }
+                *report.components.entry(component.clone()).or_default() += 1;
+                stack.push(Component::new(component, line));
+                continue;
+            }
+            "END" => {
+                let component = value.to_ascii_uppercase();
+                match stack.pop() {
+                    Some(open) if open.name == component => {
+                        check_component(&mut report, &open, method);
+                        if open.name == "VCALENDAR" {
+                            method = false;
+                        }
+                    }
+                    Some(open) => {
+                        report.error(
+                            Category::Syntax,
+                            line,
+                            format!(
+                                "END:{} does not close BEGIN:{} from line {}",
+                                component, open.name, open.line
+                            ),
+                        );
+                        stack.push(open);
+                    }
+                    None => report.error(
+                        Category::Syntax,
+                        line,
+                        format!("END:{} has no matching BEGIN", component),
+                    ),
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let Some(component) = stack.last_mut() else {
+            report.error(
+                Category::Syntax,
+                line,
+                format!("{} is outside of a VCALENDAR", name),
+            );
+            continue;
+        };
+        if !KNOWN_PROPERTIES.contains(&name.as_str()) && !name.starts_with("X-") {
+            report.warning(
+                Category::UnknownProperty,
+                line,
+                format!("Unknown property {} in {}", name, component.name),
+            );
+        }
+        if component.properties.contains_key(&name) && SINGLE_PROPERTIES.contains(&name.as_str()) {
+            report.error(
+                Category::Rfc,
+                line,
+                format!("{} appears more than once in {}", name, component.name),
+            );
+        }
+        component.properties.entry(name.clone()).or_insert(line);
+        if let Some(tzid) = &tzid {
+            used_zones.entry(tzid.clone()).or_insert(line);
+        }
+        match name.as_str() {
+            "METHOD" if component.name == "VCALENDAR" => method = true,
+            "VERSION" if component.name == "VCALENDAR" && value != "2.0" => report.error(
+                Category::Rfc,
+                line,
+                format!("VERSION is '{}'; iCalendar files must be 2.0", value),
+            ),
+            "TZID" if component.name == "VTIMEZONE" => {
+                defined_zones.insert(value.to_string());
+            }
+            "X-WR-TIMEZONE" if value.parse::<chrono_tz::Tz>().is_err() => report.warning(
+                Category::Timezone,
+                line,
+                format!("X-WR-TIMEZONE '{}' is not an IANA time zone", value),
+            ),
+            "DURATION" if parse_duration(value).is_none() => report.error(
+                Category::Rfc,
+                line,
+                format!("DURATION '{}' is not a valid duration", value),
+            ),
+            _ => {}
+        }
+        if DATE_PROPERTIES.contains(&name.as_str())
+            && !matches!(component.name.as_str(), "STANDARD" | "DAYLIGHT")
+        {
+            let Some(at) = parse_datetime(value, tzid.as_deref()) else {
+                report.error(
+                    Category::Rfc,
+                    line,
+                    format!("{} '{}' is not a valid DATE or DATE-TIME", name, value),
+                );
+                continue;
+            };
+            let date = value.len() == 8;
+            match name.as_str() {
+                "DTSTART" => component.starts = Some((at, date)),
+                "DTEND" | "DUE" => component.ends = Some((at, date, line)),
+                "DTSTAMP" | "CREATED" | "LAST-MODIFIED" | "COMPLETED" if !value.ends_with('Z') => {
+                    report.error(
+                        Category::Rfc,
+                        line,
+                        format!("{} must be a UTC time ending in Z", name),
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+    for open in stack.iter().rev() {
+        report.error(
+            Category::Syntax,
+            open.line,
+            format!("BEGIN:{} is never closed", open.name),
+        );
+    }
+    if calendars == 0 {
+        report.add(
+            Severity::Error,
+            Category::Syntax,
+            None,
+            "The input has no VCALENDAR".into(),
+        );
+    }
+    for (tzid, line) in used_zones {
+        if defined_zones.contains(&tzid) {
+            continue;
+        }
+        match tzid.parse::<chrono_tz::Tz>() {
+            Ok(_) => report.warning(
+                Category::Timezone,
+                line,
+                format!(
+                    "TZID '{}' has no VTIMEZONE; most clients resolve IANA names, but RFC 5545 requires one",
+                    tzid
+                ),
+            ),
+            Err(_) => report.error(
+                Category::Timezone,
+                line,
+                format!(
+                    "TZID '{}' has no VTIMEZONE and is not an IANA time zone, so clients cannot place these times",
+                    tzid
+                ),
+            ),
+        }
+    }
+    report.valid = report.errors == 0;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(report: &ValidationReport) -> Vec<(Severity, Option<usize>, &str)> {
+        report
+            .issues
+            .iter()
+            .map(|i| (i.severity, i.line, i.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn accepts_a_well_formed_calendar() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Test//EN\r\nBEGIN:VEVENT\r\nUID:1\r\nDTSTAMP:20260101T000000Z\r\nDTSTART;TZID=Europe/Berlin:20260310T090000\r\nDTEND;TZID=Europe/Berlin:20260310T100000\r\nX-CUSTOM:ok\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let report = validate(ics);
+        assert!(report.valid, "{:?}", report.issues);
+        assert_eq!(report.errors, 0);
+        assert_eq!(report.components["VEVENT"], 1);
+        // The IANA zone without a VTIMEZONE is only a warning.
+        assert_eq!(report.warnings, 1);
+        assert_eq!(report.issues[0].category, Category::Timezone);
+    }
+
+    #[test]
+    fn reports_rfc_violations_with_lines() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:1.0\r\nBEGIN:VEVENT\r\nUID:1\r\nUID:2\r\nDTSTART:20260310T100000Z\r\nDTEND:20260310T090000Z\r\nDTSTAMP:20260101T000000\r\nFOO:bar\r\nDTSTART;TZID=Mars/Olympus:20260310T100000\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let report = validate(ics);
+        assert!(!report.valid);
+        let found = messages(&report);
+        for expected in [
+            (
+                Severity::Error,
+                Some(2),
+                "VERSION is '1.0'; iCalendar files must be 2.0",
+            ),
+            (
+                Severity::Error,
+                Some(5),
+                "UID appears more than once in VEVENT",
+            ),
+            (
+                Severity::Error,
+                Some(8),
+                "DTSTAMP must be a UTC time ending in Z",
+            ),
+            (Severity::Warning, Some(9), "Unknown property FOO in VEVENT"),
+            (
+                Severity::Error,
+                Some(1),
+                "VCALENDAR is missing the required PRODID property",
+            ),
+        ] {
+            assert!(
+                found.contains(&expected),
+                "{:?} not in {:?}",
+                expected,
+                found
+            );
+        }
+        assert!(found.iter().any(|(s, l, m)| *s == Severity::Error
+            && *l == Some(10)
+            && m.contains("'Mars/Olympus' has no VTIMEZONE and is not an IANA")));
+    }
+
+    #[test]
+    fn reports_structure_problems() {
+        let report = validate(
+            "BEGIN:VCALENDAR\nVERSION:2.0\nPRODID:x\nBEGIN:VEVENT\nnot a property\nEND:VTODO\n",
+        );
+        let found = messages(&report);
+        assert!(found.contains(&(Severity::Warning, None, "Lines end with LF instead of CRLF")));
+        assert!(found.contains(&(
+            Severity::Error,
+            Some(5),
+            "'not a property' is not a property (NAME:value)"
+        )));
+        assert!(found.contains(&(
+            Severity::Error,
+            Some(6),
+            "END:VTODO does not close BEGIN:VEVENT from line 4"
+        )));
+        assert!(found.contains(&(Severity::Error, Some(4), "BEGIN:VEVENT is never closed")));
+        assert!(
+            validate("hello")
+                .issues
+                .iter()
+                .any(|i| i.message == "The input has no VCALENDAR")
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::BTreeSet;
+
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::ics_text::split_property;
+
+// Feeds from old devices and exporters can carry QUOTED-PRINTABLE values and
+// vCalendar 1.0 constructs that iCalendar parsers read as garbage. This
+// rewrites them into their RFC 5545 form and reports what it changed, so
+// callers can log it. Anything else passes through untouched.
+pub fn normalize(ics: &str) -> (String, BTreeSet<&'static str>) {
+    let mut fixes = BTreeSet::new();
+    let lines: Vec<&str> = ics.lines().collect();
+    let vcal1 = lines
+        .iter()
+        .any(|l| l.trim().eq_ignore_ascii_case("VERSION:1.0"));
+    let mut out = String::with_capacity(ics.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        let Some((head, value)) = split_property(line) else {
+            push_line(&mut out, line);
+            continue;
+        };
+        let mut params: Vec<&str> = head.split(';').collect();
+        let name = params.remove(0).to_ascii_uppercase();
+
+        let quoted_printable = params.iter().any(|p| {
+            p.eq_ignore_ascii_case("ENCODING=QUOTED-PRINTABLE")
+                || p.eq_ignore_ascii_case("QUOTED-PRINTABLE")
+        });
+        let mut value = value.to_string();
+        if quoted_printable {
+            // Soft line breaks end a line with '=' and continue on the next.
+            while value.ends_with('=') && i < lines.len() {
+                value.pop();
+                let next = lines[i];
+                value.push_str(next.strip_prefix([' ', '\t']).unwrap_or(next));
+                i += 1;
+            }
+            let charset = params
+                .iter()
+                .find_map(|p| {
+                    p.split_once('=')
+                        .filter(|(k, _)| k.eq_ignore_ascii_case("CHARSET"))
+                })
+                .and_then(|(_, v)| Encoding::for_label(v.trim_matches('"').as_bytes()))
+                .unwrap_or(UTF_8);
+            let decoded = charset
+                .decode(&decode_quoted_printable(&value))
+                .0
+                .into_owned();
+            value = decoded.replace("\r\n", "\\n").replace(['\n', '\r'], "\\n");
+            params.retain(|p| {
+                !p.eq_ignore_ascii_case("ENCODING=QUOTED-PRINTABLE")
+                    && !p.eq_ignore_ascii_case("QUOTED-PRINTABLE")
+                    && !p.to_ascii_uppercase().starts_with("CHARSET=")
+            });
+            fixes.insert("decoded QUOTED-PRINTABLE values");
+        }
+
+        let mut name = name.as_str();
+        if vcal1 {
+            match name {
+                "VERSION" => {
+                    value = "2.0".into();
+                    fixes.insert("upgraded VERSION 1.0 to 2.0");
+                }
+                "AALARM" | "DALARM" | "MALARM" | "PALARM" => {
+                    fixes.insert("dropped vCalendar 1.0 alarms");
+                    continue;
+                }
+                "DCREATED" => {
+                    name = "CREATED";
+                    fixes.insert("renamed DCREATED to CREATED");
+                }
+                "STATUS" if value.eq_ignore_ascii_case("NEEDS ACTION") => {
+                    value = "NEEDS-ACTION".into();
+                    fixes.insert("fixed STATUS values");
+                }
+                "RRULE" if !value.contains('=') => match convert_rrule(&value) {
+                    Some(rule) => {
+                        value = rule;
+                        fixes.insert("converted vCalendar 1.0 RRULEs");
+                    }
+                    None => {
+                        fixes.insert("kept unrecognized vCalendar 1.0 RRULEs");
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        if !quoted_printable && !vcal1 {
+            push_line(&mut out, line);
+            continue;
+        }
+        let mut rebuilt = name.to_string();
+        for param in params {
+            rebuilt.push(';');
+            rebuilt.push_str(param);
+        }
+        rebuilt.push(':');
+        rebuilt.push_str(&value);
+        push_line(&mut out, &rebuilt);
+    }
+    if fixes.is_empty() {
+        return (ics.to_string(), fixes);
+    }
+    (out, fixes)
+}
+
+pub fn log_fixes(origin: &str, fixes: &BTreeSet<&'static str>) {
+    if !fixes.is_empty() {
+        let fixes: Vec<_> = fixes.iter().copied().collect();
+        tracing::info!(
+            "Normalized legacy calendar data from {}: {}",
+            origin,
+            fixes.join(", ")
+        );
+    }
+}
+
+fn push_line(out: &mut String, line: &str) {
+    out.push_str(line);
+    out.push_str("\r\n");
+}
+
+fn decode_quoted_printable(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'=', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+// vCalendar 1.0 rules such as `W2 MO WE #10` or `MD1 1 15 20261231T000000Z`:
+// frequency and interval, then modifiers, then `#count` (0 is forever) or an
+// end date. MP rules take occurrences like `1+ MO` or `1- FR`.
+fn convert_rrule(rule: &str) -> Option<String> {
+    let mut tokens: Vec<&str> = rule.split_whitespace().collect();
+    let first = tokens.first()?.to_ascii_uppercase();
+    let (freq, interval) = ["MD", "MP", "YM", "YD", "D", "W"]
+        .iter()
+        .find_map(|code| Some((*code, first.strip_prefix(code)?.parse::<u32>().ok()?)))?;
+    tokens.remove(0);
+
+    let mut end = None;
+    if let Some(last) = tokens.last() {
+        if let Some(count) = last.strip_prefix('#') {
+            end = match count.parse::<u32>().ok()? {
+                0 => None,
+                n => Some(format!("COUNT={}", n)),
+            };
+            tokens.pop();
+        } else if last
+            .get(..8)
+            .is_some_and(|d| d.chars().all(|c| c.is_ascii_digit()))
+        {
+            end = Some(format!("UNTIL={}", last));
+            tokens.pop();
+        }
+    }
+
+    let mut parts = vec![
+        format!(
+            "FREQ={}",
+            match freq {
+                "D" => "DAILY",
+                "W" => "WEEKLY",
+                "MD" | "MP" => "MONTHLY",
+                _ => "YEARLY",
+            }
+        ),
+        format!("INTERVAL={}", interval),
+    ];
+    let is_day = |t: &str| ["MO", "TU", "WE", "TH", "FR", "SA", "SU"].contains(&t);
+    if !tokens.is_empty() {
+        let list = match freq {
+            "W" if tokens.iter().all(|t| is_day(t)) => format!("BYDAY={}", tokens.join(",")),
+            "MD" => {
+                let days: Option<Vec<String>> = tokens
+                    .iter()
+                    .map(|t| match *t {
+                        "LD" => Some("-1".to_string()),
+                        t => t.parse::<u32>().ok().map(|d| d.to_string()),
+                    })
+                    .collect();
+                format!("BYMONTHDAY={}", days?.join(","))
+            }
+            "MP" => {
+                let mut days = Vec::new();
+                let mut occurrence = None;
+                for t in &tokens {
+                    if is_day(t) {
+                        days.push(format!("{}{}", occurrence.as_ref()?, t));
+                    } else if let Some(n) = t.strip_suffix('+') {
+                        occurrence = Some(n.parse::<u32>().ok()?.to_string());
+                    } else {
+                        let n = t.strip_suffix('-')?.parse::<u32>().ok()?;
+                        occurrence = Some(format!("-{}", n));
+                    }
+                }
+                format!("BYDAY={}", days.join(","))
+            }
+            "YM" if tokens.iter().all(|t| t.parse::<u32>().is_ok()) => {
+                format!("BYMONTH={}", tokens.join(","))
+            }
+            "YD" if tokens.iter().all(|t| t.parse::<u32>().is_ok()) => {
+                format!("BYYEARDAY={}", tokens.join(","))
+            }
+            _ => return None,
+        };
+        parts.push(list);
+    }
+    parts.extend(end);
+    Some(parts.join(";"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_quoted_printable_values() {
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY;ENCODING=QUOTED-PRINTABLE;CHARSET=ISO-8859-1:Caf=E9 =\r\nmeeting=0D=0Anotes\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let (out, fixes) = normalize(ics);
+        assert!(out.contains("SUMMARY:Café meeting\\nnotes\r\n"));
+        assert_eq!(
+            fixes.into_iter().collect::<Vec<_>>(),
+            ["decoded QUOTED-PRINTABLE values"]
+        );
+    }
+
+    #[test]
+    fn upgrades_vcalendar_1_constructs() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:1.0\r\nBEGIN:VEVENT\r\nDCREATED:20260101T000000\r\nRRULE:W1 MO WE #10\r\nAALARM:20260101T090000\r\nSTATUS:NEEDS ACTION\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let (out, _) = normalize(ics);
+        assert!(out.contains("VERSION:2.0\r\n"));
+        assert!(out.contains("CREATED:20260101T000000\r\n"));
+        assert!(out.contains("RRULE:FREQ=WEEKLY;INTERVAL=1;BYDAY=MO,WE;COUNT=10\r\n"));
+        assert!(out.contains("STATUS:NEEDS-ACTION\r\n"));
+        assert!(!out.contains("AALARM"));
+    }
+
+    #[test]
+    fn converts_vcalendar_1_rules() {
+        assert_eq!(
+            convert_rrule("D2 #0").as_deref(),
+            Some("FREQ=DAILY;INTERVAL=2")
+        );
+        assert_eq!(
+            convert_rrule("MP1 1+ MO 1- FR 20261231T000000Z").as_deref(),
+            Some("FREQ=MONTHLY;INTERVAL=1;BYDAY=1MO,-1FR;UNTIL=20261231T000000Z")
+        );
+        assert_eq!(
+            convert_rrule("MD1 1 LD").as_deref(),
+            Some("FREQ=MONTHLY;INTERVAL=1;BYMONTHDAY=1,-1")
+        );
+        assert_eq!(convert_rrule("X1 #2"), None);
+    }
+
+    #[test]
+    fn leaves_modern_feeds_alone() {
+        let ics = "BEGIN:VCALENDAR\nVERSION:2.0\nEND:VCALENDAR";
+        let (out, fixes) = normalize(ics);
+        assert_eq!(out, ics);
+        assert!(fixes.is_empty());
+    }
+}
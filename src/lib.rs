@@ -1,5 +1,40 @@
 pub mod api;
 pub mod auto_sync;
+pub mod availability;
+pub mod birthdays;
+pub mod calendar_routes;
+pub mod circuit_breaker;
 pub mod config;
+pub mod config_transfer;
+pub mod credentials;
+pub mod csv_events;
+pub mod dav_urls;
 pub mod db;
+pub mod doctor;
+pub mod event_index;
+pub mod event_template;
+pub mod feed_diff;
+pub mod feed_limits;
+pub mod feed_metadata;
+pub mod feed_store;
+pub mod feed_urls;
+pub mod holidays;
+pub mod http_clients;
+pub mod ics_component;
+pub mod ics_repair;
+pub mod ics_text;
+pub mod ics_validation;
+pub mod legacy_ics;
+pub mod maintenance;
+pub mod mock_caldav;
+pub mod recurrence;
+pub mod redact;
+pub mod scheduling;
 pub mod server;
+pub mod sync_origin;
+pub mod sync_progress;
+pub mod sync_window;
+pub mod throttle;
+pub mod time_shift;
+pub mod units;
+pub mod validation;
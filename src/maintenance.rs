@@ -0,0 +1,123 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use rusqlite::{Connection, params};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{db, feed_store};
+
+// Housekeeping for instances that run for months: the database keeps a row
+// of feed usage per user and day, expired login sessions pile up between
+// logins, and deleted sources leave feed files behind. Every
+// MAINTENANCE_INTERVAL (0 turns it off) this prunes them, keeping
+// MAINTENANCE_RETENTION of usage history, then VACUUMs the database and
+// truncates the WAL so the freed pages go back to the filesystem.
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    pub interval_secs: i64,
+    pub retention_secs: i64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            interval_secs: 24 * 3600,
+            retention_secs: 90 * 24 * 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MaintenanceReport {
+    pub usage_rows_pruned: usize,
+    pub sessions_pruned: usize,
+    pub feed_files_removed: usize,
+    // Database plus WAL, before and after.
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub bytes_reclaimed: u64,
+    pub duration_ms: u64,
+    pub finished_at: String,
+}
+
+static LAST_REPORT: Mutex<Option<MaintenanceReport>> = Mutex::new(None);
+
+pub fn last_report() -> Option<MaintenanceReport> {
+    LAST_REPORT.lock().ok().and_then(|r| r.clone())
+}
+
+fn size(conn: &Connection) -> Result<u64> {
+    let pages: u64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+    let page_size: u64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+    let wal = conn
+        .path()
+        .filter(|p| !p.is_empty())
+        .and_then(|p| std::fs::metadata(format!("{}-wal", p)).ok())
+        .map_or(0, |m| m.len());
+    Ok(pages * page_size + wal)
+}
+
+pub fn run(conn: &Connection, settings: &Settings) -> Result<MaintenanceReport> {
+    let started = Instant::now();
+    let bytes_before = size(conn)?;
+    let usage_rows_pruned = conn.execute(
+        "DELETE FROM feed_usage WHERE day < date('now', ?1)",
+        params![format!("-{} seconds", settings.retention_secs.max(0))],
+    )?;
+    let sessions_pruned = conn.execute(
+        "DELETE FROM sessions WHERE expires_at <= datetime('now')",
+        [],
+    )?;
+    let feed_files_removed = feed_store::prune(&db::referenced_feed_files(conn)?)?;
+    conn.execute_batch("VACUUM")?;
+    // VACUUM goes through the WAL in WAL mode; without one this is a no-op.
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    let bytes_after = size(conn)?;
+    let report = MaintenanceReport {
+        usage_rows_pruned,
+        sessions_pruned,
+        feed_files_removed,
+        bytes_before,
+        bytes_after,
+        bytes_reclaimed: bytes_before.saturating_sub(bytes_after),
+        duration_ms: started.elapsed().as_millis() as u64,
+        finished_at: chrono::Utc::now().to_rfc3339(),
+    };
+    if let Ok(mut last) = LAST_REPORT.lock() {
+        *last = Some(report.clone());
+    }
+    Ok(report)
+}
+
+// Runs `run` off the async runtime, as VACUUM can take a while on a large
+// database. Requests wait for the connection meanwhile.
+pub async fn run_blocking(
+    db: Arc<Mutex<Connection>>,
+    settings: Settings,
+) -> Result<MaintenanceReport> {
+    tokio::task::spawn_blocking(move || run(&db.lock().unwrap(), &settings)).await?
+}
+
+pub fn spawn(db: Arc<Mutex<Connection>>, settings: Settings) {
+    if settings.interval_secs <= 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let interval = Duration::from_secs(settings.interval_secs as u64);
+        loop {
+            tokio::time::sleep(interval).await;
+            match run_blocking(db.clone(), settings).await {
+                Ok(report) => tracing::info!(
+                    "Maintenance pruned {} usage rows, {} sessions and {} feed files; reclaimed {} bytes",
+                    report.usage_rows_pruned,
+                    report.sessions_pruned,
+                    report.feed_files_removed,
+                    report.bytes_reclaimed
+                ),
+                Err(e) => tracing::warn!("Maintenance failed: {}", e),
+            }
+        }
+    });
+}
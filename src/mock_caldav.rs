@@ -0,0 +1,449 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use axum::{
+    Router,
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use chrono::{Datelike, Days, NaiveDate};
+
+use crate::db::{self, CreateSource};
+use crate::server::caldav::xml_escape;
+
+// A small in-memory CalDAV server: enough of RFC 4791 for discovery,
+// calendar-query and calendar-multiget, and object GET/HEAD/PUT/DELETE with ETags.
+// It backs demo mode and lets tests run the whole sync pipeline without an
+// external server.
+
+pub const DEMO_USERNAME: &str = "demo";
+pub const DEMO_PASSWORD: &str = "demo";
+pub const DEMO_ICS_PATH: &str = "demo";
+const MAX_BODY: usize = 10 * 1024 * 1024;
+
+struct MockCalendar {
+    name: String,
+    color: String,
+    // Object href -> (ETag, calendar object).
+    objects: BTreeMap<String, (String, String)>,
+}
+
+struct Inner {
+    username: String,
+    password: String,
+    // Collection href, ending in `/` -> calendar.
+    calendars: Mutex<BTreeMap<String, MockCalendar>>,
+    // Answer PUTs without If-Match or If-None-Match with 412.
+    require_preconditions: AtomicBool,
+}
+
+#[derive(Clone)]
+pub struct MockCalDav {
+    inner: Arc<Inner>,
+}
+
+fn etag(ics: &str) -> String {
+    format!("\"{}\"", &db::content_hash(ics)[..16])
+}
+
+fn wrap(vevent: &str) -> String {
+    format!(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//CalDAV/ICS Sync//Demo//EN\r\n{}END:VCALENDAR\r\n",
+        vevent
+    )
+}
+
+impl MockCalDav {
+    pub fn new(username: &str, password: &str) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                username: username.to_string(),
+                password: password.to_string(),
+                calendars: Mutex::new(BTreeMap::new()),
+                require_preconditions: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    pub fn home(&self) -> String {
+        format!("/dav/calendars/{}/", self.inner.username)
+    }
+
+    // Adds a calendar at `{home}{slug}/` holding one object per VEVENT,
+    // named after its UID.
+    pub fn with_calendar(self, slug: &str, name: &str, color: &str, vevents: &[String]) -> Self {
+        let href = format!("{}{}/", self.home(), slug);
+        let objects = vevents
+            .iter()
+            .map(|vevent| {
+                let uid = crate::api::sync::event_uid(vevent).unwrap_or_default();
+                let ics = wrap(vevent);
+                (format!("{}{}.ics", href, uid), (etag(&ics), ics))
+            })
+            .collect();
+        self.inner.calendars.lock().unwrap().insert(
+            href,
+            MockCalendar {
+                name: name.to_string(),
+                color: color.to_string(),
+                objects,
+            },
+        );
+        self
+    }
+
+    // Rejects blind overwrites, as some servers do.
+    pub fn requiring_preconditions(self) -> Self {
+        self.inner
+            .require_preconditions
+            .store(true, Ordering::Relaxed);
+        self
+    }
+
+    // Personal and work calendars with a week or two of events around `today`.
+    pub fn sample(today: NaiveDate) -> Self {
+        let monday = today - Days::new(today.weekday().num_days_from_monday() as u64);
+        let saturday = monday + Days::new(5);
+        let day = |offset: u64| (today + Days::new(offset)).format("%Y%m%d").to_string();
+        let event = |uid: &str, body: String| {
+            format!(
+                "BEGIN:VEVENT\r\nUID:{}@demo.caldav-ics-sync\r\nDTSTAMP:{}T000000Z\r\n{}END:VEVENT\r\n",
+                uid,
+                day(0),
+                body
+            )
+        };
+        let work = [
+            event(
+                "standup",
+                format!(
+                    "DTSTART;TZID=Europe/Berlin:{}T091500\r\nDURATION:PT15M\r\nRRULE:FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR\r\nSUMMARY:Team standup\r\n",
+                    monday.format("%Y%m%d")
+                ),
+            ),
+            event(
+                "review",
+                format!(
+                    "DTSTART:{0}T130000Z\r\nDTEND:{0}T140000Z\r\nSUMMARY:Sprint review\r\nLOCATION:Room 2\r\nORGANIZER:mailto:lead@example.com\r\nATTENDEE:mailto:demo@example.com\r\n",
+                    day(3)
+                ),
+            ),
+            event(
+                "planning",
+                format!(
+                    "DTSTART;VALUE=DATE:{}\r\nDTEND;VALUE=DATE:{}\r\nSUMMARY:Quarterly planning\r\n",
+                    day(10),
+                    day(11)
+                ),
+            ),
+        ];
+        let personal = [
+            event(
+                "dentist",
+                format!(
+                    "DTSTART:{0}T073000Z\r\nDTEND:{0}T081500Z\r\nSUMMARY:Dentist\r\nLOCATION:Main Street 12\r\n",
+                    day(1)
+                ),
+            ),
+            event(
+                "dinner",
+                format!(
+                    "DTSTART:{0}T180000Z\r\nDTEND:{0}T203000Z\r\nSUMMARY:Dinner with friends\r\n",
+                    day(2)
+                ),
+            ),
+            event(
+                "trip",
+                format!(
+                    "DTSTART;VALUE=DATE:{}\r\nDTEND;VALUE=DATE:{}\r\nSUMMARY:Weekend trip\r\nTRANSP:TRANSPARENT\r\n",
+                    (saturday + Days::new(7)).format("%Y%m%d"),
+                    (saturday + Days::new(9)).format("%Y%m%d")
+                ),
+            ),
+        ];
+        Self::new(DEMO_USERNAME, DEMO_PASSWORD)
+            .with_calendar("personal", "Personal", "#3A87ADFF", &personal)
+            .with_calendar("work", "Work", "#E67E22FF", &work)
+    }
+
+    // The calendar objects of the collection at `href`, for assertions.
+    pub fn objects(&self, href: &str) -> Vec<String> {
+        let calendars = self.inner.calendars.lock().unwrap();
+        calendars
+            .get(href)
+            .map(|c| c.objects.values().map(|(_, ics)| ics.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn router(&self) -> Router {
+        Router::new()
+            .fallback(handle)
+            .with_state(self.inner.clone())
+    }
+
+    // Serves on a free port of 127.0.0.1 until the runtime shuts down.
+    pub async fn spawn(&self) -> Result<SocketAddr> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let router = self.router();
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router).await {
+                tracing::error!("Mock CalDAV server stopped: {}", e);
+            }
+        });
+        Ok(addr)
+    }
+}
+
+// The declared source demo mode syncs from the sample server at `addr`.
+pub fn demo_source(addr: SocketAddr) -> CreateSource {
+    CreateSource {
+        name: "Demo calendars".into(),
+        caldav_url: format!("http://{}/dav/calendars/{}/", addr, DEMO_USERNAME),
+        username: DEMO_USERNAME.into(),
+        password: DEMO_PASSWORD.into(),
+        ics_path: DEMO_ICS_PATH.into(),
+        sync_interval_secs: 3600,
+        ..Default::default()
+    }
+}
+
+fn authorized(inner: &Inner, headers: &HeaderMap) -> bool {
+    let expected = base64::engine::general_purpose::STANDARD
+        .encode(format!("{}:{}", inner.username, inner.password));
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Basic "))
+        .is_some_and(|v| v.trim() == expected)
+}
+
+fn multistatus(responses: &str) -> Response {
+    Response::builder()
+        .status(StatusCode::MULTI_STATUS)
+        .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+        .body(Body::from(format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav" xmlns:a="http://apple.com/ns/ical/">
+{}</d:multistatus>"#,
+            responses
+        )))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+fn prop_response(href: &str, props: &str) -> String {
+    format!(
+        "<d:response><d:href>{}</d:href><d:propstat><d:prop>{}</d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>\n",
+        xml_escape(href),
+        props
+    )
+}
+
+fn calendar_props(calendar: &MockCalendar) -> String {
+    format!(
+        "<d:resourcetype><d:collection/><c:calendar/></d:resourcetype><d:displayname>{}</d:displayname><a:calendar-color>{}</a:calendar-color><c:supported-calendar-component-set><c:comp name=\"VEVENT\"/></c:supported-calendar-component-set>",
+        xml_escape(&calendar.name),
+        xml_escape(&calendar.color)
+    )
+}
+
+fn object_props(etag: &str, ics: Option<&str>) -> String {
+    let data = ics
+        .map(|ics| format!("<c:calendar-data>{}</c:calendar-data>", xml_escape(ics)))
+        .unwrap_or_default();
+    format!(
+        "<d:resourcetype/><d:getetag>{}</d:getetag><d:getcontenttype>text/calendar; charset=utf-8</d:getcontenttype>{}",
+        xml_escape(etag),
+        data
+    )
+}
+
+fn status(code: StatusCode) -> Response {
+    (code, "").into_response()
+}
+
+fn precondition_failed(headers: &HeaderMap, current: Option<&str>) -> bool {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|v: &header::HeaderValue| v.to_str().ok())
+    };
+    match (header(header::IF_MATCH), header(header::IF_NONE_MATCH)) {
+        (Some(tag), _) => current != Some(tag.trim()),
+        (_, Some("*")) => current.is_some(),
+        _ => false,
+    }
+}
+
+async fn handle(State(inner): State<Arc<Inner>>, req: Request) -> Response {
+    if !authorized(&inner, req.headers()) {
+        return Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header(header::WWW_AUTHENTICATE, "Basic realm=\"mock-caldav\"")
+            .body(Body::empty())
+            .unwrap_or_else(|_| StatusCode::UNAUTHORIZED.into_response());
+    }
+    let method = req.method().as_str().to_owned();
+    let headers = req.headers().clone();
+    let path = req.uri().path().to_owned();
+    let depth = headers
+        .get("depth")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("0")
+        .to_owned();
+    let Ok(body) = axum::body::to_bytes(req.into_body(), MAX_BODY).await else {
+        return status(StatusCode::PAYLOAD_TOO_LARGE);
+    };
+    let body = String::from_utf8_lossy(&body);
+    let home = format!("/dav/calendars/{}/", inner.username);
+    let collection = format!("{}/", path.trim_end_matches('/'));
+    let mut calendars = inner.calendars.lock().unwrap();
+
+    match method.as_str() {
+        "OPTIONS" => Response::builder()
+            .status(StatusCode::OK)
+            .header("DAV", "1, 2, calendar-access")
+            .header(
+                header::ALLOW,
+                "OPTIONS, PROPFIND, REPORT, GET, HEAD, PUT, DELETE, MKCALENDAR",
+            )
+            .body(Body::empty())
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        "PROPFIND" if collection == home => {
+            let mut out = prop_response(&home, "<d:resourcetype><d:collection/></d:resourcetype>");
+            if depth != "0" {
+                for (href, calendar) in calendars.iter() {
+                    out.push_str(&prop_response(href, &calendar_props(calendar)));
+                }
+            }
+            multistatus(&out)
+        }
+        "PROPFIND" => {
+            if let Some(calendar) = calendars.get(&collection) {
+                let mut out = prop_response(&collection, &calendar_props(calendar));
+                if depth != "0" {
+                    for (href, (etag, _)) in &calendar.objects {
+                        out.push_str(&prop_response(href, &object_props(etag, None)));
+                    }
+                }
+                return multistatus(&out);
+            }
+            let object = calendars
+                .values()
+                .find_map(|c| c.objects.get(&path).map(|(etag, _)| etag.clone()));
+            match object {
+                Some(etag) => multistatus(&prop_response(&path, &object_props(&etag, None))),
+                None => status(StatusCode::NOT_FOUND),
+            }
+        }
+        "REPORT" => {
+            let Some(calendar) = calendars.get(&collection) else {
+                return status(StatusCode::NOT_FOUND);
+            };
+            let mut out = String::new();
+            if body.contains("calendar-multiget") {
+                let Ok(doc) = roxmltree::Document::parse(&body) else {
+                    return status(StatusCode::BAD_REQUEST);
+                };
+                for href in doc
+                    .descendants()
+                    .filter(|n| n.has_tag_name(("DAV:", "href")))
+                    .filter_map(|n| n.text())
+                    .map(str::trim)
+                {
+                    match calendar.objects.get(href) {
+                        Some((etag, ics)) => {
+                            out.push_str(&prop_response(href, &object_props(etag, Some(ics))))
+                        }
+                        None => out.push_str(&format!(
+                            "<d:response><d:href>{}</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>\n",
+                            xml_escape(href)
+                        )),
+                    }
+                }
+            } else {
+                for (href, (etag, ics)) in &calendar.objects {
+                    out.push_str(&prop_response(href, &object_props(etag, Some(ics))));
+                }
+            }
+            multistatus(&out)
+        }
+        "MKCALENDAR" => {
+            if calendars.contains_key(&collection) {
+                return status(StatusCode::METHOD_NOT_ALLOWED);
+            }
+            let name = collection
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            calendars.insert(
+                collection,
+                MockCalendar {
+                    name,
+                    color: String::new(),
+                    objects: BTreeMap::new(),
+                },
+            );
+            status(StatusCode::CREATED)
+        }
+        "GET" | "HEAD" | "PUT" | "DELETE" => {
+            let Some((parent, _)) = path.rsplit_once('/') else {
+                return status(StatusCode::NOT_FOUND);
+            };
+            let Some(calendar) = calendars.get_mut(&format!("{}/", parent)) else {
+                return status(StatusCode::NOT_FOUND);
+            };
+            let current = calendar.objects.get(&path).map(|(etag, _)| etag.clone());
+            let unguarded = method == "PUT"
+                && inner.require_preconditions.load(Ordering::Relaxed)
+                && !headers.contains_key(header::IF_MATCH)
+                && !headers.contains_key(header::IF_NONE_MATCH);
+            if unguarded || precondition_failed(&headers, current.as_deref()) {
+                return status(StatusCode::PRECONDITION_FAILED);
+            }
+            match (method.as_str(), calendar.objects.get(&path)) {
+                ("GET" | "HEAD", Some((etag, ics))) => Response::builder()
+                    .status(StatusCode::OK)
+                    .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
+                    .header(header::ETAG, etag)
+                    .body(match method.as_str() {
+                        "HEAD" => Body::empty(),
+                        _ => Body::from(ics.clone()),
+                    })
+                    .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+                ("PUT", existing) => {
+                    if !body.trim_start().starts_with("BEGIN:VCALENDAR") {
+                        return status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+                    }
+                    let code = match existing {
+                        Some(_) => StatusCode::NO_CONTENT,
+                        None => StatusCode::CREATED,
+                    };
+                    let tag = etag(&body);
+                    calendar
+                        .objects
+                        .insert(path, (tag.clone(), body.into_owned()));
+                    Response::builder()
+                        .status(code)
+                        .header(header::ETAG, tag)
+                        .body(Body::empty())
+                        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+                }
+                ("DELETE", Some(_)) => {
+                    calendar.objects.remove(&path);
+                    status(StatusCode::NO_CONTENT)
+                }
+                _ => status(StatusCode::NOT_FOUND),
+            }
+        }
+        _ => status(StatusCode::METHOD_NOT_ALLOWED),
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::HashSet;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Weekday};
+
+use crate::event_index::{self, IndexedEvent};
+use crate::ics_component::{self, Part};
+use crate::ics_text::split_property;
+
+// Expands recurring events for pages that list what happens in a time range.
+// RRULEs with FREQ DAILY, WEEKLY, MONTHLY or YEARLY and INTERVAL, COUNT,
+// UNTIL, BYMONTH, BYMONTHDAY and BYDAY are expanded in the DTSTART time zone,
+// so a weekly 09:00 meeting stays at 09:00 across DST. EXDATEs are skipped
+// and overrides (RECURRENCE-ID) replace the occurrence they move. Series with
+// other rules only count at DTSTART.
+
+// Enough for a daily series started in 1900.
+const MAX_PERIODS: i64 = 60_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Freq {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug)]
+struct Rule {
+    freq: Freq,
+    interval: i64,
+    count: Option<usize>,
+    until: Option<NaiveDateTime>,
+    by_month: Vec<u32>,
+    by_month_day: Vec<i32>,
+    by_day: Vec<(Option<i32>, Weekday)>,
+}
+
+fn weekday(code: &str) -> Option<Weekday> {
+    Some(match code {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+// `FREQ=MONTHLY;BYDAY=-1FR` -> the rule; None for rules this module can't expand.
+fn parse_rule(value: &str, tzid: Option<&str>) -> Option<Rule> {
+    let mut rule = Rule {
+        freq: Freq::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+        by_month: Vec::new(),
+        by_month_day: Vec::new(),
+        by_day: Vec::new(),
+    };
+    let mut freq = None;
+    for part in value.trim().split(';').filter(|p| !p.is_empty()) {
+        let (key, value) = part.split_once('=')?;
+        let list = || value.split(',').map(str::trim);
+        match key.trim().to_ascii_uppercase().as_str() {
+            "FREQ" => {
+                freq = Some(match value.trim().to_ascii_uppercase().as_str() {
+                    "DAILY" => Freq::Daily,
+                    "WEEKLY" => Freq::Weekly,
+                    "MONTHLY" => Freq::Monthly,
+                    "YEARLY" => Freq::Yearly,
+                    _ => return None,
+                })
+            }
+            "INTERVAL" => rule.interval = value.trim().parse().ok().filter(|i| *i > 0)?,
+            "COUNT" => rule.count = Some(value.trim().parse().ok()?),
+            "UNTIL" => rule.until = Some(event_index::parse_datetime(value, tzid)?),
+            "BYMONTH" => rule.by_month = list().map(|m| m.parse().ok()).collect::<Option<_>>()?,
+            "BYMONTHDAY" => {
+                rule.by_month_day = list().map(|d| d.parse().ok()).collect::<Option<_>>()?
+            }
+            "BYDAY" => {
+                rule.by_day = list()
+                    .map(|d| {
+                        let d = d.to_ascii_uppercase();
+                        let (n, code) = d.split_at(d.len().checked_sub(2)?);
+                        let n = match n {
+                            "" => None,
+                            n => Some(n.trim_start_matches('+').parse().ok()?),
+                        };
+                        Some((n, weekday(code)?))
+                    })
+                    .collect::<Option<_>>()?
+            }
+            "WKST" => {}
+            _ => return None,
+        }
+    }
+    rule.freq = freq?;
+    Some(rule)
+}
+
+fn last_of_month(first: NaiveDate) -> Option<NaiveDate> {
+    first.checked_add_months(chrono::Months::new(1))?.pred_opt()
+}
+
+// The days from `first` through `last` that BYMONTHDAY and BYDAY pick, or
+// `default_day` of the month when the rule has neither.
+fn pick_days(rule: &Rule, first: NaiveDate, last: NaiveDate, default_day: u32) -> Vec<NaiveDate> {
+    if rule.by_day.is_empty() && rule.by_month_day.is_empty() {
+        return NaiveDate::from_ymd_opt(first.year(), first.month(), default_day)
+            .into_iter()
+            .collect();
+    }
+    let by_month_day: Vec<NaiveDate> = rule
+        .by_month_day
+        .iter()
+        .filter_map(|n| match *n {
+            n if n > 0 => first.checked_add_days(chrono::Days::new(n as u64 - 1)),
+            n if n < 0 => last.checked_sub_days(chrono::Days::new((-n) as u64 - 1)),
+            _ => None,
+        })
+        .filter(|d| (first..=last).contains(d))
+        .collect();
+    let mut by_day = Vec::new();
+    for (n, wd) in &rule.by_day {
+        let matching: Vec<NaiveDate> = first
+            .iter_days()
+            .take_while(|d| *d <= last)
+            .filter(|d| d.weekday() == *wd)
+            .collect();
+        match n {
+            None => by_day.extend(matching),
+            Some(n) if *n > 0 => by_day.extend(matching.get(*n as usize - 1)),
+            Some(n) => by_day.extend(
+                matching
+                    .len()
+                    .checked_sub(n.unsigned_abs() as usize)
+                    .map(|i| matching[i]),
+            ),
+        }
+    }
+    match (rule.by_day.is_empty(), rule.by_month_day.is_empty()) {
+        (true, _) => by_month_day,
+        (_, true) => by_day,
+        _ => by_day
+            .into_iter()
+            .filter(|d| by_month_day.contains(d))
+            .collect(),
+    }
+}
+
+// Candidate dates of the `period`th day, week, month or year of the series,
+// sorted; None once past the calendar's range.
+fn dates_in(rule: &Rule, start: NaiveDate, period: i64) -> Option<Vec<NaiveDate>> {
+    let step = period.checked_mul(rule.interval)?;
+    let mut dates = match rule.freq {
+        Freq::Daily => {
+            let day = start.checked_add_signed(Duration::try_days(step)?)?;
+            let weekday_ok =
+                rule.by_day.is_empty() || rule.by_day.iter().any(|(_, wd)| *wd == day.weekday());
+            let month_day_ok = rule.by_month_day.is_empty()
+                || pick_days(rule, day.with_day(1)?, last_of_month(day.with_day(1)?)?, 0)
+                    .contains(&day);
+            match weekday_ok && month_day_ok {
+                true => vec![day],
+                false => Vec::new(),
+            }
+        }
+        Freq::Weekly => {
+            let week = start
+                .checked_sub_signed(Duration::days(start.weekday().num_days_from_monday() as i64))?
+                .checked_add_signed(Duration::try_weeks(step)?)?;
+            match rule.by_day.is_empty() {
+                true => vec![week.checked_add_signed(Duration::days(
+                    start.weekday().num_days_from_monday() as i64,
+                ))?],
+                false => rule
+                    .by_day
+                    .iter()
+                    .filter_map(|(_, wd)| {
+                        week.checked_add_signed(Duration::days(wd.num_days_from_monday() as i64))
+                    })
+                    .collect(),
+            }
+        }
+        Freq::Monthly => {
+            let first = start
+                .with_day(1)?
+                .checked_add_months(chrono::Months::new(u32::try_from(step).ok()?))?;
+            pick_days(rule, first, last_of_month(first)?, start.day())
+        }
+        Freq::Yearly => {
+            let year = start.year().checked_add(i32::try_from(step).ok()?)?;
+            if rule.by_month.is_empty() && !rule.by_day.is_empty() {
+                let first = NaiveDate::from_ymd_opt(year, 1, 1)?;
+                pick_days(rule, first, NaiveDate::from_ymd_opt(year, 12, 31)?, 0)
+            } else {
+                let months = match rule.by_month.is_empty() {
+                    true => vec![start.month()],
+                    false => rule.by_month.clone(),
+                };
+                let mut dates = Vec::new();
+                for month in months {
+                    if let Some(first) = NaiveDate::from_ymd_opt(year, month, 1) {
+                        dates.extend(pick_days(rule, first, last_of_month(first)?, start.day()));
+                    }
+                }
+                dates
+            }
+        }
+    };
+    if !rule.by_month.is_empty() {
+        dates.retain(|d| rule.by_month.contains(&d.month()));
+    }
+    dates.sort();
+    dates.dedup();
+    Some(dates)
+}
+
+// The parameters' TZID and the value of each own `name` property.
+fn properties(vevent: &str, name: &str) -> Vec<(Option<String>, String)> {
+    ics_component::parts(vevent)
+        .into_iter()
+        .filter_map(|part| match part {
+            Part::Property { name: n, raw } if n == name => {
+                let line = ics_component::unfold(raw);
+                let (params, value) = split_property(&line)?;
+                let tzid = params
+                    .split(';')
+                    .find_map(|p| p.strip_prefix("TZID="))
+                    .map(str::to_owned);
+                Some((tzid, value.trim().to_owned()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// Every UTC instant listed in the own `name` properties, such as EXDATE.
+fn instants(vevent: &str, name: &str) -> Vec<NaiveDateTime> {
+    properties(vevent, name)
+        .iter()
+        .flat_map(|(tzid, value)| {
+            value
+                .split(',')
+                .filter_map(|v| event_index::parse_datetime(v, tzid.as_deref()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+// UTC starts of the occurrences of `vevent` lasting `length` that overlap
+// [from, to), apart from those in `skip`. None if it has no RRULE this
+// module can expand.
+fn occurrences(
+    vevent: &str,
+    length: Duration,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+    skip: &[NaiveDateTime],
+) -> Option<Vec<NaiveDateTime>> {
+    let (tzid, dtstart) = properties(vevent, "DTSTART").into_iter().next()?;
+    let (_, rrule) = properties(vevent, "RRULE").into_iter().next()?;
+    let rule = parse_rule(&rrule, tzid.as_deref())?;
+    // Expanded on the wall clock, then converted like DTSTART itself.
+    let local = event_index::parse_datetime(dtstart.trim_end_matches('Z'), None)?;
+    let zone = match dtstart.len() {
+        15 => tzid.and_then(|t| t.parse::<chrono_tz::Tz>().ok()),
+        _ => None,
+    };
+    let utc = |t: NaiveDateTime| match zone {
+        Some(tz) => tz
+            .from_local_datetime(&t)
+            .earliest()
+            .map(|dt| dt.naive_utc())
+            .unwrap_or(t),
+        None => t,
+    };
+    let mut found = Vec::new();
+    let mut seen = 0;
+    for period in 0..MAX_PERIODS {
+        let Some(dates) = dates_in(&rule, local.date(), period) else {
+            break;
+        };
+        for date in dates {
+            let at = date.and_time(local.time());
+            if at < local {
+                continue;
+            }
+            let start = utc(at);
+            if start >= to || rule.until.is_some_and(|until| start > until) {
+                return Some(found);
+            }
+            seen += 1;
+            if rule.count.is_some_and(|count| seen > count) {
+                return Some(found);
+            }
+            if (start >= from || start + length > from) && !skip.contains(&start) {
+                found.push(start);
+            }
+        }
+    }
+    Some(found)
+}
+
+// The events of `ics` that overlap [from, to), with recurring series
+// expanded to one event per occurrence. Times are UTC.
+pub fn expand(ics: &str, from: NaiveDateTime, to: NaiveDateTime) -> Vec<IndexedEvent> {
+    let vevents: Vec<&str> = ics_component::parts(ics)
+        .into_iter()
+        .filter_map(|part| match part {
+            Part::Component { name, raw } if name == "VEVENT" => Some(raw),
+            _ => None,
+        })
+        .collect();
+    let moved: HashSet<(String, NaiveDateTime)> = vevents
+        .iter()
+        .filter_map(|vevent| {
+            let uid = ics_component::value(vevent, "UID")?;
+            let at = *instants(vevent, "RECURRENCE-ID").first()?;
+            Some((uid, at))
+        })
+        .collect();
+
+    let mut out = Vec::new();
+    for vevent in vevents {
+        let Some(event) = event_index::index(vevent).into_iter().next() else {
+            continue;
+        };
+        let Some(start) = event.starts_at else {
+            continue;
+        };
+        let length = event.ends_at.map_or(Duration::zero(), |end| end - start);
+        let series = match ics_component::has_property(vevent, "RECURRENCE-ID") {
+            true => None,
+            false => {
+                let mut skip = instants(vevent, "EXDATE");
+                if let Some(uid) = &event.uid {
+                    skip.extend(moved.iter().filter(|(u, _)| u == uid).map(|(_, at)| *at));
+                }
+                occurrences(vevent, length, from, to, &skip)
+            }
+        };
+        match series {
+            Some(starts) => out.extend(starts.into_iter().map(|at| IndexedEvent {
+                starts_at: Some(at),
+                ends_at: event.ends_at.map(|_| at + length),
+                ..event.clone()
+            })),
+            None => {
+                let overlaps = start < to && event.ends_at.map_or(start >= from, |end| end > from);
+                if overlaps {
+                    out.push(event);
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, event_index::TIMESTAMP_FORMAT).unwrap()
+    }
+
+    fn starts(ics: &str, from: &str, to: &str) -> Vec<(String, String)> {
+        let mut events: Vec<_> = expand(ics, at(from), at(to))
+            .into_iter()
+            .map(|e| (e.uid.unwrap(), e.starts_at.unwrap().to_string()))
+            .collect();
+        events.sort_by(|a, b| (&a.1, &a.0).cmp(&(&b.1, &b.0)));
+        events
+    }
+
+    #[test]
+    fn expands_series_within_the_range() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\nUID:standup\r\nDTSTART;TZID=Europe/Berlin:20260105T091500\r\n\
+            DURATION:PT15M\r\nRRULE:FREQ=WEEKLY;BYDAY=MO,WE\r\n\
+            EXDATE;TZID=Europe/Berlin:20260325T091500\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:standup\r\nRECURRENCE-ID;TZID=Europe/Berlin:20260330T091500\r\n\
+            DTSTART;TZID=Europe/Berlin:20260330T110000\r\nDURATION:PT15M\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:review\r\nDTSTART:20250131T150000Z\r\n\
+            RRULE:FREQ=MONTHLY;BYDAY=-1FR;COUNT=15\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:leap\r\nDTSTART;VALUE=DATE:20000229\r\n\
+            RRULE:FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=-1\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:once\r\nDTSTART:20260320T100000Z\r\nEND:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        assert_eq!(
+            starts(ics, "2026-02-28 00:00:00", "2026-04-02 00:00:00"),
+            [
+                ("leap", "2026-02-28 00:00:00"),
+                ("standup", "2026-03-02 08:15:00"),
+                ("standup", "2026-03-04 08:15:00"),
+                ("standup", "2026-03-09 08:15:00"),
+                ("standup", "2026-03-11 08:15:00"),
+                ("standup", "2026-03-16 08:15:00"),
+                ("standup", "2026-03-18 08:15:00"),
+                ("once", "2026-03-20 10:00:00"),
+                ("standup", "2026-03-23 08:15:00"),
+                ("review", "2026-03-27 15:00:00"),
+                // After the switch to summer time, still 09:15 in Berlin.
+                ("standup", "2026-03-30 09:00:00"),
+                ("standup", "2026-04-01 07:15:00"),
+            ]
+            .map(|(uid, start)| (uid.to_string(), start.to_string()))
+        );
+        // COUNT=15 ends the review series in March 2026.
+        assert!(
+            starts(ics, "2026-04-02 00:00:00", "2026-06-01 00:00:00")
+                .iter()
+                .all(|(uid, _)| uid == "standup")
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::{Result, ensure};
+
+use crate::feed_metadata::fold;
+use crate::ics_component;
+use crate::ics_text::split_property;
+
+// Servers that do implicit scheduling (Zimbra, Exchange, many CalDAV servers
+// following RFC 6638) email every ATTENDEE of an uploaded event. A
+// destination's `scheduling` decides what pushed events carry:
+//
+// - `keep` uploads them as they are.
+// - `strip` removes ATTENDEE and ORGANIZER.
+// - `client` keeps them, marked SCHEDULE-AGENT=CLIENT so the server leaves
+//   scheduling to the client, and without RSVP requests.
+pub const MODES: &[&str] = &["keep", "strip", "client"];
+const PROPERTIES: &[&str] = &["ATTENDEE", "ORGANIZER"];
+// Parameters replaced by SCHEDULE-AGENT=CLIENT.
+const SCHEDULING_PARAMS: &[&str] = &[
+    "SCHEDULE-AGENT",
+    "SCHEDULE-FORCE-SEND",
+    "SCHEDULE-STATUS",
+    "RSVP",
+];
+
+pub fn validate_mode(mode: &str) -> Result<()> {
+    ensure!(
+        MODES.contains(&mode),
+        "Scheduling must be one of: {}",
+        MODES.join(", ")
+    );
+    Ok(())
+}
+
+// Splits `NAME;A=1;B="x;y"` at the semicolons outside quotes.
+fn params(head: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let (mut start, mut quoted) = (0, false);
+    for (i, c) in head.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                out.push(&head[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push(&head[start..]);
+    out
+}
+
+fn client_scheduled(raw: &str) -> Option<String> {
+    let line = ics_component::unfold(raw);
+    let (head, value) = split_property(&line)?;
+    let mut kept: Vec<&str> = params(head)
+        .into_iter()
+        .filter(|p| {
+            let name = p.split('=').next().unwrap_or_default();
+            !SCHEDULING_PARAMS
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(name))
+        })
+        .collect();
+    kept.push("SCHEDULE-AGENT=CLIENT");
+    Some(fold(&format!("{}:{}", kept.join(";"), value)))
+}
+
+// Applies `mode` to every VEVENT of `ics`. Alarms keep their attendees.
+pub fn apply(ics: &str, mode: &str) -> String {
+    if !matches!(mode, "strip" | "client") {
+        return ics.to_string();
+    }
+    let mut out = String::with_capacity(ics.len());
+    let mut vevent = String::new();
+    let mut in_vevent = false;
+    for line in ics.split_inclusive('\n') {
+        if line.starts_with("BEGIN:VEVENT") {
+            in_vevent = true;
+        }
+        if !in_vevent {
+            out.push_str(line);
+            continue;
+        }
+        vevent.push_str(line);
+        if line.starts_with("END:VEVENT") {
+            in_vevent = false;
+            out.push_str(&ics_component::rewrite(&vevent, |name, raw| {
+                if !PROPERTIES.contains(&name) {
+                    return None;
+                }
+                match mode {
+                    "strip" => Some(String::new()),
+                    _ => client_scheduled(raw),
+                }
+            }));
+            vevent.clear();
+        }
+    }
+    out.push_str(&vevent);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ICS: &str = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:review\r\n\
+        ORGANIZER;CN=\"Lee; Ops\":mailto:lee@example.com\r\n\
+        ATTENDEE;RSVP=TRUE;PARTSTAT=NEEDS-ACTION;SCHEDULE-AGENT=SERVER:mailto:kim@example.com\r\n\
+        BEGIN:VALARM\r\nACTION:EMAIL\r\nATTENDEE:mailto:lee@example.com\r\nEND:VALARM\r\n\
+        END:VEVENT\r\nEND:VCALENDAR\r\n";
+
+    #[test]
+    fn strips_or_marks_scheduling_properties() {
+        assert_eq!(apply(ICS, "keep"), ICS);
+
+        let stripped = apply(ICS, "strip");
+        assert!(!stripped.contains("ORGANIZER"));
+        assert!(!stripped.contains("kim@example.com"));
+        assert!(stripped.contains("ACTION:EMAIL\r\nATTENDEE:mailto:lee@example.com\r\n"));
+
+        let client = apply(ICS, "client");
+        assert!(client.contains(
+            "ORGANIZER;CN=\"Lee; Ops\";SCHEDULE-AGENT=CLIENT:mailto:lee@example.com\r\n"
+        ));
+        assert!(client.contains(
+            "ATTENDEE;PARTSTAT=NEEDS-ACTION;SCHEDULE-AGENT=CLIENT:mailto:kim@example.com\r\n"
+        ));
+        assert_eq!(apply(&client, "client"), client);
+        assert!(validate_mode("silent").is_err());
+    }
+}
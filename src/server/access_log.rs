@@ -0,0 +1,130 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::{
+    extract::ConnectInfo,
+    http::{Request, Response, Uri, header},
+};
+use serde::Deserialize;
+use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
+use tower_http::trace::{DefaultOnBodyChunk, DefaultOnEos, MakeSpan, OnResponse, TraceLayer};
+use tracing::Span;
+
+use super::auth::CurrentUser;
+
+pub const TARGET: &str = "access_log";
+
+// Query parameters whose names contain any of these are logged as `REDACTED`.
+const SENSITIVE_PARAMS: &[&str] = &["token", "key", "secret", "password", "auth", "sig"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    Off,
+    Text,
+    Json,
+}
+
+pub fn redact_uri(uri: &Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.path().to_owned();
+    };
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _))
+                if SENSITIVE_PARAMS
+                    .iter()
+                    .any(|s| name.to_ascii_lowercase().contains(s)) =>
+            {
+                format!("{}=REDACTED", name)
+            }
+            _ => pair.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{}", uri.path(), query)
+}
+
+#[derive(Clone)]
+pub struct AccessSpan;
+
+impl<B> MakeSpan<B> for AccessSpan {
+    fn make_span(&mut self, req: &Request<B>) -> Span {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("-")
+        };
+        let client = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|c| c.0.ip().to_string());
+        tracing::info_span!(
+            target: TARGET,
+            "request",
+            method = %req.method(),
+            path = %redact_uri(req.uri()),
+            client = client.as_deref().unwrap_or("-"),
+            forwarded_for = header("x-forwarded-for"),
+            user_agent = header(header::USER_AGENT.as_str()),
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct AccessResponse;
+
+impl<B> OnResponse<B> for AccessResponse {
+    fn on_response(self, res: &Response<B>, latency: Duration, _span: &Span) {
+        // The auth middleware tags responses with the user it authenticated.
+        let user = res
+            .extensions()
+            .get::<CurrentUser>()
+            .map(|u| u.username.as_str())
+            .unwrap_or("-");
+        tracing::info!(
+            target: TARGET,
+            status = res.status().as_u16(),
+            latency_ms = latency.as_secs_f64() * 1000.0,
+            user,
+        );
+    }
+}
+
+pub type AccessLogLayer = TraceLayer<
+    SharedClassifier<ServerErrorsAsFailures>,
+    AccessSpan,
+    (),
+    AccessResponse,
+    DefaultOnBodyChunk,
+    DefaultOnEos,
+    (),
+>;
+
+// Request bodies and failures are not logged; the response line carries the status.
+pub fn layer() -> AccessLogLayer {
+    TraceLayer::new_for_http()
+        .make_span_with(AccessSpan)
+        .on_request(())
+        .on_response(AccessResponse)
+        .on_failure(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_sensitive_query_params() {
+        let uri: Uri = "/ics/work?token=abc&days=7&API_KEY=x&signature=y"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            redact_uri(&uri),
+            "/ics/work?token=REDACTED&days=7&API_KEY=REDACTED&signature=REDACTED"
+        );
+        assert_eq!(redact_uri(&"/api/sources".parse().unwrap()), "/api/sources");
+    }
+}
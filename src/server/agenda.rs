@@ -0,0 +1,205 @@
+use axum::{
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+
+use super::caldav::xml_escape;
+use crate::db;
+use crate::event_index::IndexedEvent;
+use crate::recurrence;
+
+const AGENDA_DAYS: i64 = 30;
+const MAX_EVENTS: usize = 1000;
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:40rem;margin:2rem auto;padding:0 1rem;color:#222}\
+h2{font-size:1rem;margin:1.5rem 0 .5rem;border-bottom:1px solid #ddd}\
+ul{list-style:none;padding:0;margin:0}li{margin:.3rem 0}\
+time{display:inline-block;min-width:7.5rem;color:#555}.location{color:#777}";
+
+#[derive(Debug, PartialEq)]
+pub struct AgendaItem {
+    pub day: NaiveDate,
+    // `None` for all-day events.
+    pub time: Option<String>,
+    pub summary: String,
+    pub location: Option<String>,
+}
+
+// Indexed times are UTC; all-day events run midnight to midnight in whole days.
+// Events already under way are listed under `today`.
+pub fn agenda_items(events: &[IndexedEvent], tz: Tz, today: NaiveDate) -> Vec<AgendaItem> {
+    let mut items: Vec<AgendaItem> = events
+        .iter()
+        .filter_map(|event| {
+            let start = event.starts_at?;
+            let end = event.ends_at;
+            let all_day = start.time() == chrono::NaiveTime::MIN
+                && end.is_some_and(|e| e.time() == chrono::NaiveTime::MIN && e > start);
+            let (day, time) = match all_day {
+                true => (start.date(), None),
+                false => {
+                    let local = |t: NaiveDateTime| t.and_utc().with_timezone(&tz);
+                    let from = local(start);
+                    let time = match end.map(local) {
+                        Some(to) if to.date_naive() == from.date_naive() => {
+                            format!("{}–{}", from.format("%H:%M"), to.format("%H:%M"))
+                        }
+                        _ => from.format("%H:%M").to_string(),
+                    };
+                    (from.date_naive(), Some(time))
+                }
+            };
+            Some(AgendaItem {
+                day: day.max(today),
+                time,
+                summary: event
+                    .summary
+                    .clone()
+                    .filter(|s| !s.trim().is_empty())
+                    .unwrap_or_else(|| "(No title)".into()),
+                location: event.location.clone().filter(|l| !l.trim().is_empty()),
+            })
+        })
+        .collect();
+    // All-day events first within a day, then by time.
+    items.sort_by(|a, b| (a.day, &a.time).cmp(&(b.day, &b.time)));
+    items
+}
+
+pub fn render(title: &str, items: &[AgendaItem], tz: Tz) -> String {
+    let title = xml_escape(title);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <meta name=\"robots\" content=\"noindex\">\n<title>{title}</title>\n<style>{STYLE}</style>\n\
+         </head>\n<body>\n<h1>{title}</h1>\n<p>Next {AGENDA_DAYS} days, times in {tz}.</p>\n"
+    );
+    if items.is_empty() {
+        html.push_str("<p>Nothing scheduled.</p>\n");
+    }
+    for (i, item) in items.iter().enumerate() {
+        if i == 0 || items[i - 1].day != item.day {
+            if i > 0 {
+                html.push_str("</ul>\n");
+            }
+            html.push_str(&format!(
+                "<h2>{}</h2>\n<ul>\n",
+                item.day.format("%A, %-d %B %Y")
+            ));
+        }
+        let location = match &item.location {
+            Some(l) => format!(" <span class=\"location\">{}</span>", xml_escape(l)),
+            None => String::new(),
+        };
+        html.push_str(&format!(
+            "<li><time>{}</time> {}{}</li>\n",
+            item.time.as_deref().unwrap_or("All day"),
+            xml_escape(&item.summary),
+            location
+        ));
+    }
+    if !items.is_empty() {
+        html.push_str("</ul>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+// `/view/{token}` shows the next 30 days of a public feed as a page, for
+// people without a calendar app. The token is the feed's public path, so it
+// needs no login. Recurring events are listed at each occurrence.
+pub async fn serve_view(
+    State(state): State<crate::api::AppState>,
+    Path(token): Path<String>,
+) -> Response {
+    let page = {
+        let Ok(db) = state.db.lock() else {
+            tracing::error!("DB lock poisoned serving agenda /{}", token);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
+        };
+        let lookup = || -> anyhow::Result<Option<String>> {
+            let Some((id, name, _)) = db::get_subscription_by_path(&db, &token, true)? else {
+                return Ok(None);
+            };
+            let properties = db::get_feed_properties(&db, id)?;
+            let tz = properties
+                .timezone
+                .as_deref()
+                .and_then(|tz| tz.parse::<Tz>().ok())
+                .unwrap_or(Tz::UTC);
+            let now = Utc::now().naive_utc();
+            let ics = db::get_ics_data(&db, id)?.unwrap_or_default();
+            let mut events = recurrence::expand(&ics, now, now + Duration::days(AGENDA_DAYS));
+            events.sort_by_key(|e| e.starts_at);
+            events.truncate(MAX_EVENTS);
+            let today = now.and_utc().with_timezone(&tz).date_naive();
+            let title = properties.name.unwrap_or(name);
+            Ok(Some(render(&title, &agenda_items(&events, tz, today), tz)))
+        };
+        lookup()
+    };
+    match page {
+        Ok(Some(html)) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .header(header::CACHE_CONTROL, "no-cache")
+            // Keeps the token out of Referer headers and search engines.
+            .header(header::REFERRER_POLICY, "no-referrer")
+            .header("x-robots-tag", "noindex")
+            .body(axum::body::Body::from(html))
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        Ok(None) => (StatusCode::NOT_FOUND, "Feed not found").into_response(),
+        Err(e) => {
+            tracing::error!("Error rendering agenda for /{}: {}", token, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(summary: &str, starts_at: &str, ends_at: &str) -> IndexedEvent {
+        let at = |t| NaiveDateTime::parse_from_str(t, crate::event_index::TIMESTAMP_FORMAT).ok();
+        IndexedEvent {
+            summary: Some(summary.into()),
+            starts_at: at(starts_at),
+            ends_at: at(ends_at),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn groups_events_by_local_day() {
+        let tz: Tz = "Europe/Berlin".parse().unwrap();
+        let today = NaiveDate::from_ymd_opt(2026, 3, 10).unwrap();
+        let events = [
+            event("Standup", "2026-03-11 08:00:00", "2026-03-11 08:15:00"),
+            event("Offsite", "2026-03-11 00:00:00", "2026-03-12 00:00:00"),
+            event("Late", "2026-03-11 23:30:00", "2026-03-12 00:30:00"),
+            event("Conference", "2026-03-09 08:00:00", "2026-03-12 16:00:00"),
+        ];
+        let items = agenda_items(&events, tz, today);
+        let summary: Vec<_> = items
+            .iter()
+            .map(|i| (i.day.to_string(), i.time.clone(), i.summary.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("2026-03-10".into(), Some("09:00".into()), "Conference"),
+                ("2026-03-11".into(), None, "Offsite"),
+                ("2026-03-11".into(), Some("09:00–09:15".into()), "Standup"),
+                ("2026-03-12".into(), Some("00:30–01:30".into()), "Late"),
+            ]
+        );
+        let html = render("Team <Cal>", &items, tz);
+        assert!(html.contains("<h1>Team &lt;Cal&gt;</h1>"));
+        assert!(html.contains("<h2>Wednesday, 11 March 2026</h2>"));
+        assert!(html.contains("<li><time>All day</time> Offsite</li>"));
+    }
+}
@@ -1,158 +1,472 @@
-use argon2::{
-    Argon2,
-    password_hash::{PasswordHash, PasswordVerifier},
-};
+use std::sync::Arc;
+
 use axum::{
     Extension,
     extract::Request,
-    http::{HeaderValue, StatusCode, header},
+    http::{Extensions, HeaderMap, HeaderName, HeaderValue, header},
     middleware::Next,
     response::{IntoResponse, Response},
 };
+use axum_extra::extract::cookie::{CookieJar, Key};
 use base64::Engine;
+use serde::Serialize;
+use sha2::{Digest, Sha512};
 use subtle::ConstantTimeEq;
+use utoipa::ToSchema;
 
+use super::auth_policy::{AuthPolicy, RouteGroup};
+use super::authenticators::{
+    ApiKeyAuth, AuthRequest, Authenticator, BasicAuth, JwtAuth, Outcome, Password, ProxyHeaderAuth,
+    SessionAuth, parse_cidr,
+};
+use super::oidc::OidcVerifier;
+use crate::api::error::ApiError;
 use crate::config::AppConfig;
 
-const AUTH_EXEMPT_PATHS: &[&str] = &["/api/health"];
+pub const CSRF_COOKIE: &str = "caldav_csrf";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+const AUTH_EXEMPT_PATHS: &[&str] = &[
+    "/api/health",
+    "/api/auth/login",
+    "/api/auth/logout",
+    super::feed_signing::PUBLIC_KEY_PATH,
+];
+
+// The enabled authentication schemes, tried in order until one accepts or
+// rejects the request. Empty means auth is disabled.
+#[derive(Clone, Default)]
+pub struct AuthChain {
+    authenticators: Vec<Arc<dyn Authenticator>>,
+    policy: AuthPolicy,
+    sessions: Option<SessionSettings>,
+}
 
 #[derive(Clone)]
-pub enum AuthConfig {
-    Disabled,
-    PlainText {
-        username: String,
-        password: String,
-    },
-    Hashed {
-        username: String,
-        password_hash: String,
-    },
+pub struct SessionSettings {
+    pub key: Key,
+    pub ttl_secs: i64,
 }
 
-impl AuthConfig {
+impl SessionSettings {
     pub fn from_config(cfg: &AppConfig) -> Self {
-        let Some(username) = cfg.auth_username.as_deref().filter(|s| !s.is_empty()) else {
-            return Self::Disabled;
+        let key = match cfg.session_secret.as_deref().filter(|s| !s.is_empty()) {
+            Some(secret) => Key::from(&Sha512::digest(secret.as_bytes())),
+            None => {
+                tracing::info!(
+                    "SESSION_SECRET not set; login sessions end when the server restarts"
+                );
+                Key::generate()
+            }
         };
+        Self {
+            key,
+            ttl_secs: cfg.session_ttl,
+        }
+    }
+}
+
+impl AuthChain {
+    pub fn new(authenticators: Vec<Box<dyn Authenticator>>) -> Self {
+        Self {
+            authenticators: authenticators.into_iter().map(Arc::from).collect(),
+            policy: AuthPolicy::default(),
+            sessions: None,
+        }
+    }
+
+    // Accepts session cookies, checked before the other methods.
+    pub fn with_sessions(mut self, sessions: SessionSettings) -> Self {
+        self.authenticators.insert(
+            0,
+            Arc::new(SessionAuth {
+                key: sessions.key.clone(),
+            }),
+        );
+        self.sessions = Some(sessions);
+        self
+    }
+
+    pub fn sessions(&self) -> Option<&SessionSettings> {
+        self.sessions.as_ref()
+    }
 
-        if let Some(hash) = cfg.auth_password_hash.as_deref().filter(|s| !s.is_empty()) {
-            return Self::Hashed {
-                username: username.to_owned(),
-                password_hash: hash.to_owned(),
-            };
+    // Checks a username/password pair the way HTTP Basic would, for the login form.
+    pub async fn verify_password(
+        &self,
+        state: &crate::api::AppState,
+        username: &str,
+        password: &str,
+    ) -> Option<CurrentUser> {
+        let encoded =
+            base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Basic {}", encoded)).ok()?,
+        );
+        let mut extensions = Extensions::new();
+        extensions.insert(state.clone());
+        let req = AuthRequest {
+            headers: &headers,
+            extensions: &extensions,
+        };
+        for authenticator in self.authenticators.iter().filter(|a| a.name() == "basic") {
+            if let Outcome::Authenticated(user) = authenticator.authenticate(&req).await {
+                return Some(user);
+            }
         }
+        None
+    }
+
+    pub fn with_policy(mut self, policy: AuthPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
 
-        if let Some(pass) = cfg.auth_password.as_deref().filter(|s| !s.is_empty()) {
-            return Self::PlainText {
-                username: username.to_owned(),
-                password: pass.to_owned(),
-            };
+    pub fn from_config(cfg: &AppConfig) -> anyhow::Result<Self> {
+        let username = cfg.auth_username.as_deref().filter(|s| !s.is_empty());
+        let password = match (
+            cfg.auth_password_hash.as_deref().filter(|s| !s.is_empty()),
+            cfg.auth_password.as_deref().filter(|s| !s.is_empty()),
+        ) {
+            (Some(hash), _) => Some(Password::Hashed(hash.to_owned())),
+            (None, Some(pass)) => Some(Password::Plain(pass.to_owned())),
+            (None, None) => None,
+        };
+        let admin = username.zip(password).map(|(u, p)| (u.to_owned(), p));
+        let admin_username = admin.as_ref().map(|(u, _)| u.clone());
+
+        let mut chain: Vec<Box<dyn Authenticator>> = Vec::new();
+        if let Some(name) = cfg.auth_proxy_header.as_deref().filter(|s| !s.is_empty()) {
+            let mut trusted_proxies = cfg
+                .auth_trusted_proxies
+                .as_deref()
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(parse_cidr)
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            if trusted_proxies.is_empty() {
+                // Only a proxy on this host until told otherwise.
+                tracing::info!(
+                    "AUTH_PROXY_HEADER is only trusted from loopback; set AUTH_TRUSTED_PROXIES for a remote proxy"
+                );
+                trusted_proxies = vec![parse_cidr("127.0.0.0/8")?, parse_cidr("::1")?];
+            }
+            chain.push(Box::new(ProxyHeaderAuth {
+                header: HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| anyhow::anyhow!("Invalid AUTH_PROXY_HEADER: {}", e))?,
+                trusted_proxies,
+                admin_username: admin_username.clone(),
+            }));
+        }
+        let api_keys: Vec<String> = cfg
+            .auth_api_keys
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_owned)
+            .collect();
+        if !api_keys.is_empty() {
+            chain.push(Box::new(ApiKeyAuth { keys: api_keys }));
+        }
+        if let Some(issuer) = cfg.oidc_issuer.as_deref().filter(|s| !s.is_empty()) {
+            chain.push(Box::new(JwtAuth {
+                verifier: OidcVerifier::new(
+                    issuer.to_owned(),
+                    cfg.oidc_audience.clone(),
+                    cfg.oidc_jwks_url.clone(),
+                    cfg.oidc_username_claim.clone(),
+                ),
+                admin_username,
+            }));
+        }
+        // Users from the users table can always log in with Basic once auth is on.
+        if admin.is_some() || !chain.is_empty() {
+            chain.push(Box::new(BasicAuth { admin }));
         }
+        let mut auth = Self::new(chain);
+        if auth.is_enabled() {
+            auth = auth.with_sessions(SessionSettings::from_config(cfg));
+        }
+        let policy = AuthPolicy::parse(
+            cfg.auth_policy.as_deref().unwrap_or_default(),
+            &auth.names(),
+        )?;
+        Ok(auth.with_policy(policy))
+    }
 
-        Self::Disabled
+    pub fn is_enabled(&self) -> bool {
+        !self.authenticators.is_empty()
     }
 
-    fn username(&self) -> &str {
-        match self {
-            AuthConfig::PlainText { username, .. } | AuthConfig::Hashed { username, .. } => {
-                username
+    pub fn names(&self) -> Vec<&'static str> {
+        self.authenticators.iter().map(|a| a.name()).collect()
+    }
+
+    // Returns the user and the name of the method that authenticated them.
+    async fn authenticate(
+        &self,
+        req: &AuthRequest<'_>,
+        group: RouteGroup,
+    ) -> Option<(CurrentUser, &'static str)> {
+        for authenticator in self
+            .authenticators
+            .iter()
+            .filter(|a| self.policy.permits(group, a.name()))
+        {
+            match authenticator.authenticate(req).await {
+                Outcome::Skip => continue,
+                Outcome::Rejected => return None,
+                Outcome::Authenticated(user) => return Some((user, authenticator.name())),
             }
-            AuthConfig::Disabled => unreachable!(),
         }
+        None
+    }
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct CurrentUser {
+    pub id: Option<i64>,
+    pub username: String,
+    pub is_admin: bool,
+}
+
+impl CurrentUser {
+    // The owner to filter by, or None when the user may see everything.
+    pub fn owner_scope(&self) -> Option<i64> {
+        if self.is_admin { None } else { self.id }
     }
 }
 
-fn unauthorized() -> Response {
-    Response::builder()
-        .status(StatusCode::UNAUTHORIZED)
-        .header(
-            header::WWW_AUTHENTICATE,
-            HeaderValue::from_static("Basic realm=\"caldav-ics-sync\""),
-        )
-        .body(axum::body::Body::from("Unauthorized"))
-        .unwrap_or_else(|_| StatusCode::UNAUTHORIZED.into_response())
+// API clients get the JSON error envelope, everything else plain text.
+fn rejection(api: bool, error: ApiError, text: &'static str) -> Response {
+    match api {
+        true => error.into_response(),
+        false => (error.status_code(), text).into_response(),
+    }
+}
+
+fn unauthorized(api: bool) -> Response {
+    let mut res = rejection(
+        api,
+        ApiError::unauthorized("Authentication required"),
+        "Unauthorized",
+    );
+    res.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        HeaderValue::from_static("Basic realm=\"caldav-ics-sync\""),
+    );
+    res
 }
 
 pub async fn basic_auth_middleware(
-    Extension(config): Extension<AuthConfig>,
+    Extension(auth): Extension<AuthChain>,
     req: Request,
     next: Next,
 ) -> Response {
-    if matches!(config, AuthConfig::Disabled) {
+    if !auth.is_enabled() {
         return next.run(req).await;
     }
 
     let path = req.uri().path().to_owned();
+    // Subscription links are guarded like the feed they point to.
+    let path = match path.strip_prefix("/subscribe/") {
+        Some(feed) => format!("/ics/{}", feed),
+        None => path,
+    };
 
     if AUTH_EXEMPT_PATHS.iter().any(|p| path == *p) {
         return next.run(req).await;
     }
 
-    if path.starts_with("/ics/public/") {
+    // Agenda pages are reached through the feed's public path.
+    if path.starts_with("/ics/public/") || path.starts_with("/view/") {
         return next.run(req).await;
     }
 
-    if let Some(true) = path.strip_prefix("/ics/").map(|ics_path| {
-        let Some(state) = req.extensions().get::<crate::api::AppState>() else {
-            return false;
-        };
-        let db = match state.db.lock() {
-            Ok(g) => g,
-            Err(e) => {
-                tracing::error!("DB lock poisoned in auth middleware: {}", e);
-                return false;
-            }
-        };
-        match crate::db::is_public_standard_ics(&db, ics_path) {
-            Ok(v) => v,
-            Err(e) => {
-                tracing::error!("DB error checking public ICS: {}", e);
-                false
-            }
-        }
-    }) {
+    // The CalDAV proxy checks its own per-source token.
+    if path.starts_with("/caldav-proxy/") {
+        return next.run(req).await;
+    }
+
+    let group = RouteGroup::of(&path);
+    if auth.policy.is_public(group) {
+        return next.run(req).await;
+    }
+
+    if let Some(ics_path) = path.strip_prefix("/ics/")
+        && db_check(&req, "public ICS", |db| {
+            crate::db::is_public_standard_ics(db, feed_of(ics_path))
+        })
+    {
+        return next.run(req).await;
+    }
+
+    if let Some(collection) = path.strip_prefix("/caldav/")
+        && db_check(&req, "public CalDAV collection", |db| {
+            super::caldav::is_public_collection(db, collection)
+        })
+    {
         return next.run(req).await;
     }
 
-    let Some((req_user, req_pass)) = extract_credentials(&req) else {
-        return unauthorized();
+    if let Some(hash) = path.strip_prefix("/attachments/")
+        && db_check(&req, "public attachment", |db| {
+            crate::db::is_public_attachment(db, hash)
+        })
+    {
+        return next.run(req).await;
+    }
+
+    let auth_req = AuthRequest {
+        headers: req.headers(),
+        extensions: req.extensions(),
+    };
+    let api = path == "/api" || path.starts_with("/api/");
+    let Some((user, method)) = auth.authenticate(&auth_req, group).await else {
+        return unauthorized(api);
     };
 
-    if req_user
-        .as_bytes()
-        .ct_eq(config.username().as_bytes())
-        .unwrap_u8()
-        != 1
+    // Browsers attach cookies automatically, so cookie-authenticated writes
+    // must prove they came from our own pages.
+    if method == "session" && !req.method().is_safe() && !csrf_ok(req.headers()) {
+        return rejection(
+            api,
+            ApiError::forbidden("CSRF check failed"),
+            "CSRF check failed",
+        );
+    }
+
+    if let Some(owner_id) = user.owner_scope()
+        && !owns_path(&req, &path, owner_id)
     {
-        return unauthorized();
+        return rejection(api, ApiError::not_found("Not found"), "Not found");
     }
 
-    match &config {
-        AuthConfig::PlainText { password, .. } => {
-            if req_pass.as_bytes().ct_eq(password.as_bytes()).unwrap_u8() != 1 {
-                return unauthorized();
-            }
+    let mut req = req;
+    req.extensions_mut().insert(user.clone());
+    let mut res = next.run(req).await;
+    // Lets the access log record who made the request.
+    res.extensions_mut().insert(user);
+    res
+}
+
+// A feed's detached signature, availability, subscription profile and parts
+// are readable by whoever can read the feed.
+fn feed_of(ics_path: &str) -> &str {
+    if let Some((feed, _)) = crate::feed_limits::part_path(ics_path) {
+        return feed;
+    }
+    [
+        super::feed_signing::SIGNATURE_SUFFIX,
+        crate::availability::AVAILABILITY_SUFFIX,
+        super::subscription::MOBILECONFIG_SUFFIX,
+    ]
+    .iter()
+    .find_map(|suffix| ics_path.strip_suffix(suffix))
+    .unwrap_or(ics_path)
+}
+
+// Non-admin users only reach their own sources, destinations and feeds.
+fn owns_path(req: &Request, path: &str, owner_id: i64) -> bool {
+    let id_after = |prefix: &str| {
+        path.strip_prefix(prefix)
+            .and_then(|rest| rest.split('/').next())
+            .and_then(|id| id.parse::<i64>().ok())
+    };
+    if let Some(id) = id_after("/api/sources/") {
+        return db_check(req, "source owner", |db| {
+            crate::db::source_owned_by(db, id, owner_id)
+        });
+    }
+    if let Some(id) = id_after("/api/destinations/") {
+        return db_check(req, "destination owner", |db| {
+            crate::db::destination_owned_by(db, id, owner_id)
+        });
+    }
+    if let Some(ics_path) = path.strip_prefix("/ics/") {
+        return db_check(req, "feed owner", |db| {
+            crate::db::feed_owned_by(db, feed_of(ics_path), owner_id)
+        });
+    }
+    if let Some(collection) = path.strip_prefix("/caldav/") {
+        let trimmed = collection.trim_end_matches('/');
+        return db_check(req, "feed owner", |db| {
+            Ok(crate::db::feed_owned_by(db, trimmed, owner_id)?
+                || match trimmed.rsplit_once('/') {
+                    Some((parent, _)) => crate::db::feed_owned_by(db, parent, owner_id)?,
+                    None => false,
+                })
+        });
+    }
+    if let Some(hash) = path.strip_prefix("/attachments/") {
+        return db_check(req, "attachment owner", |db| {
+            crate::db::attachment_owned_by(db, hash, owner_id)
+        });
+    }
+    true
+}
+
+fn db_check(
+    req: &Request,
+    kind: &str,
+    check: impl FnOnce(&rusqlite::Connection) -> anyhow::Result<bool>,
+) -> bool {
+    let Some(state) = req.extensions().get::<crate::api::AppState>() else {
+        return false;
+    };
+    let db = match state.db.lock() {
+        Ok(g) => g,
+        Err(e) => {
+            tracing::error!("DB lock poisoned in auth middleware: {}", e);
+            return false;
         }
-        AuthConfig::Hashed { password_hash, .. } => {
-            let Ok(parsed_hash) = PasswordHash::new(password_hash) else {
-                tracing::error!("AUTH_PASSWORD_HASH is not a valid PHC-format hash");
-                return unauthorized();
-            };
-            if Argon2::default()
-                .verify_password(req_pass.as_bytes(), &parsed_hash)
-                .is_err()
-            {
-                return unauthorized();
-            }
+    };
+    match check(&db) {
+        Ok(v) => v,
+        Err(e) => {
+            tracing::error!("DB error checking {}: {}", kind, e);
+            false
         }
-        AuthConfig::Disabled => unreachable!(),
     }
+}
 
-    next.run(req).await
+// Double-submit token (the `caldav_csrf` cookie echoed in `X-CSRF-Token`) plus,
+// when the browser sends one, an Origin matching the Host.
+fn csrf_ok(headers: &HeaderMap) -> bool {
+    let jar = CookieJar::from_headers(headers);
+    let token_ok = match (
+        jar.get(CSRF_COOKIE).map(|c| c.value()),
+        headers.get(CSRF_HEADER).and_then(|v| v.to_str().ok()),
+    ) {
+        (Some(cookie), Some(header)) => {
+            !cookie.is_empty() && cookie.as_bytes().ct_eq(header.as_bytes()).unwrap_u8() == 1
+        }
+        _ => false,
+    };
+    if !token_ok {
+        return false;
+    }
+    let Some(origin) = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok()) else {
+        return true;
+    };
+    let host = headers
+        .get("x-forwarded-host")
+        .or_else(|| headers.get(header::HOST))
+        .and_then(|v| v.to_str().ok());
+    let origin_host = origin
+        .split_once("://")
+        .map(|(_, h)| h.trim_end_matches('/'));
+    host.is_some() && origin_host == host
 }
 
-fn extract_credentials(req: &Request) -> Option<(String, String)> {
-    let auth_header = req.headers().get(header::AUTHORIZATION)?;
+pub(crate) fn extract_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let auth_header = headers.get(header::AUTHORIZATION)?;
     let auth_str = auth_header.to_str().ok()?;
     let encoded = auth_str.strip_prefix("Basic ")?;
     let decoded_bytes = base64::engine::general_purpose::STANDARD
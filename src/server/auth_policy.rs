@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, bail};
+
+pub const PUBLIC: &str = "public";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RouteGroup {
+    Api,
+    Metrics,
+    Feeds,
+    Ui,
+}
+
+impl RouteGroup {
+    pub fn of(path: &str) -> Self {
+        if path.starts_with("/api/health/") || path == "/api/version" {
+            RouteGroup::Metrics
+        } else if path.starts_with("/api/") {
+            RouteGroup::Api
+        } else if ["/ics/", "/caldav/", "/attachments/"]
+            .iter()
+            .any(|p| path.starts_with(p))
+        {
+            RouteGroup::Feeds
+        } else {
+            RouteGroup::Ui
+        }
+    }
+
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "api" => RouteGroup::Api,
+            "metrics" => RouteGroup::Metrics,
+            "feeds" => RouteGroup::Feeds,
+            "ui" => RouteGroup::Ui,
+            _ => bail!(
+                "Unknown route group '{}' in AUTH_POLICY (expected api, metrics, feeds or ui)",
+                name
+            ),
+        })
+    }
+}
+
+// Which authentication methods each route group accepts. Groups without an
+// entry accept every enabled method.
+#[derive(Clone, Debug, Default)]
+pub struct AuthPolicy(HashMap<RouteGroup, Vec<String>>);
+
+impl AuthPolicy {
+    // Parses `group=method,method;group=method`, e.g. `feeds=basic,public;metrics=jwt`.
+    pub fn parse(spec: &str, methods: &[&str]) -> Result<Self> {
+        let mut rules = HashMap::new();
+        for rule in spec.split(';').map(str::trim).filter(|r| !r.is_empty()) {
+            let Some((group, allowed)) = rule.split_once('=') else {
+                bail!(
+                    "Invalid AUTH_POLICY rule '{}', expected group=methods",
+                    rule
+                );
+            };
+            let group = RouteGroup::parse(group.trim())?;
+            let allowed: Vec<String> = allowed
+                .split(',')
+                .map(str::trim)
+                .filter(|m| !m.is_empty())
+                .map(str::to_owned)
+                .collect();
+            for method in &allowed {
+                if method != PUBLIC && !methods.contains(&method.as_str()) {
+                    bail!(
+                        "AUTH_POLICY method '{}' is not enabled (enabled: {})",
+                        method,
+                        methods.join(", ")
+                    );
+                }
+            }
+            if group == RouteGroup::Api && allowed.iter().any(|m| m == PUBLIC) {
+                bail!("AUTH_POLICY cannot make the api group public");
+            }
+            rules.insert(group, allowed);
+        }
+        Ok(Self(rules))
+    }
+
+    pub fn allowed(&self, group: RouteGroup) -> Option<&[String]> {
+        self.0.get(&group).map(Vec::as_slice)
+    }
+
+    pub fn is_public(&self, group: RouteGroup) -> bool {
+        self.allowed(group)
+            .is_some_and(|m| m.iter().any(|m| m == PUBLIC))
+    }
+
+    pub fn permits(&self, group: RouteGroup, method: &str) -> bool {
+        self.allowed(group)
+            .is_none_or(|m| m.iter().any(|m| m == method))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rules_and_classifies_paths() {
+        let policy =
+            AuthPolicy::parse("feeds = basic, public; metrics=jwt", &["basic", "jwt"]).unwrap();
+        assert!(policy.is_public(RouteGroup::Feeds));
+        assert!(policy.permits(RouteGroup::Metrics, "jwt"));
+        assert!(!policy.permits(RouteGroup::Metrics, "basic"));
+        assert!(policy.permits(RouteGroup::Api, "basic"));
+
+        assert_eq!(RouteGroup::of("/api/sources"), RouteGroup::Api);
+        assert_eq!(RouteGroup::of("/api/health/detailed"), RouteGroup::Metrics);
+        assert_eq!(RouteGroup::of("/caldav/work/"), RouteGroup::Feeds);
+        assert_eq!(RouteGroup::of("/"), RouteGroup::Ui);
+
+        assert!(AuthPolicy::parse("api=public", &["basic"]).is_err());
+        assert!(AuthPolicy::parse("feeds=jwt", &["basic"]).is_err());
+        assert!(AuthPolicy::parse("admin=basic", &["basic"]).is_err());
+    }
+}
@@ -0,0 +1,362 @@
+use std::net::{IpAddr, SocketAddr};
+
+use argon2::{
+    Argon2,
+    password_hash::{PasswordHash, PasswordVerifier},
+};
+use axum::{
+    extract::ConnectInfo,
+    http::{Extensions, HeaderMap, HeaderName, header},
+};
+use axum_extra::extract::cookie::{Key, SignedCookieJar};
+use futures_util::future::BoxFuture;
+use subtle::ConstantTimeEq;
+
+use super::auth::{CurrentUser, extract_credentials};
+use super::oidc::OidcVerifier;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const SESSION_COOKIE: &str = "caldav_session";
+
+pub struct AuthRequest<'a> {
+    pub headers: &'a HeaderMap,
+    pub extensions: &'a Extensions,
+}
+
+pub enum Outcome {
+    // The request carries no credentials for this scheme; try the next one.
+    Skip,
+    Rejected,
+    Authenticated(CurrentUser),
+}
+
+pub trait Authenticator: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn authenticate<'a>(&'a self, req: &'a AuthRequest<'a>) -> BoxFuture<'a, Outcome>;
+}
+
+fn admin(username: String) -> CurrentUser {
+    CurrentUser {
+        id: None,
+        username,
+        is_admin: true,
+    }
+}
+
+fn with_db<T>(extensions: &Extensions, f: impl FnOnce(&rusqlite::Connection) -> T) -> Option<T> {
+    let state = extensions.get::<crate::api::AppState>()?;
+    let db = state.db.lock().ok()?;
+    Some(f(&db))
+}
+
+fn ct_eq(a: &str, b: &str) -> bool {
+    a.as_bytes().ct_eq(b.as_bytes()).unwrap_u8() == 1
+}
+
+// Externally authenticated names map to the configured admin or a known user.
+// With no users defined (single-user setups) every such identity is admin.
+fn resolve_external_user(
+    extensions: &Extensions,
+    admin_username: Option<&str>,
+    name: String,
+) -> Outcome {
+    if admin_username == Some(name.as_str()) {
+        return Outcome::Authenticated(admin(name));
+    }
+    let found = with_db(extensions, |db| {
+        Ok::<_, anyhow::Error>(match crate::db::get_user_by_username(db, &name)? {
+            Some(user) => Some(CurrentUser {
+                id: Some(user.id),
+                username: user.username,
+                is_admin: user.is_admin,
+            }),
+            None if !crate::db::has_users(db)? => Some(admin(name.clone())),
+            None => None,
+        })
+    });
+    match found {
+        Some(Ok(Some(user))) => Outcome::Authenticated(user),
+        Some(Ok(None)) => {
+            tracing::warn!(
+                "Externally authenticated user '{}' is not a known user",
+                name
+            );
+            Outcome::Rejected
+        }
+        Some(Err(e)) => {
+            tracing::error!("DB error looking up user: {}", e);
+            Outcome::Rejected
+        }
+        None => Outcome::Rejected,
+    }
+}
+
+#[derive(Clone)]
+pub enum Password {
+    Plain(String),
+    Hashed(String),
+}
+
+impl Password {
+    fn verify(&self, candidate: &str) -> bool {
+        match self {
+            Password::Plain(password) => ct_eq(candidate, password),
+            Password::Hashed(hash) => {
+                let Ok(parsed_hash) = PasswordHash::new(hash) else {
+                    tracing::error!("AUTH_PASSWORD_HASH is not a valid PHC-format hash");
+                    return false;
+                };
+                Argon2::default()
+                    .verify_password(candidate.as_bytes(), &parsed_hash)
+                    .is_ok()
+            }
+        }
+    }
+}
+
+// HTTP Basic against the configured admin account, then the users table.
+pub struct BasicAuth {
+    pub admin: Option<(String, Password)>,
+}
+
+impl BasicAuth {
+    fn check(&self, req: &AuthRequest) -> Outcome {
+        let Some((username, password)) = extract_credentials(req.headers) else {
+            return Outcome::Skip;
+        };
+        if let Some((admin_user, admin_pass)) = &self.admin
+            && ct_eq(&username, admin_user)
+        {
+            return match admin_pass.verify(&password) {
+                true => Outcome::Authenticated(admin(username)),
+                false => Outcome::Rejected,
+            };
+        }
+        let user = with_db(req.extensions, |db| {
+            crate::db::get_user_by_username(db, &username)
+        });
+        let user = match user {
+            Some(Ok(Some(user))) => user,
+            Some(Err(e)) => {
+                tracing::error!("DB error looking up user: {}", e);
+                return Outcome::Rejected;
+            }
+            _ => return Outcome::Rejected,
+        };
+        if !Password::Hashed(user.password_hash).verify(&password) {
+            return Outcome::Rejected;
+        }
+        Outcome::Authenticated(CurrentUser {
+            id: Some(user.id),
+            username: user.username,
+            is_admin: user.is_admin,
+        })
+    }
+}
+
+impl Authenticator for BasicAuth {
+    fn name(&self) -> &'static str {
+        "basic"
+    }
+
+    fn authenticate<'a>(&'a self, req: &'a AuthRequest<'a>) -> BoxFuture<'a, Outcome> {
+        Box::pin(std::future::ready(self.check(req)))
+    }
+}
+
+// Signed session cookie issued by `POST /api/auth/login`, backed by the sessions table.
+pub struct SessionAuth {
+    pub key: Key,
+}
+
+impl SessionAuth {
+    fn check(&self, req: &AuthRequest) -> Outcome {
+        let jar = SignedCookieJar::from_headers(req.headers, self.key.clone());
+        let Some(cookie) = jar.get(SESSION_COOKIE) else {
+            return Outcome::Skip;
+        };
+        let found = with_db(req.extensions, |db| {
+            let Some(session) = crate::db::get_session(db, cookie.value())? else {
+                return Ok(None);
+            };
+            Ok::<_, anyhow::Error>(Some(match session.user_id {
+                Some(id) => crate::db::get_user(db, id)?.map(|user| CurrentUser {
+                    id: Some(user.id),
+                    username: user.username,
+                    is_admin: user.is_admin,
+                }),
+                None => Some(admin(session.username)),
+            }))
+        });
+        match found {
+            Some(Ok(Some(Some(user)))) => Outcome::Authenticated(user),
+            Some(Err(e)) => {
+                tracing::error!("DB error looking up session: {}", e);
+                Outcome::Rejected
+            }
+            // Expired or logged-out sessions fall through to other methods.
+            _ => Outcome::Skip,
+        }
+    }
+}
+
+impl Authenticator for SessionAuth {
+    fn name(&self) -> &'static str {
+        "session"
+    }
+
+    fn authenticate<'a>(&'a self, req: &'a AuthRequest<'a>) -> BoxFuture<'a, Outcome> {
+        Box::pin(std::future::ready(self.check(req)))
+    }
+}
+
+// Static keys for scripts and integrations, sent as `X-API-Key`. Keys act as admin.
+pub struct ApiKeyAuth {
+    pub keys: Vec<String>,
+}
+
+impl Authenticator for ApiKeyAuth {
+    fn name(&self) -> &'static str {
+        "api-key"
+    }
+
+    fn authenticate<'a>(&'a self, req: &'a AuthRequest<'a>) -> BoxFuture<'a, Outcome> {
+        let outcome = match req
+            .headers
+            .get(API_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+        {
+            None => Outcome::Skip,
+            Some(key) if self.keys.iter().any(|k| ct_eq(key.trim(), k)) => {
+                Outcome::Authenticated(admin("api-key".into()))
+            }
+            Some(_) => Outcome::Rejected,
+        };
+        Box::pin(std::future::ready(outcome))
+    }
+}
+
+// Identity asserted by a trusted reverse proxy (Authelia, oauth2-proxy, ...).
+pub struct ProxyHeaderAuth {
+    pub header: HeaderName,
+    pub trusted_proxies: Vec<(IpAddr, u8)>,
+    pub admin_username: Option<String>,
+}
+
+impl ProxyHeaderAuth {
+    fn check(&self, req: &AuthRequest) -> Outcome {
+        let Some(user) = req
+            .headers
+            .get(&self.header)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|u| !u.is_empty())
+        else {
+            return Outcome::Skip;
+        };
+        let peer = req
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|c| c.0.ip());
+        let trusted = peer.is_some_and(|peer| {
+            self.trusted_proxies
+                .iter()
+                .any(|(net, prefix)| ip_in_network(peer, *net, *prefix))
+        });
+        if !trusted {
+            tracing::warn!(
+                "Ignoring {} header from untrusted peer {:?}",
+                self.header,
+                peer
+            );
+            return Outcome::Skip;
+        }
+        resolve_external_user(
+            req.extensions,
+            self.admin_username.as_deref(),
+            user.to_owned(),
+        )
+    }
+}
+
+impl Authenticator for ProxyHeaderAuth {
+    fn name(&self) -> &'static str {
+        "proxy-header"
+    }
+
+    fn authenticate<'a>(&'a self, req: &'a AuthRequest<'a>) -> BoxFuture<'a, Outcome> {
+        Box::pin(std::future::ready(self.check(req)))
+    }
+}
+
+// OIDC-issued JWTs sent as `Authorization: Bearer`.
+pub struct JwtAuth {
+    pub verifier: OidcVerifier,
+    pub admin_username: Option<String>,
+}
+
+impl Authenticator for JwtAuth {
+    fn name(&self) -> &'static str {
+        "jwt"
+    }
+
+    fn authenticate<'a>(&'a self, req: &'a AuthRequest<'a>) -> BoxFuture<'a, Outcome> {
+        Box::pin(async move {
+            let Some(token) = req
+                .headers
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+            else {
+                return Outcome::Skip;
+            };
+            match self.verifier.verify(token.trim()).await {
+                Ok(name) => {
+                    resolve_external_user(req.extensions, self.admin_username.as_deref(), name)
+                }
+                Err(e) => {
+                    tracing::debug!(
+                        "Rejected bearer token from {}: {}",
+                        self.verifier.issuer(),
+                        e
+                    );
+                    Outcome::Rejected
+                }
+            }
+        })
+    }
+}
+
+pub fn parse_cidr(value: &str) -> anyhow::Result<(IpAddr, u8)> {
+    let (addr, prefix) = match value.split_once('/') {
+        Some((a, p)) => (a, Some(p)),
+        None => (value, None),
+    };
+    let addr: IpAddr = addr
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid trusted proxy '{}'", value))?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(p) => p
+            .parse::<u8>()
+            .ok()
+            .filter(|p| *p <= max)
+            .ok_or_else(|| anyhow::anyhow!("Invalid prefix length in '{}'", value))?,
+        None => max,
+    };
+    Ok((addr, prefix))
+}
+
+pub fn ip_in_network(ip: IpAddr, net: IpAddr, prefix: u8) -> bool {
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
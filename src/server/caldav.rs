@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, Method, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::NaiveDateTime;
+use rusqlite::Connection;
+use sha2::{Digest, Sha256};
+
+use super::route_builder::public_origin;
+use crate::api::{AppState, attachments, sync};
+use crate::event_index;
+
+const DAV: &str = "DAV:";
+const CALDAV: &str = "urn:ietf:params:xml:ns:caldav";
+const ALLOW: &str = "OPTIONS, GET, HEAD, PROPFIND, REPORT";
+
+struct Resource {
+    name: String,
+    etag: String,
+    ics: String,
+}
+
+struct Collection {
+    href: String,
+    name: String,
+    ctag: String,
+    ics: String,
+    resources: Vec<Resource>,
+}
+
+fn short_hash(data: &str) -> String {
+    format!("{:x}", Sha256::digest(data.as_bytes()))[..32].to_owned()
+}
+
+pub(crate) fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Events sharing a UID (recurrence overrides) belong to the same resource.
+fn build_collection(href: String, name: String, ics: String) -> Collection {
+    let mut order: Vec<String> = Vec::new();
+    let mut by_key: HashMap<String, Vec<String>> = HashMap::new();
+    for ev in sync::split_vevents(&ics) {
+        let key = sync::event_uid(&ev).unwrap_or_else(|| ev.clone());
+        if !by_key.contains_key(&key) {
+            order.push(key.clone());
+        }
+        by_key.entry(key).or_default().push(ev);
+    }
+    let resources = order
+        .into_iter()
+        .map(|key| {
+            let ics = sync::build_calendar(&by_key.remove(&key).unwrap_or_default());
+            Resource {
+                name: format!("{}.ics", short_hash(&key)),
+                etag: format!("\"{}\"", short_hash(&ics)),
+                ics,
+            }
+        })
+        .collect();
+    Collection {
+        href,
+        name,
+        ctag: short_hash(&ics),
+        ics,
+        resources,
+    }
+}
+
+// Resolves `{collection}/` or `{collection}/{resource}.ics` against source ICS paths.
+fn resolve(
+    db: &Connection,
+    path: &str,
+    base_path: &str,
+    origin: &str,
+) -> anyhow::Result<Option<(Collection, Option<String>)>> {
+    let build = |path: &str, (name, ics): (String, String)| {
+        let href = format!("{}/caldav/{}/", base_path, path);
+        build_collection(href, name, attachments::absolutize(&ics, origin))
+    };
+    let trimmed = path.trim_end_matches('/');
+    if let Some(feed) = crate::db::get_named_ics_data_by_path(db, trimmed)? {
+        return Ok(Some((build(trimmed, feed), None)));
+    }
+    let Some((parent, resource)) = trimmed.rsplit_once('/') else {
+        return Ok(None);
+    };
+    Ok(crate::db::get_named_ics_data_by_path(db, parent)?
+        .map(|feed| (build(parent, feed), Some(resource.to_owned()))))
+}
+
+pub fn is_public_collection(db: &Connection, path: &str) -> anyhow::Result<bool> {
+    let trimmed = path.trim_end_matches('/');
+    if crate::db::is_public_standard_ics(db, trimmed)? {
+        return Ok(true);
+    }
+    match trimmed.rsplit_once('/') {
+        Some((parent, _)) => crate::db::is_public_standard_ics(db, parent),
+        None => Ok(false),
+    }
+}
+
+fn collection_response(c: &Collection) -> String {
+    format!(
+        "<d:response><d:href>{}</d:href><d:propstat><d:prop>\
+         <d:resourcetype><d:collection/><c:calendar/></d:resourcetype>\
+         <d:displayname>{}</d:displayname>\
+         <cs:getctag>{}</cs:getctag>\
+         <c:supported-calendar-component-set><c:comp name=\"VEVENT\"/></c:supported-calendar-component-set>\
+         <d:current-user-privilege-set><d:privilege><d:read/></d:privilege></d:current-user-privilege-set>\
+         </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+        xml_escape(&c.href),
+        xml_escape(&c.name),
+        c.ctag
+    )
+}
+
+fn resource_response(c: &Collection, r: &Resource, with_data: bool) -> String {
+    let data = if with_data {
+        format!("<c:calendar-data>{}</c:calendar-data>", xml_escape(&r.ics))
+    } else {
+        String::new()
+    };
+    format!(
+        "<d:response><d:href>{}{}</d:href><d:propstat><d:prop>\
+         <d:resourcetype/><d:getcontenttype>text/calendar; charset=utf-8</d:getcontenttype>\
+         <d:getetag>{}</d:getetag>{}\
+         </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+        xml_escape(&c.href),
+        xml_escape(&r.name),
+        xml_escape(&r.etag),
+        data
+    )
+}
+
+fn not_found_response(href: &str) -> String {
+    format!(
+        "<d:response><d:href>{}</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>",
+        xml_escape(href)
+    )
+}
+
+fn multistatus(responses: Vec<String>) -> Response {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <d:multistatus xmlns:d=\"DAV:\" xmlns:c=\"{}\" xmlns:cs=\"http://calendarserver.org/ns/\">{}</d:multistatus>",
+        CALDAV,
+        responses.concat()
+    );
+    Response::builder()
+        .status(StatusCode::MULTI_STATUS)
+        .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+        .body(Body::from(body))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+fn propfind(c: &Collection, resource: Option<&Resource>, headers: &HeaderMap) -> Response {
+    if let Some(r) = resource {
+        return multistatus(vec![resource_response(c, r, false)]);
+    }
+    let mut responses = vec![collection_response(c)];
+    let depth = headers.get("Depth").and_then(|v| v.to_str().ok());
+    if depth != Some("0") {
+        responses.extend(c.resources.iter().map(|r| resource_response(c, r, false)));
+    }
+    multistatus(responses)
+}
+
+fn report(c: &Collection, body: &str) -> Response {
+    let Ok(doc) = roxmltree::Document::parse(body) else {
+        return (StatusCode::BAD_REQUEST, "Invalid REPORT body").into_response();
+    };
+    let root = doc.root_element();
+    let with_data = doc
+        .descendants()
+        .any(|n| n.has_tag_name((CALDAV, "calendar-data")));
+
+    if root.has_tag_name((CALDAV, "calendar-multiget")) {
+        let responses = root
+            .descendants()
+            .filter(|n| n.has_tag_name((DAV, "href")))
+            .filter_map(|n| n.text())
+            .map(|href| {
+                let name = href.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+                match c.resources.iter().find(|r| r.name == name) {
+                    Some(r) => resource_response(c, r, with_data),
+                    None => not_found_response(href),
+                }
+            })
+            .collect();
+        return multistatus(responses);
+    }
+    if root.has_tag_name((CALDAV, "calendar-query")) {
+        return multistatus(
+            c.resources
+                .iter()
+                .map(|r| resource_response(c, r, with_data))
+                .collect(),
+        );
+    }
+    if root.has_tag_name((CALDAV, "free-busy-query")) {
+        return free_busy_query(c, root);
+    }
+    (StatusCode::FORBIDDEN, "Unsupported REPORT").into_response()
+}
+
+type Period = (NaiveDateTime, NaiveDateTime);
+
+fn merge_periods(mut periods: Vec<Period>) -> Vec<Period> {
+    periods.sort();
+    let mut merged: Vec<Period> = Vec::new();
+    for (start, end) in periods {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn ics_utc(t: NaiveDateTime) -> String {
+    t.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// RFC 4791 7.10: busy time of the collection's events within the requested range.
+// Transparent and cancelled events don't block time; recurrences are not expanded.
+fn free_busy_query(c: &Collection, root: roxmltree::Node) -> Response {
+    let range = root
+        .descendants()
+        .find(|n| n.has_tag_name((CALDAV, "time-range")))
+        .and_then(|n| {
+            let start = event_index::parse_datetime(n.attribute("start")?, None)?;
+            let end = event_index::parse_datetime(n.attribute("end")?, None)?;
+            (start < end).then_some((start, end))
+        });
+    let Some((range_start, range_end)) = range else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "free-busy-query requires a time-range with start and end",
+        )
+            .into_response();
+    };
+
+    let mut busy = Vec::new();
+    let mut tentative = Vec::new();
+    for event in event_index::index(&c.ics) {
+        let (Some(start), Some(end)) = (event.starts_at, event.ends_at) else {
+            continue;
+        };
+        if event.transparent || start >= range_end || end <= range_start {
+            continue;
+        }
+        let period = (start.max(range_start), end.min(range_end));
+        match event.status.as_deref() {
+            Some("CANCELLED") => {}
+            Some("TENTATIVE") => tentative.push(period),
+            _ => busy.push(period),
+        }
+    }
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        "PRODID:-//CalDAV/ICS Sync//EN".to_owned(),
+        "BEGIN:VFREEBUSY".to_owned(),
+        format!("DTSTAMP:{}", ics_utc(chrono::Utc::now().naive_utc())),
+        format!("DTSTART:{}", ics_utc(range_start)),
+        format!("DTEND:{}", ics_utc(range_end)),
+    ];
+    for (fbtype, periods) in [("BUSY", busy), ("BUSY-TENTATIVE", tentative)] {
+        for (start, end) in merge_periods(periods) {
+            lines.push(format!(
+                "FREEBUSY;FBTYPE={}:{}/{}",
+                fbtype,
+                ics_utc(start),
+                ics_utc(end)
+            ));
+        }
+    }
+    lines.extend(["END:VFREEBUSY".to_owned(), "END:VCALENDAR".to_owned()]);
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
+        .body(Body::from(lines.join("\r\n") + "\r\n"))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+fn get(ics: &str, etag: &str, headers: &HeaderMap, head: bool) -> Response {
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v == etag);
+    let builder = Response::builder().header(header::ETAG, etag);
+    let resp = if not_modified {
+        builder.status(StatusCode::NOT_MODIFIED).body(Body::empty())
+    } else {
+        builder
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
+            .body(if head {
+                Body::empty()
+            } else {
+                Body::from(ics.to_owned())
+            })
+    };
+    resp.unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+pub async fn serve_caldav(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if path.contains("..") {
+        return (StatusCode::BAD_REQUEST, "Invalid path").into_response();
+    }
+    if method == Method::OPTIONS {
+        return Response::builder()
+            .status(StatusCode::OK)
+            .header("DAV", "1, calendar-access")
+            .header(header::ALLOW, ALLOW)
+            .body(Body::empty())
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    let resolved = {
+        let Ok(db) = state.db.lock() else {
+            tracing::error!("DB lock poisoned serving CalDAV /{}", path);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
+        };
+        let origin = public_origin(&state, &headers);
+        resolve(&db, &path, &state.base_path, &origin)
+    };
+    let (collection, resource_name) = match resolved {
+        Ok(Some(r)) => r,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Collection not found").into_response(),
+        Err(e) => {
+            tracing::error!("Error serving CalDAV: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
+        }
+    };
+    let resource = match &resource_name {
+        Some(name) => match collection.resources.iter().find(|r| &r.name == name) {
+            Some(r) => Some(r),
+            None => return (StatusCode::NOT_FOUND, "Event not found").into_response(),
+        },
+        None => None,
+    };
+
+    match method.as_str() {
+        "PROPFIND" => propfind(&collection, resource, &headers),
+        "REPORT" => report(&collection, &body),
+        "GET" | "HEAD" => {
+            let head = method == Method::HEAD;
+            match resource {
+                Some(r) => get(&r.ics, &r.etag, &headers, head),
+                None => get(
+                    &collection.ics,
+                    &format!("\"{}\"", collection.ctag),
+                    &headers,
+                    head,
+                ),
+            }
+        }
+        _ => Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header(header::ALLOW, ALLOW)
+            .body(Body::empty())
+            .unwrap_or_else(|_| StatusCode::METHOD_NOT_ALLOWED.into_response()),
+    }
+}
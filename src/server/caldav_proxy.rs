@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use super::auth::extract_credentials;
+use crate::api::AppState;
+use crate::db;
+use crate::http_clients;
+
+const CACHE_TTL: Duration = Duration::from_secs(60);
+const MAX_CACHE_ENTRIES: usize = 1000;
+const FORWARDED_REQUEST_HEADERS: &[&str] = &["depth", "content-type", "prefer", "brief"];
+const FORWARDED_RESPONSE_HEADERS: &[&str] = &["content-type", "etag", "last-modified", "dav"];
+
+#[derive(Clone)]
+pub struct CachedResponse {
+    status: StatusCode,
+    headers: Vec<(String, HeaderValue)>,
+    body: Bytes,
+    fetched_at: Instant,
+}
+
+pub type ProxyCache = Arc<Mutex<HashMap<String, CachedResponse>>>;
+
+pub fn new_cache() -> ProxyCache {
+    Arc::new(Mutex::new(HashMap::new()))
+}
+
+fn proxy_token(headers: &HeaderMap) -> Option<String> {
+    if let Some(token) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    {
+        return Some(token.trim().to_owned());
+    }
+    extract_credentials(headers).map(|(_, pass)| pass)
+}
+
+fn unauthorized() -> Response {
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .header(
+            header::WWW_AUTHENTICATE,
+            "Basic realm=\"caldav-ics-sync proxy\"",
+        )
+        .body(Body::from("Unauthorized"))
+        .unwrap_or_else(|_| StatusCode::UNAUTHORIZED.into_response())
+}
+
+fn upstream_url(
+    caldav_url: &str,
+    rest: Option<&str>,
+    query: Option<&str>,
+) -> anyhow::Result<String> {
+    let base = reqwest::Url::parse(caldav_url)?;
+    let mut url = match rest {
+        Some(rest) => base.join(&format!("/{}", rest))?,
+        None => base.clone(),
+    };
+    // The source's credentials go along, so never leave its server.
+    anyhow::ensure!(
+        url.origin() == base.origin(),
+        "Proxied path leaves the upstream server"
+    );
+    url.set_query(query);
+    Ok(url.to_string())
+}
+
+// `//host/x` would be joined as a scheme-relative URL and `\` is read as
+// `/` by URL parsers, so both are rejected along with `..`.
+fn valid_rest(rest: &str) -> bool {
+    !rest.starts_with('/') && !rest.contains("//") && !rest.contains('\\') && !rest.contains("..")
+}
+
+fn upstream_origin(caldav_url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(caldav_url).ok()?;
+    Some(url.origin().ascii_serialization())
+}
+
+// Upstream hrefs are absolute paths (or URLs) on the CalDAV server; map them
+// under the proxy prefix so clients keep talking to us.
+pub fn rewrite_hrefs(body: &str, origin: &str, prefix: &str) -> String {
+    let mut out = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(pos) = rest.find("href>") {
+        let (head, tail) = rest.split_at(pos + "href>".len());
+        out.push_str(head);
+        rest = tail;
+        let is_closing = head
+            .rfind('<')
+            .is_some_and(|lt| head[lt + 1..].starts_with('/'));
+        if is_closing {
+            continue;
+        }
+        let end = rest.find('<').unwrap_or(rest.len());
+        let href = &rest[..end];
+        let path = href.strip_prefix(origin).unwrap_or(href);
+        if path.starts_with('/') {
+            out.push_str(prefix);
+            out.push_str(path);
+        } else {
+            out.push_str(href);
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn cached_response(cached: &CachedResponse) -> Response {
+    let mut builder = Response::builder().status(cached.status);
+    for (name, value) in &cached.headers {
+        builder = builder.header(name.as_str(), value.clone());
+    }
+    builder
+        .body(Body::from(cached.body.clone()))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+fn cache_lookup(cache: &ProxyCache, key: &str, allow_stale: bool) -> Option<CachedResponse> {
+    let map = cache.lock().ok()?;
+    map.get(key)
+        .filter(|c| allow_stale || c.fetched_at.elapsed() < CACHE_TTL)
+        .cloned()
+}
+
+fn cache_store(cache: &ProxyCache, key: String, entry: CachedResponse) {
+    let Ok(mut map) = cache.lock() else {
+        return;
+    };
+    if map.len() >= MAX_CACHE_ENTRIES && !map.contains_key(&key) {
+        map.retain(|_, c| c.fetched_at.elapsed() < CACHE_TTL);
+        if map.len() >= MAX_CACHE_ENTRIES
+            && let Some(oldest) = map
+                .iter()
+                .min_by_key(|(_, c)| c.fetched_at)
+                .map(|(k, _)| k.clone())
+        {
+            map.remove(&oldest);
+        }
+    }
+    map.insert(key, entry);
+}
+
+async fn forward(
+    state: &AppState,
+    source: &db::Source,
+    url: &str,
+    method: &Method,
+    headers: &HeaderMap,
+    body: Bytes,
+) -> anyhow::Result<CachedResponse> {
+    let client = http_clients::get(
+        &state.http_clients,
+        url,
+        &source.username,
+        &source.password,
+        &source.user_agent,
+        &source.custom_headers,
+    )?;
+    let mut req = client
+        .request(
+            reqwest::Method::from_bytes(method.as_str().as_bytes())?,
+            url,
+        )
+        .body(body);
+    for name in FORWARDED_REQUEST_HEADERS {
+        if let Some(value) = headers.get(*name) {
+            req = req.header(*name, value.as_bytes());
+        }
+    }
+    let res = req.send().await?;
+    let status = StatusCode::from_u16(res.status().as_u16())?;
+    let mut kept = Vec::new();
+    for name in FORWARDED_RESPONSE_HEADERS {
+        if let Some(value) = res.headers().get(*name)
+            && let Ok(value) = HeaderValue::from_bytes(value.as_bytes())
+        {
+            kept.push((name.to_string(), value));
+        }
+    }
+    let is_xml = kept
+        .iter()
+        .any(|(n, v)| n == "content-type" && v.to_str().is_ok_and(|v| v.contains("xml")));
+    let mut body = res.bytes().await?;
+    if is_xml
+        && let Ok(text) = std::str::from_utf8(&body)
+        && let Some(origin) = upstream_origin(&source.caldav_url)
+    {
+        body = Bytes::from(rewrite_hrefs(
+            text,
+            &origin,
+            &format!("{}/caldav-proxy/{}", state.base_path, source.id),
+        ));
+    }
+    Ok(CachedResponse {
+        status,
+        headers: kept,
+        body,
+        fetched_at: Instant::now(),
+    })
+}
+
+async fn proxy(
+    state: AppState,
+    id: i64,
+    rest: Option<String>,
+    method: Method,
+    headers: HeaderMap,
+    query: Option<String>,
+    body: Bytes,
+) -> Response {
+    if !matches!(
+        method.as_str(),
+        "OPTIONS" | "GET" | "HEAD" | "PROPFIND" | "REPORT"
+    ) {
+        return (StatusCode::METHOD_NOT_ALLOWED, "CalDAV proxy is read-only").into_response();
+    }
+
+    let source = {
+        let Ok(db) = state.db.lock() else {
+            tracing::error!("DB lock poisoned in CalDAV proxy for source {}", id);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
+        };
+        match db::get_source(&db, id) {
+            Ok(Some(s)) => s,
+            Ok(None) => return (StatusCode::NOT_FOUND, "Source not found").into_response(),
+            Err(e) => {
+                tracing::error!("Error loading source {} for CalDAV proxy: {}", id, e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
+            }
+        }
+    };
+    let Some(expected) = source.proxy_token.as_deref() else {
+        return (StatusCode::NOT_FOUND, "CalDAV proxy not enabled").into_response();
+    };
+    let Some(token) = proxy_token(&headers) else {
+        return unauthorized();
+    };
+    if token.as_bytes().ct_eq(expected.as_bytes()).unwrap_u8() != 1 {
+        return unauthorized();
+    }
+
+    if rest.as_deref().is_some_and(|r| !valid_rest(r)) {
+        return (StatusCode::BAD_REQUEST, "Invalid path").into_response();
+    }
+    let url = match upstream_url(&source.caldav_url, rest.as_deref(), query.as_deref()) {
+        Ok(u) => u,
+        Err(e) => {
+            tracing::error!("Invalid upstream URL for source {}: {}", id, e);
+            return (StatusCode::BAD_GATEWAY, "Invalid upstream URL").into_response();
+        }
+    };
+
+    let depth = headers
+        .get("depth")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let key = format!(
+        "{}|{}|{}|{}|{:x}",
+        id,
+        method,
+        url,
+        depth,
+        Sha256::digest(&body)
+    );
+    if let Some(cached) = cache_lookup(&state.proxy_cache, &key, false) {
+        return cached_response(&cached);
+    }
+
+    match forward(&state, &source, &url, &method, &headers, body).await {
+        Ok(fetched) if fetched.status.is_server_error() => {
+            match cache_lookup(&state.proxy_cache, &key, true) {
+                Some(stale) => cached_response(&stale),
+                None => cached_response(&fetched),
+            }
+        }
+        Ok(fetched) => {
+            if fetched.status.is_success() {
+                cache_store(&state.proxy_cache, key, fetched.clone());
+            }
+            cached_response(&fetched)
+        }
+        Err(e) => {
+            tracing::warn!("CalDAV proxy request to {} failed: {}", url, e);
+            match cache_lookup(&state.proxy_cache, &key, true) {
+                Some(stale) => cached_response(&stale),
+                None => (StatusCode::BAD_GATEWAY, "Upstream not available").into_response(),
+            }
+        }
+    }
+}
+
+pub async fn proxy_root(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    method: Method,
+    headers: HeaderMap,
+    uri: axum::http::Uri,
+    body: Bytes,
+) -> Response {
+    let query = uri.query().map(str::to_owned);
+    proxy(state, id, None, method, headers, query, body).await
+}
+
+pub async fn proxy_path(
+    State(state): State<AppState>,
+    Path((id, rest)): Path<(i64, String)>,
+    method: Method,
+    headers: HeaderMap,
+    uri: axum::http::Uri,
+    body: Bytes,
+) -> Response {
+    let query = uri.query().map(str::to_owned);
+    proxy(state, id, Some(rest), method, headers, query, body).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_absolute_paths_and_urls_under_prefix() {
+        let body = "<d:multistatus xmlns:d=\"DAV:\"><d:response><d:href>/dav/cal/</d:href></d:response>\
+                    <d:response><D:href>https://up.example.com/dav/cal/1.ics</D:href></d:response>\
+                    <d:response><href>relative.ics</href></d:response></d:multistatus>";
+        let out = rewrite_hrefs(body, "https://up.example.com", "/caldav-proxy/7");
+        assert!(out.contains("<d:href>/caldav-proxy/7/dav/cal/</d:href>"));
+        assert!(out.contains("<D:href>/caldav-proxy/7/dav/cal/1.ics</D:href>"));
+        assert!(out.contains("<href>relative.ics</href>"));
+    }
+
+    #[test]
+    fn upstream_url_maps_rest_onto_upstream_origin() {
+        assert_eq!(
+            upstream_url("https://up.example.com/dav/cal/", None, None).unwrap(),
+            "https://up.example.com/dav/cal/"
+        );
+        assert_eq!(
+            upstream_url(
+                "https://up.example.com/dav/cal/",
+                Some("dav/cal/1.ics"),
+                Some("x=1")
+            )
+            .unwrap(),
+            "https://up.example.com/dav/cal/1.ics?x=1"
+        );
+    }
+
+    #[test]
+    fn upstream_url_stays_on_the_upstream_origin() {
+        for rest in [
+            "/evil.example/x",
+            "a//evil.example",
+            "\\evil.example/x",
+            "a/../b",
+        ] {
+            assert!(!valid_rest(rest), "{}", rest);
+        }
+        assert!(valid_rest("dav/cal/1.ics"));
+        assert!(
+            upstream_url("https://up.example.com/dav/", Some("/evil.example/x"), None).is_err()
+        );
+        assert!(
+            upstream_url(
+                "https://up.example.com/dav/",
+                Some("\\evil.example/x"),
+                None
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn full_cache_of_fresh_entries_evicts_the_oldest() {
+        let cache = new_cache();
+        let start = Instant::now();
+        let entry = |i: u64| CachedResponse {
+            status: StatusCode::OK,
+            headers: Vec::new(),
+            body: Bytes::new(),
+            fetched_at: start + Duration::from_millis(i),
+        };
+        for i in 0..MAX_CACHE_ENTRIES as u64 {
+            cache_store(&cache, i.to_string(), entry(i));
+        }
+        cache_store(&cache, "new".into(), entry(5000));
+        let map = cache.lock().unwrap();
+        assert_eq!(map.len(), MAX_CACHE_ENTRIES);
+        assert!(!map.contains_key("0"));
+        assert!(map.contains_key("1") && map.contains_key("new"));
+    }
+}
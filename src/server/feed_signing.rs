@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ed25519_dalek::{Signer, SigningKey};
+use serde::Serialize;
+
+pub const PUBLIC_KEY_PATH: &str = "/.well-known/ics-signing-key";
+pub const SIGNATURE_SUFFIX: &str = ".sig";
+
+// Signs served feeds with a configured Ed25519 key.
+#[derive(Clone)]
+pub struct FeedSigner {
+    key: Arc<SigningKey>,
+}
+
+#[derive(Serialize)]
+struct PublicKey {
+    algorithm: &'static str,
+    public_key: String,
+}
+
+impl FeedSigner {
+    // `seed` is the base64-encoded 32-byte Ed25519 secret key.
+    pub fn from_base64(seed: &str) -> Result<Self> {
+        let bytes = STANDARD
+            .decode(seed.trim())
+            .context("FEED_SIGNING_KEY must be base64")?;
+        let seed: [u8; 32] = bytes.try_into().map_err(|b: Vec<u8>| {
+            anyhow::anyhow!("FEED_SIGNING_KEY must decode to 32 bytes, got {}", b.len())
+        })?;
+        Ok(Self {
+            key: Arc::new(SigningKey::from_bytes(&seed)),
+        })
+    }
+
+    pub fn public_key(&self) -> String {
+        STANDARD.encode(self.key.verifying_key().as_bytes())
+    }
+
+    // Returns the base64 detached signature over exactly the served bytes.
+    pub fn sign(&self, body: &[u8]) -> String {
+        STANDARD.encode(self.key.sign(body).to_bytes())
+    }
+}
+
+pub async fn public_key(State(state): State<crate::api::AppState>) -> Response {
+    match &state.feed_signer {
+        Some(signer) => Json(PublicKey {
+            algorithm: "Ed25519",
+            public_key: signer.public_key(),
+        })
+        .into_response(),
+        None => (StatusCode::NOT_FOUND, "Feed signing is not enabled").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    #[test]
+    fn signatures_verify_against_the_public_key() {
+        let signer = FeedSigner::from_base64(&STANDARD.encode([7u8; 32])).unwrap();
+        let body = b"BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n";
+
+        let public: [u8; 32] = STANDARD
+            .decode(signer.public_key())
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let signature: [u8; 64] = STANDARD
+            .decode(signer.sign(body))
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let key = VerifyingKey::from_bytes(&public).unwrap();
+        assert!(key.verify(body, &Signature::from_bytes(&signature)).is_ok());
+        assert!(
+            key.verify(b"tampered", &Signature::from_bytes(&signature))
+                .is_err()
+        );
+
+        assert!(FeedSigner::from_base64("c2hvcnQ=").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use axum::{
+    http::{Method, StatusCode, Uri, header},
+    response::{IntoResponse, Response},
+};
+
+#[cfg(feature = "embed-frontend")]
+mod embedded {
+    include!(concat!(env!("OUT_DIR"), "/frontend_assets.rs"));
+}
+
+#[cfg(feature = "embed-frontend")]
+static ASSETS: &[(&str, &[u8])] = embedded::ASSETS;
+#[cfg(not(feature = "embed-frontend"))]
+static ASSETS: &[(&str, &[u8])] = &[];
+
+pub const EMBEDDED: bool = cfg!(feature = "embed-frontend");
+
+// Where requests that don't hit a backend route go.
+#[derive(Debug, Clone)]
+pub enum Frontend {
+    Proxy(String),
+    Embedded,
+}
+
+fn find<'a>(assets: &'a [(&str, &[u8])], name: &str) -> Option<(&'a str, &'a [u8])> {
+    assets
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(n, b)| (*n, *b))
+}
+
+// Resolves a request path against the export the way `trailingSlash: true` lays
+// it out; anything without a file extension falls back to the app shell.
+fn resolve<'a>(assets: &'a [(&str, &[u8])], path: &str) -> Option<(&'a str, &'a [u8])> {
+    let path = path.trim_start_matches('/');
+    let dir = path.trim_end_matches('/');
+    let candidates = [
+        path.to_owned(),
+        format!("{}/index.html", dir)
+            .trim_start_matches('/')
+            .to_owned(),
+        format!("{}.html", dir),
+    ];
+    if let Some(hit) = candidates
+        .iter()
+        .filter(|c| !c.is_empty() && !c.ends_with('/'))
+        .find_map(|c| find(assets, c))
+    {
+        return Some(hit);
+    }
+    let last = dir.rsplit('/').next().unwrap_or_default();
+    match last.contains('.') {
+        true => None,
+        false => find(assets, "index.html"),
+    }
+}
+
+pub async fn serve_embedded(method: Method, uri: Uri) -> Response {
+    if method != Method::GET && method != Method::HEAD {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+    let Some((name, body)) = resolve(ASSETS, uri.path()) else {
+        return match find(ASSETS, "404.html") {
+            Some((_, body)) => (
+                StatusCode::NOT_FOUND,
+                [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+                body,
+            )
+                .into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        };
+    };
+    let content_type = match mime_guess::from_path(name).first_or_octet_stream() {
+        m if m.type_() == mime_guess::mime::TEXT || m.subtype() == "javascript" => {
+            format!("{}; charset=utf-8", m)
+        }
+        m => m.to_string(),
+    };
+    // Next.js fingerprints everything under _next/static.
+    let cache_control = match name.starts_with("_next/static/") {
+        true => "public, max-age=31536000, immutable",
+        false => "no-cache",
+    };
+    (
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, cache_control.to_owned()),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_export_paths_with_spa_fallback() {
+        let assets: &[(&str, &[u8])] = &[
+            ("index.html", b"shell"),
+            ("settings/index.html", b"settings"),
+            ("_next/static/app.js", b"js"),
+        ];
+        let name = |path| resolve(assets, path).map(|(n, _)| n);
+        assert_eq!(name("/"), Some("index.html"));
+        assert_eq!(name("/settings/"), Some("settings/index.html"));
+        assert_eq!(name("/settings"), Some("settings/index.html"));
+        assert_eq!(name("/_next/static/app.js"), Some("_next/static/app.js"));
+        assert_eq!(name("/sources/12/"), Some("index.html"));
+        assert_eq!(name("/_next/static/missing.js"), None);
+    }
+}
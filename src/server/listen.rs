@@ -0,0 +1,144 @@
+use std::net::{Ipv4Addr, SocketAddr};
+
+use anyhow::{Context, Result};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::either::Either;
+
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+// Socket peers have no IP; report them as loopback so the access log and
+// AUTH_TRUSTED_PROXIES treat a local reverse proxy like one on 127.0.0.1.
+const UNIX_PEER: SocketAddr = SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+// systemd passes activated sockets starting at this descriptor.
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: i32 = 3;
+
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, std::path::PathBuf),
+}
+
+impl Listener {
+    pub async fn tcp(addr: &str) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind {}", addr))?;
+        Ok(Self::Tcp(listener))
+    }
+
+    // A leftover socket file from an unclean shutdown would make bind fail.
+    #[cfg(unix)]
+    pub fn unix(path: &str) -> Result<Self> {
+        use std::os::unix::fs::FileTypeExt;
+        if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+            std::fs::remove_file(path)
+                .with_context(|| format!("Failed to remove stale socket '{}'", path))?;
+        }
+        let listener =
+            UnixListener::bind(path).with_context(|| format!("Failed to bind '{}'", path))?;
+        Ok(Self::Unix(listener, path.into()))
+    }
+
+    // Sockets handed over by systemd (LISTEN_PID/LISTEN_FDS), if any.
+    #[cfg(unix)]
+    pub fn from_systemd() -> Result<Option<Self>> {
+        use std::os::fd::{FromRawFd, IntoRawFd};
+
+        let pid = std::env::var("LISTEN_PID").ok();
+        if pid.and_then(|p| p.parse::<u32>().ok()) != Some(std::process::id()) {
+            return Ok(None);
+        }
+        let fds = std::env::var("LISTEN_FDS")
+            .ok()
+            .and_then(|n| n.parse::<i32>().ok())
+            .unwrap_or(0);
+        if fds < 1 {
+            return Ok(None);
+        }
+        if fds > 1 {
+            tracing::warn!("systemd passed {} sockets; using only the first", fds);
+        }
+        // SAFETY: LISTEN_PID matches this process, so systemd guarantees the
+        // descriptor is an open listening socket that nothing else owns.
+        let unix = unsafe { std::os::unix::net::UnixListener::from_raw_fd(SD_LISTEN_FDS_START) };
+        // getsockname only succeeds here for AF_UNIX sockets.
+        if unix.local_addr().is_ok() {
+            unix.set_nonblocking(true)?;
+            let listener = UnixListener::from_std(unix)?;
+            return Ok(Some(Self::Unix(listener, Default::default())));
+        }
+        // SAFETY: the descriptor was released from the UnixListener just above.
+        let tcp = unsafe { std::net::TcpListener::from_raw_fd(unix.into_raw_fd()) };
+        tcp.set_nonblocking(true)?;
+        Ok(Some(Self::Tcp(TcpListener::from_std(tcp)?)))
+    }
+
+    #[cfg(not(unix))]
+    pub fn unix(_path: &str) -> Result<Self> {
+        anyhow::bail!("LISTEN_UNIX_SOCKET is only supported on Unix")
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_systemd() -> Result<Option<Self>> {
+        Ok(None)
+    }
+
+    pub fn describe(&self, scheme: &str) -> String {
+        match self {
+            Self::Tcp(l) => match l.local_addr() {
+                Ok(addr) => format!("{}://{}", scheme, addr),
+                Err(_) => format!("{} socket", scheme),
+            },
+            #[cfg(unix)]
+            Self::Unix(_, path) if path.as_os_str().is_empty() => {
+                format!("{} over a systemd socket", scheme)
+            }
+            #[cfg(unix)]
+            Self::Unix(_, path) => format!("{} over unix:{}", scheme, path.display()),
+        }
+    }
+
+    // The socket file this process created, to remove on shutdown.
+    pub fn socket_file(&self) -> Option<std::path::PathBuf> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(_, path) if !path.as_os_str().is_empty() => Some(path.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(unix)]
+type Stream = Either<TcpStream, UnixStream>;
+#[cfg(not(unix))]
+type Stream = Either<TcpStream, TcpStream>;
+
+impl axum::serve::Listener for Listener {
+    type Io = Stream;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self {
+            Self::Tcp(l) => {
+                let (io, addr) = axum::serve::Listener::accept(l).await;
+                (Either::Left(io), addr)
+            }
+            #[cfg(unix)]
+            Self::Unix(l, _) => {
+                let (io, _) = axum::serve::Listener::accept(l).await;
+                (Either::Right(io), UNIX_PEER)
+            }
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        match self {
+            Self::Tcp(l) => l.local_addr(),
+            #[cfg(unix)]
+            Self::Unix(..) => Ok(UNIX_PEER),
+        }
+    }
+}
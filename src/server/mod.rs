@@ -1,8 +1,21 @@
 use axum::Router;
 
+pub mod access_log;
+pub mod agenda;
 pub mod auth;
+pub mod auth_policy;
+pub mod authenticators;
+pub mod caldav;
+pub mod caldav_proxy;
+pub mod feed_signing;
+pub mod frontend;
+pub mod listen;
+pub mod oidc;
+pub mod ranges;
 pub mod route_builder;
+pub mod subscription;
+pub mod tls;
 
-pub async fn build_router(state: crate::api::AppState, proxy_url: &str) -> Router {
-    route_builder::register_routes(state, proxy_url).await
+pub async fn build_router(state: crate::api::AppState, frontend: frontend::Frontend) -> Router {
+    route_builder::register_routes(state, frontend).await
 }
@@ -0,0 +1,115 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{DecodingKey, Validation, decode, decode_header};
+use serde::Deserialize;
+use tokio::sync::RwLock;
+
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize)]
+struct Discovery {
+    jwks_uri: String,
+}
+
+pub struct OidcVerifier {
+    issuer: String,
+    audience: Option<String>,
+    jwks_url: Option<String>,
+    username_claim: String,
+    client: reqwest::Client,
+    jwks: RwLock<Option<(JwkSet, Instant)>>,
+}
+
+impl OidcVerifier {
+    pub fn new(
+        issuer: String,
+        audience: Option<String>,
+        jwks_url: Option<String>,
+        username_claim: Option<String>,
+    ) -> Self {
+        Self {
+            issuer,
+            audience,
+            jwks_url,
+            username_claim: username_claim.unwrap_or_else(|| "preferred_username".into()),
+            client: reqwest::Client::new(),
+            jwks: RwLock::new(None),
+        }
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    async fn jwks_uri(&self) -> Result<String> {
+        if let Some(url) = &self.jwks_url {
+            return Ok(url.clone());
+        }
+        let discovery_url = format!(
+            "{}/.well-known/openid-configuration",
+            self.issuer.trim_end_matches('/')
+        );
+        let discovery: Discovery = self
+            .client
+            .get(&discovery_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("Failed to read OIDC discovery at {}", discovery_url))?;
+        Ok(discovery.jwks_uri)
+    }
+
+    async fn keys(&self, refresh: bool) -> Result<JwkSet> {
+        if let Some((set, fetched_at)) = self.jwks.read().await.as_ref()
+            && (!refresh || fetched_at.elapsed() < MIN_REFRESH_INTERVAL)
+        {
+            return Ok(set.clone());
+        }
+        let uri = self.jwks_uri().await?;
+        let set: JwkSet = self
+            .client
+            .get(&uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("Failed to read JWKS at {}", uri))?;
+        *self.jwks.write().await = Some((set.clone(), Instant::now()));
+        Ok(set)
+    }
+
+    // Validates signature, issuer, audience and expiry; returns the username claim.
+    pub async fn verify(&self, token: &str) -> Result<String> {
+        let header = decode_header(token)?;
+        let find = |set: &JwkSet| match &header.kid {
+            Some(kid) => set.find(kid).cloned(),
+            None if set.keys.len() == 1 => set.keys.first().cloned(),
+            None => None,
+        };
+        let jwk = match find(&self.keys(false).await?) {
+            Some(jwk) => jwk,
+            // Unknown key id: the provider may have rotated keys.
+            None => find(&self.keys(true).await?).ok_or_else(|| anyhow!("No matching JWK"))?,
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.issuer]);
+        match &self.audience {
+            Some(aud) => validation.set_audience(&[aud]),
+            None => validation.validate_aud = false,
+        }
+        let claims =
+            decode::<serde_json::Value>(token, &DecodingKey::from_jwk(&jwk)?, &validation)?.claims;
+        claims
+            .get(&self.username_claim)
+            .or_else(|| claims.get("sub"))
+            .and_then(|v| v.as_str())
+            .map(str::to_owned)
+            .ok_or_else(|| anyhow!("Token has no '{}' claim", self.username_claim))
+    }
+}
@@ -0,0 +1,141 @@
+use std::ops::Range;
+
+use axum::http::{HeaderMap, header};
+use chrono::{DateTime, NaiveDateTime};
+use sha2::{Digest, Sha256};
+
+// Strong validator over the exact bytes served, so byte ranges of two
+// responses with the same tag can be stitched together.
+pub fn etag(body: &[u8]) -> String {
+    format!("\"{}\"", &format!("{:x}", Sha256::digest(body))[..32])
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+pub fn http_date(time: NaiveDateTime) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+// If-None-Match wins over If-Modified-Since when a client sends both.
+pub fn not_modified(headers: &HeaderMap, etag: &str, last_modified: Option<NaiveDateTime>) -> bool {
+    if let Some(tags) = header_str(headers, header::IF_NONE_MATCH) {
+        return tags
+            .split(',')
+            .map(|t| t.trim().trim_start_matches("W/"))
+            .any(|t| t == "*" || t == etag);
+    }
+    let since = header_str(headers, header::IF_MODIFIED_SINCE)
+        .and_then(|v| DateTime::parse_from_rfc2822(v).ok());
+    match (since, last_modified) {
+        (Some(since), Some(modified)) => modified <= since.naive_utc(),
+        _ => false,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RangeRequest {
+    Full,
+    Partial(Range<usize>),
+    Unsatisfiable,
+}
+
+// Only single `bytes=` ranges are honoured; anything else, or an If-Range that
+// doesn't match the current tag, gets the whole body as RFC 9110 allows.
+pub fn requested_range(headers: &HeaderMap, etag: &str, len: usize) -> RangeRequest {
+    let Some(spec) = header_str(headers, header::RANGE).and_then(|v| v.strip_prefix("bytes="))
+    else {
+        return RangeRequest::Full;
+    };
+    if header_str(headers, header::IF_RANGE).is_some_and(|v| v.trim() != etag) {
+        return RangeRequest::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let range = match (start.parse::<usize>(), end.parse::<usize>()) {
+        // bytes=-N: the last N bytes.
+        _ if start.is_empty() => match end.parse::<usize>() {
+            Ok(0) => return RangeRequest::Unsatisfiable,
+            Ok(n) => len.saturating_sub(n)..len,
+            Err(_) => return RangeRequest::Full,
+        },
+        (Ok(start), _) if end.is_empty() => start..len,
+        (Ok(start), Ok(end)) if start <= end => start..len.min(end + 1),
+        _ => return RangeRequest::Full,
+    };
+    if range.start >= len {
+        return RangeRequest::Unsatisfiable;
+    }
+    RangeRequest::Partial(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.clone(), v.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_single_byte_ranges() {
+        let tag = etag(b"0123456789");
+        let range = |spec: &str| requested_range(&headers(&[(header::RANGE, spec)]), &tag, 10);
+        assert_eq!(range("bytes=2-4"), RangeRequest::Partial(2..5));
+        assert_eq!(range("bytes=7-"), RangeRequest::Partial(7..10));
+        assert_eq!(range("bytes=-3"), RangeRequest::Partial(7..10));
+        assert_eq!(range("bytes=5-99"), RangeRequest::Partial(5..10));
+        assert_eq!(range("bytes=10-"), RangeRequest::Unsatisfiable);
+        assert_eq!(range("bytes=0-1,4-5"), RangeRequest::Full);
+        assert_eq!(range("items=0-1"), RangeRequest::Full);
+
+        let stale = headers(&[(header::RANGE, "bytes=2-4"), (header::IF_RANGE, "\"old\"")]);
+        assert_eq!(requested_range(&stale, &tag, 10), RangeRequest::Full);
+        let current = headers(&[(header::RANGE, "bytes=2-4"), (header::IF_RANGE, &tag)]);
+        assert_eq!(
+            requested_range(&current, &tag, 10),
+            RangeRequest::Partial(2..5)
+        );
+
+        assert!(not_modified(
+            &headers(&[(header::IF_NONE_MATCH, &format!("\"x\", {}", tag))]),
+            &tag,
+            None
+        ));
+        assert!(!not_modified(&HeaderMap::new(), &tag, None));
+    }
+
+    #[test]
+    fn if_modified_since_compares_to_last_change() {
+        let modified = chrono::NaiveDate::from_ymd_opt(2026, 3, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        assert_eq!(http_date(modified), "Sun, 01 Mar 2026 12:00:00 GMT");
+        let since = |v: &str| headers(&[(header::IF_MODIFIED_SINCE, v)]);
+        assert!(not_modified(
+            &since("Sun, 01 Mar 2026 12:00:00 GMT"),
+            "\"a\"",
+            Some(modified)
+        ));
+        assert!(!not_modified(
+            &since("Sun, 01 Mar 2026 11:59:59 GMT"),
+            "\"a\"",
+            Some(modified)
+        ));
+        // A mismatching tag means changed, whatever the date says.
+        let both = headers(&[
+            (header::IF_NONE_MATCH, "\"b\""),
+            (header::IF_MODIFIED_SINCE, "Sun, 01 Mar 2026 12:00:00 GMT"),
+        ]);
+        assert!(!not_modified(&both, "\"a\"", Some(modified)));
+    }
+}
@@ -1,16 +1,94 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{
     Router,
-    extract::{Request, State},
-    http::StatusCode,
+    body::Body,
+    extract::{ConnectInfo, OriginalUri, Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{any, get},
 };
-use hyper_util::client::legacy::Client;
-use hyper_util::rt::TokioExecutor;
+use hyper_util::client::legacy::{Client, connect::HttpConnector};
 
-async fn proxy_to_nextjs(State(proxy_url): State<Arc<String>>, mut req: Request) -> Response {
+use crate::availability::{AVAILABILITY_SUFFIX, WorkingHours};
+
+use super::feed_signing::{FeedSigner, PUBLIC_KEY_PATH, SIGNATURE_SUFFIX};
+use super::frontend::Frontend;
+use super::ranges::{self, RangeRequest};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+
+// A Next.js response that hasn't started within this is reported as 504.
+const PROXY_TIMEOUT: Duration = Duration::from_secs(30);
+
+// RFC 7230 section 6.1, plus anything the Connection header names.
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+// Shared by all proxied requests so upstream connections are pooled.
+#[derive(Clone)]
+struct NextjsProxy {
+    url: Arc<String>,
+    client: Client<HttpConnector, Body>,
+}
+
+impl NextjsProxy {
+    fn new(url: String) -> Self {
+        Self {
+            url: Arc::new(url),
+            client: Client::builder(TokioExecutor::new()).build_http(),
+        }
+    }
+}
+
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    let listed: Vec<HeaderName> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect();
+    for name in listed {
+        headers.remove(name);
+    }
+    for name in HOP_BY_HOP {
+        headers.remove(*name);
+    }
+}
+
+fn set_forwarding_headers(headers: &mut HeaderMap, peer: Option<SocketAddr>) {
+    if let Some(peer) = peer {
+        let forwarded_for = match headers.get("x-forwarded-for").and_then(|v| v.to_str().ok()) {
+            Some(prior) => format!("{}, {}", prior, peer.ip()),
+            None => peer.ip().to_string(),
+        };
+        if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+            headers.insert("x-forwarded-for", value);
+        }
+    }
+    if !headers.contains_key("x-forwarded-host")
+        && let Some(host) = headers.get(header::HOST).cloned()
+    {
+        headers.insert("x-forwarded-host", host);
+    }
+    if !headers.contains_key("x-forwarded-proto") {
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("http"));
+    }
+}
+
+async fn proxy_to_nextjs(State(proxy): State<NextjsProxy>, mut req: Request) -> Response {
+    let proxy_url = &proxy.url;
     let proxy_uri = match proxy_url.parse::<hyper::Uri>() {
         Ok(uri) => uri,
         Err(e) => {
@@ -23,12 +101,17 @@ async fn proxy_to_nextjs(State(proxy_url): State<Arc<String>>, mut req: Request)
         }
     };
 
-    let path = req.uri().path();
-    let path_query = req
-        .uri()
+    // Under BASE_PATH the frontend is built with the same base path, so it gets
+    // the full original path rather than the one relative to the prefix.
+    let original = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|o| o.0.clone())
+        .unwrap_or_else(|| req.uri().clone());
+    let path_query = original
         .path_and_query()
         .map(|pq| pq.as_str())
-        .unwrap_or(path);
+        .unwrap_or(original.path());
 
     let new_uri = format!("{}{}", proxy_url, path_query);
     match new_uri.parse() {
@@ -39,6 +122,20 @@ async fn proxy_to_nextjs(State(proxy_url): State<Arc<String>>, mut req: Request)
         }
     }
 
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|c| c.0);
+    let upgrade = req.headers().get(header::UPGRADE).cloned();
+    strip_hop_by_hop(req.headers_mut());
+    set_forwarding_headers(req.headers_mut(), peer);
+    // Upgrade is hop-by-hop too, but this hop has to carry it for websockets.
+    if let Some(upgrade) = &upgrade {
+        req.headers_mut()
+            .insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+        req.headers_mut().insert(header::UPGRADE, upgrade.clone());
+    }
+
     if let Some(host) = proxy_uri.host() {
         let host_value = if let Some(port) = proxy_uri.port_u16() {
             format!("{}:{}", host, port)
@@ -50,46 +147,329 @@ async fn proxy_to_nextjs(State(proxy_url): State<Arc<String>>, mut req: Request)
         }
     }
 
-    let client = Client::builder(TokioExecutor::new()).build_http();
+    // Taken before forwarding, since hyper::upgrade::on removes it from the request.
+    let client_upgrade = upgrade.is_some().then(|| hyper::upgrade::on(&mut req));
 
-    match client.request(req).await {
-        Ok(response) => response.into_response(),
-        Err(e) => {
+    // Bodies stream through in both directions; only the wait for headers is bounded.
+    match tokio::time::timeout(PROXY_TIMEOUT, proxy.client.request(req)).await {
+        Ok(Ok(mut response)) => {
+            if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+                if let Some(client_upgrade) = client_upgrade {
+                    let upstream_upgrade = hyper::upgrade::on(&mut response);
+                    tokio::spawn(tunnel(client_upgrade, upstream_upgrade));
+                }
+            } else {
+                strip_hop_by_hop(response.headers_mut());
+            }
+            response.into_response()
+        }
+        Ok(Err(e)) => {
             tracing::error!("Proxy error: {}", e);
             (StatusCode::BAD_GATEWAY, "Server not available").into_response()
         }
+        Err(_) => {
+            tracing::error!("Proxy request to {} timed out", proxy_url);
+            (StatusCode::GATEWAY_TIMEOUT, "Server did not respond").into_response()
+        }
     }
 }
 
-fn ics_response(result: anyhow::Result<Option<String>>) -> Response {
-    match result {
-        Ok(Some(content)) => Response::builder()
-            .status(StatusCode::OK)
-            .header("Content-Type", "text/calendar")
-            .body(axum::body::Body::from(content))
-            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response()),
-        Ok(None) => (StatusCode::NOT_FOUND, "ICS not found").into_response(),
+// Copies bytes both ways once both sides have switched protocols (e.g. Next.js HMR websockets).
+async fn tunnel(client: hyper::upgrade::OnUpgrade, upstream: hyper::upgrade::OnUpgrade) {
+    let (client, upstream) = match tokio::try_join!(client, upstream) {
+        Ok(pair) => pair,
+        Err(e) => {
+            tracing::warn!("Proxy upgrade failed: {}", e);
+            return;
+        }
+    };
+    let mut client = TokioIo::new(client);
+    let mut upstream = TokioIo::new(upstream);
+    if let Err(e) = tokio::io::copy_bidirectional(&mut client, &mut upstream).await {
+        tracing::debug!("Proxied upgraded connection closed: {}", e);
+    }
+}
+
+// Includes BASE_PATH, so `{origin}/attachments/...` resolves under the prefix.
+pub(crate) fn request_origin(headers: &HeaderMap, base_path: &str) -> String {
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("http");
+    let host = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("localhost");
+    format!("{}://{}{}", scheme, host, base_path)
+}
+
+// The configured PUBLIC_BASE_URL, so links and signed bytes don't depend on
+// the client's Host header; the request's origin only without it.
+pub(crate) fn public_origin(state: &crate::api::AppState, headers: &HeaderMap) -> String {
+    match state.public_base_url.as_deref() {
+        Some(base) => base.to_owned(),
+        None => request_origin(headers, &state.base_path),
+    }
+}
+
+// The exact bytes served for a feed; signatures are computed over these.
+fn feed_body(content: &str, origin: &str) -> String {
+    crate::api::attachments::absolutize(content, origin)
+}
+
+// Feeds carry a strong ETag and a Last-Modified that only moves when the
+// content does, and honour conditional and single byte-range requests, so
+// large calendars can be revalidated or resumed. With a published
+// TTL, caches are told to hold the feed that long, matching the REFRESH-INTERVAL
+// inside it. Only the bytes actually sent count against the owner's quota.
+// Returns the response without its body and the byte range to send (empty for
+// 304), or a finished response.
+fn feed_response_parts(
+    db: &rusqlite::Connection,
+    path: &str,
+    etag: &str,
+    len: usize,
+    headers: &HeaderMap,
+) -> Result<(axum::http::response::Builder, std::ops::Range<usize>), Box<Response>> {
+    let caching = crate::db::feed_caching(db, path).unwrap_or_else(|e| {
+        tracing::error!("Error reading cache details for /{}: {}", path, e);
+        Default::default()
+    });
+    let last_modified = caching
+        .updated_at
+        .and_then(|t| chrono::NaiveDateTime::parse_from_str(&t, "%Y-%m-%d %H:%M:%S").ok());
+    let mut builder = Response::builder()
+        .header(header::ETAG, etag)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header("Content-Type", "text/calendar");
+    if let Some(modified) = last_modified {
+        builder = builder.header(header::LAST_MODIFIED, ranges::http_date(modified));
+    }
+    if let Some(minutes) = caching.ttl_minutes {
+        builder = builder.header(header::CACHE_CONTROL, format!("max-age={}", minutes * 60));
+    }
+
+    let (builder, range) = if ranges::not_modified(headers, etag, last_modified) {
+        (builder.status(StatusCode::NOT_MODIFIED), 0..0)
+    } else {
+        match ranges::requested_range(headers, etag, len) {
+            RangeRequest::Full => (builder.status(StatusCode::OK), 0..len),
+            RangeRequest::Partial(range) => (
+                builder.status(StatusCode::PARTIAL_CONTENT).header(
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", range.start, range.end - 1, len),
+                ),
+                range,
+            ),
+            RangeRequest::Unsatisfiable => {
+                return Err(Box::new(
+                    builder
+                        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                        .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                        .body(axum::body::Body::empty())
+                        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+                ));
+            }
+        }
+    };
+    if !range.is_empty() && !within_feed_quota(db, path, range.len()) {
+        return Err(Box::new(
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Daily feed bandwidth quota exceeded",
+            )
+                .into_response(),
+        ));
+    }
+    Ok((builder, range))
+}
+
+fn ics_response(
+    db: &rusqlite::Connection,
+    path: &str,
+    result: anyhow::Result<Option<String>>,
+    origin: &str,
+    headers: &HeaderMap,
+) -> Response {
+    let content = match result {
+        Ok(Some(content)) => content,
+        Ok(None) => return (StatusCode::NOT_FOUND, "ICS not found").into_response(),
         Err(e) => {
             tracing::error!("Error serving ICS: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response()
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
+        }
+    };
+    let mut body = feed_body(&content, origin).into_bytes();
+    let etag = ranges::etag(&body);
+    let (builder, range) = match feed_response_parts(db, path, &etag, body.len(), headers) {
+        Ok(parts) => parts,
+        Err(response) => return *response,
+    };
+    body.truncate(range.end);
+    body.drain(..range.start);
+    builder
+        .body(axum::body::Body::from(body))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+// Streams a feed kept in a file (see `feed_store`) without reading it into
+// memory. The file holds exactly the bytes served, so the ETag is the prefix
+// of its content hash that `ranges::etag` would compute.
+fn file_response(
+    db: &rusqlite::Connection,
+    path: &str,
+    hash: &str,
+    headers: &HeaderMap,
+) -> Response {
+    let opened = crate::feed_store::path(hash)
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))
+        .and_then(std::fs::File::open)
+        .and_then(|f| Ok((f.metadata()?.len() as usize, f)));
+    let (len, mut file) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            tracing::error!("Error opening feed file for /{}: {}", path, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
         }
+    };
+    let etag = format!("\"{}\"", &hash[..32.min(hash.len())]);
+    let (builder, range) = match feed_response_parts(db, path, &etag, len, headers) {
+        Ok(parts) => parts,
+        Err(response) => return *response,
+    };
+    if let Err(e) = std::io::Seek::seek(&mut file, std::io::SeekFrom::Start(range.start as u64)) {
+        tracing::error!("Error reading feed file for /{}: {}", path, e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
     }
+    let reader = tokio::io::AsyncReadExt::take(tokio::fs::File::from_std(file), range.len() as u64);
+    builder
+        .header(header::CONTENT_LENGTH, range.len())
+        .body(axum::body::Body::from_stream(
+            tokio_util::io::ReaderStream::new(reader),
+        ))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+// Counts a feed download against the owner's daily bandwidth quota.
+fn within_feed_quota(db: &rusqlite::Connection, path: &str, bytes: usize) -> bool {
+    let result = crate::db::feed_owner(db, path).and_then(|owner| match owner {
+        Some(id) => crate::db::record_feed_bytes(db, id, bytes as i64),
+        None => Ok(true),
+    });
+    result.unwrap_or_else(|e| {
+        tracing::error!("Error recording feed usage for /{}: {}", path, e);
+        true
+    })
+}
+
+// Serves `{feed}.sig` as the detached signature of `{feed}` when signing is enabled.
+fn signature_response(
+    signer: Option<&FeedSigner>,
+    path: &str,
+    origin: &str,
+    lookup: impl Fn(&str) -> anyhow::Result<Option<String>>,
+) -> Option<Response> {
+    let signer = signer?;
+    let feed = path.strip_suffix(SIGNATURE_SUFFIX)?;
+    let content = lookup(feed).ok().flatten()?;
+    let signature = signer.sign(feed_body(&content, origin).as_bytes());
+    Some(
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(axum::body::Body::from(signature))
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    )
+}
+
+// Serves `{feed}/part-{n}.ics` as the nth part of a feed whose source splits
+// it, as stored on the feed's last write; see `feed_limits::split`. Other
+// paths, including a feed that happens to be named like a part, fall through.
+fn part_response(
+    db: &rusqlite::Connection,
+    path: &str,
+    public: bool,
+    origin: &str,
+    headers: &HeaderMap,
+) -> Option<Response> {
+    let (feed, n) = crate::feed_limits::part_path(path)?;
+    match crate::db::get_feed_part_file_by_path(db, feed, n, public) {
+        Ok(Some(hash)) => return Some(file_response(db, feed, &hash, headers)),
+        Ok(None) => {}
+        Err(e) => tracing::error!("Error looking up feed part file for /{}: {}", path, e),
+    }
+    let part = crate::db::get_feed_part_by_path(db, feed, n, public);
+    if matches!(part, Ok(None)) {
+        return None;
+    }
+    Some(ics_response(db, feed, part, origin, headers))
+}
+
+// Serves `{feed}.availability` as the VAVAILABILITY of the feed's source,
+// from its working hours or its owner's.
+fn availability_response(db: &rusqlite::Connection, path: &str, public: bool) -> Option<Response> {
+    let feed = path.strip_suffix(AVAILABILITY_SUFFIX)?;
+    let (id, name, hours) = match crate::db::get_working_hours_by_path(db, feed, public) {
+        Ok(found) => found?,
+        Err(e) => {
+            tracing::error!("Error looking up working hours for /{}: {}", feed, e);
+            return Some((StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response());
+        }
+    };
+    let hours = match WorkingHours::parse(&hours) {
+        Ok(Some(hours)) => hours,
+        Ok(None) => {
+            return Some((StatusCode::NOT_FOUND, "No working hours configured").into_response());
+        }
+        Err(e) => {
+            tracing::error!("Invalid working hours for source {}: {}", id, e);
+            return Some((StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response());
+        }
+    };
+    let uid = format!("availability@source-{}.caldav-ics-sync", id);
+    Some(
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/calendar")
+            .body(axum::body::Body::from(hours.to_ics(&uid, &name)))
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    )
 }
 
 async fn serve_ics(
     State(state): State<crate::api::AppState>,
     axum::extract::Path(path): axum::extract::Path<String>,
+    headers: HeaderMap,
 ) -> Response {
     let Ok(db) = state.db.lock() else {
         tracing::error!("DB lock poisoned serving ICS /{}", path);
         return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
     };
-    ics_response(crate::db::get_ics_data_by_path(&db, &path))
+    let origin = public_origin(&state, &headers);
+    if let Some(res) = signature_response(state.feed_signer.as_ref(), &path, &origin, |feed| {
+        crate::db::get_ics_data_by_path(&db, feed)
+    }) {
+        return res;
+    }
+    if let Some(res) = availability_response(&db, &path, false) {
+        return res;
+    }
+    if let Some(res) = part_response(&db, &path, false, &origin, &headers) {
+        return res;
+    }
+    match crate::db::get_feed_file_by_path(&db, &path, false) {
+        Ok(Some(hash)) => return file_response(&db, &path, &hash, &headers),
+        Ok(None) => {}
+        Err(e) => tracing::error!("Error looking up feed file for /{}: {}", path, e),
+    }
+    let result = crate::db::get_ics_data_by_path(&db, &path);
+    ics_response(&db, &path, result, &origin, &headers)
 }
 
 async fn serve_public_ics(
     State(state): State<crate::api::AppState>,
     axum::extract::Path(path): axum::extract::Path<String>,
+    headers: HeaderMap,
 ) -> Response {
     if path.contains("..") || path.starts_with('/') {
         return (StatusCode::BAD_REQUEST, "Invalid path").into_response();
@@ -98,21 +478,81 @@ async fn serve_public_ics(
         tracing::error!("DB lock poisoned serving public ICS /{}", path);
         return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
     };
-    ics_response(crate::db::get_ics_data_by_public_path(&db, &path))
+    let origin = public_origin(&state, &headers);
+    if let Some(res) = signature_response(state.feed_signer.as_ref(), &path, &origin, |feed| {
+        crate::db::get_ics_data_by_public_path(&db, feed)
+    }) {
+        return res;
+    }
+    if let Some(res) = availability_response(&db, &path, true) {
+        return res;
+    }
+    if let Some(res) = part_response(&db, &path, true, &origin, &headers) {
+        return res;
+    }
+    match crate::db::get_feed_file_by_path(&db, &path, true) {
+        Ok(Some(hash)) => return file_response(&db, &path, &hash, &headers),
+        Ok(None) => {}
+        Err(e) => tracing::error!("Error looking up feed file for /{}: {}", path, e),
+    }
+    let result = crate::db::get_ics_data_by_public_path(&db, &path);
+    ics_response(&db, &path, result, &origin, &headers)
+}
+
+async fn serve_attachment(
+    State(state): State<crate::api::AppState>,
+    axum::extract::Path(hash): axum::extract::Path<String>,
+) -> Response {
+    let Ok(db) = state.db.lock() else {
+        tracing::error!("DB lock poisoned serving attachment {}", hash);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
+    };
+    match crate::db::get_attachment(&db, &hash) {
+        Ok(Some(a)) => Response::builder()
+            .status(StatusCode::OK)
+            .header(
+                header::CONTENT_TYPE,
+                a.fmttype.as_deref().unwrap_or("application/octet-stream"),
+            )
+            .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable")
+            .body(axum::body::Body::from(a.data))
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        Ok(None) => (StatusCode::NOT_FOUND, "Attachment not found").into_response(),
+        Err(e) => {
+            tracing::error!("Error serving attachment: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response()
+        }
+    }
 }
 
-pub async fn register_routes(state: crate::api::AppState, proxy_url: &str) -> Router {
+pub async fn register_routes(state: crate::api::AppState, frontend: Frontend) -> Router {
     let api_routes = crate::api::routes();
-    let proxy_url = Arc::new(proxy_url.to_owned());
 
-    let fallback_router = Router::new()
-        .fallback(proxy_to_nextjs)
-        .with_state(proxy_url);
+    let fallback_router = match frontend {
+        Frontend::Proxy(proxy_url) => Router::new()
+            .fallback(proxy_to_nextjs)
+            .with_state(NextjsProxy::new(proxy_url)),
+        Frontend::Embedded => Router::new().fallback(super::frontend::serve_embedded),
+    };
 
     Router::new()
         .nest("/api", api_routes)
+        .route(PUBLIC_KEY_PATH, get(super::feed_signing::public_key))
         .route("/ics/public/{*path}", get(serve_public_ics))
         .route("/ics/{*path}", get(serve_ics))
+        .route("/view/{*token}", get(super::agenda::serve_view))
+        .route(
+            "/subscribe/{*path}",
+            get(super::subscription::serve_subscribe),
+        )
+        .route("/attachments/{hash}", get(serve_attachment))
+        .route("/caldav/{*path}", any(super::caldav::serve_caldav))
+        .route("/caldav-proxy/{id}", any(super::caldav_proxy::proxy_root))
+        .route("/caldav-proxy/{id}/", any(super::caldav_proxy::proxy_root))
+        .route(
+            "/caldav-proxy/{id}/{*rest}",
+            any(super::caldav_proxy::proxy_path),
+        )
         .merge(fallback_router)
         .with_state(state)
 }
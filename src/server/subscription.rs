@@ -0,0 +1,192 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+use super::caldav::xml_escape;
+use super::route_builder::public_origin;
+use crate::feed_urls;
+
+pub const MOBILECONFIG_SUFFIX: &str = ".mobileconfig";
+
+// Profiles with the same identifier replace each other when installed, so
+// these are derived from the feed URL rather than random.
+fn profile_uuid(seed: &str) -> String {
+    let hash = Sha256::digest(seed.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    uuid::Builder::from_random_bytes(bytes)
+        .into_uuid()
+        .hyphenated()
+        .to_string()
+        .to_uppercase()
+}
+
+fn describe_refresh(secs: i64) -> String {
+    match secs {
+        s if s % 86_400 == 0 => format!("{} day(s)", s / 86_400),
+        s if s % 3600 == 0 => format!("{} hour(s)", s / 3600),
+        s => format!("{} minute(s)", (s + 59) / 60),
+    }
+}
+
+// An unsigned Apple configuration profile with a single subscribed calendar.
+// The payload has no refresh setting of its own; the interval is stated in
+// the description and the feed's REFRESH-INTERVAL is what clients follow.
+pub fn mobileconfig(name: &str, url: &str, refresh_secs: Option<i64>) -> String {
+    let uuid = profile_uuid(url);
+    let payload_uuid = profile_uuid(&format!("{}#calendar", url));
+    let description = match refresh_secs {
+        Some(secs) if secs > 0 => format!(
+            "Subscribes to {}, refreshed every {}.",
+            name,
+            describe_refresh(secs)
+        ),
+        _ => format!("Subscribes to {}.", name),
+    };
+    let (name, url, description) = (xml_escape(name), xml_escape(url), xml_escape(&description));
+    let ssl = match url.starts_with("https://") {
+        true => "<true/>",
+        false => "<false/>",
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>PayloadContent</key>
+  <array>
+    <dict>
+      <key>PayloadType</key>
+      <string>com.apple.subscribedcalendar.account</string>
+      <key>PayloadVersion</key>
+      <integer>1</integer>
+      <key>PayloadIdentifier</key>
+      <string>caldav-ics-sync.subscription.{uuid}.calendar</string>
+      <key>PayloadUUID</key>
+      <string>{payload_uuid}</string>
+      <key>PayloadDisplayName</key>
+      <string>{name}</string>
+      <key>SubCalAccountDescription</key>
+      <string>{name}</string>
+      <key>SubCalAccountHostName</key>
+      <string>{url}</string>
+      <key>SubCalAccountUseSSL</key>
+      {ssl}
+    </dict>
+  </array>
+  <key>PayloadType</key>
+  <string>Configuration</string>
+  <key>PayloadVersion</key>
+  <integer>1</integer>
+  <key>PayloadIdentifier</key>
+  <string>caldav-ics-sync.subscription.{uuid}</string>
+  <key>PayloadUUID</key>
+  <string>{uuid}</string>
+  <key>PayloadDisplayName</key>
+  <string>{name}</string>
+  <key>PayloadDescription</key>
+  <string>{description}</string>
+</dict>
+</plist>
+"#
+    )
+}
+
+// `/subscribe/{feed}` redirects to the feed's webcal:// URL so calendar apps
+// subscribe to it, and `/subscribe/{feed}.mobileconfig` hands iOS and macOS
+// the same subscription as a configuration profile. `{feed}` is what follows
+// `/ics/`, so `public/...` names a public feed.
+pub async fn serve_subscribe(
+    State(state): State<crate::api::AppState>,
+    Path(path): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    if path.contains("..") || path.starts_with('/') {
+        return (StatusCode::BAD_REQUEST, "Invalid path").into_response();
+    }
+    let (feed, profile) = match path.strip_suffix(MOBILECONFIG_SUFFIX) {
+        Some(feed) => (feed, true),
+        None => (path.as_str(), false),
+    };
+    let (lookup, public) = match feed.strip_prefix("public/") {
+        Some(rest) => (rest, true),
+        None => (feed, false),
+    };
+    let found = {
+        let Ok(db) = state.db.lock() else {
+            tracing::error!("DB lock poisoned serving subscription /{}", path);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
+        };
+        crate::db::get_subscription_by_path(&db, lookup, public)
+    };
+    let (_, name, refresh_secs) = match found {
+        Ok(Some(found)) => found,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Feed not found").into_response(),
+        Err(e) => {
+            tracing::error!("Error looking up subscription for /{}: {}", path, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response();
+        }
+    };
+    let base = public_origin(&state, &headers);
+    let Some(urls) = feed_urls::for_feed(&base, feed, None) else {
+        return (StatusCode::BAD_REQUEST, "Invalid path").into_response();
+    };
+    if !profile {
+        return Response::builder()
+            .status(StatusCode::FOUND)
+            .header(header::LOCATION, urls.webcal)
+            .body(axum::body::Body::empty())
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+    let file_name: String = feed
+        .rsplit('/')
+        .next()
+        .unwrap_or(feed)
+        .trim_end_matches(".ics")
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect();
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-apple-aspen-config")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"{}{}\"",
+                file_name, MOBILECONFIG_SUFFIX
+            ),
+        )
+        .body(axum::body::Body::from(mobileconfig(
+            &name,
+            &urls.https,
+            refresh_secs,
+        )))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_embeds_the_feed_and_refresh_interval() {
+        let profile = mobileconfig("Team & Co", "https://example.com/ics/team.ics", Some(7200));
+        assert!(profile.contains("<string>com.apple.subscribedcalendar.account</string>"));
+        assert!(profile.contains("<string>https://example.com/ics/team.ics</string>"));
+        assert!(profile.contains("<string>Team &amp; Co</string>"));
+        assert!(profile.contains("refreshed every 2 hour(s)."));
+        assert!(profile.contains("<key>SubCalAccountUseSSL</key>\n      <true/>"));
+        assert_eq!(
+            profile_uuid("https://example.com/ics/team.ics"),
+            profile_uuid("https://example.com/ics/team.ics")
+        );
+    }
+}
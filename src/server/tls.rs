@@ -0,0 +1,190 @@
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result, anyhow, ensure};
+use axum::serve::Listener;
+use rustls::ServerConfig;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use tokio::sync::mpsc;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::server::TlsStream;
+
+// How often the certificate files are checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+// A client that hasn't finished the handshake by then is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::aws_lc_rs::default_provider())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Serves the most recently loaded certificate, so renewals apply to new
+// connections without a restart.
+#[derive(Debug)]
+pub struct CertStore {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<Arc<CertifiedKey>>,
+    loaded: Mutex<(Option<SystemTime>, Option<SystemTime>)>,
+}
+
+impl CertStore {
+    pub fn load(cert_path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> Result<Self> {
+        let cert_path = cert_path.into();
+        let key_path = key_path.into();
+        let key = load_certified_key(&cert_path, &key_path)?;
+        Ok(Self {
+            loaded: Mutex::new((modified(&cert_path), modified(&key_path))),
+            current: RwLock::new(Arc::new(key)),
+            cert_path,
+            key_path,
+        })
+    }
+
+    // Returns whether a new certificate was loaded. A broken file keeps the old one.
+    pub fn reload(&self, force: bool) -> Result<bool> {
+        let stamps = (modified(&self.cert_path), modified(&self.key_path));
+        if !force && *self.loaded.lock().unwrap() == stamps {
+            return Ok(false);
+        }
+        let key = load_certified_key(&self.cert_path, &self.key_path)?;
+        *self.current.write().unwrap() = Arc::new(key);
+        *self.loaded.lock().unwrap() = stamps;
+        Ok(true)
+    }
+
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        self.current.read().unwrap().clone()
+    }
+}
+
+impl ResolvesServerCert for CertStore {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| anyhow!("{}", e))
+        .with_context(|| format!("Failed to read TLS certificate '{}'", cert_path.display()))?;
+    ensure!(
+        !certs.is_empty(),
+        "No certificates found in '{}'",
+        cert_path.display()
+    );
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| anyhow!("{}", e))
+        .with_context(|| format!("Failed to read TLS key '{}'", key_path.display()))?;
+    let key = provider()
+        .key_provider
+        .load_private_key(key)
+        .with_context(|| format!("Unsupported TLS key '{}'", key_path.display()))?;
+    let certified = CertifiedKey::new(certs, key);
+    certified
+        .keys_match()
+        .context("TLS certificate and key do not match")?;
+    Ok(certified)
+}
+
+pub fn server_config(store: Arc<CertStore>) -> Result<Arc<ServerConfig>> {
+    let mut config = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_cert_resolver(store);
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+// Reloads on SIGHUP, and whenever the files' modification times change.
+pub fn spawn_reloader(store: Arc<CertStore>) {
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        let mut hangup =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+        let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+        interval.tick().await;
+        loop {
+            #[cfg(unix)]
+            let force = tokio::select! {
+                _ = interval.tick() => false,
+                Some(_) = async { hangup.as_mut()?.recv().await } => true,
+            };
+            #[cfg(not(unix))]
+            let force = {
+                interval.tick().await;
+                false
+            };
+            match store.reload(force) {
+                Ok(true) => tracing::info!("Reloaded TLS certificate"),
+                Ok(false) => {}
+                Err(e) => tracing::error!("Keeping the current TLS certificate: {:#}", e),
+            }
+        }
+    });
+}
+
+// Handshakes run in their own tasks so a slow client can't hold up the accept loop.
+pub struct TlsListener<L: Listener> {
+    incoming: mpsc::Receiver<(TlsStream<L::Io>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl<L: Listener<Addr = SocketAddr>> TlsListener<L> {
+    pub fn new(mut listener: L, config: Arc<ServerConfig>) -> std::io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (tx, incoming) = mpsc::channel(64);
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = listener.accept().await;
+                if tx.is_closed() {
+                    break;
+                }
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(tls)) => {
+                            let _ = tx.send((tls, addr)).await;
+                        }
+                        Ok(Err(e)) => tracing::debug!("TLS handshake with {} failed: {}", addr, e),
+                        Err(_) => tracing::debug!("TLS handshake with {} timed out", addr),
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            incoming,
+            local_addr,
+        })
+    }
+}
+
+impl<L: Listener<Addr = SocketAddr>> Listener for TlsListener<L> {
+    type Io = TlsStream<L::Io>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(conn) => conn,
+            // The accept loop only stops once this listener is gone.
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
@@ -0,0 +1,144 @@
+use crate::ics_component::{self, value};
+
+// Chained pipelines (calendar A -> feed -> calendar B -> feed -> A) would pass
+// the same events around forever. A source or destination that namespaces
+// UIDs stamps each event it passes on: its namespace is appended to the UID
+// and recorded in X-SYNC-ORIGIN. Events already stamped by an earlier pipeline
+// keep both, and a pipeline drops events that carry its own namespace.
+const DOMAIN: &str = "caldav-ics-sync";
+pub const ORIGIN_PROPERTY: &str = "X-SYNC-ORIGIN";
+
+pub fn source_namespace(id: i64) -> String {
+    format!("source-{}.{}", id, DOMAIN)
+}
+
+pub fn destination_namespace(id: i64) -> String {
+    format!("dest-{}.{}", id, DOMAIN)
+}
+
+pub fn origin(vevent: &str) -> Option<String> {
+    value(vevent, ORIGIN_PROPERTY)
+}
+
+// Clients may drop X- properties when an event is edited, so the UID suffix
+// counts too.
+pub fn originated_from(vevent: &str, namespace: &str) -> bool {
+    origin(vevent).as_deref() == Some(namespace)
+        || value(vevent, "UID").is_some_and(|uid| uid.ends_with(&format!("@{}", namespace)))
+}
+
+// Namespaces the UID of one VEVENT and records its origin, unless an earlier
+// pipeline already did.
+pub fn stamp(vevent: &str, namespace: &str) -> String {
+    if origin(vevent).is_some() {
+        return vevent.to_string();
+    }
+    let Some(uid) = value(vevent, "UID") else {
+        return vevent.to_string();
+    };
+    ics_component::rewrite(vevent, |name, _| {
+        (name == "UID").then(|| {
+            format!(
+                "UID:{}@{}\r\n{}:{}\r\n",
+                uid, namespace, ORIGIN_PROPERTY, namespace
+            )
+        })
+    })
+}
+
+// Undoes `stamp` for events stamped with `namespace`, for sources that get
+// their own feed back as input, such as uploads merged into the stored feed.
+pub fn unstamp(vevent: &str, namespace: &str) -> String {
+    if !originated_from(vevent, namespace) {
+        return vevent.to_string();
+    }
+    let suffix = format!("@{}", namespace);
+    ics_component::rewrite(vevent, |name, _| match name {
+        "UID" => {
+            let uid = value(vevent, "UID")?;
+            Some(format!("UID:{}\r\n", uid.strip_suffix(&suffix)?))
+        }
+        ORIGIN_PROPERTY => (origin(vevent).as_deref() == Some(namespace)).then(String::new),
+        _ => None,
+    })
+}
+
+// Stamps every VEVENT of a calendar with `namespace`, dropping the ones that
+// already carry it.
+pub fn apply(ics: &str, namespace: &str) -> String {
+    let mut out = String::with_capacity(ics.len());
+    let mut vevent = String::new();
+    let mut in_vevent = false;
+    for line in ics.split_inclusive('\n') {
+        if line.starts_with("BEGIN:VEVENT") {
+            in_vevent = true;
+        }
+        if !in_vevent {
+            out.push_str(line);
+            continue;
+        }
+        vevent.push_str(line);
+        if line.starts_with("END:VEVENT") {
+            in_vevent = false;
+            if !originated_from(&vevent, namespace) {
+                out.push_str(&stamp(&vevent, namespace));
+            }
+            vevent.clear();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamps_once_and_drops_own_events() {
+        let ns = source_namespace(1);
+        let stamped = stamp("BEGIN:VEVENT\r\nUID:abc\r\n def\r\nEND:VEVENT\r\n", &ns);
+        assert_eq!(
+            stamped,
+            format!(
+                "BEGIN:VEVENT\r\nUID:abcdef@{ns}\r\nX-SYNC-ORIGIN:{ns}\r\nEND:VEVENT\r\n",
+                ns = ns
+            )
+        );
+        // The next pipeline passes it on untouched.
+        assert_eq!(stamp(&stamped, &destination_namespace(2)), stamped);
+        // An alarm's own UID is not the event's.
+        let alarm = "BEGIN:VALARM\r\nUID:alarm\r\nACTION:DISPLAY\r\nEND:VALARM\r\n";
+        let with_alarm = stamp(
+            &format!("BEGIN:VEVENT\r\nUID:ev\r\n{}END:VEVENT\r\n", alarm),
+            &ns,
+        );
+        assert!(with_alarm.contains(&format!("UID:ev@{}\r\n", ns)));
+        assert!(with_alarm.contains(alarm));
+
+        let ics = format!(
+            "BEGIN:VCALENDAR\r\n{}BEGIN:VEVENT\r\nUID:new\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+            stamped
+        );
+        let applied = apply(&ics, &ns);
+        assert!(!applied.contains("UID:abcdef"));
+        assert!(applied.contains(&format!("UID:new@{}\r\n", ns)));
+        assert!(applied.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(applied.ends_with("END:VCALENDAR\r\n"));
+
+        assert_eq!(
+            unstamp(&stamped, &ns),
+            "BEGIN:VEVENT\r\nUID:abcdef\r\nEND:VEVENT\r\n"
+        );
+        let foreign = stamp(
+            "BEGIN:VEVENT\r\nUID:f\r\nEND:VEVENT\r\n",
+            &destination_namespace(2),
+        );
+        assert_eq!(unstamp(&foreign, &ns), foreign);
+
+        // Stamped elsewhere, then edited by a client that dropped X-SYNC-ORIGIN.
+        assert!(originated_from(
+            &format!("BEGIN:VEVENT\r\nUID:x@{}\r\nEND:VEVENT\r\n", ns),
+            &ns
+        ));
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::auto_sync::AutoSyncKey;
+
+#[derive(Clone, Debug, Default, Serialize, ToSchema)]
+pub struct SyncProgress {
+    pub running: bool,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub calendars_found: usize,
+    pub calendars_fetched: usize,
+    pub events_fetched: usize,
+    pub events_uploaded: usize,
+}
+
+// Progress of the latest sync of each source and destination since startup.
+pub type ProgressRegistry = Arc<Mutex<HashMap<AutoSyncKey, Arc<Mutex<SyncProgress>>>>>;
+
+pub fn new_registry() -> ProgressRegistry {
+    Arc::new(Mutex::new(HashMap::new()))
+}
+
+tokio::task_local! {
+    static CURRENT: Arc<Mutex<SyncProgress>>;
+}
+
+// Runs `fut` as a fresh sync of `key`, so `update` calls inside it are visible via `get`.
+pub async fn track<F: Future>(registry: &ProgressRegistry, key: AutoSyncKey, fut: F) -> F::Output {
+    let progress = Arc::new(Mutex::new(SyncProgress {
+        running: true,
+        started_at: Utc::now().to_rfc3339(),
+        ..Default::default()
+    }));
+    if let Ok(mut map) = registry.lock() {
+        map.insert(key, Arc::clone(&progress));
+    }
+    let _finished = Finished(Arc::clone(&progress));
+    CURRENT.scope(progress, fut).await
+}
+
+// Marks the run finished even when its future is dropped, e.g. on cancellation.
+struct Finished(Arc<Mutex<SyncProgress>>);
+
+impl Drop for Finished {
+    fn drop(&mut self) {
+        if let Ok(mut p) = self.0.lock() {
+            p.running = false;
+            p.finished_at = Some(Utc::now().to_rfc3339());
+        }
+    }
+}
+
+// No-op outside `track`, e.g. when the sync functions are called directly.
+pub fn update(f: impl FnOnce(&mut SyncProgress)) {
+    let _ = CURRENT.try_with(|progress| {
+        if let Ok(mut p) = progress.lock() {
+            f(&mut p);
+        }
+    });
+}
+
+pub fn get(registry: &ProgressRegistry, key: &AutoSyncKey) -> Option<SyncProgress> {
+    let progress = Arc::clone(registry.lock().ok()?.get(key)?);
+    let snapshot = progress.lock().ok()?.clone();
+    Some(snapshot)
+}
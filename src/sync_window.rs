@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result, ensure};
+use chrono::{DateTime, Days, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+// Scheduled syncs past this many skipped ticks wait for the window to open
+// instead, for intervals that never line up with it.
+const MAX_SKIPPED_TICKS: u32 = 10_000;
+
+// A daily span of local time during which scheduled syncs may run, written
+// `06:00-23:00 Europe/Berlin`. Without a zone it is UTC; an end before the
+// start wraps past midnight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+    tz: Tz,
+}
+
+impl SyncWindow {
+    // None for an empty string, which means no window.
+    pub fn parse(value: &str) -> Result<Option<SyncWindow>> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(None);
+        }
+        let (span, tz) = match value.split_once(char::is_whitespace) {
+            Some((span, tz)) => (span, tz.trim()),
+            None => (value, "UTC"),
+        };
+        let (start, end) = span.split_once('-').context(
+            "Sync window must look like 06:00-23:00, optionally followed by a time zone",
+        )?;
+        let time = |t: &str| {
+            NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                .with_context(|| format!("Invalid time '{}' in sync window", t))
+        };
+        let window = SyncWindow {
+            start: time(start)?,
+            end: time(end)?,
+            tz: tz
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Unknown time zone '{}' in sync window", tz))?,
+        };
+        ensure!(
+            window.start != window.end,
+            "Sync window cannot start and end at the same time"
+        );
+        Ok(Some(window))
+    }
+
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        let local = at.with_timezone(&self.tz).time();
+        match self.start < self.end {
+            true => self.start <= local && local < self.end,
+            false => local >= self.start || local < self.end,
+        }
+    }
+
+    // `at` itself when inside the window, otherwise the next time it opens.
+    pub fn next_open(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        if self.contains(at) {
+            return at;
+        }
+        let local = at.with_timezone(&self.tz);
+        let mut day = local.date_naive();
+        if local.time() >= self.start {
+            day = day + Days::new(1);
+        }
+        // A start skipped by a DST change opens the window an hour later.
+        let opens = day.and_time(self.start);
+        self.tz
+            .from_local_datetime(&opens)
+            .earliest()
+            .or_else(|| {
+                self.tz
+                    .from_local_datetime(&(opens + chrono::Duration::hours(1)))
+                    .earliest()
+            })
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or(at)
+    }
+}
+
+// The first tick after `from`, every `interval`, that falls inside the
+// window. Ticks outside it are skipped rather than moved.
+pub fn next_tick(
+    window: Option<&SyncWindow>,
+    from: DateTime<Utc>,
+    interval: Duration,
+) -> DateTime<Utc> {
+    let step = chrono::Duration::seconds(interval.as_secs() as i64);
+    let first = from + step;
+    let Some(window) = window else {
+        return first;
+    };
+    let mut tick = first;
+    for _ in 0..MAX_SKIPPED_TICKS {
+        if window.contains(tick) {
+            return tick;
+        }
+        tick += step;
+    }
+    window.next_open(first)
+}
+
+// When a run due at `at` may start.
+pub fn first_run(window: Option<&SyncWindow>, at: DateTime<Utc>) -> DateTime<Utc> {
+    window.map_or(at, |w| w.next_open(at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn parses_windows() {
+        assert_eq!(SyncWindow::parse("").unwrap(), None);
+        let w = SyncWindow::parse("06:00-23:00 Europe/Berlin")
+            .unwrap()
+            .unwrap();
+        assert!(w.contains(utc("2026-07-01T05:00:00Z")));
+        assert!(!w.contains(utc("2026-07-01T21:30:00Z")));
+        assert!(SyncWindow::parse("6-23").is_err());
+        assert!(SyncWindow::parse("06:00-23:00 Mars/Base").is_err());
+        assert!(SyncWindow::parse("06:00-06:00").is_err());
+    }
+
+    #[test]
+    fn wraps_past_midnight() {
+        let w = SyncWindow::parse("22:00-06:00").unwrap().unwrap();
+        assert!(w.contains(utc("2026-07-01T23:00:00Z")));
+        assert!(w.contains(utc("2026-07-01T05:59:00Z")));
+        assert!(!w.contains(utc("2026-07-01T12:00:00Z")));
+        assert_eq!(
+            w.next_open(utc("2026-07-01T12:00:00Z")),
+            utc("2026-07-01T22:00:00Z")
+        );
+    }
+
+    #[test]
+    fn skips_ticks_outside_the_window() {
+        let w = SyncWindow::parse("06:00-23:00").unwrap().unwrap();
+        let hour = Duration::from_secs(3600);
+        assert_eq!(
+            next_tick(Some(&w), utc("2026-07-01T21:30:00Z"), hour),
+            utc("2026-07-01T22:30:00Z")
+        );
+        assert_eq!(
+            next_tick(Some(&w), utc("2026-07-01T22:30:00Z"), hour),
+            utc("2026-07-02T06:30:00Z")
+        );
+        assert_eq!(
+            first_run(Some(&w), utc("2026-07-01T23:10:00Z")),
+            utc("2026-07-02T06:00:00Z")
+        );
+        // A daily interval that always lands outside waits for the opening.
+        let day = Duration::from_secs(86_400);
+        assert_eq!(
+            next_tick(Some(&w), utc("2026-07-01T02:00:00Z"), day),
+            utc("2026-07-02T06:00:00Z")
+        );
+    }
+}
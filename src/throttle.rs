@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use anyhow::{Result, ensure};
+use chrono::{DateTime, Utc};
+use reqwest::{RequestBuilder, Response, StatusCode, header};
+use tokio::time::Instant;
+
+// Paces the requests of a destination sync, so the first upload of a large
+// calendar doesn't look like a burst to providers that ban for one (iCloud
+// locks an account for a day). A destination's `max_requests_per_second`
+// spaces requests evenly and `request_delay_ms` leaves a pause after each;
+// the longer of the two applies. Either at 0 is off.
+//
+// A `429 Too Many Requests` slows the rest of the run down, doubling the gap
+// each time, and the request is retried after the server's Retry-After.
+pub const MAX_REQUESTS_PER_SECOND: f64 = 100.0;
+pub const MAX_REQUEST_DELAY_MS: i64 = 60_000;
+// Waits the server asks for beyond this give up on the request; the sync
+// records it as failed and retries it next time.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+const RATE_LIMIT_RETRIES: usize = 3;
+const FIRST_SLOWDOWN: Duration = Duration::from_secs(1);
+const MAX_SLOWDOWN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Pace {
+    pub requests_per_second: f64,
+    pub delay_ms: i64,
+}
+
+pub fn validate(requests_per_second: f64, delay_ms: i64) -> Result<()> {
+    ensure!(
+        requests_per_second.is_finite()
+            && (0.0..=MAX_REQUESTS_PER_SECOND).contains(&requests_per_second),
+        "Requests per second must be between 0 and {}",
+        MAX_REQUESTS_PER_SECOND
+    );
+    ensure!(
+        (0..=MAX_REQUEST_DELAY_MS).contains(&delay_ms),
+        "Request delay must be between 0 and {} ms",
+        MAX_REQUEST_DELAY_MS
+    );
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct Throttle {
+    gap: Duration,
+    next: Option<Instant>,
+}
+
+impl Throttle {
+    pub fn new(pace: Pace) -> Self {
+        let per_request = match pace.requests_per_second > 0.0 {
+            true => Duration::from_secs_f64(1.0 / pace.requests_per_second),
+            false => Duration::ZERO,
+        };
+        let delay = Duration::from_millis(pace.delay_ms.max(0) as u64);
+        Self {
+            gap: per_request.max(delay),
+            next: None,
+        }
+    }
+
+    async fn wait(&mut self) {
+        if let Some(next) = self.next {
+            tokio::time::sleep_until(next).await;
+        }
+        self.next = Some(Instant::now() + self.gap);
+    }
+
+    // Widens the gap after a 429 and pushes the next request past the wait
+    // the server asked for.
+    fn slow_down(&mut self, retry_after: Duration) {
+        self.gap = (self.gap * 2).clamp(FIRST_SLOWDOWN, MAX_SLOWDOWN);
+        self.next = Some(Instant::now() + retry_after.max(self.gap));
+        tracing::warn!(
+            "Rate limited; waiting {:?}, then one request per {:?}",
+            retry_after.max(self.gap),
+            self.gap
+        );
+    }
+
+    // Sends the request `build` makes once its turn comes, retrying it a few
+    // times when the server answers 429.
+    pub async fn send(&mut self, build: impl Fn() -> RequestBuilder) -> reqwest::Result<Response> {
+        let mut retries = 0;
+        loop {
+            self.wait().await;
+            let res = build().send().await?;
+            if res.status() != StatusCode::TOO_MANY_REQUESTS || retries == RATE_LIMIT_RETRIES {
+                return Ok(res);
+            }
+            let retry_after = retry_after(&res).unwrap_or(Duration::ZERO);
+            if retry_after > MAX_RETRY_AFTER {
+                return Ok(res);
+            }
+            self.slow_down(retry_after);
+            retries += 1;
+        }
+    }
+}
+
+// Retry-After in seconds or as an HTTP date.
+fn retry_after(res: &Response) -> Option<Duration> {
+    let value = res
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gap_is_the_stricter_of_rate_and_delay() {
+        let gap = |requests_per_second, delay_ms| {
+            Throttle::new(Pace {
+                requests_per_second,
+                delay_ms,
+            })
+            .gap
+        };
+        assert_eq!(gap(2.0, 0), Duration::from_millis(500));
+        assert_eq!(gap(2.0, 800), Duration::from_millis(800));
+        assert_eq!(gap(0.0, 0), Duration::ZERO);
+        let mut throttle = Throttle::new(Pace::default());
+        throttle.slow_down(Duration::ZERO);
+        assert_eq!(throttle.gap, FIRST_SLOWDOWN);
+        throttle.slow_down(Duration::ZERO);
+        assert_eq!(throttle.gap, FIRST_SLOWDOWN * 2);
+        assert!(validate(0.5, 250).is_ok());
+        assert!(validate(-1.0, 0).is_err());
+        assert!(validate(f64::NAN, 0).is_err());
+        assert!(validate(1.0, 120_000).is_err());
+    }
+}
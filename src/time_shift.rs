@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::api::sync;
+use crate::feed_metadata::fold;
+use crate::ics_component;
+use crate::ics_text::split_property;
+
+// Publishes a calendar for subscribers in another region, whose clients often
+// get TZIDs wrong (feeds carry no VTIMEZONE blocks). With a source's
+// `time_shift_zone` set, every timed event is labelled with its start in that
+// zone, e.g. "Standup (09:00 CET)", and depending on `time_shift_mode`:
+//
+// - `annotate` only adds the label.
+// - `shift` also rewrites its times as floating local times of the zone, so
+//   every client shows them as if it were there.
+// - `duplicate` keeps the original and adds a shifted, labelled copy.
+//
+// All-day events and floating times are left alone.
+pub const MODES: &[&str] = &["annotate", "shift", "duplicate"];
+// Properties whose DATE-TIME values are rewritten by `shift`.
+const TIME_PROPERTIES: &[&str] = &["DTSTART", "DTEND", "RECURRENCE-ID", "EXDATE", "RDATE"];
+
+pub fn parse_zone(zone: &str) -> Result<Tz> {
+    match zone.trim().parse::<Tz>() {
+        Ok(tz) => Ok(tz),
+        Err(_) => bail!(
+            "Unknown time zone '{}'; use an IANA name such as Europe/Berlin",
+            zone.trim()
+        ),
+    }
+}
+
+pub fn validate_mode(mode: &str) -> Result<()> {
+    if !MODES.contains(&mode) {
+        bail!("Time shift mode must be one of: {}", MODES.join(", "));
+    }
+    Ok(())
+}
+
+// A DATE-TIME value as an instant; None for dates and floating times.
+fn instant(params: &str, value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|t| t.and_utc());
+    }
+    let tz: Tz = params
+        .split(';')
+        .find_map(|p| p.strip_prefix("TZID="))?
+        .trim_matches('"')
+        .parse()
+        .ok()?;
+    let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    tz.from_local_datetime(&local)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+fn start(vevent: &str) -> Option<DateTime<Utc>> {
+    let line = ics_component::property(vevent, "DTSTART")?;
+    let (params, value) = split_property(&line)?;
+    instant(params, value)
+}
+
+fn label(start: DateTime<Utc>, tz: Tz) -> String {
+    format!("({})", start.with_timezone(&tz).format("%H:%M %Z"))
+}
+
+// The property with each DATE-TIME value moved to local time in `tz`, without
+// TZID; None when a value is not a date-time with a known zone.
+fn shifted_property(line: &str, tz: Tz) -> Option<String> {
+    let (params, values) = split_property(line)?;
+    let shifted = values
+        .split(',')
+        .map(|v| {
+            instant(params, v).map(|t| {
+                t.with_timezone(&tz)
+                    .naive_local()
+                    .format("%Y%m%dT%H%M%S")
+                    .to_string()
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let params: Vec<&str> = params
+        .split(';')
+        .filter(|p| !p.to_ascii_uppercase().starts_with("TZID="))
+        .collect();
+    Some(fold(&format!("{}:{}", params.join(";"), shifted.join(","))))
+}
+
+fn transform(vevent: &str, tz: Tz, shift: bool, uid: Option<&str>) -> Option<String> {
+    let label = label(start(vevent)?, tz);
+    Some(ics_component::rewrite(vevent, |name, raw| {
+        let line = ics_component::unfold(raw);
+        match name {
+            "SUMMARY" if !line.ends_with(&label) => Some(fold(&format!("{} {}", line, label))),
+            "UID" => uid.map(|uid| format!("UID:{}\r\n", uid)),
+            _ if shift && TIME_PROPERTIES.contains(&name) => shifted_property(&line, tz),
+            _ => None,
+        }
+    }))
+}
+
+// `Europe/Berlin` -> `europe-berlin`, for the UIDs of duplicated events.
+fn zone_slug(tz: Tz) -> String {
+    tz.name().to_ascii_lowercase().replace(['/', '_'], "-")
+}
+
+// Applies the shift to every VEVENT of `ics`; an empty `zone` turns it off.
+pub fn apply(ics: &str, zone: &str, mode: &str) -> String {
+    let Ok(tz) = parse_zone(zone) else {
+        return ics.to_string();
+    };
+    let vevents = sync::split_vevents(ics);
+    // Copies made by an earlier run, as for events merged into an upload.
+    let existing: HashSet<String> = vevents.iter().filter_map(|v| sync::event_uid(v)).collect();
+    let suffix = format!("-{}", zone_slug(tz));
+    let mut out = String::with_capacity(ics.len());
+    let mut in_vevent = false;
+    let mut vevent = String::new();
+    for line in ics.split_inclusive('\n') {
+        if line.starts_with("BEGIN:VEVENT") {
+            in_vevent = true;
+        }
+        if !in_vevent {
+            out.push_str(line);
+            continue;
+        }
+        vevent.push_str(line);
+        if !line.starts_with("END:VEVENT") {
+            continue;
+        }
+        in_vevent = false;
+        let event = std::mem::take(&mut vevent);
+        match mode {
+            "duplicate" => {
+                out.push_str(&event);
+                let copy_uid = sync::event_uid(&event)
+                    .filter(|uid| !uid.ends_with(&suffix))
+                    .map(|uid| format!("{}{}", uid, suffix));
+                if let Some(copy_uid) = copy_uid.filter(|u| !existing.contains(u))
+                    && let Some(copy) = transform(&event, tz, true, Some(&copy_uid))
+                {
+                    out.push_str(&copy);
+                }
+            }
+            _ => match transform(&event, tz, mode == "shift", None) {
+                Some(changed) => out.push_str(&changed),
+                None => out.push_str(&event),
+            },
+        }
+    }
+    out.push_str(&vevent);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ICS: &str = "BEGIN:VCALENDAR\r\n\
+        BEGIN:VEVENT\r\nUID:standup\r\nSUMMARY:Standup\r\n\
+        DTSTART;TZID=America/New_York:20260105T090000\r\nDURATION:PT15M\r\n\
+        RRULE:FREQ=DAILY\r\nEXDATE;TZID=America/New_York:20260106T090000,20260107T090000\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\nUID:offsite\r\nSUMMARY:Offsite\r\nDTSTART;VALUE=DATE:20260110\r\nEND:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    #[test]
+    fn annotates_shifts_and_duplicates_timed_events() {
+        let annotated = apply(ICS, "Europe/Berlin", "annotate");
+        assert!(annotated.contains("SUMMARY:Standup (15:00 CET)\r\n"));
+        assert!(annotated.contains("DTSTART;TZID=America/New_York:20260105T090000\r\n"));
+        assert!(annotated.contains("SUMMARY:Offsite\r\n"));
+        // A second run, as on an upload merged with the stored feed, adds nothing.
+        assert_eq!(apply(&annotated, "Europe/Berlin", "annotate"), annotated);
+
+        let shifted = apply(ICS, "Europe/Berlin", "shift");
+        assert!(shifted.contains("DTSTART:20260105T150000\r\n"));
+        assert!(shifted.contains("EXDATE:20260106T150000,20260107T150000\r\n"));
+        assert!(shifted.contains("DTSTART;VALUE=DATE:20260110\r\n"));
+
+        let duplicated = apply(ICS, "Europe/Berlin", "duplicate");
+        assert!(duplicated.contains("UID:standup\r\nSUMMARY:Standup\r\n"));
+        assert!(
+            duplicated.contains("UID:standup-europe-berlin\r\nSUMMARY:Standup (15:00 CET)\r\n")
+        );
+        assert_eq!(duplicated.matches("BEGIN:VEVENT").count(), 3);
+        assert_eq!(apply(&duplicated, "Europe/Berlin", "duplicate"), duplicated);
+
+        assert_eq!(apply(ICS, "", "shift"), ICS);
+        assert!(parse_zone("Mars/Olympus").is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Deserializer, de};
+
+const DURATION_FORMATS: &str = "seconds as an integer or a value like 90s, 15m, 2h, 1d, 1h30m";
+const SIZE_FORMATS: &str = "bytes as an integer or a value like 512KB, 10MB, 1GB";
+
+fn split_number(s: &str) -> Option<(u64, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    if end == 0 {
+        return None;
+    }
+    Some((s[..end].parse().ok()?, &s[end..]))
+}
+
+fn parse_duration(value: &str) -> Option<u64> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    if let Ok(secs) = trimmed.parse::<u64>() {
+        return Some(secs);
+    }
+
+    let mut rest = trimmed;
+    let mut total: u64 = 0;
+    while !rest.is_empty() {
+        let (n, tail) = split_number(rest)?;
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        let multiplier = match tail[..unit_len].to_ascii_lowercase().as_str() {
+            "s" | "sec" | "secs" => 1,
+            "m" | "min" | "mins" => 60,
+            "h" | "hr" | "hrs" => 3600,
+            "d" | "day" | "days" => 86_400,
+            _ => return None,
+        };
+        total = total.checked_add(n.checked_mul(multiplier)?)?;
+        rest = &tail[unit_len..];
+    }
+    Some(total)
+}
+
+fn parse_size(value: &str) -> Option<u64> {
+    let (n, unit) = split_number(value.trim())?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => return None,
+    };
+    n.checked_mul(multiplier)
+}
+
+fn invalid_duration(value: &str) -> String {
+    format!(
+        "invalid duration '{}'; expected {}",
+        value, DURATION_FORMATS
+    )
+}
+
+fn invalid_size(value: &str) -> String {
+    format!("invalid size '{}'; expected {}", value, SIZE_FORMATS)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(i64),
+    String(String),
+}
+
+fn to_secs<E: de::Error>(value: NumberOrString) -> Result<i64, E> {
+    match value {
+        NumberOrString::Number(n) => Ok(n),
+        NumberOrString::String(s) => parse_duration(&s)
+            .and_then(|v| i64::try_from(v).ok())
+            .ok_or_else(|| de::Error::custom(invalid_duration(&s))),
+    }
+}
+
+pub fn duration_secs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    to_secs(NumberOrString::deserialize(deserializer)?)
+}
+
+pub fn opt_duration_secs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<i64>, D::Error> {
+    Option::<NumberOrString>::deserialize(deserializer)?
+        .map(to_secs)
+        .transpose()
+}
+
+pub fn size_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(n) => {
+            u64::try_from(n).map_err(|_| de::Error::custom(invalid_size(&n.to_string())))
+        }
+        NumberOrString::String(s) => {
+            parse_size(&s).ok_or_else(|| de::Error::custom(invalid_size(&s)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Serde puts the field name in front of these errors; see
+    // `create_destination_invalid_duration_names_field` and
+    // `invalid_duration_and_size_errors_name_the_setting`.
+    #[test]
+    fn durations_accept_plain_seconds_and_units() {
+        assert_eq!(parse_duration("90"), Some(90));
+        assert_eq!(parse_duration("15m"), Some(900));
+        assert_eq!(parse_duration("2h"), Some(7200));
+        assert_eq!(parse_duration("1d"), Some(86_400));
+        assert_eq!(parse_duration("1h30m"), Some(5400));
+    }
+
+    #[test]
+    fn duration_errors_list_formats() {
+        assert!(invalid_duration("15x").contains("15m"));
+        assert_eq!(parse_duration("15x"), None);
+        assert_eq!(parse_duration("-5m"), None);
+        assert_eq!(parse_duration("m"), None);
+    }
+
+    #[test]
+    fn sizes_use_binary_multiples() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("10MB"), Some(10 * 1024 * 1024));
+        assert_eq!(parse_size("1 GiB"), Some(1 << 30));
+        assert_eq!(parse_size("4k"), Some(4096));
+    }
+
+    #[test]
+    fn size_errors_list_formats() {
+        assert!(invalid_size("10XB").contains("10MB"));
+        assert_eq!(parse_size("10XB"), None);
+    }
+}
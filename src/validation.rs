@@ -0,0 +1,380 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use anyhow::Result;
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::availability::WorkingHours;
+use crate::credentials;
+use crate::db::{
+    self, ATTACHMENT_MODES, CreateSource, FETCH_MODES, SOURCE_TYPES, STARTUP_SYNC_MODES, Source,
+    UpdateSource,
+};
+use crate::feed_limits;
+use crate::holidays;
+use crate::http_clients::{self, CustomHeaders};
+use crate::ics_repair;
+use crate::sync_window::SyncWindow;
+use crate::time_shift;
+
+// 0 turns auto-sync off; anything else has to fall in this range.
+pub const MIN_SYNC_INTERVAL_SECS: i64 = 60;
+pub const MAX_SYNC_INTERVAL_SECS: i64 = 31 * 86_400;
+const MAX_NAME_LEN: usize = 200;
+const MAX_ARCHIVE_MONTHS: i64 = 1200;
+
+// Problems with a request body keyed by field name; the API returns them as
+// `details.fields` on a 422.
+#[derive(Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct FieldErrors(BTreeMap<&'static str, String>);
+
+impl FieldErrors {
+    // The first problem found for a field is the one reported.
+    fn add(&mut self, field: &'static str, message: impl Into<String>) {
+        self.0.entry(field).or_insert_with(|| message.into());
+    }
+
+    fn into_result(self) -> Result<()> {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => Err(self.into()),
+        }
+    }
+}
+
+impl fmt::Display for FieldErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid source: ")?;
+        for (i, (field, message)) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{} {}", field, message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FieldErrors {}
+
+fn check_name(
+    conn: &Connection,
+    errors: &mut FieldErrors,
+    name: &str,
+    owner_id: Option<i64>,
+    exclude: Option<i64>,
+) -> Result<()> {
+    let name = name.trim();
+    if name.is_empty() {
+        errors.add("name", "is required");
+    } else if name.chars().count() > MAX_NAME_LEN {
+        errors.add(
+            "name",
+            format!("must be at most {} characters", MAX_NAME_LEN),
+        );
+    } else if db::source_name_taken(conn, name, owner_id, exclude)? {
+        errors.add("name", "is already used by another source");
+    }
+    Ok(())
+}
+
+fn check_url(errors: &mut FieldErrors, field: &'static str, value: &str) {
+    match reqwest::Url::parse(value.trim()) {
+        Err(e) => errors.add(field, format!("is not a valid URL ({})", e)),
+        Ok(url) if !matches!(url.scheme(), "http" | "https") => {
+            errors.add(field, "must use http or https")
+        }
+        Ok(url) if url.host_str().is_none_or(str::is_empty) => {
+            errors.add(field, "must include a host")
+        }
+        Ok(_) => {}
+    }
+}
+
+fn check_connection(
+    errors: &mut FieldErrors,
+    source_type: &str,
+    caldav_url: &str,
+    username: &str,
+    password: &str,
+) {
+    let fields = [
+        ("caldav_url", caldav_url),
+        ("username", username),
+        ("password", password),
+    ];
+    match source_type {
+        // Birthday sources connect to a CardDAV server the same way.
+        "caldav" | "birthdays" => {
+            let kind = match source_type {
+                "caldav" => "CalDAV",
+                _ => "birthday",
+            };
+            for (field, value) in fields {
+                if value.trim().is_empty() {
+                    errors.add(field, format!("is required for {} sources", kind));
+                }
+            }
+            if !caldav_url.trim().is_empty() {
+                check_url(errors, "caldav_url", caldav_url);
+            }
+            if let Some(problem) = credentials::reference_problem(password) {
+                errors.add("password", problem);
+            }
+        }
+        "upload" | "holidays" => {
+            let kind = match source_type {
+                "upload" => "upload",
+                _ => "holiday",
+            };
+            for (field, value) in fields {
+                if !value.trim().is_empty() {
+                    errors.add(field, format!("is not used by {} sources", kind));
+                }
+            }
+        }
+        _ => errors.add(
+            "source_type",
+            format!("must be one of: {}", SOURCE_TYPES.join(", ")),
+        ),
+    }
+}
+
+fn check_interval(errors: &mut FieldErrors, secs: i64) {
+    if secs != 0 && !(MIN_SYNC_INTERVAL_SECS..=MAX_SYNC_INTERVAL_SECS).contains(&secs) {
+        errors.add(
+            "sync_interval_secs",
+            format!(
+                "must be 0 (off) or between {} and {} seconds",
+                MIN_SYNC_INTERVAL_SECS, MAX_SYNC_INTERVAL_SECS
+            ),
+        );
+    }
+}
+
+struct Options<'a> {
+    source_type: &'a str,
+    public_ics: bool,
+    public_ics_path: Option<&'a str>,
+    proxy_enabled: Option<bool>,
+    attachment_mode: Option<&'a str>,
+    archive_after_months: Option<i64>,
+    sync_on_startup: Option<&'a str>,
+    fetch_mode: Option<&'a str>,
+    user_agent: Option<&'a str>,
+    custom_headers: Option<&'a CustomHeaders>,
+    sync_window: Option<&'a str>,
+    working_hours: Option<&'a str>,
+    repair_rules: Option<&'a str>,
+    time_shift_zone: Option<&'a str>,
+    time_shift_mode: Option<&'a str>,
+    max_events: Option<i64>,
+    max_feed_bytes: Option<i64>,
+    feed_limit_policy: Option<&'a str>,
+    // As stored after the change, unlike the fields above.
+    holiday_region: &'a str,
+}
+
+fn check_options(errors: &mut FieldErrors, opts: Options) {
+    if opts.public_ics_path.is_some_and(|p| !p.trim().is_empty()) && !opts.public_ics {
+        errors.add("public_ics_path", "requires public_ics to be true");
+    }
+    if opts.proxy_enabled == Some(true) && opts.source_type != "caldav" {
+        errors.add("proxy_enabled", "is only available for CalDAV sources");
+    }
+    if let Some(mode) = opts.attachment_mode
+        && !ATTACHMENT_MODES.contains(&mode)
+    {
+        errors.add(
+            "attachment_mode",
+            format!("must be one of: {}", ATTACHMENT_MODES.join(", ")),
+        );
+    }
+    if let Some(mode) = opts.sync_on_startup
+        && !STARTUP_SYNC_MODES.contains(&mode)
+    {
+        errors.add(
+            "sync_on_startup",
+            format!("must be one of: {}", STARTUP_SYNC_MODES.join(", ")),
+        );
+    }
+    if let Some(mode) = opts.fetch_mode
+        && !FETCH_MODES.contains(&mode)
+    {
+        errors.add(
+            "fetch_mode",
+            format!("must be one of: {}", FETCH_MODES.join(", ")),
+        );
+    }
+    if let Some(Err(e)) = opts.user_agent.map(http_clients::validate_user_agent) {
+        errors.add("user_agent", e.to_string());
+    }
+    if let Some(Err(e)) = opts
+        .custom_headers
+        .map(http_clients::validate_custom_headers)
+    {
+        errors.add("custom_headers", e.to_string());
+    }
+    if let Some(Err(e)) = opts.sync_window.map(SyncWindow::parse) {
+        errors.add("sync_window", e.to_string());
+    }
+    if let Some(Err(e)) = opts.working_hours.map(WorkingHours::parse) {
+        errors.add("working_hours", e.to_string());
+    }
+    if let Some(Err(e)) = opts.repair_rules.map(ics_repair::normalize_rules) {
+        errors.add("repair_rules", e.to_string());
+    }
+    if let Some(zone) = opts.time_shift_zone.filter(|z| !z.trim().is_empty())
+        && let Err(e) = time_shift::parse_zone(zone)
+    {
+        errors.add("time_shift_zone", e.to_string());
+    }
+    if let Some(mode) = opts.time_shift_mode
+        && !time_shift::MODES.contains(&mode)
+    {
+        errors.add(
+            "time_shift_mode",
+            format!("must be one of: {}", time_shift::MODES.join(", ")),
+        );
+    }
+    if let Some(policy) = opts.feed_limit_policy
+        && !feed_limits::POLICIES.contains(&policy)
+    {
+        errors.add(
+            "feed_limit_policy",
+            format!("must be one of: {}", feed_limits::POLICIES.join(", ")),
+        );
+    }
+    for (field, value) in [
+        ("max_events", opts.max_events),
+        ("max_feed_bytes", opts.max_feed_bytes),
+    ] {
+        if value.is_some_and(|v| v < 0) {
+            errors.add(field, "cannot be negative");
+        }
+    }
+    match opts.source_type {
+        "holidays" if opts.holiday_region.trim().is_empty() => {
+            errors.add("holiday_region", "is required for holiday sources");
+        }
+        "holidays" => {
+            if let Err(e) = holidays::normalize_region(opts.holiday_region) {
+                errors.add("holiday_region", e.to_string());
+            }
+        }
+        _ if !opts.holiday_region.trim().is_empty() => {
+            errors.add("holiday_region", "is only used by holiday sources");
+        }
+        _ => {}
+    }
+    if let Some(months) = opts.archive_after_months
+        && !(0..=MAX_ARCHIVE_MONTHS).contains(&months)
+    {
+        errors.add(
+            "archive_after_months",
+            format!("must be between 0 and {}", MAX_ARCHIVE_MONTHS),
+        );
+    }
+}
+
+// Checks a new source before it is written. Field problems come back as a
+// FieldErrors error; anything else is a database failure.
+pub fn check_create_source(conn: &Connection, src: &CreateSource) -> Result<()> {
+    let mut errors = FieldErrors::default();
+    let source_type = src.source_type.as_deref().unwrap_or("caldav");
+    check_name(conn, &mut errors, &src.name, src.owner_id, None)?;
+    check_connection(
+        &mut errors,
+        source_type,
+        &src.caldav_url,
+        &src.username,
+        &src.password,
+    );
+    check_interval(&mut errors, src.sync_interval_secs);
+    check_options(
+        &mut errors,
+        Options {
+            source_type,
+            public_ics: src.public_ics,
+            public_ics_path: src.public_ics_path.as_deref(),
+            proxy_enabled: src.proxy_enabled,
+            attachment_mode: src.attachment_mode.as_deref(),
+            archive_after_months: src.archive_after_months,
+            sync_on_startup: src.sync_on_startup.as_deref(),
+            fetch_mode: src.fetch_mode.as_deref(),
+            user_agent: src.user_agent.as_deref(),
+            custom_headers: src.custom_headers.as_ref(),
+            sync_window: src.sync_window.as_deref(),
+            working_hours: src.working_hours.as_deref(),
+            repair_rules: src.repair_rules.as_deref(),
+            time_shift_zone: src.time_shift_zone.as_deref(),
+            time_shift_mode: src.time_shift_mode.as_deref(),
+            max_events: src.max_events,
+            max_feed_bytes: src.max_feed_bytes,
+            feed_limit_policy: src.feed_limit_policy.as_deref(),
+            holiday_region: src.holiday_region.as_deref().unwrap_or(""),
+        },
+    );
+    errors.into_result()
+}
+
+// Same as check_create_source for the fields an update sets, merged with the
+// stored source. An empty password keeps the stored one.
+pub fn check_update_source(conn: &Connection, existing: &Source, upd: &UpdateSource) -> Result<()> {
+    let mut errors = FieldErrors::default();
+    let source_type = upd.source_type.as_deref().unwrap_or(&existing.source_type);
+    if let Some(ref name) = upd.name {
+        let owner_id = upd.owner_id.or(existing.owner_id);
+        check_name(conn, &mut errors, name, owner_id, Some(existing.id))?;
+    }
+    if upd.source_type.is_some()
+        || upd.caldav_url.is_some()
+        || upd.username.is_some()
+        || upd.password.is_some()
+    {
+        check_connection(
+            &mut errors,
+            source_type,
+            upd.caldav_url.as_deref().unwrap_or(&existing.caldav_url),
+            upd.username.as_deref().unwrap_or(&existing.username),
+            upd.password
+                .as_deref()
+                .filter(|p| !p.trim().is_empty())
+                .unwrap_or(&existing.password),
+        );
+    }
+    if let Some(secs) = upd.sync_interval_secs {
+        check_interval(&mut errors, secs);
+    }
+    check_options(
+        &mut errors,
+        Options {
+            source_type,
+            public_ics: upd.public_ics.unwrap_or(existing.public_ics),
+            public_ics_path: upd.public_ics_path.as_deref(),
+            proxy_enabled: upd.proxy_enabled,
+            attachment_mode: upd.attachment_mode.as_deref(),
+            archive_after_months: upd.archive_after_months,
+            sync_on_startup: upd.sync_on_startup.as_deref(),
+            fetch_mode: upd.fetch_mode.as_deref(),
+            user_agent: upd.user_agent.as_deref(),
+            custom_headers: upd.custom_headers.as_ref(),
+            sync_window: upd.sync_window.as_deref(),
+            working_hours: upd.working_hours.as_deref(),
+            repair_rules: upd.repair_rules.as_deref(),
+            time_shift_zone: upd.time_shift_zone.as_deref(),
+            time_shift_mode: upd.time_shift_mode.as_deref(),
+            max_events: upd.max_events,
+            max_feed_bytes: upd.max_feed_bytes,
+            feed_limit_policy: upd.feed_limit_policy.as_deref(),
+            holiday_region: match (&upd.holiday_region, source_type) {
+                (Some(region), _) => region,
+                (None, "holidays") => &existing.holiday_region,
+                (None, _) => "",
+            },
+        },
+    );
+    errors.into_result()
+}
@@ -0,0 +1,301 @@
+use std::path::Path;
+
+use caldav_ics_sync::config::{AppConfig, network_filesystem};
+use caldav_ics_sync::server::access_log::AccessLogFormat;
+
+fn write_temp(name: &str, contents: &str) -> std::path::PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("caldav-ics-sync-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn load_without_file_uses_defaults() {
+    let cfg = AppConfig::load_from(None).unwrap();
+    assert!(cfg.sources.is_empty());
+    assert!(cfg.destinations.is_empty());
+}
+
+#[test]
+fn load_toml_file_with_declared_sources() {
+    let path = write_temp(
+        "config.toml",
+        r#"
+data_dir = "/tmp/from-toml"
+
+[[sources]]
+name = "Work"
+caldav_url = "https://caldav.example.com/dav"
+username = "user"
+password = "pass"
+ics_path = "work"
+sync_interval_secs = "15m"
+
+[[destinations]]
+name = "Holidays"
+ics_url = "https://example.com/holidays.ics"
+caldav_url = "https://caldav.example.com/dav"
+calendar_name = "holidays"
+username = "user"
+password = "pass"
+sync_interval_secs = 3600
+keep_local = true
+"#,
+    );
+    let cfg = AppConfig::load_from(path.to_str()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(cfg.data_dir, "/tmp/from-toml");
+    assert_eq!(cfg.sources.len(), 1);
+    assert_eq!(cfg.sources[0].ics_path, "work");
+    assert_eq!(cfg.sources[0].sync_interval_secs, 900);
+    assert_eq!(cfg.destinations.len(), 1);
+    assert!(cfg.destinations[0].keep_local);
+}
+
+#[test]
+fn load_yaml_file() {
+    let path = write_temp(
+        "config.yaml",
+        r#"
+server_port: 7000
+sources:
+  - name: Uploads
+    source_type: upload
+    ics_path: uploads
+    sync_interval_secs: 0
+"#,
+    );
+    let cfg = AppConfig::load_from(path.to_str()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(cfg.server_port, 7000);
+    assert_eq!(cfg.sources[0].source_type.as_deref(), Some("upload"));
+}
+
+#[test]
+fn load_missing_file_is_an_error() {
+    assert!(AppConfig::load_from(Some("/nonexistent/caldav-ics-sync.toml")).is_err());
+}
+
+#[test]
+fn password_file_is_read_and_trimmed() {
+    let secret = write_temp("auth_password", "s3cret\n");
+    let cfg_path = write_temp(
+        "secrets.toml",
+        &format!(
+            "auth_username = \"admin\"\nauth_password_file = \"{}\"\n",
+            secret.display()
+        ),
+    );
+    let cfg = AppConfig::load_from(cfg_path.to_str()).unwrap();
+    std::fs::remove_file(&secret).unwrap();
+    std::fs::remove_file(&cfg_path).unwrap();
+
+    assert_eq!(cfg.auth_password.as_deref(), Some("s3cret"));
+}
+
+#[test]
+fn password_and_password_file_are_mutually_exclusive() {
+    let secret = write_temp("auth_password_dup", "s3cret");
+    let cfg_path = write_temp(
+        "secrets-dup.toml",
+        &format!(
+            "auth_password = \"other\"\nauth_password_file = \"{}\"\n",
+            secret.display()
+        ),
+    );
+    let err = AppConfig::load_from(cfg_path.to_str()).unwrap_err();
+    std::fs::remove_file(&secret).unwrap();
+    std::fs::remove_file(&cfg_path).unwrap();
+
+    assert!(err.to_string().contains("AUTH_PASSWORD_FILE"));
+}
+
+#[test]
+fn writable_check_reports_actionable_error() {
+    let blocker = write_temp("not-a-dir", "");
+    let cfg_path = write_temp(
+        "readonly.toml",
+        &format!("data_dir = \"{}/data\"\n", blocker.display()),
+    );
+    let cfg = AppConfig::load_from(cfg_path.to_str()).unwrap();
+    let err = cfg.check_writable_dirs().unwrap_err();
+    std::fs::remove_file(&blocker).unwrap();
+    std::fs::remove_file(&cfg_path).unwrap();
+
+    assert!(format!("{:#}", err).contains("DATA_DIR"));
+}
+
+#[test]
+fn writable_check_passes_for_temp_dir() {
+    let dir = std::env::temp_dir().join(format!("caldav-ics-sync-{}-ok", std::process::id()));
+    let cfg_path = write_temp(
+        "writable.toml",
+        &format!("data_dir = \"{}\"\n", dir.display()),
+    );
+    let cfg = AppConfig::load_from(cfg_path.to_str()).unwrap();
+    cfg.check_writable_dirs().unwrap();
+    std::fs::remove_file(&cfg_path).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn sqlite_options_are_validated_and_rendered() {
+    let cfg_path = write_temp(
+        "sqlite.toml",
+        "sqlite_journal_mode = \"DELETE\"\nsqlite_synchronous = \"normal\"\nsqlite_busy_timeout = \"30s\"\n",
+    );
+    let cfg = AppConfig::load_from(cfg_path.to_str()).unwrap();
+    std::fs::remove_file(&cfg_path).unwrap();
+    let pragmas = cfg.sqlite_pragmas();
+    assert!(pragmas.contains("journal_mode=delete"));
+    assert!(pragmas.contains("synchronous=normal"));
+    assert!(pragmas.contains("busy_timeout=30000"));
+
+    let cfg_path = write_temp("sqlite-bad.toml", "sqlite_journal_mode = \"wall\"\n");
+    let err = AppConfig::load_from(cfg_path.to_str()).unwrap_err();
+    std::fs::remove_file(&cfg_path).unwrap();
+    assert!(err.to_string().contains("SQLITE_JOURNAL_MODE"));
+}
+
+#[test]
+fn access_log_format_is_configurable() {
+    assert_eq!(
+        AppConfig::load_from(None).unwrap().access_log,
+        AccessLogFormat::Text
+    );
+    let cfg_path = write_temp("access-log.toml", "access_log = \"json\"\n");
+    let cfg = AppConfig::load_from(cfg_path.to_str()).unwrap();
+    std::fs::remove_file(&cfg_path).unwrap();
+    assert_eq!(cfg.access_log, AccessLogFormat::Json);
+
+    let cfg_path = write_temp("access-log-bad.toml", "access_log = \"xml\"\n");
+    assert!(AppConfig::load_from(cfg_path.to_str()).is_err());
+    std::fs::remove_file(&cfg_path).unwrap();
+}
+
+#[test]
+fn sync_concurrency_must_be_positive() {
+    assert_eq!(AppConfig::load_from(None).unwrap().sync_concurrency, 4);
+    let cfg_path = write_temp("sync-concurrency.toml", "sync_concurrency = 0\n");
+    let err = AppConfig::load_from(cfg_path.to_str()).unwrap_err();
+    std::fs::remove_file(&cfg_path).unwrap();
+    assert!(err.to_string().contains("SYNC_CONCURRENCY"));
+
+    assert_eq!(AppConfig::load_from(None).unwrap().sync_host_concurrency, 2);
+    let cfg_path = write_temp("sync-host-concurrency.toml", "sync_host_concurrency = 0\n");
+    let err = AppConfig::load_from(cfg_path.to_str()).unwrap_err();
+    std::fs::remove_file(&cfg_path).unwrap();
+    assert!(err.to_string().contains("SYNC_HOST_CONCURRENCY"));
+}
+
+#[test]
+fn base_path_is_normalized() {
+    assert_eq!(AppConfig::load_from(None).unwrap().base_path, "");
+    let cfg_path = write_temp("base-path.toml", "base_path = \"calsync/\"\n");
+    let cfg = AppConfig::load_from(cfg_path.to_str()).unwrap();
+    std::fs::remove_file(&cfg_path).unwrap();
+    assert_eq!(cfg.base_path, "/calsync");
+
+    let cfg_path = write_temp("base-path-bad.toml", "base_path = \"/a/../b\"\n");
+    let err = AppConfig::load_from(cfg_path.to_str()).unwrap_err();
+    std::fs::remove_file(&cfg_path).unwrap();
+    assert!(err.to_string().contains("BASE_PATH"));
+}
+
+#[test]
+fn network_filesystem_uses_longest_matching_mount() {
+    let mounts = "overlay / overlay rw 0 0\n\
+                  nas:/export /mnt/nas nfs4 rw 0 0\n\
+                  /dev/sda1 /mnt/nas/local ext4 rw 0 0\n";
+    assert_eq!(
+        network_filesystem(mounts, Path::new("/mnt/nas/data")).as_deref(),
+        Some("nfs4")
+    );
+    assert_eq!(
+        network_filesystem(mounts, Path::new("/mnt/nas/local/data")),
+        None
+    );
+    assert_eq!(network_filesystem(mounts, Path::new("/data")), None);
+}
+
+#[tokio::test]
+async fn check_reports_outdated_schema_and_passes_otherwise() {
+    use caldav_ics_sync::doctor::{self, Level};
+
+    let dir = std::env::temp_dir().join(format!("caldav-ics-sync-{}-doctor", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cfg_path = write_temp(
+        "doctor.toml",
+        &format!("data_dir = \"{}\"\n", dir.display()),
+    );
+    let cfg = AppConfig::load_from(cfg_path.to_str()).unwrap();
+
+    let findings = doctor::run(&cfg, false).await;
+    assert!(
+        findings.iter().all(|f| f.level == Level::Ok),
+        "{:?}",
+        findings
+    );
+
+    let conn = rusqlite::Connection::open(cfg.db_path()).unwrap();
+    conn.execute_batch("CREATE TABLE sources (id INTEGER PRIMARY KEY, name TEXT)")
+        .unwrap();
+    drop(conn);
+    let findings = doctor::run(&cfg, false).await;
+    std::fs::remove_file(&cfg_path).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let schema = findings
+        .iter()
+        .find(|f| f.message.contains("migrated on startup"))
+        .unwrap();
+    assert_eq!(schema.level, Level::Warning);
+    assert!(schema.message.contains("sources.caldav_url"));
+    assert!(findings.iter().all(|f| f.level != Level::Failure));
+}
+
+#[test]
+fn legacy_single_source_settings_describe_a_source() {
+    let cfg_path = write_temp(
+        "legacy.toml",
+        "caldav_url = \"https://cal.example.com/dav/\"\ncaldav_username = \"alice\"\ncaldav_password = \"secret\"\nstorage_strategy = \"file\"\n",
+    );
+    let cfg = AppConfig::load_from(cfg_path.to_str()).unwrap();
+    std::fs::remove_file(&cfg_path).unwrap();
+
+    assert_eq!(
+        cfg.deprecated_settings(),
+        [
+            "CALDAV_URL",
+            "CALDAV_USERNAME",
+            "CALDAV_PASSWORD",
+            "STORAGE_STRATEGY"
+        ]
+    );
+    let src = cfg.legacy_source().unwrap();
+    assert_eq!(src.caldav_url, "https://cal.example.com/dav/");
+    assert_eq!(src.username, "alice");
+    assert_eq!(src.password, "secret");
+    assert_eq!(src.ics_path, "calendar");
+
+    let cfg = AppConfig::load_from(None).unwrap();
+    assert!(cfg.legacy_source().is_none());
+}
+
+#[test]
+fn invalid_duration_and_size_errors_name_the_setting() {
+    let path = write_temp("bad-units.toml", "max_body_size = \"10XB\"\n");
+    let err = AppConfig::load_from(path.to_str()).unwrap_err().to_string();
+    std::fs::remove_file(&path).unwrap();
+    assert!(err.contains("max_body_size"), "{}", err);
+    assert!(err.contains("10MB"), "{}", err);
+
+    let path = write_temp("bad-duration.toml", "session_ttl = \"7x\"\n");
+    let err = AppConfig::load_from(path.to_str()).unwrap_err().to_string();
+    std::fs::remove_file(&path).unwrap();
+    assert!(err.contains("session_ttl"), "{}", err);
+    assert!(err.contains("15m"), "{}", err);
+}
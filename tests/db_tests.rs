@@ -18,6 +18,7 @@ fn valid_source() -> CreateSource {
         sync_interval_secs: 3600,
         public_ics: false,
         public_ics_path: None,
+        ..Default::default()
     }
 }
 
@@ -32,6 +33,7 @@ fn valid_destination() -> CreateDestination {
         sync_interval_secs: 3600,
         sync_all: false,
         keep_local: false,
+        ..Default::default()
     }
 }
 
@@ -153,13 +155,8 @@ fn update_source_preserves_password_on_empty() {
     let id = create_source(&conn, &valid_source()).unwrap();
     let upd = UpdateSource {
         name: Some("Renamed".into()),
-        caldav_url: None,
-        username: None,
         password: Some("".into()),
-        ics_path: None,
-        sync_interval_secs: None,
-        public_ics: None,
-        public_ics_path: None,
+        ..Default::default()
     };
     update_source(&conn, id, &upd).unwrap();
     let src = get_source(&conn, id).unwrap().unwrap();
@@ -177,14 +174,8 @@ fn update_source_rejects_duplicate_ics_path() {
     create_source(&conn, &s2).unwrap();
 
     let upd = UpdateSource {
-        name: None,
-        caldav_url: None,
-        username: None,
-        password: None,
         ics_path: Some("other.ics".into()),
-        sync_interval_secs: None,
-        public_ics: None,
-        public_ics_path: None,
+        ..Default::default()
     };
     assert!(update_source(&conn, id1, &upd).is_err());
 }
@@ -290,14 +281,8 @@ fn update_public_ics_false_clears_public_path() {
     let id = create_source(&conn, &s).unwrap();
 
     let upd = UpdateSource {
-        name: None,
-        caldav_url: None,
-        username: None,
-        password: None,
-        ics_path: None,
-        sync_interval_secs: None,
         public_ics: Some(false),
-        public_ics_path: None,
+        ..Default::default()
     };
     update_source(&conn, id, &upd).unwrap();
     let src = get_source(&conn, id).unwrap().unwrap();
@@ -318,20 +303,84 @@ fn get_ics_data_by_public_path_only_when_public() {
     assert!(data.is_some());
 
     let upd = UpdateSource {
-        name: None,
-        caldav_url: None,
-        username: None,
-        password: None,
-        ics_path: None,
-        sync_interval_secs: None,
         public_ics: Some(false),
-        public_ics_path: None,
+        ..Default::default()
     };
     update_source(&conn, id, &upd).unwrap();
     let data = get_ics_data_by_public_path(&conn, "shared.ics").unwrap();
     assert!(data.is_none());
 }
 
+#[test]
+fn event_index_keeps_unchanged_rows_and_full_text_index_in_step() {
+    let conn = setup();
+    let id = create_source(&conn, &valid_source()).unwrap();
+    let feed = |second: &str| {
+        format!(
+            "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:1\r\nSUMMARY:Dentist\r\nEND:VEVENT\r\n\
+             BEGIN:VEVENT\r\nUID:2\r\nSUMMARY:{}\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+            second
+        )
+    };
+    let row_id = |uid: &str| -> i64 {
+        conn.query_row("SELECT id FROM events WHERE uid = ?1", [uid], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    };
+    let matches = |q: &str| -> Vec<String> {
+        let search = EventSearch {
+            keywords: Some(q.into()),
+            limit: 10,
+            ..Default::default()
+        };
+        search_events(&conn, &search)
+            .unwrap()
+            .into_iter()
+            .filter_map(|e| e.uid)
+            .collect()
+    };
+
+    save_ics_data(&conn, id, &feed("Standup")).unwrap();
+    let first = row_id("1");
+    assert_eq!(matches("stand"), ["2"]);
+
+    save_ics_data(&conn, id, &feed("Café planning")).unwrap();
+    assert_eq!(row_id("1"), first);
+    assert!(matches("standup").is_empty());
+    assert_eq!(matches("cafe"), ["2"]);
+
+    delete_source(&conn, id).unwrap();
+    assert!(matches("dentist").is_empty());
+}
+
+#[test]
+fn save_ics_data_skips_unchanged_content() {
+    let conn = setup();
+    let id = create_source(&conn, &valid_source()).unwrap();
+    let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:1\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    save_ics_data(&conn, id, ics).unwrap();
+    conn.execute(
+        "UPDATE ics_data SET updated_at = '2020-01-01 00:00:00' WHERE source_id = ?1",
+        [id],
+    )
+    .unwrap();
+    let updated_at = |conn: &Connection| -> String {
+        conn.query_row(
+            "SELECT updated_at FROM ics_data WHERE source_id = ?1",
+            [id],
+            |row| row.get(0),
+        )
+        .unwrap()
+    };
+
+    save_ics_data(&conn, id, ics).unwrap();
+    assert_eq!(updated_at(&conn), "2020-01-01 00:00:00");
+
+    save_ics_data(&conn, id, &ics.replace("UID:1", "UID:2")).unwrap();
+    assert_ne!(updated_at(&conn), "2020-01-01 00:00:00");
+}
+
 #[test]
 fn is_public_standard_ics_true_when_public_no_custom_path() {
     let conn = setup();
@@ -694,14 +743,8 @@ fn update_destination_preserves_password_on_empty() {
     let id = create_destination(&conn, &valid_destination()).unwrap();
     let upd = UpdateDestination {
         name: Some("Renamed".into()),
-        ics_url: None,
-        caldav_url: None,
-        calendar_name: None,
-        username: None,
         password: Some("".into()),
-        sync_interval_secs: None,
-        sync_all: None,
-        keep_local: None,
+        ..Default::default()
     };
     update_destination(&conn, id, &upd).unwrap();
     let dest = get_destination(&conn, id).unwrap().unwrap();
@@ -709,6 +752,90 @@ fn update_destination_preserves_password_on_empty() {
     assert_eq!(dest.password, "pass");
 }
 
+#[test]
+fn destination_templates_are_validated() {
+    let conn = setup();
+    let mut d = valid_destination();
+    d.summary_template = Some("{summary".into());
+    assert!(create_destination(&conn, &d).is_err());
+    d.summary_template = Some("[{x-ticket}] {summary}".into());
+    d.drop_properties = Some("uid".into());
+    assert!(create_destination(&conn, &d).is_err());
+    d.drop_properties = Some(" attendee, X-Ticket".into());
+    let id = create_destination(&conn, &d).unwrap();
+    let dest = get_destination(&conn, id).unwrap().unwrap();
+    assert_eq!(dest.drop_properties, "ATTENDEE,X-TICKET");
+
+    let upd = UpdateDestination {
+        location_template: Some("{room".into()),
+        ..Default::default()
+    };
+    assert!(update_destination(&conn, id, &upd).is_err());
+}
+
+#[test]
+fn destination_pace_is_validated() {
+    let conn = setup();
+    let mut d = valid_destination();
+    d.max_requests_per_second = Some(-2.0);
+    assert!(create_destination(&conn, &d).is_err());
+    d.max_requests_per_second = Some(0.5);
+    d.request_delay_ms = Some(250);
+    let id = create_destination(&conn, &d).unwrap();
+    let dest = get_destination(&conn, id).unwrap().unwrap();
+    assert_eq!(
+        (dest.max_requests_per_second, dest.request_delay_ms),
+        (0.5, 250)
+    );
+
+    let upd = UpdateDestination {
+        request_delay_ms: Some(3_600_000),
+        ..Default::default()
+    };
+    assert!(update_destination(&conn, id, &upd).is_err());
+}
+
+#[test]
+fn destination_sync_state_tracks_progress_and_failures() {
+    use std::collections::HashMap;
+
+    let conn = setup();
+    let id = create_destination(&conn, &valid_destination()).unwrap();
+    let failed = |uid: &str| FailedEvent {
+        uid: uid.into(),
+        error: "PUT returned 500".into(),
+        attempts: 1,
+    };
+    let etags = HashMap::from([
+        ("kept".to_string(), Some("\"1\"".to_string())),
+        ("orphan".to_string(), Some("\"2\"".to_string())),
+    ]);
+    save_destination_events(&conn, id, &etags, &[], &[failed("flaky")]).unwrap();
+
+    // A run that uploads one event and is cut short while deleting.
+    record_destination_upload(&conn, id, "flaky", Some("\"3\"")).unwrap();
+    let orphan = "orphan".to_string();
+    mark_destination_deletes(&conn, id, &[&orphan]).unwrap();
+    let state = get_destination_sync_state(&conn, id).unwrap();
+    assert_eq!(state.synced, 2);
+    assert_eq!(state.pending_deletes, ["orphan"]);
+    assert!(state.failed.is_empty());
+    assert_eq!(get_destination_event_etags(&conn, id).unwrap().len(), 3);
+
+    // Failing on consecutive syncs adds up; an event that stops failing is cleared.
+    save_destination_events(&conn, id, &etags, &[], &[failed("new")]).unwrap();
+    save_destination_events(&conn, id, &etags, &[], &[failed("new")]).unwrap();
+    let state = get_destination_sync_state(&conn, id).unwrap();
+    assert!(state.pending_deletes.is_empty());
+    assert_eq!(state.failed.len(), 1);
+    assert_eq!(
+        (state.failed[0].uid.as_str(), state.failed[0].attempts),
+        ("new", 2)
+    );
+    save_destination_events(&conn, id, &etags, &[], &[]).unwrap();
+    assert!(list_destination_failures(&conn, id).unwrap().is_empty());
+}
+
 #[test]
 fn delete_destination_removes_it() {
     let conn = setup();
@@ -842,3 +969,371 @@ fn create_source_rejects_public_path_matching_existing_source_path() {
     s2.public_ics_path = Some("taken.ics".into());
     assert!(create_source(&conn, &s2).is_err());
 }
+
+// ---- Attachments ----
+
+#[test]
+fn create_source_rejects_unknown_attachment_mode() {
+    let conn = setup();
+    let mut s = valid_source();
+    s.attachment_mode = Some("inline".into());
+    assert!(create_source(&conn, &s).is_err());
+}
+
+#[test]
+fn create_source_defaults_attachment_mode_to_keep() {
+    let conn = setup();
+    let id = create_source(&conn, &valid_source()).unwrap();
+    let s = get_source(&conn, id).unwrap().unwrap();
+    assert_eq!(s.attachment_mode, "keep");
+}
+
+#[test]
+fn save_attachments_prunes_unreferenced_entries() {
+    let conn = setup();
+    let id = create_source(&conn, &valid_source()).unwrap();
+    let a = Attachment {
+        hash: "aaa".into(),
+        fmttype: Some("image/png".into()),
+        data: vec![1, 2, 3],
+    };
+    let b = Attachment {
+        hash: "bbb".into(),
+        fmttype: None,
+        data: vec![4],
+    };
+    save_attachments(&conn, id, &[a], "ATTACH:/attachments/aaa").unwrap();
+    save_attachments(
+        &conn,
+        id,
+        std::slice::from_ref(&b),
+        "ATTACH:/attachments/aaa\r\nATTACH:/attachments/bbb",
+    )
+    .unwrap();
+    assert!(get_attachment(&conn, "aaa").unwrap().is_some());
+    save_attachments(&conn, id, &[b], "ATTACH:/attachments/bbb").unwrap();
+    assert!(get_attachment(&conn, "aaa").unwrap().is_none());
+    let got = get_attachment(&conn, "bbb").unwrap().unwrap();
+    assert_eq!(got.data, vec![4]);
+}
+
+// ---- Declarative config ----
+
+#[test]
+fn apply_declared_config_is_idempotent() {
+    let conn = setup();
+    let mut src = valid_source();
+    src.name = "Declared".into();
+    let dest = valid_destination();
+
+    apply_declared_config(
+        &conn,
+        std::slice::from_ref(&src),
+        std::slice::from_ref(&dest),
+    )
+    .unwrap();
+    apply_declared_config(
+        &conn,
+        std::slice::from_ref(&src),
+        std::slice::from_ref(&dest),
+    )
+    .unwrap();
+    assert_eq!(list_sources(&conn).unwrap().len(), 1);
+    assert_eq!(list_destinations(&conn).unwrap().len(), 1);
+
+    src.name = "Renamed".into();
+    src.sync_interval_secs = 60;
+    apply_declared_config(&conn, &[src], &[dest]).unwrap();
+    let sources = list_sources(&conn).unwrap();
+    assert_eq!(sources.len(), 1);
+    assert_eq!(sources[0].name, "Renamed");
+    assert_eq!(sources[0].sync_interval_secs, 60);
+}
+
+#[test]
+fn apply_declared_config_rolls_back_on_invalid_entry() {
+    let conn = setup();
+    let mut bad = valid_source();
+    bad.ics_path = "bad.ics".into();
+    bad.name = "".into();
+
+    assert!(apply_declared_config(&conn, &[valid_source(), bad], &[]).is_err());
+    assert!(list_sources(&conn).unwrap().is_empty());
+}
+
+// ---- Users ----
+
+#[test]
+fn create_user_hashes_password_and_rejects_duplicates() {
+    let conn = setup();
+    let id = create_user(
+        &conn,
+        &CreateUser {
+            username: "alice".into(),
+            password: "secret".into(),
+            is_admin: false,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let user = get_user(&conn, id).unwrap().unwrap();
+    assert!(user.password_hash.starts_with("$argon2"));
+    assert!(!user.is_admin);
+
+    let err = create_user(
+        &conn,
+        &CreateUser {
+            username: "alice".into(),
+            password: "other".into(),
+            is_admin: false,
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("Duplicate username"));
+}
+
+#[test]
+fn owned_sources_are_scoped_and_released_on_user_delete() {
+    let conn = setup();
+    let alice = create_user(
+        &conn,
+        &CreateUser {
+            username: "alice".into(),
+            password: "secret".into(),
+            is_admin: false,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let mut src = valid_source();
+    src.owner_id = Some(alice);
+    let owned = create_source(&conn, &src).unwrap();
+    let mut other = valid_source();
+    other.ics_path = "other.ics".into();
+    create_source(&conn, &other).unwrap();
+
+    let visible = list_sources_for_owner(&conn, alice).unwrap();
+    assert_eq!(visible.len(), 1);
+    assert_eq!(visible[0].id, owned);
+    assert!(source_owned_by(&conn, owned, alice).unwrap());
+    assert!(feed_owned_by(&conn, "cal.ics", alice).unwrap());
+    assert!(!feed_owned_by(&conn, "other.ics", alice).unwrap());
+
+    delete_user(&conn, alice).unwrap();
+    assert_eq!(get_source(&conn, owned).unwrap().unwrap().owner_id, None);
+}
+
+#[test]
+fn create_source_rejects_unknown_owner() {
+    let conn = setup();
+    let mut src = valid_source();
+    src.owner_id = Some(42);
+    assert!(create_source(&conn, &src).is_err());
+}
+
+// ---- Quotas ----
+
+fn quota_user(conn: &Connection, quotas: Quotas) -> i64 {
+    create_user(
+        conn,
+        &CreateUser {
+            username: "alice".into(),
+            password: "secret".into(),
+            is_admin: false,
+            quotas,
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+const TWO_EVENTS: &str = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:a\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nUID:b\r\nEND:VEVENT\r\nEND:VCALENDAR";
+
+#[test]
+fn source_and_event_quotas_are_enforced() {
+    let conn = setup();
+    let alice = quota_user(
+        &conn,
+        Quotas {
+            max_sources: Some(1),
+            max_events: Some(3),
+            ..Default::default()
+        },
+    );
+    let mut src = valid_source();
+    src.owner_id = Some(alice);
+    let id = create_source(&conn, &src).unwrap();
+    let mut second = valid_source();
+    second.ics_path = "second.ics".into();
+    second.owner_id = Some(alice);
+    let err = create_source(&conn, &second).unwrap_err();
+    assert!(err.to_string().contains("Source quota reached"));
+
+    let source = get_source(&conn, id).unwrap().unwrap();
+    check_event_quota(&conn, &source, 2).unwrap();
+    save_ics_data(&conn, id, TWO_EVENTS).unwrap();
+    check_event_quota(&conn, &source, 3).unwrap();
+    assert!(check_event_quota(&conn, &source, 4).is_err());
+
+    let usage = get_usage(&conn, Some(alice)).unwrap();
+    assert_eq!(usage.sources, 1);
+    assert_eq!(usage.events, 2);
+}
+
+#[test]
+fn feed_bytes_are_metered_per_day() {
+    let conn = setup();
+    let alice = quota_user(
+        &conn,
+        Quotas {
+            max_feed_bytes_per_day: Some(100),
+            ..Default::default()
+        },
+    );
+    let mut src = valid_source();
+    src.owner_id = Some(alice);
+    create_source(&conn, &src).unwrap();
+    assert_eq!(feed_owner(&conn, "cal.ics").unwrap(), Some(alice));
+
+    assert!(record_feed_bytes(&conn, alice, 60).unwrap());
+    assert!(!record_feed_bytes(&conn, alice, 60).unwrap());
+    assert!(record_feed_bytes(&conn, alice, 40).unwrap());
+    assert_eq!(get_usage(&conn, Some(alice)).unwrap().feed_bytes_today, 100);
+}
+
+#[test]
+fn update_user_replaces_quotas() {
+    let conn = setup();
+    let alice = quota_user(
+        &conn,
+        Quotas {
+            max_sources: Some(1),
+            ..Default::default()
+        },
+    );
+    update_user(
+        &conn,
+        alice,
+        &UpdateUser {
+            quotas: Some(Quotas {
+                max_events: Some(10),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let user = get_user(&conn, alice).unwrap().unwrap();
+    assert_eq!(user.quotas.max_sources, None);
+    assert_eq!(user.quotas.max_events, Some(10));
+
+    let negative = UpdateUser {
+        quotas: Some(Quotas {
+            max_sources: Some(-1),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    assert!(update_user(&conn, alice, &negative).is_err());
+}
+
+#[test]
+fn maintenance_prunes_history_and_reclaims_space() {
+    use caldav_ics_sync::maintenance::{self, Settings};
+
+    let path = std::env::temp_dir().join(format!(
+        "caldav-ics-sync-{}-maintenance.db",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let conn = Connection::open(&path).unwrap();
+    conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON;")
+        .unwrap();
+    init_db(&conn).unwrap();
+    let user = create_user(
+        &conn,
+        &CreateUser {
+            username: "alice".into(),
+            password: "secret".into(),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    // Ten years of daily usage, and one session that has run out.
+    conn.execute(
+        "WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 3650)
+         INSERT INTO feed_usage (user_id, day, bytes) SELECT ?1, date('now', '-' || i || ' days'), 1000 FROM n",
+        [user],
+    )
+    .unwrap();
+    let kept = create_session(&conn, Some(user), "alice", 3600).unwrap();
+    conn.execute(
+        "INSERT INTO sessions (id, user_id, username, expires_at) VALUES ('old', ?1, 'alice', datetime('now', '-1 day'))",
+        [user],
+    )
+    .unwrap();
+
+    let report = maintenance::run(
+        &conn,
+        &Settings {
+            retention_secs: 30 * 24 * 3600,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(report.usage_rows_pruned, 3650 - 30);
+    assert_eq!(report.sessions_pruned, 1);
+    assert!(report.bytes_reclaimed > 0, "{:?}", report);
+    assert_eq!(
+        report.bytes_before - report.bytes_after,
+        report.bytes_reclaimed
+    );
+    let left: i64 = conn
+        .query_row("SELECT COUNT(*) FROM feed_usage", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(left, 31);
+    assert!(get_session(&conn, &kept).unwrap().is_some());
+    assert_eq!(
+        maintenance::last_report().unwrap().finished_at,
+        report.finished_at
+    );
+    drop(conn);
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(path.with_extension("db-wal"));
+    let _ = std::fs::remove_file(path.with_extension("db-shm"));
+}
+
+#[test]
+fn legacy_source_is_migrated_once() {
+    let conn = setup();
+    let legacy = CreateSource {
+        ics_path: "calendar".into(),
+        ..valid_source()
+    };
+
+    let id = migrate_legacy_source(&conn, &legacy).unwrap().unwrap();
+    assert_eq!(get_source(&conn, id).unwrap().unwrap().ics_path, "calendar");
+    assert_eq!(migrate_legacy_source(&conn, &legacy).unwrap(), None);
+
+    // Deleting it does not bring it back on the next startup.
+    delete_source(&conn, id).unwrap();
+    assert_eq!(migrate_legacy_source(&conn, &legacy).unwrap(), None);
+    assert!(list_sources(&conn).unwrap().is_empty());
+}
+
+#[test]
+fn legacy_source_adopts_a_matching_source() {
+    let conn = setup();
+    let existing = create_source(&conn, &valid_source()).unwrap();
+    let legacy = CreateSource {
+        ics_path: "calendar".into(),
+        ..valid_source()
+    };
+
+    assert_eq!(
+        migrate_legacy_source(&conn, &legacy).unwrap(),
+        Some(existing)
+    );
+    assert_eq!(list_sources(&conn).unwrap().len(), 1);
+}
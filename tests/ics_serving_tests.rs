@@ -1,13 +1,21 @@
 use std::sync::{Arc, Mutex};
 
+use axum::extract::ConnectInfo;
 use axum::http::{Request, StatusCode, header};
 use axum::middleware;
 use base64::Engine;
 use caldav_ics_sync::api::AppState;
 use caldav_ics_sync::auto_sync;
 use caldav_ics_sync::db::{self, CreateSource, CreateSourcePath};
-use caldav_ics_sync::server::auth::{AuthConfig, basic_auth_middleware};
+use caldav_ics_sync::server::auth::{AuthChain, SessionSettings, basic_auth_middleware};
+use caldav_ics_sync::server::auth_policy::AuthPolicy;
+use caldav_ics_sync::server::authenticators::{
+    ApiKeyAuth, Authenticator, BasicAuth, JwtAuth, Password, ProxyHeaderAuth, ip_in_network,
+    parse_cidr,
+};
 use caldav_ics_sync::server::build_router;
+use caldav_ics_sync::server::frontend::Frontend;
+use caldav_ics_sync::server::oidc::OidcVerifier;
 use http_body_util::BodyExt;
 use tower::ServiceExt;
 
@@ -22,6 +30,21 @@ fn test_state() -> AppState {
         db: Arc::new(Mutex::new(conn)),
         start_time: std::time::Instant::now(),
         sync_tasks: auto_sync::new_registry(),
+        sync_limit: auto_sync::new_sync_limit(4, 2),
+        running_syncs: auto_sync::new_running_syncs(),
+        sync_progress: caldav_ics_sync::sync_progress::new_registry(),
+        feed_signer: None,
+        public_base_url: None,
+        base_path: String::new(),
+        proxy_cache: caldav_ics_sync::server::caldav_proxy::new_cache(),
+        http_clients: caldav_ics_sync::http_clients::new_cache(),
+        startup_syncs: auto_sync::new_startup_syncs(),
+        circuit_breakers: caldav_ics_sync::circuit_breaker::CircuitBreakers::new(
+            5,
+            std::time::Duration::from_secs(300),
+        ),
+        holiday_provider: None,
+        maintenance: Default::default(),
     }
 }
 
@@ -43,6 +66,7 @@ fn insert_source(
             sync_interval_secs: 0,
             public_ics,
             public_ics_path: public_ics_path.map(str::to_owned),
+            ..Default::default()
         },
     )
     .unwrap()
@@ -67,18 +91,30 @@ fn insert_source_path(state: &AppState, source_id: i64, path: &str, is_public: b
 }
 
 async fn router_no_auth(state: AppState) -> axum::Router {
-    build_router(state, PROXY_URL).await
+    build_router(state, Frontend::Proxy(PROXY_URL.into())).await
 }
 
 async fn router_with_auth(state: AppState) -> axum::Router {
-    let auth_config = AuthConfig::PlainText {
-        username: "test".into(),
-        password: "test".into(),
-    };
-    build_router(state.clone(), PROXY_URL)
+    router_with_chain(state, Vec::new()).await
+}
+
+// Basic auth for test:test, preceded by any extra authenticators.
+async fn router_with_chain(
+    state: AppState,
+    mut authenticators: Vec<Box<dyn Authenticator>>,
+) -> axum::Router {
+    authenticators.push(Box::new(BasicAuth {
+        admin: Some(("test".into(), Password::Plain("test".into()))),
+    }));
+    build_router(state.clone(), Frontend::Proxy(PROXY_URL.into()))
         .await
         .layer(middleware::from_fn(basic_auth_middleware))
-        .layer(axum::Extension(auth_config))
+        .layer(axum::Extension(
+            AuthChain::new(authenticators).with_sessions(SessionSettings {
+                key: axum_extra::extract::cookie::Key::generate(),
+                ttl_secs: 3600,
+            }),
+        ))
         .layer(axum::Extension(state))
 }
 
@@ -118,6 +154,183 @@ async fn ics_existing_path_returns_200() {
     assert!(body.contains("BEGIN:VCALENDAR"));
 }
 
+#[tokio::test]
+async fn published_ttl_sets_cache_control_and_refresh_interval() {
+    let state = test_state();
+    let id = insert_source(&state, "ttl-path", true, Some("ttl-public"));
+    save_ics(&state, id, VCALENDAR);
+    let app = router_no_auth(state.clone()).await;
+    let get = |uri: &str| Request::get(uri).body(axum::body::Body::empty()).unwrap();
+
+    let resp = app.clone().oneshot(get("/ics/ttl-path")).await.unwrap();
+    assert!(resp.headers().get(header::CACHE_CONTROL).is_none());
+
+    {
+        let db = state.db.lock().unwrap();
+        let properties = caldav_ics_sync::feed_metadata::FeedProperties {
+            published_ttl_minutes: Some(120),
+            ..Default::default()
+        };
+        db::set_feed_properties(&db, id, &properties).unwrap();
+    }
+    for uri in ["/ics/ttl-path", "/ics/public/ttl-public"] {
+        let resp = app.clone().oneshot(get(uri)).await.unwrap();
+        assert_eq!(resp.headers()[header::CACHE_CONTROL], "max-age=7200");
+        let body = body_string(resp).await;
+        assert!(body.contains("REFRESH-INTERVAL;VALUE=DURATION:PT2H\r\nX-PUBLISHED-TTL:PT2H\r\n"));
+    }
+}
+
+#[tokio::test]
+async fn ics_supports_etag_revalidation_and_byte_ranges() {
+    let state = test_state();
+    let id = insert_source(&state, "big-path", false, None);
+    save_ics(&state, id, VCALENDAR);
+    let app = router_no_auth(state).await;
+    let get = |headers: &[(header::HeaderName, &str)]| {
+        let mut req = Request::get("/ics/big-path");
+        for (name, value) in headers {
+            req = req.header(name, *value);
+        }
+        req.body(axum::body::Body::empty()).unwrap()
+    };
+
+    let resp = app.clone().oneshot(get(&[])).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()[header::ACCEPT_RANGES], "bytes");
+    let etag = resp.headers()[header::ETAG].to_str().unwrap().to_owned();
+    assert!(etag.starts_with('"') && !etag.starts_with("W/"));
+    let last_modified = resp.headers()[header::LAST_MODIFIED]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    assert!(last_modified.ends_with(" GMT"));
+    let full = body_string(resp).await;
+
+    let resp = app
+        .clone()
+        .oneshot(get(&[(header::IF_NONE_MATCH, &etag)]))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+    let resp = app
+        .clone()
+        .oneshot(get(&[(header::IF_MODIFIED_SINCE, &last_modified)]))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+    let resp = app
+        .clone()
+        .oneshot(get(&[
+            (header::RANGE, "bytes=6-"),
+            (header::IF_RANGE, &etag),
+        ]))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        resp.headers()[header::CONTENT_RANGE],
+        format!("bytes 6-{}/{}", full.len() - 1, full.len())
+    );
+    assert_eq!(body_string(resp).await, full[6..]);
+
+    // A range against an outdated tag gets the whole current feed.
+    let resp = app
+        .clone()
+        .oneshot(get(&[
+            (header::RANGE, "bytes=6-"),
+            (header::IF_RANGE, "\"stale\""),
+        ]))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(body_string(resp).await, full);
+
+    let resp = app
+        .oneshot(get(&[(header::RANGE, "bytes=100000-")]))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+}
+
+#[tokio::test]
+async fn large_feeds_are_kept_in_files_and_streamed() {
+    let dir = std::env::temp_dir().join(format!("caldav-feeds-{}", std::process::id()));
+    // Shared by every test in this binary; their feeds stay below the limit.
+    caldav_ics_sync::feed_store::init(&dir, 64 * 1024).unwrap();
+    let files = || std::fs::read_dir(&dir).unwrap().count();
+
+    let state = test_state();
+    let id = insert_source(&state, "huge", false, None);
+    let feed = |summary: &str| {
+        let events: String = (0..1500)
+            .map(|i| {
+                format!(
+                    "BEGIN:VEVENT\r\nUID:big-{i}\r\nSUMMARY:{summary} {i}\r\nDTSTART:20270101T080000Z\r\nEND:VEVENT\r\n"
+                )
+            })
+            .collect();
+        format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{events}END:VCALENDAR\r\n")
+    };
+    save_ics(&state, id, &feed("First"));
+    assert_eq!(files(), 1);
+    let stored = db::get_ics_data(&state.db.lock().unwrap(), id)
+        .unwrap()
+        .unwrap();
+    assert!(stored.contains("SUMMARY:First 1499"));
+
+    let app = router_no_auth(state.clone()).await;
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::get("/ics/huge")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let etag = resp.headers()[header::ETAG].to_str().unwrap().to_owned();
+    let body = body_string(resp).await;
+    assert_eq!(body, stored);
+    assert_eq!(
+        etag,
+        caldav_ics_sync::server::ranges::etag(stored.as_bytes())
+    );
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::get("/ics/huge")
+                .header(header::RANGE, "bytes=-10")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(body_string(resp).await, "CALENDAR\r\n");
+
+    let resp = app
+        .oneshot(
+            Request::get("/ics/huge")
+                .header(header::IF_NONE_MATCH, &etag)
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+    // The replaced feed's file goes, and so does the last one with the source.
+    save_ics(&state, id, &feed("Second"));
+    assert_eq!(files(), 1);
+    db::delete_source(&state.db.lock().unwrap(), id).unwrap();
+    assert_eq!(files(), 0);
+}
+
 #[tokio::test]
 async fn ics_nonexistent_returns_404() {
     let state = test_state();
@@ -292,6 +505,10 @@ async fn auth_sources_without_credentials_returns_401() {
         .unwrap();
 
     assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert!(resp.headers().contains_key(header::WWW_AUTHENTICATE));
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    assert_eq!(body["status"], "error");
+    assert_eq!(body["code"], "unauthorized");
 }
 
 #[tokio::test]
@@ -410,3 +627,1762 @@ async fn auth_public_standard_ics_no_custom_path_bypasses_auth() {
     let body = body_string(resp).await;
     assert!(body.contains("BEGIN:VCALENDAR"));
 }
+
+// ---------------------------------------------------------------------------
+// Attachments
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn rehosted_attachment_is_served_and_feed_links_absolute() {
+    let state = test_state();
+    let id = insert_source(&state, "attach", false, None);
+    {
+        let db = state.db.lock().unwrap();
+        db::update_source(
+            &db,
+            id,
+            &db::UpdateSource {
+                attachment_mode: Some("rehost".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let source = db::get_source(&db, id).unwrap().unwrap();
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:1\r\nATTACH;FMTTYPE=text/plain;ENCODING=BASE64;VALUE=BINARY:aGk=\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        caldav_ics_sync::api::sync::store_sync_result(&db, &source, ics).unwrap();
+    }
+    let app = router_no_auth(state).await;
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::get("/ics/attach")
+                .header(header::HOST, "cal.example.com")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = body_string(resp).await;
+    let url = body
+        .lines()
+        .find_map(|l| l.strip_prefix("ATTACH;FMTTYPE=text/plain:"))
+        .expect("rewritten ATTACH line");
+    let path = url
+        .strip_prefix("http://cal.example.com")
+        .expect("absolute attachment URL");
+
+    let resp = app
+        .oneshot(Request::get(path).body(axum::body::Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "text/plain");
+    assert_eq!(body_string(resp).await, "hi");
+}
+
+#[tokio::test]
+async fn public_base_url_overrides_host_for_links_and_signatures() {
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    let decode = |s: &str| base64::engine::general_purpose::STANDARD.decode(s).unwrap();
+    let mut state = test_state();
+    state.public_base_url = Some("https://cal.example.com/calsync".into());
+    let signer = caldav_ics_sync::server::feed_signing::FeedSigner::from_base64(
+        &base64::engine::general_purpose::STANDARD.encode([9u8; 32]),
+    )
+    .unwrap();
+    let public: [u8; 32] = decode(&signer.public_key()).try_into().unwrap();
+    let key = VerifyingKey::from_bytes(&public).unwrap();
+    state.feed_signer = Some(signer);
+    let id = insert_source(&state, "attach", false, None);
+    {
+        let db = state.db.lock().unwrap();
+        db::update_source(
+            &db,
+            id,
+            &db::UpdateSource {
+                attachment_mode: Some("rehost".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let source = db::get_source(&db, id).unwrap().unwrap();
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:1\r\nATTACH;FMTTYPE=text/plain;ENCODING=BASE64;VALUE=BINARY:aGk=\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        caldav_ics_sync::api::sync::store_sync_result(&db, &source, ics).unwrap();
+    }
+    let app = router_no_auth(state).await;
+    let get = |uri: &str, host: &str| {
+        app.clone().oneshot(
+            Request::get(uri)
+                .header(header::HOST, host)
+                .header("x-forwarded-proto", "http")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+    };
+    let attach_url = |body: &str| {
+        body.lines()
+            .find_map(|l| l.strip_prefix("ATTACH;FMTTYPE=text/plain:"))
+            .expect("rewritten ATTACH line")
+            .to_string()
+    };
+
+    let feed = body_string(get("/ics/attach", "evil.example").await.unwrap()).await;
+    assert!(
+        attach_url(&feed).starts_with("https://cal.example.com/calsync/attachments/"),
+        "{}",
+        feed
+    );
+
+    let resp = get("/ics/attach.sig", "other.example").await.unwrap();
+    let signature: [u8; 64] = decode(&body_string(resp).await).try_into().unwrap();
+    assert!(
+        key.verify(feed.as_bytes(), &Signature::from_bytes(&signature))
+            .is_ok()
+    );
+
+    let resp = app
+        .clone()
+        .oneshot(caldav_request("PROPFIND", "/caldav/attach/", "1", ""))
+        .await
+        .unwrap();
+    let event = hrefs(&body_string(resp).await)[1].clone();
+    let body = body_string(get(&event, "evil.example").await.unwrap()).await;
+    assert_eq!(attach_url(&body), attach_url(&feed));
+}
+
+// ---------------------------------------------------------------------------
+// Feed signatures
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn signed_feed_signature_verifies_with_published_key() {
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    let mut state = test_state();
+    state.feed_signer = Some(
+        caldav_ics_sync::server::feed_signing::FeedSigner::from_base64(
+            &base64::engine::general_purpose::STANDARD.encode([9u8; 32]),
+        )
+        .unwrap(),
+    );
+    let id = insert_source(&state, "signed-priv", true, Some("signed-pub"));
+    save_ics(&state, id, VCALENDAR);
+    let app = router_with_auth(state).await;
+    let get = |uri: &str| {
+        app.clone()
+            .oneshot(Request::get(uri).body(axum::body::Body::empty()).unwrap())
+    };
+
+    let resp = get("/.well-known/ics-signing-key").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let key: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    assert_eq!(key["algorithm"], "Ed25519");
+    let decode = |s: &str| base64::engine::general_purpose::STANDARD.decode(s).unwrap();
+    let public: [u8; 32] = decode(key["public_key"].as_str().unwrap())
+        .try_into()
+        .unwrap();
+
+    let feed = body_string(get("/ics/public/signed-pub").await.unwrap()).await;
+    let resp = get("/ics/public/signed-pub.sig").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let signature: [u8; 64] = decode(&body_string(resp).await).try_into().unwrap();
+    let key = VerifyingKey::from_bytes(&public).unwrap();
+    assert!(
+        key.verify(feed.as_bytes(), &Signature::from_bytes(&signature))
+            .is_ok()
+    );
+
+    let resp = get("/ics/signed-priv.sig").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+// ---------------------------------------------------------------------------
+// Repair rules
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn regenerated_dtstamps_alone_do_not_rewrite_the_feed() {
+    let state = test_state();
+    let id = insert_source(&state, "stamped", false, None);
+    let db = state.db.lock().unwrap();
+    db::update_source(
+        &db,
+        id,
+        &db::UpdateSource {
+            repair_rules: Some("regenerate_dtstamp, clamp_timestamps".into()),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let source = db::get_source(&db, id).unwrap().unwrap();
+    let store = |summary: &str| {
+        let ics = format!(
+            "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:1\r\nDTSTAMP:20990101T000000Z\r\nSUMMARY:{}\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+            summary
+        );
+        caldav_ics_sync::api::sync::store_sync_result(&db, &source, &ics).unwrap()
+    };
+
+    let first = store("Standup");
+    assert_eq!(first.repairs.by_rule["clamp_timestamps"], 1);
+    assert_eq!(first.repairs.by_rule["regenerate_dtstamp"], 1);
+    let stored = db::get_ics_data(&db, id).unwrap().unwrap();
+    assert!(!stored.contains("2099"));
+
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    assert!(store("Standup").unchanged);
+    assert_eq!(db::get_ics_data(&db, id).unwrap().unwrap(), stored);
+    assert!(!store("Retro").unchanged);
+}
+
+// ---------------------------------------------------------------------------
+// Split feeds
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn split_feeds_are_served_in_parts_with_the_feeds_auth() {
+    let state = test_state();
+    let id = insert_source(&state, "big", true, None);
+    {
+        let db = state.db.lock().unwrap();
+        db::update_source(
+            &db,
+            id,
+            &db::UpdateSource {
+                max_events: Some(2),
+                feed_limit_policy: Some("split".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let source = db::get_source(&db, id).unwrap().unwrap();
+        let events: String = ["20260301", "20250101", "20260201"]
+            .iter()
+            .map(|day| {
+                format!("BEGIN:VEVENT\r\nUID:{day}\r\nDTSTART:{day}T100000Z\r\nEND:VEVENT\r\n")
+            })
+            .collect();
+        let ics = format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}END:VCALENDAR\r\n",
+            events
+        );
+        let diff = caldav_ics_sync::api::sync::store_sync_result(&db, &source, &ics).unwrap();
+        assert!(diff.warnings[0].ends_with("split into 2 parts"));
+        assert_eq!(db::get_source(&db, id).unwrap().unwrap().feed_parts, 2);
+    }
+    // Public without a custom path, so parts need no credentials either.
+    let app = router_with_auth(state.clone()).await;
+    let get = |path: &str| {
+        app.clone()
+            .oneshot(Request::get(path).body(axum::body::Body::empty()).unwrap())
+    };
+
+    let full = body_string(get("/ics/big").await.unwrap()).await;
+    assert_eq!(full.matches("BEGIN:VEVENT").count(), 3);
+    let resp = get("/ics/big/part-1.ics").await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let first = body_string(resp).await;
+    assert!(first.contains("UID:20250101") && first.contains("UID:20260201"));
+    assert!(first.ends_with("END:VCALENDAR\r\n"));
+    let second = body_string(get("/ics/big/part-2.ics").await.unwrap()).await;
+    assert!(second.contains("UID:20260301") && !second.contains("UID:20250101"));
+    assert_eq!(
+        get("/ics/big/part-3.ics").await.unwrap().status(),
+        StatusCode::NOT_FOUND
+    );
+
+    // Parts are stored when the feed is written, and served from there.
+    {
+        let db = state.db.lock().unwrap();
+        let stored: i64 = db
+            .query_row(
+                "SELECT count(*) FROM feed_part_data WHERE source_id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(stored, 2);
+        db.execute(
+            "UPDATE feed_part_data SET ics_content = 'stored' WHERE source_id = ?1 AND part = 2",
+            [id],
+        )
+        .unwrap();
+    }
+    assert_eq!(
+        body_string(get("/ics/big/part-2.ics").await.unwrap()).await,
+        "stored"
+    );
+
+    {
+        let db = state.db.lock().unwrap();
+        db::update_source(
+            &db,
+            id,
+            &db::UpdateSource {
+                public_ics: Some(false),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let err = db::update_source(
+            &db,
+            id,
+            &db::UpdateSource {
+                ics_path: Some("big/part-1.ics".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("reserved for feed parts"));
+    }
+    assert_eq!(
+        get("/ics/big/part-1.ics").await.unwrap().status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Sync loops
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn namespaced_source_stamps_events_and_drops_its_own() {
+    let state = test_state();
+    let id = insert_source(&state, "chained", false, None);
+    let namespace = caldav_ics_sync::sync_origin::source_namespace(id);
+    {
+        let db = state.db.lock().unwrap();
+        db::update_source(
+            &db,
+            id,
+            &db::UpdateSource {
+                namespace_uids: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let source = db::get_source(&db, id).unwrap().unwrap();
+        // `looped` went out through this source and came back via another calendar.
+        let ics = format!(
+            "BEGIN:VCALENDAR\r\n\
+             BEGIN:VEVENT\r\nUID:fresh\r\nEND:VEVENT\r\n\
+             BEGIN:VEVENT\r\nUID:looped@{ns}\r\nX-SYNC-ORIGIN:{ns}\r\nEND:VEVENT\r\n\
+             BEGIN:VEVENT\r\nUID:passing@elsewhere\r\nX-SYNC-ORIGIN:dest-9.caldav-ics-sync\r\nEND:VEVENT\r\n\
+             END:VCALENDAR\r\n",
+            ns = namespace
+        );
+        caldav_ics_sync::api::sync::store_sync_result(&db, &source, &ics).unwrap();
+    }
+    let app = router_no_auth(state).await;
+    let resp = app
+        .oneshot(
+            Request::get("/ics/chained")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let body = body_string(resp).await;
+    assert!(body.contains(&format!(
+        "UID:fresh@{}\r\nX-SYNC-ORIGIN:{}",
+        namespace, namespace
+    )));
+    assert!(!body.contains("looped"));
+    assert!(body.contains("UID:passing@elsewhere\r\nX-SYNC-ORIGIN:dest-9.caldav-ics-sync"));
+}
+
+// ---------------------------------------------------------------------------
+// CalDAV collections
+// ---------------------------------------------------------------------------
+
+const TWO_EVENTS: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+    BEGIN:VEVENT\r\nUID:a@example.com\r\nSUMMARY:First\r\nEND:VEVENT\r\n\
+    BEGIN:VEVENT\r\nUID:b@example.com\r\nSUMMARY:Second\r\nEND:VEVENT\r\n\
+    END:VCALENDAR\r\n";
+
+fn caldav_request(method: &str, uri: &str, depth: &str, body: &str) -> Request<axum::body::Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("Depth", depth)
+        .header(header::CONTENT_TYPE, "application/xml")
+        .body(axum::body::Body::from(body.to_owned()))
+        .unwrap()
+}
+
+fn hrefs(body: &str) -> Vec<String> {
+    body.split("<d:href>")
+        .skip(1)
+        .filter_map(|s| s.split_once("</d:href>").map(|(h, _)| h.to_owned()))
+        .collect()
+}
+
+#[tokio::test]
+async fn caldav_propfind_lists_events_with_ctag() {
+    let state = test_state();
+    let id = insert_source(&state, "team", false, None);
+    save_ics(&state, id, TWO_EVENTS);
+    let app = router_no_auth(state).await;
+
+    let resp = app
+        .clone()
+        .oneshot(caldav_request("PROPFIND", "/caldav/team/", "0", ""))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    let body = body_string(resp).await;
+    assert!(body.contains("<c:calendar/>"));
+    assert!(body.contains("<cs:getctag>"));
+    assert_eq!(hrefs(&body), vec!["/caldav/team/"]);
+
+    let resp = app
+        .oneshot(caldav_request("PROPFIND", "/caldav/team/", "1", ""))
+        .await
+        .unwrap();
+    let body = body_string(resp).await;
+    let hrefs = hrefs(&body);
+    assert_eq!(hrefs.len(), 3);
+    assert!(hrefs[1].starts_with("/caldav/team/") && hrefs[1].ends_with(".ics"));
+    assert_eq!(body.matches("<d:getetag>").count(), 2);
+}
+
+#[tokio::test]
+async fn routes_and_hrefs_follow_base_path() {
+    let mut state = test_state();
+    state.base_path = "/calsync".into();
+    let id = insert_source(&state, "team", false, None);
+    save_ics(&state, id, TWO_EVENTS);
+    let app = axum::Router::new().nest("/calsync", router_with_auth(state).await);
+    let auth = basic_auth_header("test", "test");
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::get("/calsync/ics/team")
+                .header(header::AUTHORIZATION, &auth)
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let mut propfind = caldav_request("PROPFIND", "/calsync/caldav/team/", "0", "");
+    propfind
+        .headers_mut()
+        .insert(header::AUTHORIZATION, auth.parse().unwrap());
+    let resp = app.clone().oneshot(propfind).await.unwrap();
+    assert_eq!(
+        hrefs(&body_string(resp).await),
+        vec!["/calsync/caldav/team/"]
+    );
+
+    let resp = app
+        .oneshot(
+            Request::get("/calsync/api/openapi.json")
+                .header(header::AUTHORIZATION, &auth)
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert!(
+        body_string(resp)
+            .await
+            .contains(r#""servers":[{"url":"/calsync"}]"#)
+    );
+}
+
+#[tokio::test]
+async fn caldav_multiget_and_get_return_single_event() {
+    let state = test_state();
+    let id = insert_source(&state, "team", false, None);
+    save_ics(&state, id, TWO_EVENTS);
+    let app = router_no_auth(state).await;
+
+    let resp = app
+        .clone()
+        .oneshot(caldav_request("PROPFIND", "/caldav/team/", "1", ""))
+        .await
+        .unwrap();
+    let first = hrefs(&body_string(resp).await)[1].clone();
+
+    let multiget = format!(
+        r#"<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><d:getetag/><c:calendar-data/></d:prop>
+  <d:href>{first}</d:href>
+  <d:href>/caldav/team/missing.ics</d:href>
+</c:calendar-multiget>"#
+    );
+    let resp = app
+        .clone()
+        .oneshot(caldav_request("REPORT", "/caldav/team/", "1", &multiget))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    let body = body_string(resp).await;
+    assert!(body.contains("SUMMARY:First"));
+    assert!(!body.contains("SUMMARY:Second"));
+    assert!(body.contains("404 Not Found"));
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::get(&first)
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let etag = resp.headers().get(header::ETAG).unwrap().clone();
+    assert!(body_string(resp).await.contains("UID:a@example.com"));
+
+    let resp = app
+        .oneshot(
+            Request::get(&first)
+                .header(header::IF_NONE_MATCH, etag)
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+}
+
+#[tokio::test]
+async fn caldav_free_busy_query_merges_busy_periods() {
+    let state = test_state();
+    let id = insert_source(&state, "team", false, None);
+    save_ics(
+        &state,
+        id,
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+         BEGIN:VEVENT\r\nUID:1\r\nDTSTART:20260310T090000Z\r\nDTEND:20260310T100000Z\r\nEND:VEVENT\r\n\
+         BEGIN:VEVENT\r\nUID:2\r\nDTSTART:20260310T093000Z\r\nDURATION:PT1H\r\nEND:VEVENT\r\n\
+         BEGIN:VEVENT\r\nUID:3\r\nDTSTART:20260310T140000Z\r\nDTEND:20260310T150000Z\r\nSTATUS:TENTATIVE\r\nEND:VEVENT\r\n\
+         BEGIN:VEVENT\r\nUID:4\r\nDTSTART:20260310T160000Z\r\nDTEND:20260310T170000Z\r\nTRANSP:TRANSPARENT\r\nEND:VEVENT\r\n\
+         BEGIN:VEVENT\r\nUID:5\r\nDTSTART:20260310T170000Z\r\nDTEND:20260310T180000Z\r\nSTATUS:CANCELLED\r\nEND:VEVENT\r\n\
+         BEGIN:VEVENT\r\nUID:6\r\nDTSTART:20260311T090000Z\r\nDTEND:20260311T100000Z\r\nEND:VEVENT\r\n\
+         END:VCALENDAR\r\n",
+    );
+    let app = router_no_auth(state).await;
+
+    let query = r#"<c:free-busy-query xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <c:time-range start="20260310T000000Z" end="20260311T000000Z"/>
+</c:free-busy-query>"#;
+    let resp = app
+        .clone()
+        .oneshot(caldav_request("REPORT", "/caldav/team/", "1", query))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(
+        resp.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/calendar")
+    );
+    let body = body_string(resp).await;
+    let freebusy: Vec<&str> = body.lines().filter(|l| l.starts_with("FREEBUSY")).collect();
+    assert_eq!(
+        freebusy,
+        vec![
+            "FREEBUSY;FBTYPE=BUSY:20260310T090000Z/20260310T103000Z",
+            "FREEBUSY;FBTYPE=BUSY-TENTATIVE:20260310T140000Z/20260310T150000Z",
+        ]
+    );
+    assert!(body.contains("DTSTART:20260310T000000Z"));
+
+    let resp = app
+        .oneshot(caldav_request(
+            "REPORT",
+            "/caldav/team/",
+            "1",
+            r#"<c:free-busy-query xmlns:c="urn:ietf:params:xml:ns:caldav"/>"#,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn caldav_private_collection_requires_auth() {
+    let state = test_state();
+    let private = insert_source(&state, "private", false, None);
+    save_ics(&state, private, TWO_EVENTS);
+    let public = insert_source(&state, "open", true, None);
+    save_ics(&state, public, TWO_EVENTS);
+    let app = router_with_auth(state).await;
+
+    let resp = app
+        .clone()
+        .oneshot(caldav_request("PROPFIND", "/caldav/private/", "0", ""))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app
+        .oneshot(caldav_request("PROPFIND", "/caldav/open/", "0", ""))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+}
+
+// ---------------------------------------------------------------------------
+// CalDAV proxy
+// ---------------------------------------------------------------------------
+
+async fn start_upstream(hits: Arc<std::sync::atomic::AtomicUsize>) -> std::net::SocketAddr {
+    let app = axum::Router::new().fallback(move |req: Request<axum::body::Body>| {
+        let hits = hits.clone();
+        async move {
+            hits.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let expected = basic_auth_header("upstream-user", "upstream-pass");
+            if req.headers().get(header::AUTHORIZATION).map(|v| v.as_bytes())
+                != Some(expected.as_bytes())
+            {
+                return axum::response::Response::builder()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .body(axum::body::Body::empty())
+                    .unwrap();
+            }
+            let body = format!(
+                "<d:multistatus xmlns:d=\"DAV:\"><d:response><d:href>{}</d:href></d:response></d:multistatus>",
+                req.uri().path()
+            );
+            axum::response::Response::builder()
+                .status(StatusCode::MULTI_STATUS)
+                .header(header::CONTENT_TYPE, "application/xml")
+                .body(axum::body::Body::from(body))
+                .unwrap()
+        }
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    addr
+}
+
+#[tokio::test]
+async fn caldav_proxy_forwards_with_stored_credentials_and_caches() {
+    let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let addr = start_upstream(hits.clone()).await;
+    let state = test_state();
+    let token = {
+        let db = state.db.lock().unwrap();
+        let id = db::create_source(
+            &db,
+            &CreateSource {
+                name: "Proxy".into(),
+                caldav_url: format!("http://{}/dav/cal/", addr),
+                username: "upstream-user".into(),
+                password: "upstream-pass".into(),
+                ics_path: "proxied".into(),
+                proxy_enabled: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(id, 1);
+        db::get_source(&db, id)
+            .unwrap()
+            .unwrap()
+            .proxy_token
+            .unwrap()
+    };
+    let app = router_with_auth(state).await;
+
+    let propfind = |auth: String| {
+        Request::builder()
+            .method("PROPFIND")
+            .uri("/caldav-proxy/1/")
+            .header("Depth", "1")
+            .header(header::AUTHORIZATION, auth)
+            .body(axum::body::Body::empty())
+            .unwrap()
+    };
+
+    let resp = app
+        .clone()
+        .oneshot(propfind(basic_auth_header("device", "wrong")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app
+        .clone()
+        .oneshot(propfind(basic_auth_header("device", &token)))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    assert!(
+        body_string(resp)
+            .await
+            .contains("<d:href>/caldav-proxy/1/dav/cal/</d:href>")
+    );
+
+    let resp = app
+        .clone()
+        .oneshot(propfind(basic_auth_header("device", &token)))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    // A scheme-relative path must not send the stored credentials elsewhere.
+    for uri in [
+        "/caldav-proxy/1//evil.example/x",
+        "/caldav-proxy/1/a%5C%5Cevil/x",
+    ] {
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(uri)
+                    .header(header::AUTHORIZATION, basic_auth_header("device", &token))
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", uri);
+    }
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri("/caldav-proxy/1/dav/cal/x.ics")
+                .header(header::AUTHORIZATION, basic_auth_header("device", &token))
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
+
+// ---------------------------------------------------------------------------
+// Multi-user ownership
+// ---------------------------------------------------------------------------
+
+fn insert_user(state: &AppState, username: &str, is_admin: bool) -> i64 {
+    let db = state.db.lock().unwrap();
+    db::create_user(
+        &db,
+        &db::CreateUser {
+            username: username.into(),
+            password: "pw".into(),
+            is_admin,
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+fn set_owner(state: &AppState, source_id: i64, owner_id: i64) {
+    let db = state.db.lock().unwrap();
+    db::update_source(
+        &db,
+        source_id,
+        &db::UpdateSource {
+            owner_id: Some(owner_id),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+}
+
+fn get_as(uri: &str, user: &str, pass: &str) -> Request<axum::body::Body> {
+    Request::get(uri)
+        .header(header::AUTHORIZATION, basic_auth_header(user, pass))
+        .body(axum::body::Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn users_only_see_their_own_sources_and_feeds() {
+    let state = test_state();
+    let alice = insert_user(&state, "alice", false);
+    let bob = insert_user(&state, "bob", false);
+    let alice_src = insert_source(&state, "alice-cal", false, None);
+    let bob_src = insert_source(&state, "bob-cal", false, None);
+    set_owner(&state, alice_src, alice);
+    set_owner(&state, bob_src, bob);
+    save_ics(&state, alice_src, VCALENDAR);
+    save_ics(&state, bob_src, VCALENDAR);
+    let app = router_with_auth(state).await;
+
+    let resp = app
+        .clone()
+        .oneshot(get_as("/api/sources", "alice", "pw"))
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    let ids: Vec<i64> = body["sources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["id"].as_i64().unwrap())
+        .collect();
+    assert_eq!(ids, vec![alice_src]);
+
+    let resp = app
+        .clone()
+        .oneshot(get_as("/api/sources", "test", "test"))
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    assert_eq!(body["sources"].as_array().unwrap().len(), 2);
+
+    let resp = app
+        .clone()
+        .oneshot(get_as("/ics/alice-cal", "alice", "pw"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+        .clone()
+        .oneshot(get_as("/ics/bob-cal", "alice", "pw"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = app
+        .clone()
+        .oneshot(get_as(
+            &format!("/api/sources/{}/status", bob_src),
+            "alice",
+            "pw",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = app
+        .oneshot(get_as("/api/sources", "alice", "wrong"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn user_management_requires_admin() {
+    let state = test_state();
+    insert_user(&state, "alice", false);
+    insert_user(&state, "carol", true);
+    let app = router_with_auth(state).await;
+
+    let resp = app
+        .clone()
+        .oneshot(get_as("/api/users", "alice", "pw"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = app
+        .clone()
+        .oneshot(get_as("/api/users", "carol", "pw"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+        .oneshot(get_as("/api/users/me", "alice", "pw"))
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    assert_eq!(body["username"], "alice");
+    assert_eq!(body["is_admin"], false);
+}
+
+// ---------------------------------------------------------------------------
+// Working hours
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn availability_feed_publishes_source_or_owner_working_hours() {
+    let state = test_state();
+    let alice = {
+        let db = state.db.lock().unwrap();
+        db::create_user(
+            &db,
+            &db::CreateUser {
+                username: "alice".into(),
+                password: "pw".into(),
+                working_hours: "Mon-Fri 09:00-17:00; Europe/Berlin".into(),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    };
+    let owned = insert_source(&state, "alice-cal", true, Some("alice-pub"));
+    set_owner(&state, owned, alice);
+    let own_hours = insert_source(&state, "weekend-cal", false, None);
+    {
+        let db = state.db.lock().unwrap();
+        db::update_source(
+            &db,
+            own_hours,
+            &db::UpdateSource {
+                owner_id: Some(alice),
+                working_hours: Some("Sat 10:00-12:00".into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    }
+    insert_source(&state, "no-hours", false, None);
+    let app = router_with_auth(state).await;
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::get("/ics/public/alice-pub.availability")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = body_string(resp).await;
+    assert!(body.contains("BEGIN:VAVAILABILITY\r\n"));
+    assert!(body.contains("DTSTART;TZID=Europe/Berlin:20240101T090000\r\n"));
+    assert!(body.contains("RRULE:FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR\r\n"));
+
+    let resp = app
+        .clone()
+        .oneshot(get_as("/ics/weekend-cal.availability", "alice", "pw"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = body_string(resp).await;
+    assert!(body.contains("DTSTART:20240106T100000Z\r\n"));
+    assert!(!body.contains("Europe/Berlin"));
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::get("/ics/weekend-cal.availability")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app
+        .oneshot(get_as("/ics/no-hours.availability", "test", "test"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+// ---------------------------------------------------------------------------
+// Quotas
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn feed_bandwidth_quota_returns_429_and_usage_is_reported() {
+    let state = test_state();
+    let alice = insert_user(&state, "alice", false);
+    let id = insert_source(&state, "alice-cal", false, None);
+    set_owner(&state, id, alice);
+    save_ics(&state, id, VCALENDAR);
+    {
+        let db = state.db.lock().unwrap();
+        db::update_user(
+            &db,
+            alice,
+            &db::UpdateUser {
+                quotas: Some(db::Quotas {
+                    max_feed_bytes_per_day: Some(VCALENDAR.len() as i64),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    }
+    let app = router_with_auth(state).await;
+
+    let resp = app
+        .clone()
+        .oneshot(get_as("/ics/alice-cal", "alice", "pw"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = app
+        .clone()
+        .oneshot(get_as("/ics/alice-cal", "alice", "pw"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let resp = app
+        .oneshot(get_as("/api/users/me/usage", "alice", "pw"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    assert_eq!(body["usage"]["sources"], 1);
+    assert_eq!(body["usage"]["feed_bytes_today"], VCALENDAR.len());
+    assert_eq!(body["quotas"]["max_feed_bytes_per_day"], VCALENDAR.len());
+}
+
+// ---------------------------------------------------------------------------
+// External authentication
+// ---------------------------------------------------------------------------
+
+fn from_peer(uri: &str, peer: &str, header: Option<(&str, &str)>) -> Request<axum::body::Body> {
+    let mut builder = Request::get(uri);
+    if let Some((name, value)) = header {
+        builder = builder.header(name, value);
+    }
+    let mut req = builder.body(axum::body::Body::empty()).unwrap();
+    req.extensions_mut()
+        .insert(ConnectInfo(peer.parse::<std::net::SocketAddr>().unwrap()));
+    req
+}
+
+#[tokio::test]
+async fn proxy_header_auth_only_trusts_configured_peers() {
+    let state = test_state();
+    let alice = insert_user(&state, "alice", false);
+    let id = insert_source(&state, "alice-cal", false, None);
+    set_owner(&state, id, alice);
+    let proxy = ProxyHeaderAuth {
+        header: axum::http::HeaderName::from_static("x-remote-user"),
+        trusted_proxies: vec![parse_cidr("10.0.0.0/8").unwrap()],
+        admin_username: Some("test".into()),
+    };
+    let app = router_with_chain(state, vec![Box::new(proxy)]).await;
+
+    let resp = app
+        .clone()
+        .oneshot(from_peer(
+            "/api/users/me",
+            "10.1.2.3:5000",
+            Some(("X-Remote-User", "alice")),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    assert_eq!(body["username"], "alice");
+    assert_eq!(body["is_admin"], false);
+
+    let resp = app
+        .clone()
+        .oneshot(from_peer(
+            "/api/users/me",
+            "192.168.1.5:5000",
+            Some(("X-Remote-User", "alice")),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app
+        .clone()
+        .oneshot(from_peer(
+            "/api/sources",
+            "10.1.2.3:5000",
+            Some(("X-Remote-User", "mallory")),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app
+        .oneshot(from_peer("/api/sources", "10.1.2.3:5000", None))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn proxy_header_auth_defaults_to_loopback_peers() {
+    let state = test_state();
+    insert_user(&state, "alice", false);
+    let mut cfg = caldav_ics_sync::config::AppConfig::load_from(None).unwrap();
+    cfg.auth_proxy_header = Some("X-Remote-User".into());
+    cfg.auth_trusted_proxies = None;
+    let app = build_router(state.clone(), Frontend::Proxy(PROXY_URL.into()))
+        .await
+        .layer(middleware::from_fn(basic_auth_middleware))
+        .layer(axum::Extension(AuthChain::from_config(&cfg).unwrap()))
+        .layer(axum::Extension(state));
+
+    for (peer, status) in [
+        ("127.0.0.1:5000", StatusCode::OK),
+        ("[::1]:5000", StatusCode::OK),
+        ("203.0.113.9:5000", StatusCode::UNAUTHORIZED),
+    ] {
+        let resp = app
+            .clone()
+            .oneshot(from_peer(
+                "/api/users/me",
+                peer,
+                Some(("X-Remote-User", "alice")),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), status, "{}", peer);
+    }
+}
+
+#[test]
+fn cidr_matching() {
+    let (net, prefix) = parse_cidr("192.168.0.0/16").unwrap();
+    assert!(ip_in_network("192.168.10.1".parse().unwrap(), net, prefix));
+    assert!(!ip_in_network("192.169.0.1".parse().unwrap(), net, prefix));
+    let (net, prefix) = parse_cidr("fd00::/8").unwrap();
+    assert!(ip_in_network("fd12::1".parse().unwrap(), net, prefix));
+    assert!(!ip_in_network("10.0.0.1".parse().unwrap(), net, prefix));
+    let (net, prefix) = parse_cidr("127.0.0.1").unwrap();
+    assert!(ip_in_network("127.0.0.1".parse().unwrap(), net, prefix));
+    assert!(parse_cidr("10.0.0.0/33").is_err());
+    assert!(parse_cidr("proxy").is_err());
+}
+
+const OIDC_SECRET: &[u8] = b"oidc-test-secret-for-hs256-signing";
+
+async fn start_jwks() -> String {
+    let jwks = serde_json::json!({
+        "keys": [{
+            "kty": "oct",
+            "kid": "test-key",
+            "alg": "HS256",
+            "k": base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(OIDC_SECRET),
+        }]
+    });
+    let app = axum::Router::new().route(
+        "/jwks",
+        axum::routing::get(move || async move { axum::Json(jwks) }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/jwks", addr)
+}
+
+fn sign_token(issuer: &str, audience: &str, username: &str) -> String {
+    let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256);
+    header.kid = Some("test-key".into());
+    let exp = chrono::Utc::now().timestamp() + 300;
+    let claims = serde_json::json!({
+        "iss": issuer,
+        "aud": audience,
+        "sub": "0001",
+        "preferred_username": username,
+        "exp": exp,
+    });
+    jsonwebtoken::encode(
+        &header,
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(OIDC_SECRET),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn oidc_bearer_tokens_are_validated_against_jwks() {
+    let jwks_url = start_jwks().await;
+    let issuer = "https://sso.example.com";
+    let jwt = JwtAuth {
+        verifier: OidcVerifier::new(
+            issuer.into(),
+            Some("caldav-sync".into()),
+            Some(jwks_url),
+            None,
+        ),
+        admin_username: Some("test".into()),
+    };
+    let app = router_with_chain(test_state(), vec![Box::new(jwt)]).await;
+    let bearer = |token: String| {
+        Request::get("/api/users/me")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(axum::body::Body::empty())
+            .unwrap()
+    };
+
+    let resp = app
+        .clone()
+        .oneshot(bearer(sign_token(issuer, "caldav-sync", "alice")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    assert_eq!(body["username"], "alice");
+    assert_eq!(body["is_admin"], true);
+
+    let resp = app
+        .clone()
+        .oneshot(bearer(sign_token(issuer, "other-app", "alice")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app
+        .clone()
+        .oneshot(bearer(sign_token(
+            "https://evil.example.com",
+            "caldav-sync",
+            "alice",
+        )))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app.oneshot(bearer("not-a-jwt".into())).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn api_keys_and_basic_auth_are_tried_in_turn() {
+    let app = router_with_chain(
+        test_state(),
+        vec![Box::new(ApiKeyAuth {
+            keys: vec!["k-123".into()],
+        })],
+    )
+    .await;
+    let with_key = |key: &str| {
+        Request::get("/api/sources")
+            .header("X-API-Key", key)
+            .body(axum::body::Body::empty())
+            .unwrap()
+    };
+
+    let resp = app.clone().oneshot(with_key("k-123")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app.clone().oneshot(with_key("wrong")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app
+        .oneshot(get_as("/api/sources", "test", "test"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+// ---------------------------------------------------------------------------
+// Auth policy
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn auth_policy_limits_methods_per_route_group() {
+    let state = test_state();
+    let id = insert_source(&state, "private-feed", false, None);
+    save_ics(&state, id, VCALENDAR);
+    let chain = AuthChain::new(vec![
+        Box::new(ApiKeyAuth {
+            keys: vec!["k-123".into()],
+        }),
+        Box::new(BasicAuth {
+            admin: Some(("test".into(), Password::Plain("test".into()))),
+        }),
+    ]);
+    let policy = AuthPolicy::parse("feeds=public;metrics=api-key", &chain.names()).unwrap();
+    let app = build_router(state.clone(), Frontend::Proxy(PROXY_URL.into()))
+        .await
+        .layer(middleware::from_fn(basic_auth_middleware))
+        .layer(axum::Extension(chain.with_policy(policy)))
+        .layer(axum::Extension(state));
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::get("/ics/private-feed")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+        .clone()
+        .oneshot(get_as("/api/health/detailed", "test", "test"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::get("/api/health/detailed")
+                .header("X-API-Key", "k-123")
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+        .oneshot(get_as("/api/sources", "test", "test"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+// ---------------------------------------------------------------------------
+// Sessions
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn login_issues_session_cookie_until_logout() {
+    let state = test_state();
+    insert_user(&state, "alice", false);
+    let app = router_with_chain(state, Vec::new()).await;
+    let login = |user: &str, pass: &str| {
+        Request::post("/api/auth/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(axum::body::Body::from(
+                serde_json::json!({ "username": user, "password": pass }).to_string(),
+            ))
+            .unwrap()
+    };
+    let with_cookie = |method: &str, uri: &str, cookie: &str| {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::COOKIE, cookie)
+            .body(axum::body::Body::empty())
+            .unwrap()
+    };
+
+    let resp = app.clone().oneshot(login("alice", "nope")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert!(resp.headers().get(header::SET_COOKIE).is_none());
+
+    let resp = app.clone().oneshot(login("alice", "pw")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let set_cookie = resp
+        .headers()
+        .get(header::SET_COOKIE)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+    assert!(set_cookie.contains("HttpOnly"));
+    let cookie = set_cookie.split(';').next().unwrap().to_owned();
+
+    let resp = app
+        .clone()
+        .oneshot(with_cookie("GET", "/api/users/me", &cookie))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    assert_eq!(body["username"], "alice");
+    assert_eq!(body["is_admin"], false);
+
+    let tampered = format!("{}x", cookie);
+    let resp = app
+        .clone()
+        .oneshot(with_cookie("GET", "/api/users/me", &tampered))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app
+        .clone()
+        .oneshot(with_cookie("POST", "/api/auth/logout", &cookie))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app
+        .oneshot(with_cookie("GET", "/api/users/me", &cookie))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn session_cookies_are_scoped_to_base_path() {
+    let mut state = test_state();
+    state.base_path = "/calsync".into();
+    let app = axum::Router::new().nest("/calsync", router_with_chain(state, Vec::new()).await);
+    let post = |uri: &str, body: &'static str| {
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(axum::body::Body::from(body))
+            .unwrap()
+    };
+    let paths = |resp: &axum::response::Response| -> Vec<String> {
+        resp.headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|v| {
+                v.to_str()
+                    .unwrap()
+                    .split("; ")
+                    .find_map(|attr| attr.strip_prefix("Path="))
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect()
+    };
+
+    let resp = app
+        .clone()
+        .oneshot(post(
+            "/calsync/api/auth/login",
+            r#"{"username":"test","password":"test"}"#,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(paths(&resp), ["/calsync", "/calsync"]);
+    let cookies: Vec<String> = resp
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .map(|v| v.to_str().unwrap().split(';').next().unwrap().to_owned())
+        .collect();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+
+    let mut logout = post("/calsync/api/auth/logout", "{}");
+    let headers = logout.headers_mut();
+    headers.insert(header::COOKIE, cookies.join("; ").parse().unwrap());
+    headers.insert(
+        "X-CSRF-Token",
+        body["csrf_token"].as_str().unwrap().parse().unwrap(),
+    );
+    let resp = app.oneshot(logout).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(paths(&resp), ["/calsync", "/calsync"]);
+}
+
+#[tokio::test]
+async fn cookie_authenticated_writes_require_csrf_token_and_same_origin() {
+    let app = router_with_chain(test_state(), Vec::new()).await;
+    let resp = app
+        .clone()
+        .oneshot(
+            Request::post("/api/auth/login")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(axum::body::Body::from(
+                    r#"{"username":"test","password":"test"}"#,
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let cookies: Vec<String> = resp
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .map(|v| v.to_str().unwrap().split(';').next().unwrap().to_owned())
+        .collect();
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    let token = body["csrf_token"].as_str().unwrap().to_owned();
+    let cookie_header = cookies.join("; ");
+
+    let create = |csrf: Option<&str>, origin: Option<&str>| {
+        let mut builder = Request::post("/api/sources")
+            .header(header::HOST, "cal.example.com")
+            .header(header::COOKIE, &cookie_header)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(csrf) = csrf {
+            builder = builder.header("X-CSRF-Token", csrf);
+        }
+        if let Some(origin) = origin {
+            builder = builder.header(header::ORIGIN, origin);
+        }
+        builder
+            .body(axum::body::Body::from(
+                serde_json::json!({
+                    "name": "Work",
+                    "caldav_url": "https://dav.example.com",
+                    "username": "u",
+                    "password": "p",
+                    "ics_path": "work",
+                    "sync_interval_secs": 0,
+                })
+                .to_string(),
+            ))
+            .unwrap()
+    };
+
+    let resp = app.clone().oneshot(create(None, None)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    assert_eq!(
+        (body["code"].as_str(), body["message"].as_str()),
+        (Some("forbidden"), Some("CSRF check failed"))
+    );
+
+    let resp = app
+        .clone()
+        .oneshot(create(Some(&token), Some("https://evil.example.com")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = app
+        .clone()
+        .oneshot(create(Some(&token), Some("https://cal.example.com")))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let resp = app
+        .oneshot(
+            Request::get("/api/sources")
+                .header(header::COOKIE, &cookie_header)
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+// ---------------------------------------------------------------------------
+// Frontend proxy
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn frontend_proxy_passes_websocket_upgrades_through() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Stands in for the Next.js dev server: switches protocols, then echoes.
+    let upstream = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_url = format!("http://{}", upstream.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (mut conn, _) = upstream.accept().await.unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(conn.read_u8().await.unwrap());
+        }
+        let head = String::from_utf8(head).unwrap().to_ascii_lowercase();
+        assert!(head.starts_with("get /_next/webpack-hmr "));
+        assert!(head.contains("upgrade: websocket"));
+        conn.write_all(
+            b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n",
+        )
+        .await
+        .unwrap();
+        let mut buf = [0u8; 4];
+        conn.read_exact(&mut buf).await.unwrap();
+        conn.write_all(&buf).await.unwrap();
+    });
+
+    let app = build_router(test_state(), Frontend::Proxy(upstream_url)).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+    client
+        .write_all(
+            b"GET /_next/webpack-hmr HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(client.read_u8().await.unwrap());
+    }
+    assert!(String::from_utf8(head).unwrap().starts_with("HTTP/1.1 101"));
+
+    client.write_all(b"ping").await.unwrap();
+    let mut echoed = [0u8; 4];
+    tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        client.read_exact(&mut echoed),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(&echoed, b"ping");
+}
+
+#[tokio::test]
+async fn frontend_proxy_sets_forwarding_headers_and_strips_hop_by_hop() {
+    // Stands in for Next.js and reports the headers it received.
+    let upstream = axum::Router::new().fallback(|headers: axum::http::HeaderMap| async move {
+        let seen: std::collections::BTreeMap<String, String> = headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_str().unwrap().to_owned()))
+            .collect();
+        (
+            [("keep-alive", "timeout=5")],
+            axum::Json(serde_json::to_value(seen).unwrap()),
+        )
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, upstream).await.unwrap() });
+
+    let app = build_router(test_state(), Frontend::Proxy(upstream_url)).await;
+    let mut req = Request::get("/settings/")
+        .header(header::HOST, "cal.example.com")
+        .header(header::CONNECTION, "keep-alive, x-hop")
+        .header("x-hop", "1")
+        .header("proxy-authorization", "Basic Zm9vOmJhcg==")
+        .header("x-forwarded-for", "203.0.113.9")
+        .body(axum::body::Body::empty())
+        .unwrap();
+    req.extensions_mut()
+        .insert(ConnectInfo(std::net::SocketAddr::from((
+            [192, 0, 2, 7],
+            4000,
+        ))));
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(!resp.headers().contains_key("keep-alive"));
+
+    let seen: serde_json::Value = serde_json::from_str(&body_string(resp).await).unwrap();
+    assert_eq!(seen["x-forwarded-for"], "203.0.113.9, 192.0.2.7");
+    assert_eq!(seen["x-forwarded-host"], "cal.example.com");
+    assert_eq!(seen["x-forwarded-proto"], "http");
+    assert!(seen.get("x-hop").is_none());
+    assert!(seen.get("proxy-authorization").is_none());
+}
+
+// ---------------------------------------------------------------------------
+// Subscriptions
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn subscribe_redirects_to_webcal_and_serves_a_profile() {
+    let state = test_state();
+    let id = insert_source(&state, "team/work.ics", true, Some("work"));
+    {
+        let db = state.db.lock().unwrap();
+        let properties = caldav_ics_sync::feed_metadata::FeedProperties {
+            published_ttl_minutes: Some(30),
+            ..Default::default()
+        };
+        db::set_feed_properties(&db, id, &properties).unwrap();
+    }
+    let app = router_with_auth(state).await;
+    let get = |uri: &str| {
+        Request::get(uri)
+            .header(header::HOST, "cal.example.com")
+            .header("x-forwarded-proto", "https")
+            .body(axum::body::Body::empty())
+            .unwrap()
+    };
+
+    let resp = app
+        .clone()
+        .oneshot(get("/subscribe/public/work"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FOUND);
+    assert_eq!(
+        resp.headers()[header::LOCATION],
+        "webcal://cal.example.com/ics/public/work"
+    );
+
+    let resp = app
+        .clone()
+        .oneshot(get("/subscribe/public/work.mobileconfig"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers()[header::CONTENT_TYPE],
+        "application/x-apple-aspen-config"
+    );
+    assert_eq!(
+        resp.headers()[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"work.mobileconfig\""
+    );
+    let body = body_string(resp).await;
+    assert!(body.contains("<string>https://cal.example.com/ics/public/work</string>"));
+    assert!(body.contains("refreshed every 30 minute(s)."));
+
+    // Private feeds need the same credentials as the feed itself.
+    let resp = app
+        .clone()
+        .oneshot(get("/subscribe/team/work.ics"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let mut req = get("/subscribe/team/work.ics");
+    req.headers_mut().insert(
+        header::AUTHORIZATION,
+        basic_auth_header("test", "test").parse().unwrap(),
+    );
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(
+        resp.headers()[header::LOCATION],
+        "webcal://cal.example.com/ics/team/work.ics"
+    );
+
+    let resp = app
+        .clone()
+        .oneshot(get("/subscribe/public/missing"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+// ---------------------------------------------------------------------------
+// Agenda view
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn view_renders_upcoming_events_without_login() {
+    let state = test_state();
+    let id = insert_source(&state, "private-cal", true, Some("view-token"));
+    let soon = chrono::Utc::now() + chrono::Duration::days(2);
+    let later = chrono::Utc::now() + chrono::Duration::days(40);
+    let event = |uid: &str, summary: &str, at: chrono::DateTime<chrono::Utc>| {
+        format!(
+            "BEGIN:VEVENT\r\nUID:{uid}\r\nDTSTART:{}\r\nDTEND:{}\r\nSUMMARY:{summary}\r\nLOCATION:Room <1>\r\nEND:VEVENT\r\n",
+            at.format("%Y%m%dT100000Z"),
+            at.format("%Y%m%dT110000Z"),
+        )
+    };
+    let weekly = format!(
+        "BEGIN:VEVENT\r\nUID:c\r\nDTSTART:{}\r\nRRULE:FREQ=WEEKLY\r\nSUMMARY:Weekly sync\r\nEND:VEVENT\r\n",
+        (chrono::Utc::now() - chrono::Duration::days(60)).format("%Y%m%dT%H%M%SZ")
+    );
+    save_ics(
+        &state,
+        id,
+        &format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}{}{}END:VCALENDAR\r\n",
+            event("a", "Planning", soon),
+            event("b", "Far away", later),
+            weekly,
+        ),
+    );
+    let app = router_with_auth(state).await;
+    let get = |uri: &str| Request::get(uri).body(axum::body::Body::empty()).unwrap();
+
+    let resp = app.clone().oneshot(get("/view/view-token")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers()[header::CONTENT_TYPE],
+        "text/html; charset=utf-8"
+    );
+    let body = body_string(resp).await;
+    assert!(body.contains(&format!("<h2>{}</h2>", soon.format("%A, %-d %B %Y"))));
+    assert!(body.contains(
+        "<li><time>10:00–11:00</time> Planning <span class=\"location\">Room &lt;1&gt;</span></li>"
+    ));
+    assert!(!body.contains("Far away"));
+    // A series that started before the window shows every occurrence in it.
+    assert!(body.matches("Weekly sync").count() >= 4);
+
+    // Only public paths work as view tokens.
+    let resp = app.clone().oneshot(get("/view/private-cal")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
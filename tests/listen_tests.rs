@@ -0,0 +1,50 @@
+#![cfg(unix)]
+
+use axum::Router;
+use axum::extract::ConnectInfo;
+use axum::routing::get;
+use axum::serve::ListenerExt;
+use caldav_ics_sync::server::listen::Listener;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+async fn get_over_socket(path: &std::path::Path) -> String {
+    let mut stream = tokio::net::UnixStream::connect(path).await.unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn serves_over_unix_socket_and_replaces_stale_socket() {
+    let dir = std::env::temp_dir().join(format!("caldav-ics-sync-uds-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("server.sock");
+    // Left behind by a previous run that didn't shut down cleanly.
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+    let listener = Listener::unix(path.to_str().unwrap()).unwrap();
+    assert_eq!(listener.socket_file().as_deref(), Some(path.as_path()));
+    let app = Router::new().route(
+        "/",
+        get(|ConnectInfo(peer): ConnectInfo<SocketAddr>| async move { peer.ip().to_string() }),
+    );
+    tokio::spawn(async move {
+        axum::serve(
+            listener.tap_io(|_| {}),
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap()
+    });
+
+    let response = get_over_socket(&path).await;
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("127.0.0.1"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
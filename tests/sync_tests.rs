@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::net::SocketAddr;
 
 use axum::{
@@ -8,8 +9,12 @@ use axum::{
     response::{IntoResponse, Response},
     routing::any,
 };
-use caldav_ics_sync::api::reverse_sync::run_reverse_sync;
-use caldav_ics_sync::api::sync::{fetch_calendars, fetch_events, run_sync, toggle_slash};
+use caldav_ics_sync::api::reverse_sync::{PushTarget, fetch_feed, push_feed, run_reverse_sync};
+use caldav_ics_sync::api::sync::{
+    ObjectCache, fetch_calendar, fetch_calendars, fetch_events, run_sync, sort_events, toggle_slash,
+};
+use caldav_ics_sync::db::CachedObject;
+use caldav_ics_sync::sync_origin::destination_namespace;
 use reqwest::{Client, header};
 use tokio::net::TcpListener;
 
@@ -161,6 +166,28 @@ fn toggle_slash_roundtrips() {
     assert_eq!(back, original);
 }
 
+#[test]
+fn sort_events_orders_by_uid_then_start() {
+    let event = |uid: &str, start: &str| {
+        format!(
+            "BEGIN:VEVENT\r\nUID:{}\r\nDTSTART:{}\r\nEND:VEVENT\r\n",
+            uid, start
+        )
+    };
+    let mut events = vec![
+        event("b", "20270101T090000Z"),
+        event("a", "20270301T090000Z"),
+        event("a", "20270201T090000Z"),
+    ];
+    let mut shuffled = vec![events[2].clone(), events[0].clone(), events[1].clone()];
+    sort_events(&mut events);
+    sort_events(&mut shuffled);
+    assert_eq!(events, shuffled);
+    assert!(events[0].contains("UID:a\r\nDTSTART:20270201"));
+    assert!(events[1].contains("UID:a\r\nDTSTART:20270301"));
+    assert!(events[2].contains("UID:b"));
+}
+
 // ---------------------------------------------------------------------------
 // fetch_calendars tests
 // ---------------------------------------------------------------------------
@@ -255,6 +282,105 @@ async fn fetch_events_returns_vevent_strings() {
     assert!(result[0].contains("SUMMARY:Meeting"));
 }
 
+// Caps calendar-query results at one object, like servers that answer
+// large REPORTs with 507, but serves any number through calendar-multiget.
+async fn limited_caldav_handler(req: Request<Body>) -> Response {
+    let method = req.method().as_str().to_string();
+    let body = axum::body::to_bytes(req.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    let uids = ["uid-1", "uid-2", "uid-3"];
+    let events: Vec<_> = uids
+        .iter()
+        .map(|uid| (*uid, "Limited", "20250101T100000Z", "20250101T110000Z"))
+        .collect();
+    match method.as_str() {
+        "PROPFIND" => {
+            let objects: String = uids
+                .iter()
+                .map(|uid| format!("<d:response><d:href>/cal/{uid}.ics</d:href><d:propstat><d:prop><d:resourcetype/><d:getetag>\"{uid}\"</d:getetag></d:prop></d:propstat></d:response>"))
+                .collect();
+            (
+                StatusCode::MULTI_STATUS,
+                format!(
+                    r#"<d:multistatus xmlns:d="DAV:"><d:response><d:href>/cal/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat></d:response>{objects}</d:multistatus>"#
+                ),
+            )
+                .into_response()
+        }
+        "REPORT" if body.contains("calendar-multiget") => {
+            let requested: Vec<_> = events
+                .iter()
+                .filter(|(uid, ..)| body.contains(&format!("/cal/{uid}.ics")))
+                .cloned()
+                .collect();
+            (StatusCode::MULTI_STATUS, mock_report_response(&requested)).into_response()
+        }
+        "REPORT" => {
+            let truncated = mock_report_response(&events[..1]).replace(
+                "</d:multistatus>",
+                "<d:response><d:href>/cal/</d:href><d:status>HTTP/1.1 507 Insufficient Storage</d:status><d:error><d:number-of-matches-within-limits/></d:error></d:response></d:multistatus>",
+            );
+            (StatusCode::MULTI_STATUS, truncated).into_response()
+        }
+        _ => (StatusCode::METHOD_NOT_ALLOWED, "").into_response(),
+    }
+}
+
+#[tokio::test]
+async fn fetch_events_falls_back_to_multiget_when_truncated() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            Router::new().fallback(any(limited_caldav_handler)),
+        )
+        .await
+        .unwrap();
+    });
+    let client = build_client("user", "pass");
+
+    let result = fetch_events(&client, &format!("http://{}", addr), "/cal/")
+        .await
+        .unwrap();
+
+    assert_eq!(result.len(), 3);
+    assert!(result[2].contains("UID:uid-3"));
+}
+
+#[tokio::test]
+async fn fetch_calendar_multiget_reuses_unchanged_objects() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            Router::new().fallback(any(limited_caldav_handler)),
+        )
+        .await
+        .unwrap();
+    });
+    let client = build_client("user", "pass");
+    let cached = CachedObject {
+        href: "/cal/uid-1.ics".into(),
+        etag: "\"uid-1\"".into(),
+        calendar_data: "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:uid-1\r\nSUMMARY:Cached\r\nEND:VEVENT\r\nEND:VCALENDAR".into(),
+    };
+    let cache = ObjectCache::from([(cached.href.clone(), cached)]);
+
+    let calendar = fetch_calendar(&client, &format!("http://{}", addr), "/cal/", Some(&cache))
+        .await
+        .unwrap();
+
+    assert_eq!(calendar.events.len(), 3);
+    assert!(calendar.events[0].contains("SUMMARY:Cached"));
+    assert!(calendar.events[2].contains("SUMMARY:Limited"));
+    assert_eq!(calendar.objects.len(), 3);
+    assert_eq!(calendar.objects[1].etag, "\"uid-2\"");
+}
+
 #[tokio::test]
 async fn fetch_events_handles_non_standard_port() {
     let events = [(
@@ -425,6 +551,28 @@ async fn reverse_sync_uploads_events() {
     assert_eq!(stats.total, 2);
 }
 
+#[tokio::test]
+async fn fetch_feed_skips_feed_matching_last_hash() {
+    let events = [("uid-f1", "Feed", "20270601T080000Z", "20270601T090000Z")];
+    let (ics_addr, _) = start_reverse_sync_mocks(&events, StatusCode::CREATED).await;
+    let url = format!("http://{}/feed.ics", ics_addr);
+
+    let feed = fetch_feed(&url, None, None).await.unwrap().unwrap();
+    assert!(feed.text.contains("UID:uid-f1"));
+    assert!(
+        fetch_feed(&url, Some(&feed.hash), None)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        fetch_feed(&url, Some("stale"), None)
+            .await
+            .unwrap()
+            .is_some()
+    );
+}
+
 #[tokio::test]
 async fn reverse_sync_handles_double_calendar_path() {
     // caldav_url already ends with the calendar name
@@ -474,7 +622,7 @@ async fn reverse_sync_reports_correct_uploaded_count() {
 }
 
 #[tokio::test]
-async fn reverse_sync_returns_error_when_uploads_fail() {
+async fn reverse_sync_reports_failed_uploads_without_failing_the_run() {
     let events = [("uid-fail", "Fail", "20270901T080000Z", "20270901T090000Z")];
     let (ics_addr, caldav_addr) =
         start_reverse_sync_mocks(&events, StatusCode::INTERNAL_SERVER_ERROR).await;
@@ -490,12 +638,13 @@ async fn reverse_sync_returns_error_when_uploads_fail() {
     )
     .await;
 
-    assert!(result.is_err());
-    let err_msg = result.unwrap_err().to_string();
-    assert!(
-        err_msg.contains("failed"),
-        "Expected failure message, got: {err_msg}"
-    );
+    // The server answered, so the event is retried next run rather than
+    // failing this one.
+    let stats = result.unwrap();
+    assert_eq!(stats.uploaded, 0);
+    assert_eq!(stats.failed.len(), 1);
+    assert_eq!(stats.failed[0].uid, "uid-fail");
+    assert!(stats.failed[0].error.contains("500"));
 }
 
 #[tokio::test]
@@ -555,3 +704,673 @@ async fn reverse_sync_skips_unchanged_events() {
     assert_eq!(stats.uploaded, 1, "only uid-new should be uploaded");
     assert_eq!(stats.deleted, 0);
 }
+
+async fn start_conflict_mocks() -> (String, SocketAddr) {
+    let feed = mock_ics_feed(&[(
+        "uid-edit",
+        "From Feed",
+        "20270601T080000Z",
+        "20270601T090000Z",
+    )]);
+    // The calendar's copy was edited by a client, which bumped SEQUENCE.
+    let report =
+        mock_report_response(&[("uid-edit", "Edited", "20270601T080000Z", "20270601T090000Z")])
+            .replace("SUMMARY:Edited", "SUMMARY:Edited\r\nSEQUENCE:2");
+    let caldav_state = std::sync::Arc::new(MockState {
+        propfind_body: String::new(),
+        report_body: report,
+        put_status: StatusCode::CREATED,
+    });
+    (feed, start_mock_server(caldav_state).await)
+}
+
+#[tokio::test]
+async fn reverse_sync_applies_conflict_policy_to_edited_events() {
+    let (feed, caldav_addr) = start_conflict_mocks().await;
+    let caldav_url = format!("http://{}/dav/", caldav_addr);
+    let target = |conflict_policy| PushTarget {
+        caldav_url: &caldav_url,
+        calendar_name: "cal",
+        username: "user",
+        password: "pass",
+        sync_all: true,
+        keep_local: true,
+        conflict_policy,
+        origin: None,
+        namespace_uids: false,
+        bidirectional: false,
+        scheduling: "keep",
+        pace: Default::default(),
+        template: Default::default(),
+    };
+    let none = HashMap::new();
+
+    let stats = push_feed(&feed, &target("skip"), &none).await.unwrap();
+    assert_eq!(stats.uploaded, 0);
+    assert_eq!(stats.conflicts.len(), 1);
+    assert_eq!(stats.conflicts[0].uid, "uid-edit");
+    assert_eq!(stats.conflicts[0].summary.as_deref(), Some("From Feed"));
+    assert_eq!(stats.conflicts[0].resolution, "skip");
+
+    // The feed's version goes in next to the edited one.
+    let stats = push_feed(&feed, &target("duplicate"), &none).await.unwrap();
+    assert_eq!((stats.added, stats.changed), (1, 0));
+    assert_eq!(stats.conflicts.len(), 1);
+
+    let stats = push_feed(&feed, &target("overwrite"), &none).await.unwrap();
+    assert_eq!((stats.added, stats.changed), (0, 1));
+    assert_eq!(stats.conflicts[0].resolution, "overwrite");
+}
+
+#[tokio::test]
+async fn reverse_sync_detects_edits_by_etag() {
+    let (feed, caldav_addr) = start_conflict_mocks().await;
+    let caldav_url = format!("http://{}/dav/", caldav_addr);
+    let target = PushTarget {
+        caldav_url: &caldav_url,
+        calendar_name: "cal",
+        username: "user",
+        password: "pass",
+        sync_all: true,
+        keep_local: true,
+        conflict_policy: "skip",
+        origin: None,
+        namespace_uids: false,
+        bidirectional: false,
+        scheduling: "keep",
+        pace: Default::default(),
+        template: Default::default(),
+    };
+
+    // Same ETag as last time: the calendar copy is ours, whatever SEQUENCE says.
+    let seen = HashMap::from([("uid-edit".to_string(), Some("\"uid-edit\"".to_string()))]);
+    let stats = push_feed(&feed, &target, &seen).await.unwrap();
+    assert!(stats.conflicts.is_empty());
+    assert_eq!(stats.changed, 1);
+
+    let stale = HashMap::from([("uid-edit".to_string(), Some("\"older\"".to_string()))]);
+    let stats = push_feed(&feed, &target, &stale).await.unwrap();
+    assert_eq!(stats.conflicts.len(), 1);
+    assert_eq!(stats.uploaded, 0);
+    // A skipped event keeps the old ETag so the conflict is reported again.
+    assert_eq!(stats.etags["uid-edit"].as_deref(), Some("\"older\""));
+}
+
+#[tokio::test]
+async fn bidirectional_sync_merges_calendar_events_into_combined_feed() {
+    let namespace = destination_namespace(1);
+    let own = format!("mirrored@{}", namespace);
+    let feed = mock_ics_feed(&[
+        ("uid-a", "From Feed", "20270601T080000Z", "20270601T090000Z"),
+        (
+            &own,
+            "Came From Here",
+            "20270602T080000Z",
+            "20270602T090000Z",
+        ),
+    ]);
+    let report = mock_report_response(&[
+        (
+            "cal-only",
+            "Made In Calendar",
+            "20270603T080000Z",
+            "20270603T090000Z",
+        ),
+        (
+            "pushed-before",
+            "Left The Feed",
+            "20270604T080000Z",
+            "20270604T090000Z",
+        ),
+    ]);
+    let caldav_addr = start_mock_server(std::sync::Arc::new(MockState {
+        propfind_body: String::new(),
+        report_body: report,
+        put_status: StatusCode::CREATED,
+    }))
+    .await;
+    let caldav_url = format!("http://{}/dav/", caldav_addr);
+    let target = PushTarget {
+        caldav_url: &caldav_url,
+        calendar_name: "cal",
+        username: "user",
+        password: "pass",
+        sync_all: true,
+        keep_local: true,
+        conflict_policy: "overwrite",
+        origin: Some(namespace.clone()),
+        namespace_uids: false,
+        bidirectional: true,
+        scheduling: "keep",
+        pace: Default::default(),
+        template: Default::default(),
+    };
+    let known = HashMap::from([("pushed-before".to_string(), None)]);
+
+    let stats = push_feed(&feed, &target, &known).await.unwrap();
+    // The feed's copy of a calendar event is not pushed back.
+    assert_eq!((stats.added, stats.total), (1, 1));
+    assert_eq!(stats.merged, 1);
+    let combined = stats.combined_feed.unwrap();
+    assert!(combined.contains("UID:uid-a\r\n"));
+    assert!(combined.contains(&format!("UID:cal-only@{}\r\n", namespace)));
+    assert!(!combined.contains("UID:pushed-before"));
+    assert!(combined.trim_end().ends_with("END:VCALENDAR"));
+}
+
+#[tokio::test]
+async fn namespaced_destination_stamps_pushed_events() {
+    let namespace = destination_namespace(4);
+    let feed = mock_ics_feed(&[("plain", "Plain", "20270601T080000Z", "20270601T090000Z")])
+        .replace(
+            "END:VCALENDAR",
+            &format!(
+                "BEGIN:VEVENT\r\nUID:back@x\r\nX-SYNC-ORIGIN:{}\r\nDTSTART:20270602T080000Z\r\nEND:VEVENT\r\nEND:VCALENDAR",
+                namespace
+            ),
+        );
+    let (_, caldav_addr) = start_reverse_sync_mocks(&[], StatusCode::CREATED).await;
+    let caldav_url = format!("http://{}/dav/", caldav_addr);
+    let target = PushTarget {
+        caldav_url: &caldav_url,
+        calendar_name: "cal",
+        username: "user",
+        password: "pass",
+        sync_all: true,
+        keep_local: true,
+        conflict_policy: "overwrite",
+        origin: Some(namespace.clone()),
+        namespace_uids: true,
+        bidirectional: false,
+        scheduling: "keep",
+        pace: Default::default(),
+        template: Default::default(),
+    };
+
+    let stats = push_feed(&feed, &target, &HashMap::new()).await.unwrap();
+    assert_eq!((stats.added, stats.total), (1, 1));
+    let pushed: Vec<&String> = stats.etags.keys().collect();
+    assert_eq!(pushed, [&format!("plain@{}", namespace)]);
+    assert!(stats.combined_feed.is_none());
+}
+
+#[derive(Default)]
+struct BatchCounts {
+    posts: std::sync::atomic::AtomicUsize,
+    puts: std::sync::atomic::AtomicUsize,
+}
+
+// Advertises batch uploads, stores every part except `uid-rejected`.
+async fn batch_caldav_handler(
+    axum::extract::State(counts): axum::extract::State<std::sync::Arc<BatchCounts>>,
+    req: Request<Body>,
+) -> Response {
+    use std::sync::atomic::Ordering;
+    match req.method().as_str() {
+        "OPTIONS" => (
+            StatusCode::OK,
+            [("DAV", "1, 3, calendar-access, bulk-upload")],
+        )
+            .into_response(),
+        "REPORT" => (StatusCode::MULTI_STATUS, mock_report_response(&[])).into_response(),
+        "POST" => {
+            counts.posts.fetch_add(1, Ordering::SeqCst);
+            let body = axum::body::to_bytes(req.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body = String::from_utf8(body.to_vec()).unwrap();
+            let responses: String = body
+                .lines()
+                .filter_map(|l| l.strip_prefix("Content-Location: "))
+                .map(|name| {
+                    let status = match name {
+                        "uid-rejected.ics" => "403 Forbidden",
+                        _ => "201 Created",
+                    };
+                    format!(
+                        r#"<d:response><d:href>/dav/cal/{name}</d:href><d:propstat><d:prop><d:getetag>"b-{name}"</d:getetag></d:prop><d:status>HTTP/1.1 {status}</d:status></d:propstat></d:response>"#
+                    )
+                })
+                .collect();
+            (
+                StatusCode::MULTI_STATUS,
+                format!(r#"<d:multistatus xmlns:d="DAV:">{responses}</d:multistatus>"#),
+            )
+                .into_response()
+        }
+        "PUT" => {
+            counts.puts.fetch_add(1, Ordering::SeqCst);
+            (StatusCode::CREATED, "").into_response()
+        }
+        _ => (StatusCode::METHOD_NOT_ALLOWED, "").into_response(),
+    }
+}
+
+#[tokio::test]
+async fn reverse_sync_uploads_in_batches_when_server_supports_it() {
+    use std::sync::atomic::Ordering;
+    let counts = std::sync::Arc::new(BatchCounts::default());
+    let app = Router::new()
+        .fallback(any(batch_caldav_handler))
+        .with_state(counts.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let caldav_addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let feed = mock_ics_feed(&[
+        ("uid-b1", "B1", "20270601T080000Z", "20270601T090000Z"),
+        ("uid-b2", "B2", "20270602T080000Z", "20270602T090000Z"),
+        ("uid-rejected", "R", "20270603T080000Z", "20270603T090000Z"),
+    ]);
+    let caldav_url = format!("http://{}/dav/", caldav_addr);
+    let target = PushTarget {
+        caldav_url: &caldav_url,
+        calendar_name: "cal",
+        username: "user",
+        password: "pass",
+        sync_all: true,
+        keep_local: true,
+        conflict_policy: "overwrite",
+        origin: None,
+        namespace_uids: false,
+        bidirectional: false,
+        scheduling: "keep",
+        pace: Default::default(),
+        template: Default::default(),
+    };
+
+    let stats = push_feed(&feed, &target, &HashMap::new()).await.unwrap();
+    assert_eq!((stats.added, stats.total), (3, 3));
+    assert_eq!(counts.posts.load(Ordering::SeqCst), 1);
+    // Only the event the batch rejected is PUT on its own.
+    assert_eq!(counts.puts.load(Ordering::SeqCst), 1);
+    assert_eq!(stats.etags["uid-b1"].as_deref(), Some("\"b-uid-b1.ics\""));
+}
+
+#[tokio::test]
+async fn sync_limit_caps_syncs_per_host() {
+    let limit = caldav_ics_sync::auto_sync::new_sync_limit(4, 1);
+    let wait = std::time::Duration::from_millis(50);
+    let held = limit
+        .acquire("https://caldav.icloud.com/123/calendars/")
+        .await
+        .unwrap();
+    // Same host, different account path: has to wait.
+    assert!(
+        tokio::time::timeout(wait, limit.acquire("https://CalDAV.iCloud.com/456/"))
+            .await
+            .is_err()
+    );
+    assert!(
+        tokio::time::timeout(wait, limit.acquire("https://dav.example.com/"))
+            .await
+            .is_ok()
+    );
+    drop(held);
+    assert!(
+        tokio::time::timeout(wait, limit.acquire("https://caldav.icloud.com/456/"))
+            .await
+            .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn full_round_trip_against_the_mock_caldav_server() {
+    use caldav_ics_sync::mock_caldav::{DEMO_PASSWORD, DEMO_USERNAME, MockCalDav};
+
+    let mock = MockCalDav::sample(chrono::NaiveDate::from_ymd_opt(2026, 3, 11).unwrap())
+        .with_calendar("copy", "Copy", "", &[]);
+    let addr = mock.spawn().await.unwrap();
+    let home = format!("http://{}{}", addr, mock.home());
+
+    assert!(run_sync(&home, "demo", "wrong").await.is_err());
+    let (event_count, calendar_count, ics) =
+        run_sync(&home, DEMO_USERNAME, DEMO_PASSWORD).await.unwrap();
+    assert_eq!((event_count, calendar_count), (6, 3));
+    assert!(ics.contains("SUMMARY:Team standup"));
+
+    let target = PushTarget {
+        caldav_url: &home,
+        calendar_name: "copy",
+        username: DEMO_USERNAME,
+        password: DEMO_PASSWORD,
+        sync_all: true,
+        keep_local: false,
+        conflict_policy: "overwrite",
+        origin: None,
+        namespace_uids: false,
+        bidirectional: false,
+        scheduling: "keep",
+        pace: Default::default(),
+        template: Default::default(),
+    };
+    let stats = push_feed(&ics, &target, &HashMap::new()).await.unwrap();
+    assert_eq!((stats.added, stats.total), (6, 6));
+    assert_eq!(mock.objects(&format!("{}copy/", mock.home())).len(), 6);
+
+    // Unchanged on the second run; a dropped event is deleted.
+    let stats = push_feed(&ics, &target, &stats.etags).await.unwrap();
+    assert_eq!((stats.uploaded, stats.deleted), (0, 0));
+    let start = ics.find("BEGIN:VEVENT\r\nUID:dentist@").unwrap();
+    let end = start + ics[start..].find("END:VEVENT\r\n").unwrap() + "END:VEVENT\r\n".len();
+    let fewer = format!("{}{}", &ics[..start], &ics[end..]);
+    let stats = push_feed(&fewer, &target, &stats.etags).await.unwrap();
+    assert_eq!(stats.deleted, 1);
+    assert_eq!(mock.objects(&format!("{}copy/", mock.home())).len(), 5);
+}
+
+#[tokio::test]
+async fn reverse_sync_uploads_templated_events() {
+    use caldav_ics_sync::event_template::EventTemplate;
+    use caldav_ics_sync::mock_caldav::MockCalDav;
+
+    let mock = MockCalDav::new("user", "pass").with_calendar("rooms", "Rooms", "", &[]);
+    let addr = mock.spawn().await.unwrap();
+    let home = format!("http://{}{}", addr, mock.home());
+    let feed = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nX-WR-CALNAME:Booker\r\n\
+        BEGIN:VEVENT\r\nUID:booking-1\r\nDTSTAMP:20260101T000000Z\r\n\
+        DTSTART:20300310T090000Z\r\nSUMMARY:Board meeting\r\nX-TICKET:FAC-7\r\n\
+        ATTENDEE:mailto:guest@example.com\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    let target = PushTarget {
+        caldav_url: &home,
+        calendar_name: "rooms",
+        username: "user",
+        password: "pass",
+        sync_all: true,
+        keep_local: false,
+        conflict_policy: "overwrite",
+        origin: None,
+        namespace_uids: false,
+        bidirectional: false,
+        scheduling: "keep",
+        pace: Default::default(),
+        template: EventTemplate {
+            summary: "{x-ticket}: {summary}",
+            description: "From {calendar}",
+            drop_properties: "ATTENDEE,X-TICKET",
+            ..Default::default()
+        },
+    };
+    let stats = push_feed(feed, &target, &HashMap::new()).await.unwrap();
+    assert_eq!(stats.added, 1);
+    let stored = mock.objects(&format!("{}rooms/", mock.home()));
+    assert!(stored[0].contains("SUMMARY:FAC-7: Board meeting\r\n"));
+    assert!(stored[0].contains("DESCRIPTION:From Booker\r\n"));
+    assert!(!stored[0].contains("ATTENDEE") && !stored[0].contains("X-TICKET"));
+
+    // The templated event matches what is stored, so nothing is re-uploaded.
+    let stats = push_feed(feed, &target, &stats.etags).await.unwrap();
+    assert_eq!((stats.uploaded, stats.skipped), (0, 1));
+}
+
+#[tokio::test]
+async fn reverse_sync_keeps_invitations_from_reaching_the_server() {
+    use caldav_ics_sync::mock_caldav::MockCalDav;
+
+    let mock = MockCalDav::new("user", "pass")
+        .with_calendar("strip", "Strip", "", &[])
+        .with_calendar("client", "Client", "", &[]);
+    let addr = mock.spawn().await.unwrap();
+    let home = format!("http://{}{}", addr, mock.home());
+    let feed = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:review\r\n\
+        DTSTAMP:20260101T000000Z\r\nDTSTART:20300310T090000Z\r\nSUMMARY:Review\r\n\
+        ORGANIZER:mailto:lee@example.com\r\nATTENDEE;RSVP=TRUE:mailto:kim@example.com\r\n\
+        END:VEVENT\r\nEND:VCALENDAR\r\n";
+    for scheduling in ["strip", "client"] {
+        let target = PushTarget {
+            caldav_url: &home,
+            calendar_name: scheduling,
+            username: "user",
+            password: "pass",
+            sync_all: true,
+            keep_local: false,
+            conflict_policy: "overwrite",
+            origin: None,
+            namespace_uids: false,
+            bidirectional: false,
+            scheduling,
+            pace: Default::default(),
+            template: Default::default(),
+        };
+        let stats = push_feed(feed, &target, &HashMap::new()).await.unwrap();
+        assert_eq!(stats.added, 1);
+        let stats = push_feed(feed, &target, &stats.etags).await.unwrap();
+        assert_eq!(stats.skipped, 1);
+    }
+    let stored = |calendar: &str| mock.objects(&format!("{}{}/", mock.home(), calendar))[0].clone();
+    assert!(!stored("strip").contains("ATTENDEE") && !stored("strip").contains("ORGANIZER"));
+    assert!(stored("client").contains("ATTENDEE;SCHEDULE-AGENT=CLIENT:mailto:kim@example.com\r\n"));
+}
+
+#[tokio::test]
+async fn reverse_sync_sends_preconditions_to_servers_refusing_blind_writes() {
+    use caldav_ics_sync::mock_caldav::MockCalDav;
+
+    let event = |uid: &str, summary: &str| {
+        format!(
+            "BEGIN:VEVENT\r\nUID:{}\r\nDTSTAMP:20260101T000000Z\r\nDTSTART:20300310T090000Z\r\nSUMMARY:{}\r\nEND:VEVENT\r\n",
+            uid, summary
+        )
+    };
+    let mock = MockCalDav::new("user", "pass")
+        .with_calendar("strict", "Strict", "", &[event("kept", "Old title")])
+        .requiring_preconditions();
+    let addr = mock.spawn().await.unwrap();
+    let home = format!("http://{}{}", addr, mock.home());
+    let feed = format!(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}{}END:VCALENDAR\r\n",
+        event("kept", "New title"),
+        event("fresh", "Fresh")
+    );
+    let target = PushTarget {
+        caldav_url: &home,
+        calendar_name: "strict",
+        username: "user",
+        password: "pass",
+        sync_all: true,
+        keep_local: true,
+        conflict_policy: "overwrite",
+        origin: None,
+        namespace_uids: false,
+        bidirectional: false,
+        scheduling: "keep",
+        pace: Default::default(),
+        template: Default::default(),
+    };
+    let stats = push_feed(&feed, &target, &HashMap::new()).await.unwrap();
+    assert_eq!((stats.added, stats.changed), (1, 1));
+    let stored = mock.objects(&format!("{}strict/", mock.home()));
+    assert!(stored.iter().any(|o| o.contains("SUMMARY:New title")));
+}
+
+// Lists `uid-race` without an ETag and only takes a PUT that names the
+// current one.
+async fn etag_guarded_handler(
+    axum::extract::State(puts): axum::extract::State<std::sync::Arc<std::sync::Mutex<Vec<String>>>>,
+    req: Request<Body>,
+) -> Response {
+    let current = "\"v2\"";
+    match req.method().as_str() {
+        "REPORT" => {
+            let report = mock_report_response(&[(
+                "uid-race",
+                "Calendar copy",
+                "20270601T080000Z",
+                "20270601T090000Z",
+            )])
+            .replace("<d:getetag>\"uid-race\"</d:getetag>", "");
+            (StatusCode::MULTI_STATUS, report).into_response()
+        }
+        "HEAD" => (StatusCode::OK, [(header::ETAG, current)]).into_response(),
+        "PUT" => {
+            let condition = req
+                .headers()
+                .get(header::IF_MATCH)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("none")
+                .to_string();
+            puts.lock().unwrap().push(condition.clone());
+            match condition == current {
+                true => (StatusCode::NO_CONTENT, [(header::ETAG, "\"v3\"")]).into_response(),
+                false => (StatusCode::PRECONDITION_FAILED, "").into_response(),
+            }
+        }
+        _ => (StatusCode::METHOD_NOT_ALLOWED, "").into_response(),
+    }
+}
+
+#[tokio::test]
+async fn reverse_sync_retries_a_put_that_fails_its_precondition() {
+    let puts = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let app = Router::new()
+        .fallback(any(etag_guarded_handler))
+        .with_state(puts.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let caldav_addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let feed = mock_ics_feed(&[(
+        "uid-race",
+        "From feed",
+        "20270601T080000Z",
+        "20270601T090000Z",
+    )]);
+    let caldav_url = format!("http://{}/dav/", caldav_addr);
+    let target = PushTarget {
+        caldav_url: &caldav_url,
+        calendar_name: "cal",
+        username: "user",
+        password: "pass",
+        sync_all: true,
+        keep_local: true,
+        conflict_policy: "overwrite",
+        origin: None,
+        namespace_uids: false,
+        bidirectional: false,
+        scheduling: "keep",
+        pace: Default::default(),
+        template: Default::default(),
+    };
+    let stats = push_feed(&feed, &target, &HashMap::new()).await.unwrap();
+    assert_eq!(stats.changed, 1);
+    assert_eq!(*puts.lock().unwrap(), ["none", "\"v2\""]);
+    assert_eq!(stats.etags["uid-race"].as_deref(), Some("\"v3\""));
+}
+
+// Refuses `uid-bad` and stores everything else.
+async fn flaky_caldav_handler(req: Request<Body>) -> Response {
+    match req.method().as_str() {
+        "REPORT" => (StatusCode::MULTI_STATUS, mock_report_response(&[])).into_response(),
+        "PUT" if req.uri().path().ends_with("/uid-bad.ics") => {
+            (StatusCode::INTERNAL_SERVER_ERROR, "").into_response()
+        }
+        "PUT" => (StatusCode::CREATED, [(header::ETAG, "\"stored\"")]).into_response(),
+        _ => (StatusCode::METHOD_NOT_ALLOWED, "").into_response(),
+    }
+}
+
+#[tokio::test]
+async fn reverse_sync_reports_failed_events_without_failing_the_run() {
+    let app = Router::new().fallback(any(flaky_caldav_handler));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let caldav_addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let feed = mock_ics_feed(&[
+        ("uid-good", "Good", "20270601T080000Z", "20270601T090000Z"),
+        ("uid-bad", "Bad", "20270602T080000Z", "20270602T090000Z"),
+    ]);
+    let caldav_url = format!("http://{}/dav/", caldav_addr);
+    let target = PushTarget {
+        caldav_url: &caldav_url,
+        calendar_name: "cal",
+        username: "user",
+        password: "pass",
+        sync_all: true,
+        keep_local: true,
+        conflict_policy: "overwrite",
+        origin: None,
+        namespace_uids: false,
+        bidirectional: false,
+        scheduling: "keep",
+        pace: Default::default(),
+        template: Default::default(),
+    };
+    let stats = push_feed(&feed, &target, &HashMap::new()).await.unwrap();
+    assert_eq!(stats.added, 1);
+    assert_eq!(stats.failed.len(), 1);
+    assert_eq!(stats.failed[0].uid, "uid-bad");
+    assert!(stats.failed[0].error.contains("500"));
+    assert!(stats.etags.contains_key("uid-good") && !stats.etags.contains_key("uid-bad"));
+}
+
+// Answers the first PUT with 429 and stores everything after it.
+async fn rate_limited_handler(
+    axum::extract::State(puts): axum::extract::State<
+        std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    >,
+    req: Request<Body>,
+) -> Response {
+    use std::sync::atomic::Ordering;
+    match req.method().as_str() {
+        "REPORT" => (StatusCode::MULTI_STATUS, mock_report_response(&[])).into_response(),
+        "PUT" if puts.fetch_add(1, Ordering::SeqCst) == 0 => {
+            (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, "0")]).into_response()
+        }
+        "PUT" => (StatusCode::CREATED, "").into_response(),
+        _ => (StatusCode::METHOD_NOT_ALLOWED, "").into_response(),
+    }
+}
+
+#[tokio::test]
+async fn reverse_sync_paces_uploads_and_retries_rate_limited_ones() {
+    use caldav_ics_sync::throttle::Pace;
+    use std::sync::atomic::Ordering;
+
+    let puts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let app = Router::new()
+        .fallback(any(rate_limited_handler))
+        .with_state(puts.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let caldav_addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let feed = mock_ics_feed(&[
+        ("uid-p1", "P1", "20270601T080000Z", "20270601T090000Z"),
+        ("uid-p2", "P2", "20270602T080000Z", "20270602T090000Z"),
+    ]);
+    let caldav_url = format!("http://{}/dav/", caldav_addr);
+    let target = PushTarget {
+        caldav_url: &caldav_url,
+        calendar_name: "cal",
+        username: "user",
+        password: "pass",
+        sync_all: true,
+        keep_local: true,
+        conflict_policy: "overwrite",
+        origin: None,
+        namespace_uids: false,
+        bidirectional: false,
+        scheduling: "keep",
+        pace: Pace {
+            requests_per_second: 50.0,
+            delay_ms: 0,
+        },
+        template: Default::default(),
+    };
+    let started = std::time::Instant::now();
+    let stats = push_feed(&feed, &target, &HashMap::new()).await.unwrap();
+    assert_eq!(stats.added, 2);
+    assert!(stats.failed.is_empty());
+    assert_eq!(puts.load(Ordering::SeqCst), 3);
+    // After the 429 the remaining requests go at most one a second.
+    assert!(started.elapsed() >= std::time::Duration::from_secs(2));
+}
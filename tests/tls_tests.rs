@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use axum::Router;
+use axum::routing::get;
+use caldav_ics_sync::server::tls::{self, CertStore, TlsListener};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_rustls::TlsConnector;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/tls")
+        .join(name)
+}
+
+// Copies a fixture pair into a temp dir so the test can swap it out.
+fn install(dir: &Path, name: &str, mtime: SystemTime) {
+    for ext in ["pem", "key"] {
+        let target = dir.join(format!("server.{}", ext));
+        std::fs::copy(fixture(&format!("{}.{}", name, ext)), &target).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&target)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+    }
+}
+
+fn connector() -> TlsConnector {
+    let mut roots = rustls::RootCertStore::empty();
+    roots
+        .add(CertificateDer::from_pem_file(fixture("ca.pem")).unwrap())
+        .unwrap();
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::aws_lc_rs::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+}
+
+// Returns the served certificate and the HTTP response.
+async fn fetch(addr: std::net::SocketAddr) -> (CertificateDer<'static>, String) {
+    let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let mut stream = connector()
+        .connect(ServerName::try_from("localhost").unwrap(), tcp)
+        .await
+        .unwrap();
+    let cert = stream.get_ref().1.peer_certificates().unwrap()[0].clone();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    (cert, response)
+}
+
+#[tokio::test]
+async fn serves_https_and_picks_up_renewed_certificates() {
+    let dir = std::env::temp_dir().join(format!("caldav-ics-sync-tls-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let start = SystemTime::now() - Duration::from_secs(3600);
+    install(&dir, "a", start);
+
+    let store = Arc::new(CertStore::load(dir.join("server.pem"), dir.join("server.key")).unwrap());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let listener = TlsListener::new(listener, tls::server_config(store.clone()).unwrap()).unwrap();
+    let app = Router::new().route("/", get(|| async { "hello over tls" }));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let (first, response) = fetch(addr).await;
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("hello over tls"));
+    assert!(!store.reload(false).unwrap());
+
+    install(&dir, "b", start + Duration::from_secs(60));
+    assert!(store.reload(false).unwrap());
+    let (second, _) = fetch(addr).await;
+    assert_ne!(first, second);
+    assert_eq!(
+        second,
+        CertificateDer::from_pem_file(fixture("b.pem")).unwrap()
+    );
+
+    // A key that doesn't match the certificate is rejected and the old pair stays.
+    std::fs::copy(fixture("a.key"), dir.join("server.key")).unwrap();
+    assert!(store.reload(true).is_err());
+    let (third, _) = fetch(addr).await;
+    assert_eq!(third, second);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}